@@ -48,6 +48,7 @@ use trust_dns_server::config::dnssec::{self, TlsCertConfig};
 use trust_dns_server::config::{Config, ZoneConfig};
 use trust_dns_server::logger;
 use trust_dns_server::server::ServerFuture;
+use trust_dns_server::store::chaos::ChaosConfig;
 use trust_dns_server::store::file::{FileAuthority, FileConfig};
 #[cfg(feature = "resolver")]
 use trust_dns_server::store::forwarder::ForwardAuthority;
@@ -68,6 +69,11 @@ fn load_zone(
     let zone_path: Option<String> = zone_config.file.clone();
     let zone_type: ZoneType = zone_config.get_zone_type();
     let is_axfr_allowed = zone_config.is_axfr_allowed();
+    let notify_targets = zone_config.get_notify().to_vec();
+    let primaries = zone_config.get_primaries().to_vec();
+    let rotate_rrsets = zone_config.is_rrset_rotation_enabled();
+    #[allow(unused_variables)]
+    let serial_scheme = zone_config.get_serial_scheme();
     #[allow(unused_variables)]
     let is_dnssec_enabled = zone_config.is_dnssec_enabled();
 
@@ -91,7 +97,12 @@ fn load_zone(
                 Some(zone_dir),
                 config,
             )
-            .map(|a| Box::new(Arc::new(RwLock::new(a))))?
+            .map(|mut a| {
+                a.set_notify_targets(notify_targets.clone());
+                a.set_rotate_rrsets(rotate_rrsets);
+                a.set_serial_scheme(serial_scheme);
+                Box::new(Arc::new(RwLock::new(a)))
+            })?
         }
         Some(StoreConfig::File(ref config)) => {
             if zone_path.is_some() {
@@ -104,7 +115,12 @@ fn load_zone(
                 Some(zone_dir),
                 config,
             )
-            .map(|a| Box::new(Arc::new(RwLock::new(a))))?
+            .map(|mut a| {
+                a.set_notify_targets(notify_targets.clone());
+                a.set_primaries(primaries.clone());
+                a.set_rotate_rrsets(rotate_rrsets);
+                Box::new(Arc::new(RwLock::new(a)))
+            })?
         }
         #[cfg(feature = "resolver")]
         Some(StoreConfig::Forward(ref config)) => {
@@ -139,7 +155,12 @@ fn load_zone(
                 Some(zone_dir),
                 &config,
             )
-            .map(|a| Box::new(Arc::new(RwLock::new(a))))?
+            .map(|mut a| {
+                a.set_notify_targets(notify_targets.clone());
+                a.set_rotate_rrsets(rotate_rrsets);
+                a.set_serial_scheme(serial_scheme);
+                Box::new(Arc::new(RwLock::new(a)))
+            })?
         }
         None => {
             let config = FileConfig {
@@ -152,7 +173,12 @@ fn load_zone(
                 Some(zone_dir),
                 &config,
             )
-            .map(|a| Box::new(Arc::new(RwLock::new(a))))?
+            .map(|mut a| {
+                a.set_notify_targets(notify_targets.clone());
+                a.set_primaries(primaries.clone());
+                a.set_rotate_rrsets(rotate_rrsets);
+                Box::new(Arc::new(RwLock::new(a)))
+            })?
         }
     };
 
@@ -359,12 +385,31 @@ fn main() {
             .get_zone()
             .unwrap_or_else(|_| panic!("bad zone name in {:?}", config_path));
 
+        if let Some(acl) = zone.get_acl() {
+            info!("restricting access to zone {}: {:?}", zone_name, acl);
+            catalog.set_acl(zone_name.clone().into(), acl.clone());
+        }
+
         match load_zone(&zone_dir, zone, &mut runtime) {
             Ok(authority) => catalog.upsert(zone_name.into(), authority),
             Err(error) => panic!("could not load zone {}: {}", zone_name, error),
         }
     }
 
+    if let Some(chaos) = config.get_chaos() {
+        let authority = chaos.authority();
+        info!("serving CHAOS class zone: {}", ChaosConfig::origin());
+        catalog.upsert(
+            ChaosConfig::origin().into(),
+            Box::new(Arc::new(RwLock::new(authority))),
+        );
+    }
+
+    if let Some(rate_limit) = config.get_rate_limit() {
+        info!("limiting the rate of UDP responses: {:?}", rate_limit);
+        catalog.set_rate_limit(*rate_limit);
+    }
+
     // TODO: support all the IPs asked to listen on...
     // TODO:, there should be the option to listen on any port, IP and protocol option...
     let v4addr = config.get_listen_addrs_ipv4();
@@ -0,0 +1,305 @@
+// Copyright 2015-2020 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Zone transfers, AXFR, streamed record by record so that large zones are not held in memory
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_util::stream::{Stream, StreamExt};
+use futures_util::{ready, FutureExt};
+use log::debug;
+use tokio::net::TcpStream;
+
+use crate::error::*;
+use crate::op::{Message, MessageType, OpCode, Query, ResponseCode};
+use crate::proto::error::ProtoError;
+use crate::proto::iocompat::AsyncIoTokioAsStd;
+use crate::proto::tcp::{TcpClientConnect, TcpClientStream};
+use crate::proto::xfer::{DnsStreamHandle, SerialMessage};
+use crate::rr::{Name, Record, RecordType};
+
+/// The stream of a zone transfer over TCP, see `axfr_stream`
+pub type TcpAxfrStream = AxfrStream<
+    TcpClientConnect<AsyncIoTokioAsStd<TcpStream>>,
+    TcpClientStream<AsyncIoTokioAsStd<TcpStream>>,
+>;
+
+/// Transfers the zone from the name server over TCP, yielding the records as they arrive
+///
+/// # Arguments
+///
+/// * `zone` - the origin of the zone to transfer
+/// * `name_server` - the IP and Port of the name server to transfer the zone from
+pub fn axfr_stream(zone: Name, name_server: SocketAddr) -> TcpAxfrStream {
+    let (connect, sender) = TcpClientStream::<AsyncIoTokioAsStd<TcpStream>>::new(name_server);
+    AxfrStream::new(zone, name_server, connect, sender)
+}
+
+enum AxfrState<F, S> {
+    Connecting(F),
+    Transferring(S),
+    Done,
+}
+
+/// The records of a zone transfer, AXFR, yielded as each message of the transfer arrives
+///
+/// The transfer starts and ends with the SOA record of the zone, and the stream ends after the
+///  closing SOA. A refused transfer, or one which is aborted before the closing SOA, yields an
+///  error after which the stream ends.
+#[must_use = "streams do nothing unless polled"]
+pub struct AxfrStream<F, S> {
+    zone: Name,
+    id: u16,
+    state: AxfrState<F, S>,
+    records: VecDeque<Record>,
+    soas: usize,
+}
+
+impl<F, S> AxfrStream<F, S>
+where
+    F: Future<Output = Result<S, ProtoError>> + Unpin,
+    S: Stream<Item = Result<SerialMessage, ProtoError>> + Unpin,
+{
+    /// Sends the AXFR request for the zone, the transfer is read from the stream once connected
+    ///
+    /// # Arguments
+    ///
+    /// * `zone` - the origin of the zone to transfer
+    /// * `name_server` - the IP and Port of the name server to transfer the zone from
+    /// * `connect` - the connection to the name server, e.g. from `TcpClientStream::new`
+    /// * `sender` - the handle for sending to the connection
+    pub fn new(
+        zone: Name,
+        name_server: SocketAddr,
+        connect: F,
+        mut sender: Box<dyn DnsStreamHandle + Send>,
+    ) -> Self {
+        let id: u16 = rand::random();
+
+        let mut message = Message::new();
+        message
+            .set_id(id)
+            .set_message_type(MessageType::Query)
+            .set_op_code(OpCode::Query)
+            .add_query(Query::query(zone.clone(), RecordType::AXFR));
+
+        let state = match message
+            .to_vec()
+            .and_then(|bytes| sender.send(SerialMessage::new(bytes, name_server)))
+        {
+            Ok(()) => AxfrState::Connecting(connect),
+            Err(e) => {
+                debug!("failed to send AXFR of {}: {}", zone, e);
+                AxfrState::Done
+            }
+        };
+
+        AxfrStream {
+            zone,
+            id,
+            state,
+            records: VecDeque::new(),
+            soas: 0,
+        }
+    }
+
+    /// Queues the records of the message, the transfer is done at the closing SOA
+    fn receive(&mut self, message: SerialMessage) -> ClientResult<()> {
+        let mut message = message.to_message()?;
+        if message.id() != self.id {
+            debug!("ignoring unexpected message: {}", message.id());
+            return Ok(());
+        }
+
+        if message.response_code() != ResponseCode::NoError {
+            return Err(format!(
+                "AXFR of {} failed: {}",
+                self.zone,
+                message.response_code()
+            )
+            .into());
+        }
+
+        for record in message.take_answers() {
+            let is_soa = record.rr_type() == RecordType::SOA;
+            if self.soas == 0 && !is_soa {
+                return Err(format!("AXFR of {} did not start with the SOA", self.zone).into());
+            }
+
+            self.records.push_back(record);
+            if is_soa {
+                self.soas += 1;
+                if self.soas == 2 {
+                    self.state = AxfrState::Done;
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<F, S> Stream for AxfrStream<F, S>
+where
+    F: Future<Output = Result<S, ProtoError>> + Unpin,
+    S: Stream<Item = Result<SerialMessage, ProtoError>> + Unpin,
+{
+    type Item = ClientResult<Record>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(record) = self.records.pop_front() {
+                return Poll::Ready(Some(Ok(record)));
+            }
+
+            let next = match self.state {
+                AxfrState::Connecting(ref mut connect) => match ready!(connect.poll_unpin(cx)) {
+                    Ok(stream) => {
+                        self.state = AxfrState::Transferring(stream);
+                        continue;
+                    }
+                    Err(e) => Err(ClientError::from(e)),
+                },
+                AxfrState::Transferring(ref mut stream) => {
+                    match ready!(stream.poll_next_unpin(cx)) {
+                        Some(Ok(message)) => Ok(message),
+                        Some(Err(e)) => Err(ClientError::from(e)),
+                        None => Err(ClientError::from(format!(
+                            "AXFR of {} aborted before the closing SOA",
+                            self.zone
+                        ))),
+                    }
+                }
+                AxfrState::Done => return Poll::Ready(None),
+            };
+
+            if let Err(e) = next.and_then(|message| self.receive(message)) {
+                self.state = AxfrState::Done;
+                return Poll::Ready(Some(Err(e)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use futures::executor::block_on;
+    use futures::future;
+    use futures_channel::mpsc;
+
+    use super::*;
+    use crate::rr::rdata::SOA;
+    use crate::rr::RData;
+
+    type Responses = mpsc::UnboundedReceiver<Result<SerialMessage, ProtoError>>;
+    type Script = fn() -> Vec<Result<Vec<Record>, ProtoError>>;
+
+    /// Answers the AXFR request with the messages of the `script`, in order
+    struct ScriptedHandle {
+        script: Script,
+        responses: mpsc::UnboundedSender<Result<SerialMessage, ProtoError>>,
+    }
+
+    impl DnsStreamHandle for ScriptedHandle {
+        fn send(&mut self, buffer: SerialMessage) -> Result<(), ProtoError> {
+            let request = buffer.to_message()?;
+            assert_eq!(request.queries()[0].query_type(), RecordType::AXFR);
+
+            for response in (self.script)() {
+                let response = response.and_then(|records| {
+                    let mut message = Message::new();
+                    message
+                        .set_id(request.id())
+                        .set_message_type(MessageType::Response);
+                    message.insert_answers(records);
+                    Ok(SerialMessage::new(message.to_vec()?, buffer.addr()))
+                });
+                self.responses.unbounded_send(response).unwrap();
+            }
+
+            Ok(())
+        }
+    }
+
+    fn origin() -> Name {
+        Name::from_str("example.com.").unwrap()
+    }
+
+    fn soa() -> Record {
+        let soa = SOA::new(origin(), origin(), 1, 3600, 600, 86400, 300);
+        Record::from_rdata(origin(), 3600, RData::SOA(soa))
+    }
+
+    fn a(label: &str) -> Record {
+        let name = Name::from_str(label).unwrap().append_domain(&origin());
+        Record::from_rdata(name, 3600, RData::A([127, 0, 0, 1].into()))
+    }
+
+    fn transfer(script: Script) -> Vec<ClientResult<Record>> {
+        let (responses, stream) = mpsc::unbounded();
+        let handle = ScriptedHandle { script, responses };
+
+        let name_server = SocketAddr::from(([127, 0, 0, 1], 53));
+        let connect = future::ok::<Responses, ProtoError>(stream);
+        let axfr = AxfrStream::new(origin(), name_server, connect, Box::new(handle));
+        block_on(axfr.collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_axfr_stream() {
+        let records = transfer(|| {
+            vec![
+                Ok(vec![soa(), a("www"), a("ftp")]),
+                Ok(vec![a("mail")]),
+                Ok(vec![a("ns"), soa()]),
+                // after the closing SOA nothing more is read
+                Ok(vec![a("extra")]),
+            ]
+        });
+
+        let records = records
+            .into_iter()
+            .collect::<ClientResult<Vec<_>>>()
+            .expect("transfer failed");
+        assert_eq!(
+            records,
+            vec![soa(), a("www"), a("ftp"), a("mail"), a("ns"), soa()]
+        );
+    }
+
+    #[test]
+    fn test_axfr_stream_aborted() {
+        let records = transfer(|| {
+            vec![
+                Ok(vec![soa(), a("www")]),
+                Err(ProtoError::from("connection reset")),
+                Ok(vec![a("ns"), soa()]),
+            ]
+        });
+
+        // the records received before the failure are yielded, followed by the error
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].as_ref().unwrap(), &soa());
+        assert_eq!(records[1].as_ref().unwrap(), &a("www"));
+        assert!(records[2].is_err());
+    }
+
+    #[test]
+    fn test_axfr_stream_closed() {
+        let records = transfer(|| vec![Ok(vec![soa(), a("www")])]);
+
+        assert_eq!(records.len(), 3);
+        assert!(records[2].is_err());
+    }
+}
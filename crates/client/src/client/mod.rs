@@ -17,6 +17,7 @@
 //! DNS Client associated classes for performing queries and other operations.
 
 pub(crate) mod async_client;
+mod axfr_stream;
 #[cfg(feature = "dnssec")]
 pub(crate) mod async_secure_client;
 #[allow(clippy::module_inception)]
@@ -29,6 +30,7 @@ mod rc_future;
 pub use self::async_client::{
     AsyncClient, AsyncClientConnect, ClientFuture, ClientHandle, ClientResponse,
 };
+pub use self::axfr_stream::{axfr_stream, AxfrStream, TcpAxfrStream};
 #[cfg(feature = "dnssec")]
 pub use self::async_secure_client::{
     AsyncDnssecClient, AsyncSecureClientBuilder, AsyncSecureClientConnect,
@@ -36,6 +36,15 @@ pub enum ErrorKind {
     #[error("token is missing: {0}")]
     MissingToken(String),
 
+    /// An error found on a specific line of the input
+    #[error("line {line}: {error}")]
+    Line {
+        /// The line, starting at 1, on which the error was found
+        line: usize,
+        /// The error found on the line
+        error: Box<Error>,
+    },
+
     /// An error with an arbitrary message, stored as String
     #[error("{0}")]
     Msg(String),
@@ -83,6 +92,10 @@ impl Clone for ErrorKind {
         use self::ErrorKind::*;
         match self {
             CharToInt(c) => CharToInt(*c),
+            Line { line, error } => Line {
+                line: *line,
+                error: Box::new(error.kind.clone().into()),
+            },
             Message(msg) => Message(msg),
             MissingToken(ref s) => MissingToken(s.clone()),
             Msg(ref msg) => Msg(msg.clone()),
@@ -32,6 +32,10 @@ pub use self::dnssec::PublicKeyBuf;
 pub use self::dnssec::PublicKeyEnum;
 pub use self::dnssec::SupportedAlgorithms;
 pub use self::dnssec::TrustAnchor;
+pub use self::dnssec::TrustAnchorStore;
+pub use self::dnssec::TsigAlgorithm;
+pub use self::dnssec::TsigKey;
+pub use self::dnssec::TsigResponder;
 pub use self::dnssec::Verifier;
 pub use self::dnssec::TBS;
 #[cfg(any(feature = "openssl", feature = "ring"))]
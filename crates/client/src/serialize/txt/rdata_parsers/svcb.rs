@@ -0,0 +1,201 @@
+// Copyright 2015-2020 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! SVCB and HTTPS records for service binding
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use crate::error::*;
+use crate::rr::domain::Name;
+use crate::rr::rdata::svcb::{SvcParamKey, SvcParamValue, SVCB};
+
+/// Parse the RData from a set of Tokens
+///
+/// ```text
+/// Name TTL IN SVCB SvcPriority TargetName SvcParams
+///
+/// example.com. 7200 IN HTTPS 1 . alpn=h2,h3 port=8443 ipv4hint=192.0.2.1
+/// ```
+pub fn parse<'i, I: Iterator<Item = &'i str>>(
+    mut tokens: I,
+    origin: Option<&Name>,
+) -> ParseResult<SVCB> {
+    let svc_priority: u16 = tokens
+        .next()
+        .ok_or_else(|| ParseError::from(ParseErrorKind::MissingToken("SvcPriority".to_string())))
+        .and_then(|s| u16::from_str(s).map_err(Into::into))?;
+
+    let target_name: Name = tokens
+        .next()
+        .ok_or_else(|| ParseError::from(ParseErrorKind::MissingToken("TargetName".to_string())))
+        .and_then(|s| Name::parse(s, origin).map_err(ParseError::from))?;
+
+    let svc_params = tokens.map(parse_svc_param).collect::<ParseResult<Vec<_>>>()?;
+
+    Ok(SVCB::new(svc_priority, target_name, svc_params))
+}
+
+/// Parses a `key=value` pair, or a `key` without a value
+fn parse_svc_param(token: &str) -> ParseResult<(SvcParamKey, SvcParamValue)> {
+    let (key, value) = match token.find('=') {
+        Some(idx) => (&token[..idx], Some(unquote(&token[idx + 1..]))),
+        None => (token, None),
+    };
+
+    let key = SvcParamKey::from_str(key)?;
+    let value = match (key, value) {
+        (SvcParamKey::NoDefaultAlpn, None) => SvcParamValue::NoDefaultAlpn,
+        (SvcParamKey::NoDefaultAlpn, Some(_)) => {
+            return Err(ParseErrorKind::Message("no-default-alpn has no value").into())
+        }
+        (key, None) => {
+            let msg = format!("missing value of SvcParamKey: {}", key);
+            return Err(ParseErrorKind::Msg(msg).into());
+        }
+        (SvcParamKey::Mandatory, Some(value)) => SvcParamValue::Mandatory(
+            value
+                .split(',')
+                .map(SvcParamKey::from_str)
+                .collect::<Result<_, _>>()?,
+        ),
+        (SvcParamKey::Alpn, Some(value)) => {
+            SvcParamValue::Alpn(value.split(',').map(ToString::to_string).collect())
+        }
+        (SvcParamKey::Port, Some(value)) => SvcParamValue::Port(u16::from_str(value)?),
+        (SvcParamKey::Ipv4Hint, Some(value)) => SvcParamValue::Ipv4Hint(
+            value
+                .split(',')
+                .map(Ipv4Addr::from_str)
+                .collect::<Result<_, _>>()?,
+        ),
+        (SvcParamKey::Ech, Some(value)) => {
+            SvcParamValue::Ech(data_encoding::BASE64.decode(value.as_bytes())?)
+        }
+        (SvcParamKey::Ipv6Hint, Some(value)) => SvcParamValue::Ipv6Hint(
+            value
+                .split(',')
+                .map(Ipv6Addr::from_str)
+                .collect::<Result<_, _>>()?,
+        ),
+        (SvcParamKey::Key(_), Some(value)) => SvcParamValue::Unknown(unescape(value)?),
+    };
+
+    Ok((key, value))
+}
+
+fn unquote(value: &str) -> &str {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        &value[1..value.len() - 1]
+    } else {
+        value
+    }
+}
+
+/// The octets of the value, with `\DDD` and `\X` escapes as for character strings
+fn unescape(value: &str) -> ParseResult<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut iter = value.bytes();
+    while let Some(b) = iter.next() {
+        if b != b'\\' {
+            bytes.push(b);
+            continue;
+        }
+
+        match iter.next() {
+            Some(d) if d.is_ascii_digit() => {
+                let digits = [Some(d), iter.next(), iter.next()];
+                let mut octet = 0_u16;
+                for digit in digits.iter() {
+                    match digit {
+                        Some(digit) if digit.is_ascii_digit() => {
+                            octet = octet * 10 + u16::from(digit - b'0')
+                        }
+                        _ => return Err(ParseErrorKind::Message("invalid \\DDD escape").into()),
+                    }
+                }
+                if octet > 255 {
+                    return Err(ParseErrorKind::Message("invalid \\DDD escape").into());
+                }
+                bytes.push(octet as u8);
+            }
+            Some(escaped) => bytes.push(escaped),
+            None => return Err(ParseErrorKind::Message("unterminated escape").into()),
+        }
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parsing() {
+        let svcb = parse(
+            vec![
+                "1",
+                ".",
+                "alpn=h2,h3",
+                "no-default-alpn",
+                "port=\"8443\"",
+                "ipv4hint=192.0.2.1,192.0.2.2",
+                "ech=ZWNoY29uZmln",
+                "ipv6hint=2001:db8::1",
+                "key667=\"hello\\032world\"",
+                "mandatory=alpn,port",
+            ]
+            .into_iter(),
+            None,
+        )
+        .expect("failed to parse SVCB");
+
+        assert_eq!(svcb.svc_priority(), 1);
+        assert_eq!(svcb.target_name(), &Name::root());
+        assert_eq!(svcb.alpn(), Some(&["h2".to_string(), "h3".to_string()][..]));
+        assert_eq!(svcb.port(), Some(8443));
+        assert_eq!(
+            svcb.ipv4hint(),
+            Some(&[Ipv4Addr::new(192, 0, 2, 1), Ipv4Addr::new(192, 0, 2, 2)][..])
+        );
+        assert_eq!(svcb.ech(), Some(&b"echconfig"[..]));
+        assert_eq!(
+            svcb.ipv6hint(),
+            Some(&[Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)][..])
+        );
+        assert_eq!(
+            svcb.svc_param(SvcParamKey::Key(667)),
+            Some(&SvcParamValue::Unknown(b"hello world".to_vec()))
+        );
+
+        // the params are in the order of the keys
+        let keys: Vec<u16> = svcb.svc_params().iter().map(|(k, _)| u16::from(*k)).collect();
+        assert_eq!(keys, vec![0, 1, 2, 3, 4, 5, 6, 667]);
+    }
+
+    #[test]
+    fn test_parsing_alias() {
+        let origin = Name::from_str("example.com.").unwrap();
+        let svcb = parse(vec!["0", "pool.svc"].into_iter(), Some(&origin)).unwrap();
+
+        assert!(svcb.is_alias());
+        assert_eq!(
+            svcb.target_name(),
+            &Name::from_str("pool.svc.example.com.").unwrap()
+        );
+        assert!(svcb.svc_params().is_empty());
+    }
+
+    #[test]
+    fn test_parsing_invalid() {
+        assert!(parse(vec!["1", ".", "port"].into_iter(), None).is_err());
+        assert!(parse(vec!["1", ".", "no-default-alpn=h2"].into_iter(), None).is_err());
+        assert!(parse(vec!["1", ".", "unknown=1"].into_iter(), None).is_err());
+        assert!(parse(vec!["1", ".", "ipv4hint=2001:db8::1"].into_iter(), None).is_err());
+    }
+}
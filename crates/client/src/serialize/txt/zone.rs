@@ -122,12 +122,19 @@ use crate::serialize::txt::zone_lex::{Lexer, Token};
 ///                 the line is ignored.
 /// ```
 #[derive(Default)]
-pub struct Parser;
+pub struct Parser {
+    default_ttl: Option<u32>,
+}
 
 impl Parser {
     /// Returns a new Zone file parser
     pub fn new() -> Self {
-        Parser
+        Parser { default_ttl: None }
+    }
+
+    /// The `$TTL` of the last parsed zone, if it was specified
+    pub fn default_ttl(&self) -> Option<u32> {
+        self.default_ttl
     }
 
     /// Parse a file from the Lexer
@@ -135,6 +142,11 @@ impl Parser {
     /// # Return
     ///
     /// A pair of the Zone origin name and a map of all Keys to RecordSets
+    ///
+    /// # Errors
+    ///
+    /// Errors are reported as `ParseErrorKind::Line`, with the line on which the failing record
+    ///  started.
     pub fn parse(
         &mut self,
         lexer: Lexer,
@@ -142,6 +154,25 @@ impl Parser {
         class: Option<DNSClass>,
     ) -> ParseResult<(Name, BTreeMap<RrKey, RecordSet>)> {
         let mut lexer = lexer;
+        let mut line = lexer.line();
+        self.default_ttl = None;
+
+        self.parse_lines(&mut lexer, &mut line, origin, class).map_err(|error| {
+            ParseErrorKind::Line {
+                line,
+                error: Box::new(error),
+            }
+            .into()
+        })
+    }
+
+    fn parse_lines(
+        &mut self,
+        lexer: &mut Lexer,
+        line: &mut usize,
+        origin: Option<Name>,
+        class: Option<DNSClass>,
+    ) -> ParseResult<(Name, BTreeMap<RrKey, RecordSet>)> {
         let mut records: BTreeMap<RrKey, RecordSet> = BTreeMap::new();
 
         let mut origin: Option<Name> = origin;
@@ -149,14 +180,30 @@ impl Parser {
         let mut current_name: Option<Name> = None;
         let mut rtype: Option<RecordType> = None;
         let mut ttl: Option<u32> = None;
+        let mut default_ttl: Option<u32> = None;
         let mut state = State::StartLine;
 
-        while let Some(t) = lexer.next_token()? {
+        loop {
+            let t = match lexer.next_token() {
+                Ok(Some(t)) => t,
+                Ok(None) => break,
+                Err(e) => {
+                    *line = lexer.line();
+                    return Err(e.into());
+                }
+            };
+
             state = match state {
                 State::StartLine => {
+                    *line = lexer.line();
                     // current_name is not reset on the next line b/c it might be needed from the previous
                     rtype = None;
 
+                    // an explicit TTL only applies to its own record once $TTL has been specified
+                    if default_ttl.is_some() {
+                        ttl = default_ttl;
+                    }
+
                     match t {
                         // if Dollar, then $INCLUDE or $ORIGIN
                         Token::Include => {
@@ -186,6 +233,7 @@ impl Parser {
                 State::Ttl => match t {
                     Token::CharData(data) => {
                         ttl = Some(Self::parse_time(&data)?);
+                        default_ttl = ttl;
                         State::StartLine
                     }
                     _ => return Err(ParseErrorKind::UnexpectedToken(t).into()),
@@ -286,6 +334,7 @@ impl Parser {
         let origin = origin.ok_or_else(|| {
             ParseError::from(ParseErrorKind::Message("$ORIGIN was not specified"))
         })?;
+        self.default_ttl = default_ttl;
         Ok((origin, records))
     }
 
@@ -325,14 +374,10 @@ impl Parser {
         //  then check the Type again and have custom add logic.
         match rtype.unwrap() {
             RecordType::SOA => {
-                // TTL for the SOA is set internally...
-                // expire is for the SOA, minimum is default for records
+                // without an explicit TTL or $TTL, the minimum is the default for all records,
+                //  including the SOA itself
                 if let RData::SOA(ref soa) = rdata {
-                    // TODO, this looks wrong, get_expire() should be get_minimum(), right?
-                    record.set_ttl(soa.expire() as u32); // the spec seems a little inaccurate with u32 and i32
-                    if ttl.is_none() {
-                        *ttl = Some(soa.minimum());
-                    } // TODO: should this only set it if it's not set?
+                    record.set_ttl(*ttl.get_or_insert(soa.minimum()));
                 } else {
                     panic!("Invalid RData here, expected SOA: {:?}", rdata);
                 }
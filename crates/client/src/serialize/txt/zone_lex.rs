@@ -15,6 +15,8 @@ use crate::error::*;
 pub struct Lexer<'a> {
     txt: Peekable<Chars<'a>>,
     state: State,
+    line: usize,
+    token_line: usize,
 }
 
 impl<'a> Lexer<'a> {
@@ -23,13 +25,21 @@ impl<'a> Lexer<'a> {
         Lexer {
             txt: txt.chars().peekable(),
             state: State::StartLine,
+            line: 1,
+            token_line: 1,
         }
     }
 
+    /// The line, starting at 1, on which the most recently returned Token, or error, began
+    pub fn line(&self) -> usize {
+        self.token_line
+    }
+
     /// Return the next Token in the string
     pub fn next_token(&mut self) -> LexerResult<Option<Token>> {
         let mut char_data_vec: Option<Vec<String>> = None;
         let mut char_data: Option<String> = None;
+        self.token_line = self.line;
 
         for i in 0..4096 {
             // max chars in a single lex, helps with issues in the lexer...
@@ -62,7 +72,7 @@ impl<'a> Lexer<'a> {
                     match ch {
                         Some('@') => self.state = State::At,
                         Some('(') => {
-                            self.txt.next();
+                            self.next_char();
                             char_data_vec = Some(Vec::new());
                             self.state = State::List;
                         }
@@ -70,7 +80,7 @@ impl<'a> Lexer<'a> {
                             return Err(LexerErrorKind::IllegalCharacter(ch.unwrap_or(')')).into())
                         }
                         Some('$') => {
-                            self.txt.next();
+                            self.next_char();
                             char_data = Some(String::new());
                             self.state = State::Dollar;
                         }
@@ -78,13 +88,13 @@ impl<'a> Lexer<'a> {
                             self.state = State::EOL;
                         }
                         Some('"') => {
-                            self.txt.next();
+                            self.next_char();
                             char_data = Some(String::new());
                             self.state = State::Quote;
                         }
                         Some(';') => self.state = State::Comment { is_list: false },
                         Some(ch) if ch.is_whitespace() => {
-                            self.txt.next();
+                            self.next_char();
                         } // gobble other whitespace
                         Some(ch) if !ch.is_control() && !ch.is_whitespace() => {
                             char_data = Some(String::new());
@@ -98,7 +108,7 @@ impl<'a> Lexer<'a> {
                 }
                 State::Blank => {
                     // consume the whitespace
-                    self.txt.next();
+                    self.next_char();
                     self.state = State::RestOfLine;
                     return Ok(Some(Token::Blank));
                 }
@@ -112,7 +122,7 @@ impl<'a> Lexer<'a> {
                             }
                         } // out of the comment
                         Some(_) => {
-                            self.txt.next();
+                            self.next_char();
                         } // advance the token by default and maintain state
                         None => {
                             self.state = State::EOF;
@@ -124,7 +134,7 @@ impl<'a> Lexer<'a> {
                         // end and gobble the '"'
                         Some('"') => {
                             self.state = State::RestOfLine;
-                            self.txt.next();
+                            self.next_char();
                             return Ok(Some(Token::CharData(
                                 char_data.take().unwrap_or_else(|| "".into()),
                             )));
@@ -133,7 +143,7 @@ impl<'a> Lexer<'a> {
                             Self::push_to_str(&mut char_data, self.escape_seq()?)?;
                         }
                         Some(ch) => {
-                            self.txt.next();
+                            self.next_char();
                             Self::push_to_str(&mut char_data, ch)?;
                         }
                         None => return Err(LexerErrorKind::UnclosedQuotedString.into()),
@@ -143,7 +153,7 @@ impl<'a> Lexer<'a> {
                     match ch {
                         // even this is a little broad for what's actually possible in a dollar...
                         Some('A'..='Z') => {
-                            self.txt.next();
+                            self.next_char();
                             Self::push_to_str(&mut char_data, ch.unwrap())?;
                         }
                         // finishes the Dollar...
@@ -173,11 +183,11 @@ impl<'a> Lexer<'a> {
                 }
                 State::List => match ch {
                     Some(';') => {
-                        self.txt.next();
+                        self.next_char();
                         self.state = State::Comment { is_list: true }
                     }
                     Some(')') => {
-                        self.txt.next();
+                        self.next_char();
                         self.state = State::RestOfLine;
                         return char_data_vec
                             .take()
@@ -187,7 +197,7 @@ impl<'a> Lexer<'a> {
                             .map(|v| Some(Token::List(v)));
                     }
                     Some(ch) if ch.is_whitespace() => {
-                        self.txt.next();
+                        self.next_char();
                     }
                     Some(ch) if !ch.is_control() && !ch.is_whitespace() => {
                         char_data = Some(String::new());
@@ -231,7 +241,7 @@ impl<'a> Lexer<'a> {
                         // TODO: this next one can be removed, but will keep unescaping for quoted strings
                         //Some('\\') => { try!(Self::push_to_str(&mut char_data, try!(self.escape_seq()))); },
                         Some(ch) if !ch.is_control() && !ch.is_whitespace() => {
-                            self.txt.next();
+                            self.next_char();
                             Self::push_to_str(&mut char_data, ch)?;
                         }
                         Some(ch) => return Err(LexerErrorKind::UnrecognizedChar(ch).into()),
@@ -247,16 +257,16 @@ impl<'a> Lexer<'a> {
                     }
                 }
                 State::At => {
-                    self.txt.next();
+                    self.next_char();
                     self.state = State::RestOfLine;
                     return Ok(Some(Token::At));
                 }
                 State::EOL => match ch {
                     Some('\r') => {
-                        self.txt.next();
+                        self.next_char();
                     }
                     Some('\n') => {
-                        self.txt.next();
+                        self.next_char();
                         self.state = State::StartLine;
                         return Ok(Some(Token::EOL));
                     }
@@ -265,7 +275,7 @@ impl<'a> Lexer<'a> {
                 },
                 // to exhaust all cases, this should never be run...
                 State::EOF => {
-                    self.txt.next(); // making sure we consume the last... it will always return None after.
+                    self.next_char(); // making sure we consume the last... it will always return None after.
                     return Ok(None);
                 }
             }
@@ -285,7 +295,7 @@ impl<'a> Lexer<'a> {
 
     fn escape_seq(&mut self) -> LexerResult<char> {
         // escaped character, let's decode it.
-        self.txt.next(); // consume the escape
+        self.next_char(); // consume the escape
         let ch = self
             .peek()
             .ok_or_else(|| LexerError::from(LexerErrorKind::EOF))?;
@@ -294,24 +304,21 @@ impl<'a> Lexer<'a> {
             if ch.is_numeric() {
                 // in this case it's an escaped octal: \DDD
                 let d1: u32 = self
-                    .txt
-                    .next()
+                    .next_char()
                     .ok_or_else(|| LexerError::from(LexerErrorKind::EOF))
                     .map(|c| {
                         c.to_digit(10)
                             .ok_or_else(|| LexerError::from(LexerErrorKind::IllegalCharacter(c)))
                     })??; // gobble
                 let d2: u32 = self
-                    .txt
-                    .next()
+                    .next_char()
                     .ok_or_else(|| LexerError::from(LexerErrorKind::EOF))
                     .map(|c| {
                         c.to_digit(10)
                             .ok_or_else(|| LexerError::from(LexerErrorKind::IllegalCharacter(c)))
                     })??; // gobble
                 let d3: u32 = self
-                    .txt
-                    .next()
+                    .next_char()
                     .ok_or_else(|| LexerError::from(LexerErrorKind::EOF))
                     .map(|c| {
                         c.to_digit(10)
@@ -325,7 +332,7 @@ impl<'a> Lexer<'a> {
                 Ok(ch)
             } else {
                 // this is an escaped char: \X
-                self.txt.next(); // gobble the char
+                self.next_char(); // gobble the char
                 Ok(ch)
             }
         } else {
@@ -336,6 +343,15 @@ impl<'a> Lexer<'a> {
     fn peek(&mut self) -> Option<char> {
         self.txt.peek().cloned()
     }
+
+    /// Consumes the next char, keeping track of the current line
+    fn next_char(&mut self) -> Option<char> {
+        let ch = self.txt.next();
+        if ch == Some('\n') {
+            self.line += 1;
+        }
+        ch
+    }
 }
 
 #[doc(hidden)]
@@ -766,4 +782,41 @@ $INCLUDE <SUBSYS>ISI-MAILBOXES.TXT",
         );
         assert!(next_token(&mut lexer).is_none());
     }
+
+    #[test]
+    fn line() {
+        let mut lexer = Lexer::new("a A 1.2.3.4\nb SOA (\n a\n b )\n\"c\nd\"");
+        assert_eq!(lexer.line(), 1);
+        assert_eq!(
+            next_token(&mut lexer).unwrap(),
+            Token::CharData("a".to_string())
+        );
+        assert_eq!(lexer.line(), 1);
+        next_token(&mut lexer); // A
+        next_token(&mut lexer); // 1.2.3.4
+        assert_eq!(next_token(&mut lexer).unwrap(), Token::EOL);
+        assert_eq!(lexer.line(), 1);
+
+        assert_eq!(
+            next_token(&mut lexer).unwrap(),
+            Token::CharData("b".to_string())
+        );
+        assert_eq!(lexer.line(), 2);
+        next_token(&mut lexer); // SOA
+        assert_eq!(
+            next_token(&mut lexer).unwrap(),
+            Token::List(vec!["a".to_string(), "b".to_string()])
+        );
+        assert_eq!(lexer.line(), 2);
+        assert_eq!(next_token(&mut lexer).unwrap(), Token::EOL);
+        assert_eq!(lexer.line(), 4);
+
+        assert_eq!(
+            next_token(&mut lexer).unwrap(),
+            Token::CharData("c\nd".to_string())
+        );
+        assert_eq!(lexer.line(), 5);
+        assert!(next_token(&mut lexer).is_none());
+        assert_eq!(lexer.line(), 6);
+    }
 }
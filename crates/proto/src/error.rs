@@ -124,6 +124,13 @@ pub enum ProtoErrorKind {
         ptr: u16,
     },
 
+    /// More compression pointers were followed for a name than it could have labels
+    #[error("too many pointers followed for name at idx: {idx}")]
+    TooManyPointers {
+        /// index of the last pointer followed
+        idx: usize,
+    },
+
     /// The maximum buffer size was exceeded
     #[error("maximum buffer size exceeded: {0}")]
     MaxBufferSizeExceeded(usize),
@@ -406,6 +413,7 @@ impl Clone for ProtoErrorKind {
             IncorrectRDataLengthRead { read, len } => IncorrectRDataLengthRead { read, len },
             LabelBytesTooLong(len) => LabelBytesTooLong(len),
             PointerNotPriorToLabel { idx, ptr } => PointerNotPriorToLabel { idx, ptr },
+            TooManyPointers { idx } => TooManyPointers { idx },
             MaxBufferSizeExceeded(max) => MaxBufferSizeExceeded(max),
             Message(msg) => Message(msg),
             Msg(ref msg) => Msg(msg.clone()),
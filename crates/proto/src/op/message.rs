@@ -25,6 +25,7 @@ use log::debug;
 
 use super::{Edns, Header, MessageType, OpCode, Query, ResponseCode};
 use crate::error::*;
+use crate::rr::rdata::opt::EdnsOption;
 use crate::rr::{Record, RecordType};
 use crate::serialize::binary::{BinDecodable, BinDecoder, BinEncodable, BinEncoder, EncodeMode};
 
@@ -100,7 +101,8 @@ pub fn update_header_counts(
     header.set_answer_count(counts.answer_count as u16);
     header.set_name_server_count(counts.nameserver_count as u16);
     header.set_additional_count(counts.additional_count as u16);
-    header.set_truncated(is_truncated);
+    // a response which was already truncated, e.g. by the server, remains truncated
+    header.set_truncated(is_truncated || current_header.truncated());
 
     header
 }
@@ -328,12 +330,15 @@ impl Message {
         self
     }
 
-    /// Add a SIG0 record, i.e. sign this message
+    /// Add a SIG0 or TSIG record, i.e. sign this message
     ///
     /// This must be don't only after all records have been associated. Generally this will be handled by the client and not need to be used directly
     #[cfg(feature = "dnssec")]
     pub fn add_sig0(&mut self, record: Record) -> &mut Self {
-        assert_eq!(RecordType::DNSSEC(DNSSECRecordType::SIG), record.rr_type());
+        assert!(
+            record.rr_type() == RecordType::DNSSEC(DNSSECRecordType::SIG)
+                || record.rr_type() == RecordType::DNSSEC(DNSSECRecordType::TSIG)
+        );
         self.sig0.push(record);
         self
     }
@@ -391,11 +396,12 @@ impl Message {
     /// # Return value
     ///
     /// The `ResponseCode`, if this is an EDNS message then this will join the section from the OPT
-    ///  record to create the EDNS `ResponseCode`
+    ///  record to create the EDNS `ResponseCode`, see `ResponseCode::from_extended`
     pub fn response_code(&self) -> ResponseCode {
-        ResponseCode::from(
+        ResponseCode::from_extended(
             self.edns.as_ref().map_or(0, Edns::rcode_high),
             self.header.response_code(),
+            !self.sig0.is_empty(),
         )
     }
 
@@ -520,6 +526,11 @@ impl Message {
         self.edns.as_mut().unwrap()
     }
 
+    /// Removes the EDNS section from the Message, returning it if there was one
+    pub fn take_edns(&mut self) -> Option<Edns> {
+        self.edns.take()
+    }
+
     /// # Return value
     ///
     /// the max payload value as it's defined in the EDNS section.
@@ -612,7 +623,8 @@ impl Message {
             } else {
                 match record.rr_type() {
                     #[cfg(feature = "dnssec")]
-                    RecordType::DNSSEC(DNSSECRecordType::SIG) => {
+                    RecordType::DNSSEC(DNSSECRecordType::SIG)
+                    | RecordType::DNSSEC(DNSSECRecordType::TSIG) => {
                         saw_sig0 = true;
                         sig0s.push(record);
                     }
@@ -658,11 +670,33 @@ impl Message {
         Ok(buffer)
     }
 
+    /// Pads the message with the EDNS Padding option, to a multiple of `block_size` octets
+    ///
+    /// [RFC 8467](https://tools.ietf.org/html/rfc8467#section-4.1), Padding Policies for EDNS(0),
+    ///  October 2018, recommends a block size of 128 for queries and 468 for responses.
+    ///
+    /// Only a message with EDNS is padded, any padding it already has is replaced. As padding
+    ///  only hides the size of encrypted messages, it should not be used over UDP or TCP.
+    pub fn pad_to_block_size(&mut self, block_size: u16) -> ProtoResult<()> {
+        match self.edns {
+            Some(ref mut edns) if block_size > 0 => edns.set_option(EdnsOption::Padding(0)),
+            _ => return Ok(()),
+        }
+
+        // the unpadded length includes the header of the padding option
+        let len = self.to_vec()?.len();
+        let block_size = usize::from(block_size);
+        let padding = (block_size - len % block_size) % block_size;
+
+        self.edns_mut().set_option(EdnsOption::Padding(padding as u16));
+        Ok(())
+    }
+
     /// Finalize the message prior to sending.
     ///
     /// Subsequent to calling this, the Message should not change.
     #[allow(clippy::match_single_binding)]
-    pub fn finalize<MF: MessageFinalizer>(
+    pub fn finalize<MF: MessageFinalizer + ?Sized>(
         &mut self,
         finalizer: &MF,
         inception_time: u32,
@@ -673,9 +707,10 @@ impl Message {
         // append all records to message
         for fin in finals {
             match fin.rr_type() {
-                // SIG0's are special, and come at the very end of the message
+                // SIG0's and TSIG's are special, and come at the very end of the message
                 #[cfg(feature = "dnssec")]
-                RecordType::DNSSEC(DNSSECRecordType::SIG) => self.add_sig0(fin),
+                RecordType::DNSSEC(DNSSECRecordType::SIG)
+                | RecordType::DNSSEC(DNSSECRecordType::TSIG) => self.add_sig0(fin),
                 _ => self.add_additional(fin),
             };
         }
@@ -772,12 +807,26 @@ where
     let include_sig0: bool = encoder.mode() != EncodeMode::Signing;
     let place = encoder.place::<Header>()?;
 
+    // room is kept for the OPT and SIG(0) records, a truncated message still needs them
+    let max_size = encoder.max_size();
+    let mut reserved = 0;
+    if let Some(edns) = edns {
+        reserved += Record::from(edns).to_bytes()?.len();
+    }
+    if include_sig0 {
+        for sig in sig0 {
+            reserved += sig.to_bytes()?.len();
+        }
+    }
+    encoder.set_max_size(max_size.saturating_sub(reserved as u16));
+
     let query_count = queries.emit(encoder)?;
     // TODO: need to do something on max records
     //  return offset of last emitted record.
     let answer_count = count_was_truncated(answers.emit(encoder))?;
     let nameserver_count = count_was_truncated(name_servers.emit(encoder))?;
     let mut additional_count = count_was_truncated(additionals.emit(encoder))?;
+    encoder.set_max_size(max_size);
 
     if let Some(edns) = edns {
         // need to commit the error code
@@ -914,6 +963,54 @@ fn test_emit_and_read_records() {
     test_emit_and_read(message);
 }
 
+#[test]
+fn test_pad_to_block_size() {
+    use crate::rr::rdata::opt::EdnsCode;
+
+    let mut message = Message::new();
+    message.add_query(Query::new());
+
+    // without EDNS there is nowhere to put the padding
+    let len = message.to_vec().unwrap().len();
+    message.pad_to_block_size(128).unwrap();
+    assert_eq!(message.to_vec().unwrap().len(), len);
+
+    message.set_edns(Edns::new());
+    message.pad_to_block_size(128).unwrap();
+    assert_eq!(message.to_vec().unwrap().len(), 128);
+
+    // the padding is replaced, not added to
+    message.pad_to_block_size(468).unwrap();
+    assert_eq!(message.to_vec().unwrap().len(), 468);
+
+    let bytes = message.to_vec().unwrap();
+    let padded = Message::from_vec(&bytes).unwrap();
+    assert_eq!(
+        padded.edns().unwrap().option(EdnsCode::Padding),
+        message.edns().unwrap().option(EdnsCode::Padding)
+    );
+}
+
+#[test]
+fn test_extended_response_code() {
+    let read = |response_code: ResponseCode| {
+        let mut message = Message::new();
+        message.set_response_code(response_code);
+        let mut edns = Edns::new();
+        edns.set_rcode_high(response_code.high());
+        message.set_edns(edns);
+
+        Message::from_vec(&message.to_vec().unwrap())
+            .unwrap()
+            .response_code()
+    };
+
+    assert_eq!(read(ResponseCode::NXDomain), ResponseCode::NXDomain);
+    assert_eq!(read(ResponseCode::BADVERS), ResponseCode::BADVERS);
+    assert_eq!(read(ResponseCode::BADCOOKIE), ResponseCode::BADCOOKIE);
+    assert_eq!(read(ResponseCode::BADTIME), ResponseCode::BADTIME);
+}
+
 #[cfg(test)]
 fn test_emit_and_read(message: Message) {
     let mut byte_vec: Vec<u8> = Vec::with_capacity(512);
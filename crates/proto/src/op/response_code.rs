@@ -146,6 +146,24 @@ impl ResponseCode {
         ((u16::from(high) << 4) | ((u16::from(low)) & 0x000F)).into()
     }
 
+    /// Combines the EDNS high and low from the Header to produce the Extended ResponseCode of a
+    ///  message, which is `signed` if it carries a TSIG or SIG(0) record
+    ///
+    /// BADVERS and BADSIG share the code 16, BADSIG is only returned in signed messages, see
+    ///  [RFC 6895](https://tools.ietf.org/html/rfc6895#section-2.3), otherwise it is BADVERS.
+    pub fn from_extended(high: u8, low: u8, signed: bool) -> ResponseCode {
+        match ResponseCode::from(high, low) {
+            ResponseCode::BADSIG if !signed => ResponseCode::BADVERS,
+            response_code => response_code,
+        }
+    }
+
+    /// Returns true if the code needs the high bits of the EDNS OPT record, e.g. BADVERS or
+    ///  BADCOOKIE, rather than only the 4 bits of the Header
+    pub fn is_extended(self) -> bool {
+        self.high() != 0
+    }
+
     /// Transforms the response code into the human message
     pub fn to_str(self) -> &'static str {
         match self {
@@ -267,3 +285,32 @@ impl From<u16> for ResponseCode {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_extended() {
+        assert_eq!(ResponseCode::from_extended(0, 3, false), ResponseCode::NXDomain);
+        assert_eq!(ResponseCode::from_extended(1, 0, false), ResponseCode::BADVERS);
+        assert_eq!(ResponseCode::from_extended(1, 0, true), ResponseCode::BADSIG);
+        assert_eq!(ResponseCode::from_extended(1, 7, false), ResponseCode::BADCOOKIE);
+        assert_eq!(ResponseCode::from_extended(1, 1, true), ResponseCode::BADKEY);
+        assert_eq!(ResponseCode::from_extended(0xFF, 0xF, false), ResponseCode::Unknown(4095));
+
+        // the high bits of the header code are not part of the extended code
+        assert_eq!(ResponseCode::from_extended(0, 0xF3, false), ResponseCode::NXDomain);
+    }
+
+    #[test]
+    fn test_is_extended() {
+        assert!(!ResponseCode::NoError.is_extended());
+        assert!(!ResponseCode::NotZone.is_extended());
+        assert!(ResponseCode::BADVERS.is_extended());
+        assert!(ResponseCode::BADCOOKIE.is_extended());
+
+        assert_eq!(ResponseCode::BADCOOKIE.high(), 1);
+        assert_eq!(ResponseCode::BADCOOKIE.low(), 7);
+    }
+}
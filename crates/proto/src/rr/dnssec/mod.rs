@@ -28,6 +28,8 @@ mod rsa_public_key;
 mod supported_algorithm;
 pub mod tbs;
 mod trust_anchor;
+mod trust_anchor_store;
+pub mod tsig;
 mod verifier;
 
 pub use self::algorithm::Algorithm;
@@ -39,6 +41,8 @@ pub use self::public_key::PublicKeyEnum;
 pub use self::supported_algorithm::SupportedAlgorithms;
 pub use self::tbs::TBS;
 pub use self::trust_anchor::TrustAnchor;
+pub use self::trust_anchor_store::TrustAnchorStore;
+pub use self::tsig::{TsigAlgorithm, TsigKey, TsigResponder};
 pub use self::verifier::Verifier;
 
 #[cfg(all(not(feature = "ring"), feature = "openssl"))]
@@ -28,6 +28,7 @@ pub mod nsec;
 pub mod nsec3;
 pub mod nsec3param;
 pub mod sig;
+pub mod tsig;
 
 use std::str::FromStr;
 
@@ -48,6 +49,7 @@ pub use self::nsec::NSEC;
 pub use self::nsec3::NSEC3;
 pub use self::nsec3param::NSEC3PARAM;
 pub use self::sig::SIG;
+pub use self::tsig::TSIG;
 
 /// The type of the resource record, for DNSSEC-specific records.
 #[cfg_attr(feature = "serde-config", derive(Deserialize, Serialize))]
@@ -76,6 +78,10 @@ pub enum DNSSECRecordType {
     /// for now, we enable/disable SIG(0) in exactly the same circumstances that
     /// we enable/disable DNSSEC. This may change in the future.
     SIG,
+    /// RFC 8945 Transaction signature, authenticating a message with a shared secret
+    ///
+    /// Like SIG, this is only used in messages, and is enabled along with SIG(0).
+    TSIG,
     /// Unknown or not yet supported DNSSec record type
     Unknown(u16),
 }
@@ -93,6 +99,7 @@ impl FromStr for DNSSECRecordType {
             "NSEC3PARAM" => Ok(DNSSECRecordType::NSEC3PARAM),
             "RRSIG" => Ok(DNSSECRecordType::RRSIG),
             "SIG" => Ok(DNSSECRecordType::SIG),
+            "TSIG" => Ok(DNSSECRecordType::TSIG),
             _ => Err(ProtoErrorKind::UnknownRecordTypeStr(str.to_string()).into()),
         }
     }
@@ -109,6 +116,7 @@ impl From<u16> for DNSSECRecordType {
             51 => DNSSECRecordType::NSEC3PARAM,
             46 => DNSSECRecordType::RRSIG,
             24 => DNSSECRecordType::SIG,
+            250 => DNSSECRecordType::TSIG,
             _ => DNSSECRecordType::Unknown(value),
         }
     }
@@ -125,6 +133,7 @@ impl From<DNSSECRecordType> for &'static str {
             DNSSECRecordType::NSEC3PARAM => "NSEC3PARAM",
             DNSSECRecordType::RRSIG => "RRSIG",
             DNSSECRecordType::SIG => "SIG",
+            DNSSECRecordType::TSIG => "TSIG",
             DNSSECRecordType::Unknown(..) => "DnsSecUnknown",
         }
     }
@@ -141,6 +150,7 @@ impl From<DNSSECRecordType> for u16 {
             DNSSECRecordType::NSEC3PARAM => 51,
             DNSSECRecordType::RRSIG => 46,
             DNSSECRecordType::SIG => 24,
+            DNSSECRecordType::TSIG => 250,
             DNSSECRecordType::Unknown(value) => value,
         }
     }
@@ -474,6 +484,33 @@ pub enum DNSSECRData {
     /// ```
     SIG(SIG),
 
+    /// ```text
+    /// RFC 8945          Secret Key Transaction Authentication for DNS    November 2020
+    ///
+    /// 4.2.  TSIG Record Format
+    ///
+    ///                         1 1 1 1 1 1 1 1 1 1 2 2 2 2 2 2 2 2 2 2 3 3
+    ///     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+    ///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    ///    /                         Algorithm Name                        /
+    ///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    ///    |                                                               |
+    ///    |          Time Signed          +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    ///    |                               |            Fudge              |
+    ///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    ///    |          MAC Size             |                               /
+    ///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+             MAC               /
+    ///    /                                                               /
+    ///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    ///    |          Original ID          |            Error              |
+    ///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    ///    |          Other Len            |                               /
+    ///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+           Other Data          /
+    ///    /                                                               /
+    ///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    /// ```
+    TSIG(TSIG),
+
     /// Unknown or unsupported DNSSec record data
     Unknown {
         /// RecordType code
@@ -522,6 +559,10 @@ impl DNSSECRData {
                 trace!("reading SIG");
                 sig::read(decoder, rdata_length).map(DNSSECRData::SIG)
             }
+            DNSSECRecordType::TSIG => {
+                trace!("reading TSIG");
+                tsig::read(decoder).map(DNSSECRData::TSIG)
+            }
             DNSSECRecordType::Unknown(code) => {
                 trace!("reading unknown dnssec: {}", code);
                 null::read(decoder, rdata_length).map(|rdata| DNSSECRData::Unknown { code, rdata })
@@ -552,6 +593,9 @@ impl DNSSECRData {
             DNSSECRData::SIG(ref sig) => {
                 encoder.with_canonical_names(|encoder| sig::emit(encoder, sig))
             }
+            DNSSECRData::TSIG(ref tsig) => {
+                encoder.with_canonical_names(|encoder| tsig::emit(encoder, tsig))
+            }
             DNSSECRData::Unknown { ref rdata, .. } => {
                 encoder.with_canonical_names(|encoder| null::emit(encoder, rdata))
             }
@@ -567,6 +611,7 @@ impl DNSSECRData {
             DNSSECRData::NSEC3(..) => DNSSECRecordType::NSEC3,
             DNSSECRData::NSEC3PARAM(..) => DNSSECRecordType::NSEC3PARAM,
             DNSSECRData::SIG(..) => DNSSECRecordType::SIG,
+            DNSSECRData::TSIG(..) => DNSSECRecordType::TSIG,
             DNSSECRData::Unknown { code, .. } => DNSSECRecordType::Unknown(code),
         }
     }
@@ -586,6 +631,7 @@ impl fmt::Display for DNSSECRData {
             DNSSECRData::NSEC3(nsec3) => w(f, nsec3),
             DNSSECRData::NSEC3PARAM(nsec3param) => w(f, nsec3param),
             DNSSECRData::SIG(sig) => w(f, sig),
+            DNSSECRData::TSIG(tsig) => w(f, tsig),
             DNSSECRData::Unknown { rdata, .. } => w(f, rdata),
         }
     }
@@ -0,0 +1,259 @@
+// Copyright 2015-2020 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! transaction signature, used to authenticate messages with a shared secret
+
+use std::fmt;
+
+use crate::error::*;
+use crate::rr::Name;
+use crate::serialize::binary::*;
+
+/// The largest value of the 48 bit `time_signed` field
+const MAX_TIME_SIGNED: u64 = (1 << 48) - 1;
+
+/// [RFC 8945](https://tools.ietf.org/html/rfc8945#section-4.2), Secret Key Transaction
+///  Authentication for DNS (TSIG), November 2020
+///
+/// ```text
+/// 4.2.  TSIG Record Format
+///
+///    The fields of the TSIG RR are described below.  All multi-octet
+///    integers in the record are sent in network byte order (see
+///    Section 2.3.2 of [RFC1035]).
+///
+///    NAME:  The name of the key used, in domain name syntax.  The name
+///       should reflect the names of the hosts and uniquely identify the
+///       key among a set of keys these two hosts may share at any given
+///       time.
+///
+///    TYPE:  This MUST be TSIG (250: Transaction SIGnature).
+///
+///    CLASS:  This MUST be ANY.
+///
+///    TTL:  This MUST be 0.
+///
+///    RDLENGTH:  (variable)
+///
+///    RDATA:  The RDATA for a TSIG RR consists of a number of fields,
+///       described below:
+///
+///                            1 1 1 1 1 1 1 1 1 1 2 2 2 2 2 2 2 2 2 2 3 3
+///        0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+///       +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///       /                         Algorithm Name                        /
+///       +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///       |                                                               |
+///       |          Time Signed          +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///       |                               |            Fudge              |
+///       +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///       |          MAC Size             |                               /
+///       +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+             MAC               /
+///       /                                                               /
+///       +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///       |          Original ID          |            Error              |
+///       +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///       |          Other Len            |                               /
+///       +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+           Other Data          /
+///       /                                                               /
+///       +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct TSIG {
+    algorithm: Name,
+    time_signed: u64,
+    fudge: u16,
+    mac: Vec<u8>,
+    original_id: u16,
+    error: u16,
+    other: Vec<u8>,
+}
+
+impl TSIG {
+    /// Creates a new TSIG record data
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm` - name of the MAC algorithm, e.g. `hmac-sha256.`
+    /// * `time_signed` - seconds since the epoch at which the message was signed, only the lower
+    ///                   48 bits are sent.
+    /// * `fudge` - seconds of clock skew permitted between the signer and the verifier
+    /// * `mac` - the MAC of the message and the TSIG variables
+    /// * `original_id` - the id of the message when it was originally signed
+    /// * `error` - the extended response code of the TSIG processing, 0 for a request
+    /// * `other` - other data, only used with the BADTIME error
+    pub fn new(
+        algorithm: Name,
+        time_signed: u64,
+        fudge: u16,
+        mac: Vec<u8>,
+        original_id: u16,
+        error: u16,
+        other: Vec<u8>,
+    ) -> Self {
+        TSIG {
+            algorithm,
+            time_signed: time_signed & MAX_TIME_SIGNED,
+            fudge,
+            mac,
+            original_id,
+            error,
+            other,
+        }
+    }
+
+    /// Add the MAC to existing TSIG record data, i.e. the TSIG with which it was computed
+    pub fn set_mac(self, mac: Vec<u8>) -> Self {
+        TSIG { mac, ..self }
+    }
+
+    /// Name of the MAC algorithm
+    pub fn algorithm(&self) -> &Name {
+        &self.algorithm
+    }
+
+    /// Seconds since the epoch at which the message was signed
+    pub fn time_signed(&self) -> u64 {
+        self.time_signed
+    }
+
+    /// Seconds of clock skew permitted between the signer and the verifier
+    pub fn fudge(&self) -> u16 {
+        self.fudge
+    }
+
+    /// The MAC of the message and the TSIG variables
+    pub fn mac(&self) -> &[u8] {
+        &self.mac
+    }
+
+    /// The id of the message when it was originally signed
+    pub fn original_id(&self) -> u16 {
+        self.original_id
+    }
+
+    /// The extended response code of the TSIG processing
+    pub fn error(&self) -> u16 {
+        self.error
+    }
+
+    /// Other data, the server's time for a BADTIME error
+    pub fn other(&self) -> &[u8] {
+        &self.other
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder<'_>) -> ProtoResult<TSIG> {
+    let algorithm = Name::read(decoder)?;
+
+    let time_high = u64::from(decoder.read_u16()?.unverified(/*valid as any u16*/));
+    let time_low = u64::from(decoder.read_u32()?.unverified(/*valid as any u32*/));
+    let time_signed = (time_high << 32) | time_low;
+    let fudge = decoder.read_u16()?.unverified(/*valid as any u16*/);
+
+    let mac_size = decoder
+        .read_u16()?
+        .map(|u| u as usize)
+        .verify_unwrap(|mac_size| *mac_size <= decoder.len())
+        .map_err(|_| ProtoError::from("mac size exceeds buffer length"))?;
+    let mac = decoder.read_vec(mac_size)?.unverified(/*will fail in verification if invalid*/);
+
+    let original_id = decoder.read_u16()?.unverified(/*valid as any u16*/);
+    let error = decoder.read_u16()?.unverified(/*valid as any u16*/);
+
+    let other_len = decoder
+        .read_u16()?
+        .map(|u| u as usize)
+        .verify_unwrap(|other_len| *other_len <= decoder.len())
+        .map_err(|_| ProtoError::from("other len exceeds buffer length"))?;
+    let other = decoder.read_vec(other_len)?.unverified(/*valid as any array of u8*/);
+
+    Ok(TSIG::new(
+        algorithm,
+        time_signed,
+        fudge,
+        mac,
+        original_id,
+        error,
+        other,
+    ))
+}
+
+/// Write the RData from the given Decoder
+///
+/// The algorithm name is never compressed, and is always written in lowercase.
+pub fn emit(encoder: &mut BinEncoder<'_>, tsig: &TSIG) -> ProtoResult<()> {
+    tsig.algorithm()
+        .to_lowercase()
+        .emit_as_canonical(encoder, true)?;
+    emit_time_signed(encoder, tsig.time_signed())?;
+    encoder.emit_u16(tsig.fudge())?;
+    encoder.emit_u16(tsig.mac().len() as u16)?;
+    encoder.emit_vec(tsig.mac())?;
+    encoder.emit_u16(tsig.original_id())?;
+    encoder.emit_u16(tsig.error())?;
+    encoder.emit_u16(tsig.other().len() as u16)?;
+    encoder.emit_vec(tsig.other())?;
+
+    Ok(())
+}
+
+/// Writes the lower 48 bits of `time_signed`
+pub fn emit_time_signed(encoder: &mut BinEncoder<'_>, time_signed: u64) -> ProtoResult<()> {
+    encoder.emit_u16((time_signed >> 32) as u16)?;
+    encoder.emit_u32(time_signed as u32)
+}
+
+impl fmt::Display for TSIG {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "{alg} {time} {fudge} {mac} {id} {error} {other}",
+            alg = self.algorithm,
+            time = self.time_signed,
+            fudge = self.fudge,
+            mac = data_encoding::BASE64.encode(&self.mac),
+            id = self.original_id,
+            error = self.error,
+            other = data_encoding::BASE64.encode(&self.other),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::dbg_macro, clippy::print_stdout)]
+
+    use super::*;
+
+    #[test]
+    fn test() {
+        use std::str::FromStr;
+
+        let rdata = TSIG::new(
+            Name::from_str("hmac-sha256.").unwrap(),
+            0x0102_0304_0506,
+            300,
+            vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+            0xABCD,
+            0,
+            vec![],
+        );
+
+        let mut bytes = Vec::new();
+        let mut encoder: BinEncoder<'_> = BinEncoder::new(&mut bytes);
+        assert!(emit(&mut encoder, &rdata).is_ok());
+        let bytes = encoder.into_bytes();
+
+        println!("bytes: {:?}", bytes);
+
+        let mut decoder: BinDecoder<'_> = BinDecoder::new(bytes);
+        let read_rdata = read(&mut decoder).expect("Decoding error");
+        assert_eq!(rdata, read_rdata);
+    }
+}
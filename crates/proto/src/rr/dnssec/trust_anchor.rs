@@ -24,7 +24,7 @@ const ROOT_ANCHOR_ORIG: &[u8] = include_bytes!("roots/19036.rsa");
 const ROOT_ANCHOR_2018: &[u8] = include_bytes!("roots/20326.rsa");
 
 /// The root set of trust anchors for validating DNSSec, anything in this set will be trusted
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct TrustAnchor {
     // TODO: these should also store some information, or more specifically, metadata from the signed
     //  public certificate.
@@ -0,0 +1,272 @@
+// Copyright 2015-2020 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Configured DNSKEY and DS trust anchors, from which DNSSEC chains of trust are built
+
+use std::fs;
+use std::path::Path;
+use std::str::{FromStr, SplitWhitespace};
+
+use data_encoding::{BASE64, HEXUPPER_PERMISSIVE};
+
+use crate::error::*;
+use crate::rr::dnssec::rdata::{DNSSECRData, DNSKEY, DS};
+use crate::rr::dnssec::{Algorithm, DigestType, TrustAnchor};
+use crate::rr::{Name, RData, Record};
+
+/// The DNSKEY and DS records trusted for validating DNSSEC
+///
+/// A DNSKEY is trusted if it is one of the configured DNSKEYs for its zone, or if it is covered by
+///  one of the configured DS records for its zone. Keys of a `TrustAnchor` are trusted in any
+///  zone. The default store contains the root key-signing-keys.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrustAnchorStore {
+    trust_anchor: TrustAnchor,
+    dnskeys: Vec<(Name, DNSKEY)>,
+    ds: Vec<(Name, DS)>,
+}
+
+impl Default for TrustAnchorStore {
+    fn default() -> Self {
+        TrustAnchor::default().into()
+    }
+}
+
+impl From<TrustAnchor> for TrustAnchorStore {
+    fn from(trust_anchor: TrustAnchor) -> Self {
+        TrustAnchorStore {
+            trust_anchor,
+            dnskeys: vec![],
+            ds: vec![],
+        }
+    }
+}
+
+impl TrustAnchorStore {
+    /// Creates a new empty store, nothing is trusted until anchors are inserted
+    pub fn new() -> Self {
+        TrustAnchor::new().into()
+    }
+
+    /// Parses the anchors from DS and DNSKEY records in zone file format, as in the `root.key`
+    ///  written by `unbound-anchor`, e.g.
+    ///
+    /// ```text
+    /// . 86400 IN DS 20326 8 2 E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D
+    /// ```
+    ///
+    /// Each record must be on a single line, the TTL and class are optional and ignored. Blank
+    ///  lines and comments, starting with `;`, are skipped. The XML `root-anchors.xml` published
+    ///  by IANA is not supported, its DS records must first be converted to this format.
+    pub fn from_root_anchors(anchors: &str) -> ProtoResult<Self> {
+        let mut store = Self::new();
+        for line in anchors.lines() {
+            let line = line.split(';').next().unwrap_or_default();
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            store.insert_record(&parse_anchor(line)?)?;
+        }
+
+        Ok(store)
+    }
+
+    /// Reads the anchors from a file, see `from_root_anchors` for the format
+    pub fn from_root_anchors_file<P: AsRef<Path>>(path: P) -> ProtoResult<Self> {
+        Self::from_root_anchors(&fs::read_to_string(path)?)
+    }
+
+    /// Trusts the DNSKEY for the zone `name`
+    pub fn insert_dnskey(&mut self, name: Name, dnskey: DNSKEY) {
+        if !self.dnskeys.contains(&(name.clone(), dnskey.clone())) {
+            self.dnskeys.push((name, dnskey));
+        }
+    }
+
+    /// Trusts the DNSKEYs of the zone `name` which are covered by the DS
+    pub fn insert_ds(&mut self, name: Name, ds: DS) {
+        if !self.ds.contains(&(name.clone(), ds.clone())) {
+            self.ds.push((name, ds));
+        }
+    }
+
+    /// Inserts a DNSKEY or DS record, any other record is an error
+    pub fn insert_record(&mut self, record: &Record) -> ProtoResult<()> {
+        match record.rdata() {
+            RData::DNSSEC(DNSSECRData::DNSKEY(dnskey)) => {
+                self.insert_dnskey(record.name().clone(), dnskey.clone())
+            }
+            RData::DNSSEC(DNSSECRData::DS(ds)) => self.insert_ds(record.name().clone(), ds.clone()),
+            _ => {
+                return Err(format!(
+                    "trust anchors must be DNSKEY or DS records: {}",
+                    record.rr_type()
+                )
+                .into())
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if the DNSKEY of the zone `name` is trusted, revoked keys never are
+    pub fn contains(&self, name: &Name, dnskey: &DNSKEY) -> bool {
+        if dnskey.revoke() {
+            return false;
+        }
+
+        self.trust_anchor.contains_dnskey_bytes(dnskey.public_key())
+            || self
+                .dnskeys
+                .iter()
+                .any(|(anchor, key)| anchor == name && key == dnskey)
+            || self
+                .ds
+                .iter()
+                .filter(|(anchor, _)| anchor == name)
+                .any(|(_, ds)| ds.covers(name, dnskey).unwrap_or(false))
+    }
+
+    /// The number of anchors in the store
+    pub fn len(&self) -> usize {
+        self.trust_anchor.len() + self.dnskeys.len() + self.ds.len()
+    }
+
+    /// Returns true if there are no anchors in the store
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Parses a single line DS or DNSKEY record
+fn parse_anchor(line: &str) -> ProtoResult<Record> {
+    let mut tokens = line.split_whitespace();
+    let name = tokens.next().ok_or("missing name of trust anchor")?;
+    let name = Name::parse(name, Some(&Name::root()))?;
+
+    // skip past the optional TTL and class
+    let record_type = tokens
+        .find(|token| token.parse::<u32>().is_err() && !token.eq_ignore_ascii_case("IN"))
+        .ok_or_else(|| format!("missing type of trust anchor: {}", name))?;
+
+    let rdata = match record_type.to_ascii_uppercase().as_str() {
+        "DS" => {
+            let key_tag = next_field(&mut tokens, line)?;
+            let algorithm = Algorithm::from_u8(next_field(&mut tokens, line)?);
+            let digest_type = DigestType::from_u8(next_field(&mut tokens, line)?)?;
+            let digest = HEXUPPER_PERMISSIVE
+                .decode(tokens.collect::<String>().as_bytes())
+                .map_err(|e| format!("bad digest of trust anchor: {}: {}", name, e))?;
+
+            DNSSECRData::DS(DS::new(key_tag, algorithm, digest_type, digest))
+        }
+        "DNSKEY" => {
+            let flags: u16 = next_field(&mut tokens, line)?;
+            let protocol: u8 = next_field(&mut tokens, line)?;
+            if protocol != 3 {
+                return Err(format!("bad protocol of trust anchor: {}: {}", name, protocol).into());
+            }
+            let algorithm = Algorithm::from_u8(next_field(&mut tokens, line)?);
+            let public_key = BASE64
+                .decode(tokens.collect::<String>().as_bytes())
+                .map_err(|e| format!("bad public key of trust anchor: {}: {}", name, e))?;
+
+            DNSSECRData::DNSKEY(DNSKEY::new(
+                flags & 0b0000_0001_0000_0000 != 0,
+                flags & 0b0000_0000_0000_0001 != 0,
+                flags & 0b0000_0000_1000_0000 != 0,
+                algorithm,
+                public_key,
+            ))
+        }
+        record_type => {
+            return Err(format!(
+                "trust anchors must be DNSKEY or DS records: {}",
+                record_type
+            )
+            .into())
+        }
+    };
+
+    Ok(Record::from_rdata(name, 0, RData::DNSSEC(rdata)))
+}
+
+/// Parses the next field of the record on `line`
+fn next_field<T>(tokens: &mut SplitWhitespace<'_>, line: &str) -> ProtoResult<T>
+where
+    T: FromStr,
+    ProtoError: From<T::Err>,
+{
+    Ok(tokens
+        .next()
+        .ok_or_else(|| format!("incomplete trust anchor: {}", line))?
+        .parse::<T>()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOT_DS: &str =
+        ". 86400 IN DS 20326 8 2 E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D";
+    const ROOT_DNSKEY: &str = concat!(
+        ". IN DNSKEY 257 3 8 ",
+        "AwEAAaz/tAm8yTn4Mfeh5eyI96WSVexTBAvkMgJzkKTOiW1vkIbzxeF3+/4RgWOq",
+        "7HrxRixHlFlExOLAJr5emLvN7SWXgnLh4+B5xQlNVz8Og8kvArMtNROxVQuCaSnI",
+        "DdD5LKyWbRd2n9WGe2R8PzgCmr3EgVLrjyBxWezF0jLHwVN8efS3rCj/EWgvIWgb",
+        "9tarpVUDK/b58Da+sqqls3eNbuv7pr+eoZG+SrDK6nWeL3c6H5Apxz7LjVc1uTId",
+        "sIXxuOLYA4/ilBmSVIzuDWfdRUfhHdY6+cn8HFRm+2hM8AnXGXws9555KrUB5qih",
+        "ylGa8subX2Nn6UwNR1AkUTV74bU=",
+    );
+
+    fn root_dnskey() -> DNSKEY {
+        let record = parse_anchor(ROOT_DNSKEY).expect("failed to parse DNSKEY");
+        match record.rdata() {
+            RData::DNSSEC(DNSSECRData::DNSKEY(dnskey)) => dnskey.clone(),
+            rdata => panic!("not a DNSKEY: {:?}", rdata),
+        }
+    }
+
+    #[test]
+    fn test_from_root_anchors() {
+        let anchors = format!(
+            "; the root KSK-2017\n\n{}\n{} ; as a DNSKEY\n",
+            ROOT_DS, ROOT_DNSKEY
+        );
+        let store = TrustAnchorStore::from_root_anchors(&anchors).expect("failed to parse anchors");
+        assert_eq!(store.len(), 2);
+
+        let dnskey = root_dnskey();
+        assert!(dnskey.zone_key());
+        assert!(dnskey.secure_entry_point());
+        assert_eq!(dnskey.algorithm(), Algorithm::RSASHA256);
+        assert!(TrustAnchor::default().contains_dnskey_bytes(dnskey.public_key()));
+        assert!(store.contains(&Name::root(), &dnskey));
+        assert!(!store.contains(&Name::from_ascii("com.").unwrap(), &dnskey));
+
+        assert!(TrustAnchorStore::from_root_anchors(". IN A 127.0.0.1").is_err());
+    }
+
+    #[test]
+    #[cfg(any(feature = "openssl", feature = "ring"))]
+    fn test_ds_anchor() {
+        let store = TrustAnchorStore::from_root_anchors(ROOT_DS).expect("failed to parse DS");
+        let dnskey = root_dnskey();
+        assert!(store.contains(&Name::root(), &dnskey));
+        assert!(!TrustAnchorStore::new().contains(&Name::root(), &dnskey));
+
+        let revoked = DNSKEY::new(
+            true,
+            true,
+            true,
+            dnskey.algorithm(),
+            dnskey.public_key().to_vec(),
+        );
+        assert!(!store.contains(&Name::root(), &revoked));
+    }
+}
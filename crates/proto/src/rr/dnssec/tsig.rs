@@ -0,0 +1,577 @@
+// Copyright 2015-2020 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Shared secret keys for signing and verifying messages with TSIG, RFC 8945
+
+#[cfg(all(not(feature = "ring"), feature = "openssl"))]
+use openssl::{hash::MessageDigest, memcmp, pkey::PKey, sign::Signer};
+#[cfg(feature = "ring")]
+use ring::hmac;
+
+use log::debug;
+
+use crate::error::*;
+use crate::op::{Message, MessageFinalizer, ResponseCode};
+use crate::rr::dnssec::rdata::tsig::{self, TSIG};
+use crate::rr::dnssec::rdata::DNSSECRData;
+use crate::rr::{DNSClass, Name, RData, Record};
+use crate::serialize::binary::{BinEncodable, BinEncoder, EncodeMode};
+
+/// The default number of seconds of clock skew permitted between the signer and the verifier
+pub const DEFAULT_FUDGE: u16 = 300;
+
+/// The MAC algorithm of a TSIG key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TsigAlgorithm {
+    /// HMAC with SHA-256, `hmac-sha256.`
+    HmacSha256,
+    /// HMAC with SHA-384, `hmac-sha384.`
+    HmacSha384,
+    /// HMAC with SHA-512, `hmac-sha512.`
+    HmacSha512,
+}
+
+impl TsigAlgorithm {
+    /// The name of the algorithm, as sent in the TSIG record
+    pub fn to_name(self) -> Name {
+        let name = match self {
+            TsigAlgorithm::HmacSha256 => "hmac-sha256.",
+            TsigAlgorithm::HmacSha384 => "hmac-sha384.",
+            TsigAlgorithm::HmacSha512 => "hmac-sha512.",
+        };
+
+        Name::from_ascii(name).expect("bad tsig algorithm name")
+    }
+
+    /// The *ring* counterpart for the algorithm
+    #[cfg(feature = "ring")]
+    fn to_ring_hmac(self) -> hmac::Algorithm {
+        match self {
+            TsigAlgorithm::HmacSha256 => hmac::HMAC_SHA256,
+            TsigAlgorithm::HmacSha384 => hmac::HMAC_SHA384,
+            TsigAlgorithm::HmacSha512 => hmac::HMAC_SHA512,
+        }
+    }
+
+    /// The OpenSSL counterpart for the algorithm
+    #[cfg(all(not(feature = "ring"), feature = "openssl"))]
+    fn to_openssl_digest(self) -> MessageDigest {
+        match self {
+            TsigAlgorithm::HmacSha256 => MessageDigest::sha256(),
+            TsigAlgorithm::HmacSha384 => MessageDigest::sha384(),
+            TsigAlgorithm::HmacSha512 => MessageDigest::sha512(),
+        }
+    }
+}
+
+/// A shared secret used to sign and verify messages with TSIG
+///
+/// The same key, by name, algorithm and secret, must be configured on both ends, e.g. on the
+///  primary and the secondary of a zone, or on the server and the client sending updates.
+#[derive(Clone)]
+pub struct TsigKey {
+    name: Name,
+    algorithm: TsigAlgorithm,
+    secret: Vec<u8>,
+    fudge: u16,
+}
+
+impl TsigKey {
+    /// Creates a new key, permitting `DEFAULT_FUDGE` seconds of clock skew
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - name of the key, this is the name of the TSIG record
+    /// * `algorithm` - the MAC algorithm
+    /// * `secret` - the shared secret
+    pub fn new(name: Name, algorithm: TsigAlgorithm, secret: Vec<u8>) -> Self {
+        TsigKey {
+            name,
+            algorithm,
+            secret,
+            fudge: DEFAULT_FUDGE,
+        }
+    }
+
+    /// Sets the seconds of clock skew permitted between the signer and the verifier
+    pub fn set_fudge(&mut self, fudge: u16) {
+        self.fudge = fudge;
+    }
+
+    /// The name of the key
+    pub fn name(&self) -> &Name {
+        &self.name
+    }
+
+    /// The MAC algorithm of the key
+    pub fn algorithm(&self) -> TsigAlgorithm {
+        self.algorithm
+    }
+
+    /// Signs the message, returning the TSIG record to append to it
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - the message to sign, any SIG0 or TSIG records of it are not signed
+    /// * `id` - the id of the message
+    /// * `time_signed` - the current time, in seconds since the epoch
+    pub fn sign_message<M: BinEncodable>(
+        &self,
+        message: &M,
+        id: u16,
+        time_signed: u64,
+    ) -> ProtoResult<Record> {
+        let pre_tsig = TSIG::new(
+            self.algorithm.to_name(),
+            time_signed,
+            self.fudge,
+            vec![],
+            id,
+            0,
+            vec![],
+        );
+
+        self.sign(&emit_for_signing(message)?, pre_tsig, None)
+    }
+
+    /// Signs the message, as emitted without any SIG0 or TSIG records, over the MAC of the request
+    ///  if this is a response
+    fn sign(
+        &self,
+        message: &[u8],
+        pre_tsig: TSIG,
+        request_mac: Option<&[u8]>,
+    ) -> ProtoResult<Record> {
+        let mac = self.mac(&self.tbs(message, &pre_tsig, request_mac)?)?;
+
+        Ok(tsig_record(self.name.clone(), pre_tsig.set_mac(mac)))
+    }
+
+    /// Verifies the TSIG record of a message, with this key
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - the message which was signed, any SIG0 or TSIG records of it are ignored
+    /// * `tsig` - the TSIG record of the message
+    /// * `now` - the current time, in seconds since the epoch
+    ///
+    /// # Return
+    ///
+    /// The TSIG error on failure, `BADKEY` if the record was not signed with this key, `BADSIG` if
+    ///  the MAC does not match, and `BADTIME` if the record was signed outside the fudge.
+    pub fn verify_message<M: BinEncodable>(
+        &self,
+        message: &M,
+        tsig: &Record,
+        now: u64,
+    ) -> Result<(), ResponseCode> {
+        self.verify(message, tsig, None, now)
+    }
+
+    /// Verifies the TSIG record of the response to a request signed with this key
+    ///
+    /// # Arguments
+    ///
+    /// * `response` - the response which was signed, any SIG0 or TSIG records of it are ignored
+    /// * `tsig` - the TSIG record of the response
+    /// * `request_mac` - the MAC of the TSIG record of the request
+    /// * `now` - the current time, in seconds since the epoch
+    ///
+    /// # Return
+    ///
+    /// The TSIG error of the response, e.g. `BADKEY`, if it reports one, otherwise as
+    ///  `verify_message`. Only a `BADTIME` error is signed.
+    pub fn verify_response<M: BinEncodable>(
+        &self,
+        response: &M,
+        tsig: &Record,
+        request_mac: &[u8],
+        now: u64,
+    ) -> Result<(), ResponseCode> {
+        let rdata = match tsig.rdata() {
+            RData::DNSSEC(DNSSECRData::TSIG(rdata)) if rdata.error() != 0 => rdata,
+            _ => return self.verify(response, tsig, Some(request_mac), now),
+        };
+
+        let error =
+            ResponseCode::from_extended((rdata.error() >> 4) as u8, rdata.error() as u8, true);
+        if error == ResponseCode::BADTIME {
+            // the time signed is that of the request, which was out of the fudge
+            self.verify(response, tsig, Some(request_mac), rdata.time_signed())?;
+        }
+
+        Err(error)
+    }
+
+    fn verify<M: BinEncodable>(
+        &self,
+        message: &M,
+        tsig: &Record,
+        request_mac: Option<&[u8]>,
+        now: u64,
+    ) -> Result<(), ResponseCode> {
+        let rdata = match tsig.rdata() {
+            RData::DNSSEC(DNSSECRData::TSIG(rdata)) => rdata,
+            _ => return Err(ResponseCode::FormErr),
+        };
+
+        if *tsig.name() != self.name || *rdata.algorithm() != self.algorithm.to_name() {
+            return Err(ResponseCode::BADKEY);
+        }
+
+        let verified = emit_for_signing(message)
+            .and_then(|message| self.tbs(&message, rdata, request_mac))
+            .and_then(|tbs| self.verify_mac(&tbs, rdata.mac()))
+            .map_err(|_| ResponseCode::FormErr)?;
+        if !verified {
+            return Err(ResponseCode::BADSIG);
+        }
+
+        let skew = now.max(rdata.time_signed()) - now.min(rdata.time_signed());
+        if skew > u64::from(rdata.fudge()) {
+            return Err(ResponseCode::BADTIME);
+        }
+
+        Ok(())
+    }
+
+    /// The data to be signed, the message with the original id followed by the TSIG variables
+    ///
+    /// The MAC of the request precedes these when signing a response, RFC 8945 section 5.3.
+    fn tbs(&self, message: &[u8], tsig: &TSIG, request_mac: Option<&[u8]>) -> ProtoResult<Vec<u8>> {
+        let mut buf: Vec<u8> = Vec::with_capacity(512);
+        let mut variables: Vec<u8> = Vec::with_capacity(64);
+
+        if message.len() < 2 {
+            return Err("message too short to sign".into());
+        }
+        if let Some(request_mac) = request_mac {
+            buf.extend_from_slice(&(request_mac.len() as u16).to_be_bytes());
+            buf.extend_from_slice(request_mac);
+        }
+        buf.extend_from_slice(&tsig.original_id().to_be_bytes());
+        buf.extend_from_slice(&message[2..]);
+
+        // need a separate encoder here, names in the variables are never compressed
+        {
+            let mut encoder = BinEncoder::new(&mut variables);
+            self.name
+                .to_lowercase()
+                .emit_as_canonical(&mut encoder, true)?;
+            DNSClass::ANY.emit(&mut encoder)?;
+            encoder.emit_u32(0)?;
+            tsig.algorithm()
+                .to_lowercase()
+                .emit_as_canonical(&mut encoder, true)?;
+            tsig::emit_time_signed(&mut encoder, tsig.time_signed())?;
+            encoder.emit_u16(tsig.fudge())?;
+            encoder.emit_u16(tsig.error())?;
+            encoder.emit_u16(tsig.other().len() as u16)?;
+            encoder.emit_vec(tsig.other())?;
+        }
+
+        buf.extend_from_slice(&variables);
+        Ok(buf)
+    }
+
+    #[cfg(feature = "ring")]
+    fn mac(&self, data: &[u8]) -> ProtoResult<Vec<u8>> {
+        let key = hmac::Key::new(self.algorithm.to_ring_hmac(), &self.secret);
+        Ok(hmac::sign(&key, data).as_ref().to_vec())
+    }
+
+    #[cfg(all(not(feature = "ring"), feature = "openssl"))]
+    fn mac(&self, data: &[u8]) -> ProtoResult<Vec<u8>> {
+        let key = PKey::hmac(&self.secret)?;
+        let mut signer = Signer::new(self.algorithm.to_openssl_digest(), &key)?;
+        signer.update(data)?;
+        signer.sign_to_vec().map_err(Into::into)
+    }
+
+    /// This will always error, enable openssl or ring feature at compile time
+    #[cfg(not(any(feature = "openssl", feature = "ring")))]
+    fn mac(&self, _: &[u8]) -> ProtoResult<Vec<u8>> {
+        Err("The openssl and ring features are both disabled".into())
+    }
+
+    #[cfg(feature = "ring")]
+    fn verify_mac(&self, data: &[u8], mac: &[u8]) -> ProtoResult<bool> {
+        let key = hmac::Key::new(self.algorithm.to_ring_hmac(), &self.secret);
+        Ok(hmac::verify(&key, data, mac).is_ok())
+    }
+
+    #[cfg(not(feature = "ring"))]
+    fn verify_mac(&self, data: &[u8], mac: &[u8]) -> ProtoResult<bool> {
+        let expected = self.mac(data)?;
+
+        #[cfg(feature = "openssl")]
+        let equal = expected.len() == mac.len() && memcmp::eq(&expected, mac);
+        #[cfg(not(feature = "openssl"))]
+        let equal = expected == mac;
+
+        Ok(equal)
+    }
+}
+
+/// The TSIG of the response to a request signed with TSIG, RFC 8945 section 5.3
+///
+/// The response to a request which verified is signed with the same key, over the MAC of the
+///  request. That to a request which did not carries the TSIG error, and is unsigned unless the
+///  error is `BADTIME`.
+#[derive(Clone)]
+pub struct TsigResponder {
+    key: Option<TsigKey>,
+    key_name: Name,
+    request: TSIG,
+    error: Option<ResponseCode>,
+}
+
+impl TsigResponder {
+    /// Verifies the TSIG record of the request with the key of the same name
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - the keys the request may be signed with, `BADKEY` if it is none of them
+    /// * `request` - the request which was signed, any SIG0 or TSIG records of it are ignored
+    /// * `tsig` - the TSIG record of the request
+    /// * `now` - the current time, in seconds since the epoch
+    ///
+    /// # Return
+    ///
+    /// None if the record is not a TSIG
+    pub fn verify<M: BinEncodable>(
+        keys: &[TsigKey],
+        request: &M,
+        tsig: &Record,
+        now: u64,
+    ) -> Option<Self> {
+        let rdata = match tsig.rdata() {
+            RData::DNSSEC(DNSSECRData::TSIG(rdata)) => rdata.clone(),
+            _ => return None,
+        };
+
+        let key = keys.iter().find(|key| key.name() == tsig.name()).cloned();
+        let error = match key {
+            Some(ref key) => key.verify_message(request, tsig, now).err(),
+            None => Some(ResponseCode::BADKEY),
+        };
+
+        Some(TsigResponder {
+            key,
+            key_name: tsig.name().clone(),
+            request: rdata,
+            error,
+        })
+    }
+
+    /// The name of the key the request was signed with
+    pub fn key_name(&self) -> &Name {
+        &self.key_name
+    }
+
+    /// The TSIG error of the request, None if it verified
+    pub fn error(&self) -> Option<ResponseCode> {
+        self.error
+    }
+
+    /// Returns the TSIG record to append to the response
+    ///
+    /// # Arguments
+    ///
+    /// * `response` - the response, as emitted without the TSIG record
+    /// * `now` - the current time, in seconds since the epoch
+    pub fn sign_response(&self, response: &[u8], now: u64) -> ProtoResult<Record> {
+        let request_mac = Some(self.request.mac());
+        match (self.error, self.key.as_ref()) {
+            (None, Some(key)) => {
+                let pre_tsig = self.response_tsig(key.algorithm.to_name(), now, key.fudge, 0);
+                key.sign(response, pre_tsig, request_mac)
+            }
+            // the time of the request is kept, the other data is the time of the server
+            (Some(ResponseCode::BADTIME), Some(key)) => {
+                let mut other = Vec::with_capacity(6);
+                tsig::emit_time_signed(&mut BinEncoder::new(&mut other), now)?;
+                let pre_tsig = TSIG::new(
+                    key.algorithm.to_name(),
+                    self.request.time_signed(),
+                    key.fudge,
+                    vec![],
+                    self.request.original_id(),
+                    u16::from(ResponseCode::BADTIME),
+                    other,
+                );
+                key.sign(response, pre_tsig, request_mac)
+            }
+            (error, _) => {
+                let error = u16::from(error.unwrap_or(ResponseCode::BADKEY));
+                let tsig = self.response_tsig(
+                    self.request.algorithm().clone(),
+                    now,
+                    self.request.fudge(),
+                    error,
+                );
+                Ok(tsig_record(self.key_name.clone(), tsig))
+            }
+        }
+    }
+
+    /// The TSIG of the response, without a MAC
+    fn response_tsig(&self, algorithm: Name, now: u64, fudge: u16, error: u16) -> TSIG {
+        TSIG::new(
+            algorithm,
+            now,
+            fudge,
+            vec![],
+            self.request.original_id(),
+            error,
+            vec![],
+        )
+    }
+}
+
+/// The message, as it is signed, i.e. without any SIG0 or TSIG records
+fn emit_for_signing<M: BinEncodable>(message: &M) -> ProtoResult<Vec<u8>> {
+    let mut buf: Vec<u8> = Vec::with_capacity(512);
+    {
+        let mut encoder = BinEncoder::with_mode(&mut buf, EncodeMode::Signing);
+        message.emit(&mut encoder)?;
+    }
+
+    Ok(buf)
+}
+
+/// The TSIG record of the key, the class is always ANY and the TTL 0
+fn tsig_record(key_name: Name, tsig: TSIG) -> Record {
+    let rdata = RData::DNSSEC(DNSSECRData::TSIG(tsig));
+    let mut record = Record::from_rdata(key_name, 0, rdata);
+    record.set_dns_class(DNSClass::ANY);
+    record
+}
+
+impl MessageFinalizer for TsigKey {
+    fn finalize_message(&self, message: &Message, current_time: u32) -> ProtoResult<Vec<Record>> {
+        debug!("signing message with tsig key: {}", self.name);
+        let tsig = self.sign_message(message, message.id(), u64::from(current_time))?;
+        Ok(vec![tsig])
+    }
+}
+
+#[cfg(test)]
+#[cfg(any(feature = "openssl", feature = "ring"))]
+mod tests {
+    use super::*;
+    use crate::op::{MessageType, OpCode, Query};
+    use crate::rr::RecordType;
+
+    fn tsig_key(secret: &[u8]) -> TsigKey {
+        TsigKey::new(
+            Name::from_ascii("transfer.example.com.").unwrap(),
+            TsigAlgorithm::HmacSha256,
+            secret.to_vec(),
+        )
+    }
+
+    fn message() -> Message {
+        let mut message = Message::new();
+        message
+            .set_id(1234)
+            .set_message_type(MessageType::Query)
+            .set_op_code(OpCode::Query)
+            .add_query(Query::query(
+                Name::from_ascii("example.com.").unwrap(),
+                RecordType::AXFR,
+            ));
+        message
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key = tsig_key(b"the secret");
+        let mut message = message();
+        message.finalize(&key, 1_600_000_000).unwrap();
+
+        let message = Message::from_vec(&message.to_vec().unwrap()).unwrap();
+        assert_eq!(message.sig0().len(), 1);
+        let tsig = &message.sig0()[0];
+
+        assert!(key.verify_message(&message, tsig, 1_600_000_010).is_ok());
+        assert_eq!(
+            key.verify_message(&message, tsig, 1_600_001_000),
+            Err(ResponseCode::BADTIME)
+        );
+        assert_eq!(
+            tsig_key(b"another secret").verify_message(&message, tsig, 1_600_000_010),
+            Err(ResponseCode::BADSIG)
+        );
+    }
+
+    #[test]
+    fn test_sign_and_verify_response() {
+        let key = tsig_key(b"the secret");
+        let mut request = message();
+        request.finalize(&key, 1_600_000_000).unwrap();
+        let request = Message::from_vec(&request.to_vec().unwrap()).unwrap();
+        let request_tsig = &request.sig0()[0];
+        let request_mac = match request_tsig.rdata() {
+            RData::DNSSEC(DNSSECRData::TSIG(rdata)) => rdata.mac().to_vec(),
+            rdata => panic!("expected a tsig: {:?}", rdata),
+        };
+
+        let mut response = message();
+        response.set_message_type(MessageType::Response);
+        let response_bytes = response.to_vec().unwrap();
+
+        let responder = TsigResponder::verify(&[key.clone()], &request, request_tsig, 1_600_000_010)
+            .expect("not a tsig");
+        assert_eq!(responder.error(), None);
+        let tsig = responder
+            .sign_response(&response_bytes, 1_600_000_020)
+            .unwrap();
+        assert!(key
+            .verify_response(&response, &tsig, &request_mac, 1_600_000_020)
+            .is_ok());
+        // the response is only valid as the response to the request
+        assert_eq!(
+            key.verify_message(&response, &tsig, 1_600_000_020),
+            Err(ResponseCode::BADSIG)
+        );
+
+        // the response to a request with an unknown key is not signed
+        let responder =
+            TsigResponder::verify(&[], &request, request_tsig, 1_600_000_010).expect("not a tsig");
+        assert_eq!(responder.error(), Some(ResponseCode::BADKEY));
+        let tsig = responder
+            .sign_response(&response_bytes, 1_600_000_020)
+            .unwrap();
+        match tsig.rdata() {
+            RData::DNSSEC(DNSSECRData::TSIG(rdata)) => {
+                assert!(rdata.mac().is_empty());
+                assert_eq!(rdata.error(), u16::from(ResponseCode::BADKEY));
+            }
+            rdata => panic!("expected a tsig: {:?}", rdata),
+        }
+        assert_eq!(
+            key.verify_response(&response, &tsig, &request_mac, 1_600_000_020),
+            Err(ResponseCode::BADKEY)
+        );
+    }
+
+    #[test]
+    fn test_tampered() {
+        let key = tsig_key(b"the secret");
+        let mut message = message();
+        message.finalize(&key, 1_600_000_000).unwrap();
+        let tsig = message.sig0()[0].clone();
+
+        let mut tampered = message.clone();
+        tampered.set_recursion_desired(true);
+        assert_eq!(
+            key.verify_message(&tampered, &tsig, 1_600_000_000),
+            Err(ResponseCode::BADSIG)
+        );
+    }
+}
@@ -106,6 +106,22 @@ impl Label {
         }
     }
 
+    /// Randomizes the case of the letters of this label, see `Name::to_random_case`
+    pub fn to_random_case(&self) -> Self {
+        let random_label: Vec<u8> = self
+            .0
+            .iter()
+            .map(|c| {
+                if rand::random() {
+                    c.to_ascii_uppercase()
+                } else {
+                    c.to_ascii_lowercase()
+                }
+            })
+            .collect();
+        Label(Rc::from(random_label))
+    }
+
     /// Returns true if this label is the wildcard, '*', label
     pub fn is_wildcard(&self) -> bool {
         self.as_bytes() == WILDCARD
@@ -251,6 +251,28 @@ impl Name {
         }
     }
 
+    /// Randomizes the case of the letters of this name, for the DNS 0x20 encoding
+    ///
+    /// A response must echo the name of the query exactly, which a spoofed response would have to
+    ///  guess, see
+    ///  [draft-vixie-dnsext-dns0x20](https://tools.ietf.org/html/draft-vixie-dnsext-dns0x20-00).
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use trust_dns_proto::rr::domain::Name;
+    ///
+    /// let name = Name::from_str("www.example.com.").unwrap();
+    /// let random = name.to_random_case();
+    /// assert_eq!(random, name);
+    /// assert!(random.to_lowercase().eq_case(&name));
+    /// ```
+    pub fn to_random_case(&self) -> Self {
+        Name {
+            is_fqdn: self.is_fqdn,
+            labels: self.labels.iter().map(Label::to_random_case).collect(),
+        }
+    }
+
     /// Trims off the first part of the name, to help with searching for the domain piece
     ///
     /// # Examples
@@ -1039,11 +1061,19 @@ impl<'r> BinDecodable<'r> for Name {
     ///  all names will be stored lowercase internally.
     /// This will consume the portions of the `Vec` which it is reading...
     fn read(decoder: &mut BinDecoder<'r>) -> ProtoResult<Name> {
-        read_inner(decoder, None)
+        read_inner(decoder, None, 0)
     }
 }
 
-fn read_inner<'r>(decoder: &mut BinDecoder<'r>, max_idx: Option<usize>) -> ProtoResult<Name> {
+/// The most compression pointers followed for a single name, as many as a name can have labels
+const MAX_POINTERS: usize = 127;
+
+/// `pointers` is the number of compression pointers already followed to reach this name
+fn read_inner<'r>(
+    decoder: &mut BinDecoder<'r>,
+    max_idx: Option<usize>,
+    pointers: usize,
+) -> ProtoResult<Name> {
     let mut state: LabelParseState = LabelParseState::LabelLengthOrPointer;
     let mut labels: Vec<Label> = Vec::with_capacity(3); // most labels will be around three, e.g. www.example.com
     let name_start = decoder.index();
@@ -1121,6 +1151,13 @@ fn read_inner<'r>(decoder: &mut BinDecoder<'r>, max_idx: Option<usize>) -> Proto
             // etc.
             LabelParseState::Pointer => {
                 let pointer_location = decoder.index();
+                if pointers >= MAX_POINTERS {
+                    return Err(ProtoErrorKind::TooManyPointers {
+                        idx: pointer_location,
+                    }
+                    .into());
+                }
+
                 let location = decoder
                     .read_u16()?
                     .map(|u| {
@@ -1139,7 +1176,7 @@ fn read_inner<'r>(decoder: &mut BinDecoder<'r>, max_idx: Option<usize>) -> Proto
                     })?;
 
                 let mut pointer = decoder.clone(location);
-                let pointed = read_inner(&mut pointer, Some(name_start))?;
+                let pointed = read_inner(&mut pointer, Some(name_start), pointers + 1)?;
 
                 for l in &*pointed.labels {
                     if !l.is_empty() {
@@ -1160,6 +1197,12 @@ fn read_inner<'r>(decoder: &mut BinDecoder<'r>, max_idx: Option<usize>) -> Proto
     }
 
     run_len += if labels.is_empty() { 1 } else { labels.len() };
+
+    // the labels of a pointer are only counted once expanded
+    if run_len > 255 {
+        return Err(ProtoErrorKind::DomainNameTooLong(run_len).into());
+    }
+
     let name = Name {
         is_fqdn: true,
         labels,
@@ -1454,6 +1497,67 @@ mod tests {
         assert!(Name::read(&mut d).is_err());
     }
 
+    #[test]
+    fn test_forward_pointer() {
+        // a.<pointer to b.>, b.
+        let bytes = vec![0x01, b'a', 0xC0, 0x04, 0x01, b'b', 0x00];
+        let mut d = BinDecoder::new(&bytes);
+
+        let err = Name::read(&mut d).unwrap_err();
+        assert!(matches!(err.kind(), ProtoErrorKind::PointerNotPriorToLabel { idx: 2, ptr: 4 }));
+    }
+
+    #[test]
+    fn test_pointer_loop() {
+        // a.<pointer to b.>, b.<pointer to a.>, read from the second
+        let bytes = vec![0x01, b'a', 0xC0, 0x04, 0x01, b'b', 0xC0, 0x00];
+        let mut d = BinDecoder::new(&bytes);
+        d.read_slice(4).unwrap();
+
+        assert!(Name::read(&mut d).is_err());
+    }
+
+    #[test]
+    fn test_backward_pointer() {
+        // a., b.<pointer to a.>
+        let bytes = vec![0x01, b'a', 0x00, 0x01, b'b', 0xC0, 0x00];
+        let mut d = BinDecoder::new(&bytes);
+
+        assert_eq!(Name::read(&mut d).unwrap(), Name::from_ascii("a.").unwrap());
+        assert_eq!(Name::read(&mut d).unwrap(), Name::from_ascii("b.a.").unwrap());
+        assert_eq!(d.index(), bytes.len());
+    }
+
+    #[test]
+    fn test_pointer_expansion_limits() {
+        // a chain of pointers, each to the one before, is capped
+        let mut bytes = vec![0x01, b'a', 0x00];
+        for i in 0..=MAX_POINTERS {
+            let ptr = if i == 0 { 0 } else { 3 + (i - 1) * 2 };
+            bytes.extend_from_slice(&[0xC0, ptr as u8]);
+        }
+        let mut d = BinDecoder::new(&bytes);
+        d.read_slice(bytes.len() - 2).unwrap();
+
+        let err = Name::read(&mut d).unwrap_err();
+        assert!(matches!(err.kind(), ProtoErrorKind::TooManyPointers { .. }));
+
+        // the expanded name must not exceed 255 octets, the pointed to name is 254
+        let mut bytes = Vec::with_capacity(512);
+        for len in &[63, 63, 63, 61] {
+            bytes.push(*len);
+            bytes.extend(iter::repeat(b'a').take(*len as usize));
+        }
+        bytes.push(0);
+        let pointed = bytes.len();
+        bytes.extend_from_slice(&[0x01, b'b', 0xC0, 0x00]);
+
+        let mut d = BinDecoder::new(&bytes);
+        d.read_slice(pointed).unwrap();
+        let err = Name::read(&mut d).unwrap_err();
+        assert!(matches!(err.kind(), ProtoErrorKind::DomainNameTooLong(_)));
+    }
+
     #[test]
     fn test_bin_overlap_enforced() {
         let mut bytes: Vec<u8> = Vec::with_capacity(512);
@@ -1831,4 +1935,17 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn test_to_random_case() {
+        let name = "the-quick-brown-fox.jumps-over-the-lazy-dog.example.com.";
+        let name = Name::from_ascii(name).unwrap();
+        let random = name.to_random_case();
+
+        // the same name, though the chance of all 45 letters keeping their case is negligible
+        assert_eq!(random, name);
+        assert!(!random.eq_case(&name));
+        assert!(random.to_lowercase().eq_case(&name));
+        assert_eq!(random.is_fqdn(), name.is_fqdn());
+    }
 }
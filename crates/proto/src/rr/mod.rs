@@ -26,6 +26,7 @@ pub mod record_data;
 pub mod record_type;
 pub mod resource;
 mod rr_set;
+pub mod serial;
 
 pub use self::dns_class::DNSClass;
 pub use self::domain::{IntoName, Name, TryParseIp};
@@ -36,3 +37,4 @@ pub use self::resource::Record;
 pub use self::rr_set::IntoRecordSet;
 pub use self::rr_set::RecordSet;
 pub use self::rr_set::RrsetRecords;
+pub use self::serial::{serial_gt, Serial};
@@ -17,6 +17,9 @@
 //! option record for passing protocol options between the client and server
 
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
 use log::warn;
 
@@ -335,6 +338,9 @@ pub enum EdnsCode {
     /// [RFC 7901, CHAIN Query Requests in DNS, Optional](https://tools.ietf.org/html/rfc7901)
     Chain,
 
+    /// [RFC 8914, Extended DNS Errors](https://tools.ietf.org/html/rfc8914)
+    ExtendedError,
+
     /// Unknown, used to deal with unknown or unsupported codes
     Unknown(u16),
 }
@@ -357,6 +363,7 @@ impl From<u16> for EdnsCode {
             11 => EdnsCode::Keepalive,
             12 => EdnsCode::Padding,
             13 => EdnsCode::Chain,
+            15 => EdnsCode::ExtendedError,
             _ => EdnsCode::Unknown(value),
         }
     }
@@ -379,6 +386,7 @@ impl From<EdnsCode> for u16 {
             EdnsCode::Keepalive => 11,
             EdnsCode::Padding => 12,
             EdnsCode::Chain => 13,
+            EdnsCode::ExtendedError => 15,
             EdnsCode::Unknown(value) => value,
         }
     }
@@ -403,6 +411,23 @@ pub enum EdnsOption {
     #[cfg(feature = "dnssec")]
     N3U(SupportedAlgorithms),
 
+    /// [RFC 7871, Client Subnet, Optional](https://tools.ietf.org/html/rfc7871)
+    Subnet(ClientSubnet),
+
+    /// [RFC 7873, DNS Cookies](https://tools.ietf.org/html/rfc7873)
+    Cookie(Cookie),
+
+    /// [RFC 8914, Extended DNS Errors](https://tools.ietf.org/html/rfc8914)
+    ExtendedError(ExtendedError),
+
+    /// [RFC 7828, edns-tcp-keepalive](https://tools.ietf.org/html/rfc7828), the idle timeout of
+    ///  TCP connections in units of 100 milliseconds, which is absent in queries
+    Keepalive(Option<u16>),
+
+    /// [RFC 7830, The EDNS(0) Padding](https://tools.ietf.org/html/rfc7830), the number of octets
+    ///  of padding, which are all zero
+    Padding(u16),
+
     /// Unknown, used to deal with unknown or unsupported codes
     Unknown(u16, Vec<u8>),
 }
@@ -415,6 +440,11 @@ impl EdnsOption {
             EdnsOption::DAU(ref algorithms)
             | EdnsOption::DHU(ref algorithms)
             | EdnsOption::N3U(ref algorithms) => algorithms.len(),
+            EdnsOption::Subnet(ref subnet) => subnet.len(),
+            EdnsOption::Cookie(ref cookie) => cookie.len(),
+            EdnsOption::ExtendedError(ref error) => error.len(),
+            EdnsOption::Keepalive(timeout) => timeout.map_or(0, |_| 2),
+            EdnsOption::Padding(len) => len,
             EdnsOption::Unknown(_, ref data) => data.len() as u16, // TODO: should we verify?
         }
     }
@@ -426,6 +456,11 @@ impl EdnsOption {
             EdnsOption::DAU(ref algorithms)
             | EdnsOption::DHU(ref algorithms)
             | EdnsOption::N3U(ref algorithms) => algorithms.is_empty(),
+            EdnsOption::Subnet(..)
+            | EdnsOption::Cookie(..)
+            | EdnsOption::ExtendedError(..) => false,
+            EdnsOption::Keepalive(timeout) => timeout.is_none(),
+            EdnsOption::Padding(len) => len == 0,
             EdnsOption::Unknown(_, ref data) => data.is_empty(),
         }
     }
@@ -438,6 +473,12 @@ impl BinEncodable for EdnsOption {
             EdnsOption::DAU(ref algorithms)
             | EdnsOption::DHU(ref algorithms)
             | EdnsOption::N3U(ref algorithms) => algorithms.emit(encoder),
+            EdnsOption::Subnet(ref subnet) => subnet.emit(encoder),
+            EdnsOption::Cookie(ref cookie) => cookie.emit(encoder),
+            EdnsOption::ExtendedError(ref error) => error.emit(encoder),
+            EdnsOption::Keepalive(Some(timeout)) => encoder.emit_u16(timeout),
+            EdnsOption::Keepalive(None) => Ok(()),
+            EdnsOption::Padding(len) => encoder.emit_vec(&vec![0; len as usize]),
             EdnsOption::Unknown(_, ref data) => encoder.emit_vec(data), // gah, clone needed or make a crazy api.
         }
     }
@@ -454,6 +495,34 @@ impl<'a> From<(EdnsCode, &'a [u8])> for EdnsOption {
             EdnsCode::DHU => EdnsOption::DHU(value.1.into()),
             #[cfg(feature = "dnssec")]
             EdnsCode::N3U => EdnsOption::N3U(value.1.into()),
+            EdnsCode::Subnet => ClientSubnet::try_from(value.1)
+                .map(EdnsOption::Subnet)
+                .unwrap_or_else(|e| {
+                    warn!("ignoring malformed client subnet option: {}", e);
+                    EdnsOption::Unknown(value.0.into(), value.1.to_vec())
+                }),
+            EdnsCode::Cookie => Cookie::try_from(value.1)
+                .map(EdnsOption::Cookie)
+                .unwrap_or_else(|e| {
+                    warn!("ignoring malformed cookie option: {}", e);
+                    EdnsOption::Unknown(value.0.into(), value.1.to_vec())
+                }),
+            EdnsCode::ExtendedError => ExtendedError::try_from(value.1)
+                .map(EdnsOption::ExtendedError)
+                .unwrap_or_else(|e| {
+                    warn!("ignoring malformed extended error option: {}", e);
+                    EdnsOption::Unknown(value.0.into(), value.1.to_vec())
+                }),
+            EdnsCode::Keepalive => match *value.1 {
+                [] => EdnsOption::Keepalive(None),
+                [high, low] => EdnsOption::Keepalive(Some(u16::from_be_bytes([high, low]))),
+                _ => {
+                    warn!("ignoring malformed keepalive option of {} bytes", value.1.len());
+                    EdnsOption::Unknown(value.0.into(), value.1.to_vec())
+                }
+            },
+            // the contents of the padding are ignored, only its length is kept
+            EdnsCode::Padding => EdnsOption::Padding(value.1.len() as u16),
             _ => EdnsOption::Unknown(value.0.into(), value.1.to_vec()),
         }
     }
@@ -466,6 +535,34 @@ impl<'a> From<&'a EdnsOption> for Vec<u8> {
             EdnsOption::DAU(ref algorithms)
             | EdnsOption::DHU(ref algorithms)
             | EdnsOption::N3U(ref algorithms) => algorithms.into(),
+            EdnsOption::Subnet(ref subnet) => {
+                let mut bytes = Vec::with_capacity(subnet.len() as usize);
+                let mut encoder = BinEncoder::new(&mut bytes);
+                subnet
+                    .emit(&mut encoder)
+                    .expect("encoding to a Vec does not fail");
+                bytes
+            }
+            EdnsOption::Cookie(ref cookie) => {
+                let mut bytes = Vec::with_capacity(cookie.len() as usize);
+                let mut encoder = BinEncoder::new(&mut bytes);
+                cookie
+                    .emit(&mut encoder)
+                    .expect("encoding to a Vec does not fail");
+                bytes
+            }
+            EdnsOption::ExtendedError(ref error) => {
+                let mut bytes = Vec::with_capacity(error.len() as usize);
+                let mut encoder = BinEncoder::new(&mut bytes);
+                error
+                    .emit(&mut encoder)
+                    .expect("encoding to a Vec does not fail");
+                bytes
+            }
+            EdnsOption::Keepalive(timeout) => timeout
+                .map(|timeout| timeout.to_be_bytes().to_vec())
+                .unwrap_or_default(),
+            EdnsOption::Padding(len) => vec![0; len as usize],
             EdnsOption::Unknown(_, ref data) => data.clone(), // gah, clone needed or make a crazy api.
         }
     }
@@ -480,11 +577,387 @@ impl<'a> From<&'a EdnsOption> for EdnsCode {
             EdnsOption::DHU(..) => EdnsCode::DHU,
             #[cfg(feature = "dnssec")]
             EdnsOption::N3U(..) => EdnsCode::N3U,
+            EdnsOption::Subnet(..) => EdnsCode::Subnet,
+            EdnsOption::Cookie(..) => EdnsCode::Cookie,
+            EdnsOption::ExtendedError(..) => EdnsCode::ExtendedError,
+            EdnsOption::Keepalive(..) => EdnsCode::Keepalive,
+            EdnsOption::Padding(..) => EdnsCode::Padding,
             EdnsOption::Unknown(code, _) => code.into(),
         }
     }
 }
 
+/// The client subnet on whose behalf a query is made, for geographically aware answers
+///
+/// [RFC 7871, Client Subnet in DNS Queries, May 2016](https://tools.ietf.org/html/rfc7871#section-6)
+///
+/// ```text
+/// 6.  Option Format
+///
+///                 +0 (MSB)                            +1 (LSB)
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    0: |                          OPTION-CODE                          |
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    2: |                         OPTION-LENGTH                         |
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    4: |                            FAMILY                             |
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    6: |     SOURCE PREFIX-LENGTH      |     SCOPE PREFIX-LENGTH       |
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    8: |                           ADDRESS...                          /
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///
+///    o  ADDRESS, variable number of octets, contains either an IPv4 or
+///       IPv6 address, depending on FAMILY, which MUST be truncated to the
+///       number of bits indicated by the SOURCE PREFIX-LENGTH field,
+///       padding with 0 bits to pad to the end of the last octet needed.
+/// ```
+#[derive(Debug, PartialOrd, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct ClientSubnet {
+    address: IpAddr,
+    source_prefix: u8,
+    scope_prefix: u8,
+}
+
+impl ClientSubnet {
+    /// Creates a new client subnet, the address is truncated to the `source_prefix`
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - the address of the client, only the first `source_prefix` bits are sent
+    /// * `source_prefix` - the number of significant bits of `address`, at most 32 for IPv4 and
+    ///                     128 for IPv6
+    /// * `scope_prefix` - in queries this must be 0, in responses the number of bits the answer
+    ///                    covers
+    pub fn new(address: IpAddr, source_prefix: u8, scope_prefix: u8) -> Self {
+        let (address, source_prefix) = match address {
+            IpAddr::V4(ip) => {
+                let prefix = source_prefix.min(32);
+                let mask = u32::max_value()
+                    .checked_shl(u32::from(32 - prefix))
+                    .unwrap_or(0);
+                (IpAddr::V4(Ipv4Addr::from(u32::from(ip) & mask)), prefix)
+            }
+            IpAddr::V6(ip) => {
+                let prefix = source_prefix.min(128);
+                let mask = u128::max_value()
+                    .checked_shl(u32::from(128 - prefix))
+                    .unwrap_or(0);
+                (IpAddr::V6(Ipv6Addr::from(u128::from(ip) & mask)), prefix)
+            }
+        };
+
+        ClientSubnet {
+            address,
+            source_prefix,
+            scope_prefix,
+        }
+    }
+
+    /// The address of the client, truncated to the source prefix
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    /// The number of significant bits of the address
+    pub fn source_prefix(&self) -> u8 {
+        self.source_prefix
+    }
+
+    /// The number of bits of the address which the answer covers, this is 0 in queries
+    pub fn scope_prefix(&self) -> u8 {
+        self.scope_prefix
+    }
+
+    fn family(&self) -> u16 {
+        match self.address {
+            IpAddr::V4(..) => 1,
+            IpAddr::V6(..) => 2,
+        }
+    }
+
+    /// The number of address octets sent
+    fn address_len(&self) -> usize {
+        (self.source_prefix as usize + 7) / 8
+    }
+
+    /// Returns the length in bytes of the option data
+    fn len(&self) -> u16 {
+        4 + self.address_len() as u16
+    }
+}
+
+impl BinEncodable for ClientSubnet {
+    fn emit(&self, encoder: &mut BinEncoder<'_>) -> ProtoResult<()> {
+        encoder.emit_u16(self.family())?;
+        encoder.emit(self.source_prefix)?;
+        encoder.emit(self.scope_prefix)?;
+
+        let len = self.address_len();
+        match self.address {
+            IpAddr::V4(ip) => encoder.emit_vec(&ip.octets()[..len]),
+            IpAddr::V6(ip) => encoder.emit_vec(&ip.octets()[..len]),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for ClientSubnet {
+    type Error = ProtoError;
+
+    fn try_from(data: &'a [u8]) -> ProtoResult<Self> {
+        if data.len() < 4 {
+            return Err("client subnet option is too short".into());
+        }
+
+        let family = u16::from_be_bytes([data[0], data[1]]);
+        let source_prefix = data[2];
+        let scope_prefix = data[3];
+        let address = &data[4..];
+
+        if address.len() != (source_prefix as usize + 7) / 8 {
+            return Err("client subnet address does not match the source prefix".into());
+        }
+
+        let address = match family {
+            1 if address.len() <= 4 => {
+                let mut octets = [0_u8; 4];
+                octets[..address.len()].copy_from_slice(address);
+                IpAddr::V4(Ipv4Addr::from(octets))
+            }
+            2 if address.len() <= 16 => {
+                let mut octets = [0_u8; 16];
+                octets[..address.len()].copy_from_slice(address);
+                IpAddr::V6(Ipv6Addr::from(octets))
+            }
+            _ => return Err(format!("unsupported client subnet family: {}", family).into()),
+        };
+
+        Ok(ClientSubnet::new(address, source_prefix, scope_prefix))
+    }
+}
+
+/// A DNS Cookie, a lightweight protection against off-path spoofing and amplification
+///
+/// [RFC 7873, Domain Name System (DNS) Cookies, May 2016](https://tools.ietf.org/html/rfc7873#section-4)
+///
+/// The client cookie is always 8 bytes. The server cookie, between 8 and 32 bytes, is absent
+///  until the client has received one from the server, after which it is echoed back.
+#[derive(Debug, PartialOrd, PartialEq, Eq, Clone, Hash)]
+pub struct Cookie {
+    client: [u8; 8],
+    server: Option<Vec<u8>>,
+}
+
+impl Cookie {
+    /// The shortest server cookie which may be sent
+    pub const MIN_SERVER_LEN: usize = 8;
+    /// The longest server cookie which may be sent
+    pub const MAX_SERVER_LEN: usize = 32;
+
+    /// Creates a new cookie
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - the client cookie, this should be the same for all requests to a server
+    /// * `server` - the server cookie last received from the server, if any
+    pub fn new(client: [u8; 8], server: Option<Vec<u8>>) -> Self {
+        Cookie { client, server }
+    }
+
+    /// The client cookie
+    pub fn client(&self) -> &[u8; 8] {
+        &self.client
+    }
+
+    /// The server cookie, this is None in the first request to a server
+    pub fn server(&self) -> Option<&[u8]> {
+        self.server.as_deref()
+    }
+
+    /// Returns the length in bytes of the option data
+    fn len(&self) -> u16 {
+        (self.client.len() + self.server.as_ref().map_or(0, Vec::len)) as u16
+    }
+}
+
+impl BinEncodable for Cookie {
+    fn emit(&self, encoder: &mut BinEncoder<'_>) -> ProtoResult<()> {
+        encoder.emit_vec(&self.client)?;
+        if let Some(ref server) = self.server {
+            encoder.emit_vec(server)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for Cookie {
+    type Error = ProtoError;
+
+    fn try_from(data: &'a [u8]) -> ProtoResult<Self> {
+        if data.len() < 8 {
+            return Err("cookie option is too short".into());
+        }
+
+        let (client_bytes, server) = data.split_at(8);
+        let server = match server.len() {
+            0 => None,
+            len if (Self::MIN_SERVER_LEN..=Self::MAX_SERVER_LEN).contains(&len) => {
+                Some(server.to_vec())
+            }
+            len => return Err(format!("bad server cookie length: {}", len).into()),
+        };
+
+        let mut client = [0_u8; 8];
+        client.copy_from_slice(client_bytes);
+        Ok(Cookie { client, server })
+    }
+}
+
+/// An Extended DNS Error, explaining why a response failed or was otherwise unexpected
+///
+/// [RFC 8914, Extended DNS Errors, October 2020](https://tools.ietf.org/html/rfc8914#section-2)
+///
+/// ```text
+/// 2.  Extended DNS Error EDNS0 Option Format
+///
+///                                                 1   1   1   1   1   1
+///         0   1   2   3   4   5   6   7   8   9   0   1   2   3   4   5
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    0: |                            OPTION-CODE                        |
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    2: |                           OPTION-LENGTH                       |
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    4: | INFO-CODE                                                     |
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    6: / EXTRA-TEXT ...                                                /
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+/// ```
+#[derive(Debug, PartialOrd, PartialEq, Eq, Clone, Hash)]
+pub struct ExtendedError {
+    info_code: u16,
+    extra_text: String,
+}
+
+impl ExtendedError {
+    /// Other, the error is explained by the extra text
+    pub const OTHER: u16 = 0;
+    /// The answer was produced from a stale cache
+    pub const STALE_ANSWER: u16 = 3;
+    /// DNSSEC validation failed
+    pub const DNSSEC_BOGUS: u16 = 6;
+    /// The request was prohibited by the policy of the server
+    pub const PROHIBITED: u16 = 18;
+    /// The server is not authoritative for the zone, and does not recurse
+    pub const NOT_AUTHORITATIVE: u16 = 20;
+    /// The requested operation or query is not supported
+    pub const NOT_SUPPORTED: u16 = 21;
+    /// None of the authoritative name servers could be reached
+    pub const NO_REACHABLE_AUTHORITY: u16 = 22;
+    /// An unrecoverable error occurred while communicating with another server
+    pub const NETWORK_ERROR: u16 = 23;
+
+    /// Creates a new extended error
+    ///
+    /// # Arguments
+    ///
+    /// * `info_code` - the INFO-CODE of the error, as registered with IANA
+    /// * `extra_text` - text for a human, may be empty
+    pub fn new(info_code: u16, extra_text: impl Into<String>) -> Self {
+        ExtendedError {
+            info_code,
+            extra_text: extra_text.into(),
+        }
+    }
+
+    /// The INFO-CODE of the error
+    pub fn info_code(&self) -> u16 {
+        self.info_code
+    }
+
+    /// Additional text explaining the error, this is meant for humans and may be empty
+    pub fn extra_text(&self) -> &str {
+        &self.extra_text
+    }
+
+    /// The purpose of the INFO-CODE as registered with IANA, if it is known
+    pub fn purpose(&self) -> Option<&'static str> {
+        let purpose = match self.info_code {
+            0 => "Other",
+            1 => "Unsupported DNSKEY Algorithm",
+            2 => "Unsupported DS Digest Type",
+            3 => "Stale Answer",
+            4 => "Forged Answer",
+            5 => "DNSSEC Indeterminate",
+            6 => "DNSSEC Bogus",
+            7 => "Signature Expired",
+            8 => "Signature Not Yet Valid",
+            9 => "DNSKEY Missing",
+            10 => "RRSIGs Missing",
+            11 => "No Zone Key Bit Set",
+            12 => "NSEC Missing",
+            13 => "Cached Error",
+            14 => "Not Ready",
+            15 => "Blocked",
+            16 => "Censored",
+            17 => "Filtered",
+            18 => "Prohibited",
+            19 => "Stale NXDOMAIN Answer",
+            20 => "Not Authoritative",
+            21 => "Not Supported",
+            22 => "No Reachable Authority",
+            23 => "Network Error",
+            24 => "Invalid Data",
+            _ => return None,
+        };
+
+        Some(purpose)
+    }
+
+    /// Returns the length in bytes of the option data
+    fn len(&self) -> u16 {
+        (2 + self.extra_text.len()) as u16
+    }
+}
+
+impl BinEncodable for ExtendedError {
+    fn emit(&self, encoder: &mut BinEncoder<'_>) -> ProtoResult<()> {
+        encoder.emit_u16(self.info_code)?;
+        encoder.emit_vec(self.extra_text.as_bytes())
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for ExtendedError {
+    type Error = ProtoError;
+
+    fn try_from(data: &'a [u8]) -> ProtoResult<Self> {
+        if data.len() < 2 {
+            return Err("extended error option is too short".into());
+        }
+
+        let (info_code, extra_text) = data.split_at(2);
+        let info_code = u16::from_be_bytes([info_code[0], info_code[1]]);
+
+        // the text is not null terminated, though some implementations have sent one
+        let extra_text = std::str::from_utf8(extra_text)?.trim_end_matches('\0');
+        Ok(ExtendedError::new(info_code, extra_text))
+    }
+}
+
+impl fmt::Display for ExtendedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self.purpose() {
+            Some(purpose) => write!(f, "{} ({})", purpose, self.info_code)?,
+            None => write!(f, "extended error {}", self.info_code)?,
+        }
+
+        if !self.extra_text.is_empty() {
+            write!(f, ": {}", self.extra_text)?;
+        }
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #![allow(clippy::dbg_macro, clippy::print_stdout)]
@@ -510,6 +983,134 @@ mod tests {
         let read_rdata = read(&mut decoder, restrict).expect("Decoding error");
         assert_eq!(rdata, read_rdata);
     }
+
+    #[test]
+    pub fn test_client_subnet() {
+        use super::*;
+
+        let subnet = ClientSubnet::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 77)), 24, 0);
+        assert_eq!(subnet.address(), IpAddr::V4(Ipv4Addr::new(192, 0, 2, 0)));
+        assert_eq!(subnet.len(), 7);
+
+        let mut rdata = OPT::default();
+        rdata.insert(EdnsOption::Subnet(subnet));
+
+        let mut bytes = Vec::new();
+        let mut encoder: BinEncoder<'_> = BinEncoder::new(&mut bytes);
+        assert!(emit(&mut encoder, &rdata).is_ok());
+        let bytes = encoder.into_bytes();
+        assert_eq!(bytes, &[0, 8, 0, 7, 0, 1, 24, 0, 192, 0, 2]);
+
+        let mut decoder: BinDecoder<'_> = BinDecoder::new(bytes);
+        let restrict = Restrict::new(bytes.len() as u16);
+        let read_rdata = read(&mut decoder, restrict).expect("Decoding error");
+        assert_eq!(rdata, read_rdata);
+
+        let address = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0x80ff, 0, 0, 0, 0, 1));
+        let subnet = ClientSubnet::new(address, 33, 0);
+        assert_eq!(
+            subnet.address(),
+            IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0x8000, 0, 0, 0, 0, 0))
+        );
+
+        let bytes = Vec::<u8>::from(&EdnsOption::Subnet(subnet));
+        assert_eq!(bytes.len(), 9);
+        assert_eq!(ClientSubnet::try_from(&bytes as &[u8]).unwrap(), subnet);
+    }
+
+    #[test]
+    pub fn test_cookie() {
+        use super::*;
+
+        let client = [1, 2, 3, 4, 5, 6, 7, 8];
+        let cookie = Cookie::new(client, None);
+        assert_eq!(cookie.len(), 8);
+        let bytes = Vec::<u8>::from(&EdnsOption::Cookie(cookie));
+        assert_eq!(bytes, client.to_vec());
+
+        let mut rdata = OPT::default();
+        rdata.insert(EdnsOption::Cookie(Cookie::new(client, Some(vec![9; 16]))));
+
+        let mut bytes = Vec::new();
+        let mut encoder: BinEncoder<'_> = BinEncoder::new(&mut bytes);
+        assert!(emit(&mut encoder, &rdata).is_ok());
+        let bytes = encoder.into_bytes();
+        assert_eq!(&bytes[..4], &[0, 10, 0, 24]);
+
+        let mut decoder: BinDecoder<'_> = BinDecoder::new(bytes);
+        let restrict = Restrict::new(bytes.len() as u16);
+        let read_rdata = read(&mut decoder, restrict).expect("Decoding error");
+        assert_eq!(rdata, read_rdata);
+
+        // server cookies are between 8 and 32 bytes
+        assert!(Cookie::try_from(&[0_u8; 7] as &[u8]).is_err());
+        assert!(Cookie::try_from(&[0_u8; 12] as &[u8]).is_err());
+        assert!(Cookie::try_from(&[0_u8; 41] as &[u8]).is_err());
+        assert!(Cookie::try_from(&[0_u8; 40] as &[u8]).is_ok());
+    }
+
+    #[test]
+    pub fn test_extended_error() {
+        use super::*;
+
+        let error = ExtendedError::new(ExtendedError::NETWORK_ERROR, "timed out");
+        assert_eq!(error.len(), 11);
+        assert_eq!(error.to_string(), "Network Error (23): timed out");
+
+        let mut rdata = OPT::default();
+        rdata.insert(EdnsOption::ExtendedError(error.clone()));
+
+        let mut bytes = Vec::new();
+        let mut encoder: BinEncoder<'_> = BinEncoder::new(&mut bytes);
+        assert!(emit(&mut encoder, &rdata).is_ok());
+        let bytes = encoder.into_bytes();
+        assert_eq!(&bytes[..6], &[0, 15, 0, 11, 0, 23]);
+        assert_eq!(&bytes[6..], b"timed out");
+
+        let mut decoder: BinDecoder<'_> = BinDecoder::new(bytes);
+        let restrict = Restrict::new(bytes.len() as u16);
+        let read_rdata = read(&mut decoder, restrict).expect("Decoding error");
+        assert_eq!(
+            read_rdata.get(EdnsCode::ExtendedError),
+            Some(&EdnsOption::ExtendedError(error))
+        );
+
+        let error = ExtendedError::try_from(&[0, 6] as &[u8]).unwrap();
+        assert_eq!(error.info_code(), ExtendedError::DNSSEC_BOGUS);
+        assert_eq!(error.extra_text(), "");
+        assert_eq!(error.to_string(), "DNSSEC Bogus (6)");
+
+        assert!(ExtendedError::try_from(&[0_u8] as &[u8]).is_err());
+        assert!(ExtendedError::try_from(&[0, 0, 0xff] as &[u8]).is_err());
+    }
+
+    #[test]
+    pub fn test_keepalive() {
+        use super::*;
+
+        let mut rdata = OPT::default();
+        rdata.insert(EdnsOption::Keepalive(Some(600)));
+
+        let mut bytes = Vec::new();
+        let mut encoder: BinEncoder<'_> = BinEncoder::new(&mut bytes);
+        assert!(emit(&mut encoder, &rdata).is_ok());
+        let bytes = encoder.into_bytes();
+        assert_eq!(bytes, &[0, 11, 0, 2, 0x02, 0x58]);
+
+        let mut decoder: BinDecoder<'_> = BinDecoder::new(bytes);
+        let restrict = Restrict::new(bytes.len() as u16);
+        let read_rdata = read(&mut decoder, restrict).expect("Decoding error");
+        assert_eq!(rdata, read_rdata);
+
+        // queries carry the option without a timeout
+        let option = EdnsOption::from((EdnsCode::Keepalive, &[] as &[u8]));
+        assert_eq!(option, EdnsOption::Keepalive(None));
+        assert!(option.is_empty());
+        assert!(Vec::<u8>::from(&option).is_empty());
+
+        let option = EdnsOption::from((EdnsCode::Keepalive, &[0_u8] as &[u8]));
+        assert_eq!(option, EdnsOption::Unknown(11, vec![0]));
+    }
 }
 
 #[test]
@@ -528,12 +1129,18 @@ pub fn test_read_empty_option_at_end_of_opt() {
 
     let opt = read_rdata.unwrap();
     let mut options = HashMap::default();
-    options.insert(EdnsCode::Subnet, EdnsOption::Unknown(8, vec![0, 1, 0, 0]));
+    options.insert(
+        EdnsCode::Subnet,
+        EdnsOption::Subnet(ClientSubnet::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0, 0)),
+    );
     options.insert(
         EdnsCode::Cookie,
-        EdnsOption::Unknown(10, vec![0x0b, 0x64, 0xb4, 0xdc, 0xd7, 0xb0, 0xcc, 0x8f]),
+        EdnsOption::Cookie(Cookie::new(
+            [0x0b, 0x64, 0xb4, 0xdc, 0xd7, 0xb0, 0xcc, 0x8f],
+            None,
+        )),
     );
-    options.insert(EdnsCode::Keepalive, EdnsOption::Unknown(11, vec![]));
+    options.insert(EdnsCode::Keepalive, EdnsOption::Keepalive(None));
     let options = OPT::new(options);
     assert_eq!(opt, options);
 }
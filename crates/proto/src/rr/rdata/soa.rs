@@ -114,8 +114,17 @@ impl SOA {
     }
 
     /// Increments the serial number by one
+    ///
+    /// The serial wraps around to 0 after `u32::MAX`, as in serial number arithmetic,
+    ///  [RFC 1982](https://tools.ietf.org/html/rfc1982)
     pub fn increment_serial(&mut self) {
-        self.serial += 1; // TODO: what to do on overflow?
+        self.serial = self.serial.wrapping_add(1);
+    }
+
+    /// Sets the serial number, this should be greater than the current serial in serial number
+    ///  arithmetic for secondaries to pick up the change
+    pub fn set_serial(&mut self, serial: u32) {
+        self.serial = serial;
     }
 
     /// ```text
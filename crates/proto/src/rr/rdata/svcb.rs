@@ -0,0 +1,599 @@
+// Copyright 2015-2020 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! SVCB and HTTPS records for service binding, the alternative endpoints of a service
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use super::{a, aaaa};
+
+use crate::error::*;
+use crate::rr::domain::Name;
+use crate::serialize::binary::*;
+
+/// [draft-ietf-dnsop-svcb-https](https://tools.ietf.org/html/draft-ietf-dnsop-svcb-https-02#section-2.2), Service binding and parameter specification via the DNS, November 2020
+///
+/// The HTTPS record, type 65, has the same format as SVCB, type 64.
+///
+/// ```text
+/// 2.2.  RDATA wire format
+///
+///    The RDATA for the SVCB RR consists of:
+///
+///    *  a 2 octet field for SvcPriority as an integer in network byte
+///       order.
+///    *  the uncompressed, fully-qualified TargetName, represented as a
+///       sequence of length-prefixed labels as in Section 3.1 of [RFC1035].
+///    *  the SvcParams, consuming the remainder of the record (so smaller
+///       than 65535 octets and constrained by the RDATA and DNS message
+///       sizes).
+///
+///    When the list of SvcParams is non-empty (ServiceMode), it contains a
+///    series of SvcParamKey=SvcParamValue pairs, represented as:
+///
+///    *  a 2 octet field containing the SvcParamKey as an integer in
+///       network byte order.
+///    *  a 2 octet field containing the length of the SvcParamValue as an
+///       integer between 0 and 65535 in network byte order (but constrained
+///       by the RDATA and DNS message sizes).
+///    *  an octet string of this length whose contents are in a format
+///       determined by the SvcParamKey.
+///
+///    SvcParamKeys SHALL appear in increasing numeric order.
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct SVCB {
+    svc_priority: u16,
+    target_name: Name,
+    svc_params: Vec<(SvcParamKey, SvcParamValue)>,
+}
+
+impl SVCB {
+    /// Constructs a new SVCB, or HTTPS, record
+    ///
+    /// # Arguments
+    ///
+    /// * `svc_priority` - 0 for AliasMode, otherwise the priority of the ServiceMode endpoint
+    /// * `target_name` - the name of the alias, or of the endpoint
+    /// * `svc_params` - the parameters of the endpoint, these are sorted by key
+    pub fn new(
+        svc_priority: u16,
+        target_name: Name,
+        mut svc_params: Vec<(SvcParamKey, SvcParamValue)>,
+    ) -> Self {
+        svc_params.sort_by_key(|(key, _)| u16::from(*key));
+
+        SVCB {
+            svc_priority,
+            target_name,
+            svc_params,
+        }
+    }
+
+    /// The priority of the endpoint, lower values are preferred, 0 is AliasMode
+    pub fn svc_priority(&self) -> u16 {
+        self.svc_priority
+    }
+
+    /// Returns true if this is an alias, in AliasMode, to the target name
+    pub fn is_alias(&self) -> bool {
+        self.svc_priority == 0
+    }
+
+    /// The name of the alias, or of the endpoint, the root means the owner name of the record
+    pub fn target_name(&self) -> &Name {
+        &self.target_name
+    }
+
+    /// All of the parameters of the endpoint, in increasing order of the key
+    pub fn svc_params(&self) -> &[(SvcParamKey, SvcParamValue)] {
+        &self.svc_params
+    }
+
+    /// The value of the parameter with the key
+    pub fn svc_param(&self, key: SvcParamKey) -> Option<&SvcParamValue> {
+        self.svc_params
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, value)| value)
+    }
+
+    /// The application protocols supported by the endpoint, e.g. `h2`
+    pub fn alpn(&self) -> Option<&[String]> {
+        match self.svc_param(SvcParamKey::Alpn) {
+            Some(SvcParamValue::Alpn(alpn)) => Some(alpn),
+            _ => None,
+        }
+    }
+
+    /// The port of the endpoint, if not the default for the protocol
+    pub fn port(&self) -> Option<u16> {
+        match self.svc_param(SvcParamKey::Port) {
+            Some(SvcParamValue::Port(port)) => Some(*port),
+            _ => None,
+        }
+    }
+
+    /// The IPv4 addresses of the endpoint, to use before those of the target name are resolved
+    pub fn ipv4hint(&self) -> Option<&[Ipv4Addr]> {
+        match self.svc_param(SvcParamKey::Ipv4Hint) {
+            Some(SvcParamValue::Ipv4Hint(hints)) => Some(hints),
+            _ => None,
+        }
+    }
+
+    /// The ECHConfigList of the endpoint, for Encrypted Client Hello
+    pub fn ech(&self) -> Option<&[u8]> {
+        match self.svc_param(SvcParamKey::Ech) {
+            Some(SvcParamValue::Ech(ech)) => Some(ech),
+            _ => None,
+        }
+    }
+
+    /// The IPv6 addresses of the endpoint, to use before those of the target name are resolved
+    pub fn ipv6hint(&self) -> Option<&[Ipv6Addr]> {
+        match self.svc_param(SvcParamKey::Ipv6Hint) {
+            Some(SvcParamValue::Ipv6Hint(hints)) => Some(hints),
+            _ => None,
+        }
+    }
+}
+
+/// The key of a parameter of the endpoint
+///
+/// ```text
+/// 14.3.2.  Initial contents
+///
+///    +-------------+-----------------+----------------+
+///    | Number      | Name            | Meaning        |
+///    +=============+=================+================+
+///    | 0           | mandatory       | Mandatory keys |
+///    |             |                 | in this RR     |
+///    | 1           | alpn            | Additional     |
+///    |             |                 | supported      |
+///    |             |                 | protocols      |
+///    | 2           | no-default-alpn | No support for |
+///    |             |                 | default        |
+///    |             |                 | protocol       |
+///    | 3           | port            | Port for       |
+///    |             |                 | alternative    |
+///    |             |                 | endpoint       |
+///    | 4           | ipv4hint        | IPv4 address   |
+///    |             |                 | hints          |
+///    | 5           | echconfig       | Encrypted      |
+///    |             |                 | ClientHello    |
+///    |             |                 | info           |
+///    | 6           | ipv6hint        | IPv6 address   |
+///    |             |                 | hints          |
+///    | 65280-65534 | keyNNNNN        | Private Use    |
+///    | 65535       | key65535        | Reserved       |
+///    |             |                 | ("Invalid      |
+///    |             |                 | key")          |
+///    +-------------+-----------------+----------------+
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum SvcParamKey {
+    /// The keys which must be understood to use the endpoint
+    Mandatory,
+    /// The application protocols of the endpoint, in addition to the default for the record
+    Alpn,
+    /// The default protocol for the record is not supported by the endpoint
+    NoDefaultAlpn,
+    /// The port of the endpoint
+    Port,
+    /// IPv4 address hints
+    Ipv4Hint,
+    /// The configuration for Encrypted Client Hello
+    Ech,
+    /// IPv6 address hints
+    Ipv6Hint,
+    /// Any other key, `keyNNNNN` in the presentation format
+    Key(u16),
+}
+
+impl From<u16> for SvcParamKey {
+    fn from(value: u16) -> Self {
+        match value {
+            0 => SvcParamKey::Mandatory,
+            1 => SvcParamKey::Alpn,
+            2 => SvcParamKey::NoDefaultAlpn,
+            3 => SvcParamKey::Port,
+            4 => SvcParamKey::Ipv4Hint,
+            5 => SvcParamKey::Ech,
+            6 => SvcParamKey::Ipv6Hint,
+            _ => SvcParamKey::Key(value),
+        }
+    }
+}
+
+impl From<SvcParamKey> for u16 {
+    fn from(key: SvcParamKey) -> Self {
+        match key {
+            SvcParamKey::Mandatory => 0,
+            SvcParamKey::Alpn => 1,
+            SvcParamKey::NoDefaultAlpn => 2,
+            SvcParamKey::Port => 3,
+            SvcParamKey::Ipv4Hint => 4,
+            SvcParamKey::Ech => 5,
+            SvcParamKey::Ipv6Hint => 6,
+            SvcParamKey::Key(value) => value,
+        }
+    }
+}
+
+impl FromStr for SvcParamKey {
+    type Err = ProtoError;
+
+    fn from_str(s: &str) -> ProtoResult<Self> {
+        let key = match s {
+            "mandatory" => SvcParamKey::Mandatory,
+            "alpn" => SvcParamKey::Alpn,
+            "no-default-alpn" => SvcParamKey::NoDefaultAlpn,
+            "port" => SvcParamKey::Port,
+            "ipv4hint" => SvcParamKey::Ipv4Hint,
+            "ech" | "echconfig" => SvcParamKey::Ech,
+            "ipv6hint" => SvcParamKey::Ipv6Hint,
+            _ => {
+                let value = s
+                    .strip_prefix("key")
+                    .and_then(|value| u16::from_str(value).ok())
+                    .ok_or_else(|| ProtoError::from(format!("unknown SvcParamKey: {}", s)))?;
+                SvcParamKey::from(value)
+            }
+        };
+
+        Ok(key)
+    }
+}
+
+impl fmt::Display for SvcParamKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match *self {
+            SvcParamKey::Mandatory => f.write_str("mandatory"),
+            SvcParamKey::Alpn => f.write_str("alpn"),
+            SvcParamKey::NoDefaultAlpn => f.write_str("no-default-alpn"),
+            SvcParamKey::Port => f.write_str("port"),
+            SvcParamKey::Ipv4Hint => f.write_str("ipv4hint"),
+            SvcParamKey::Ech => f.write_str("ech"),
+            SvcParamKey::Ipv6Hint => f.write_str("ipv6hint"),
+            SvcParamKey::Key(value) => write!(f, "key{}", value),
+        }
+    }
+}
+
+/// The value of a parameter of the endpoint, the format is determined by the key
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum SvcParamValue {
+    /// The keys which must be understood to use the endpoint
+    Mandatory(Vec<SvcParamKey>),
+    /// The application protocol identifiers, [RFC 7301](https://tools.ietf.org/html/rfc7301)
+    Alpn(Vec<String>),
+    /// The default protocol is not supported, this has no value
+    NoDefaultAlpn,
+    /// The port of the endpoint
+    Port(u16),
+    /// IPv4 address hints
+    Ipv4Hint(Vec<Ipv4Addr>),
+    /// The ECHConfigList, which is base64 encoded in the presentation format
+    Ech(Vec<u8>),
+    /// IPv6 address hints
+    Ipv6Hint(Vec<Ipv6Addr>),
+    /// The value of any other key, as opaque data
+    Unknown(Vec<u8>),
+}
+
+impl SvcParamValue {
+    /// Reads the value of the key, the decoder must contain only the value
+    fn read(key: SvcParamKey, decoder: &mut BinDecoder<'_>) -> ProtoResult<Self> {
+        let value = match key {
+            SvcParamKey::Mandatory => {
+                let mut keys = Vec::new();
+                while !decoder.is_empty() {
+                    keys.push(SvcParamKey::from(
+                        decoder.read_u16()?.unverified(/*any u16 is a valid key*/),
+                    ));
+                }
+                SvcParamValue::Mandatory(keys)
+            }
+            SvcParamKey::Alpn => {
+                let mut alpn = Vec::new();
+                while !decoder.is_empty() {
+                    let id = decoder.read_character_data()?.unverified(/*any data is valid*/);
+                    alpn.push(String::from_utf8_lossy(id).into_owned());
+                }
+                SvcParamValue::Alpn(alpn)
+            }
+            SvcParamKey::NoDefaultAlpn => SvcParamValue::NoDefaultAlpn,
+            SvcParamKey::Port => {
+                SvcParamValue::Port(decoder.read_u16()?.unverified(/*any u16 is a valid port*/))
+            }
+            SvcParamKey::Ipv4Hint => {
+                let mut hints = Vec::new();
+                while !decoder.is_empty() {
+                    hints.push(a::read(decoder)?);
+                }
+                SvcParamValue::Ipv4Hint(hints)
+            }
+            SvcParamKey::Ech => {
+                let len = decoder.len();
+                SvcParamValue::Ech(decoder.read_vec(len)?.unverified(/*opaque data*/))
+            }
+            SvcParamKey::Ipv6Hint => {
+                let mut hints = Vec::new();
+                while !decoder.is_empty() {
+                    hints.push(aaaa::read(decoder)?);
+                }
+                SvcParamValue::Ipv6Hint(hints)
+            }
+            SvcParamKey::Key(_) => {
+                let len = decoder.len();
+                SvcParamValue::Unknown(decoder.read_vec(len)?.unverified(/*opaque data*/))
+            }
+        };
+
+        if !decoder.is_empty() {
+            return Err(format!("invalid value of SvcParamKey: {}", key).into());
+        }
+
+        Ok(value)
+    }
+
+    fn emit(&self, encoder: &mut BinEncoder<'_>) -> ProtoResult<()> {
+        match *self {
+            SvcParamValue::Mandatory(ref keys) => {
+                for key in keys {
+                    encoder.emit_u16((*key).into())?;
+                }
+            }
+            SvcParamValue::Alpn(ref alpn) => {
+                for id in alpn {
+                    encoder.emit_character_data(id)?;
+                }
+            }
+            SvcParamValue::NoDefaultAlpn => (),
+            SvcParamValue::Port(port) => encoder.emit_u16(port)?,
+            SvcParamValue::Ipv4Hint(ref hints) => {
+                for hint in hints {
+                    a::emit(encoder, *hint)?;
+                }
+            }
+            SvcParamValue::Ipv6Hint(ref hints) => {
+                for hint in hints {
+                    aaaa::emit(encoder, hint)?;
+                }
+            }
+            SvcParamValue::Ech(ref data) | SvcParamValue::Unknown(ref data) => {
+                encoder.emit_vec(data)?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for SvcParamValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        fn list<D: fmt::Display>(f: &mut fmt::Formatter<'_>, items: &[D]) -> fmt::Result {
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(",")?;
+                }
+                write!(f, "{}", item)?;
+            }
+            Ok(())
+        }
+
+        match *self {
+            SvcParamValue::Mandatory(ref keys) => list(f, keys),
+            SvcParamValue::Alpn(ref alpn) => list(f, alpn),
+            SvcParamValue::NoDefaultAlpn => Ok(()),
+            SvcParamValue::Port(port) => write!(f, "{}", port),
+            SvcParamValue::Ipv4Hint(ref hints) => list(f, hints),
+            SvcParamValue::Ech(ref ech) => f.write_str(&data_encoding::BASE64.encode(ech)),
+            SvcParamValue::Ipv6Hint(ref hints) => list(f, hints),
+            SvcParamValue::Unknown(ref data) => {
+                f.write_str("\"")?;
+                for b in data {
+                    match *b {
+                        b'"' | b'\\' => write!(f, "\\{}", *b as char)?,
+                        0x21..=0x7E => write!(f, "{}", *b as char)?,
+                        _ => write!(f, "\\{:03}", b)?,
+                    }
+                }
+                f.write_str("\"")
+            }
+        }
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder<'_>, rdata_length: Restrict<u16>) -> ProtoResult<SVCB> {
+    let start_idx = decoder.index();
+    let rdata_length =
+        rdata_length.map(|u| u as usize).unverified(/*rdata length usage is bounded*/);
+
+    let svc_priority = decoder.read_u16()?.unverified(/*any u16 is valid*/);
+    let target_name = Name::read(decoder)?;
+
+    let mut svc_params = Vec::new();
+    while rdata_length > decoder.index() - start_idx {
+        let key = SvcParamKey::from(decoder.read_u16()?.unverified(/*any u16 is valid*/));
+        let remaining = rdata_length.saturating_sub(decoder.index() - start_idx);
+        let len = decoder
+            .read_u16()?
+            .map(|u| u as usize)
+            .verify_unwrap(|len| *len + 2 <= remaining)
+            .map_err(|_| ProtoError::from("SvcParamValue length exceeds rdata length"))?;
+
+        let mut value = BinDecoder::new(decoder.read_slice(len)?.unverified(/*len verified*/));
+        svc_params.push((key, SvcParamValue::read(key, &mut value)?));
+    }
+
+    Ok(SVCB {
+        svc_priority,
+        target_name,
+        svc_params,
+    })
+}
+
+/// Write the RData from the given Decoder
+pub fn emit(encoder: &mut BinEncoder<'_>, svcb: &SVCB) -> ProtoResult<()> {
+    encoder.emit_u16(svcb.svc_priority)?;
+    // the target name is never compressed
+    svcb.target_name.emit_as_canonical(encoder, true)?;
+
+    for (key, value) in &svcb.svc_params {
+        encoder.emit_u16((*key).into())?;
+
+        let place = encoder.place::<u16>()?;
+        value.emit(encoder)?;
+        let len = encoder.len_since_place(&place);
+        if len > u16::max_value() as usize {
+            return Err(format!("SvcParamValue too long: {}", key).into());
+        }
+
+        place.replace(encoder, len as u16)?;
+    }
+
+    Ok(())
+}
+
+/// [draft-ietf-dnsop-svcb-https](https://tools.ietf.org/html/draft-ietf-dnsop-svcb-https-02#section-2.1), Service binding and parameter specification via the DNS, November 2020
+///
+/// ```text
+/// 2.1.  Zone file presentation format
+///
+///    The presentation format of the record is:
+///
+///    Name TTL IN SVCB SvcPriority TargetName SvcParams
+/// ```
+impl fmt::Display for SVCB {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "{priority} {target}",
+            priority = self.svc_priority,
+            target = self.target_name,
+        )?;
+
+        for (key, value) in &self.svc_params {
+            match value {
+                SvcParamValue::NoDefaultAlpn => write!(f, " {}", key)?,
+                value => write!(f, " {}={}", key, value)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::dbg_macro, clippy::print_stdout)]
+
+    use super::*;
+
+    fn https() -> SVCB {
+        SVCB::new(
+            1,
+            Name::from_str("svc.example.net.").unwrap(),
+            vec![
+                (
+                    SvcParamKey::Ipv6Hint,
+                    SvcParamValue::Ipv6Hint(vec![Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)]),
+                ),
+                (
+                    SvcParamKey::Alpn,
+                    SvcParamValue::Alpn(vec!["h2".to_string(), "h3".to_string()]),
+                ),
+                (SvcParamKey::Port, SvcParamValue::Port(8443)),
+                (
+                    SvcParamKey::Ipv4Hint,
+                    SvcParamValue::Ipv4Hint(vec![Ipv4Addr::new(192, 0, 2, 1)]),
+                ),
+                (SvcParamKey::Ech, SvcParamValue::Ech(b"echconfig".to_vec())),
+                (SvcParamKey::Key(667), SvcParamValue::Unknown(b"hello".to_vec())),
+            ],
+        )
+    }
+
+    #[rustfmt::skip]
+    const HTTPS_WIRE: &[u8] = &[
+        0, 1,                                                       // SvcPriority
+        3, b's', b'v', b'c', 7, b'e', b'x', b'a', b'm', b'p', b'l', b'e',
+        3, b'n', b'e', b't', 0,                                     // TargetName
+        0, 1, 0, 6, 2, b'h', b'2', 2, b'h', b'3',                   // alpn=h2,h3
+        0, 3, 0, 2, 0x20, 0xFB,                                     // port=8443
+        0, 4, 0, 4, 192, 0, 2, 1,                                   // ipv4hint=192.0.2.1
+        0, 5, 0, 9, b'e', b'c', b'h', b'c', b'o', b'n', b'f', b'i', b'g', // ech
+        0, 6, 0, 16, 0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 1,                                     // ipv6hint=2001:db8::1
+        0x02, 0x9B, 0, 5, b'h', b'e', b'l', b'l', b'o',             // key667=hello
+    ];
+
+    #[test]
+    fn test_read_https() {
+        let mut decoder = BinDecoder::new(HTTPS_WIRE);
+        let read_rdata = read(&mut decoder, Restrict::new(HTTPS_WIRE.len() as u16))
+            .expect("failed to read HTTPS");
+
+        assert_eq!(read_rdata, https());
+        assert_eq!(read_rdata.svc_priority(), 1);
+        assert!(!read_rdata.is_alias());
+        assert_eq!(
+            read_rdata.alpn(),
+            Some(&["h2".to_string(), "h3".to_string()][..])
+        );
+        assert_eq!(read_rdata.port(), Some(8443));
+        assert_eq!(read_rdata.ipv4hint(), Some(&[Ipv4Addr::new(192, 0, 2, 1)][..]));
+        assert_eq!(
+            read_rdata.ipv6hint(),
+            Some(&[Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)][..])
+        );
+        assert_eq!(read_rdata.ech(), Some(&b"echconfig"[..]));
+    }
+
+    #[test]
+    fn test_emit_https() {
+        let mut bytes = Vec::new();
+        let mut encoder: BinEncoder<'_> = BinEncoder::new(&mut bytes);
+        emit(&mut encoder, &https()).expect("failed to emit HTTPS");
+        assert_eq!(bytes, HTTPS_WIRE);
+    }
+
+    #[test]
+    fn test_read_alias() {
+        let rdata = SVCB::new(0, Name::from_str("pool.svc.example.").unwrap(), vec![]);
+
+        let mut bytes = Vec::new();
+        let mut encoder: BinEncoder<'_> = BinEncoder::new(&mut bytes);
+        emit(&mut encoder, &rdata).expect("failed to emit SVCB");
+
+        let mut decoder = BinDecoder::new(&bytes);
+        let read_rdata = read(&mut decoder, Restrict::new(bytes.len() as u16)).unwrap();
+        assert!(read_rdata.is_alias());
+        assert_eq!(read_rdata, rdata);
+    }
+
+    #[test]
+    fn test_read_invalid_value() {
+        // the port must be exactly 2 octets
+        let bytes = [0, 1, 0, 0, 3, 0, 3, 0x20, 0xFB, 0];
+        let mut decoder = BinDecoder::new(&bytes);
+        assert!(read(&mut decoder, Restrict::new(bytes.len() as u16)).is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            https().to_string(),
+            "1 svc.example.net. alpn=h2,h3 port=8443 ipv4hint=192.0.2.1 ech=ZWNoY29uZmln \
+             ipv6hint=2001:db8::1 key667=\"hello\""
+        );
+    }
+}
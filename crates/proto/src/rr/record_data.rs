@@ -27,7 +27,9 @@ use log::{trace, warn};
 
 use super::domain::Name;
 use super::rdata;
-use super::rdata::{CAA, MX, NAPTR, NULL, OPENPGPKEY, OPT, SOA, SRV, SSHFP, TLSA, TXT};
+use super::rdata::{
+    CAA, MX, NAPTR, NULL, OPENPGPKEY, OPT, SOA, SRV, SSHFP, SVCB, TLSA, TXT,
+};
 use super::record_type::RecordType;
 use crate::error::*;
 use crate::serialize::binary::*;
@@ -169,6 +171,31 @@ pub enum RData {
     /// ```
     CNAME(Name),
 
+    /// ```text
+    /// RFC 6672       DNAME Redirection in the DNS               June 2012
+    ///
+    /// 2.1.  Format of the DNAME RR
+    ///
+    ///    The format of the DNAME RR has not changed from [RFC2672]; it is
+    ///    formatted as follows:
+    ///
+    ///    <owner> <ttl> <class> DNAME <target>
+    ///
+    ///    The RDATA field <target> contains one field, the domain name of the
+    ///    target, which is not compressed when sent.
+    /// ```
+    DNAME(Name),
+
+    /// [draft-ietf-dnsop-svcb-https](https://tools.ietf.org/html/draft-ietf-dnsop-svcb-https-02#section-8)
+    ///
+    /// ```text
+    /// 8.  Using SVCB with HTTPS and HTTP
+    ///
+    ///    The HTTPS RR uses the SVCB RR format, with the RR type 65, for the
+    ///    service binding of "https" and "http" origins.
+    /// ```
+    HTTPS(SVCB),
+
     /// ```text
     /// 3.3.9. MX RDATA format
     ///
@@ -548,6 +575,12 @@ pub enum RData {
     /// [RFC 7479](https://tools.ietf.org/html/rfc7479).
     SSHFP(SSHFP),
 
+    /// [draft-ietf-dnsop-svcb-https](https://tools.ietf.org/html/draft-ietf-dnsop-svcb-https-02#section-2.2)
+    ///
+    /// The service binding, the alternative endpoints of a service and their parameters, see
+    ///  [`SVCB`].
+    SVCB(SVCB),
+
     /// [RFC 6698, DNS-Based Authentication for TLS](https://tools.ietf.org/html/rfc6698#section-2.1)
     ///
     /// ```text
@@ -642,6 +675,14 @@ impl RData {
                 trace!("reading CNAME");
                 rdata::name::read(decoder).map(RData::CNAME)
             }
+            RecordType::DNAME => {
+                trace!("reading DNAME");
+                rdata::name::read(decoder).map(RData::DNAME)
+            }
+            RecordType::HTTPS => {
+                trace!("reading HTTPS");
+                rdata::svcb::read(decoder, rdata_length).map(RData::HTTPS)
+            }
             RecordType::ZERO => {
                 trace!("reading EMPTY");
                 return Ok(RData::ZERO);
@@ -686,6 +727,10 @@ impl RData {
                 trace!("reading SSHFP");
                 rdata::sshfp::read(decoder, rdata_length).map(RData::SSHFP)
             }
+            RecordType::SVCB => {
+                trace!("reading SVCB");
+                rdata::svcb::read(decoder, rdata_length).map(RData::SVCB)
+            }
             RecordType::TLSA => {
                 trace!("reading TLSA");
                 rdata::tlsa::read(decoder, rdata_length).map(RData::TLSA)
@@ -800,6 +845,11 @@ impl RData {
             RData::CNAME(ref name) | RData::NS(ref name) | RData::PTR(ref name) => {
                 rdata::name::emit(encoder, name)
             }
+            // the target of the DNAME is never compressed, RFC 6672
+            RData::DNAME(ref name) => {
+                encoder.with_canonical_names(|encoder| rdata::name::emit(encoder, name))
+            }
+            RData::HTTPS(ref svcb) | RData::SVCB(ref svcb) => rdata::svcb::emit(encoder, svcb),
             RData::ZERO => Ok(()),
             // to_lowercase for rfc4034 and rfc6840
             RData::MX(ref mx) => rdata::mx::emit(encoder, mx),
@@ -838,6 +888,8 @@ impl RData {
             RData::ANAME(..) => RecordType::ANAME,
             RData::CAA(..) => RecordType::CAA,
             RData::CNAME(..) => RecordType::CNAME,
+            RData::DNAME(..) => RecordType::DNAME,
+            RData::HTTPS(..) => RecordType::HTTPS,
             RData::MX(..) => RecordType::MX,
             RData::NAPTR(..) => RecordType::NAPTR,
             RData::NS(..) => RecordType::NS,
@@ -848,6 +900,7 @@ impl RData {
             RData::SOA(..) => RecordType::SOA,
             RData::SRV(..) => RecordType::SRV,
             RData::SSHFP(..) => RecordType::SSHFP,
+            RData::SVCB(..) => RecordType::SVCB,
             RData::TLSA(..) => RecordType::TLSA,
             RData::TXT(..) => RecordType::TXT,
             #[cfg(feature = "dnssec")]
@@ -880,6 +933,8 @@ impl fmt::Display for RData {
             RData::CAA(ref caa) => w(f, caa),
             // to_lowercase for rfc4034 and rfc6840
             RData::CNAME(ref name) | RData::NS(ref name) | RData::PTR(ref name) => w(f, name),
+            RData::DNAME(ref name) => w(f, name),
+            RData::HTTPS(ref svcb) | RData::SVCB(ref svcb) => w(f, svcb),
             RData::ZERO => Ok(()),
             // to_lowercase for rfc4034 and rfc6840
             RData::MX(ref mx) => w(f, mx),
@@ -959,6 +1014,10 @@ mod tests {
                     b'o', b'm', 0,
                 ],
             ),
+            (
+                RData::DNAME(Name::from_str("example.net").unwrap()),
+                vec![7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 3, b'n', b'e', b't', 0],
+            ),
             (
                 RData::MX(MX::new(256, Name::from_str("n").unwrap())),
                 vec![1, 0, 1, b'n', 0],
@@ -1120,6 +1179,8 @@ mod tests {
             RData::ANAME(..) => RecordType::ANAME,
             RData::CAA(..) => RecordType::CAA,
             RData::CNAME(..) => RecordType::CNAME,
+            RData::DNAME(..) => RecordType::DNAME,
+            RData::HTTPS(..) => RecordType::HTTPS,
             RData::MX(..) => RecordType::MX,
             RData::NAPTR(..) => RecordType::NAPTR,
             RData::NS(..) => RecordType::NS,
@@ -1130,6 +1191,7 @@ mod tests {
             RData::SOA(..) => RecordType::SOA,
             RData::SRV(..) => RecordType::SRV,
             RData::SSHFP(..) => RecordType::SSHFP,
+            RData::SVCB(..) => RecordType::SVCB,
             RData::TLSA(..) => RecordType::TLSA,
             RData::TXT(..) => RecordType::TXT,
             #[cfg(feature = "dnssec")]
@@ -60,8 +60,11 @@ pub enum RecordType {
     /// RFC 1035[1] Canonical name record
     CNAME,
     //  DHCID,      // 49 RFC 4701 DHCP identifier
-    //  DNAME,      // 39 RFC 2672 Delegation Name
+    /// RFC 6672 Delegation name, redirection of a subtree
+    DNAME,
     //  HIP,        // 55 RFC 5205 Host Identity Protocol
+    /// draft-ietf-dnsop-svcb-https Service binding for HTTPS
+    HTTPS,
     //  IPSECKEY,   // 45 RFC 4025 IPsec Key
     /// RFC 1996 Incremental Zone Transfer
     IXFR,
@@ -88,6 +91,8 @@ pub enum RecordType {
     SRV,
     /// RFC 4255 SSH Public Key Fingerprint
     SSHFP,
+    /// draft-ietf-dnsop-svcb-https Service binding
+    SVCB,
     //  TA,         // 32768 N/A DNSSEC Trust Authorities
     //  TKEY,       // 249 RFC 2930 Secret key record
     /// RFC 6698 TLSA certificate association
@@ -169,6 +174,8 @@ impl FromStr for RecordType {
             "ANAME" => Ok(RecordType::ANAME),
             "CAA" => Ok(RecordType::CAA),
             "CNAME" => Ok(RecordType::CNAME),
+            "DNAME" => Ok(RecordType::DNAME),
+            "HTTPS" => Ok(RecordType::HTTPS),
             "NULL" => Ok(RecordType::NULL),
             "MX" => Ok(RecordType::MX),
             "NAPTR" => Ok(RecordType::NAPTR),
@@ -178,12 +185,14 @@ impl FromStr for RecordType {
             "SOA" => Ok(RecordType::SOA),
             "SRV" => Ok(RecordType::SRV),
             "SSHFP" => Ok(RecordType::SSHFP),
+            "SVCB" => Ok(RecordType::SVCB),
             "TLSA" => Ok(RecordType::TLSA),
             "TXT" => Ok(RecordType::TXT),
             "ANY" | "*" => Ok(RecordType::ANY),
             "AXFR" => Ok(RecordType::AXFR),
             #[cfg(feature = "dnssec")]
-            "DNSKEY" | "DS" | "KEY" | "NSEC" | "NSEC3" | "NSEC3PARAM" | "RRSIG" | "SIG" => {
+            "DNSKEY" | "DS" | "KEY" | "NSEC" | "NSEC3" | "NSEC3PARAM" | "RRSIG" | "SIG"
+            | "TSIG" => {
                 Ok(RecordType::DNSSEC(str.parse()?))
             }
             _ => Err(ProtoErrorKind::UnknownRecordTypeStr(str.to_string()).into()),
@@ -210,6 +219,8 @@ impl From<u16> for RecordType {
             252 => RecordType::AXFR,
             257 => RecordType::CAA,
             5 => RecordType::CNAME,
+            39 => RecordType::DNAME,
+            65 => RecordType::HTTPS,
             0 => RecordType::ZERO,
             15 => RecordType::MX,
             35 => RecordType::NAPTR,
@@ -221,6 +232,7 @@ impl From<u16> for RecordType {
             6 => RecordType::SOA,
             33 => RecordType::SRV,
             44 => RecordType::SSHFP,
+            64 => RecordType::SVCB,
             52 => RecordType::TLSA,
             16 => RecordType::TXT,
             #[cfg(feature = "dnssec")]
@@ -231,7 +243,8 @@ impl From<u16> for RecordType {
             50/*NSEC3*/|
             51/*NSEC3PARAM*/|
             46/*RRSIG*/|
-            24/*SIG*/ => RecordType::DNSSEC(DNSSECRecordType::from(value)),
+            24/*SIG*/|
+            250/*TSIG*/ => RecordType::DNSSEC(DNSSECRecordType::from(value)),
             // all unknown record types
             _ => RecordType::Unknown(value),
         }
@@ -279,6 +292,8 @@ impl From<RecordType> for &'static str {
             RecordType::AXFR => "AXFR",
             RecordType::CAA => "CAA",
             RecordType::CNAME => "CNAME",
+            RecordType::DNAME => "DNAME",
+            RecordType::HTTPS => "HTTPS",
             RecordType::ZERO => "",
             RecordType::IXFR => "IXFR",
             RecordType::MX => "MX",
@@ -291,6 +306,7 @@ impl From<RecordType> for &'static str {
             RecordType::SOA => "SOA",
             RecordType::SRV => "SRV",
             RecordType::SSHFP => "SSHFP",
+            RecordType::SVCB => "SVCB",
             RecordType::TLSA => "TLSA",
             RecordType::TXT => "TXT",
             #[cfg(feature = "dnssec")]
@@ -320,6 +336,8 @@ impl From<RecordType> for u16 {
             RecordType::AXFR => 252,
             RecordType::CAA => 257,
             RecordType::CNAME => 5,
+            RecordType::DNAME => 39,
+            RecordType::HTTPS => 65,
             RecordType::ZERO => 0,
             RecordType::IXFR => 251,
             RecordType::MX => 15,
@@ -332,6 +350,7 @@ impl From<RecordType> for u16 {
             RecordType::SOA => 6,
             RecordType::SRV => 33,
             RecordType::SSHFP => 44,
+            RecordType::SVCB => 64,
             RecordType::TLSA => 52,
             RecordType::TXT => 16,
             #[cfg(feature = "dnssec")]
@@ -418,6 +437,8 @@ mod tests {
             "ANAME",
             "CAA",
             "CNAME",
+            "DNAME",
+            "HTTPS",
             "NULL",
             "MX",
             "NAPTR",
@@ -427,6 +448,7 @@ mod tests {
             "SOA",
             "SRV",
             "SSHFP",
+            "SVCB",
             "TLSA",
             "TXT",
             "ANY",
@@ -443,6 +465,7 @@ mod tests {
             "NSEC3PARAM",
             "RRSIG",
             "SIG",
+            "TSIG",
         ];
         #[cfg(not(feature = "dnssec"))]
         let dnssec_record_names = &[];
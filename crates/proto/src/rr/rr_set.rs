@@ -4,13 +4,14 @@
 // http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
+use std::cmp::Ordering;
 use std::iter::Chain;
 use std::slice::Iter;
 use std::vec;
 
 use log::info;
 
-use crate::rr::{DNSClass, Name, RData, Record, RecordType};
+use crate::rr::{DNSClass, Name, RData, Record, RecordType, Serial};
 
 #[cfg(feature = "dnssec")]
 use crate::rr::dnssec::SupportedAlgorithms;
@@ -216,6 +217,16 @@ impl RecordSet {
         self.rrsigs.clear()
     }
 
+    /// Rotates the records in the set, the first `mid`, modulo the number of records, move to the end
+    ///
+    /// The order of the records in an RRset is not significant, so the signatures still cover it.
+    pub fn rotate_records(&mut self, mid: usize) {
+        if !self.records.is_empty() {
+            let mid = mid % self.records.len();
+            self.records.rotate_left(mid);
+        }
+    }
+
     fn updated(&mut self, serial: u32) {
         self.serial = serial;
         self.rrsigs.clear(); // on updates, the rrsigs are invalid
@@ -295,7 +306,11 @@ impl RecordSet {
                     match soa_record.rdata() {
                         &RData::SOA(ref existing_soa) => {
                             if let RData::SOA(ref new_soa) = *record.rdata() {
-                                if new_soa.serial() <= existing_soa.serial() {
+                                // the serial may wrap around, see RFC 1982
+                                let serial = Serial::from(new_soa.serial());
+                                if serial.partial_cmp(&Serial::from(existing_soa.serial()))
+                                    != Some(Ordering::Greater)
+                                {
                                     info!(
                                         "update ignored serial out of data: {:?} <= {:?}",
                                         new_soa, existing_soa
@@ -339,7 +354,9 @@ impl RecordSet {
             //   same owner name; in fact, the two can be used cooperatively to
             //   redirect both the owner name address records (via ANAME) and
             //   everything under it (via DNAME).
-            RecordType::CNAME | RecordType::ANAME => {
+            //
+            // DNAME, RFC 6672, as with CNAME there can only be one per owner name
+            RecordType::CNAME | RecordType::ANAME | RecordType::DNAME => {
                 assert!(self.records.len() <= 1);
                 self.records.clear();
             }
@@ -678,6 +695,25 @@ mod test {
             .any(|ref x| x == &&same_serial));
     }
 
+    #[test]
+    fn test_insert_soa_wrapped() {
+        let name = Name::from_str("example.com.").unwrap();
+        let soa = |serial| {
+            let mname = Name::from_str("sns.dns.icann.org.").unwrap();
+            let rname = Name::from_str("noc.dns.icann.org.").unwrap();
+            let soa = SOA::new(mname, rname, serial, 7200, 3600, 1209600, 3600);
+            Record::from_rdata(name.clone(), 3600, RData::SOA(soa))
+        };
+
+        let mut rr_set = RecordSet::new(&name, RecordType::SOA, 0);
+        assert!(rr_set.insert(soa(u32::MAX), 0));
+
+        // the serial following the maximum is 0
+        assert!(rr_set.insert(soa(1), 0));
+        assert!(!rr_set.insert(soa(u32::MAX), 0));
+        assert!(rr_set.records_without_rrsigs().any(|x| x == &soa(1)));
+    }
+
     #[test]
     fn test_insert_cname() {
         let name = Name::from_str("web.example.com.").unwrap();
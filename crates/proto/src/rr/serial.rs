@@ -0,0 +1,118 @@
+// Copyright 2015-2020 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! SOA serial numbers, compared in serial number arithmetic
+
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Half of the serial number space, see `serial_gt`
+const SERIAL_HALF: u32 = 1 << 31;
+
+/// A SOA serial number, ordered by serial number arithmetic so that it may wrap around
+///
+/// Serials which are exactly half of the serial number space apart are not comparable, see
+///  `serial_gt`.
+///
+/// ```
+/// use trust_dns_proto::rr::Serial;
+///
+/// assert!(Serial::from(1) > Serial::from(u32::MAX));
+/// assert!(Serial::from(u32::MAX) < Serial::from(1));
+/// ```
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub struct Serial(u32);
+
+impl Serial {
+    /// The serial number as an integer
+    pub fn value(self) -> u32 {
+        self.0
+    }
+}
+
+impl From<u32> for Serial {
+    fn from(serial: u32) -> Self {
+        Serial(serial)
+    }
+}
+
+impl From<Serial> for u32 {
+    fn from(serial: Serial) -> Self {
+        serial.0
+    }
+}
+
+impl PartialOrd for Serial {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        if self.0 == other.0 {
+            Some(Ordering::Equal)
+        } else if serial_gt(self.0, other.0) {
+            Some(Ordering::Greater)
+        } else if serial_gt(other.0, self.0) {
+            Some(Ordering::Less)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Serial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Returns true if `s1` is greater than `s2` in serial number arithmetic
+///
+/// [RFC 1982](https://tools.ietf.org/html/rfc1982), Serial Number Arithmetic, August 1996
+///
+/// ```text
+/// 3.2. Comparison
+///
+///    s1 is said to be greater than s2 if, and only if, s1 is not equal to
+///    s2, and
+///
+///         (i1 < i2 and i2 - i1 > 2^(SERIAL_BITS - 1)) or
+///         (i1 > i2 and i1 - i2 < 2^(SERIAL_BITS - 1))
+/// ```
+pub fn serial_gt(s1: u32, s2: u32) -> bool {
+    (s1 < s2 && s2 - s1 > SERIAL_HALF) || (s1 > s2 && s1 - s2 < SERIAL_HALF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serial_gt() {
+        assert!(serial_gt(1, 0));
+        assert!(!serial_gt(0, 1));
+        assert!(!serial_gt(7, 7));
+
+        // wrapped around
+        assert!(serial_gt(0, u32::MAX));
+        assert!(serial_gt(5, u32::MAX - 5));
+        assert!(!serial_gt(u32::MAX, 0));
+    }
+
+    #[test]
+    fn test_serial_ord() {
+        assert!(Serial::from(2) > Serial::from(1));
+        assert!(Serial::from(1) < Serial::from(2));
+        assert!(Serial::from(7) <= Serial::from(7));
+
+        // plain integer comparison gets these backwards
+        assert!(Serial::from(0x0000_0001) > Serial::from(0xFFFF_FFFF));
+        assert!(Serial::from(0xFFFF_FFFF) < Serial::from(0x0000_0001));
+        assert!(Serial::from(0x8000_0000) > Serial::from(0x0000_0001));
+
+        // half of the space apart is undefined
+        let (s1, s2) = (Serial::from(0), Serial::from(0x8000_0000));
+        assert_eq!(s1.partial_cmp(&s2), None);
+        assert_eq!(s2.partial_cmp(&s1), None);
+    }
+}
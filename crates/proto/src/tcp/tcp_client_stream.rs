@@ -6,7 +6,6 @@
 // copied, modified, or distributed except according to those terms.
 
 use std::fmt::{self, Display};
-#[cfg(feature = "tokio-runtime")]
 use std::io;
 use std::net::SocketAddr;
 use std::pin::Pin;
@@ -69,7 +68,50 @@ impl<S: Connect> TcpClientStream<S> {
         TcpClientConnect<S>,
         Box<dyn DnsStreamHandle + 'static + Send>,
     ) {
-        let (stream_future, sender) = TcpStream::<S>::with_timeout(name_server, timeout);
+        Self::with_bind_addr_and_timeout(name_server, None, timeout)
+    }
+
+    /// Constructs a new TcpStream for a client to the specified SocketAddr, connecting from a
+    ///  local address
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - the IP and Port of the DNS server to connect to
+    /// * `bind_addr` - the local address to connect from, see [`Connect::connect_with_bind`]
+    /// * `timeout` - connection timeout
+    pub fn with_bind_addr_and_timeout(
+        name_server: SocketAddr,
+        bind_addr: Option<SocketAddr>,
+        timeout: Duration,
+    ) -> (
+        TcpClientConnect<S>,
+        Box<dyn DnsStreamHandle + 'static + Send>,
+    ) {
+        Self::with_future(S::connect_with_bind(name_server, bind_addr), name_server, timeout)
+    }
+}
+
+impl<S: DnsTcpStream> TcpClientStream<S> {
+    /// Constructs a new TcpStream for a client to the specified SocketAddr, over the IO stream
+    ///  established by `future`, e.g. through a proxy
+    ///
+    /// # Arguments
+    ///
+    /// * `future` - the future establishing the IO stream to the DNS server
+    /// * `name_server` - the IP and Port of the DNS server to connect to
+    /// * `timeout` - connection timeout
+    pub fn with_future<F>(
+        future: F,
+        name_server: SocketAddr,
+        timeout: Duration,
+    ) -> (
+        TcpClientConnect<S>,
+        Box<dyn DnsStreamHandle + 'static + Send>,
+    )
+    where
+        F: Future<Output = Result<S, io::Error>> + Send + 'static,
+    {
+        let (stream_future, sender) = TcpStream::<S>::with_future(future, name_server, timeout);
 
         let new_future = Box::pin(
             stream_future
@@ -81,9 +123,7 @@ impl<S: Connect> TcpClientStream<S> {
 
         (TcpClientConnect(new_future), sender)
     }
-}
 
-impl<S: DnsTcpStream> TcpClientStream<S> {
     /// Wraps the TcpStream in TcpClientStream
     pub fn from_stream(tcp_stream: TcpStream<S>) -> Self {
         TcpClientStream { tcp_stream }
@@ -152,6 +192,15 @@ impl Connect for AsyncIoTokioAsStd<TokioTcpStream> {
     async fn connect(addr: SocketAddr) -> io::Result<Self> {
         super::tokio::connect(&addr).await.map(AsyncIoTokioAsStd)
     }
+
+    async fn connect_with_bind(
+        addr: SocketAddr,
+        bind_addr: Option<SocketAddr>,
+    ) -> io::Result<Self> {
+        super::tokio::connect_with_bind(&addr, &bind_addr)
+            .await
+            .map(AsyncIoTokioAsStd)
+    }
 }
 
 #[cfg(test)]
@@ -35,6 +35,22 @@ pub trait DnsTcpStream: AsyncRead + AsyncWrite + Unpin + Send + Sync + Sized + '
 pub trait Connect: DnsTcpStream {
     /// connect to tcp
     async fn connect(addr: SocketAddr) -> io::Result<Self>;
+
+    /// connect to tcp, from the local `bind_addr` if one is given
+    ///
+    /// The default implementation does not support binding, it fails if `bind_addr` is given.
+    async fn connect_with_bind(
+        addr: SocketAddr,
+        bind_addr: Option<SocketAddr>,
+    ) -> io::Result<Self> {
+        match bind_addr {
+            None => Self::connect(addr).await,
+            Some(bind_addr) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("binding TCP connections to {} is not supported", bind_addr),
+            )),
+        }
+    }
 }
 
 /// Current state while writing to the remote of the TCP connection
@@ -77,6 +93,9 @@ pub enum ReadTcpState {
     },
 }
 
+/// A timer bounding the read of a single message
+type ReadDeadline = Pin<Box<dyn Future<Output = ()> + Send>>;
+
 /// A Stream used for sending data to and from a remote DNS endpoint (client or server).
 #[must_use = "futures do nothing unless polled"]
 pub struct TcpStream<S: DnsTcpStream> {
@@ -84,6 +103,8 @@ pub struct TcpStream<S: DnsTcpStream> {
     outbound_messages: StreamReceiver,
     send_state: Option<WriteTcpState>,
     read_state: ReadTcpState,
+    read_timeout: Option<Duration>,
+    read_deadline: Option<ReadDeadline>,
     peer_addr: SocketAddr,
 }
 
@@ -122,21 +143,69 @@ impl<S: Connect> TcpStream<S> {
         impl Future<Output = Result<TcpStream<S>, io::Error>> + Send,
         BufStreamHandle,
     ) {
+        Self::with_bind_addr_and_timeout(name_server, None, timeout)
+    }
+
+    /// Creates a new future of the eventually establish a IO stream connection or fail trying
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - the IP and Port of the DNS server to connect to
+    /// * `bind_addr` - the local address to connect from, see [`Connect::connect_with_bind`]
+    /// * `timeout` - connection timeout
+    #[allow(clippy::type_complexity)]
+    pub fn with_bind_addr_and_timeout(
+        name_server: SocketAddr,
+        bind_addr: Option<SocketAddr>,
+        timeout: Duration,
+    ) -> (
+        impl Future<Output = Result<TcpStream<S>, io::Error>> + Send,
+        BufStreamHandle,
+    ) {
+        Self::with_future(S::connect_with_bind(name_server, bind_addr), name_server, timeout)
+    }
+}
+
+impl<S: DnsTcpStream> TcpStream<S> {
+    /// Creates a new future of the eventually establish a IO stream connection or fail trying,
+    ///  where the connection itself is established by `future`, e.g. through a proxy
+    ///
+    /// # Arguments
+    ///
+    /// * `future` - the future establishing the IO stream to the DNS server
+    /// * `name_server` - the IP and Port of the DNS server to connect to
+    /// * `timeout` - connection timeout, also bounding the read of each message once it has begun,
+    ///               see [`TcpStream::set_read_timeout`]
+    #[allow(clippy::type_complexity)]
+    pub fn with_future<F>(
+        future: F,
+        name_server: SocketAddr,
+        timeout: Duration,
+    ) -> (
+        impl Future<Output = Result<TcpStream<S>, io::Error>> + Send,
+        BufStreamHandle,
+    )
+    where
+        F: Future<Output = Result<S, io::Error>> + Send + 'static,
+    {
         let (message_sender, outbound_messages) = BufStreamHandle::create();
         // This set of futures collapses the next tcp socket into a stream which can be used for
         //  sending and receiving tcp packets.
-        let stream_fut = Self::connect(name_server, timeout, outbound_messages);
+        let stream_fut = Self::connect(future, name_server, timeout, outbound_messages);
 
         (stream_fut, message_sender)
     }
 
-    async fn connect(
+    async fn connect<F>(
+        future: F,
         name_server: SocketAddr,
         timeout: Duration,
         outbound_messages: StreamReceiver,
-    ) -> Result<TcpStream<S>, io::Error> {
-        let tcp = S::connect(name_server);
-        S::Time::timeout(timeout, tcp)
+    ) -> Result<TcpStream<S>, io::Error>
+    where
+        F: Future<Output = Result<S, io::Error>> + Send + 'static,
+    {
+        S::Time::timeout(timeout, future)
             .map(move |tcp_stream: Result<Result<S, io::Error>, _>| {
                 tcp_stream
                     .and_then(|tcp_stream| tcp_stream)
@@ -150,20 +219,30 @@ impl<S: Connect> TcpStream<S> {
                                 pos: 0,
                                 bytes: [0u8; 2],
                             },
+                            read_timeout: Some(timeout),
+                            read_deadline: None,
                             peer_addr: name_server,
                         }
                     })
             })
             .await
     }
-}
 
-impl<S: DnsTcpStream> TcpStream<S> {
     /// Returns the address of the peer connection.
     pub fn peer_addr(&self) -> SocketAddr {
         self.peer_addr
     }
 
+    /// Sets the time allowed to read a message once its first byte has been received, `None` for
+    ///  no limit
+    ///
+    /// When exceeded the stream fails with `io::ErrorKind::TimedOut`, so that a broken or
+    ///  malicious peer can not stall the connection with a partial message.
+    pub fn set_read_timeout(&mut self, read_timeout: Option<Duration>) {
+        self.read_timeout = read_timeout;
+    }
+
+    #[allow(clippy::type_complexity)]
     fn pollable_split(
         &mut self,
     ) -> (
@@ -171,12 +250,16 @@ impl<S: DnsTcpStream> TcpStream<S> {
         &mut StreamReceiver,
         &mut Option<WriteTcpState>,
         &mut ReadTcpState,
+        &mut Option<ReadDeadline>,
+        Option<Duration>,
     ) {
         (
             &mut self.socket,
             &mut self.outbound_messages,
             &mut self.send_state,
             &mut self.read_state,
+            &mut self.read_deadline,
+            self.read_timeout,
         )
     }
 
@@ -208,6 +291,8 @@ impl<S: DnsTcpStream> TcpStream<S> {
                 pos: 0,
                 bytes: [0u8; 2],
             },
+            read_timeout: None,
+            read_deadline: None,
             peer_addr,
         }
     }
@@ -219,7 +304,8 @@ impl<S: DnsTcpStream> Stream for TcpStream<S> {
     #[allow(clippy::cognitive_complexity)]
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         let peer = self.peer_addr;
-        let (socket, outbound_messages, send_state, read_state) = self.pollable_split();
+        let (socket, outbound_messages, send_state, read_state, read_deadline, read_timeout) =
+            self.pollable_split();
         let mut socket = Pin::new(socket);
         let mut outbound_messages = Pin::new(outbound_messages);
 
@@ -336,7 +422,10 @@ impl<S: DnsTcpStream> Stream for TcpStream<S> {
                     ref mut bytes,
                 } => {
                     // debug!("reading length {}", bytes.len());
-                    let read = ready!(socket.as_mut().poll_read(cx, &mut bytes[*pos..]))?;
+                    let read = match socket.as_mut().poll_read(cx, &mut bytes[*pos..]) {
+                        Poll::Ready(read) => read?,
+                        Poll::Pending => return poll_read_deadline(read_deadline, cx),
+                    };
                     if read == 0 {
                         // the Stream was closed!
                         debug!("zero bytes read, stream closed?");
@@ -355,6 +444,11 @@ impl<S: DnsTcpStream> Stream for TcpStream<S> {
                     debug!("in ReadTcpState::LenBytes: {}", pos);
                     *pos += read;
 
+                    // the message has begun, it must now be read in full before the deadline
+                    if read_deadline.is_none() {
+                        *read_deadline = read_timeout.map(S::Time::delay_for);
+                    }
+
                     if *pos < bytes.len() {
                         debug!("remain ReadTcpState::LenBytes: {}", pos);
                         None
@@ -362,6 +456,15 @@ impl<S: DnsTcpStream> Stream for TcpStream<S> {
                         let length =
                             u16::from(bytes[0]) << 8 & 0xFF00 | u16::from(bytes[1]) & 0x00FF;
                         debug!("got length: {}", length);
+
+                        // the length can not exceed the 65535 bytes of a message by construction,
+                        //  but an empty message is never valid
+                        if length == 0 {
+                            return Poll::Ready(Some(Err(io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                "invalid message length: 0",
+                            ))));
+                        }
                         let mut bytes = vec![0; length as usize];
                         bytes.resize(length as usize, 0);
 
@@ -373,7 +476,10 @@ impl<S: DnsTcpStream> Stream for TcpStream<S> {
                     ref mut pos,
                     ref mut bytes,
                 } => {
-                    let read = ready!(socket.as_mut().poll_read(cx, &mut bytes[*pos..]))?;
+                    let read = match socket.as_mut().poll_read(cx, &mut bytes[*pos..]) {
+                        Poll::Ready(read) => read?,
+                        Poll::Pending => return poll_read_deadline(read_deadline, cx),
+                    };
                     if read == 0 {
                         // the Stream was closed!
                         debug!("zero bytes read for message, stream closed?");
@@ -408,6 +514,7 @@ impl<S: DnsTcpStream> Stream for TcpStream<S> {
                 if let ReadTcpState::Bytes { pos, bytes } = mem::replace(read_state, state) {
                     debug!("returning bytes");
                     assert_eq!(pos, bytes.len());
+                    *read_deadline = None;
                     ret_buf = Some(bytes);
                 }
             }
@@ -427,15 +534,40 @@ impl<S: DnsTcpStream> Stream for TcpStream<S> {
     }
 }
 
+/// Fails the read of a partially received message once its deadline has passed, otherwise the
+///  stream is `Pending` on further data
+fn poll_read_deadline(
+    read_deadline: &mut Option<ReadDeadline>,
+    cx: &mut Context<'_>,
+) -> Poll<Option<io::Result<SerialMessage>>> {
+    match read_deadline {
+        Some(deadline) if deadline.as_mut().poll(cx).is_ready() => {
+            debug!("timed out reading message");
+            Poll::Ready(Some(Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "timed out reading message",
+            ))))
+        }
+        _ => Poll::Pending,
+    }
+}
+
 #[cfg(test)]
 #[cfg(feature = "tokio-runtime")]
 mod tests {
+    use std::io::{self, Write};
     #[cfg(not(target_os = "linux"))]
     use std::net::Ipv6Addr;
-    use std::net::{IpAddr, Ipv4Addr};
+    use std::net::{IpAddr, Ipv4Addr, TcpListener};
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    use futures_util::stream::StreamExt;
     use tokio::net::TcpStream as TokioTcpStream;
     use tokio::runtime::Runtime;
 
+    use super::TcpStream;
     use crate::iocompat::AsyncIoTokioAsStd;
     use crate::TokioTime;
 
@@ -458,4 +590,47 @@ mod tests {
             io_loop,
         )
     }
+
+    /// Connects to a peer which only ever writes `bytes`, returning the first item of the stream
+    fn read_from_peer(bytes: &'static [u8]) -> Option<io::Result<Vec<u8>>> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (done, wait) = mpsc::channel::<()>();
+        let peer = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(bytes).unwrap();
+
+            // hold the connection open, without sending anything further
+            wait.recv().ok();
+        });
+
+        let io_loop = Runtime::new().expect("failed to create tokio runtime");
+        let (stream, _sender) = TcpStream::<AsyncIoTokioAsStd<TokioTcpStream>>::with_timeout(
+            addr,
+            Duration::from_millis(100),
+        );
+        let mut stream = io_loop.block_on(stream).expect("failed to connect");
+        let message = io_loop.block_on(stream.next());
+
+        done.send(()).unwrap();
+        peer.join().unwrap();
+        message.map(|message| message.map(|message| message.bytes().to_vec()))
+    }
+
+    #[test]
+    fn test_read_bogus_length() {
+        // claims the longest message possible, but sends only part of it
+        let error = read_from_peer(&[0xFF, 0xFF, 0xDE, 0xAD])
+            .expect("stream closed")
+            .expect_err("bogus length was read");
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn test_read_zero_length() {
+        let error = read_from_peer(&[0x00, 0x00])
+            .expect("stream closed")
+            .expect_err("zero length was read");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
 }
@@ -19,7 +19,7 @@ use log::{debug, warn};
 
 use crate::error::ProtoError;
 use crate::op::message::NoopMessageFinalizer;
-use crate::op::{MessageFinalizer, OpCode};
+use crate::op::{MessageFinalizer, OpCode, Query, ResponseCode};
 use crate::udp::udp_stream::{NextRandomUdpSocket, UdpSocket};
 use crate::xfer::{DnsRequest, DnsRequestSender, DnsResponse, DnsResponseFuture, SerialMessage};
 use crate::Time;
@@ -27,14 +27,16 @@ use crate::Time;
 /// A UDP client stream of DNS binary packets
 ///
 /// This stream will create a new UDP socket for every request. This is to avoid potential cache
-///   poisoning during use by UDP based attacks.
+///   poisoning during use by UDP based attacks. For the same reason, responses are only accepted
+///   from the name server, and with the ID and question of the request, others are dropped.
 #[must_use = "futures do nothing unless polled"]
 pub struct UdpClientStream<S, MF = NoopMessageFinalizer>
 where
     S: Send,
-    MF: MessageFinalizer,
+    MF: MessageFinalizer + ?Sized,
 {
     name_server: SocketAddr,
+    bind_addr: Option<SocketAddr>,
     timeout: Duration,
     is_shutdown: bool,
     signer: Option<Arc<MF>>,
@@ -69,7 +71,7 @@ impl<S: Send> UdpClientStream<S, NoopMessageFinalizer> {
     }
 }
 
-impl<S: Send, MF: MessageFinalizer> UdpClientStream<S, MF> {
+impl<S: Send, MF: MessageFinalizer + ?Sized> UdpClientStream<S, MF> {
     /// Constructs a new TcpStream for a client to the specified SocketAddr.
     ///
     /// # Arguments
@@ -80,9 +82,29 @@ impl<S: Send, MF: MessageFinalizer> UdpClientStream<S, MF> {
         name_server: SocketAddr,
         timeout: Duration,
         signer: Option<Arc<MF>>,
+    ) -> UdpClientConnect<S, MF> {
+        Self::with_bind_addr_and_timeout(name_server, None, timeout, signer)
+    }
+
+    /// Constructs a new UdpStream for a client to the specified SocketAddr, sending from a local
+    ///  address
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - the IP and Port of the DNS server to connect to
+    /// * `bind_addr` - the local address each socket is bound to, the port is always chosen
+    ///                 at random
+    /// * `timeout` - connection timeout
+    /// * `signer` - optional signer for update messages
+    pub fn with_bind_addr_and_timeout(
+        name_server: SocketAddr,
+        bind_addr: Option<SocketAddr>,
+        timeout: Duration,
+        signer: Option<Arc<MF>>,
     ) -> UdpClientConnect<S, MF> {
         UdpClientConnect {
             name_server: Some(name_server),
+            bind_addr,
             timeout,
             signer,
             marker: PhantomData::<S>,
@@ -90,7 +112,7 @@ impl<S: Send, MF: MessageFinalizer> UdpClientStream<S, MF> {
     }
 }
 
-impl<S: Send, MF: MessageFinalizer> Display for UdpClientStream<S, MF> {
+impl<S: Send, MF: MessageFinalizer + ?Sized> Display for UdpClientStream<S, MF> {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         write!(formatter, "UDP({})", self.name_server)
     }
@@ -104,7 +126,7 @@ fn random_query_id() -> u16 {
     Standard.sample(&mut rand)
 }
 
-impl<S: UdpSocket + Send + 'static, MF: MessageFinalizer> DnsRequestSender
+impl<S: UdpSocket + Send + 'static, MF: MessageFinalizer + ?Sized> DnsRequestSender
     for UdpClientStream<S, MF>
 {
     fn send_message(&mut self, mut message: DnsRequest) -> DnsResponseFuture {
@@ -142,11 +164,12 @@ impl<S: UdpSocket + Send + 'static, MF: MessageFinalizer> DnsRequestSender
         };
 
         let message_id = message.id();
+        let queries = message.queries().to_vec();
         let message = SerialMessage::new(bytes, self.name_server);
 
         S::Time::timeout::<Pin<Box<dyn Future<Output = Result<DnsResponse, ProtoError>> + Send>>>(
             self.timeout,
-            Box::pin(send_serial_message::<S>(message, message_id)),
+            Box::pin(send_serial_message::<S>(message, message_id, queries, self.bind_addr)),
         )
         .into()
     }
@@ -161,7 +184,7 @@ impl<S: UdpSocket + Send + 'static, MF: MessageFinalizer> DnsRequestSender
 }
 
 // TODO: is this impl necessary? there's nothing being driven here...
-impl<S: Send, MF: MessageFinalizer> Stream for UdpClientStream<S, MF> {
+impl<S: Send, MF: MessageFinalizer + ?Sized> Stream for UdpClientStream<S, MF> {
     type Item = Result<(), ProtoError>;
 
     fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
@@ -178,15 +201,16 @@ impl<S: Send, MF: MessageFinalizer> Stream for UdpClientStream<S, MF> {
 pub struct UdpClientConnect<S, MF = NoopMessageFinalizer>
 where
     S: Send,
-    MF: MessageFinalizer,
+    MF: MessageFinalizer + ?Sized,
 {
     name_server: Option<SocketAddr>,
+    bind_addr: Option<SocketAddr>,
     timeout: Duration,
     signer: Option<Arc<MF>>,
     marker: PhantomData<S>,
 }
 
-impl<S: Send + Unpin, MF: MessageFinalizer> Future for UdpClientConnect<S, MF> {
+impl<S: Send + Unpin, MF: MessageFinalizer + ?Sized> Future for UdpClientConnect<S, MF> {
     type Output = Result<UdpClientStream<S, MF>, ProtoError>;
 
     fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
@@ -196,6 +220,7 @@ impl<S: Send + Unpin, MF: MessageFinalizer> Future for UdpClientConnect<S, MF> {
                 .name_server
                 .take()
                 .expect("UdpClientConnect invalid state: name_server"),
+            bind_addr: self.bind_addr,
             is_shutdown: false,
             timeout: self.timeout,
             signer: self.signer.take(),
@@ -207,9 +232,11 @@ impl<S: Send + Unpin, MF: MessageFinalizer> Future for UdpClientConnect<S, MF> {
 async fn send_serial_message<S: UdpSocket + Send>(
     msg: SerialMessage,
     msg_id: u16,
+    queries: Vec<Query>,
+    bind_addr: Option<SocketAddr>,
 ) -> Result<DnsResponse, ProtoError> {
     let name_server = msg.addr();
-    let socket: S = NextRandomUdpSocket::new(&name_server).await?;
+    let socket: S = NextRandomUdpSocket::new(&name_server, &bind_addr).await?;
     let bytes = msg.bytes();
     let addr = msg.addr();
     let len_sent: usize = socket.send_to(bytes, addr).await?;
@@ -244,14 +271,9 @@ async fn send_serial_message<S: UdpSocket + Send>(
             continue;
         }
 
-        // TODO: match query strings from request and response?
-
         match response.to_message() {
             Ok(message) => {
-                if msg_id == message.id() {
-                    debug!("received message id: {}", message.id());
-                    return Ok(DnsResponse::from(message));
-                } else {
+                if msg_id != message.id() {
                     // on wrong id, attempted poison?
                     warn!(
                         "expected message id: {} got: {}, dropped",
@@ -261,6 +283,24 @@ async fn send_serial_message<S: UdpSocket + Send>(
 
                     continue;
                 }
+
+                // the question must be echoed back as well, see RFC 5452, though error responses
+                //  such as FORMERR and NOTIMP often leave it out
+                let omitted = message.queries().is_empty()
+                    && message.response_code() != ResponseCode::NoError;
+                if message.queries() != queries.as_slice() && !omitted {
+                    warn!(
+                        "expected queries: {:?} got: {:?} for message id: {}, dropped",
+                        queries,
+                        message.queries(),
+                        msg_id
+                    );
+
+                    continue;
+                }
+
+                debug!("received message id: {}", message.id());
+                return Ok(DnsResponse::from(message));
             }
             Err(e) => {
                 // on errors deserializing, continue
@@ -284,8 +324,14 @@ mod tests {
     #[cfg(not(target_os = "linux"))]
     use std::net::Ipv6Addr;
     use std::net::{IpAddr, Ipv4Addr};
+    use std::time::Duration;
     use tokio::{net::UdpSocket as TokioUdpSocket, runtime::Runtime};
 
+    use crate::op::{Message, Query, ResponseCode};
+    use crate::rr::{Name, RecordType};
+    use crate::udp::UdpClientStream;
+    use crate::xfer::{DnsRequest, DnsRequestSender};
+
     #[test]
     fn test_udp_client_stream_ipv4() {
         let io_loop = Runtime::new().expect("failed to create tokio runtime");
@@ -304,4 +350,101 @@ mod tests {
             io_loop,
         )
     }
+
+    #[test]
+    fn test_udp_client_stream_mismatched_query() {
+        let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let server_addr = server.local_addr().unwrap();
+
+        let name = Name::from_ascii("www.example.com.").unwrap();
+        let server_handle = std::thread::spawn(move || {
+            let mut buffer = [0_u8; 512];
+            for _ in 0..2 {
+                let (len, addr) = server.recv_from(&mut buffer).expect("receive failed");
+                let request = Message::from_vec(&buffer[..len]).expect("failed parse of request");
+
+                // the id matches, but not the question
+                let mut poisoned = Message::new();
+                poisoned.set_id(request.id());
+                poisoned.set_response_code(ResponseCode::NXDomain);
+                poisoned.add_query(Query::query(
+                    Name::from_ascii("poisoned.example.com.").unwrap(),
+                    RecordType::A,
+                ));
+                let bytes = poisoned.to_vec().unwrap();
+                server.send_to(&bytes, addr).expect("send failed");
+
+                // the genuine response follows to the second request only
+                if request.queries()[0].query_type() == RecordType::AAAA {
+                    let mut response = Message::new();
+                    response.set_id(request.id());
+                    response.add_queries(request.queries().to_vec());
+                    let bytes = response.to_vec().unwrap();
+                    server.send_to(&bytes, addr).expect("send failed");
+                }
+            }
+        });
+
+        let io_loop = Runtime::new().expect("failed to create tokio runtime");
+        let stream = UdpClientStream::<TokioUdpSocket>::with_timeout(
+            server_addr,
+            Duration::from_millis(500),
+        );
+        let mut stream = io_loop.block_on(stream).expect("failed to create stream");
+
+        let mut query = Message::new();
+        query.add_query(Query::query(name.clone(), RecordType::A));
+        let response =
+            io_loop.block_on(stream.send_message(DnsRequest::new(query, Default::default())));
+        assert!(response.is_err(), "accepted response with mismatched question");
+
+        let mut query = Message::new();
+        query.add_query(Query::query(name.clone(), RecordType::AAAA));
+        let response = io_loop
+            .block_on(stream.send_message(DnsRequest::new(query, Default::default())))
+            .expect("genuine response was dropped");
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(response.queries(), &[Query::query(name, RecordType::AAAA)]);
+
+        server_handle.join().expect("server thread failed");
+    }
+
+    #[test]
+    fn test_udp_client_stream_error_without_question() {
+        let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let server_addr = server.local_addr().unwrap();
+
+        let server_handle = std::thread::spawn(move || {
+            let mut buffer = [0_u8; 512];
+            let (len, addr) = server.recv_from(&mut buffer).expect("receive failed");
+            let request = Message::from_vec(&buffer[..len]).expect("failed parse of request");
+
+            // the error response leaves out the question
+            let mut response = Message::new();
+            response.set_id(request.id());
+            response.set_response_code(ResponseCode::FormErr);
+            let bytes = response.to_vec().unwrap();
+            server.send_to(&bytes, addr).expect("send failed");
+        });
+
+        let io_loop = Runtime::new().expect("failed to create tokio runtime");
+        let stream = UdpClientStream::<TokioUdpSocket>::with_timeout(
+            server_addr,
+            Duration::from_millis(500),
+        );
+        let mut stream = io_loop.block_on(stream).expect("failed to create stream");
+
+        let mut query = Message::new();
+        let name = Name::from_ascii("www.example.com.").unwrap();
+        query.add_query(Query::query(name, RecordType::A));
+        let response = io_loop
+            .block_on(stream.send_message(DnsRequest::new(query, Default::default())))
+            .expect("error response was dropped");
+        assert_eq!(response.response_code(), ResponseCode::FormErr);
+        assert!(response.queries().is_empty());
+
+        server_handle.join().expect("server thread failed");
+    }
 }
@@ -91,9 +91,8 @@ impl<S: UdpSocket + Send + 'static> UdpStream<S> {
     ) {
         let (message_sender, outbound_messages) = BufStreamHandle::create();
 
-        // TODO: allow the bind address to be specified...
         // constructs a future for getting the next randomly bound port to a UdpSocket
-        let next_socket = NextRandomUdpSocket::new(&name_server);
+        let next_socket = NextRandomUdpSocket::new(&name_server, &None);
 
         // This set of futures collapses the next udp socket into a stream which can be used for
         //  sending and receiving udp packets.
@@ -188,14 +187,23 @@ pub(crate) struct NextRandomUdpSocket<S> {
 
 impl<S: UdpSocket> NextRandomUdpSocket<S> {
     /// Creates a future for randomly binding to a local socket address for client connections.
-    pub(crate) fn new(name_server: &SocketAddr) -> NextRandomUdpSocket<S> {
-        let zero_addr: IpAddr = match *name_server {
-            SocketAddr::V4(..) => IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)),
-            SocketAddr::V6(..) => IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0)),
+    ///
+    /// The socket is bound to the IP of `bind_addr` if given, its port is ignored: the port is
+    ///  always chosen at random, as a fixed one would make responses easier to spoof.
+    pub(crate) fn new(
+        name_server: &SocketAddr,
+        bind_addr: &Option<SocketAddr>,
+    ) -> NextRandomUdpSocket<S> {
+        let bind_address = match *bind_addr {
+            Some(bind_addr) => bind_addr.ip(),
+            None => match *name_server {
+                SocketAddr::V4(..) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                SocketAddr::V6(..) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            },
         };
 
         NextRandomUdpSocket {
-            bind_address: zero_addr,
+            bind_address,
             marker: PhantomData,
         }
     }
@@ -226,6 +234,10 @@ impl<S: UdpSocket> Future for NextRandomUdpSocket<S> {
                     debug!("created socket successfully");
                     return Poll::Ready(Ok(socket));
                 }
+                // no port is available on an address which is not local
+                Poll::Ready(Err(err)) if err.kind() == io::ErrorKind::AddrNotAvailable => {
+                    return Poll::Ready(Err(err));
+                }
                 Poll::Ready(Err(err)) => {
                     debug!("unable to bind port, attempt: {}: {}", attempt, err)
                 }
@@ -289,6 +301,47 @@ mod tests {
         next_random_socket_test::<TokioUdpSocket, Runtime>(io_loop)
     }
 
+    #[test]
+    fn test_next_random_socket_bind_addr() {
+        use super::NextRandomUdpSocket;
+        use std::net::SocketAddr;
+
+        let io_loop = Runtime::new().expect("failed to create tokio runtime");
+        let name_server = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 53);
+
+        let bind_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+        let socket = io_loop
+            .block_on(NextRandomUdpSocket::<TokioUdpSocket>::new(
+                &name_server,
+                &Some(bind_addr),
+            ))
+            .expect("failed to bind");
+        let local_addr = socket.local_addr().unwrap();
+        assert_eq!(local_addr.ip(), bind_addr.ip());
+        assert_ne!(local_addr.port(), 0);
+
+        // the port of the address is not used, each socket gets a random one
+        let bind_addr = local_addr;
+        let socket = io_loop
+            .block_on(NextRandomUdpSocket::<TokioUdpSocket>::new(
+                &name_server,
+                &Some(bind_addr),
+            ))
+            .expect("failed to bind");
+        let local_addr = socket.local_addr().unwrap();
+        assert_eq!(local_addr.ip(), bind_addr.ip());
+        assert_ne!(local_addr.port(), bind_addr.port());
+
+        // an address which is not local fails, rather than trying other ports
+        let not_local = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)), 0);
+        assert!(io_loop
+            .block_on(NextRandomUdpSocket::<TokioUdpSocket>::new(
+                &name_server,
+                &Some(not_local),
+            ))
+            .is_err());
+    }
+
     #[test]
     fn test_udp_stream_ipv4() {
         use crate::tests::udp_stream_test;
@@ -15,7 +15,10 @@ use rand;
 
 use crate::error::*;
 use crate::op::{Message, MessageType, OpCode, Query};
-use crate::xfer::{DnsRequest, DnsRequestOptions, DnsResponse, SerialMessage};
+use crate::rr::rdata::opt::EdnsOption;
+use crate::xfer::{
+    DnsRequest, DnsRequestOptions, DnsResponse, SerialMessage, FORWARD_HOPS_OPTION,
+};
 
 // TODO: this should be configurable
 // > An EDNS buffer size of 1232 bytes will avoid fragmentation on nearly all current networks.
@@ -92,13 +95,18 @@ pub trait DnsHandle: 'static + Clone + Send + Sync + Unpin {
             .set_id(id)
             .set_message_type(MessageType::Query)
             .set_op_code(OpCode::Query)
-            .set_recursion_desired(true);
+            .set_recursion_desired(true)
+            .set_checking_disabled(options.checking_disabled);
 
         // Extended dns
-        if options.use_edns {
+        if options.use_edns || options.forward_hops.is_some() {
             let edns = message.edns_mut();
             edns.set_max_payload(MAX_PAYLOAD_LEN);
             edns.set_version(0);
+
+            if let Some(hops) = options.forward_hops {
+                edns.set_option(EdnsOption::Unknown(FORWARD_HOPS_OPTION, vec![hops]));
+            }
         }
 
         self.send(DnsRequest::new(message, options))
@@ -119,7 +119,7 @@ pub struct DnsMultiplexer<S, MF, D = Box<dyn DnsStreamHandle>>
 where
     D: Send + 'static,
     S: DnsClientStream + 'static,
-    MF: MessageFinalizer,
+    MF: MessageFinalizer + ?Sized,
 {
     stream: S,
     timeout_duration: Duration,
@@ -132,7 +132,7 @@ where
 impl<S, MF> DnsMultiplexer<S, MF, Box<dyn DnsStreamHandle>>
 where
     S: DnsClientStream + Unpin + 'static,
-    MF: MessageFinalizer,
+    MF: MessageFinalizer + ?Sized,
 {
     /// Spawns a new DnsMultiplexer Stream. This uses a default timeout of 5 seconds for all requests.
     ///
@@ -257,7 +257,7 @@ pub struct DnsMultiplexerConnect<F, S, MF>
 where
     F: Future<Output = Result<S, ProtoError>> + Send + Unpin + 'static,
     S: Stream<Item = Result<SerialMessage, ProtoError>> + Unpin,
-    MF: MessageFinalizer + Send + Sync + 'static,
+    MF: MessageFinalizer + Send + Sync + 'static + ?Sized,
 {
     stream: F,
     stream_handle: Option<Box<dyn DnsStreamHandle>>,
@@ -269,7 +269,7 @@ impl<F, S, MF> Future for DnsMultiplexerConnect<F, S, MF>
 where
     F: Future<Output = Result<S, ProtoError>> + Send + Unpin + 'static,
     S: DnsClientStream + Unpin + 'static,
-    MF: MessageFinalizer + Send + Sync + 'static,
+    MF: MessageFinalizer + Send + Sync + 'static + ?Sized,
 {
     type Output = Result<DnsMultiplexer<S, MF, Box<dyn DnsStreamHandle>>, ProtoError>;
 
@@ -293,7 +293,7 @@ where
 impl<S, MF> Display for DnsMultiplexer<S, MF>
 where
     S: DnsClientStream + 'static,
-    MF: MessageFinalizer + Send + Sync + 'static,
+    MF: MessageFinalizer + Send + Sync + 'static + ?Sized,
 {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         write!(formatter, "{}", self.stream)
@@ -303,7 +303,7 @@ where
 impl<S, MF> DnsRequestSender for DnsMultiplexer<S, MF>
 where
     S: DnsClientStream + Unpin + 'static,
-    MF: MessageFinalizer + Send + Sync + 'static,
+    MF: MessageFinalizer + Send + Sync + 'static + ?Sized,
 {
     fn send_message(&mut self, request: DnsRequest) -> DnsResponseFuture {
         if self.is_shutdown {
@@ -389,7 +389,7 @@ where
 impl<S, MF> Stream for DnsMultiplexer<S, MF>
 where
     S: DnsClientStream + Unpin + 'static,
-    MF: MessageFinalizer + Send + Sync + 'static,
+    MF: MessageFinalizer + Send + Sync + 'static + ?Sized,
 {
     type Item = Result<(), ProtoError>;
 
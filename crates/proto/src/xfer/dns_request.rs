@@ -11,6 +11,12 @@ use std::ops::{Deref, DerefMut};
 
 use crate::op::Message;
 
+/// The EDNS option code carrying `DnsRequestOptions::forward_hops`, a single byte, from the range
+///  reserved for local and experimental use
+///
+/// [RFC 6891](https://tools.ietf.org/html/rfc6891#section-9), EDNS(0) Extensions, April 2013
+pub const FORWARD_HOPS_OPTION: u16 = 65_001;
+
 /// A set of options for expressing options to how requests should be treated
 #[derive(Clone, Default)]
 pub struct DnsRequestOptions {
@@ -23,6 +29,13 @@ pub struct DnsRequestOptions {
     // TODO: add EDNS options here?
     /// When true, will add EDNS options to the request.
     pub use_edns: bool,
+    /// The number of times this request has been forwarded between servers, when set it is sent
+    ///  in the `FORWARD_HOPS_OPTION` EDNS option so that forwarding loops can be detected.
+    pub forward_hops: Option<u8>,
+    /// When true, the CD, checking disabled, bit is set on the request so that the upstream
+    ///  resolver returns the answer without validating it, the answer is not validated locally
+    ///  either.
+    pub checking_disabled: bool,
 }
 
 /// A DNS request object
@@ -22,7 +22,7 @@ use crate::op::{OpCode, Query};
 use crate::rr::dnssec::rdata::{DNSSECRData, DNSSECRecordType, DNSKEY, SIG};
 #[cfg(feature = "dnssec")]
 use crate::rr::dnssec::Verifier;
-use crate::rr::dnssec::{Algorithm, SupportedAlgorithms, TrustAnchor};
+use crate::rr::dnssec::{Algorithm, SupportedAlgorithms, TrustAnchor, TrustAnchorStore};
 use crate::rr::rdata::opt::EdnsOption;
 use crate::rr::{DNSClass, Name, RData, Record, RecordType};
 use crate::xfer::dns_handle::DnsHandle;
@@ -48,7 +48,7 @@ where
     H: DnsHandle + Unpin + 'static,
 {
     handle: H,
-    trust_anchor: Arc<TrustAnchor>,
+    trust_anchor: Arc<TrustAnchorStore>,
     request_depth: usize,
     minimum_key_len: usize,
     minimum_algorithm: Algorithm, // used to prevent down grade attacks...
@@ -76,6 +76,17 @@ where
     /// * `handle` - handle to use for all connections to a remote server.
     /// * `trust_anchor` - custom DNSKEYs that will be trusted, can be used to pin trusted keys.
     pub fn with_trust_anchor(handle: H, trust_anchor: TrustAnchor) -> Self {
+        Self::with_trust_anchor_store(handle, trust_anchor.into())
+    }
+
+    /// Create a new DnssecDnsHandle wrapping the specified handle.
+    ///
+    /// This allows the DNSKEYs and DS records from which chains of trust are built to be defined.
+    ///
+    /// # Arguments
+    /// * `handle` - handle to use for all connections to a remote server.
+    /// * `trust_anchor` - DNSKEYs, and DS records covering DNSKEYs, that will be trusted.
+    pub fn with_trust_anchor_store(handle: H, trust_anchor: TrustAnchorStore) -> Self {
         DnssecDnsHandle {
             handle,
             trust_anchor: Arc::new(trust_anchor),
@@ -121,6 +132,11 @@ where
             ))));
         }
 
+        // the client asked for the answer without validation, see `DnsRequestOptions`
+        if request.options().checking_disabled {
+            return Box::pin(self.handle.send(request));
+        }
+
         // dnssec only matters on queries.
         if let OpCode::Query = request.op_code() {
             // This will panic on no queries, that is a very odd type of request, isn't it?
@@ -178,8 +194,7 @@ where
                     })
                     .and_then(move |verified_message| {
                         // at this point all of the message is verified.
-                        //  This is where NSEC (and possibly NSEC3) validation occurs
-                        // As of now, only NSEC is supported.
+                        //  This is where NSEC and NSEC3 validation occurs
                         if verified_message.answers().is_empty() {
                             // get SOA name
                             let soa_name = if let Some(soa_name) = verified_message
@@ -202,7 +217,20 @@ where
                                 .filter(|rr| is_dnssec(rr, DNSSECRecordType::NSEC))
                                 .collect::<Vec<_>>();
 
-                            if !verify_nsec(&query, soa_name, nsecs.as_slice()) {
+                            // zones signed with NSEC3 have no NSEC records
+                            if nsecs.is_empty() {
+                                let nsec3s = verified_message
+                                    .name_servers()
+                                    .iter()
+                                    .filter(|rr| is_dnssec(rr, DNSSECRecordType::NSEC3))
+                                    .collect::<Vec<_>>();
+
+                                if !verify_nsec3(&query, soa_name, nsec3s.as_slice()) {
+                                    return future::err(Self::Error::from(ProtoError::from(
+                                        "could not validate negative response with NSEC3",
+                                    )));
+                                }
+                            } else if !verify_nsec(&query, soa_name, nsecs.as_slice()) {
                                 // TODO change this to remove the NSECs, like we do for the others?
                                 return future::err(Self::Error::from(ProtoError::from(
                                     "could not validate negative response with NSEC",
@@ -483,10 +511,7 @@ where
                 }
             })
             .filter_map(|(i, rdata)| {
-                if handle
-                    .trust_anchor
-                    .contains_dnskey_bytes(rdata.public_key())
-                {
+                if handle.trust_anchor.contains(&rrset.name, rdata) {
                     debug!(
                         "validated dnskey with trust_anchor: {}, {}",
                         rrset.name, rdata
@@ -942,3 +967,329 @@ pub fn verify_nsec(query: &Query, soa_name: &Name, nsecs: &[&Record]) -> bool {
         verify_nsec_coverage(&wildcard)
     }
 }
+
+/// Verifies NSEC3 records
+///
+/// ```text
+/// RFC 5155                         NSEC3                        March 2008
+///
+/// 8.3.  Closest Encloser Proof
+///
+///    For some NSEC3 responses, the validator MUST verify that the closest
+///    encloser proof is present and valid.
+///
+///    ...
+///
+///    o  One of the NSEC3 RRs MUST match the closest encloser.
+///
+///    o  One of the NSEC3 RRs MUST cover the next closer name.
+///
+/// 8.4.  Validating Name Error Responses
+///
+///    A validator MUST verify that there is a closest encloser proof for
+///    QNAME present in the response and that there is an NSEC3 RR that
+///    covers the wildcard at the closest encloser (i.e., the name formed by
+///    prepending the asterisk label to the closest encloser).
+///
+/// 8.5.  Validating No Data Responses, QTYPE is not DS
+///
+///    The validator MUST verify that an NSEC3 RR that matches QNAME is
+///    present and that both the QTYPE and the CNAME type are not set in its
+///    Type Bit Maps field.
+///
+/// 8.6.  Validating No Data Responses, QTYPE is DS
+///
+///    If there is an NSEC3 RR that matches QNAME, the validator MUST ensure
+///    that both the QTYPE and the CNAME type are not set in its Type Bit
+///    Maps field.
+///
+///    If there is no such NSEC3 RR, then the validator MUST verify that a
+///    closest provable encloser proof for QNAME is present in the response,
+///    and that the NSEC3 RR that covers the "next closer" name has the Opt-
+///    Out bit set.
+///
+/// 8.7.  Validating Wildcard No Data Responses
+///
+///    The validator MUST verify a closest encloser proof for QNAME and MUST
+///    find an NSEC3 RR present in the response that matches the wildcard
+///    name generated by prepending the asterisk label to the closest
+///    encloser.  Furthermore, the bits corresponding to both QTYPE and
+///    CNAME MUST NOT be set in the wildcard matching NSEC3 RR.
+/// ```
+#[cfg(any(feature = "openssl", feature = "ring"))]
+#[doc(hidden)]
+pub fn verify_nsec3(query: &Query, soa_name: &Name, nsec3s: &[&Record]) -> bool {
+    use data_encoding::BASE32_DNSSEC;
+
+    use crate::rr::dnssec::rdata::NSEC3;
+
+    // the NSEC3 records of the zone, with their owner names decoded to the hash
+    let nsec3s: Vec<(Vec<u8>, &NSEC3)> = nsec3s
+        .iter()
+        .filter(|nsec3| nsec3.name().base_name() == *soa_name)
+        .filter_map(|nsec3| {
+            let label = nsec3.name().iter().next()?;
+            let owner_hash = BASE32_DNSSEC.decode(&label.to_ascii_lowercase()).ok()?;
+            let rdata = nsec3.rdata().as_dnssec().and_then(DNSSECRData::as_nsec3)?;
+            Some((owner_hash, rdata))
+        })
+        .collect();
+
+    let hash = |nsec3: &NSEC3, name: &Name| -> Option<Vec<u8>> {
+        nsec3
+            .hash_algorithm()
+            .hash(nsec3.salt(), name, nsec3.iterations())
+            .ok()
+            .map(|digest| digest.as_ref().to_vec())
+    };
+
+    let matching = |name: &Name| {
+        nsec3s
+            .iter()
+            .find(|(owner_hash, nsec3)| hash(*nsec3, name).as_ref() == Some(owner_hash))
+            .map(|(_, nsec3)| *nsec3)
+    };
+
+    let covering = |name: &Name| {
+        nsec3s
+            .iter()
+            .find(|(owner_hash, nsec3)| {
+                let next = nsec3.next_hashed_owner_name();
+                hash(*nsec3, name).map_or(false, |name_hash| {
+                    let (owner_hash, name_hash) = (owner_hash.as_slice(), name_hash.as_slice());
+                    if owner_hash < next {
+                        owner_hash < name_hash && name_hash < next
+                    } else {
+                        // the last NSEC3 of the zone wraps around to the first
+                        owner_hash < name_hash || name_hash < next
+                    }
+                })
+            })
+            .map(|(_, nsec3)| *nsec3)
+    };
+
+    let denies_type = |nsec3: &NSEC3| -> bool {
+        let types = nsec3.type_bit_maps();
+        !types.contains(&query.query_type()) && !types.contains(&RecordType::CNAME)
+    };
+
+    let name = query.name().to_lowercase();
+    if !soa_name.zone_of(&name) {
+        return false;
+    }
+
+    // the name exists, but not with the type
+    if let Some(nsec3) = matching(&name) {
+        return denies_type(nsec3);
+    }
+
+    // the closest encloser is the nearest ancestor of the name which exists
+    let closest_encloser = (soa_name.num_labels()..name.num_labels())
+        .rev()
+        .map(|labels| name.trim_to(usize::from(labels)))
+        .find_map(|encloser| matching(&encloser).map(|nsec3| (encloser, nsec3)));
+    let (closest_encloser, encloser_nsec3) = match closest_encloser {
+        Some(closest_encloser) => closest_encloser,
+        None => return false,
+    };
+
+    // names beneath a delegation or DNAME are not in the zone
+    let encloser_types = encloser_nsec3.type_bit_maps();
+    if encloser_types.contains(&RecordType::DNAME)
+        || (encloser_types.contains(&RecordType::NS) && !encloser_types.contains(&RecordType::SOA))
+    {
+        return false;
+    }
+
+    let next_closer = name.trim_to(usize::from(closest_encloser.num_labels()) + 1);
+    let next_closer_nsec3 = match covering(&next_closer) {
+        Some(nsec3) => nsec3,
+        None => return false,
+    };
+
+    // an opt-out span may hold unsigned delegations, which have no DS records
+    if query.query_type() == RecordType::DNSSEC(DNSSECRecordType::DS)
+        && next_closer_nsec3.opt_out()
+    {
+        return true;
+    }
+
+    let wildcard = Name::from_ascii("*")
+        .expect("* is a valid label")
+        .append_domain(&closest_encloser);
+
+    // either the wildcard exists, but not with the type, or there is no wildcard
+    match matching(&wildcard) {
+        Some(nsec3) => denies_type(nsec3),
+        None => covering(&wildcard).is_some(),
+    }
+}
+
+/// Will always return false. To enable NSEC3 verification compile with the openssl or ring feature.
+#[cfg(not(any(feature = "openssl", feature = "ring")))]
+#[doc(hidden)]
+pub fn verify_nsec3(_: &Query, _: &Name, _: &[&Record]) -> bool {
+    false
+}
+
+/// A record of the NSEC3 chain of the example zone, RFC 5155 Appendix A
+#[cfg(test)]
+#[cfg(any(feature = "openssl", feature = "ring"))]
+fn example_nsec3(owner: &str, next: &str, opt_out: bool, types: &[RecordType]) -> Record {
+    use data_encoding::BASE32_DNSSEC;
+
+    use crate::rr::dnssec::rdata::NSEC3;
+    use crate::rr::dnssec::Nsec3HashAlgorithm;
+
+    let zone = Name::from_ascii("example.").unwrap();
+    let name = Name::from_ascii(owner).unwrap().append_domain(&zone);
+    let nsec3 = NSEC3::new(
+        Nsec3HashAlgorithm::SHA1,
+        opt_out,
+        12,
+        vec![0xAA, 0xBB, 0xCC, 0xDD],
+        BASE32_DNSSEC.decode(next.as_bytes()).unwrap(),
+        types.to_vec(),
+    );
+    Record::from_rdata(name, 3600, RData::DNSSEC(DNSSECRData::NSEC3(nsec3)))
+}
+
+#[cfg(test)]
+#[cfg(any(feature = "openssl", feature = "ring"))]
+fn verify_example_nsec3(name: &str, query_type: RecordType, nsec3s: &[Record]) -> bool {
+    let query = Query::query(Name::from_ascii(name).unwrap(), query_type);
+    let nsec3s = nsec3s.iter().collect::<Vec<_>>();
+    verify_nsec3(&query, &Name::from_ascii("example.").unwrap(), &nsec3s)
+}
+
+#[test]
+#[cfg(any(feature = "openssl", feature = "ring"))]
+fn test_verify_nsec3_name_error() {
+    let rrsig = RecordType::DNSSEC(DNSSECRecordType::RRSIG);
+    let ds = RecordType::DNSSEC(DNSSECRecordType::DS);
+    let apex = vec![
+        RecordType::NS,
+        RecordType::SOA,
+        RecordType::MX,
+        rrsig,
+        RecordType::DNSSEC(DNSSECRecordType::DNSKEY),
+        RecordType::DNSSEC(DNSSECRecordType::NSEC3PARAM),
+    ];
+
+    // RFC 5155 Appendix B.1, the closest encloser is x.w.example
+    let mut nsec3s = vec![
+        // covers the next closer name, c.x.w.example
+        example_nsec3(
+            "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom",
+            "2t7b4g4vsa5smi47k61mv5bv1a22bojr",
+            true,
+            &apex,
+        ),
+        // matches the closest encloser
+        example_nsec3(
+            "b4um86eghhds6nea196smvmlo4ors995",
+            "gjeqe526plbf1g8mklp59enfd789njgi",
+            true,
+            &[RecordType::MX, rrsig],
+        ),
+        // covers the wildcard, *.x.w.example
+        example_nsec3(
+            "35mthgpgcu1qg68fab165klnsnk3dpvl",
+            "b4um86eghhds6nea196smvmlo4ors995",
+            true,
+            &[RecordType::NS, ds, rrsig],
+        ),
+    ];
+    assert!(verify_example_nsec3("a.c.x.w.example.", RecordType::A, &nsec3s));
+
+    // without the wildcard proof, the name might have been synthesized from a wildcard
+    nsec3s.pop();
+    assert!(!verify_example_nsec3("a.c.x.w.example.", RecordType::A, &nsec3s));
+}
+
+#[test]
+#[cfg(any(feature = "openssl", feature = "ring"))]
+fn test_verify_nsec3_no_data() {
+    let rrsig = RecordType::DNSSEC(DNSSECRecordType::RRSIG);
+
+    // RFC 5155 Appendix B.2, ns1.example has only A records
+    let nsec3s = vec![example_nsec3(
+        "2t7b4g4vsa5smi47k61mv5bv1a22bojr",
+        "2vptu5timamqttgl4luu9kg21e0aor3s",
+        true,
+        &[RecordType::A, rrsig],
+    )];
+    assert!(verify_example_nsec3("ns1.example.", RecordType::MX, &nsec3s));
+    assert!(!verify_example_nsec3("ns1.example.", RecordType::A, &nsec3s));
+
+    // RFC 5155 Appendix B.2.1, y.w.example is an empty non-terminal
+    let nsec3s = vec![example_nsec3(
+        "ji6neoaepv8b5o6k4ev33abha8ht9fgc",
+        "k8udemvp1j2f7eg6jebps17vp3n8i58h",
+        true,
+        &[],
+    )];
+    assert!(verify_example_nsec3("y.w.example.", RecordType::A, &nsec3s));
+}
+
+#[test]
+#[cfg(any(feature = "openssl", feature = "ring"))]
+fn test_verify_nsec3_wildcard_no_data() {
+    let rrsig = RecordType::DNSSEC(DNSSECRecordType::RRSIG);
+
+    // RFC 5155 Appendix B.5, the closest encloser is w.example, which has a wildcard
+    let nsec3s = vec![
+        // matches the closest encloser
+        example_nsec3(
+            "k8udemvp1j2f7eg6jebps17vp3n8i58h",
+            "kohar7mbb8dc2ce8a9qvl8hon4k53uhi",
+            true,
+            &[],
+        ),
+        // covers the next closer name, z.w.example
+        example_nsec3(
+            "q04jkcevqvmu85r014c7dkba38o0ji5r",
+            "r53bq7cc2uvmubfu5ocmm6pers9tk9en",
+            true,
+            &[RecordType::A, rrsig],
+        ),
+        // matches the wildcard, *.w.example
+        example_nsec3(
+            "r53bq7cc2uvmubfu5ocmm6pers9tk9en",
+            "t644ebqk9bibcna874givr6joj62mlhv",
+            true,
+            &[RecordType::MX, rrsig],
+        ),
+    ];
+    assert!(verify_example_nsec3("a.z.w.example.", RecordType::AAAA, &nsec3s));
+    assert!(!verify_example_nsec3("a.z.w.example.", RecordType::MX, &nsec3s));
+}
+
+#[test]
+#[cfg(any(feature = "openssl", feature = "ring"))]
+fn test_verify_nsec3_opt_out() {
+    let rrsig = RecordType::DNSSEC(DNSSECRecordType::RRSIG);
+    let ds = RecordType::DNSSEC(DNSSECRecordType::DS);
+    let nsec3s = |opt_out| {
+        vec![
+            // matches the closest encloser, example
+            example_nsec3(
+                "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom",
+                "2t7b4g4vsa5smi47k61mv5bv1a22bojr",
+                true,
+                &[RecordType::NS, RecordType::SOA, RecordType::MX, rrsig],
+            ),
+            // covers the next closer name, c.example
+            example_nsec3(
+                "35mthgpgcu1qg68fab165klnsnk3dpvl",
+                "b4um86eghhds6nea196smvmlo4ors995",
+                opt_out,
+                &[RecordType::NS, ds, rrsig],
+            ),
+        ]
+    };
+
+    // c.example is an unsigned delegation within an opt-out span, RFC 5155 Appendix B.3
+    assert!(verify_example_nsec3("c.example.", ds, &nsec3s(true)));
+    assert!(!verify_example_nsec3("c.example.", ds, &nsec3s(false)));
+}
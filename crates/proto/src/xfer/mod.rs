@@ -34,7 +34,7 @@ pub use self::dns_exchange::{
 };
 pub use self::dns_handle::{DnsHandle, DnsStreamHandle, StreamHandle};
 pub use self::dns_multiplexer::{DnsMultiplexer, DnsMultiplexerConnect};
-pub use self::dns_request::{DnsRequest, DnsRequestOptions};
+pub use self::dns_request::{DnsRequest, DnsRequestOptions, FORWARD_HOPS_OPTION};
 pub use self::dns_response::{DnsResponse, DnsResponseFuture};
 #[cfg(feature = "dnssec")]
 pub use self::dnssec_dns_handle::DnssecDnsHandle;
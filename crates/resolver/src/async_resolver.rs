@@ -19,12 +19,12 @@ use proto::rr::{IntoName, Name, Record, RecordType};
 use proto::xfer::{DnsRequestOptions, RetryDnsHandle};
 use proto::DnsHandle;
 
-use crate::caching_client::CachingClient;
+use crate::caching_client::{CachingClient, Spawner};
 use crate::config::{ResolverConfig, ResolverOpts};
 use crate::dns_lru::{self, DnsLru};
 use crate::error::*;
 use crate::lookup::{self, Lookup, LookupEither, LookupFuture};
-use crate::lookup_ip::{LookupIp, LookupIpFuture};
+use crate::lookup_ip::{LookupIp, LookupIpFuture, LookupIpStream};
 use crate::name_server::{
     ConnectionProvider, GenericConnection, GenericConnectionProvider, NameServerPool,
     RuntimeProvider,
@@ -66,6 +66,8 @@ pub struct AsyncResolver<C: DnsHandle<Error = ResolveError>, P: ConnectionProvid
     options: ResolverOpts,
     client_cache: CachingClient<LookupEither<C, P>, ResolveError>,
     hosts: Option<Arc<Hosts>>,
+    // the name servers, shared with the client_cache
+    pool: NameServerPool<C, P>,
 }
 
 /// An AsyncResolver used with Tokio
@@ -164,11 +166,33 @@ impl<R: RuntimeProvider> AsyncResolver<GenericConnection, GenericConnectionProvi
         options: ResolverOpts,
         runtime: R::Handle,
     ) -> Result<Self, ResolveError> {
-        AsyncResolver::<GenericConnection, GenericConnectionProvider<R>>::new_with_conn(
-            config,
-            options,
-            GenericConnectionProvider::<R>::new(runtime),
-        )
+        let mut handle = runtime.clone();
+        let mut resolver =
+            AsyncResolver::<GenericConnection, GenericConnectionProvider<R>>::new_with_conn(
+                config,
+                options,
+                GenericConnectionProvider::<R>::new(runtime),
+            )?;
+
+        // nearly expired records are refreshed in the background
+        let spawner = handle.clone();
+        resolver.client_cache.set_spawner(Spawner::new(move |future| {
+            spawner.clone().spawn_bg(async move {
+                future.await;
+                Ok(())
+            })
+        }));
+
+        // the resolver can be used while the connections are opened in the background
+        if resolver.options.eager_connect {
+            let warmup = resolver.warmup();
+            handle.spawn_bg(async move {
+                warmup.await;
+                Ok(())
+            });
+        }
+
+        Ok(resolver)
     }
 
     /// Constructs a new Resolver with the system configuration.
@@ -202,14 +226,20 @@ impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> AsyncR
         options: ResolverOpts,
         conn_provider: P,
     ) -> Result<Self, ResolveError> {
-        let pool = NameServerPool::from_config_with_provider(&config, &options, conn_provider);
+        let pool = NameServerPool::from_config_with_provider(&config, &options, conn_provider)?;
         let either;
-        let client = RetryDnsHandle::new(pool, options.attempts);
+        // the first pass over the name servers is an attempt, the rest are retries
+        let client = RetryDnsHandle::new(pool.clone(), options.attempts.saturating_sub(1));
         if options.validate {
             #[cfg(feature = "dnssec")]
             {
                 use proto::xfer::DnssecDnsHandle;
-                either = LookupEither::Secure(DnssecDnsHandle::new(client));
+                either = match config.trust_anchor() {
+                    Some(trust_anchor) => LookupEither::Secure(
+                        DnssecDnsHandle::with_trust_anchor_store(client, trust_anchor.clone()),
+                    ),
+                    None => LookupEither::Secure(DnssecDnsHandle::new(client)),
+                };
             }
 
             #[cfg(not(feature = "dnssec"))]
@@ -229,15 +259,30 @@ impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> AsyncR
         };
 
         trace!("handle passed back");
-        let lru = DnsLru::new(options.cache_size, dns_lru::TtlConfig::from_opts(&options));
+        let mut lru = DnsLru::new(options.cache_size, dns_lru::TtlConfig::from_opts(&options));
+        lru.set_client_subnet(options.client_subnet);
+        let mut client_cache =
+            CachingClient::with_cache(lru, either, options.preserve_intermediates);
+        client_cache.set_dnssec_ok(options.dnssec_ok);
+        client_cache.set_max_records(options.max_records);
+        client_cache.set_prefetch_threshold(options.prefetch_threshold);
         Ok(AsyncResolver {
             config,
             options,
-            client_cache: CachingClient::with_cache(lru, either, options.preserve_intermediates),
+            client_cache,
             hosts,
+            pool,
         })
     }
 
+    /// Connects to each of the name servers, see `NameServerPool::warmup`
+    ///
+    /// With `ResolverOpts::eager_connect` this is spawned as the resolver is constructed. The
+    ///  returned future resolves to the number of name servers which were connected.
+    pub fn warmup(&self) -> impl Future<Output = usize> + Send + 'static {
+        self.pool.warmup()
+    }
+
     /// Constructs a new Resolver with the system configuration.
     ///
     /// This will use `/etc/resolv.conf` on Unix OSes and the registry on Windows.
@@ -271,6 +316,13 @@ impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> AsyncR
             Err(err) => return future::Either::Left(future::err(err.into())),
         };
 
+        if let Some(hosts) = self.hosts.as_ref() {
+            let query = Query::query(name.clone(), record_type);
+            if let Some(lookup) = hosts.lookup_static_host(&query) {
+                return future::Either::Left(future::ok(lookup));
+            }
+        }
+
         let names = self.build_names(name);
         future::Either::Right(LookupFuture::lookup(
             names,
@@ -396,6 +448,34 @@ impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> AsyncR
         .await
     }
 
+    /// Performs a dual-stack DNS lookup for the IP for the given hostname, returning a stream of
+    ///  the addresses as they are found
+    ///
+    /// Unlike `lookup_ip`, the A and AAAA queries are always sent in parallel, unless the
+    ///  `ResolverOpts::ip_strategy` is only for one of them. The addresses of each are yielded as
+    ///  soon as its query completes, e.g. to begin connecting before the other has completed. The
+    ///  stream ends once all of the queries have completed, failed queries yield no addresses.
+    ///
+    /// # Arguments
+    /// * `host` - string hostname, if this is an invalid hostname, an error will be returned.
+    pub fn lookup_ip_stream<N: IntoName + TryParseIp>(
+        &self,
+        host: N,
+    ) -> Result<LookupIpStream<LookupEither<C, P>, ResolveError>, ResolveError> {
+        if let Some(ip) = host.try_parse_ip().and_then(|ip| ip.to_ip_addr()) {
+            return Ok(LookupIpStream::from_ip(ip, self.client_cache.clone()));
+        }
+
+        let names = self.build_names(host.into_name()?);
+        Ok(LookupIpStream::lookup(
+            names,
+            self.options.ip_strategy,
+            self.client_cache.clone(),
+            DnsRequestOptions::default(),
+            self.hosts.clone(),
+        ))
+    }
+
     lookup_fn!(
         reverse_lookup,
         lookup::ReverseLookup,
@@ -404,12 +484,64 @@ impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> AsyncR
     );
     lookup_fn!(ipv4_lookup, lookup::Ipv4Lookup, RecordType::A);
     lookup_fn!(ipv6_lookup, lookup::Ipv6Lookup, RecordType::AAAA);
+    lookup_fn!(https_lookup, lookup::HttpsLookup, RecordType::HTTPS);
     lookup_fn!(mx_lookup, lookup::MxLookup, RecordType::MX);
     lookup_fn!(ns_lookup, lookup::NsLookup, RecordType::NS);
     lookup_fn!(soa_lookup, lookup::SoaLookup, RecordType::SOA);
     lookup_fn!(srv_lookup, lookup::SrvLookup, RecordType::SRV);
     lookup_fn!(tlsa_lookup, lookup::TlsaLookup, RecordType::TLSA);
     lookup_fn!(txt_lookup, lookup::TxtLookup, RecordType::TXT);
+
+    /// Performs a lookup of the CAA records relevant to the name, which authorize the certificate
+    ///  authorities that may issue certificates for it
+    ///
+    /// Per [RFC 8659, section 3](https://tools.ietf.org/html/rfc8659#section-3), these are the CAA
+    ///  records of the name, or otherwise those of its closest ancestor which has any, excluding
+    ///  the root. The name the records were found at is that of `CaaLookup::query`.
+    ///
+    /// When none of them have CAA records, the result of the lookup of the name itself is
+    ///  returned, generally `ResolveErrorKind::NoRecordsFound`, any certificate authority may
+    ///  then issue certificates for it. Any other failure is returned as it is met, as the
+    ///  relevant records are unknown.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - a string which parses to a domain name, failure to parse will return an error,
+    ///             the name is always treated as fully qualified
+    pub async fn caa_lookup<N: IntoName>(
+        &self,
+        query: N,
+    ) -> Result<lookup::CaaLookup, ResolveError> {
+        let mut name = query.into_name()?;
+        name.set_fqdn(true);
+
+        let options = DnsRequestOptions {
+            use_edns: self.options.edns0,
+            ..Default::default()
+        };
+
+        // climb the tree, until a name with CAA records is found
+        let mut own_result = None;
+        while !name.is_root() {
+            let parent = name.base_name();
+            let result = self
+                .inner_lookup::<lookup::CaaLookup>(name, RecordType::CAA, options.clone())
+                .await;
+
+            match result {
+                Ok(ref lookup) if lookup.iter().next().is_some() => return result,
+                Err(ref e) if !matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. }) => {
+                    return result
+                }
+                _ => (),
+            }
+
+            own_result.get_or_insert(result);
+            name = parent;
+        }
+
+        own_result.unwrap_or_else(|| Err("no CAA records are relevant to the root".into()))
+    }
 }
 
 impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> fmt::Debug
@@ -1052,10 +1184,14 @@ pub mod testing {
 #[cfg(test)]
 #[cfg(feature = "tokio-runtime")]
 mod tests {
-    use proto::xfer::DnsRequest;
+    use proto::op::{Message, MessageType};
+    use proto::rr::rdata::caa::{Value, CAA};
+    use proto::rr::RData;
+    use proto::xfer::{DnsRequest, DnsResponse};
+    use proto::TokioTime;
     use tokio::runtime::Runtime;
 
-    use crate::config::{ResolverConfig, ResolverOpts};
+    use crate::config::{NameServerConfig, NameServerConfigGroup, ResolverConfig, ResolverOpts};
     use crate::name_server::{TokioConnection, TokioConnectionProvider, TokioRuntime};
 
     use super::*;
@@ -1087,6 +1223,33 @@ mod tests {
         assert!(is_send_t::<LookupFuture<TokioConnection, ResolveError>>());
     }
 
+    #[test]
+    fn test_build_names() {
+        use std::str::FromStr;
+
+        let mut config = ResolverConfig::new();
+        config.add_search(Name::from_str("example.com.").unwrap());
+        config.add_search(Name::from_str("example.net.").unwrap());
+
+        let resolver = TokioAsyncResolver::tokio(config, ResolverOpts::default())
+            .expect("failed to create resolver");
+
+        // names are popped off the end, i.e. this is the reverse of the lookup order
+        let names = resolver.build_names(Name::from_str("host").unwrap());
+        assert_eq!(
+            names,
+            vec![
+                Name::from_str("host").unwrap(),
+                Name::from_str("host.example.net.").unwrap(),
+                Name::from_str("host.example.com.").unwrap(),
+            ]
+        );
+
+        // a trailing dot bypasses the search list
+        let names = resolver.build_names(Name::from_str("host.").unwrap());
+        assert_eq!(names, vec![Name::from_str("host.").unwrap()]);
+    }
+
     #[test]
     fn test_lookup_google() {
         use super::testing::lookup_test;
@@ -1254,4 +1417,119 @@ mod tests {
         let handle = TokioHandle;
         search_ipv6_name_parse_fails_test::<Runtime, TokioRuntime>(io_loop, handle);
     }
+
+    /// Answers with the CAA record of example.com., and without records for any other query
+    #[derive(Clone)]
+    struct CaaHandle {
+        queries: Arc<std::sync::Mutex<Vec<Name>>>,
+    }
+
+    impl DnsHandle for CaaHandle {
+        type Response = future::Ready<Result<DnsResponse, ResolveError>>;
+        type Error = ResolveError;
+
+        fn send<R: Into<DnsRequest> + Unpin + Send + 'static>(
+            &mut self,
+            request: R,
+        ) -> Self::Response {
+            let request = request.into();
+            let query = request.queries()[0].clone();
+            self.queries.lock().unwrap().push(query.name().clone());
+
+            let mut message = Message::new();
+            message.set_id(request.id());
+            message.set_message_type(MessageType::Response);
+            if query.name() == &Name::from_ascii("example.com.").unwrap() {
+                let caa = CAA::new_issue(false, Name::from_ascii("ca.example.net").ok(), vec![]);
+                let record = Record::from_rdata(query.name().clone(), 300, RData::CAA(caa));
+                message.add_answer(record);
+            }
+            message.add_query(query);
+
+            future::ok(message.into())
+        }
+    }
+
+    #[derive(Clone)]
+    struct CaaProvider(CaaHandle);
+
+    impl ConnectionProvider for CaaProvider {
+        type Conn = CaaHandle;
+        type FutureConn = future::Ready<Result<CaaHandle, ResolveError>>;
+        type Time = TokioTime;
+
+        fn new_connection(&self, _: &NameServerConfig, _: &ResolverOpts) -> Self::FutureConn {
+            future::ok(self.0.clone())
+        }
+    }
+
+    fn caa_resolver() -> (AsyncResolver<CaaHandle, CaaProvider>, Arc<std::sync::Mutex<Vec<Name>>>) {
+        let handle = CaaHandle {
+            queries: Arc::new(std::sync::Mutex::new(Vec::new())),
+        };
+        let queries = Arc::clone(&handle.queries);
+
+        let name_servers =
+            NameServerConfigGroup::from_ips_clear(&[IpAddr::from([127, 0, 0, 1])], 53, true);
+        let config = ResolverConfig::from_parts(None, vec![], name_servers);
+        let options = ResolverOpts {
+            use_hosts_file: false,
+            ..ResolverOpts::default()
+        };
+
+        let resolver = AsyncResolver::new_with_conn(config, options, CaaProvider(handle))
+            .expect("failed to create resolver");
+        (resolver, queries)
+    }
+
+    #[test]
+    fn test_caa_lookup() {
+        let (resolver, queries) = caa_resolver();
+        let io_loop = Runtime::new().unwrap();
+
+        let lookup = io_loop
+            .block_on(resolver.caa_lookup("example.com."))
+            .expect("lookup failed");
+        let caa = lookup.iter().next().expect("no CAA records");
+        assert!(caa.tag().is_issue());
+        assert_eq!(
+            *caa.value(),
+            Value::Issuer(Name::from_ascii("ca.example.net").ok(), vec![])
+        );
+
+        // the name has its own records, there is no need to climb the tree
+        assert_eq!(*queries.lock().unwrap(), vec![Name::from_ascii("example.com.").unwrap()]);
+    }
+
+    #[test]
+    fn test_caa_lookup_inherits_from_parent() {
+        let (resolver, queries) = caa_resolver();
+        let io_loop = Runtime::new().unwrap();
+
+        // the records of the closest ancestor with any are relevant, the tree is climbed no further
+        let lookup = io_loop
+            .block_on(resolver.caa_lookup("www.sub.example.com"))
+            .expect("lookup failed");
+        assert_eq!(lookup.query().name(), &Name::from_ascii("example.com.").unwrap());
+        assert_eq!(lookup.iter().count(), 1);
+        assert_eq!(
+            *queries.lock().unwrap(),
+            vec![
+                Name::from_ascii("www.sub.example.com.").unwrap(),
+                Name::from_ascii("sub.example.com.").unwrap(),
+                Name::from_ascii("example.com.").unwrap(),
+            ]
+        );
+
+        // without any CAA records up the tree, the name itself has none
+        let error = io_loop
+            .block_on(resolver.caa_lookup("www.example.org."))
+            .expect_err("lookup should fail");
+        match error.kind() {
+            ResolveErrorKind::NoRecordsFound { query, .. } => {
+                assert_eq!(query.name(), &Name::from_ascii("www.example.org.").unwrap())
+            }
+            kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
 }
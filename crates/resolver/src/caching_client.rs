@@ -9,11 +9,11 @@
 
 use std::borrow::Cow;
 use std::error::Error;
+use std::fmt;
 use std::net::{Ipv4Addr, Ipv6Addr};
 use std::pin::Pin;
 use std::sync::atomic::{AtomicU8, Ordering};
 use std::sync::Arc;
-use std::time::Instant;
 
 use futures_util::future::Future;
 
@@ -23,10 +23,14 @@ use proto::rr::domain::usage::{
     ResolverUsage, DEFAULT, INVALID, IN_ADDR_ARPA_127, IP6_ARPA_1, LOCAL,
     LOCALHOST as LOCALHOST_usage,
 };
+#[cfg(feature = "dnssec")]
+use proto::rr::dnssec::rdata::{DNSSECRData, DNSSECRecordType};
+use proto::rr::rdata::opt::{EdnsCode, EdnsOption};
 use proto::rr::rdata::SOA;
 use proto::rr::{DNSClass, Name, RData, Record, RecordType};
 use proto::xfer::{DnsHandle, DnsRequestOptions, DnsResponse};
 
+use crate::clock::{Clock, SystemClock};
 use crate::dns_lru;
 use crate::dns_lru::DnsLru;
 use crate::error::*;
@@ -57,6 +61,29 @@ impl Drop for DepthTracker {
     }
 }
 
+/// Spawns the background work of the client, i.e. the refresh of cached records, onto the runtime
+#[derive(Clone)]
+pub(crate) struct Spawner(Arc<dyn Fn(Pin<Box<dyn Future<Output = ()> + Send>>) + Send + Sync>);
+
+impl Spawner {
+    pub(crate) fn new<F>(spawn: F) -> Self
+    where
+        F: Fn(Pin<Box<dyn Future<Output = ()> + Send>>) + Send + Sync + 'static,
+    {
+        Spawner(Arc::new(spawn))
+    }
+
+    fn spawn<F: Future<Output = ()> + Send + 'static>(&self, future: F) {
+        (self.0)(Box::pin(future))
+    }
+}
+
+impl fmt::Debug for Spawner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "spawner")
+    }
+}
+
 // TODO: need to consider this storage type as it compares to Authority in server...
 //       should it just be an variation on Authority?
 #[derive(Clone, Debug)]
@@ -70,6 +97,12 @@ where
     client: C,
     query_depth: Arc<AtomicU8>,
     preserve_intermediates: bool,
+    dnssec_ok: bool,
+    max_records: Option<usize>,
+    /// the expiry of the cached records is relative to the time read from this
+    clock: Arc<dyn Clock>,
+    prefetch_threshold: f32,
+    spawner: Option<Spawner>,
 }
 
 impl<C, E> CachingClient<C, E>
@@ -93,6 +126,48 @@ where
             client,
             query_depth,
             preserve_intermediates,
+            dnssec_ok: false,
+            max_records: None,
+            clock: Arc::new(SystemClock),
+            prefetch_threshold: 0.0,
+            spawner: None,
+        }
+    }
+
+    /// Keep the RRSIGs of the records in the responses, see `ResolverOpts::dnssec_ok`
+    pub(crate) fn set_dnssec_ok(&mut self, dnssec_ok: bool) {
+        self.dnssec_ok = dnssec_ok;
+    }
+
+    /// Fail lookups with more records than the maximum, see `ResolverOpts::max_records`
+    pub(crate) fn set_max_records(&mut self, max_records: Option<usize>) {
+        self.max_records = max_records;
+    }
+
+    /// Refresh cached records within the fraction of their TTL of expiring in the background, see
+    ///  `ResolverOpts::prefetch_threshold`
+    pub(crate) fn set_prefetch_threshold(&mut self, prefetch_threshold: f32) {
+        self.prefetch_threshold = prefetch_threshold;
+    }
+
+    /// Sets the spawner of the background refreshes, without one records are never prefetched
+    pub(crate) fn set_spawner(&mut self, spawner: Spawner) {
+        self.spawner = Some(spawner);
+    }
+
+    /// Replaces the clock the expiry of cached records is read from, e.g. to step past their TTLs
+    #[cfg(test)]
+    pub(crate) fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Returns the error if there are more records than the maximum
+    fn check_max_records(&self, count: usize) -> Result<(), ResolveError> {
+        match self.max_records {
+            Some(max_records) if count > max_records => {
+                Err(ResolveErrorKind::TooManyRecords { count, max_records }.into())
+            }
+            _ => Ok(()),
         }
     }
 
@@ -131,12 +206,19 @@ where
                 _ => &*DEFAULT,
             };
 
+            let now = client.clock.now();
             match usage.resolver() {
                 ResolverUsage::Loopback => match query.query_type() {
                     // TODO: look in hosts for these ips/names first...
-                    RecordType::A => return Ok(Lookup::from_rdata(query, LOCALHOST_V4.clone())),
-                    RecordType::AAAA => return Ok(Lookup::from_rdata(query, LOCALHOST_V6.clone())),
-                    RecordType::PTR => return Ok(Lookup::from_rdata(query, LOCALHOST.clone())),
+                    RecordType::A => {
+                        return Ok(Lookup::from_rdata_at(query, LOCALHOST_V4.clone(), now))
+                    }
+                    RecordType::AAAA => {
+                        return Ok(Lookup::from_rdata_at(query, LOCALHOST_V6.clone(), now))
+                    }
+                    RecordType::PTR => {
+                        return Ok(Lookup::from_rdata_at(query, LOCALHOST.clone(), now))
+                    }
                     _ => {
                         return Err(ResolveError::nx_error(
                             query,
@@ -168,19 +250,58 @@ where
         }
 
         let _tracker = DepthTracker::track(client.query_depth.clone());
-        let is_dnssec = client.client.is_verifying_dnssec();
 
         // first transition any polling that is needed (mutable refs...)
-        if let Some(cached_lookup) = client.from_cache(&query) {
+        let checking_disabled = options.checking_disabled;
+        if let Some(cached_lookup) = client.from_cache(&query, checking_disabled) {
+            client.prefetch(&query, &options);
             return cached_lookup;
         };
 
+        let lookup =
+            Self::lookup_upstream(query.clone(), options, client.clone(), preserved_records).await;
+
+        // when the name servers fail, expired records may still be served, RFC 8767
+        match lookup {
+            Err(e) if is_failure(&e) => match client.from_stale_cache(&query, checking_disabled) {
+                Some(stale_lookup) => {
+                    debug!("serving stale records of {} after: {}", query, e);
+                    Ok(stale_lookup)
+                }
+                None => Err(e),
+            },
+            lookup => lookup,
+        }
+    }
+
+    /// Performs the lookup against the upstream client, bypassing and then updating the cache
+    async fn lookup_upstream(
+        query: Query,
+        options: DnsRequestOptions,
+        mut client: Self,
+        preserved_records: Vec<(Record, u32)>,
+    ) -> Result<Lookup, ResolveError> {
+        let is_dnssec = client.client.is_verifying_dnssec();
+        let checking_disabled = options.checking_disabled;
+
         let response_message = client
             .client
             .lookup(query.clone(), options.clone())
             .await
             .map_err(E::into);
 
+        // answers to the EDNS Client Subnet option are only cached for the clients they cover
+        let scope_prefix = response_message.as_ref().map_or(0, scope_prefix);
+
+        // the records of an oversized response are not processed at all
+        if let Ok(ref response) = response_message {
+            let count = response
+                .messages()
+                .map(|m| m.answers().len() + m.name_servers().len() + m.additionals().len())
+                .sum();
+            client.check_max_records(count)?;
+        }
+
         // TODO: technically this might be duplicating work, as name_server already performs this evaluation.
         //  we may want to create a new type, if evaluated... but this is most generic to support any impl in LookupState...
         let response_message = if let Ok(response) = response_message {
@@ -188,6 +309,9 @@ where
         } else {
             response_message
         };
+        let authentic_data = response_message
+            .as_ref()
+            .map_or(false, |response| response.authentic_data());
 
         // TODO: take all records and cache them?
         //  if it's DNSSec they must be signed, otherwise?
@@ -235,15 +359,62 @@ where
             Ok(Records::CnameChain {
                 next: future,
                 min_ttl: ttl,
-            }) => client.cname(future.await?, query, ttl),
-            Ok(Records::Exists(rdata)) => client.cache(query, Ok(rdata)),
-            Err(e) => client.cache(query, Err(e)),
+            }) => {
+                // the chain is only authenticated if each of the responses was
+                let lookup = future.await?;
+                let authentic_data = authentic_data && lookup.authentic_data();
+                let lookup = lookup.with_authentic_data(authentic_data);
+                client.cname(lookup, query, checking_disabled, scope_prefix, ttl)
+            }
+            Ok(Records::Exists(rdata)) => {
+                client.cache(query, checking_disabled, scope_prefix, Ok((rdata, authentic_data)))
+            }
+            Err(e) => client.cache(query, checking_disabled, scope_prefix, Err(e)),
         }
     }
 
-    /// Check if this query is already cached
-    fn from_cache(&self, query: &Query) -> Option<Result<Lookup, ResolveError>> {
-        self.lru.get(query, Instant::now())
+    /// Check if this query is already cached, the cache is segmented by the CD bit of the request
+    fn from_cache(
+        &self,
+        query: &Query,
+        checking_disabled: bool,
+    ) -> Option<Result<Lookup, ResolveError>> {
+        self.lru.get(query, checking_disabled, self.clock.now())
+    }
+
+    /// The expired records of the query, if serving them stale is enabled, see
+    ///  `ResolverOpts::serve_stale_ttl`
+    fn from_stale_cache(&self, query: &Query, checking_disabled: bool) -> Option<Lookup> {
+        self.lru.get_stale(query, checking_disabled, self.clock.now())
+    }
+
+    /// Refreshes the cached records of the query in the background, if they are close to expiring
+    ///
+    /// Only one refresh is started for the cached records, the lookups in the meantime continue to
+    ///  be answered from the cache.
+    fn prefetch(&self, query: &Query, options: &DnsRequestOptions) {
+        let spawner = match self.spawner {
+            Some(ref spawner) if self.prefetch_threshold > 0.0 => spawner,
+            _ => return,
+        };
+
+        let now = self.clock.now();
+        let checking_disabled = options.checking_disabled;
+        if !self
+            .lru
+            .start_prefetch(query, checking_disabled, self.prefetch_threshold, now)
+        {
+            return;
+        }
+
+        debug!("prefetching: {}", query);
+        let refresh = Self::lookup_upstream(query.clone(), options.clone(), self.clone(), vec![]);
+        let query = query.clone();
+        spawner.spawn(async move {
+            if let Err(e) = refresh.await {
+                debug!("prefetch of {} failed: {}", query, e);
+            }
+        });
     }
 
     /// See https://tools.ietf.org/html/rfc2308
@@ -393,6 +564,13 @@ where
                             found_name = true;
                             return Some((r, ttl));
                         }
+                        // the signatures of the records, returned for the caller to validate
+                        if client.dnssec_ok
+                            && is_rrsig_of(&r, query.query_type())
+                            && (search_name.as_ref() == r.name() || query.name() == r.name())
+                        {
+                            return Some((r, ttl));
+                        }
                         // CNAME evaluation, it's an A/AAAA lookup and the record is from the CNAME lookup chain.
                         if client.preserve_intermediates
                             && r.rr_type() == RecordType::CNAME
@@ -420,6 +598,7 @@ where
 
             // adding the newly collected records to the preserved records
             preserved_records.extend(records);
+            client.check_max_records(preserved_records.len())?;
             if !preserved_records.is_empty() && found_name {
                 return Ok(Records::Exists(preserved_records));
             }
@@ -462,24 +641,55 @@ where
         }
     }
 
-    fn cname(&self, lookup: Lookup, query: Query, cname_ttl: u32) -> Result<Lookup, ResolveError> {
+    fn cname(
+        &self,
+        lookup: Lookup,
+        query: Query,
+        checking_disabled: bool,
+        scope_prefix: u8,
+        cname_ttl: u32,
+    ) -> Result<Lookup, ResolveError> {
         // this duplicates the cache entry under the original query
-        Ok(self.lru.duplicate(query, lookup, cname_ttl, Instant::now()))
+        let now = self.clock.now();
+        Ok(self.lru.duplicate(query, checking_disabled, scope_prefix, lookup, cname_ttl, now))
     }
 
+    /// Caches the records, with the AD bit of the response, or the error, for the clients within
+    ///  the `scope_prefix` of the EDNS Client Subnet option of the response
     fn cache(
         &self,
         query: Query,
-        records: Result<Vec<(Record, u32)>, ResolveError>,
+        checking_disabled: bool,
+        scope_prefix: u8,
+        records: Result<(Vec<(Record, u32)>, bool), ResolveError>,
     ) -> Result<Lookup, ResolveError> {
         // this will put this object into an inconsistent state, but no one should call poll again...
+        let now = self.clock.now();
         match records {
-            Ok(rdata) => Ok(self.lru.insert(query, rdata, Instant::now())),
-            Err(err) => Err(self.lru.negative(query, err, Instant::now())),
+            Ok((rdata, authentic_data)) => {
+                Ok(self.lru.insert(
+                    query,
+                    checking_disabled,
+                    scope_prefix,
+                    rdata,
+                    authentic_data,
+                    now,
+                ))
+            }
+            Err(err) => Err(self.lru.negative(query, checking_disabled, scope_prefix, err, now)),
         }
     }
 }
 
+/// The scope prefix of the EDNS Client Subnet option of the response, 0, i.e. covering all clients,
+///  without one
+fn scope_prefix(response: &DnsResponse) -> u8 {
+    match response.edns().and_then(|edns| edns.option(EdnsCode::Subnet)) {
+        Some(EdnsOption::Subnet(subnet)) => subnet.scope_prefix(),
+        _ => 0,
+    }
+}
+
 enum Records {
     /// The records exists, a vec of rdata with ttl
     Exists(Vec<(Record, u32)>),
@@ -490,6 +700,38 @@ enum Records {
     },
 }
 
+/// Returns true if the name servers failed to answer, rather than answering that there are no
+///  records, or with more records than allowed
+fn is_failure(error: &ResolveError) -> bool {
+    match error.kind() {
+        ResolveErrorKind::NoRecordsFound { response_code, .. } => {
+            *response_code == ResponseCode::ServFail
+                || *response_code == ResponseCode::Refused
+                || response_code.is_extended()
+        }
+        ResolveErrorKind::TooManyRecords { .. } => false,
+        _ => true,
+    }
+}
+
+/// True if the record is an RRSIG over the records of the type
+#[cfg(feature = "dnssec")]
+fn is_rrsig_of(record: &Record, record_type: RecordType) -> bool {
+    match record.rdata() {
+        RData::DNSSEC(DNSSECRData::SIG(sig)) => {
+            record.rr_type() == RecordType::DNSSEC(DNSSECRecordType::RRSIG)
+                && sig.type_covered() == record_type
+        }
+        _ => false,
+    }
+}
+
+/// Without the dnssec feature the RRSIGs are not decoded
+#[cfg(not(feature = "dnssec"))]
+fn is_rrsig_of(_record: &Record, _record_type: RecordType) -> bool {
+    false
+}
+
 // see also the lookup_tests.rs in integration-tests crate
 #[cfg(test)]
 mod tests {
@@ -501,8 +743,10 @@ mod tests {
     use proto::op::{Message, Query};
     use proto::rr::rdata::SRV;
     use proto::rr::{Name, Record};
+    use proto::xfer::DnsRequest;
 
     use super::*;
+    use crate::clock::MockClock;
     use crate::lookup_ip::tests::*;
 
     #[test]
@@ -537,6 +781,8 @@ mod tests {
         let query = Query::new();
         cache.insert(
             query.clone(),
+            false,
+            0,
             vec![(
                 Record::from_rdata(
                     query.name().clone(),
@@ -545,6 +791,7 @@ mod tests {
                 ),
                 u32::max_value(),
             )],
+            false,
             Instant::now(),
         );
 
@@ -603,6 +850,132 @@ mod tests {
         );
     }
 
+    pub fn nxdomain_message() -> Result<DnsResponse, ResolveError> {
+        let mut message = Message::new();
+        message.add_query(Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        message.set_response_code(ResponseCode::NXDomain);
+        message.insert_name_servers(vec![Record::from_rdata(
+            Name::from_str("example.com.").unwrap(),
+            3600,
+            RData::SOA(SOA::new(
+                Name::from_str("ns.example.com.").unwrap(),
+                Name::from_str("hostmaster.example.com.").unwrap(),
+                1,
+                3600,
+                600,
+                86400,
+                300,
+            )),
+        )]);
+        Ok(message.into())
+    }
+
+    #[test]
+    fn test_nxdomain_from_cache() {
+        let cache = DnsLru::new(1, dns_lru::TtlConfig::default());
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+
+        // first should come from client...
+        let client = mock(vec![nxdomain_message()]);
+        let client = CachingClient::with_cache(cache.clone(), client, false);
+
+        let err = block_on(CachingClient::inner_lookup(
+            query.clone(),
+            Default::default(),
+            client,
+            vec![],
+        ))
+        .unwrap_err();
+
+        // the SOA minimum bounds the negative ttl
+        if let ResolveErrorKind::NoRecordsFound {
+            negative_ttl,
+            response_code,
+            ..
+        } = err.kind()
+        {
+            assert_eq!(*negative_ttl, Some(300));
+            assert_eq!(*response_code, ResponseCode::NXDomain);
+        } else {
+            panic!("wrong error received: {}", err)
+        }
+
+        // next should come from cache, the client would fail if it were used...
+        let client = mock(vec![error()]);
+        let client = CachingClient::with_cache(cache.clone(), client, false);
+
+        let err = block_on(CachingClient::inner_lookup(
+            query.clone(),
+            Default::default(),
+            client,
+            vec![],
+        ))
+        .unwrap_err();
+
+        if let ResolveErrorKind::NoRecordsFound { response_code, .. } = err.kind() {
+            assert_eq!(*response_code, ResponseCode::NXDomain);
+        } else {
+            panic!("wrong error received: {}", err)
+        }
+
+        // and expires with the negative ttl
+        let now = Instant::now();
+        assert!(cache.get(&query, false, now + Duration::from_secs(299)).is_some());
+        assert!(cache.get(&query, false, now + Duration::from_secs(301)).is_none());
+    }
+
+    fn ad_message(ip: Ipv4Addr, authentic_data: bool) -> Result<DnsResponse, ResolveError> {
+        let mut message = Message::new();
+        message.add_query(Query::query(Name::root(), RecordType::A));
+        message.insert_answers(vec![Record::from_rdata(Name::root(), 86400, RData::A(ip))]);
+        message.set_authentic_data(authentic_data);
+        Ok(message.into())
+    }
+
+    fn cd_lookup(cache: &DnsLru, message: Result<DnsResponse, ResolveError>, cd: bool) -> Lookup {
+        let client = CachingClient::with_cache(cache.clone(), mock(vec![message]), false);
+        let options = DnsRequestOptions {
+            checking_disabled: cd,
+            ..Default::default()
+        };
+
+        block_on(CachingClient::inner_lookup(
+            Query::query(Name::root(), RecordType::A),
+            options,
+            client,
+            vec![],
+        ))
+        .expect("lookup failed")
+    }
+
+    #[test]
+    fn test_checking_disabled_not_shared() {
+        let cache = DnsLru::new(2, dns_lru::TtlConfig::default());
+        let validated = Ipv4Addr::new(127, 0, 0, 1);
+        let unvalidated = Ipv4Addr::new(127, 0, 0, 2);
+
+        let lookup = cd_lookup(&cache, ad_message(validated, true), false);
+        assert_eq!(lookup.iter().next(), Some(&RData::A(validated)));
+        assert!(lookup.authentic_data());
+
+        // the validated answer is not returned with CD set, the request is sent
+        let lookup = cd_lookup(&cache, ad_message(unvalidated, false), true);
+        assert_eq!(lookup.iter().next(), Some(&RData::A(unvalidated)));
+        assert!(!lookup.authentic_data());
+
+        // both are now cached, with the AD bit, the client would fail if it were used...
+        let lookup = cd_lookup(&cache, error(), false);
+        assert_eq!(lookup.iter().next(), Some(&RData::A(validated)));
+        assert!(lookup.authentic_data());
+
+        let lookup = cd_lookup(&cache, error(), true);
+        assert_eq!(lookup.iter().next(), Some(&RData::A(unvalidated)));
+        assert!(!lookup.authentic_data());
+    }
+
     pub fn cname_message() -> Result<DnsResponse, ResolveError> {
         let mut message = Message::new();
         message.add_query(Query::query(
@@ -846,6 +1219,272 @@ mod tests {
         cname_ttl_test(2, 1);
     }
 
+    fn cname_message(name: &str, target: &str) -> Result<DnsResponse, ResolveError> {
+        let name = Name::from_str(name).unwrap();
+        let mut message = Message::new();
+        message.add_query(Query::query(name.clone(), RecordType::A));
+        message.insert_answers(vec![Record::from_rdata(
+            name,
+            86400,
+            RData::CNAME(Name::from_str(target).unwrap()),
+        )]);
+        Ok(message.into())
+    }
+
+    #[test]
+    fn test_cname_chain() {
+        let mut message = Message::new();
+        message.add_query(Query::query(Name::from_str("c.example.com.").unwrap(), RecordType::A));
+        message.insert_answers(vec![Record::from_rdata(
+            Name::from_str("c.example.com.").unwrap(),
+            86400,
+            RData::A(Ipv4Addr::new(127, 0, 0, 1)),
+        )]);
+
+        // the mock pops responses off the end
+        let client = mock(vec![
+            Ok(message.into()),
+            cname_message("b.example.com.", "c.example.com."),
+            cname_message("a.example.com.", "b.example.com."),
+        ]);
+        let cache = DnsLru::new(8, dns_lru::TtlConfig::default());
+        let mut client = CachingClient::with_cache(cache, client, true);
+
+        let query = Query::query(Name::from_str("a.example.com.").unwrap(), RecordType::A);
+        let lookup = block_on(client.lookup(query, Default::default())).expect("lookup failed");
+
+        assert_eq!(
+            lookup.iter().cloned().collect::<Vec<_>>(),
+            vec![
+                RData::CNAME(Name::from_str("b.example.com.").unwrap()),
+                RData::CNAME(Name::from_str("c.example.com.").unwrap()),
+                RData::A(Ipv4Addr::new(127, 0, 0, 1)),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "dnssec")]
+    fn test_dnssec_ok_keeps_rrsigs() {
+        use proto::rr::dnssec::rdata::SIG;
+        use proto::rr::dnssec::Algorithm;
+
+        let name = Name::from_str("www.example.com.").unwrap();
+        let rrsig = |type_covered| {
+            let sig = SIG::new(
+                type_covered,
+                Algorithm::ECDSAP256SHA256,
+                3,
+                86400,
+                1_700_000_000,
+                1_600_000_000,
+                12345,
+                Name::from_str("example.com.").unwrap(),
+                vec![1, 2, 3, 4],
+            );
+            let mut record =
+                Record::from_rdata(name.clone(), 86400, RData::DNSSEC(DNSSECRData::SIG(sig)));
+            record.set_rr_type(RecordType::DNSSEC(DNSSECRecordType::RRSIG));
+            record
+        };
+
+        let message = || {
+            let mut message = Message::new();
+            message.add_query(Query::query(name.clone(), RecordType::A));
+            message.insert_answers(vec![
+                Record::from_rdata(name.clone(), 86400, RData::A(Ipv4Addr::new(127, 0, 0, 1))),
+                rrsig(RecordType::A),
+            ]);
+            // the signature of other records at the name is not part of the answer
+            message.insert_additionals(vec![rrsig(RecordType::AAAA)]);
+            message
+        };
+        let query = Query::query(name.clone(), RecordType::A);
+
+        // without the DO bit the signatures are stripped
+        let cache = DnsLru::new(8, dns_lru::TtlConfig::default());
+        let mut client = CachingClient::with_cache(cache, mock(vec![Ok(message().into())]), false);
+        let lookup = block_on(client.lookup(query.clone(), Default::default())).unwrap();
+        assert_eq!(lookup.record_iter().count(), 1);
+
+        let cache = DnsLru::new(8, dns_lru::TtlConfig::default());
+        let mut client = CachingClient::with_cache(cache, mock(vec![Ok(message().into())]), false);
+        client.set_dnssec_ok(true);
+        let lookup = block_on(client.lookup(query, Default::default())).unwrap();
+
+        let records: Vec<&Record> = lookup.record_iter().collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].rdata(), &RData::A(Ipv4Addr::new(127, 0, 0, 1)));
+        assert_eq!(records[1].rr_type(), RecordType::DNSSEC(DNSSECRecordType::RRSIG));
+        assert_eq!(records[1].rdata(), rrsig(RecordType::A).rdata());
+    }
+
+    #[test]
+    fn test_max_records() {
+        let name = Name::from_str("www.example.com.").unwrap();
+        let mut message = Message::new();
+        message.add_query(Query::query(name.clone(), RecordType::A));
+        let a = |i| Record::from_rdata(name.clone(), 86400, RData::A(Ipv4Addr::new(10, 0, 0, i)));
+        message.insert_answers((0..10).map(a).collect());
+
+        let cache = DnsLru::new(8, dns_lru::TtlConfig::default());
+        let mut client = CachingClient::with_cache(cache, mock(vec![Ok(message.into())]), false);
+        client.set_max_records(Some(5));
+
+        let query = Query::query(name, RecordType::A);
+        let error = block_on(client.lookup(query, Default::default())).unwrap_err();
+        match error.kind() {
+            ResolveErrorKind::TooManyRecords { count, max_records } => {
+                assert_eq!(*count, 10);
+                assert_eq!(*max_records, 5);
+            }
+            kind => panic!("expected too many records: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn test_prefetch() {
+        let clock = MockClock::new();
+        let cache = DnsLru::new(1, dns_lru::TtlConfig::default());
+        let mut client =
+            CachingClient::with_cache(cache, mock(vec![v4_message(), v4_message()]), false);
+        client.set_clock(Arc::new(clock.clone()));
+        client.set_prefetch_threshold(0.1);
+
+        let spawned = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let spawner = spawned.clone();
+        client.set_spawner(Spawner::new(move |future| spawner.lock().push(future)));
+
+        let ttl = |lookup: Lookup| lookup.record_iter().next().unwrap().ttl();
+        assert_eq!(ttl(block_on(client.lookup(Query::new(), Default::default())).unwrap()), 86400);
+
+        // outside of the last tenth of the TTL
+        clock.advance(Duration::from_secs(77759));
+        block_on(client.lookup(Query::new(), Default::default())).unwrap();
+        assert!(spawned.lock().is_empty());
+
+        // within it, the records are still answered from the cache, refreshed only once
+        clock.advance(Duration::from_secs(1));
+        for _ in 0..3 {
+            let lookup = block_on(client.lookup(Query::new(), Default::default())).unwrap();
+            assert_eq!(ttl(lookup), 8640);
+        }
+        let refresh = {
+            let mut spawned = spawned.lock();
+            assert_eq!(spawned.len(), 1);
+            spawned.pop().unwrap()
+        };
+
+        block_on(refresh);
+        assert_eq!(ttl(block_on(client.lookup(Query::new(), Default::default())).unwrap()), 86400);
+        assert!(spawned.lock().is_empty());
+    }
+
+    #[test]
+    fn test_serve_stale() {
+        let clock = MockClock::new();
+        let ttls = dns_lru::TtlConfig {
+            serve_stale_ttl: Some(Duration::from_secs(3600)),
+            ..Default::default()
+        };
+        let cache = DnsLru::new(1, ttls);
+        let client = mock(vec![error(), error(), v4_message()]);
+        let mut client = CachingClient::with_cache(cache, client, false);
+        client.set_clock(Arc::new(clock.clone()));
+
+        let lookup = block_on(client.lookup(Query::new(), Default::default())).unwrap();
+        assert!(!lookup.is_stale());
+
+        // expired, the name servers fail, so the records are served stale
+        clock.advance(Duration::from_secs(86400 + 60));
+        let lookup = block_on(client.lookup(Query::new(), Default::default())).unwrap();
+        assert!(lookup.is_stale());
+        assert_eq!(lookup.record_iter().next().unwrap().ttl(), dns_lru::STALE_TTL);
+        assert_eq!(
+            lookup.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::A(Ipv4Addr::new(127, 0, 0, 1))]
+        );
+
+        // past the window of serving them stale, the failure is returned
+        clock.advance(Duration::from_secs(3600));
+        assert!(block_on(client.lookup(Query::new(), Default::default())).is_err());
+    }
+
+    #[test]
+    fn test_max_records_cname_chain() {
+        let mut message = Message::new();
+        message.add_query(Query::query(Name::from_str("c.example.com.").unwrap(), RecordType::A));
+        message.insert_answers(vec![Record::from_rdata(
+            Name::from_str("c.example.com.").unwrap(),
+            86400,
+            RData::A(Ipv4Addr::new(127, 0, 0, 1)),
+        )]);
+
+        // each response is within the maximum, the chain of them is not
+        let client = mock(vec![
+            Ok(message.into()),
+            cname_message("b.example.com.", "c.example.com."),
+            cname_message("a.example.com.", "b.example.com."),
+        ]);
+        let cache = DnsLru::new(8, dns_lru::TtlConfig::default());
+        let mut client = CachingClient::with_cache(cache, client, true);
+        client.set_max_records(Some(2));
+
+        let query = Query::query(Name::from_str("a.example.com.").unwrap(), RecordType::A);
+        let error = block_on(client.lookup(query, Default::default())).unwrap_err();
+        assert!(
+            matches!(error.kind(), ResolveErrorKind::TooManyRecords { count: 3, .. }),
+            "{}",
+            error
+        );
+    }
+
+    /// Answers every query for `a` with a CNAME to `b`, and every other query with a CNAME to `a`
+    #[derive(Clone)]
+    struct CnameLoopHandle {
+        sends: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    impl DnsHandle for CnameLoopHandle {
+        type Response =
+            Pin<Box<dyn Future<Output = Result<DnsResponse, ResolveError>> + Send + Unpin>>;
+        type Error = ResolveError;
+
+        fn send<R: Into<DnsRequest> + Unpin + Send + 'static>(
+            &mut self,
+            request: R,
+        ) -> Self::Response {
+            self.sends.fetch_add(1, Ordering::SeqCst);
+
+            let request = request.into();
+            let name = request.queries()[0].name().to_string();
+            let response = if name == "a.example.com." {
+                cname_message("a.example.com.", "b.example.com.")
+            } else {
+                cname_message(&name, "a.example.com.")
+            };
+
+            Box::pin(futures_util::future::ready(response))
+        }
+    }
+
+    #[test]
+    fn test_cname_loop() {
+        let sends = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let client = CnameLoopHandle {
+            sends: sends.clone(),
+        };
+        let cache = DnsLru::new(8, dns_lru::TtlConfig::default());
+        let mut client = CachingClient::with_cache(cache, client, false);
+
+        let query = Query::query(Name::from_str("a.example.com.").unwrap(), RecordType::A);
+        let error = block_on(client.lookup(query, Default::default()))
+            .expect_err("the loop should have been stopped");
+
+        assert!(matches!(error.kind(), ResolveErrorKind::NoRecordsFound { .. }));
+        assert_eq!(sends.load(Ordering::SeqCst), usize::from(MAX_QUERY_DEPTH));
+    }
+
     #[test]
     fn test_early_return_localhost() {
         let cache = DnsLru::new(0, dns_lru::TtlConfig::default());
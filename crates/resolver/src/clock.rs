@@ -0,0 +1,66 @@
+// Copyright 2015-2020 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The source of the current time for the time-based logic of the resolver
+
+use std::fmt::Debug;
+use std::time::Instant;
+
+#[cfg(test)]
+pub(crate) use self::mock::MockClock;
+
+/// Reads the current time, e.g. for the backoff from reconnecting to failed name servers and the
+///  expiry of cached records
+///
+/// This is the `SystemClock`, other than in tests which step through time with a `MockClock`,
+///  rather than sleeping.
+pub(crate) trait Clock: Debug + Send + Sync {
+    /// The current time
+    fn now(&self) -> Instant;
+}
+
+/// The monotonic clock of the system, i.e. `Instant::now`
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+#[cfg(test)]
+mod mock {
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use parking_lot::Mutex;
+
+    use super::Clock;
+
+    /// A clock which stands still until it is advanced, clones share the same time
+    #[derive(Clone, Debug)]
+    pub(crate) struct MockClock(Arc<Mutex<Instant>>);
+
+    impl MockClock {
+        /// A clock starting at the current time of the system
+        pub(crate) fn new() -> Self {
+            MockClock(Arc::new(Mutex::new(Instant::now())))
+        }
+
+        /// Moves the time of the clock forward
+        pub(crate) fn advance(&self, duration: Duration) {
+            *self.0.lock() += duration;
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> Instant {
+            *self.0.lock()
+        }
+    }
+}
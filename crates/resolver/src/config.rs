@@ -7,17 +7,26 @@
 
 //! Configuration for a resolver
 use std::fmt;
+#[cfg(any(feature = "dns-over-tls", feature = "dns-over-https"))]
+use std::io;
+#[cfg(any(feature = "dns-over-tls", feature = "dns-over-https"))]
+use std::net::ToSocketAddrs;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::ops::{Deref, DerefMut};
-use std::time::Duration;
-
-#[cfg(feature = "dns-over-rustls")]
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
-use proto::rr::Name;
+use proto::error::ProtoResult;
+use proto::op::{Message, MessageFinalizer};
+#[cfg(feature = "dnssec")]
+use proto::rr::dnssec::TrustAnchorStore;
+use proto::rr::{Name, Record};
 #[cfg(feature = "dns-over-rustls")]
 use rustls::ClientConfig;
 
+use crate::error::ResolveError;
+
 #[cfg(all(feature = "serde-config", feature = "dns-over-rustls"))]
 use serde::{
     de::{Deserialize as DeserializeT, Deserializer},
@@ -36,6 +45,13 @@ pub struct ResolverConfig {
     search: Vec<Name>,
     // nameservers to use for resolution.
     name_servers: NameServerConfigGroup,
+    // nameservers to use for resolution of names in each zone, in place of `name_servers`
+    #[cfg_attr(feature = "serde-config", serde(default))]
+    forward_zones: Vec<(Name, NameServerConfigGroup)>,
+    // trust anchor for DNSSEC validation, the root anchors are used if not set
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(feature = "serde-config", serde(skip))]
+    trust_anchor: Option<TrustAnchorStore>,
 }
 
 impl ResolverConfig {
@@ -46,6 +62,9 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::new(),
+            forward_zones: vec![],
+            #[cfg(feature = "dnssec")]
+            trust_anchor: None,
         }
     }
 
@@ -60,6 +79,27 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::google(),
+            forward_zones: vec![],
+            #[cfg(feature = "dnssec")]
+            trust_anchor: None,
+        }
+    }
+
+    /// Creates a configuration, using `8.8.8.8`, `8.8.4.4` and `2001:4860:4860::8888`, `2001:4860:4860::8844` (thank you, Google). This limits the registered connections to just HTTPS lookups
+    ///
+    /// Please see Google's [privacy statement](https://developers.google.com/speed/public-dns/privacy) for important information about what they track, many ISP's track similar information in DNS.
+    ///
+    /// NameServerConfigGroups can be combined to use a set of different providers, see `NameServerConfigGroup` and `ResolverConfig::from_parts`
+    #[cfg(feature = "dns-over-https")]
+    pub fn google_https() -> Self {
+        ResolverConfig {
+            // TODO: this should get the hostname and use the basename as the default
+            domain: None,
+            search: vec![],
+            name_servers: NameServerConfigGroup::google_https(),
+            forward_zones: vec![],
+            #[cfg(feature = "dnssec")]
+            trust_anchor: None,
         }
     }
 
@@ -74,6 +114,9 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::cloudflare(),
+            forward_zones: vec![],
+            #[cfg(feature = "dnssec")]
+            trust_anchor: None,
         }
     }
 
@@ -89,6 +132,9 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::cloudflare_tls(),
+            forward_zones: vec![],
+            #[cfg(feature = "dnssec")]
+            trust_anchor: None,
         }
     }
 
@@ -104,6 +150,9 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::cloudflare_https(),
+            forward_zones: vec![],
+            #[cfg(feature = "dnssec")]
+            trust_anchor: None,
         }
     }
 
@@ -118,6 +167,9 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::quad9(),
+            forward_zones: vec![],
+            #[cfg(feature = "dnssec")]
+            trust_anchor: None,
         }
     }
 
@@ -133,6 +185,9 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::quad9_tls(),
+            forward_zones: vec![],
+            #[cfg(feature = "dnssec")]
+            trust_anchor: None,
         }
     }
 
@@ -152,9 +207,31 @@ impl ResolverConfig {
             domain,
             search,
             name_servers: name_servers.into(),
+            forward_zones: vec![],
+            #[cfg(feature = "dnssec")]
+            trust_anchor: None,
         }
     }
 
+    /// Returns a builder for the configuration, and the `ResolverOpts` to use with it
+    ///
+    /// ```
+    /// use trust_dns_resolver::config::ResolverConfig;
+    ///
+    /// let (config, options) = ResolverConfig::builder()
+    ///     .add_udp("8.8.8.8:53")
+    ///     .search("example.com")
+    ///     .ndots(2)
+    ///     .build()
+    ///     .expect("invalid configuration");
+    ///
+    /// assert_eq!(config.name_servers().len(), 1);
+    /// assert_eq!(options.ndots, 2);
+    /// ```
+    pub fn builder() -> ResolverConfigBuilder {
+        ResolverConfigBuilder::default()
+    }
+
     /// Returns the local domain
     ///
     /// By default any names will be appended to all non-fully-qualified-domain names, and searched for after any ndots rules
@@ -191,6 +268,37 @@ impl ResolverConfig {
         &self.name_servers
     }
 
+    /// Forward lookups of names in the `zone`, and its subdomains, to the `name_servers`
+    ///
+    /// The zone with the longest match of the name is used, names outside of all of the zones are
+    ///  resolved by the `name_servers` of the config, e.g. to resolve `company.internal.` with an
+    ///  internal resolver and everything else with public DNS.
+    pub fn add_forward_zone(&mut self, zone: Name, name_servers: NameServerConfigGroup) {
+        self.forward_zones.push((zone, name_servers));
+    }
+
+    /// Returns the zones forwarded to other name servers, see `add_forward_zone`
+    pub fn forward_zones(&self) -> &[(Name, NameServerConfigGroup)] {
+        &self.forward_zones
+    }
+
+    /// Returns the trust anchors used for DNSSEC validation, if they have been set
+    ///
+    /// When `None`, the default root trust anchors are used, see `ResolverOpts::validate`
+    #[cfg(feature = "dnssec")]
+    pub fn trust_anchor(&self) -> Option<&TrustAnchorStore> {
+        self.trust_anchor.as_ref()
+    }
+
+    /// Set the trust anchors used for DNSSEC validation in place of the default root trust anchors
+    ///
+    /// Either a `TrustAnchor` of trusted keys, or a `TrustAnchorStore` of DNSKEY and DS records,
+    ///  e.g. loaded with `TrustAnchorStore::from_root_anchors_file`.
+    #[cfg(feature = "dnssec")]
+    pub fn set_trust_anchor<T: Into<TrustAnchorStore>>(&mut self, trust_anchor: T) {
+        self.trust_anchor = Some(trust_anchor.into());
+    }
+
     /// return the associated TlsClientConfig
     #[cfg(feature = "dns-over-rustls")]
     pub fn client_config(&self) -> &Option<TlsClientConfig> {
@@ -224,6 +332,160 @@ impl ResolverConfig {
     }
 }
 
+/// Builds a `ResolverConfig`, see `ResolverConfig::builder`
+///
+/// The addresses and names are only checked by `build`, which returns the first that is invalid.
+#[derive(Clone, Debug, Default)]
+pub struct ResolverConfigBuilder {
+    domain: Option<String>,
+    search: Vec<String>,
+    name_servers: Vec<NameServerConfig>,
+    trust_nx_responses: bool,
+    options: ResolverOpts,
+    error: Option<String>,
+}
+
+impl ResolverConfigBuilder {
+    /// Adds a name server, e.g. `8.8.8.8:53`, for both UDP and TCP
+    pub fn add_dual(self, socket_addr: &str) -> Self {
+        self.add_protocol(socket_addr, Protocol::Dual, None)
+    }
+
+    /// Adds a name server, e.g. `8.8.8.8:53`, for UDP
+    pub fn add_udp(self, socket_addr: &str) -> Self {
+        self.add_protocol(socket_addr, Protocol::Udp, None)
+    }
+
+    /// Adds a name server, e.g. `8.8.8.8:53`, for TCP
+    pub fn add_tcp(self, socket_addr: &str) -> Self {
+        self.add_protocol(socket_addr, Protocol::Tcp, None)
+    }
+
+    /// Adds a name server for DNS-over-TLS
+    ///
+    /// # Arguments
+    ///
+    /// * `socket_addr` - the address of the name server, e.g. `1.1.1.1:853`
+    /// * `tls_dns_name` - the name which the certificate of the name server is verified against
+    #[cfg(feature = "dns-over-tls")]
+    pub fn add_tls(self, socket_addr: &str, tls_dns_name: &str) -> Self {
+        self.add_protocol(socket_addr, Protocol::Tls, Some(tls_dns_name))
+    }
+
+    /// Adds a name server for DNS-over-HTTPS
+    ///
+    /// # Arguments
+    ///
+    /// * `socket_addr` - the address of the name server, e.g. `1.1.1.1:443`
+    /// * `tls_dns_name` - the name which the certificate of the name server is verified against
+    #[cfg(feature = "dns-over-https")]
+    pub fn add_https(self, socket_addr: &str, tls_dns_name: &str) -> Self {
+        self.add_protocol(socket_addr, Protocol::Https, Some(tls_dns_name))
+    }
+
+    /// Adds the configuration for a name server, for the options not covered by the other methods
+    pub fn add_name_server(mut self, name_server: NameServerConfig) -> Self {
+        self.name_servers.push(name_server);
+        self
+    }
+
+    fn add_protocol(mut self, socket_addr: &str, protocol: Protocol, name: Option<&str>) -> Self {
+        let socket_addr = match socket_addr.parse::<SocketAddr>() {
+            Ok(socket_addr) => socket_addr,
+            Err(e) => {
+                self.error
+                    .get_or_insert_with(|| format!("invalid name server {}: {}", socket_addr, e));
+                return self;
+            }
+        };
+
+        self.add_name_server(NameServerConfig {
+            socket_addr,
+            protocol,
+            tls_dns_name: name.map(ToString::to_string),
+            trust_nx_responses: false,
+            signer: None,
+            weight: None,
+            bind_addr: None,
+            #[cfg(feature = "dns-over-rustls")]
+            tls_config: None,
+        })
+    }
+
+    /// Sets the local domain, which is also the first of the search domains
+    pub fn domain(mut self, domain: &str) -> Self {
+        self.domain = Some(domain.to_string());
+        self
+    }
+
+    /// Adds a search domain, searched after the local domain in the order added
+    pub fn search(mut self, search: &str) -> Self {
+        self.search.push(search.to_string());
+        self
+    }
+
+    /// Trust the negative responses of all of the name servers, see
+    ///  `NameServerConfig::trust_nx_responses`
+    pub fn trust_nx_responses(mut self, trust_nx_responses: bool) -> Self {
+        self.trust_nx_responses = trust_nx_responses;
+        self
+    }
+
+    /// Replaces the options returned with the configuration, the defaults are used otherwise
+    pub fn options(mut self, options: ResolverOpts) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets `ResolverOpts::ndots` of the options returned with the configuration
+    pub fn ndots(mut self, ndots: usize) -> Self {
+        self.options.ndots = ndots;
+        self
+    }
+
+    /// Returns the configuration, and the options, or an error for the first invalid input
+    ///
+    /// At least one name server must be added, and those for DNS-over-TLS or DNS-over-HTTPS must
+    ///  have a DNS name to verify the certificate of the name server against.
+    pub fn build(self) -> Result<(ResolverConfig, ResolverOpts), ResolveError> {
+        if let Some(error) = self.error {
+            return Err(error.into());
+        }
+
+        if self.name_servers.is_empty() {
+            return Err("no name servers were added".into());
+        }
+
+        let mut config = ResolverConfig::new();
+        for mut name_server in self.name_servers {
+            let has_dns_name = name_server
+                .tls_dns_name
+                .as_ref()
+                .map_or(false, |name| !name.is_empty());
+            if name_server.protocol.is_encrypted() && !has_dns_name {
+                return Err(format!(
+                    "name server {} requires a DNS name for {}",
+                    name_server.socket_addr, name_server.protocol
+                )
+                .into());
+            }
+
+            name_server.trust_nx_responses |= self.trust_nx_responses;
+            config.add_name_server(name_server);
+        }
+
+        if let Some(domain) = self.domain {
+            config.set_domain(Name::from_str(&domain)?);
+        }
+
+        for search in self.search {
+            config.add_search(Name::from_str(&search)?);
+        }
+
+        Ok((config, self.options))
+    }
+}
+
 impl Default for ResolverConfig {
     /// Creates a default configuration, using `8.8.8.8`, `8.8.4.4` and `2001:4860:4860::8888`, `2001:4860:4860::8844` (thank you, Google).
     ///
@@ -245,6 +507,9 @@ pub enum Protocol {
     Udp,
     /// TCP can be used for large queries, but not all NameServers support it
     Tcp,
+    /// UDP is preferred, the same NameServer is queried over TCP for zone transfers and when the
+    /// UDP response is truncated
+    Dual,
     /// Tls for DNS over TLS
     #[cfg(feature = "dns-over-tls")]
     Tls,
@@ -261,6 +526,7 @@ impl fmt::Display for Protocol {
         let protocol = match self {
             Protocol::Udp => "udp",
             Protocol::Tcp => "tcp",
+            Protocol::Dual => "dual",
             #[cfg(feature = "dns-over-tls")]
             Protocol::Tls => "tls",
             #[cfg(feature = "dns-over-https")]
@@ -279,6 +545,7 @@ impl Protocol {
         match self {
             Protocol::Udp => true,
             Protocol::Tcp => false,
+            Protocol::Dual => true,
             #[cfg(feature = "dns-over-tls")]
             Protocol::Tls => false,
             #[cfg(feature = "dns-over-https")]
@@ -293,11 +560,29 @@ impl Protocol {
         !self.is_datagram()
     }
 
+    /// Returns true if the NameServer can be queried through a proxy, see `ResolverOpts::proxy`
+    ///
+    /// UDP can not be tunneled, so UDP and dual NameServers are queried over TCP instead.
+    pub fn is_proxyable(self) -> bool {
+        match self {
+            Protocol::Udp => true,
+            Protocol::Tcp => true,
+            Protocol::Dual => true,
+            #[cfg(feature = "dns-over-tls")]
+            Protocol::Tls => false,
+            #[cfg(feature = "dns-over-https")]
+            Protocol::Https => false,
+            #[cfg(feature = "mdns")]
+            Protocol::Mdns => false,
+        }
+    }
+
     /// Is this an encrypted protocol, i.e. TLS or HTTPS
     pub fn is_encrypted(self) -> bool {
         match self {
             Protocol::Udp => false,
             Protocol::Tcp => false,
+            Protocol::Dual => false,
             #[cfg(feature = "dns-over-tls")]
             Protocol::Tls => true,
             #[cfg(feature = "dns-over-https")]
@@ -338,11 +623,41 @@ impl std::fmt::Debug for TlsClientConfig {
     }
 }
 
+/// Signs outgoing update messages, e.g. with a SIG(0) `Signer` for authenticated dynamic updates
+#[derive(Clone)]
+pub struct MessageSigner(pub Arc<dyn MessageFinalizer>);
+
+impl MessageFinalizer for MessageSigner {
+    fn finalize_message(&self, message: &Message, current_time: u32) -> ProtoResult<Vec<Record>> {
+        self.0.finalize_message(message, current_time)
+    }
+}
+
+impl std::cmp::PartialEq for MessageSigner {
+    fn eq(&self, other: &Self) -> bool {
+        // only the data pointers are compared, vtables may differ across codegen units
+        Arc::as_ptr(&self.0) as *const u8 == Arc::as_ptr(&other.0) as *const u8
+    }
+}
+
+impl std::cmp::Eq for MessageSigner {}
+
+impl std::fmt::Debug for MessageSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "message signer")
+    }
+}
+
 /// Configuration for the NameServer
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
 pub struct NameServerConfig {
     /// The address which the DNS NameServer is registered at.
+    ///
+    /// This address is connected to as is, it is independent of `tls_dns_name`, which is never
+    /// resolved. Pinning the address of an encrypted name server this way means that no other
+    /// resolver is needed to bootstrap the connection, see
+    /// `NameServerConfigGroup::from_host_pinned`.
     pub socket_addr: SocketAddr,
     /// The protocol to use when communicating with the NameServer.
     #[cfg_attr(feature = "serde-config", serde(default))]
@@ -356,6 +671,24 @@ pub struct NameServerConfig {
     /// retried against other configured name servers.
     #[cfg_attr(feature = "serde-config", serde(default))]
     pub trust_nx_responses: bool,
+    /// Optional signer for outgoing update messages, not supported for DNS over HTTPS
+    #[cfg_attr(feature = "serde-config", serde(skip))]
+    pub signer: Option<MessageSigner>,
+    /// Optional weight of this NameServer relative to the others in the pool, defaults to 1
+    ///
+    /// When any NameServer in the pool has a weight, requests are spread in proportion to their
+    /// weights across the NameServers ranked equally by the `ServerOrderingStrategy`, i.e. all the
+    /// healthy ones for `RoundRobin` and those with the same stats for `QueryStats`, the others
+    /// remain as fallbacks. A weight of 0 will only be used as a fallback.
+    #[cfg_attr(feature = "serde-config", serde(default))]
+    pub weight: Option<u8>,
+    /// Optional local address to send queries to this NameServer from, only used for UDP and TCP
+    ///
+    /// UDP queries are sent from a random port of the address, its port is ignored, for TCP a port
+    /// of 0 will pick a random port. An address which is not local is an error of
+    /// `NameServer::new`.
+    #[cfg_attr(feature = "serde-config", serde(default))]
+    pub bind_addr: Option<SocketAddr>,
     #[cfg(feature = "dns-over-rustls")]
     #[cfg_attr(feature = "serde-config", serde(skip))]
     /// optional configuration for the tls client
@@ -434,6 +767,9 @@ impl NameServerConfigGroup {
                 protocol: Protocol::Udp,
                 tls_dns_name: None,
                 trust_nx_responses,
+                signer: None,
+                weight: None,
+                bind_addr: None,
                 #[cfg(feature = "dns-over-rustls")]
                 tls_config: None,
             };
@@ -442,6 +778,9 @@ impl NameServerConfigGroup {
                 protocol: Protocol::Tcp,
                 tls_dns_name: None,
                 trust_nx_responses,
+                signer: None,
+                weight: None,
+                bind_addr: None,
                 #[cfg(feature = "dns-over-rustls")]
                 tls_config: None,
             };
@@ -453,6 +792,32 @@ impl NameServerConfigGroup {
         name_servers
     }
 
+    /// Configure a NameServer address and port, as a single dual UDP and TCP NameServer
+    ///
+    /// UDP is preferred, the same NameServer is queried over TCP when needed, see
+    /// [`Protocol::Dual`].
+    pub fn from_ips_dual(ips: &[IpAddr], port: u16, trust_nx_responses: bool) -> Self {
+        let mut name_servers = Self::with_capacity(ips.len());
+
+        for ip in ips {
+            let config = NameServerConfig {
+                socket_addr: SocketAddr::new(*ip, port),
+                protocol: Protocol::Dual,
+                tls_dns_name: None,
+                trust_nx_responses,
+                signer: None,
+                weight: None,
+                bind_addr: None,
+                #[cfg(feature = "dns-over-rustls")]
+                tls_config: None,
+            };
+
+            name_servers.push(config);
+        }
+
+        name_servers
+    }
+
     #[cfg(any(feature = "dns-over-tls", feature = "dns-over-https"))]
     fn from_ips_encrypted(
         ips: &[IpAddr],
@@ -471,6 +836,9 @@ impl NameServerConfigGroup {
                 protocol,
                 tls_dns_name: Some(tls_dns_name.clone()),
                 trust_nx_responses,
+                signer: None,
+                weight: None,
+                bind_addr: None,
                 #[cfg(feature = "dns-over-rustls")]
                 tls_config: None,
             };
@@ -507,6 +875,56 @@ impl NameServerConfigGroup {
         Self::from_ips_encrypted(ips, port, tls_dns_name, Protocol::Https, trust_nx_responses)
     }
 
+    /// Pre-resolves the host name of an encrypted name server with the system resolver, pinning
+    ///  each of its addresses
+    ///
+    /// The host name is kept as the `tls_dns_name` to verify the certificate of the name server
+    ///  against. The returned configuration can be stored, so that later connections to the name
+    ///  server need no lookup of its host name.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - the host name of the name server, e.g. `dns.google`
+    /// * `port` - the port of the name server, e.g. `853` for DNS-over-TLS
+    /// * `protocol` - DNS-over-TLS or DNS-over-HTTPS, any other protocol is an error
+    /// * `trust_nx_responses` - see `NameServerConfig::trust_nx_responses`
+    #[cfg(any(feature = "dns-over-tls", feature = "dns-over-https"))]
+    pub fn from_host_pinned(
+        host: &str,
+        port: u16,
+        protocol: Protocol,
+        trust_nx_responses: bool,
+    ) -> io::Result<Self> {
+        if !protocol.is_encrypted() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not an encrypted protocol", protocol),
+            ));
+        }
+
+        let mut ips = Vec::new();
+        for socket_addr in (host, port).to_socket_addrs()? {
+            if !ips.contains(&socket_addr.ip()) {
+                ips.push(socket_addr.ip());
+            }
+        }
+
+        if ips.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no addresses found for name server: {}", host),
+            ));
+        }
+
+        Ok(Self::from_ips_encrypted(
+            &ips,
+            port,
+            host.to_string(),
+            protocol,
+            trust_nx_responses,
+        ))
+    }
+
     /// Creates a default configuration, using `8.8.8.8`, `8.8.4.4` and `2001:4860:4860::8888`, `2001:4860:4860::8844` (thank you, Google).
     ///
     /// Please see Google's [privacy statement](https://developers.google.com/speed/public-dns/privacy) for important information about what they track, many ISP's track similar information in DNS. To use the system configuration see: `Resolver::from_system_conf` and `AsyncResolver::from_system_conf`
@@ -519,7 +937,7 @@ impl NameServerConfigGroup {
     /// Please see Google's [privacy statement](https://developers.google.com/speed/public-dns/privacy) for important information about what they track, many ISP's track similar information in DNS. To use the system configuration see: `Resolver::from_system_conf` and `AsyncResolver::from_system_conf`
     #[cfg(feature = "dns-over-https")]
     pub fn google_https() -> Self {
-        Self::from_ips_https(GOOGLE_IPS, 53, "dns.google".to_string(), true)
+        Self::from_ips_https(GOOGLE_IPS, 443, "dns.google".to_string(), true)
     }
 
     /// Creates a default configuration, using `1.1.1.1`, `1.0.0.1` and `2606:4700:4700::1111`, `2606:4700:4700::1001` (thank you, Cloudflare).
@@ -647,8 +1065,43 @@ impl Default for LookupIpStrategy {
     }
 }
 
+/// The order in which the name servers are tried for each request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+pub enum ServerOrderingStrategy {
+    /// Prefer the name servers which have performed best, by their successes, failures and
+    ///  latency (default)
+    QueryStats,
+    /// Always try the name servers in the order of the configuration
+    Sequential,
+    /// Each request starts with the next name server which has not failed, spreading the load
+    ///  evenly, like `options rotate` in resolv.conf
+    RoundRobin,
+    /// Try the name servers which have not failed in a random order, spreading the requests
+    ///  unpredictably
+    Random,
+}
+
+impl Default for ServerOrderingStrategy {
+    /// Returns [`ServerOrderingStrategy::QueryStats`] as the default.
+    fn default() -> Self {
+        ServerOrderingStrategy::QueryStats
+    }
+}
+
+/// Configuration of a SOCKS5 proxy, [RFC 1928](https://tools.ietf.org/html/rfc1928), which the
+///  connections to the name servers are tunneled through
+///
+/// Only proxies which require no authentication are supported.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+pub struct ProxyConfig {
+    /// The address of the SOCKS5 proxy
+    pub socket_addr: SocketAddr,
+}
+
 /// Configuration for the Resolver
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(
     feature = "serde-config",
     derive(Serialize, Deserialize),
@@ -662,18 +1115,94 @@ pub struct ResolverOpts {
     ///  the search
     pub ndots: usize,
     /// Specify the timeout for a request. Defaults to 5 seconds
+    ///
+    /// This bounds each request to an individual name server, a name server which does not respond
+    ///  within this time is considered failed.
     pub timeout: Duration,
-    /// Number of retries after lookup failure before giving up. Defaults to 2
+    /// Number of times a lookup is sent to the name servers before giving up, like
+    ///  `options attempts` in resolv.conf. Defaults to 2
+    ///
+    /// Each attempt tries the name servers in turn, ranked by how well they have performed, and a
+    ///  name server which fails is recorded as such before the next is tried.
     pub attempts: usize,
-    /// Rotate through the resource records in the response (if there is more than one for a given name)
+    /// The order in which the name servers are tried, defaults to
+    ///  [`ServerOrderingStrategy::QueryStats`]
+    ///
+    /// `options rotate` in resolv.conf is [`ServerOrderingStrategy::RoundRobin`]. The weights of
+    ///  the name servers are only considered by the `QueryStats` and `RoundRobin` strategies.
+    pub server_ordering: ServerOrderingStrategy,
+    /// Round-robin requests across the name servers, like `options rotate` in resolv.conf
+    ///
+    /// When set, the name servers are tried by [`ServerOrderingStrategy::RoundRobin`] regardless of
+    ///  `server_ordering`.
+    #[deprecated(note = "use `server_ordering` with `ServerOrderingStrategy::RoundRobin` instead")]
     pub rotate: bool,
+    /// The number of consecutive failures after which a name server is taken out of rotation.
+    ///  Defaults to 3, 0 disables this
+    ///
+    /// Once it has cooled down, see `failure_cooldown`, the next request probes the name server,
+    ///  which is only used again once it responds. It is not taken out of rotation if no other
+    ///  name server is available.
+    pub failure_threshold: usize,
+    /// How long a name server taken out of rotation by `failure_threshold` is left before it is
+    ///  probed, defaults to 30 seconds
+    ///
+    /// This is extended to the backoff from reconnecting to the name server, should that be
+    ///  longer.
+    pub failure_cooldown: Duration,
     /// Validate the names in the response, not implemented don't really see the point unless you need to support
     ///  badly configured DNS
     pub check_names: bool,
     /// Enable edns, for larger records
     pub edns0: bool,
+    /// The maximum UDP payload size advertised via EDNS when `edns0` is enabled. Defaults to 1232
+    pub edns_max_payload: u16,
+    /// Retry a request without EDNS when the name server responds to it with FORMERR, and without
+    ///  an OPT record of its own, as name servers which do not implement EDNS do. Defaults to false
+    ///
+    /// A NOTIMP response of the same kind is always retried without EDNS, either way the request
+    ///  is only retried once.
+    pub edns_formerr_fallback: bool,
+    /// The client subnet, address and source prefix length, sent with each request in the EDNS
+    ///  Client Subnet option, [RFC 7871](https://tools.ietf.org/html/rfc7871). Defaults to None
+    ///
+    /// Setting this sends EDNS with requests, regardless of `edns0`.
+    pub client_subnet: Option<(IpAddr, u8)>,
+    /// Send a DNS Cookie with each request, echoing the cookie each name server responds with,
+    ///  [RFC 7873](https://tools.ietf.org/html/rfc7873). Defaults to false
+    ///
+    /// Setting this sends EDNS with requests, regardless of `edns0`. Name servers which do not
+    ///  support cookies ignore them.
+    pub cookies: bool,
+    /// Additional EDNS options, by option code and payload, sent with each request, e.g. NSID,
+    ///  [RFC 5001](https://tools.ietf.org/html/rfc5001). Defaults to none
+    ///
+    /// Setting this sends EDNS with requests, regardless of `edns0`. The options in the responses
+    ///  of each name server are available from `NameServerStatus::edns_options`.
+    pub edns_options: Vec<(u16, Vec<u8>)>,
     /// Use DNSSec to validate the request
     pub validate: bool,
+    /// Set the DO bit, DNSSEC OK, in the EDNS of the requests, defaults to false
+    ///
+    /// The name servers include the RRSIGs of the records in their responses, which are returned
+    ///  along with the records, e.g. for the caller to validate. Setting this sends EDNS with
+    ///  requests, regardless of `edns0`.
+    pub dnssec_ok: bool,
+    /// Pad the requests to TLS and HTTPS name servers to a multiple of 128 octets, with the EDNS
+    ///  Padding option, defaults to false
+    ///
+    /// Padding hides the size of the encrypted requests, see
+    ///  [RFC 8467](https://tools.ietf.org/html/rfc8467). Setting this sends EDNS with requests
+    ///  to those name servers, regardless of `edns0`, the option is ignored for UDP and TCP.
+    pub enable_padding: bool,
+    /// Ask the stream (TCP, TLS, HTTPS) name servers how long they keep idle connections open,
+    ///  with the EDNS TCP Keepalive option, [RFC 7828](https://tools.ietf.org/html/rfc7828).
+    ///  Defaults to false
+    ///
+    /// The connections to a name server which advertises a timeout are kept open for as long,
+    ///  rather than `stream_idle_timeout`. Setting this sends EDNS with requests to those name
+    ///  servers, regardless of `edns0`, the option is never sent over UDP.
+    pub edns_keepalive: bool,
     /// The ip_strategy for the Resolver to use when lookup Ipv4 or Ipv6 addresses
     pub ip_strategy: LookupIpStrategy,
     /// Cache size is in number of records (some records can be large)
@@ -704,28 +1233,106 @@ pub struct ResolverOpts {
     ///
     /// [`MAX_TTL`]: ../dns_lru/const.MAX_TTL.html
     pub negative_max_ttl: Option<Duration>,
+    /// Optional minimum TTL of the records, as they are cached and returned. Defaults to None
+    ///
+    /// Unlike `positive_min_ttl`, which only extends how long responses are cached, this raises
+    /// the TTL of the records themselves, e.g. those of 0 from upstreams, to this value.
+    pub min_ttl: Option<Duration>,
+    /// Optional maximum TTL of the records, as they are cached and returned. Defaults to None
+    ///
+    /// Unlike `positive_max_ttl`, which only limits how long responses are cached, this lowers
+    /// the TTL of the records themselves, e.g. absurdly long ones from upstreams, to this value.
+    pub max_ttl: Option<Duration>,
     /// Number of concurrent requests per query
     ///
     /// Where more than one nameserver is configured, this configures the resolver to send queries
     /// to a number of servers in parallel. Defaults to 2; 0 or 1 will execute requests serially.
     pub num_concurrent_reqs: usize,
+    /// The maximum number of connections to each stream (TCP, TLS, HTTPS) name server. Defaults
+    ///  to 1
+    ///
+    /// Another connection is only opened when all others have requests in flight, connections
+    ///  which go unused for a while are closed. Datagram name servers always use a single socket.
+    pub connections_per_server: usize,
+    /// How long the connections to a stream name server beyond the first are kept open while
+    ///  unused, defaults to 30 seconds
+    ///
+    /// A timeout advertised by the name server takes precedence, see `edns_keepalive`, in which
+    ///  case the first connection is closed as well.
+    pub stream_idle_timeout: Duration,
     /// Preserve all intermediate records in the lookup response, suchas CNAME records
     pub preserve_intermediates: bool,
+    /// Send each name server only as many labels of the name as it needs to see, QNAME
+    ///  minimization, [RFC 7816](https://tools.ietf.org/html/rfc7816). Defaults to false
+    ///
+    /// The name servers are asked for the NS records of each ancestor of the name in turn,
+    ///  following the referrals to the name servers of the zones below. A name server which does
+    ///  not answer these, e.g. with NXDOMAIN for an empty non-terminal, is sent the full name.
+    pub qname_minimization: bool,
+    /// Randomize the case of the names sent to the name servers, the DNS 0x20 encoding, defaults
+    ///  to false
+    ///
+    /// A response must echo the name with the same case, which a spoofed response is unlikely to
+    ///  guess. Responses which do not are discarded as suspicious, so this should only be enabled
+    ///  for name servers known to preserve the case of the query.
+    pub case_randomization: bool,
+    /// Connect to the name servers as the resolver is constructed, rather than on the first
+    ///  request, defaults to false
+    ///
+    /// The connections are opened in the background, see `NameServerPool::warmup`. This requires
+    ///  the resolver to be constructed within the runtime it is spawned on.
+    pub eager_connect: bool,
+    /// The maximum number of records in a response, or in the chain of responses of a lookup,
+    ///  e.g. following CNAMEs, defaults to None
+    ///
+    /// A lookup with more records fails with `ResolveErrorKind::TooManyRecords`, rather than
+    ///  any of the records being dropped.
+    pub max_records: Option<usize>,
+    /// Tunnel the connections to the name servers through a SOCKS5 proxy, defaults to None
+    ///
+    /// UDP can not be tunneled, so UDP and dual name servers are queried over TCP instead. DNS over
+    ///  TLS, HTTPS and mDNS are not supported through a proxy, their connections fail.
+    pub proxy: Option<ProxyConfig>,
+    /// Refresh cached records in the background once they are within this fraction of their TTL
+    ///  of expiring, e.g. 0.1 for the last tenth of it, defaults to 0.0, which disables it
+    ///
+    /// The lookups of the records are answered from the cache in the meantime, only one refresh is
+    ///  started for each of them. This requires the resolver to be constructed with a runtime to
+    ///  spawn the refreshes on, e.g. with `AsyncResolver::new`.
+    pub prefetch_threshold: f32,
+    /// Serve the cached records for up to this long after they expire, when the name servers fail
+    ///  to answer, defaults to None, [RFC 8767](https://tools.ietf.org/html/rfc8767)
+    ///
+    /// The stale records are returned with a TTL of 30 seconds, see `Lookup::is_stale`. Name
+    ///  servers fail to answer with errors, e.g. timeouts, or with SERVFAIL or REFUSED responses.
+    pub serve_stale_ttl: Option<Duration>,
 }
 
 impl Default for ResolverOpts {
     /// Default values for the Resolver configuration.
     ///
     /// This follows the resolv.conf defaults as defined in the [Linux man pages](http://man7.org/linux/man-pages/man5/resolv.conf.5.html)
+    #[allow(deprecated)]
     fn default() -> Self {
         ResolverOpts {
             ndots: 1,
             timeout: Duration::from_secs(5),
             attempts: 2,
+            server_ordering: ServerOrderingStrategy::default(),
             rotate: false,
+            failure_threshold: 3,
+            failure_cooldown: Duration::from_secs(30),
             check_names: true,
             edns0: false,
+            edns_max_payload: 1232,
+            edns_formerr_fallback: false,
+            client_subnet: None,
+            cookies: false,
+            edns_options: vec![],
             validate: false,
+            dnssec_ok: false,
+            enable_padding: false,
+            edns_keepalive: false,
             ip_strategy: LookupIpStrategy::default(),
             cache_size: 32,
             use_hosts_file: true,
@@ -733,8 +1340,19 @@ impl Default for ResolverOpts {
             negative_min_ttl: None,
             positive_max_ttl: None,
             negative_max_ttl: None,
+            min_ttl: None,
+            max_ttl: None,
             num_concurrent_reqs: 2,
+            connections_per_server: 1,
+            stream_idle_timeout: Duration::from_secs(30),
             preserve_intermediates: false,
+            qname_minimization: false,
+            case_randomization: false,
+            eager_connect: false,
+            max_records: None,
+            proxy: None,
+            prefetch_threshold: 0.0,
+            serve_stale_ttl: None,
         }
     }
 }
@@ -760,3 +1378,125 @@ pub const QUAD9_IPS: &[IpAddr] = &[
     IpAddr::V4(Ipv4Addr::new(9, 9, 9, 9)),
     IpAddr::V6(Ipv6Addr::new(0x2620, 0x00fe, 0, 0, 0, 0, 0, 0x00fe)),
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name_server(socket_addr: &str, protocol: Protocol) -> NameServerConfig {
+        NameServerConfig {
+            socket_addr: socket_addr.parse().unwrap(),
+            protocol,
+            tls_dns_name: None,
+            trust_nx_responses: false,
+            signer: None,
+            weight: None,
+            bind_addr: None,
+            #[cfg(feature = "dns-over-rustls")]
+            tls_config: None,
+        }
+    }
+
+    #[test]
+    fn test_builder() {
+        let (config, options) = ResolverConfig::builder()
+            .add_udp("8.8.8.8:53")
+            .add_tcp("[2001:4860:4860::8888]:53")
+            .domain("example.com")
+            .search("example.net")
+            .ndots(2)
+            .build()
+            .expect("invalid configuration");
+
+        let mut expected = ResolverConfig::new();
+        expected.add_name_server(name_server("8.8.8.8:53", Protocol::Udp));
+        expected.add_name_server(name_server("[2001:4860:4860::8888]:53", Protocol::Tcp));
+        expected.set_domain(Name::from_str("example.com").unwrap());
+        expected.add_search(Name::from_str("example.net").unwrap());
+
+        assert_eq!(config, expected);
+        assert_eq!(options.ndots, 2);
+        assert_eq!(options.timeout, ResolverOpts::default().timeout);
+    }
+
+    #[test]
+    fn test_builder_matches_presets() {
+        let (config, _) = ResolverConfig::builder()
+            .add_dual("8.8.8.8:53")
+            .add_dual("8.8.4.4:53")
+            .add_dual("[2001:4860:4860::8888]:53")
+            .add_dual("[2001:4860:4860::8844]:53")
+            .trust_nx_responses(true)
+            .build()
+            .expect("invalid configuration");
+
+        let name_servers = NameServerConfigGroup::from_ips_dual(GOOGLE_IPS, 53, true);
+        let expected = ResolverConfig::from_parts(None, vec![], name_servers);
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    #[cfg(feature = "dns-over-tls")]
+    fn test_builder_tls() {
+        let (config, _) = ResolverConfig::builder()
+            .add_tls("1.1.1.1:853", "cloudflare-dns.com")
+            .build()
+            .expect("invalid configuration");
+
+        let expected = ResolverConfig::from_parts(
+            None,
+            vec![],
+            NameServerConfigGroup::from_ips_tls(
+                &[CLOUDFLARE_IPS[0]],
+                853,
+                "cloudflare-dns.com".to_string(),
+                false,
+            ),
+        );
+        assert_eq!(config, expected);
+
+        // the certificate can not be verified without a name
+        assert!(ResolverConfig::builder()
+            .add_tls("1.1.1.1:853", "")
+            .build()
+            .is_err());
+        assert!(ResolverConfig::builder()
+            .add_name_server(name_server("1.1.1.1:853", Protocol::Tls))
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_builder_invalid() {
+        assert!(ResolverConfig::builder().build().is_err());
+        assert!(ResolverConfig::builder().add_udp("8.8.8.8").build().is_err());
+
+        // labels are at most 63 bytes
+        let search = format!("{}.com", "a".repeat(64));
+        assert!(ResolverConfig::builder()
+            .add_udp("8.8.8.8:53")
+            .search(&search)
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "dns-over-tls")]
+    fn test_from_host_pinned() {
+        let name_servers =
+            NameServerConfigGroup::from_host_pinned("localhost", 853, Protocol::Tls, false)
+                .expect("failed to resolve localhost");
+
+        assert!(!name_servers.is_empty());
+        for name_server in name_servers.iter() {
+            assert!(name_server.socket_addr.ip().is_loopback());
+            assert_eq!(name_server.socket_addr.port(), 853);
+            assert_eq!(name_server.protocol, Protocol::Tls);
+            assert_eq!(name_server.tls_dns_name.as_deref(), Some("localhost"));
+        }
+
+        assert!(
+            NameServerConfigGroup::from_host_pinned("localhost", 53, Protocol::Udp, false).is_err()
+        );
+    }
+}
@@ -8,6 +8,7 @@
 //! An LRU cache designed for work with DNS lookups
 
 use std::convert::TryFrom;
+use std::net::IpAddr;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
@@ -15,6 +16,7 @@ use lru_cache::LruCache;
 use parking_lot::Mutex;
 
 use proto::op::Query;
+use proto::rr::rdata::opt::ClientSubnet;
 use proto::rr::Record;
 
 use crate::config;
@@ -25,11 +27,45 @@ use crate::lookup::Lookup;
 ///   Setting this to a value of 1 day, in seconds
 pub const MAX_TTL: u32 = 86400_u32;
 
+/// The TTL of stale records, as recommended by https://tools.ietf.org/html/rfc8767#section-4
+pub const STALE_TTL: u32 = 30;
+
+/// The cache is segmented by the CD, checking disabled, bit of the request, so that answers which
+///  were validated are not returned for requests which disabled validation, and vice versa
+///
+/// Answers to requests with the EDNS Client Subnet option are further segmented by the network of
+///  clients their scope covers, [RFC 7871](https://tools.ietf.org/html/rfc7871#section-7.3.1).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct LruKey {
+    query: Query,
+    checking_disabled: bool,
+    /// the client address truncated to the scope prefix of the answer, None if it covers all
+    scope: Option<ClientSubnet>,
+}
+
+impl LruKey {
+    fn new(query: Query, checking_disabled: bool) -> Self {
+        Self::with_scope(query, checking_disabled, None)
+    }
+
+    fn with_scope(query: Query, checking_disabled: bool, scope: Option<ClientSubnet>) -> Self {
+        LruKey {
+            query,
+            checking_disabled,
+            scope,
+        }
+    }
+}
+
 #[derive(Debug)]
 struct LruValue {
     // In the None case, this represents an NXDomain
     lookup: Result<Lookup, ResolveError>,
     valid_until: Instant,
+    /// The TTL the value was cached with, which `valid_until` counts down
+    original_ttl: Duration,
+    /// True while the lookup is being refreshed in the background, see `DnsLru::start_prefetch`
+    prefetching: bool,
 }
 
 impl LruValue {
@@ -42,11 +78,21 @@ impl LruValue {
     fn ttl(&self, now: Instant) -> Duration {
         self.valid_until.saturating_duration_since(now)
     }
+
+    /// Returns true if the value expired no more than `max_stale` ago
+    fn is_stale(&self, max_stale: Duration, now: Instant) -> bool {
+        !self.is_current(now) && now <= self.valid_until + max_stale
+    }
+
+    /// Returns true if the time remaining is within `threshold`, a fraction of the original TTL
+    fn is_expiring(&self, threshold: f32, now: Instant) -> bool {
+        self.ttl(now) <= self.original_ttl.mul_f32(threshold)
+    }
 }
 
 #[derive(Clone, Debug)]
 pub(crate) struct DnsLru {
-    cache: Arc<Mutex<LruCache<Query, LruValue>>>,
+    cache: Arc<Mutex<LruCache<LruKey, LruValue>>>,
     /// A minimum TTL value for positive responses.
     ///
     /// Positive responses with TTLs under `positive_max_ttl` will use
@@ -83,6 +129,25 @@ pub(crate) struct DnsLru {
     ///
     /// [`MAX_TTL`]: const.MAX_TTL.html
     negative_max_ttl: Duration,
+    /// A minimum TTL value for the records themselves, as they are cached and returned.
+    ///
+    /// If this value is not set on the `TtlConfig` used to construct this
+    /// `DnsLru`, it will default to 0.
+    min_ttl: Duration,
+    /// A maximum TTL value for the records themselves, as they are cached and returned.
+    ///
+    /// If this value is not set on the `TtlConfig` used to construct this
+    /// `DnsLru`, it will default to `u32::MAX` seconds, i.e. no limit.
+    max_ttl: Duration,
+    /// How long after they expire the records may still be served, when the name servers fail.
+    ///
+    /// If this value is not set on the `TtlConfig` used to construct this
+    /// `DnsLru`, it will default to 0, i.e. stale records are never served.
+    serve_stale_ttl: Duration,
+    /// The client subnet sent with the requests, see `ResolverOpts::client_subnet`.
+    ///
+    /// Answers are only returned for clients within the scope they were cached with.
+    client_subnet: Option<ClientSubnet>,
 }
 
 /// The time-to-live, TTL, configuration for use by the cache.
@@ -114,6 +179,19 @@ pub(crate) struct TtlConfig {
     /// `NXDOMAIN` responses with TTLs over `negative_max_ttl` will use
     /// `negative_max_ttl` instead.
     pub negative_max_ttl: Option<Duration>,
+    /// An optional minimum TTL value for the records.
+    ///
+    /// Records with TTLs under `min_ttl` are cached and returned with
+    /// `min_ttl` instead.
+    pub min_ttl: Option<Duration>,
+    /// An optional maximum TTL value for the records.
+    ///
+    /// Records with TTLs over `max_ttl` are cached and returned with
+    /// `max_ttl` instead.
+    pub max_ttl: Option<Duration>,
+    /// An optional time after they expire, for which the records are
+    /// kept to be served when the name servers fail.
+    pub serve_stale_ttl: Option<Duration>,
 }
 
 impl TtlConfig {
@@ -123,6 +201,9 @@ impl TtlConfig {
             negative_min_ttl: opts.negative_min_ttl,
             positive_max_ttl: opts.positive_max_ttl,
             negative_max_ttl: opts.negative_max_ttl,
+            min_ttl: opts.min_ttl,
+            max_ttl: opts.max_ttl,
+            serve_stale_ttl: opts.serve_stale_ttl,
         }
     }
 }
@@ -134,6 +215,9 @@ impl DnsLru {
             negative_min_ttl,
             positive_max_ttl,
             negative_max_ttl,
+            min_ttl,
+            max_ttl,
+            serve_stale_ttl,
         } = ttl_cfg;
         let cache = Arc::new(Mutex::new(LruCache::new(capacity)));
         Self {
@@ -144,22 +228,85 @@ impl DnsLru {
                 .unwrap_or_else(|| Duration::from_secs(u64::from(MAX_TTL))),
             negative_max_ttl: negative_max_ttl
                 .unwrap_or_else(|| Duration::from_secs(u64::from(MAX_TTL))),
+            min_ttl: min_ttl.unwrap_or_else(|| Duration::from_secs(0)),
+            max_ttl: max_ttl.unwrap_or_else(|| Duration::from_secs(u64::from(u32::max_value()))),
+            serve_stale_ttl: serve_stale_ttl.unwrap_or_else(|| Duration::from_secs(0)),
+            client_subnet: None,
         }
     }
 
+    /// Sets the client subnet, address and source prefix length, the requests are sent with, see
+    ///  `ResolverOpts::client_subnet`
+    pub(crate) fn set_client_subnet(&mut self, client_subnet: Option<(IpAddr, u8)>) {
+        self.client_subnet = client_subnet
+            .map(|(address, source_prefix)| ClientSubnet::new(address, source_prefix, 0));
+    }
+
+    /// The key of the answer to the query, for the clients covered by its `scope_prefix`
+    ///
+    /// A scope of 0 covers all clients, as does any without a client subnet. Scopes longer than the
+    ///  source prefix are limited to it, as the address is not known past it.
+    fn scoped_key(&self, query: Query, checking_disabled: bool, scope_prefix: u8) -> LruKey {
+        let scope = match self.client_subnet {
+            Some(subnet) if scope_prefix > 0 => Some(ClientSubnet::new(
+                subnet.address(),
+                scope_prefix.min(subnet.source_prefix()),
+                0,
+            )),
+            _ => None,
+        };
+
+        LruKey::with_scope(query, checking_disabled, scope)
+    }
+
+    /// The key of the cached answer to the query which covers the client subnet, the most specific
+    ///  scope first, otherwise the key of the answer for all clients
+    fn find_key(
+        &self,
+        cache: &mut LruCache<LruKey, LruValue>,
+        query: &Query,
+        checking_disabled: bool,
+    ) -> LruKey {
+        if let Some(subnet) = self.client_subnet {
+            for scope_prefix in (1..=subnet.source_prefix()).rev() {
+                let key = self.scoped_key(query.clone(), checking_disabled, scope_prefix);
+                if cache.contains_key(&key) {
+                    return key;
+                }
+            }
+        }
+
+        LruKey::new(query.clone(), checking_disabled)
+    }
+
+    /// Clamps the TTL of a record between the configured `min_ttl` and `max_ttl`
+    fn clamp_ttl(&self, ttl: u32) -> u32 {
+        let ttl = Duration::from_secs(u64::from(ttl))
+            .max(self.min_ttl)
+            .min(self.max_ttl);
+        u32::try_from(ttl.as_secs()).unwrap_or_else(|_| u32::max_value())
+    }
+
+    /// Inserts the records for the query, `authentic_data` is the AD bit of the response
+    ///
+    /// The `scope_prefix` is that of the EDNS Client Subnet option of the response, 0 without one.
     pub(crate) fn insert(
         &self,
         query: Query,
+        checking_disabled: bool,
+        scope_prefix: u8,
         records_and_ttl: Vec<(Record, u32)>,
+        authentic_data: bool,
         now: Instant,
     ) -> Lookup {
         let len = records_and_ttl.len();
         // collapse the values, we're going to take the Minimum TTL as the correct one
         let (records, ttl): (Vec<Record>, Duration) = records_and_ttl.into_iter().fold(
             (Vec::with_capacity(len), self.positive_max_ttl),
-            |(mut records, mut min_ttl), (record, ttl)| {
+            |(mut records, mut min_ttl), (mut record, ttl)| {
+                record.set_ttl(self.clamp_ttl(record.ttl()));
                 records.push(record);
-                let ttl = Duration::from_secs(u64::from(ttl));
+                let ttl = Duration::from_secs(u64::from(self.clamp_ttl(ttl)));
                 min_ttl = min_ttl.min(ttl);
                 (records, min_ttl)
             },
@@ -171,12 +318,15 @@ impl DnsLru {
         let valid_until = now + ttl;
 
         // insert into the LRU
-        let lookup = Lookup::new_with_deadline(query.clone(), Arc::from(records), valid_until);
+        let lookup = Lookup::new_with_deadline(query.clone(), Arc::from(records), valid_until)
+            .with_authentic_data(authentic_data);
         self.cache.lock().insert(
-            query,
+            self.scoped_key(query, checking_disabled, scope_prefix),
             LruValue {
                 lookup: Ok(lookup.clone()),
                 valid_until,
+                original_ttl: ttl,
+                prefetching: false,
             },
         );
 
@@ -184,15 +334,25 @@ impl DnsLru {
     }
 
     /// Generally for inserting a set of records that have already been cached, but with a different Query.
-    pub(crate) fn duplicate(&self, query: Query, lookup: Lookup, ttl: u32, now: Instant) -> Lookup {
-        let ttl = Duration::from_secs(u64::from(ttl));
+    pub(crate) fn duplicate(
+        &self,
+        query: Query,
+        checking_disabled: bool,
+        scope_prefix: u8,
+        lookup: Lookup,
+        ttl: u32,
+        now: Instant,
+    ) -> Lookup {
+        let ttl = Duration::from_secs(u64::from(self.clamp_ttl(ttl)));
         let valid_until = now + ttl;
 
         self.cache.lock().insert(
-            query,
+            self.scoped_key(query, checking_disabled, scope_prefix),
             LruValue {
                 lookup: Ok(lookup.clone()),
                 valid_until,
+                original_ttl: ttl,
+                prefetching: false,
             },
         );
 
@@ -215,9 +375,33 @@ impl DnsLru {
         }
     }
 
+    /// This counts down the TTLs of the cached records to the time remaining, so that consumers of
+    ///  the records do not hold onto them for longer than the upstream intended.
+    fn lookup_with_ttl(lookup: &Lookup, remaining: Duration) -> Lookup {
+        let remaining = u32::try_from(remaining.as_secs()).unwrap_or(MAX_TTL);
+        let records = lookup
+            .record_iter()
+            .cloned()
+            .map(|mut record| {
+                let ttl = record.ttl().min(remaining);
+                record.set_ttl(ttl);
+                record
+            })
+            .collect::<Vec<_>>();
+
+        Lookup::new_with_deadline(
+            lookup.query().clone(),
+            Arc::from(records),
+            lookup.valid_until(),
+        )
+        .with_authentic_data(lookup.authentic_data())
+    }
+
     pub(crate) fn negative(
         &self,
         query: Query,
+        checking_disabled: bool,
+        scope_prefix: u8,
         mut error: ResolveError,
         now: Instant,
     ) -> ResolveError {
@@ -243,10 +427,12 @@ impl DnsLru {
                 let error = error.clone();
 
                 self.cache.lock().insert(
-                    query,
+                    self.scoped_key(query, checking_disabled, scope_prefix),
                     LruValue {
                         lookup: Err(error),
                         valid_until,
+                        original_ttl: ttl_duration,
+                        prefetching: false,
                     },
                 );
             }
@@ -260,20 +446,31 @@ impl DnsLru {
     }
 
     /// This needs to be mut b/c it's an LRU, meaning the ordering of elements will potentially change on retrieval...
-    pub(crate) fn get(&self, query: &Query, now: Instant) -> Option<Result<Lookup, ResolveError>> {
+    pub(crate) fn get(
+        &self,
+        query: &Query,
+        checking_disabled: bool,
+        now: Instant,
+    ) -> Option<Result<Lookup, ResolveError>> {
+        let serve_stale_ttl = self.serve_stale_ttl;
         let mut out_of_date = false;
         let mut cache = self.cache.lock();
-        let lookup = cache.get_mut(query).and_then(|value| {
+        let key = self.find_key(&mut cache, query, checking_disabled);
+        let lookup = cache.get_mut(&key).and_then(|value| {
             if value.is_current(now) {
                 out_of_date = false;
-                let mut result = value.lookup.clone();
-
-                if let Err(ref mut err) = result {
-                    Self::nx_error_with_ttl(err, value.ttl(now));
-                }
+                let result = match value.lookup {
+                    Ok(ref lookup) => Ok(Self::lookup_with_ttl(lookup, value.ttl(now))),
+                    Err(ref err) => {
+                        let mut err = err.clone();
+                        Self::nx_error_with_ttl(&mut err, value.ttl(now));
+                        Err(err)
+                    }
+                };
                 Some(result)
             } else {
-                out_of_date = true;
+                // stale values are kept for when the name servers fail, see `get_stale`
+                out_of_date = !value.is_stale(serve_stale_ttl, now);
                 None
             }
         });
@@ -282,11 +479,66 @@ impl DnsLru {
         // this assumes time is always moving forward, this would only not be true in contrived situations where now
         //  is not current time, like tests...
         if out_of_date {
-            cache.remove(query);
+            cache.remove(&key);
         }
 
         lookup
     }
+
+    /// Returns the expired records of the query, if they expired within the `serve_stale_ttl`, with
+    ///  a TTL of `STALE_TTL`
+    ///
+    /// This is for when the name servers fail to answer, negative responses are not served stale.
+    pub(crate) fn get_stale(
+        &self,
+        query: &Query,
+        checking_disabled: bool,
+        now: Instant,
+    ) -> Option<Lookup> {
+        let mut cache = self.cache.lock();
+        let key = self.find_key(&mut cache, query, checking_disabled);
+        let value = cache.get_mut(&key)?;
+        match value.lookup {
+            Ok(ref lookup) if value.is_stale(self.serve_stale_ttl, now) => {
+                let ttl = Duration::from_secs(u64::from(STALE_TTL));
+                let records = Self::lookup_with_ttl(lookup, ttl).records().to_vec();
+                let lookup =
+                    Lookup::new_with_deadline(lookup.query().clone(), Arc::from(records), now + ttl)
+                        .with_authentic_data(lookup.authentic_data())
+                        .with_stale(true);
+                Some(lookup)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns true if the records of the query are cached, but within `threshold`, a fraction of
+    ///  their TTL, of expiring, and are not already being refreshed
+    ///
+    /// The records are then marked as being refreshed, until they are replaced in the cache, so
+    ///  that only one refresh is started for them.
+    pub(crate) fn start_prefetch(
+        &self,
+        query: &Query,
+        checking_disabled: bool,
+        threshold: f32,
+        now: Instant,
+    ) -> bool {
+        let mut cache = self.cache.lock();
+        let key = self.find_key(&mut cache, query, checking_disabled);
+        match cache.get_mut(&key) {
+            Some(value)
+                if value.lookup.is_ok()
+                    && !value.prefetching
+                    && value.is_current(now)
+                    && value.is_expiring(threshold, now) =>
+            {
+                value.prefetching = true;
+                true
+            }
+            _ => false,
+        }
+    }
 }
 
 // see also the lookup_tests.rs in integration-tests crate
@@ -311,6 +563,8 @@ mod tests {
         let value = LruValue {
             lookup: Err(ResolveErrorKind::Message("test error").into()),
             valid_until: future,
+            original_ttl: Duration::from_secs(5),
+            prefetching: false,
         };
 
         assert!(value.is_current(now));
@@ -339,7 +593,7 @@ mod tests {
         };
         let lru = DnsLru::new(1, ttls);
 
-        let rc_ips = lru.insert(query.clone(), ips_ttl, now);
+        let rc_ips = lru.insert(query.clone(), false, 0, ips_ttl, false, now);
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
         // the returned lookup should use the cache's min TTL, since the
         // query's TTL was below the minimum.
@@ -351,7 +605,7 @@ mod tests {
             3,
         )];
 
-        let rc_ips = lru.insert(query, ips_ttl, now);
+        let rc_ips = lru.insert(query, false, 0, ips_ttl, false, now);
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
         // the returned lookup should use the record's TTL, since it's
         // greater than the cache's minimum.
@@ -379,7 +633,7 @@ mod tests {
             response_code: ResponseCode::NoError,
             trusted: false,
         };
-        let nx_error = lru.negative(name.clone(), err.into(), now);
+        let nx_error = lru.negative(name.clone(), false, 0, err.into(), now);
         match nx_error.kind() {
             &ResolveErrorKind::NoRecordsFound { negative_ttl, .. } => {
                 let valid_until = negative_ttl.expect("resolve error should have a deadline");
@@ -397,7 +651,7 @@ mod tests {
             response_code: ResponseCode::NoError,
             trusted: false,
         };
-        let nx_error = lru.negative(name, err.into(), now);
+        let nx_error = lru.negative(name, false, 0, err.into(), now);
         match nx_error.kind() {
             &ResolveErrorKind::NoRecordsFound { negative_ttl, .. } => {
                 let negative_ttl = negative_ttl.expect("ResolveError should have a deadline");
@@ -429,7 +683,7 @@ mod tests {
         };
         let lru = DnsLru::new(1, ttls);
 
-        let rc_ips = lru.insert(query.clone(), ips_ttl, now);
+        let rc_ips = lru.insert(query.clone(), false, 0, ips_ttl, false, now);
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
         // the returned lookup should use the cache's min TTL, since the
         // query's TTL was above the maximum.
@@ -441,7 +695,7 @@ mod tests {
             59,
         )];
 
-        let rc_ips = lru.insert(query, ips_ttl, now);
+        let rc_ips = lru.insert(query, false, 0, ips_ttl, false, now);
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
         // the returned lookup should use the record's TTL, since it's
         // below than the cache's maximum.
@@ -469,7 +723,7 @@ mod tests {
             response_code: ResponseCode::NoError,
             trusted: false,
         };
-        let nx_error = lru.negative(name.clone(), err.into(), now);
+        let nx_error = lru.negative(name.clone(), false, 0, err.into(), now);
         match nx_error.kind() {
             &ResolveErrorKind::NoRecordsFound { negative_ttl, .. } => {
                 let negative_ttl = negative_ttl.expect("resolve error should have a deadline");
@@ -487,7 +741,7 @@ mod tests {
             response_code: ResponseCode::NoError,
             trusted: false,
         };
-        let nx_error = lru.negative(name, err.into(), now);
+        let nx_error = lru.negative(name, false, 0, err.into(), now);
         match nx_error.kind() {
             &ResolveErrorKind::NoRecordsFound { negative_ttl, .. } => {
                 let negative_ttl = negative_ttl.expect("resolve error should have a deadline");
@@ -512,13 +766,82 @@ mod tests {
         let ips = vec![RData::A(Ipv4Addr::new(127, 0, 0, 1))];
         let lru = DnsLru::new(1, TtlConfig::default());
 
-        let rc_ips = lru.insert(query.clone(), ips_ttl, now);
+        let rc_ips = lru.insert(query.clone(), false, 0, ips_ttl, false, now);
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
 
-        let rc_ips = lru.get(&query, now).unwrap().expect("records should exist");
+        let rc_ips = lru.get(&query, false, now).unwrap().expect("records should exist");
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
     }
 
+    #[test]
+    fn test_checking_disabled_segments() {
+        let now = Instant::now();
+
+        let name = Name::from_str("www.example.com.").unwrap();
+        let query = Query::query(name.clone(), RecordType::A);
+        let validated = vec![(
+            Record::from_rdata(name.clone(), 1, RData::A(Ipv4Addr::new(127, 0, 0, 1))),
+            1,
+        )];
+        let unvalidated = vec![(
+            Record::from_rdata(name, 1, RData::A(Ipv4Addr::new(127, 0, 0, 2))),
+            1,
+        )];
+        let lru = DnsLru::new(2, TtlConfig::default());
+
+        lru.insert(query.clone(), false, 0, validated, true, now);
+        assert!(lru.get(&query, true, now).is_none());
+
+        lru.insert(query.clone(), true, 0, unvalidated, false, now);
+
+        let lookup = lru.get(&query, false, now).unwrap().unwrap();
+        assert_eq!(lookup.iter().next(), Some(&RData::A(Ipv4Addr::new(127, 0, 0, 1))));
+        assert!(lookup.authentic_data());
+
+        let lookup = lru.get(&query, true, now).unwrap().unwrap();
+        assert_eq!(lookup.iter().next(), Some(&RData::A(Ipv4Addr::new(127, 0, 0, 2))));
+        assert!(!lookup.authentic_data());
+    }
+
+    #[test]
+    fn test_client_subnet_scope() {
+        let now = Instant::now();
+
+        let name = Name::from_str("www.example.com.").unwrap();
+        let query = Query::query(name.clone(), RecordType::A);
+        let global = Query::query(name.clone(), RecordType::AAAA);
+        let ips_ttl = vec![(
+            Record::from_rdata(name.clone(), 1, RData::A(Ipv4Addr::new(127, 0, 0, 1))),
+            1,
+        )];
+        let ipv6s_ttl = vec![(
+            Record::from_rdata(name, 1, RData::AAAA(Ipv6Addr::LOCALHOST)),
+            1,
+        )];
+
+        // the caches of clients in different networks share the records
+        let cache = DnsLru::new(4, TtlConfig::default());
+        let client = |address: [u8; 4]| {
+            let mut lru = cache.clone();
+            lru.set_client_subnet(Some((IpAddr::V4(Ipv4Addr::from(address)), 24)));
+            lru
+        };
+        let lru = client([192, 0, 2, 77]);
+
+        // the answer covers the clients in 192.0.0.0/16, and a scope of 0 covers all clients
+        lru.insert(query.clone(), false, 16, ips_ttl, false, now);
+        lru.insert(global.clone(), false, 0, ipv6s_ttl, false, now);
+        assert!(lru.get(&query, false, now).is_some());
+
+        let neighbour = client([192, 0, 3, 1]);
+        assert!(neighbour.get(&query, false, now).is_some());
+        assert!(neighbour.get(&global, false, now).is_some());
+
+        let stranger = client([198, 51, 100, 1]);
+        assert!(stranger.get(&query, false, now).is_none());
+        assert!(stranger.get(&global, false, now).is_some());
+    }
+
     #[test]
     fn test_insert_ttl() {
         let now = Instant::now();
@@ -541,20 +864,75 @@ mod tests {
         ];
         let lru = DnsLru::new(1, TtlConfig::default());
 
-        lru.insert(query.clone(), ips_ttl, now);
+        lru.insert(query.clone(), false, 0, ips_ttl, false, now);
 
         // still valid
         let rc_ips = lru
-            .get(&query, now + Duration::from_secs(1))
+            .get(&query, false, now + Duration::from_secs(1))
             .unwrap()
             .expect("records should exist");
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
 
         // 2 should be one too far
-        let rc_ips = lru.get(&query, now + Duration::from_secs(2));
+        let rc_ips = lru.get(&query, false, now + Duration::from_secs(2));
         assert!(rc_ips.is_none());
     }
 
+    #[test]
+    fn test_ttl_countdown() {
+        let now = Instant::now();
+        let name = Name::from_str("www.example.com.").unwrap();
+        let query = Query::query(name.clone(), RecordType::A);
+        let ips_ttl = vec![(
+            Record::from_rdata(name, 10, RData::A(Ipv4Addr::new(127, 0, 0, 1))),
+            10,
+        )];
+        let lru = DnsLru::new(1, TtlConfig::default());
+
+        lru.insert(query.clone(), false, 0, ips_ttl, false, now);
+
+        let lookup = lru
+            .get(&query, false, now + Duration::from_secs(3))
+            .unwrap()
+            .expect("records should exist");
+        assert_eq!(lookup.records()[0].ttl(), 7);
+
+        let lookup = lru
+            .get(&query, false, now + Duration::from_secs(10))
+            .unwrap()
+            .expect("records should exist");
+        assert_eq!(lookup.records()[0].ttl(), 0);
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let now = Instant::now();
+        let lru = DnsLru::new(2, TtlConfig::default());
+
+        let queries = ["a.example.com.", "b.example.com.", "c.example.com."]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let name = Name::from_str(name).unwrap();
+                let query = Query::query(name.clone(), RecordType::A);
+                let ip = RData::A(Ipv4Addr::new(127, 0, 0, i as u8 + 1));
+                (query, vec![(Record::from_rdata(name, 10, ip), 10)])
+            })
+            .collect::<Vec<_>>();
+
+        lru.insert(queries[0].0.clone(), false, 0, queries[0].1.clone(), false, now);
+        lru.insert(queries[1].0.clone(), false, 0, queries[1].1.clone(), false, now);
+
+        // touch a, making b the least recently used
+        assert!(lru.get(&queries[0].0, false, now).is_some());
+
+        lru.insert(queries[2].0.clone(), false, 0, queries[2].1.clone(), false, now);
+
+        assert!(lru.get(&queries[0].0, false, now).is_some());
+        assert!(lru.get(&queries[1].0, false, now).is_none());
+        assert!(lru.get(&queries[2].0, false, now).is_some());
+    }
+
     #[test]
     fn test_insert_positive_min_ttl() {
         let now = Instant::now();
@@ -583,11 +961,11 @@ mod tests {
             ..Default::default()
         };
         let lru = DnsLru::new(1, ttls);
-        lru.insert(query.clone(), ips_ttl, now);
+        lru.insert(query.clone(), false, 0, ips_ttl, false, now);
 
         // still valid
         let rc_ips = lru
-            .get(&query, now + Duration::from_secs(1))
+            .get(&query, false, now + Duration::from_secs(1))
             .unwrap()
             .expect("records should exist");
         for (rc_ip, ip) in rc_ips.iter().zip(ips.iter()) {
@@ -595,7 +973,7 @@ mod tests {
         }
 
         let rc_ips = lru
-            .get(&query, now + Duration::from_secs(2))
+            .get(&query, false, now + Duration::from_secs(2))
             .unwrap()
             .expect("records should exist");
         for (rc_ip, ip) in rc_ips.iter().zip(ips.iter()) {
@@ -603,7 +981,7 @@ mod tests {
         }
 
         let rc_ips = lru
-            .get(&query, now + Duration::from_secs(3))
+            .get(&query, false, now + Duration::from_secs(3))
             .unwrap()
             .expect("records should exist");
         for (rc_ip, ip) in rc_ips.iter().zip(ips.iter()) {
@@ -611,7 +989,7 @@ mod tests {
         }
 
         // after 4 seconds, the records should be invalid.
-        let rc_ips = lru.get(&query, now + Duration::from_secs(4));
+        let rc_ips = lru.get(&query, false, now + Duration::from_secs(4));
         assert!(rc_ips.is_none());
     }
 
@@ -643,11 +1021,11 @@ mod tests {
             ..Default::default()
         };
         let lru = DnsLru::new(1, ttls);
-        lru.insert(query.clone(), ips_ttl, now);
+        lru.insert(query.clone(), false, 0, ips_ttl, false, now);
 
         // still valid
         let rc_ips = lru
-            .get(&query, now + Duration::from_secs(1))
+            .get(&query, false, now + Duration::from_secs(1))
             .unwrap()
             .expect("records should exist");
         for (rc_ip, ip) in rc_ips.iter().zip(ips.iter()) {
@@ -655,7 +1033,7 @@ mod tests {
         }
 
         let rc_ips = lru
-            .get(&query, now + Duration::from_secs(2))
+            .get(&query, false, now + Duration::from_secs(2))
             .unwrap()
             .expect("records should exist");
         for (rc_ip, ip) in rc_ips.iter().zip(ips.iter()) {
@@ -663,7 +1041,57 @@ mod tests {
         }
 
         // after 3 seconds, the records should be invalid.
-        let rc_ips = lru.get(&query, now + Duration::from_secs(3));
+        let rc_ips = lru.get(&query, false, now + Duration::from_secs(3));
         assert!(rc_ips.is_none());
     }
+
+    #[test]
+    fn test_insert_min_ttl() {
+        let now = Instant::now();
+        let name = Name::from_str("www.example.com.").unwrap();
+        let query = Query::query(name.clone(), RecordType::A);
+        let record = Record::from_rdata(name, 0, RData::A(Ipv4Addr::new(127, 0, 0, 1)));
+
+        let ttls = TtlConfig {
+            min_ttl: Some(Duration::from_secs(30)),
+            ..Default::default()
+        };
+        let lru = DnsLru::new(1, ttls);
+
+        // the TTL of 0 is raised to the minimum, both as returned and as cached
+        let lookup = lru.insert(query.clone(), false, 0, vec![(record, 0)], false, now);
+        assert_eq!(lookup.record_iter().next().unwrap().ttl(), 30);
+
+        let lookup = lru
+            .get(&query, false, now + Duration::from_secs(10))
+            .unwrap()
+            .expect("records should exist");
+        assert_eq!(lookup.record_iter().next().unwrap().ttl(), 20);
+    }
+
+    #[test]
+    fn test_insert_max_ttl() {
+        let now = Instant::now();
+        let name = Name::from_str("www.example.com.").unwrap();
+        let query = Query::query(name.clone(), RecordType::A);
+        let record = Record::from_rdata(name, 999_999, RData::A(Ipv4Addr::new(127, 0, 0, 1)));
+
+        let ttls = TtlConfig {
+            max_ttl: Some(Duration::from_secs(300)),
+            ..Default::default()
+        };
+        let lru = DnsLru::new(1, ttls);
+
+        // the huge TTL is lowered to the maximum, both as returned and as cached
+        let lookup = lru.insert(query.clone(), false, 0, vec![(record, 999_999)], false, now);
+        assert_eq!(lookup.record_iter().next().unwrap().ttl(), 300);
+
+        let lookup = lru
+            .get(&query, false, now + Duration::from_secs(10))
+            .unwrap()
+            .expect("records should exist");
+        assert_eq!(lookup.record_iter().next().unwrap().ttl(), 290);
+
+        assert!(lru.get(&query, false, now + Duration::from_secs(301)).is_none());
+    }
 }
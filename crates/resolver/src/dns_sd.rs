@@ -52,6 +52,8 @@ impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> DnsSdH
                 // TODO: This should use the AsyncResolver's options.edns0
                 // setting, but options is private.
                 use_edns: false,
+                forward_hops: None,
+                checking_disabled: false,
             };
 
             this.inner_lookup(name, RecordType::PTR, options).await
@@ -14,6 +14,7 @@ use thiserror::Error;
 
 use crate::proto::error::{ProtoError, ProtoErrorKind};
 use crate::proto::op::{Query, ResponseCode};
+use crate::proto::rr::rdata::opt::{EdnsCode, EdnsOption, ExtendedError};
 use crate::proto::rr::rdata::SOA;
 use crate::proto::xfer::retry_dns_handle::RetryableError;
 use crate::proto::xfer::DnsResponse;
@@ -63,6 +64,15 @@ pub enum ResolveErrorKind {
     /// A request timed out
     #[error("request timed out")]
     Timeout,
+
+    /// A response had more records than allowed, see `ResolverOpts::max_records`
+    #[error("response has {count} records, more than the maximum of {max_records}")]
+    TooManyRecords {
+        /// The number of records, in the response or the chain of responses for the query
+        count: usize,
+        /// The maximum number of records allowed
+        max_records: usize,
+    },
 }
 
 impl Clone for ResolveErrorKind {
@@ -88,6 +98,10 @@ impl Clone for ResolveErrorKind {
             Io(io) => ResolveErrorKind::from(std::io::Error::from(io.kind())),
             Proto(proto) => ResolveErrorKind::from(proto.clone()),
             Timeout => Timeout,
+            TooManyRecords { count, max_records } => TooManyRecords {
+                count: *count,
+                max_records: *max_records,
+            },
         }
     }
 }
@@ -96,6 +110,7 @@ impl Clone for ResolveErrorKind {
 #[derive(Debug, Clone, Error)]
 pub struct ResolveError {
     pub(crate) kind: ResolveErrorKind,
+    extended_error: Option<ExtendedError>,
     #[cfg(feature = "with-backtrace")]
     backtrack: Option<ExtBacktrace>,
 }
@@ -123,9 +138,24 @@ impl ResolveError {
         &self.kind
     }
 
+    /// The Extended DNS Error, RFC 8914, sent by the name server with the failed response, if any
+    pub fn extended_error(&self) -> Option<&ExtendedError> {
+        self.extended_error.as_ref()
+    }
+
     /// A conversion to determine if the response is an error
+    ///
+    /// The Extended DNS Error of the response, if any, is kept with the error.
     pub fn from_response(response: DnsResponse, trust_nx: bool) -> Result<DnsResponse, Self> {
-        match response.response_code() {
+        let extended_error = match response
+            .edns()
+            .and_then(|edns| edns.option(EdnsCode::ExtendedError))
+        {
+            Some(EdnsOption::ExtendedError(error)) => Some(error.clone()),
+            _ => None,
+        };
+
+        let result = match response.response_code() {
             ResponseCode::ServFail => {
                 let note = "Nameserver responded with SERVFAIL";
                 debug!("{}", note);
@@ -189,8 +219,30 @@ impl ResolveError {
 
                 Err(ResolveError::from(error_kind))
             }
+            // the extended codes of the EDNS OPT record, e.g. BADVERS, are failures like SERVFAIL
+            response_code if response_code.is_extended() => {
+                debug!("Nameserver responded with {}", response_code);
+
+                let mut response = response;
+                let soa = response.soa();
+                let query = response.take_queries().drain(..).next().unwrap_or_default();
+                let error_kind = ResolveErrorKind::NoRecordsFound {
+                    query,
+                    soa,
+                    negative_ttl: None,
+                    response_code,
+                    trusted: false,
+                };
+
+                Err(ResolveError::from(error_kind))
+            }
             _ => Ok(response),
-        }
+        };
+
+        result.map_err(|mut error| {
+            error.extended_error = extended_error;
+            error
+        })
     }
 
     /// Compare two errors to see if one contains a server response.
@@ -249,18 +301,20 @@ impl RetryableError for ResolveError {
 
 impl fmt::Display for ResolveError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.kind, f)?;
+        if let Some(ref extended_error) = self.extended_error {
+            write!(f, ", {}", extended_error)?;
+        }
+
         cfg_if::cfg_if! {
             if #[cfg(feature = "with-backtrace")] {
                 if let Some(ref backtrace) = self.backtrack {
-                    fmt::Display::fmt(&self.kind, f)?;
-                    fmt::Debug::fmt(backtrace, f)
-                } else {
-                    fmt::Display::fmt(&self.kind, f)
+                    fmt::Debug::fmt(backtrace, f)?;
                 }
-            } else {
-                fmt::Display::fmt(&self.kind, f)
             }
         }
+
+        Ok(())
     }
 }
 
@@ -268,6 +322,7 @@ impl From<ResolveErrorKind> for ResolveError {
     fn from(kind: ResolveErrorKind) -> ResolveError {
         ResolveError {
             kind,
+            extended_error: None,
             #[cfg(feature = "with-backtrace")]
             backtrack: trace!(),
         }
@@ -325,3 +380,51 @@ impl<T> From<sync::PoisonError<T>> for ResolveError {
         ResolveErrorKind::Msg(format!("lock was poisoned, this is non-recoverable: {}", e)).into()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::proto::op::{Edns, Message};
+    use crate::proto::rr::{Name, RecordType};
+
+    use super::*;
+
+    #[test]
+    fn test_extended_error_from_response() {
+        let mut message = Message::new();
+        message.add_query(Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        message.set_response_code(ResponseCode::ServFail);
+
+        let mut edns = Edns::new();
+        edns.set_option(EdnsOption::ExtendedError(ExtendedError::new(
+            ExtendedError::DNSSEC_BOGUS,
+            "signature expired",
+        )));
+        message.set_edns(edns);
+
+        // round trip through the wire format, as the error would be received
+        let message = Message::from_vec(&message.to_vec().unwrap()).unwrap();
+        let error = ResolveError::from_response(DnsResponse::from(message), false).unwrap_err();
+
+        match error.kind() {
+            ResolveErrorKind::NoRecordsFound { response_code, .. } => {
+                assert_eq!(*response_code, ResponseCode::ServFail)
+            }
+            kind => panic!("unexpected error: {}", kind),
+        }
+
+        let extended_error = error.extended_error().expect("extended error missing");
+        assert_eq!(extended_error.info_code(), ExtendedError::DNSSEC_BOGUS);
+        assert_eq!(extended_error.extra_text(), "signature expired");
+        assert!(error.to_string().ends_with("DNSSEC Bogus (6): signature expired"));
+
+        let mut message = Message::new();
+        message.set_response_code(ResponseCode::ServFail);
+        let error = ResolveError::from_response(DnsResponse::from(message), false).unwrap_err();
+        assert!(error.extended_error().is_none());
+    }
+}
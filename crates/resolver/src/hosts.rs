@@ -21,12 +21,14 @@ struct LookupType {
     a: Option<Lookup>,
     /// represents the AAAA record type
     aaaa: Option<Lookup>,
+    /// represents the PTR record type, for reverse lookups of addresses
+    ptr: Option<Lookup>,
 }
 
 /// Configuration for the local hosts file
 #[derive(Debug, Default)]
 pub struct Hosts {
-    /// Name -> RDatas map
+    /// Name -> RDatas map, all names are stored as FQDNs
     by_name: HashMap<Name, LookupType>,
 }
 
@@ -38,13 +40,17 @@ impl Hosts {
         read_hosts_conf(hosts_path()).unwrap_or_default()
     }
 
-    /// Look up the addresses for the given host from the system hosts file.
+    /// Look up the addresses for the given host, or the host for a reverse address name, from the
+    ///  system hosts file.
+    ///
+    /// Names are matched case-insensitively, and as though they were fully qualified.
     pub fn lookup_static_host(&self, query: &Query) -> Option<Lookup> {
         if !self.by_name.is_empty() {
-            if let Some(val) = self.by_name.get(query.name()) {
+            if let Some(val) = self.by_name.get(&to_fqdn(query.name().clone())) {
                 let result = match query.query_type() {
                     RecordType::A => val.a.clone(),
                     RecordType::AAAA => val.aaaa.clone(),
+                    RecordType::PTR => val.ptr.clone(),
                     _ => None,
                 };
 
@@ -56,8 +62,13 @@ impl Hosts {
 
     /// Insert a new Lookup for the associated `Name` and `RecordType`
     pub fn insert(&mut self, name: Name, record_type: RecordType, lookup: Lookup) {
-        assert!(record_type == RecordType::A || record_type == RecordType::AAAA);
+        assert!(
+            record_type == RecordType::A
+                || record_type == RecordType::AAAA
+                || record_type == RecordType::PTR
+        );
 
+        let name = to_fqdn(name);
         let lookup_type = self
             .by_name
             .entry(name.clone())
@@ -73,6 +84,10 @@ impl Hosts {
                     let query = Query::query(name.clone(), record_type);
                     Lookup::new_with_max_ttl(query, Arc::from([]))
                 }),
+                RecordType::PTR => lookup_type.ptr.get_or_insert_with(|| {
+                    let query = Query::query(name.clone(), record_type);
+                    Lookup::new_with_max_ttl(query, Arc::from([]))
+                }),
                 _ => {
                     warn!("unsupported IP type from Hosts file: {:#?}", record_type);
                     return;
@@ -86,11 +101,18 @@ impl Hosts {
         match record_type {
             RecordType::A => lookup_type.a = Some(new_lookup),
             RecordType::AAAA => lookup_type.aaaa = Some(new_lookup),
+            RecordType::PTR => lookup_type.ptr = Some(new_lookup),
             _ => warn!("unsupported IP type from Hosts file"),
         }
     }
 }
 
+/// Hosts file entries are always absolute, the search list is never applied to them
+fn to_fqdn(mut name: Name) -> Name {
+    name.set_fqdn(true);
+    name
+}
+
 #[cfg(unix)]
 fn hosts_path() -> &'static str {
     "/etc/hosts"
@@ -109,6 +131,7 @@ fn hosts_path() -> std::path::PathBuf {
 pub fn read_hosts_conf<P: AsRef<Path>>(path: P) -> io::Result<Hosts> {
     use std::fs::File;
     use std::io::{BufRead, BufReader};
+    use std::net::IpAddr;
 
     use proto::rr::domain::TryParseIp;
 
@@ -144,28 +167,31 @@ pub fn read_hosts_conf<P: AsRef<Path>>(path: P) -> io::Result<Hosts> {
             continue;
         };
 
+        let (record_type, ip) = match addr {
+            RData::A(ip) => (RecordType::A, IpAddr::V4(ip)),
+            RData::AAAA(ip) => (RecordType::AAAA, IpAddr::V6(ip)),
+            _ => {
+                warn!("unsupported IP type from Hosts file: {:#?}", addr);
+                continue;
+            }
+        };
+        let reverse = Name::from(ip);
+
         for domain in fields.iter().skip(1).map(|domain| domain.to_lowercase()) {
             if let Ok(name) = Name::from_str(&domain) {
-                let record = Record::from_rdata(name.clone(), dns_lru::MAX_TTL, addr.clone());
-
-                match addr {
-                    RData::A(..) => {
-                        let query = Query::query(name.clone(), RecordType::A);
-                        let lookup = Lookup::new_with_max_ttl(query, Arc::from([record]));
-                        hosts.insert(name.clone(), RecordType::A, lookup);
-                    }
-                    RData::AAAA(..) => {
-                        let query = Query::query(name.clone(), RecordType::AAAA);
-                        let lookup = Lookup::new_with_max_ttl(query, Arc::from([record]));
-                        hosts.insert(name.clone(), RecordType::AAAA, lookup);
-                    }
-                    _ => {
-                        warn!("unsupported IP type from Hosts file: {:#?}", addr);
-                        continue;
-                    }
-                };
+                let name = to_fqdn(name);
 
-                // TODO: insert reverse lookup as well.
+                let record = Record::from_rdata(name.clone(), dns_lru::MAX_TTL, addr.clone());
+                let query = Query::query(name.clone(), record_type);
+                let lookup = Lookup::new_with_max_ttl(query, Arc::from([record]));
+                hosts.insert(name.clone(), record_type, lookup);
+
+                // the reverse lookup of the address to each of its names
+                let record =
+                    Record::from_rdata(reverse.clone(), dns_lru::MAX_TTL, RData::PTR(name));
+                let query = Query::query(reverse.clone(), RecordType::PTR);
+                let lookup = Lookup::new_with_max_ttl(query, Arc::from([record]));
+                hosts.insert(reverse.clone(), RecordType::PTR, lookup);
             };
         }
     }
@@ -186,7 +212,7 @@ pub fn read_hosts_conf<P: AsRef<Path>>(_path: P) -> io::Result<Hosts> {
 mod tests {
     use super::*;
     use std::env;
-    use std::net::{Ipv4Addr, Ipv6Addr};
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
     fn tests_dir() -> String {
         let server_path = env::var("TDNS_WORKSPACE_ROOT").unwrap_or_else(|_| "../..".to_owned());
@@ -255,5 +281,70 @@ mod tests {
             .map(ToOwned::to_owned)
             .collect::<Vec<RData>>();
         assert_eq!(rdatas, vec![RData::A(Ipv4Addr::new(10, 0, 1, 111))]);
+
+        let v6 = RData::AAAA(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+        for name in &["v6.example.com", "v6-alias"] {
+            let name = Name::from_str(name).unwrap();
+            let rdatas = hosts
+                .lookup_static_host(&Query::query(name, RecordType::AAAA))
+                .unwrap()
+                .iter()
+                .map(ToOwned::to_owned)
+                .collect::<Vec<RData>>();
+            assert_eq!(rdatas, vec![v6.clone()]);
+        }
+    }
+
+    #[test]
+    fn test_hosts_case_insensitive_fqdn() {
+        let path = format!("{}/hosts", tests_dir());
+        let hosts = read_hosts_conf(&path).unwrap();
+
+        for name in &["mixed.example.com", "MIXED.EXAMPLE.COM.", "Mixed.Example.Com."] {
+            let name = Name::from_str(name).unwrap();
+            let rdatas = hosts
+                .lookup_static_host(&Query::query(name, RecordType::A))
+                .unwrap()
+                .iter()
+                .map(ToOwned::to_owned)
+                .collect::<Vec<RData>>();
+            assert_eq!(rdatas, vec![RData::A(Ipv4Addr::new(10, 0, 1, 112))]);
+        }
+    }
+
+    #[test]
+    fn test_hosts_reverse() {
+        let path = format!("{}/hosts", tests_dir());
+        let hosts = read_hosts_conf(&path).unwrap();
+
+        let name = Name::from(IpAddr::V4(Ipv4Addr::new(10, 0, 1, 111)));
+        let rdatas = hosts
+            .lookup_static_host(&Query::query(name, RecordType::PTR))
+            .unwrap()
+            .iter()
+            .map(ToOwned::to_owned)
+            .collect::<Vec<RData>>();
+        assert_eq!(
+            rdatas,
+            vec![
+                RData::PTR(Name::from_str("a.example.com.").unwrap()),
+                RData::PTR(Name::from_str("b.example.com.").unwrap()),
+            ]
+        );
+
+        let name = Name::from(IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)));
+        let rdatas = hosts
+            .lookup_static_host(&Query::query(name, RecordType::PTR))
+            .unwrap()
+            .iter()
+            .map(ToOwned::to_owned)
+            .collect::<Vec<RData>>();
+        assert_eq!(
+            rdatas,
+            vec![
+                RData::PTR(Name::from_str("v6.example.com.").unwrap()),
+                RData::PTR(Name::from_str("v6-alias.").unwrap()),
+            ]
+        );
     }
 }
@@ -85,4 +85,9 @@ mod tests {
     fn test_cloudflare_https() {
         https_test(ResolverConfig::cloudflare_https())
     }
+
+    #[test]
+    fn test_google_https() {
+        https_test(ResolverConfig::google_https())
+    }
 }
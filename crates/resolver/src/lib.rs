@@ -194,6 +194,7 @@ pub extern crate trust_dns_proto as proto;
 
 mod async_resolver;
 pub mod caching_client;
+mod clock;
 pub mod config;
 mod dns_lru;
 pub mod dns_sd;
@@ -207,6 +208,8 @@ pub mod lookup_ip;
 pub mod name_server;
 #[cfg(feature = "tokio-runtime")]
 mod resolver;
+#[cfg(feature = "tokio-runtime")]
+mod resolver_service;
 pub mod system_conf;
 #[cfg(feature = "dns-over-tls")]
 mod tls;
@@ -225,6 +228,8 @@ pub use name_server::ConnectionProvider;
 pub use name_server::{TokioConnection, TokioConnectionProvider, TokioHandle};
 #[cfg(feature = "tokio-runtime")]
 pub use resolver::Resolver;
+#[cfg(feature = "tokio-runtime")]
+pub use resolver_service::ResolverService;
 
 /// This is an alias for [`AsyncResolver`], which replaced the type previously
 /// called `ResolverFuture`.
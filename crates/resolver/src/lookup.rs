@@ -17,9 +17,10 @@ use std::task::{Context, Poll};
 use std::time::{Duration, Instant};
 
 use futures_util::{future, future::Future, FutureExt};
+use rand::Rng;
 
 use proto::error::ProtoError;
-use proto::op::Query;
+use proto::op::{Message, Query};
 use proto::rr::rdata;
 use proto::rr::{Name, RData, Record, RecordType};
 use proto::xfer::{DnsRequest, DnsRequestOptions, DnsResponse};
@@ -41,22 +42,36 @@ pub struct Lookup {
     query: Query,
     records: Arc<[Record]>,
     valid_until: Instant,
+    authentic_data: bool,
+    stale: bool,
 }
 
 impl Lookup {
     /// Return new instance with given rdata and the maximum TTL.
     pub fn from_rdata(query: Query, rdata: RData) -> Self {
+        Self::from_rdata_at(query, rdata, Instant::now())
+    }
+
+    /// As `from_rdata`, with the maximum TTL from `now`, e.g. the time read from a `Clock`
+    pub(crate) fn from_rdata_at(query: Query, rdata: RData, now: Instant) -> Self {
         let record = Record::from_rdata(query.name().clone(), MAX_TTL, rdata);
-        Self::new_with_max_ttl(query, Arc::from([record]))
+        Self::new_with_max_ttl_at(query, Arc::from([record]), now)
     }
 
     /// Return new instance with given records and the maximum TTL.
     pub fn new_with_max_ttl(query: Query, records: Arc<[Record]>) -> Self {
-        let valid_until = Instant::now() + Duration::from_secs(u64::from(MAX_TTL));
+        Self::new_with_max_ttl_at(query, records, Instant::now())
+    }
+
+    /// As `new_with_max_ttl`, with the maximum TTL from `now`
+    pub(crate) fn new_with_max_ttl_at(query: Query, records: Arc<[Record]>, now: Instant) -> Self {
+        let valid_until = now + Duration::from_secs(u64::from(MAX_TTL));
         Lookup {
             query,
             records,
             valid_until,
+            authentic_data: false,
+            stale: false,
         }
     }
 
@@ -66,9 +81,49 @@ impl Lookup {
             query,
             records,
             valid_until,
+            authentic_data: false,
+            stale: false,
+        }
+    }
+
+    /// Return a new instance with the answers of the response to its first query, valid for the
+    ///  minimum TTL of the answers, up to the maximum TTL
+    pub fn from_message(message: &Message) -> Result<Self, ResolveError> {
+        Self::from_message_at(message, Instant::now())
+    }
+
+    /// As `from_message`, with the TTL from `now`
+    pub(crate) fn from_message_at(message: &Message, now: Instant) -> Result<Self, ResolveError> {
+        let query = message
+            .queries()
+            .first()
+            .cloned()
+            .ok_or_else(|| ResolveError::from("response does not contain a query"))?;
+
+        let ttl = message
+            .answers()
+            .iter()
+            .map(Record::ttl)
+            .fold(MAX_TTL, u32::min);
+        let valid_until = now + Duration::from_secs(u64::from(ttl));
+
+        let lookup = Self::new_with_deadline(query, Arc::from(message.answers()), valid_until);
+        Ok(lookup.with_authentic_data(message.authentic_data()))
+    }
+
+    /// Sets whether the records were authenticated, i.e. the AD bit of the response
+    pub(crate) fn with_authentic_data(self, authentic_data: bool) -> Self {
+        Lookup {
+            authentic_data,
+            ..self
         }
     }
 
+    /// Sets whether the records were served from the cache after they expired
+    pub(crate) fn with_stale(self, stale: bool) -> Self {
+        Lookup { stale, ..self }
+    }
+
     /// Returns a reference to the `Query` that was used to produce this result.
     pub fn query(&self) -> &Query {
         &self.query
@@ -79,7 +134,13 @@ impl Lookup {
         LookupIter(self.records.iter())
     }
 
-    /// Returns a borrowed iterator of the returned IPs
+    /// Returns a borrowed iterator of the addresses of the A and AAAA records, other records are
+    ///  skipped
+    pub fn ip_addrs(&self) -> LookupIpIter {
+        LookupIpIter(self.iter())
+    }
+
+    /// Returns a borrowed iterator of the returned records
     pub fn record_iter(&self) -> LookupRecordIter {
         LookupRecordIter(self.records.iter())
     }
@@ -89,6 +150,20 @@ impl Lookup {
         self.valid_until
     }
 
+    /// Returns true if the records were authenticated with DNSSEC, i.e. the AD, authentic data,
+    ///  bit was set on the response
+    ///
+    /// This is kept for answers returned from the cache.
+    pub fn authentic_data(&self) -> bool {
+        self.authentic_data
+    }
+
+    /// Returns true if the records had expired, and were served from the cache as the name
+    ///  servers failed to answer, see `ResolverOpts::serve_stale_ttl`
+    pub fn is_stale(&self) -> bool {
+        self.stale
+    }
+
     #[doc(hidden)]
     pub fn is_empty(&self) -> bool {
         self.records.is_empty()
@@ -98,7 +173,7 @@ impl Lookup {
         self.records.len()
     }
 
-    #[cfg(test)]
+    /// Returns the records, along with their TTLs and types
     pub fn records(&self) -> &[Record] {
         self.records.as_ref()
     }
@@ -111,7 +186,11 @@ impl Lookup {
 
         // Choose the sooner deadline of the two lookups.
         let valid_until = min(self.valid_until(), other.valid_until());
+        let authentic_data = self.authentic_data && other.authentic_data;
+        let stale = self.stale || other.stale;
         Self::new_with_deadline(self.query.clone(), Arc::from(records), valid_until)
+            .with_authentic_data(authentic_data)
+            .with_stale(stale)
     }
 }
 
@@ -333,6 +412,28 @@ impl SrvLookup {
         LookupIpIter(self.0.iter())
     }
 
+    /// Returns the SRV RData in the order in which the targets should be contacted
+    ///
+    /// [RFC 2782](https://tools.ietf.org/html/rfc2782), DNS SRV RR, February 2000
+    ///
+    /// ```text
+    /// Priority
+    ///     The priority of this target host.  A client MUST attempt to
+    ///     contact the target host with the lowest-numbered priority it can
+    ///     reach; target hosts with the same priority SHOULD be tried in an
+    ///     order defined by the weight field.
+    ///
+    /// Target
+    ///     The domain name of the target host. ... A Target of "." means
+    ///     that the service is decidedly not available at this domain.
+    /// ```
+    ///
+    /// Records of the same priority are ordered by a weighted random selection, see the usage
+    ///  rules of RFC 2782. An empty list is returned if the service is not available.
+    pub fn ordered(&self) -> Vec<&rdata::SRV> {
+        order_srvs(self.iter().collect(), &mut rand::thread_rng())
+    }
+
     /// Return a reference to the inner lookup
     ///
     /// This can be useful for getting all records from the request
@@ -341,6 +442,52 @@ impl SrvLookup {
     }
 }
 
+/// Orders the SRVs by priority, and within a priority by a weighted random selection
+///
+/// [RFC 2782](https://tools.ietf.org/html/rfc2782), DNS SRV RR, February 2000
+///
+/// ```text
+/// To select a target to be contacted next, arrange all SRV RRs
+/// (that have not been ordered yet) in any order, except that all
+/// those with weight 0 are placed at the beginning of the list.
+///
+/// Compute the sum of the weights of those RRs, and with each RR
+/// associate the running sum in the selected order. Then choose a
+/// uniform random number between 0 and the sum computed
+/// (inclusive), and select the RR whose running sum value is the
+/// first in the selected order which is greater than or equal to
+/// the random number selected.
+/// ```
+fn order_srvs<'a, R: Rng>(mut srvs: Vec<&'a rdata::SRV>, rng: &mut R) -> Vec<&'a rdata::SRV> {
+    // the service is decidedly not available at this domain
+    if srvs.len() == 1 && srvs[0].target().is_root() {
+        return Vec::new();
+    }
+
+    srvs.sort_by_key(|srv| (srv.priority(), srv.weight() != 0));
+
+    let mut ordered = Vec::with_capacity(srvs.len());
+    while !srvs.is_empty() {
+        let priority = srvs[0].priority();
+        let group = srvs.iter().take_while(|srv| srv.priority() == priority).count();
+        let total: u32 = srvs[..group].iter().map(|srv| u32::from(srv.weight())).sum();
+
+        let selected = rng.gen_range(0, total + 1);
+        let mut running = 0;
+        let next = srvs[..group]
+            .iter()
+            .position(|srv| {
+                running += u32::from(srv.weight());
+                running >= selected
+            })
+            .unwrap_or(0);
+
+        ordered.push(srvs.remove(next));
+    }
+
+    ordered
+}
+
 impl From<Lookup> for SrvLookup {
     fn from(lookup: Lookup) -> Self {
         SrvLookup(lookup)
@@ -501,6 +648,13 @@ lookup_type!(
     RData::MX,
     rdata::MX
 );
+lookup_type!(
+    CaaLookup,
+    CaaLookupIter,
+    CaaLookupIntoIter,
+    RData::CAA,
+    rdata::CAA
+);
 lookup_type!(
     TlsaLookup,
     TlsaLookupIter,
@@ -523,6 +677,13 @@ lookup_type!(
     rdata::SOA
 );
 lookup_type!(NsLookup, NsLookupIter, NsLookupIntoIter, RData::NS, Name);
+lookup_type!(
+    HttpsLookup,
+    HttpsLookupIter,
+    HttpsLookupIntoIter,
+    RData::HTTPS,
+    rdata::SVCB
+);
 
 #[cfg(test)]
 pub mod tests {
@@ -601,6 +762,96 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn test_lookup_from_message() {
+        let name = Name::from_str("www.example.com.").unwrap();
+        let alias = Name::from_str("example.com.").unwrap();
+        let mut message = Message::new();
+        message.add_query(Query::query(name.clone(), RecordType::A));
+        message.insert_answers(vec![
+            Record::from_rdata(name, 3600, RData::CNAME(alias.clone())),
+            Record::from_rdata(alias.clone(), 300, RData::A(Ipv4Addr::new(127, 0, 0, 1))),
+            Record::from_rdata(alias, 60, RData::A(Ipv4Addr::new(127, 0, 0, 2))),
+        ]);
+
+        let now = Instant::now();
+        let lookup = Lookup::from_message_at(&message, now).expect("response has a query");
+
+        // the lookup expires with the record of the minimum TTL
+        assert_eq!(lookup.valid_until(), now + Duration::from_secs(60));
+
+        assert_eq!(lookup.query(), &message.queries()[0]);
+        assert_eq!(
+            lookup.ip_addrs().collect::<Vec<_>>(),
+            vec![Ipv4Addr::new(127, 0, 0, 1), Ipv4Addr::new(127, 0, 0, 2)]
+        );
+        assert_eq!(
+            lookup
+                .records()
+                .iter()
+                .map(|r| (r.record_type(), r.ttl()))
+                .collect::<Vec<_>>(),
+            vec![
+                (RecordType::CNAME, 3600),
+                (RecordType::A, 300),
+                (RecordType::A, 60)
+            ]
+        );
+
+        assert!(Lookup::from_message(&Message::new()).is_err());
+    }
+
+    fn srv(priority: u16, weight: u16, target: &str) -> rdata::SRV {
+        rdata::SRV::new(priority, weight, 443, Name::from_str(target).unwrap())
+    }
+
+    #[test]
+    fn test_srv_priority_order() {
+        let srvs = vec![
+            srv(20, 0, "c.example.com."),
+            srv(10, 5, "b.example.com."),
+            srv(5, 100, "a.example.com."),
+            srv(20, 0, "d.example.com."),
+        ];
+
+        let ordered = order_srvs(srvs.iter().collect(), &mut rand::thread_rng());
+        let priorities: Vec<u16> = ordered.iter().map(|srv| srv.priority()).collect();
+        assert_eq!(priorities, vec![5, 10, 20, 20]);
+    }
+
+    #[test]
+    fn test_srv_weighted_order() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let srvs = vec![srv(10, 60, "a.example.com."), srv(10, 20, "b.example.com.")];
+        let mut rng = StdRng::seed_from_u64(2782);
+
+        let mut a_first = 0;
+        for _ in 0..10_000 {
+            let ordered = order_srvs(srvs.iter().collect(), &mut rng);
+            assert_eq!(ordered.len(), 2);
+            if ordered[0] == &srvs[0] {
+                a_first += 1;
+            }
+        }
+
+        // a is selected first for 61 of the 81 possible random values
+        assert!(a_first > 7_200 && a_first < 7_800, "{}", a_first);
+    }
+
+    #[test]
+    fn test_srv_not_available() {
+        let srvs = vec![srv(0, 0, ".")];
+        assert!(order_srvs(srvs.iter().collect(), &mut rand::thread_rng()).is_empty());
+
+        let lookup = SrvLookup::from(Lookup::from_rdata(
+            Query::query(Name::from_str("_https._tcp.example.com.").unwrap(), RecordType::SRV),
+            RData::SRV(srv(0, 0, ".")),
+        ));
+        assert_eq!(lookup.iter().count(), 1);
+        assert!(lookup.ordered().is_empty());
+    }
+
     #[test]
     fn test_lookup_into_iter() {
         assert_eq!(
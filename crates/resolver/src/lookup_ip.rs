@@ -16,7 +16,8 @@ use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::time::Instant;
 
-use futures_util::{future, future::Either, future::Future, FutureExt};
+use futures_util::stream::{self, Stream, StreamExt};
+use futures_util::{future, future::Future, FutureExt};
 
 use proto::error::ProtoError;
 use proto::op::Query;
@@ -230,6 +231,160 @@ where
     }
 }
 
+/// The Stream returned from [`AsyncResolver::lookup_ip_stream`], yielding addresses as each of
+///  the A and AAAA queries completes
+///
+/// [`AsyncResolver::lookup_ip_stream`]: crate::AsyncResolver::lookup_ip_stream
+#[must_use = "streams do nothing unless polled"]
+pub struct LookupIpStream<C, E>
+where
+    C: DnsHandle<Error = E> + 'static,
+    E: Into<ResolveError> + From<ProtoError> + Error + Clone + Send + Unpin + 'static,
+{
+    client_cache: CachingClient<C, E>,
+    names: Vec<Name>,
+    strategy: LookupIpStrategy,
+    options: DnsRequestOptions,
+    query: Option<Pin<Box<dyn Stream<Item = IpAddr> + Send>>>,
+    hosts: Option<Arc<Hosts>>,
+    found: bool,
+}
+
+impl<C, E> Stream for LookupIpStream<C, E>
+where
+    C: DnsHandle<Error = E> + 'static,
+    E: Into<ResolveError> + From<ProtoError> + Error + Clone + Send + Unpin + 'static,
+{
+    type Item = IpAddr;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(query) = self.query.as_mut() {
+                match query.as_mut().poll_next(cx) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(Some(ip)) => {
+                        self.found = true;
+                        return Poll::Ready(Some(ip));
+                    }
+                    Poll::Ready(None) => {
+                        // the next name is only tried if nothing was found for this one
+                        self.query = None;
+                        if self.found {
+                            return Poll::Ready(None);
+                        }
+                    }
+                }
+            }
+
+            match self.names.pop() {
+                Some(name) => {
+                    self.query = Some(strategic_stream(
+                        name,
+                        self.strategy,
+                        self.client_cache.clone(),
+                        self.options.clone(),
+                        self.hosts.clone(),
+                    ))
+                }
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+impl<C, E> LookupIpStream<C, E>
+where
+    C: DnsHandle<Error = E> + 'static,
+    E: Into<ResolveError> + From<ProtoError> + Error + Clone + Send + Unpin + 'static,
+{
+    /// Perform a lookup from a hostname to a stream of IPs
+    ///
+    /// The A and AAAA queries are sent in parallel, unless the strategy is only for one of them,
+    ///  the addresses of each are yielded as soon as its query completes. Failed queries yield no
+    ///  addresses, the stream ends once all of the queries have completed.
+    ///
+    /// # Arguments
+    ///
+    /// * `names` - a set of DNS names to attempt to resolve, they will be attempted in queue order, i.e. the first is `names.pop()`. The next is only attempted if no addresses are found.
+    /// * `strategy` - the lookup IP strategy to use
+    /// * `client_cache` - cache with a connection to use for performing all lookups
+    pub fn lookup(
+        names: Vec<Name>,
+        strategy: LookupIpStrategy,
+        client_cache: CachingClient<C, E>,
+        options: DnsRequestOptions,
+        hosts: Option<Arc<Hosts>>,
+    ) -> Self {
+        LookupIpStream {
+            client_cache,
+            names,
+            strategy,
+            options,
+            query: None,
+            hosts,
+            found: false,
+        }
+    }
+
+    /// A stream of the single address, e.g. when the host to lookup was an IP address
+    pub(crate) fn from_ip(ip: IpAddr, client_cache: CachingClient<C, E>) -> Self {
+        let mut lookup = Self::lookup(
+            vec![],
+            LookupIpStrategy::default(),
+            client_cache,
+            DnsRequestOptions::default(),
+            None,
+        );
+        lookup.query = Some(stream::once(future::ready(ip)).boxed());
+        lookup
+    }
+}
+
+/// returns a new stream for lookup, only the `Ipv4Only` and `Ipv6Only` strategies are respected,
+///  otherwise both are queried in parallel
+fn strategic_stream<C, E>(
+    name: Name,
+    strategy: LookupIpStrategy,
+    client: CachingClient<C, E>,
+    options: DnsRequestOptions,
+    hosts: Option<Arc<Hosts>>,
+) -> Pin<Box<dyn Stream<Item = IpAddr> + Send>>
+where
+    C: DnsHandle<Error = E> + 'static,
+    E: Into<ResolveError> + From<ProtoError> + Error + Clone + Send + Unpin + 'static,
+{
+    let query_stream = |record_type| {
+        hosts_lookup(
+            Query::query(name.clone(), record_type),
+            client.clone(),
+            options.clone(),
+            hosts.clone(),
+        )
+        .into_stream()
+        .flat_map(|lookup| {
+            let ips = match lookup {
+                Ok(lookup) => LookupIp::from(lookup).iter().collect(),
+                Err(e) => {
+                    debug!("lookup failed in stream of ips: {}", e);
+                    Vec::new()
+                }
+            };
+
+            stream::iter(ips)
+        })
+    };
+
+    match strategy {
+        LookupIpStrategy::Ipv4Only => query_stream(RecordType::A).boxed(),
+        LookupIpStrategy::Ipv6Only => query_stream(RecordType::AAAA).boxed(),
+        LookupIpStrategy::Ipv4AndIpv6
+        | LookupIpStrategy::Ipv6thenIpv4
+        | LookupIpStrategy::Ipv4thenIpv6 => {
+            stream::select(query_stream(RecordType::A), query_stream(RecordType::AAAA)).boxed()
+        }
+    }
+}
+
 /// returns a new future for lookup
 async fn strategic_lookup<C, E>(
     name: Name,
@@ -300,7 +455,7 @@ where
 }
 
 // TODO: this really needs to have a stream interface
-/// queries only for A and AAAA in parallel
+/// queries only for A and AAAA in parallel, the ipv4 addresses are always ordered before the ipv6
 async fn ipv4_and_ipv6<C, E>(
     name: Name,
     client: CachingClient<C, E>,
@@ -311,25 +466,19 @@ where
     C: DnsHandle<Error = E> + 'static,
     E: Into<ResolveError> + From<ProtoError> + Error + Clone + Send + Unpin + 'static,
 {
-    let sel_res = future::select(
+    // both are required for the result, join rather than select so that the order of the
+    //  addresses doesn't depend on which of the queries completed first
+    let (ips, next_ips) = future::join(
         hosts_lookup(
             Query::query(name.clone(), RecordType::A),
             client.clone(),
             options.clone(),
             hosts.clone(),
-        )
-        .boxed(),
-        hosts_lookup(Query::query(name, RecordType::AAAA), client, options, hosts).boxed(),
+        ),
+        hosts_lookup(Query::query(name, RecordType::AAAA), client, options, hosts),
     )
     .await;
 
-    let (ips, remaining_query) = match sel_res {
-        Either::Left(ips_and_remaining) => ips_and_remaining,
-        Either::Right(ips_and_remaining) => ips_and_remaining,
-    };
-
-    let next_ips = remaining_query.await;
-
     match (ips, next_ips) {
         (Ok(ips), Ok(next_ips)) => {
             // TODO: create a LookupIp enum with the ability to chain these together
@@ -450,11 +599,12 @@ where
 pub mod tests {
     use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
     use std::sync::{Arc, Mutex};
+    use std::task::Waker;
 
     use futures_executor::block_on;
     use futures_util::{future, future::Future};
 
-    use proto::op::Message;
+    use proto::op::{Message, ResponseCode};
     use proto::rr::{Name, RData, Record};
     use proto::xfer::{DnsHandle, DnsRequest, DnsResponse};
 
@@ -506,6 +656,14 @@ pub mod tests {
         Ok(resp)
     }
 
+    pub fn nx_message() -> Result<DnsResponse, ResolveError> {
+        let mut message = Message::new();
+        message.add_query(Query::query(Name::root(), RecordType::A));
+        message.set_response_code(ResponseCode::NXDomain);
+
+        Ok(message.into())
+    }
+
     pub fn empty() -> Result<DnsResponse, ResolveError> {
         Ok(Message::new().into())
     }
@@ -520,6 +678,46 @@ pub mod tests {
         }
     }
 
+    /// Answers A queries immediately, and AAAA queries only once released
+    #[derive(Clone, Default)]
+    struct GatedDnsHandle {
+        released: Arc<Mutex<(bool, Option<Waker>)>>,
+    }
+
+    impl GatedDnsHandle {
+        fn release(&self) {
+            let mut released = self.released.lock().unwrap();
+            released.0 = true;
+            if let Some(waker) = released.1.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    impl DnsHandle for GatedDnsHandle {
+        type Response =
+            Pin<Box<dyn Future<Output = Result<DnsResponse, ResolveError>> + Send + Unpin>>;
+        type Error = ResolveError;
+
+        fn send<R: Into<DnsRequest>>(&mut self, request: R) -> Self::Response {
+            let request = request.into();
+            if request.queries()[0].query_type() == RecordType::A {
+                return Box::pin(future::ready(v4_message()));
+            }
+
+            let released = Arc::clone(&self.released);
+            Box::pin(future::poll_fn(move |cx| {
+                let mut released = released.lock().unwrap();
+                if released.0 {
+                    Poll::Ready(v6_message())
+                } else {
+                    released.1 = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }))
+        }
+    }
+
     #[test]
     fn test_ipv4_only_strategy() {
         assert_eq!(
@@ -634,6 +832,30 @@ pub mod tests {
             .collect::<Vec<IpAddr>>(),
             vec![IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1))]
         );
+
+        // NXDOMAIN for ipv6, ipv4 available
+        assert_eq!(
+            block_on(ipv4_and_ipv6(
+                Name::root(),
+                CachingClient::new(0, mock(vec![nx_message(), v4_message()]), false),
+                Default::default(),
+                None,
+            ))
+            .unwrap()
+            .iter()
+            .map(|r| r.to_ip_addr().unwrap())
+            .collect::<Vec<IpAddr>>(),
+            vec![IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))]
+        );
+
+        // both NXDOMAIN
+        assert!(block_on(ipv4_and_ipv6(
+            Name::root(),
+            CachingClient::new(0, mock(vec![nx_message(), nx_message()]), false),
+            Default::default(),
+            None,
+        ))
+        .is_err());
     }
 
     #[test]
@@ -731,4 +953,30 @@ pub mod tests {
             vec![Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)]
         );
     }
+
+    #[test]
+    fn test_lookup_ip_stream() {
+        let handle = GatedDnsHandle::default();
+        let mut stream = LookupIpStream::lookup(
+            vec![Name::root()],
+            LookupIpStrategy::Ipv4AndIpv6,
+            CachingClient::new(0, handle.clone(), false),
+            Default::default(),
+            None,
+        );
+
+        // the ipv4 address is yielded while the ipv6 query is outstanding
+        assert_eq!(
+            block_on(stream.next()),
+            Some(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)))
+        );
+        assert!(stream.next().now_or_never().is_none());
+
+        handle.release();
+        assert_eq!(
+            block_on(stream.next()),
+            Some(IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)))
+        );
+        assert_eq!(block_on(stream.next()), None);
+    }
 }
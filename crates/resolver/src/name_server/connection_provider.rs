@@ -5,11 +5,13 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std::io;
 use std::marker::Unpin;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 
-use futures_util::future::{Future, FutureExt};
+use futures_util::future::{self, Future, FutureExt};
 use futures_util::ready;
 #[cfg(feature = "tokio-runtime")]
 use tokio::net::TcpStream as TokioTcpStream;
@@ -26,6 +28,7 @@ use tokio_rustls::client::TlsStream as TokioTlsStream;
 
 use proto;
 use proto::error::ProtoError;
+use proto::op::MessageFinalizer;
 
 #[cfg(feature = "tokio-runtime")]
 use proto::{iocompat::AsyncIoTokioAsStd, TokioTime};
@@ -33,8 +36,6 @@ use proto::{iocompat::AsyncIoTokioAsStd, TokioTime};
 #[cfg(feature = "mdns")]
 use proto::multicast::{MdnsClientConnect, MdnsClientStream, MdnsQueryType};
 
-use proto::op::NoopMessageFinalizer;
-
 use proto::udp::UdpClientStream;
 use proto::xfer::{DnsExchange, DnsExchangeSend, DnsHandle, DnsRequest, DnsResponse};
 
@@ -52,6 +53,7 @@ use trust_dns_https::{self, HttpsClientConnect, HttpsClientStream};
 
 use crate::config::Protocol;
 use crate::config::{NameServerConfig, ResolverOpts};
+use crate::name_server::socks5;
 
 /// A type to allow for custom ConnectionProviders. Needed mainly for mocking purposes.
 ///
@@ -119,10 +121,24 @@ where
         config: &NameServerConfig,
         options: &ResolverOpts,
     ) -> Self::FutureConn {
-        let dns_connect = match config.protocol {
-            Protocol::Udp => {
+        let signer = config.signer.as_ref().map(|signer| Arc::clone(&signer.0));
+
+        // all connections through a proxy are over TCP, see `Protocol::is_proxyable`
+        let protocol = match options.proxy {
+            Some(_) => Protocol::Tcp,
+            None => config.protocol,
+        };
+
+        let dns_connect = match protocol {
+            // a dual NameServer connects over TCP with a Tcp config of its own
+            Protocol::Udp | Protocol::Dual => {
                 let stream =
-                    UdpClientStream::<R::Udp>::with_timeout(config.socket_addr, options.timeout);
+                    UdpClientStream::<R::Udp, dyn MessageFinalizer>::with_bind_addr_and_timeout(
+                        config.socket_addr,
+                        config.bind_addr,
+                        options.timeout,
+                        signer,
+                    );
                 let exchange = DnsExchange::connect(stream);
                 ConnectionConnect::Udp(exchange)
             }
@@ -130,15 +146,28 @@ where
                 let socket_addr = config.socket_addr;
                 let timeout = options.timeout;
 
-                let (stream, handle) =
-                    TcpClientStream::<R::Tcp>::with_timeout(socket_addr, timeout);
-                // TODO: need config for Signer...
-                let dns_conn = DnsMultiplexer::with_timeout(
-                    stream,
-                    handle,
-                    timeout,
-                    NoopMessageFinalizer::new(),
-                );
+                let (stream, handle) = match options.proxy {
+                    Some(proxy) if config.protocol.is_proxyable() => {
+                        TcpClientStream::<R::Tcp>::with_future(
+                            socks5::connect(proxy.socket_addr, socket_addr, config.bind_addr),
+                            socket_addr,
+                            timeout,
+                        )
+                    }
+                    Some(_) => {
+                        let unsupported = future::err(io::Error::new(
+                            io::ErrorKind::Other,
+                            format!("{} is not supported through a proxy", config.protocol),
+                        ));
+                        TcpClientStream::<R::Tcp>::with_future(unsupported, socket_addr, timeout)
+                    }
+                    None => TcpClientStream::<R::Tcp>::with_bind_addr_and_timeout(
+                        socket_addr,
+                        config.bind_addr,
+                        timeout,
+                    ),
+                };
+                let dns_conn = DnsMultiplexer::with_timeout(stream, handle, timeout, signer);
 
                 let exchange = DnsExchange::connect(dns_conn);
                 ConnectionConnect::Tcp(exchange)
@@ -147,7 +176,15 @@ where
             Protocol::Tls => {
                 let socket_addr = config.socket_addr;
                 let timeout = options.timeout;
-                let tls_dns_name = config.tls_dns_name.clone().unwrap_or_default();
+                let tls_dns_name = match tls_dns_name(config) {
+                    Ok(tls_dns_name) => tls_dns_name,
+                    Err(error) => {
+                        return ConnectionFuture {
+                            connect: ConnectionConnect::Error(Some(error)),
+                            spawner: self.0.clone(),
+                        }
+                    }
+                };
                 #[cfg(feature = "dns-over-rustls")]
                 let client_config = config.tls_config.clone();
 
@@ -157,12 +194,8 @@ where
                 #[cfg(not(feature = "dns-over-rustls"))]
                 let (stream, handle) = { crate::tls::new_tls_stream(socket_addr, tls_dns_name) };
 
-                let dns_conn = DnsMultiplexer::with_timeout(
-                    stream,
-                    Box::new(handle),
-                    timeout,
-                    NoopMessageFinalizer::new(),
-                );
+                let dns_conn =
+                    DnsMultiplexer::with_timeout(stream, Box::new(handle), timeout, signer);
 
                 let exchange = DnsExchange::connect(dns_conn);
                 ConnectionConnect::Tls(exchange)
@@ -170,7 +203,15 @@ where
             #[cfg(feature = "dns-over-https")]
             Protocol::Https => {
                 let socket_addr = config.socket_addr;
-                let tls_dns_name = config.tls_dns_name.clone().unwrap_or_default();
+                let tls_dns_name = match tls_dns_name(config) {
+                    Ok(tls_dns_name) => tls_dns_name,
+                    Err(error) => {
+                        return ConnectionFuture {
+                            connect: ConnectionConnect::Error(Some(error)),
+                            spawner: self.0.clone(),
+                        }
+                    }
+                };
                 #[cfg(feature = "dns-over-rustls")]
                 let client_config = config.tls_config.clone();
 
@@ -185,13 +226,7 @@ where
 
                 let (stream, handle) =
                     MdnsClientStream::new(socket_addr, MdnsQueryType::OneShot, None, None, None);
-                // TODO: need config for Signer...
-                let dns_conn = DnsMultiplexer::with_timeout(
-                    stream,
-                    handle,
-                    timeout,
-                    NoopMessageFinalizer::new(),
-                );
+                let dns_conn = DnsMultiplexer::with_timeout(stream, handle, timeout, signer);
 
                 let exchange = DnsExchange::connect(dns_conn);
                 ConnectionConnect::Mdns(exchange)
@@ -205,18 +240,37 @@ where
     }
 }
 
+/// The DNS name to verify the certificate of an encrypted name server against
+#[cfg(any(feature = "dns-over-tls", feature = "dns-over-https"))]
+fn tls_dns_name(config: &NameServerConfig) -> Result<String, ResolveError> {
+    config.tls_dns_name.clone().ok_or_else(|| {
+        ResolveError::from(format!(
+            "tls_dns_name is required for encrypted connections: {}",
+            config
+        ))
+    })
+}
+
 /// The variants of all supported connections for the Resolver
 #[allow(clippy::large_enum_variant, clippy::type_complexity)]
 pub(crate) enum ConnectionConnect<R: RuntimeProvider> {
-    Udp(DnsExchangeConnect<UdpClientConnect<R::Udp>, UdpClientStream<R::Udp>, R::Timer>),
+    /// The connection can not be made, the error is taken when polled
+    Error(Option<ResolveError>),
+    Udp(
+        DnsExchangeConnect<
+            UdpClientConnect<R::Udp, dyn MessageFinalizer>,
+            UdpClientStream<R::Udp, dyn MessageFinalizer>,
+            R::Timer,
+        >,
+    ),
     Tcp(
         DnsExchangeConnect<
             DnsMultiplexerConnect<
                 TcpClientConnect<<R as RuntimeProvider>::Tcp>,
                 TcpClientStream<<R as RuntimeProvider>::Tcp>,
-                NoopMessageFinalizer,
+                dyn MessageFinalizer,
             >,
-            DnsMultiplexer<TcpClientStream<<R as RuntimeProvider>::Tcp>, NoopMessageFinalizer>,
+            DnsMultiplexer<TcpClientStream<<R as RuntimeProvider>::Tcp>, dyn MessageFinalizer>,
             R::Timer,
         >,
     ),
@@ -238,11 +292,11 @@ pub(crate) enum ConnectionConnect<R: RuntimeProvider> {
                     >,
                 >,
                 TcpClientStream<AsyncIoTokioAsStd<TokioTlsStream<TokioTcpStream>>>,
-                NoopMessageFinalizer,
+                dyn MessageFinalizer,
             >,
             DnsMultiplexer<
                 TcpClientStream<AsyncIoTokioAsStd<TokioTlsStream<TokioTcpStream>>>,
-                NoopMessageFinalizer,
+                dyn MessageFinalizer,
             >,
             TokioTime,
         >,
@@ -252,8 +306,8 @@ pub(crate) enum ConnectionConnect<R: RuntimeProvider> {
     #[cfg(feature = "mdns")]
     Mdns(
         DnsExchangeConnect<
-            DnsMultiplexerConnect<MdnsClientConnect, MdnsClientStream, NoopMessageFinalizer>,
-            DnsMultiplexer<MdnsClientStream, NoopMessageFinalizer>,
+            DnsMultiplexerConnect<MdnsClientConnect, MdnsClientStream, dyn MessageFinalizer>,
+            DnsMultiplexer<MdnsClientStream, dyn MessageFinalizer>,
             TokioTime,
         >,
     ),
@@ -271,6 +325,10 @@ impl<R: RuntimeProvider> Future for ConnectionFuture<R> {
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
         Poll::Ready(Ok(match &mut self.connect {
+            ConnectionConnect::Error(ref mut error) => {
+                let error = error.take();
+                return Poll::Ready(Err(error.unwrap_or_else(|| "polled after completion".into())));
+            }
             ConnectionConnect::Udp(ref mut conn) => {
                 let (conn, bg) = ready!(conn.poll_unpin(cx))?;
                 self.spawner.spawn_bg(bg);
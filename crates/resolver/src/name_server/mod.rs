@@ -11,14 +11,19 @@ mod name_server;
 mod name_server_pool;
 mod name_server_state;
 mod name_server_stats;
+mod qname_minimization;
+mod resolver_observer;
+mod socks5;
 
 pub use self::connection_provider::{ConnectionProvider, RuntimeProvider, Spawn};
 pub use self::connection_provider::{GenericConnection, GenericConnectionProvider};
 #[cfg(feature = "mdns")]
 pub(crate) use self::name_server::mdns_nameserver;
-pub use self::name_server::NameServer;
+pub use self::name_server::{NameServer, NameServerStatsSnapshot, NameServerStatus, ResponseMeta};
 pub use self::name_server_pool::NameServerPool;
-use self::name_server_state::NameServerState;
+pub use self::name_server_state::{ConnectionState, StateChange};
+pub use self::resolver_observer::ResolverObserver;
+use self::name_server_state::{NameServerState, Probe};
 use self::name_server_stats::NameServerStats;
 
 #[cfg(feature = "tokio-runtime")]
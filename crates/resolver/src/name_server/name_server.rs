@@ -7,24 +7,34 @@
 
 use std::cmp::Ordering;
 use std::fmt::{self, Debug, Formatter};
+use std::net::SocketAddr;
 use std::pin::Pin;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
+use futures_channel::mpsc::UnboundedSender;
 use futures_util::{future::Future, lock::Mutex};
 
 #[cfg(feature = "mdns")]
 use proto::multicast::MDNS_IPV4;
+use proto::op::{Edns, ResponseCode};
+use proto::rr::rdata::opt::{ClientSubnet, Cookie, EdnsCode, EdnsOption};
+use proto::rr::{Name, RecordType};
 use proto::xfer::{DnsHandle, DnsRequest, DnsResponse};
+use proto::Time;
 
-#[cfg(feature = "mdns")]
-use crate::config::Protocol;
-use crate::config::{NameServerConfig, ResolverOpts};
+use crate::clock::{Clock, SystemClock};
+use crate::config::{NameServerConfig, Protocol, ResolverOpts};
 use crate::error::ResolveError;
-use crate::name_server::{ConnectionProvider, NameServerState, NameServerStats};
+use crate::name_server::{
+    ConnectionProvider, ConnectionState, NameServerState, NameServerStats, Probe, StateChange,
+};
 #[cfg(feature = "tokio-runtime")]
 use crate::name_server::{TokioConnection, TokioConnectionProvider, TokioHandle};
 
+/// Requests are padded to a multiple of this many octets, as recommended by RFC 8467
+const QUERY_BLOCK_SIZE: u16 = 128;
+
 /// Specifies the details of a remote NameServer used for lookups
 #[derive(Clone)]
 pub struct NameServer<
@@ -33,9 +43,16 @@ pub struct NameServer<
 > {
     config: NameServerConfig,
     options: ResolverOpts,
-    client: Arc<Mutex<Option<C>>>,
+    /// the open connections, only stream name servers will have more than one
+    client: Arc<Mutex<Vec<PooledConnection<C>>>>,
+    /// the open TCP connections of a dual name server, see `Protocol::Dual`
+    stream_client: Arc<Mutex<Vec<PooledConnection<C>>>>,
     state: Arc<NameServerState>,
     stats: Arc<NameServerStats>,
+    /// the client half of the DNS Cookie sent to this name server, see `ResolverOpts::cookies`
+    client_cookie: [u8; 8],
+    /// the time of failures, and of the backoff from them, is read from this
+    clock: Arc<dyn Clock>,
     conn_provider: P,
 }
 
@@ -49,23 +66,46 @@ impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> Debug
 
 #[cfg(feature = "tokio-runtime")]
 impl NameServer<TokioConnection, TokioConnectionProvider> {
-    pub fn new(config: NameServerConfig, options: ResolverOpts, runtime: TokioHandle) -> Self {
+    /// Returns an error if the config is invalid, see [`Self::new_with_provider`]
+    pub fn new(
+        config: NameServerConfig,
+        options: ResolverOpts,
+        runtime: TokioHandle,
+    ) -> Result<Self, ResolveError> {
         Self::new_with_provider(config, options, TokioConnectionProvider::new(runtime))
     }
 }
 
 impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> NameServer<C, P> {
+    /// Returns an error if the config is invalid, e.g. an encrypted protocol without a
+    ///  `tls_dns_name` or a `bind_addr` which is not local, rather than failing each request sent
+    ///  to the NameServer
     pub fn new_with_provider(
         config: NameServerConfig,
         options: ResolverOpts,
         conn_provider: P,
+    ) -> Result<NameServer<C, P>, ResolveError> {
+        validate(&config)?;
+        Ok(Self::with_provider(config, options, conn_provider))
+    }
+
+    /// Constructs a NameServer of a config which is known to be valid
+    fn with_provider(
+        config: NameServerConfig,
+        options: ResolverOpts,
+        conn_provider: P,
     ) -> NameServer<C, P> {
+        let send_edns = send_edns(&config, &options);
+        let state = Arc::new(NameServerState::init(config.socket_addr, send_edns));
         Self {
             config,
             options,
-            client: Arc::new(Mutex::new(None)),
-            state: Arc::new(NameServerState::init(None)),
+            client: Arc::new(Mutex::new(Vec::new())),
+            stream_client: Arc::new(Mutex::new(Vec::new())),
+            state,
             stats: Arc::new(NameServerStats::default()),
+            client_cookie: rand::random(),
+            clock: Arc::new(SystemClock),
             conn_provider,
         }
     }
@@ -77,65 +117,389 @@ impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> NameSe
         client: C,
         conn_provider: P,
     ) -> NameServer<C, P> {
+        let send_edns = send_edns(&config, &options);
+        let state = Arc::new(NameServerState::init(config.socket_addr, send_edns));
+        let clock = Arc::new(SystemClock);
         Self {
             config,
             options,
-            client: Arc::new(Mutex::new(Some(client))),
-            state: Arc::new(NameServerState::init(None)),
+            client: Arc::new(Mutex::new(vec![PooledConnection::new(client, clock.now())])),
+            stream_client: Arc::new(Mutex::new(Vec::new())),
+            state,
             stats: Arc::new(NameServerStats::default()),
+            client_cookie: rand::random(),
+            clock,
             conn_provider,
         }
     }
 
+    /// The address of the remote name server
+    pub(crate) fn socket_addr(&self) -> SocketAddr {
+        self.config.socket_addr
+    }
+
+    /// The protocol used to communicate with the remote name server
+    pub(crate) fn protocol(&self) -> Protocol {
+        self.config.protocol
+    }
+
+    /// Limits the timeout of requests sent with this handle, e.g. to the time left before a
+    ///  deadline, the configured timeout is kept if it is shorter
+    pub(crate) fn limit_timeout(&mut self, timeout: Duration) {
+        self.options.timeout = self.options.timeout.min(timeout);
+    }
+
+    /// The weight of this NameServer in the pool, see [`NameServerConfig::weight`]
+    pub(crate) fn weight(&self) -> u8 {
+        self.config.weight.unwrap_or(1)
+    }
+
+    /// The components of the ranking of this NameServer, for logging why it was selected
+    pub(crate) fn rank(&self) -> Rank {
+        Rank {
+            socket_addr: self.config.socket_addr,
+            protocol: self.config.protocol,
+            state: self.state.connection_state(),
+            failures: self.stats.failures(),
+            consecutive_failures: self.stats.consecutive_failures(),
+            latency: self.stats.latency(),
+            successes: self.stats.successes(),
+            weight: self.weight(),
+        }
+    }
+
+    /// Compares the state and stats of the NameServers, Equal if neither is preferred
+    pub(crate) fn cmp_rank(&self, other: &Self) -> Ordering {
+        // run our evaluation to determine the next to be returned from the Heap
+        //   this will prefer established connections, we should try other connections after
+        //   some number to make sure that all are used. This is more important for when
+        //   latency is started to be used.
+        match self.state.cmp(&other.state) {
+            Ordering::Equal => (),
+            o => {
+                return o;
+            }
+        }
+
+        self.stats.cmp(&other.stats)
+    }
+
+    /// True if the last attempt to use this NameServer failed
+    pub(crate) fn is_failed(&self) -> bool {
+        self.state.is_failed()
+    }
+
+    /// True if this NameServer has failed at least `failure_threshold` times in a row, it is out of
+    ///  rotation until a probe succeeds, see `try_probe`
+    pub(crate) fn is_tripped(&self, failure_threshold: usize) -> bool {
+        failure_threshold != 0 && self.stats.consecutive_failures() >= failure_threshold
+    }
+
+    /// Claims this NameServer for a request probing whether it has recovered, once it has cooled
+    ///  down from its last failure, None if it has not or another request is probing it
+    ///
+    /// The cooldown is extended to the backoff from reconnecting, should that be longer. The claim
+    ///  is released once the returned `Probe` is dropped.
+    pub(crate) fn try_probe(&self, cooldown: Duration) -> Option<Probe> {
+        let failed_at = self.state.failed_at()?;
+        if self.elapsed_since(failed_at) < cooldown.max(self.stats.retry_delay()) {
+            return None;
+        }
+
+        self.state.try_probe()
+    }
+
+    /// Sets the sender of the transitions of the state of the connection, see
+    ///  [`NameServerPool::state_changes`]
+    ///
+    /// [`NameServerPool::state_changes`]: struct.NameServerPool.html#method.state_changes
+    pub(crate) fn set_state_changes(&self, sender: Option<UnboundedSender<StateChange>>) {
+        self.state.set_state_changes(sender);
+    }
+
+    /// The time since `earlier`, as read from the clock
+    fn elapsed_since(&self, earlier: Instant) -> Duration {
+        self.clock.now().saturating_duration_since(earlier)
+    }
+
+    /// Replaces the clock the time of failures and of the use of connections is read from, e.g. to
+    ///  step through the backoff
+    #[cfg(test)]
+    pub(crate) fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Records a failure, as a failed request would
+    #[cfg(test)]
+    pub(crate) fn fail(&self, when: Instant) {
+        self.state.fail(when);
+        self.stats.next_failure();
+    }
+
     #[cfg(test)]
     pub(crate) fn is_connected(&self) -> bool {
         !self.state.is_failed()
             && if let Some(client) = self.client.try_lock() {
-                client.is_some()
+                !client.is_empty()
             } else {
                 // assuming that if someone has it locked it will be or is connected
                 true
             }
     }
 
-    /// This will return a mutable client to allows for sending messages.
+    /// The config to connect with, the TCP half of a dual name server connects with `Protocol::Tcp`
+    fn connection_config(&self, stream: bool) -> NameServerConfig {
+        let mut config = self.config.clone();
+        if stream && config.protocol == Protocol::Dual {
+            config.protocol = Protocol::Tcp;
+        }
+
+        config
+    }
+
+    /// True if the request is sent over TCP by a dual name server, i.e. zone transfers
+    fn use_stream(&self, request: &DnsRequest) -> bool {
+        self.config.protocol == Protocol::Dual
+            && request
+                .queries()
+                .iter()
+                .any(|q| q.query_type() == RecordType::AXFR || q.query_type() == RecordType::IXFR)
+    }
+
+    /// This will return a mutable client to allows for sending messages, along with a token to
+    ///  hold while the client is in use.
     ///
-    /// If the connection is in a failed state, then this will establish a new connection
-    async fn connected_mut_client(&mut self) -> Result<C, ResolveError> {
-        let mut client = self.client.lock().await;
+    /// If the connection is in a failed state, then this will establish a new connection. The TCP
+    ///  connections of a dual name server are used with `stream`.
+    async fn connected_mut_client(&mut self, stream: bool) -> Result<(C, Arc<()>), ResolveError> {
+        let stream = stream && self.config.protocol == Protocol::Dual;
+        let mut client = if stream {
+            self.stream_client.lock().await
+        } else {
+            self.client.lock().await
+        };
+
+        // back off from reconnecting to a name server which is continuing to fail
+        if let Some(failed_at) = self.state.failed_at() {
+            let retry_delay = self.stats.retry_delay();
+            if self.elapsed_since(failed_at) < retry_delay {
+                debug!("backing off reconnect for {:?}: {}", retry_delay, self.config);
+
+                // report what the name server last failed with, rather than that it was skipped
+                return Err(match self.state.last_error() {
+                    Some(error) => ResolveError::clone(&error),
+                    None => ResolveError::from(format!(
+                        "name server is backing off after failures: {}",
+                        self.config
+                    )),
+                });
+            }
+        }
 
         // if this is in a failure state
-        if self.state.is_failed() || client.is_none() {
+        if self.state.is_failed() || client.is_empty() {
             debug!("reconnecting: {:?}", self.config);
 
-            // TODO: we need the local EDNS options
-            self.state.reinit(None);
+            self.state.reinit(send_edns(&self.config, &self.options));
 
             let new_client = self
                 .conn_provider
-                .new_connection(&self.config, &self.options)
+                .new_connection(&self.connection_config(stream), &self.options)
                 .await?;
 
             // establish a new connection
-            *client = Some(new_client);
+            client.clear();
+            client.push(PooledConnection::new(new_client, self.clock.now()));
+        } else if stream || self.config.protocol.is_stream() {
+            // the first connection is kept open, others are closed once they go unused, unless
+            //  the name server advertised how long it keeps them open, see RFC 7828
+            let keepalive = self.state.keepalive();
+            let idle_timeout = keepalive.unwrap_or(self.options.stream_idle_timeout);
+            let mut first = keepalive.is_none();
+            let now = self.clock.now();
+            client.retain(|conn| {
+                let keep = first || conn.in_use() || conn.idle(now) < idle_timeout;
+                first = false;
+                keep
+            });
+
+            // open another connection when all of the existing ones are in use, or closed
+            let max_connections = self.options.connections_per_server.max(1);
+            if client.iter().all(PooledConnection::in_use) && client.len() < max_connections {
+                debug!("adding connection: {:?}", self.config);
+
+                let new_client = self
+                    .conn_provider
+                    .new_connection(&self.connection_config(stream), &self.options)
+                    .await?;
+
+                client.push(PooledConnection::new(new_client, self.clock.now()));
+            }
         } else {
             debug!("existing connection: {:?}", self.config);
         }
 
-        Ok((*client)
-            .clone()
-            .expect("bad state, client should be connected"))
+        // the least used connection takes the request
+        let conn = client
+            .iter_mut()
+            .min_by_key(|conn| conn.requests_in_flight())
+            .expect("bad state, client should be connected");
+
+        Ok(conn.take(self.clock.now()))
     }
 
     async fn inner_send<R: Into<DnsRequest> + Unpin + Send + 'static>(
         mut self,
         request: R,
     ) -> Result<DnsResponse, ResolveError> {
-        let mut client = self.connected_mut_client().await?;
-        let response = client.send(request).await;
+        let mut request: DnsRequest = request.into();
+        let stream = self.use_stream(&request);
+
+        // the connection is considered in use until the response is received
+        let (mut client, in_use) = self.connected_mut_client(stream).await?;
+
+        // advertise our EDNS options, unless the request already carries its own
+        if request.edns().is_none() {
+            if let Some(edns) = self.state.send_edns() {
+                request.set_edns(edns);
+            }
+        } else {
+            // add to the request's own EDNS, leaving its other options in place
+            let edns = request.edns_mut();
+            for option in client_subnet(&self.options)
+                .into_iter()
+                .chain(edns_options(&self.options))
+            {
+                if edns.option(EdnsCode::from(&option)).is_none() {
+                    edns.set_option(option);
+                }
+            }
+
+            if self.options.dnssec_ok {
+                edns.set_dnssec_ok(true);
+            }
+        }
+
+        // the version is limited to the highest the name server implements, once it is known
+        if let Some(version) = self.state.edns_version() {
+            if request.edns().map_or(false, |edns| edns.version() > version) {
+                request.edns_mut().set_version(version);
+            }
+        }
+
+        // the cookie echoes the server cookie, once the name server has responded with one
+        if self.options.cookies && request.edns().is_some() {
+            let edns = request.edns_mut();
+            if edns.option(EdnsCode::Cookie).is_none() {
+                let cookie = Cookie::new(self.client_cookie, self.state.server_cookie());
+                edns.set_option(EdnsOption::Cookie(cookie));
+            }
+        }
+
+        // stream connections ask how long the name server keeps them open, see RFC 7828
+        let mut over_stream = stream || self.config.protocol.is_stream();
+        if over_stream && self.options.edns_keepalive {
+            request_keepalive(&mut request);
+        }
+
+        // the response must echo the case of the names, see `ResolverOpts::case_randomization`
+        let names = if self.options.case_randomization {
+            Some(randomize_case(&mut request))
+        } else {
+            None
+        };
+
+        // padding is added last, once the rest of the request is complete
+        if pads(&self.config, &self.options) {
+            request.pad_to_block_size(QUERY_BLOCK_SIZE)?;
+        }
+
+        // a dual name server retries a truncated response over TCP
+        let retry = if self.config.protocol == Protocol::Dual && !stream {
+            Some(request.clone())
+        } else {
+            None
+        };
+
+        // a name server which does not implement the EDNS version may support a lower one, or one
+        //  which does not implement EDNS at all may accept the request without it
+        let downgrade = if request.edns().is_some() {
+            Some(request.clone())
+        } else {
+            None
+        };
+
+        let sent_at = self.clock.now();
+        let mut response =
+            Self::send_with_timeout(&mut client, request, self.options.timeout).await;
+        drop(in_use);
+
+        if let Some(mut request) = retry {
+            if response.as_ref().map_or(false, |response| response.truncated()) {
+                debug!("truncated response received, retrying over tcp: {}", self.config);
+                if self.options.edns_keepalive {
+                    request_keepalive(&mut request);
+                }
+                over_stream = true;
+
+                response = self.resend(true, request).await;
+            }
+        }
+
+        if let Some(mut request) = downgrade {
+            let sent_version = request.edns().map_or(0, Edns::version);
+            let version = response
+                .as_ref()
+                .ok()
+                .and_then(|response| downgraded_version(response, sent_version));
+
+            if let Some(version) = version {
+                debug!(
+                    "EDNS version {} is not supported, retrying with {}: {}",
+                    sent_version, version, self.config
+                );
+                self.state.downgrade_edns(version);
+                request.edns_mut().set_version(version);
+
+                response = self.resend(stream, request).await;
+            } else if let Some(response_code) = response
+                .as_ref()
+                .ok()
+                .and_then(|response| refused_edns(response, &self.options))
+            {
+                debug!(
+                    "EDNS is not supported, {} response, retrying without it: {}",
+                    response_code, self.config
+                );
+                request.take_edns();
+
+                response = self.resend(stream, request).await;
+            }
+        }
+
+        // an extended response code, e.g. BADVERS or BADCOOKIE, is a failure of the name server to
+        //  process the request, rather than an answer
+        let response = response.and_then(|response| {
+            if response.response_code().is_extended() {
+                ResolveError::from_response(response, false)
+            } else {
+                Ok(response)
+            }
+        });
 
         match response {
-            Ok(response) => {
+            Ok(mut response) => {
+                // a response which does not echo the case is discarded, without failing the
+                //  name server, as it may well be spoofed
+                if let Some(ref names) = names {
+                    if !restore_case(names, &mut response) {
+                        debug!("discarding response with mismatched case from: {}", self.config);
+                        return Err(ResolveError::from(
+                            "response does not match the case of the query",
+                        ));
+                    }
+                }
+
                 // first we'll evaluate if the message succeeded
                 //   see https://github.com/bluejekyll/trust-dns/issues/606
                 //   TODO: there are probably other return codes from the server we may want to
@@ -149,31 +513,361 @@ impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> NameSe
                 // TODO: consider making message::take_edns...
                 let remote_edns = response.edns().cloned();
 
+                // only a cookie in response to our own is kept, name servers may not support them
+                if let Some(EdnsOption::Cookie(cookie)) =
+                    remote_edns.as_ref().and_then(|edns| edns.option(EdnsCode::Cookie))
+                {
+                    if let Some(server) = cookie.server() {
+                        if *cookie.client() == self.client_cookie {
+                            self.state.set_server_cookie(server.to_vec());
+                        }
+                    }
+                }
+
+                // the timeout is ignored over UDP, where connections are not kept open
+                if let Some(EdnsOption::Keepalive(Some(timeout))) =
+                    remote_edns.as_ref().and_then(|edns| edns.option(EdnsCode::Keepalive))
+                {
+                    if over_stream {
+                        let keepalive = Duration::from_millis(u64::from(*timeout) * 100);
+                        self.state.set_keepalive(keepalive);
+                    }
+                }
+
                 // take the remote edns options and store them
                 self.state.establish(remote_edns);
 
-                // record the success
-                self.stats.next_success();
+                // record the success, along with the round-trip time
+                let rtt = self.elapsed_since(sent_at);
+                self.stats.next_success(rtt);
+                trace!(
+                    "name server success: addr={} rtt={:?} latency={:?} successes={}",
+                    self.config.socket_addr,
+                    rtt,
+                    self.stats.latency(),
+                    self.stats.successes()
+                );
                 Ok(response)
             }
             Err(error) => {
                 debug!("name_server connection failure: {}", error);
 
-                // this transitions the state to failure
-                self.state.fail(Instant::now());
+                // this transitions the state to failure, the error is kept for reporting
+                let error = Arc::new(error);
+                self.state.fail_with(self.clock.now(), Arc::clone(&error));
 
                 // record the failure
                 self.stats.next_failure();
+                debug!(
+                    "name server failure: addr={} consecutive_failures={} retry_delay={:?}",
+                    self.config.socket_addr,
+                    self.stats.consecutive_failures(),
+                    self.stats.retry_delay()
+                );
 
                 // These are connection failures, not lookup failures, that is handled in the resolver layer
-                Err(error)
+                Err(ResolveError::clone(&error))
             }
         }
     }
 
+    /// Sends the request again, e.g. over TCP after a truncated response, a failure to connect is
+    ///  returned as the response so that it is recorded as a failure of the name server
+    async fn resend(
+        &mut self,
+        stream: bool,
+        request: DnsRequest,
+    ) -> Result<DnsResponse, ResolveError> {
+        let (mut client, _in_use) = self.connected_mut_client(stream).await?;
+        Self::send_with_timeout(&mut client, request, self.options.timeout).await
+    }
+
+    /// Sends the request on the connection, bounding the time spent waiting on it, a hung server
+    ///  is treated as failed
+    async fn send_with_timeout(
+        client: &mut C,
+        request: DnsRequest,
+        timeout: Duration,
+    ) -> Result<DnsResponse, ResolveError> {
+        P::Time::timeout(timeout, client.send(request))
+            .await
+            .unwrap_or_else(|e| Err(ResolveError::from(e)))
+    }
+
     pub fn trust_nx_responses(&self) -> bool {
         self.config.trust_nx_responses
     }
+
+    /// Returns a snapshot of the current health of this NameServer, this does not block
+    pub fn status(&self) -> NameServerStatus {
+        NameServerStatus {
+            socket_addr: self.config.socket_addr,
+            protocol: self.config.protocol,
+            state: self.state.connection_state(),
+            successes: self.stats.successes(),
+            failures: self.stats.failures(),
+            last_failure: self.state.last_failure(),
+            edns_options: self.remote_edns_options(),
+        }
+    }
+
+    fn remote_edns_options(&self) -> Vec<(u16, Vec<u8>)> {
+        let remote_edns = match self.state.remote_edns() {
+            Some(remote_edns) => remote_edns,
+            None => return vec![],
+        };
+
+        let mut options: Vec<(u16, Vec<u8>)> = remote_edns
+            .options()
+            .options()
+            .iter()
+            .map(|(code, option)| (u16::from(*code), Vec::<u8>::from(option)))
+            .collect();
+        options.sort();
+        options
+    }
+
+    /// Returns the learned stats of this NameServer, these can be used to seed the same NameServer
+    ///  after a restart, see [`NameServerPool::from_config_with_stats`]
+    ///
+    /// [`NameServerPool::from_config_with_stats`]: struct.NameServerPool.html#method.from_config_with_stats
+    pub fn export_stats(&self) -> NameServerStatsSnapshot {
+        NameServerStatsSnapshot {
+            socket_addr: self.config.socket_addr,
+            protocol: self.config.protocol,
+            successes: self.stats.successes(),
+            failures: self.stats.failures(),
+            consecutive_failures: self.stats.consecutive_failures(),
+            latency: self.stats.latency(),
+            failed_ago: self.state.failed_at().map(|failed_at| self.elapsed_since(failed_at)),
+        }
+    }
+
+    /// Seeds the stats from the snapshot with the same address and protocol, if there is one
+    ///
+    /// A failure is rebased to the same age relative to now, so any back off carries on.
+    pub(crate) fn import_stats(&self, snapshots: &[NameServerStatsSnapshot]) {
+        let snapshot = match snapshots.iter().find(|snapshot| {
+            snapshot.socket_addr == self.config.socket_addr
+                && snapshot.protocol == self.config.protocol
+        }) {
+            Some(snapshot) => snapshot,
+            None => return,
+        };
+
+        self.stats.restore(
+            snapshot.successes,
+            snapshot.failures,
+            snapshot.consecutive_failures,
+            snapshot.latency,
+        );
+
+        if let Some(failed_ago) = snapshot.failed_ago {
+            let now = self.clock.now();
+            self.state.fail(now.checked_sub(failed_ago).unwrap_or(now));
+        }
+    }
+}
+
+/// A connection to a NameServer, tracking the requests in flight on it
+struct PooledConnection<C> {
+    conn: C,
+    /// a clone is held by each request in flight
+    in_use: Arc<()>,
+    last_used: Instant,
+}
+
+impl<C: Clone> PooledConnection<C> {
+    fn new(conn: C, now: Instant) -> Self {
+        PooledConnection {
+            conn,
+            in_use: Arc::new(()),
+            last_used: now,
+        }
+    }
+
+    /// Returns the connection for a request, the token should be held until the request completes
+    fn take(&mut self, now: Instant) -> (C, Arc<()>) {
+        self.last_used = now;
+        (self.conn.clone(), Arc::clone(&self.in_use))
+    }
+
+    fn requests_in_flight(&self) -> usize {
+        Arc::strong_count(&self.in_use) - 1
+    }
+
+    fn in_use(&self) -> bool {
+        self.requests_in_flight() > 0
+    }
+
+    /// The time since the connection was last used
+    fn idle(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.last_used)
+    }
+}
+
+/// A point in time snapshot of the health of a NameServer, see [`NameServerPool::stats`]
+///
+/// [`NameServerPool::stats`]: struct.NameServerPool.html#method.stats
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NameServerStatus {
+    socket_addr: SocketAddr,
+    protocol: Protocol,
+    state: ConnectionState,
+    successes: usize,
+    failures: usize,
+    last_failure: Option<Instant>,
+    edns_options: Vec<(u16, Vec<u8>)>,
+}
+
+impl NameServerStatus {
+    /// The address of the remote NameServer
+    pub fn socket_addr(&self) -> SocketAddr {
+        self.socket_addr
+    }
+
+    /// The protocol used to communicate with the NameServer
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// The state of the connection to the NameServer
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    /// The total number of successful responses from the NameServer
+    pub fn successes(&self) -> usize {
+        self.successes
+    }
+
+    /// The total number of failed requests to the NameServer
+    pub fn failures(&self) -> usize {
+        self.failures
+    }
+
+    /// The time of the most recent failure, if any
+    pub fn last_failure(&self) -> Option<Instant> {
+        self.last_failure
+    }
+
+    /// The EDNS options, by option code and payload, of the responses from the NameServer
+    ///
+    /// These are the options of the most recent response with EDNS, ordered by code.
+    pub fn edns_options(&self) -> &[(u16, Vec<u8>)] {
+        &self.edns_options
+    }
+}
+
+/// The components of the ranking of a NameServer, as they are logged with the selection of
+///  NameServers by the pool
+///
+/// These are compared in order: the state, failures, latency and then fewest successes, see
+///  `ServerOrderingStrategy::QueryStats`.
+pub(crate) struct Rank {
+    socket_addr: SocketAddr,
+    protocol: Protocol,
+    state: ConnectionState,
+    failures: usize,
+    consecutive_failures: usize,
+    latency: Duration,
+    successes: usize,
+    weight: u8,
+}
+
+impl fmt::Display for Rank {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "addr={} protocol={} state={:?} failures={} consecutive_failures={} latency={:?} \
+             successes={} weight={}",
+            self.socket_addr,
+            self.protocol,
+            self.state,
+            self.failures,
+            self.consecutive_failures,
+            self.latency,
+            self.successes,
+            self.weight
+        )
+    }
+}
+
+/// The NameServer which gave a response, see [`NameServerPool::send_with_meta`]
+///
+/// [`NameServerPool::send_with_meta`]: struct.NameServerPool.html#method.send_with_meta
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResponseMeta {
+    socket_addr: SocketAddr,
+    protocol: Protocol,
+    latency: Duration,
+}
+
+impl ResponseMeta {
+    pub(crate) fn new(socket_addr: SocketAddr, protocol: Protocol, latency: Duration) -> Self {
+        ResponseMeta {
+            socket_addr,
+            protocol,
+            latency,
+        }
+    }
+
+    /// The address of the remote NameServer
+    pub fn socket_addr(&self) -> SocketAddr {
+        self.socket_addr
+    }
+
+    /// The protocol used to communicate with the NameServer
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// The time between sending the request to the NameServer and receiving its response
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+}
+
+/// The learned stats of a NameServer, which can be persisted across restarts of the resolver, see
+///  [`NameServer::export_stats`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+pub struct NameServerStatsSnapshot {
+    socket_addr: SocketAddr,
+    protocol: Protocol,
+    successes: usize,
+    failures: usize,
+    consecutive_failures: usize,
+    latency: Duration,
+    /// how long before the snapshot was taken the NameServer failed, if it was in the Failed state
+    failed_ago: Option<Duration>,
+}
+
+impl NameServerStatsSnapshot {
+    /// The address of the remote NameServer
+    pub fn socket_addr(&self) -> SocketAddr {
+        self.socket_addr
+    }
+
+    /// The protocol used to communicate with the NameServer
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// The total number of successful responses from the NameServer
+    pub fn successes(&self) -> usize {
+        self.successes
+    }
+
+    /// The total number of failed requests to the NameServer
+    pub fn failures(&self) -> usize {
+        self.failures
+    }
+
+    /// The moving average of the round-trip time of successful responses
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
 }
 
 impl<C, P> DnsHandle for NameServer<C, P>
@@ -198,24 +892,16 @@ where
 
 impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> Ord for NameServer<C, P> {
     /// Custom implementation of Ord for NameServer which incorporates the performance of the connection into it's ranking
+    ///
+    /// The state and stats are all atomics, this never takes a lock, so it will not block or panic
+    ///  while a connection is being established or used.
     fn cmp(&self, other: &Self) -> Ordering {
         // if they are literally equal, just return
         if self == other {
             return Ordering::Equal;
         }
 
-        // otherwise, run our evaluation to determine the next to be returned from the Heap
-        //   this will prefer established connections, we should try other connections after
-        //   some number to make sure that all are used. This is more important for when
-        //   latency is started to be used.
-        match self.state.cmp(&other.state) {
-            Ordering::Equal => (),
-            o => {
-                return o;
-            }
-        }
-
-        self.stats.cmp(&other.stats)
+        self.cmp_rank(other)
     }
 }
 
@@ -238,55 +924,336 @@ impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> Partia
 
 impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> Eq for NameServer<C, P> {}
 
-// TODO: once IPv6 is better understood, also make this a binary keep.
-#[cfg(feature = "mdns")]
-pub(crate) fn mdns_nameserver<C, P>(
-    options: ResolverOpts,
-    conn_provider: P,
-    trust_nx_responses: bool,
-) -> NameServer<C, P>
-where
-    C: DnsHandle<Error = ResolveError>,
-    P: ConnectionProvider<Conn = C>,
-{
-    let config = NameServerConfig {
-        socket_addr: *MDNS_IPV4,
-        protocol: Protocol::Mdns,
-        tls_dns_name: None,
-        trust_nx_responses,
-        #[cfg(feature = "dns-over-rustls")]
-        tls_config: None,
-    };
-    NameServer::new_with_provider(config, options, conn_provider)
+/// Randomizes the case of the names of the queries, returning each name along with the name sent
+fn randomize_case(request: &mut DnsRequest) -> Vec<(Name, Name)> {
+    request
+        .queries_mut()
+        .iter_mut()
+        .map(|query| {
+            let name = query.name().clone();
+            let sent = name.to_random_case();
+            query.set_name(sent.clone());
+            (name, sent)
+        })
+        .collect()
 }
 
-#[cfg(test)]
-#[cfg(feature = "tokio-runtime")]
-mod tests {
-    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
-    use std::time::Duration;
+/// Returns false if the response does not echo the names exactly as sent, otherwise the names of
+///  the queries, and the answers for them, are restored to those of the request
+fn restore_case(names: &[(Name, Name)], response: &mut DnsResponse) -> bool {
+    let echoed = response.queries().len() == names.len()
+        && response
+            .queries()
+            .iter()
+            .zip(names)
+            .all(|(query, (_, sent))| query.name().eq_case(sent));
+    if !echoed {
+        return false;
+    }
 
-    use futures_util::{future, FutureExt};
-    use tokio::runtime::Runtime;
+    for (query, (name, _)) in response.queries_mut().iter_mut().zip(names) {
+        query.set_name(name.clone());
+    }
 
-    use proto::op::{Query, ResponseCode};
-    use proto::rr::{Name, RecordType};
-    use proto::xfer::{DnsHandle, DnsRequestOptions};
+    for record in response.answers_mut() {
+        if let Some((name, _)) = names.iter().find(|(_, sent)| record.name().eq_case(sent)) {
+            record.set_name(name.clone());
+        }
+    }
 
-    use super::*;
-    use crate::config::Protocol;
+    true
+}
 
-    #[test]
-    fn test_name_server() {
-        //env_logger::try_init().ok();
+/// The EDNS version to retry with, if the response is BADVERS for the version which was sent
+///
+/// [RFC 6891](https://tools.ietf.org/html/rfc6891#section-6.1.3), EDNS(0), April 2013
+///
+/// ```text
+/// If a responder does not implement the VERSION level of the request,
+/// then it MUST respond with RCODE=BADVERS.  All responses MUST be
+/// limited in format to the VERSION level of the request, but the
+/// VERSION of each response SHOULD be the highest implementation level
+/// of the responder.
+/// ```
+fn downgraded_version(response: &DnsResponse, sent_version: u8) -> Option<u8> {
+    if response.response_code() != ResponseCode::BADVERS {
+        return None;
+    }
 
-        let config = NameServerConfig {
-            socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), 53),
-            protocol: Protocol::Udp,
-            tls_dns_name: None,
-            trust_nx_responses: false,
+    response
+        .edns()
+        .map(Edns::version)
+        .filter(|version| *version < sent_version)
+}
+
+/// The response code, if the response refuses the EDNS of the request, which may then be retried
+///  without it
+///
+/// NOTIMP is always considered, FORMERR if `ResolverOpts::edns_formerr_fallback` is set, but
+///  only without an OPT record in the response,
+///  [RFC 6891](https://tools.ietf.org/html/rfc6891#section-7), EDNS(0), April 2013
+///
+/// ```text
+/// Responders that choose not to implement the protocol extensions
+/// defined in this document MUST respond with a return code (RCODE) of
+/// FORMERR to messages containing an OPT record in the additional
+/// section and MUST NOT include an OPT record in the response.
+///
+/// If there is a problem with processing the OPT record itself, such as
+/// an option value that is badly formatted or that includes out-of-range
+/// values, a FORMERR MUST be returned.  If this occurs, the response
+/// MUST include an OPT record.  This is intended to allow the requestor
+/// to distinguish between servers that do not implement EDNS and format
+/// errors within EDNS.
+/// ```
+fn refused_edns(response: &DnsResponse, options: &ResolverOpts) -> Option<ResponseCode> {
+    if response.edns().is_some() {
+        return None;
+    }
+
+    match response.response_code() {
+        ResponseCode::NotImp => Some(ResponseCode::NotImp),
+        ResponseCode::FormErr if options.edns_formerr_fallback => Some(ResponseCode::FormErr),
+        _ => None,
+    }
+}
+
+/// Checks that the NameServer of the config can be connected to
+///
+/// Encrypted connections verify the certificate of the name server against the `tls_dns_name`,
+///  which must be given. The `bind_addr` must be an address of this host.
+fn validate(config: &NameServerConfig) -> Result<(), ResolveError> {
+    let has_dns_name = config
+        .tls_dns_name
+        .as_ref()
+        .map_or(false, |name| !name.is_empty());
+    if config.protocol.is_encrypted() && !has_dns_name {
+        return Err(ResolveError::from(format!(
+            "tls_dns_name is required for encrypted connections: {}",
+            config
+        )));
+    }
+
+    // the port is not checked, a random one is chosen for each UDP query
+    if let Some(bind_addr) = config.bind_addr {
+        let unbound = SocketAddr::new(bind_addr.ip(), 0);
+        if let Err(e) = std::net::UdpSocket::bind(unbound) {
+            return Err(ResolveError::from(format!(
+                "can not bind to {} for {}: {}",
+                bind_addr.ip(),
+                config,
+                e
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// The EDNS to advertise to the remote, None if EDNS is disabled in the options
+fn send_edns(config: &NameServerConfig, options: &ResolverOpts) -> Option<Edns> {
+    // the client subnet, cookies and other options can only be sent with EDNS
+    if !options.edns0
+        && options.client_subnet.is_none()
+        && !options.cookies
+        && options.edns_options.is_empty()
+        && !options.dnssec_ok
+        && !pads(config, options)
+        && !keeps_alive(config, options)
+    {
+        return None;
+    }
+
+    let mut edns = Edns::new();
+    edns.set_max_payload(options.edns_max_payload);
+    edns.set_version(0);
+    edns.set_dnssec_ok(options.validate || options.dnssec_ok);
+    if let Some(subnet) = client_subnet(options) {
+        edns.set_option(subnet);
+    }
+    for option in edns_options(options) {
+        edns.set_option(option);
+    }
+    Some(edns)
+}
+
+/// Whether requests to the name server are padded, see `ResolverOpts::enable_padding`
+fn pads(config: &NameServerConfig, options: &ResolverOpts) -> bool {
+    options.enable_padding && config.protocol.is_encrypted()
+}
+
+/// Whether stream requests to the name server ask to keep the connection open, see
+///  `ResolverOpts::edns_keepalive`
+fn keeps_alive(config: &NameServerConfig, options: &ResolverOpts) -> bool {
+    options.edns_keepalive && (config.protocol.is_stream() || config.protocol == Protocol::Dual)
+}
+
+/// Adds the EDNS TCP Keepalive option to the request, which must not be sent over UDP
+fn request_keepalive(request: &mut DnsRequest) {
+    let absent = request
+        .edns()
+        .map_or(false, |edns| edns.option(EdnsCode::Keepalive).is_none());
+    if absent {
+        request.edns_mut().set_option(EdnsOption::Keepalive(None));
+    }
+}
+
+/// The additional EDNS options to send with requests, see `ResolverOpts::edns_options`
+fn edns_options(options: &ResolverOpts) -> impl Iterator<Item = EdnsOption> + '_ {
+    options
+        .edns_options
+        .iter()
+        .map(|(code, data)| EdnsOption::from((EdnsCode::from(*code), data.as_slice())))
+}
+
+/// The EDNS Client Subnet option to send with requests, if one is configured
+fn client_subnet(options: &ResolverOpts) -> Option<EdnsOption> {
+    options.client_subnet.map(|(address, source_prefix)| {
+        EdnsOption::Subnet(ClientSubnet::new(address, source_prefix, 0))
+    })
+}
+
+// TODO: once IPv6 is better understood, also make this a binary keep.
+#[cfg(feature = "mdns")]
+pub(crate) fn mdns_nameserver<C, P>(
+    options: ResolverOpts,
+    conn_provider: P,
+    trust_nx_responses: bool,
+) -> NameServer<C, P>
+where
+    C: DnsHandle<Error = ResolveError>,
+    P: ConnectionProvider<Conn = C>,
+{
+    let config = NameServerConfig {
+        socket_addr: *MDNS_IPV4,
+        protocol: Protocol::Mdns,
+        tls_dns_name: None,
+        trust_nx_responses,
+        signer: None,
+        weight: None,
+        bind_addr: None,
+        #[cfg(feature = "dns-over-rustls")]
+        tls_config: None,
+    };
+    NameServer::with_provider(config, options, conn_provider)
+}
+
+#[cfg(test)]
+#[cfg(feature = "tokio-runtime")]
+pub(crate) mod tests {
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use std::sync::atomic::{self, AtomicUsize};
+    use std::time::Duration;
+
+    use futures_util::{future, FutureExt};
+    use tokio::runtime::Runtime;
+
+    use proto::error::ProtoResult;
+    use proto::op::{Message, MessageFinalizer, MessageType, OpCode, Query, ResponseCode};
+    use proto::rr::rdata::TXT;
+    use proto::rr::{Name, RData, Record, RecordType};
+    use proto::xfer::{DnsHandle, DnsRequestOptions};
+    use proto::TokioTime;
+
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::config::{MessageSigner, Protocol};
+    use crate::error::ResolveErrorKind;
+    use crate::name_server::name_server_stats::MIN_RETRY_DELAY;
+
+    /// The config of a UDP name server on the loopback address
+    pub(crate) fn config(port: u16) -> NameServerConfig {
+        NameServerConfig {
+            socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), port),
+            protocol: Protocol::Udp,
+            tls_dns_name: None,
+            trust_nx_responses: false,
+            signer: None,
+            weight: None,
+            bind_addr: None,
             #[cfg(feature = "dns-over-rustls")]
             tls_config: None,
+        }
+    }
+
+    /// The response a `RecordingHandle` gives to a request
+    type Respond = dyn Fn(&DnsRequest) -> Result<Message, ResolveError> + Send + Sync;
+
+    /// Records all requests sent, responding to each with a copy of `response`
+    #[derive(Clone)]
+    pub(crate) struct RecordingHandle {
+        pub(crate) requests: Arc<std::sync::Mutex<Vec<DnsRequest>>>,
+        respond: Arc<Respond>,
+    }
+
+    impl RecordingHandle {
+        pub(crate) fn new(response: Message) -> Self {
+            Self::answering(move |_| Ok(response.clone()))
+        }
+
+        /// Records all requests sent, failing each with a copy of `error`
+        pub(crate) fn failing(error: ResolveError) -> Self {
+            Self::answering(move |_| Err(error.clone()))
+        }
+
+        /// Records all requests sent, responding to each as `respond` does
+        pub(crate) fn answering<F>(respond: F) -> Self
+        where
+            F: Fn(&DnsRequest) -> Result<Message, ResolveError> + Send + Sync + 'static,
+        {
+            RecordingHandle {
+                requests: Arc::new(std::sync::Mutex::new(Vec::new())),
+                respond: Arc::new(respond),
+            }
+        }
+    }
+
+    /// A response with the queries of the request, as sent, and an A record for each
+    pub(crate) fn echo(request: &DnsRequest) -> Message {
+        let mut response = Message::new();
+        response.add_queries(request.queries().to_vec());
+        for query in request.queries() {
+            let rdata = RData::A(Ipv4Addr::new(127, 0, 0, 1));
+            response.add_answer(Record::from_rdata(query.name().clone(), 300, rdata));
+        }
+        response
+    }
+
+    impl DnsHandle for RecordingHandle {
+        type Response = future::Ready<Result<DnsResponse, ResolveError>>;
+        type Error = ResolveError;
+
+        fn send<R: Into<DnsRequest> + Unpin + Send + 'static>(
+            &mut self,
+            request: R,
+        ) -> Self::Response {
+            let request = request.into();
+            let response = (self.respond)(&request);
+            self.requests.lock().unwrap().push(request);
+            future::ready(response.map(DnsResponse::from))
+        }
+    }
+
+    #[derive(Clone)]
+    pub(crate) struct RecordingProvider(pub(crate) RecordingHandle);
+
+    impl ConnectionProvider for RecordingProvider {
+        type Conn = RecordingHandle;
+        type FutureConn = future::Ready<Result<RecordingHandle, ResolveError>>;
+        type Time = TokioTime;
+
+        fn new_connection(&self, _: &NameServerConfig, _: &ResolverOpts) -> Self::FutureConn {
+            future::ok(self.0.clone())
+        }
+    }
+
+    #[test]
+    fn test_name_server() {
+        //env_logger::try_init().ok();
+
+        let config = NameServerConfig {
+            socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), 53),
+            ..config(53)
         };
         let io_loop = Runtime::new().unwrap();
         let runtime_handle = TokioHandle;
@@ -296,6 +1263,7 @@ mod tests {
                 ResolverOpts::default(),
                 runtime_handle,
             )
+            .unwrap()
         });
 
         let name = Name::parse("www.example.com.", None).unwrap();
@@ -316,16 +1284,12 @@ mod tests {
         options.timeout = Duration::from_millis(1); // this is going to fail, make it fail fast...
         let config = NameServerConfig {
             socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 252)), 252),
-            protocol: Protocol::Udp,
-            tls_dns_name: None,
-            trust_nx_responses: false,
-            #[cfg(feature = "dns-over-rustls")]
-            tls_config: None,
+            ..config(53)
         };
         let io_loop = Runtime::new().unwrap();
         let runtime_handle = TokioHandle;
         let name_server = future::lazy(|_| {
-            NameServer::<_, TokioConnectionProvider>::new(config, options, runtime_handle)
+            NameServer::<_, TokioConnectionProvider>::new(config, options, runtime_handle).unwrap()
         });
 
         let name = Name::parse("www.example.com.", None).unwrap();
@@ -336,4 +1300,1083 @@ mod tests {
             )))
             .is_err());
     }
+
+    #[test]
+    fn test_bind_addr() {
+        // a name server which answers a single request, and reports where it came from
+        let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_addr = server.local_addr().unwrap();
+        let server_thread = std::thread::spawn(move || {
+            let mut buf = [0_u8; 512];
+            let (len, src) = server.recv_from(&mut buf).unwrap();
+            let request = Message::from_vec(&buf[..len]).unwrap();
+
+            let mut response = Message::new();
+            response
+                .set_id(request.id())
+                .set_message_type(MessageType::Response)
+                .add_queries(request.queries().to_vec());
+            server.send_to(&response.to_vec().unwrap(), src).unwrap();
+            src
+        });
+
+        // the port of the address is in use, only its IP is bound to
+        let taken = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let bind_addr = taken.local_addr().unwrap();
+        let config = NameServerConfig {
+            socket_addr: server_addr,
+            bind_addr: Some(bind_addr),
+            ..config(53)
+        };
+        let io_loop = Runtime::new().unwrap();
+        let name_server = future::lazy(|_| {
+            NameServer::<_, TokioConnectionProvider>::new(
+                config,
+                ResolverOpts::default(),
+                TokioHandle,
+            )
+            .unwrap()
+        });
+
+        let name = Name::parse("www.example.com.", None).unwrap();
+        io_loop
+            .block_on(name_server.then(|mut name_server| {
+                name_server.lookup(
+                    Query::query(name.clone(), RecordType::A),
+                    DnsRequestOptions::default(),
+                )
+            }))
+            .expect("query failed");
+        let src = server_thread.join().unwrap();
+        assert_eq!(src.ip(), bind_addr.ip());
+        assert_ne!(src.port(), bind_addr.port());
+    }
+
+    #[test]
+    fn test_bind_addr_not_available() {
+        let config = NameServerConfig {
+            // TEST-NET-1, not an address of this host
+            bind_addr: Some(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)), 0)),
+            ..config(53)
+        };
+        let error = NameServer::new(config, ResolverOpts::default(), TokioHandle)
+            .expect_err("bound to an address which is not local");
+        assert!(error.to_string().contains("192.0.2.1"));
+    }
+
+    #[test]
+    fn test_cmp_with_locked_connections() {
+        let conn_provider = TokioConnectionProvider::new(TokioHandle);
+        let name_server = |port| {
+            NameServer::new_with_provider(
+                config(port),
+                ResolverOpts::default(),
+                conn_provider.clone(),
+            )
+            .unwrap()
+        };
+
+        // ranked by state, then by failures
+        let failed = name_server(53);
+        failed.fail(Instant::now());
+        let flaky = name_server(54);
+        flaky.stats.next_success(Duration::from_millis(10));
+        flaky.stats.next_failure();
+        let healthy = name_server(55);
+        healthy.stats.next_success(Duration::from_millis(10));
+
+        // simulate in-flight requests holding every connection of each
+        let _guards: Vec<_> = [&failed, &flaky, &healthy]
+            .iter()
+            .flat_map(|name_server| {
+                vec![
+                    name_server.client.try_lock().expect("client should not be locked"),
+                    name_server.stream_client.try_lock().expect("client should not be locked"),
+                ]
+            })
+            .collect();
+
+        // a comparison which waited on the locks would not complete, one which gave up on them
+        //  would not rank by the stats
+        let conns = vec![failed.clone(), flaky.clone(), healthy.clone()];
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let mut conns = conns;
+            conns.sort_unstable_by(|a, b| b.cmp(a));
+            sender.send(conns).expect("receiver dropped");
+        });
+
+        let conns = receiver
+            .recv_timeout(Duration::from_secs(5))
+            .expect("ordering blocked on a lock");
+        assert_eq!(conns, vec![healthy, flaky, failed]);
+    }
+
+    #[test]
+    fn test_status() {
+        let config = NameServerConfig {
+            protocol: Protocol::Tcp,
+            ..config(53)
+        };
+        let name_server = NameServer::new_with_provider(
+            config,
+            ResolverOpts::default(),
+            TokioConnectionProvider::new(TokioHandle),
+        )
+        .unwrap();
+
+        let status = name_server.status();
+        assert_eq!(status.state(), ConnectionState::Init);
+        assert_eq!(status.successes(), 0);
+        assert_eq!(status.failures(), 0);
+        assert!(status.last_failure().is_none());
+
+        name_server.stats.next_success(Duration::from_millis(10));
+        name_server.stats.next_success(Duration::from_millis(10));
+        name_server.state.establish(None);
+        assert_eq!(name_server.status().state(), ConnectionState::Established);
+
+        let failed_at = Instant::now();
+        name_server.stats.next_failure();
+        name_server.state.fail(failed_at);
+
+        let status = name_server.status();
+        assert_eq!(
+            status.socket_addr(),
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 53)
+        );
+        assert_eq!(status.protocol(), Protocol::Tcp);
+        assert_eq!(status.state(), ConnectionState::Failed);
+        assert_eq!(status.successes(), 2);
+        assert_eq!(status.failures(), 1);
+        assert_eq!(status.last_failure(), Some(failed_at));
+    }
+
+    #[test]
+    fn test_first_request_sends_edns() {
+        let config = config(53);
+        let mut options = ResolverOpts::default();
+        options.edns0 = true;
+        options.edns_max_payload = 4096;
+
+        let mut response = Message::new();
+        let mut remote_edns = Edns::new();
+        remote_edns.set_max_payload(1400);
+        response.set_edns(remote_edns);
+
+        let handle = RecordingHandle::new(response);
+        let requests = Arc::clone(&handle.requests);
+        let mut name_server =
+            NameServer::new_with_provider(config, options, RecordingProvider(handle)).unwrap();
+
+        let name = Name::parse("www.example.com.", None).unwrap();
+        let io_loop = Runtime::new().unwrap();
+        io_loop
+            .block_on(name_server.lookup(
+                Query::query(name, RecordType::A),
+                DnsRequestOptions::default(),
+            ))
+            .expect("query failed");
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        let edns = requests[0].edns().expect("first request should carry EDNS");
+        assert_eq!(edns.max_payload(), 4096);
+        assert_eq!(edns.version(), 0);
+        assert!(!edns.dnssec_ok());
+
+        let remote_edns = name_server.state.remote_edns().expect("remote EDNS");
+        assert_eq!(remote_edns.max_payload(), 1400);
+    }
+
+    #[test]
+    fn test_client_subnet() {
+        let config = config(53);
+        let mut options = ResolverOpts::default();
+        options.client_subnet = Some((IpAddr::V4(Ipv4Addr::new(192, 0, 2, 77)), 24));
+
+        let handle = RecordingHandle::new(Message::new());
+        let requests = Arc::clone(&handle.requests);
+        let mut name_server =
+            NameServer::new_with_provider(config, options, RecordingProvider(handle)).unwrap();
+
+        let name = Name::parse("www.example.com.", None).unwrap();
+        let io_loop = Runtime::new().unwrap();
+        io_loop
+            .block_on(name_server.lookup(
+                Query::query(name.clone(), RecordType::A),
+                DnsRequestOptions::default(),
+            ))
+            .expect("query failed");
+
+        // a request with its own EDNS keeps its options, and gains the client subnet
+        let mut message = Message::new();
+        message.add_query(Query::query(name, RecordType::A));
+        let mut edns = Edns::new();
+        edns.set_option(EdnsOption::Unknown(65001, vec![1, 2, 3]));
+        message.set_edns(edns);
+        io_loop
+            .block_on(name_server.send(DnsRequest::new(message, DnsRequestOptions::default())))
+            .expect("query failed");
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+
+        let expected = ClientSubnet::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 0)), 24, 0);
+        for request in requests.iter() {
+            let edns = request.edns().expect("request should carry EDNS");
+            match edns.option(EdnsCode::Subnet) {
+                Some(EdnsOption::Subnet(subnet)) => {
+                    assert_eq!(*subnet, expected);
+                    assert_eq!(subnet.source_prefix(), 24);
+                }
+                option => panic!("expected the client subnet: {:?}", option),
+            }
+        }
+
+        let edns = requests[1].edns().unwrap();
+        assert_eq!(
+            edns.option(EdnsCode::Unknown(65001)),
+            Some(&EdnsOption::Unknown(65001, vec![1, 2, 3]))
+        );
+    }
+
+    #[test]
+    fn test_dnssec_ok() {
+        let config = config(53);
+        let mut options = ResolverOpts::default();
+        options.dnssec_ok = true;
+
+        let handle = RecordingHandle::new(Message::new());
+        let requests = Arc::clone(&handle.requests);
+        let mut name_server =
+            NameServer::new_with_provider(config, options, RecordingProvider(handle)).unwrap();
+
+        let name = Name::parse("www.example.com.", None).unwrap();
+        let io_loop = Runtime::new().unwrap();
+        io_loop
+            .block_on(name_server.lookup(
+                Query::query(name.clone(), RecordType::A),
+                DnsRequestOptions::default(),
+            ))
+            .expect("query failed");
+
+        // a request with its own EDNS has the DO bit set as well
+        let mut message = Message::new();
+        message.add_query(Query::query(name, RecordType::A));
+        message.set_edns(Edns::new());
+        io_loop
+            .block_on(name_server.send(DnsRequest::new(message, DnsRequestOptions::default())))
+            .expect("query failed");
+
+        // EDNS is sent without edns0, the DO bit can only be sent with it
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        for request in requests.iter() {
+            let edns = request.edns().expect("request should carry EDNS");
+            assert!(edns.dnssec_ok());
+        }
+    }
+
+    #[test]
+    fn test_padding() {
+        let name = Name::parse("www.example.com.", None).unwrap();
+        let io_loop = Runtime::new().unwrap();
+        let mut options = ResolverOpts::default();
+        options.enable_padding = true;
+
+        let sent_len = |config: &NameServerConfig| {
+            let handle = RecordingHandle::new(Message::new());
+            let requests = Arc::clone(&handle.requests);
+            let provider = RecordingProvider(handle);
+            let mut name_server =
+                NameServer::new_with_provider(config.clone(), options.clone(), provider).unwrap();
+
+            io_loop
+                .block_on(name_server.lookup(
+                    Query::query(name.clone(), RecordType::A),
+                    DnsRequestOptions::default(),
+                ))
+                .expect("query failed");
+
+            let requests = requests.lock().unwrap();
+            requests[0].to_vec().unwrap().len()
+        };
+
+        // plaintext requests are not padded, nor sent with EDNS
+        let config = config(53);
+        assert_eq!(sent_len(&config), 33);
+
+        #[cfg(feature = "dns-over-tls")]
+        {
+            let config = NameServerConfig {
+                protocol: Protocol::Tls,
+                tls_dns_name: Some("dns.example.com".to_string()),
+                ..config
+            };
+            assert_eq!(sent_len(&config), 128);
+        }
+    }
+
+    /// A name server which answers a request for its NSID with its identifier
+    fn nsid_provider() -> RecordingProvider {
+        RecordingProvider(RecordingHandle::answering(|request| {
+            let mut response = Message::new();
+            response.add_queries(request.queries().to_vec());
+
+            let requested = request
+                .edns()
+                .and_then(|edns| edns.option(EdnsCode::NSID))
+                .is_some();
+            if requested {
+                let mut edns = Edns::new();
+                edns.set_option(EdnsOption::Unknown(3, b"ns1.example".to_vec()));
+                response.set_edns(edns);
+            }
+
+            Ok(response)
+        }))
+    }
+
+    #[test]
+    fn test_edns_options() {
+        let config = config(53);
+        let query = Query::query(Name::parse("www.example.com.", None).unwrap(), RecordType::A);
+        let io_loop = Runtime::new().unwrap();
+
+        // without the option, the name server does not identify itself
+        let mut name_server =
+            NameServer::new_with_provider(config.clone(), ResolverOpts::default(), nsid_provider())
+                .unwrap();
+        io_loop
+            .block_on(name_server.lookup(query.clone(), DnsRequestOptions::default()))
+            .expect("query failed");
+        assert!(name_server.status().edns_options().is_empty());
+
+        // an empty NSID option requests the identifier of the name server
+        let mut options = ResolverOpts::default();
+        options.edns_options = vec![(3, vec![])];
+        let mut name_server =
+            NameServer::new_with_provider(config, options, nsid_provider()).unwrap();
+        io_loop
+            .block_on(name_server.lookup(query, DnsRequestOptions::default()))
+            .expect("query failed");
+        assert_eq!(
+            name_server.status().edns_options(),
+            &[(3, b"ns1.example".to_vec())]
+        );
+    }
+
+    #[test]
+    fn test_edns_version_downgrade() {
+        let config = config(53);
+
+        // the name server implements EDNS version 0 only
+        let handle = RecordingHandle::answering(|request| {
+            let version = request.edns().map_or(0, Edns::version);
+            let mut response = if version > 0 {
+                let mut response = Message::new();
+                response.add_queries(request.queries().to_vec());
+                response.set_response_code(ResponseCode::BADVERS);
+                response
+            } else {
+                echo(request)
+            };
+
+            let mut edns = Edns::new();
+            edns.set_rcode_high(response.response_code().high());
+            response.set_edns(edns);
+            Ok(response)
+        });
+        let versions = || -> Vec<u8> {
+            let requests = handle.requests.lock().unwrap();
+            requests
+                .iter()
+                .map(|request| request.edns().map_or(0, Edns::version))
+                .collect()
+        };
+        let provider = RecordingProvider(handle.clone());
+        let mut name_server =
+            NameServer::new_with_provider(config, ResolverOpts::default(), provider).unwrap();
+
+        let request = || {
+            let mut edns = Edns::new();
+            edns.set_version(1);
+
+            let mut message = Message::new();
+            let name = Name::parse("www.example.com.", None).unwrap();
+            message.add_query(Query::query(name, RecordType::A));
+            message.set_edns(edns);
+            DnsRequest::new(message, DnsRequestOptions::default())
+        };
+
+        // the BADVERS response is retried once, with the version of the name server
+        let io_loop = Runtime::new().unwrap();
+        let response = io_loop
+            .block_on(name_server.send(request()))
+            .expect("query failed");
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(response.answers().len(), 1);
+        assert_eq!(versions(), vec![1, 0]);
+
+        // the version is remembered for later requests
+        io_loop
+            .block_on(name_server.send(request()))
+            .expect("query failed");
+        assert_eq!(versions(), vec![1, 0, 0]);
+    }
+
+    #[test]
+    fn test_extended_response_code_failure() {
+        let config = config(53);
+        // the name server responds to all requests with an extended response code
+        let provider = RecordingProvider(RecordingHandle::answering(|request| {
+            let mut response = Message::new();
+            response.add_queries(request.queries().to_vec());
+            response.set_response_code(ResponseCode::BADCOOKIE);
+            let mut edns = Edns::new();
+            edns.set_rcode_high(ResponseCode::BADCOOKIE.high());
+            response.set_edns(edns);
+            Ok(response)
+        }));
+        let mut name_server =
+            NameServer::new_with_provider(config, ResolverOpts::default(), provider).unwrap();
+
+        // BADCOOKIE is read from the OPT record as well, rather than as YXRRSET from the header
+        let io_loop = Runtime::new().unwrap();
+        let name = Name::parse("www.example.com.", None).unwrap();
+        let error = io_loop
+            .block_on(name_server.lookup(
+                Query::query(name, RecordType::A),
+                DnsRequestOptions::default(),
+            ))
+            .expect_err("BADCOOKIE response succeeded");
+        match error.kind() {
+            ResolveErrorKind::NoRecordsFound { response_code, .. } => {
+                assert_eq!(*response_code, ResponseCode::BADCOOKIE)
+            }
+            kind => panic!("unexpected error: {}", kind),
+        }
+
+        // and is counted as a failure of the name server
+        let status = name_server.status();
+        assert_eq!(status.successes(), 0);
+        assert_eq!(status.failures(), 1);
+    }
+
+    /// Looks up an A record from a legacy name server responding with `response_code` to EDNS,
+    ///  returning the response and whether each request sent had EDNS
+    fn legacy_lookup(
+        response_code: ResponseCode,
+        options: ResolverOpts,
+    ) -> (DnsResponse, Vec<bool>) {
+        let config = config(53);
+        // the name server does not implement EDNS, responding to requests with it with the code
+        let handle = RecordingHandle::answering(move |request| {
+            if request.edns().is_some() {
+                let mut response = Message::new();
+                response.add_queries(request.queries().to_vec());
+                response.set_response_code(response_code);
+                Ok(response)
+            } else {
+                Ok(echo(request))
+            }
+        });
+        let provider = RecordingProvider(handle.clone());
+        let mut name_server = NameServer::new_with_provider(config, options, provider).unwrap();
+
+        let io_loop = Runtime::new().unwrap();
+        let name = Name::parse("www.example.com.", None).unwrap();
+        let response = io_loop
+            .block_on(name_server.lookup(
+                Query::query(name, RecordType::A),
+                DnsRequestOptions::default(),
+            ))
+            .expect("query failed");
+
+        let requests = handle.requests.lock().unwrap();
+        let edns = requests.iter().map(|request| request.edns().is_some());
+        (response, edns.collect())
+    }
+
+    #[test]
+    fn test_edns_notimp_fallback() {
+        let mut options = ResolverOpts::default();
+        options.edns0 = true;
+
+        // the NOTIMP response is retried once, without EDNS
+        let (response, requests) = legacy_lookup(ResponseCode::NotImp, options);
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(response.answers().len(), 1);
+        assert_eq!(requests, vec![true, false]);
+
+        // a request without EDNS is not retried
+        let (response, requests) = legacy_lookup(ResponseCode::NotImp, ResolverOpts::default());
+        assert_eq!(response.answers().len(), 1);
+        assert_eq!(requests, vec![false]);
+    }
+
+    #[test]
+    fn test_edns_formerr_fallback() {
+        let mut options = ResolverOpts::default();
+        options.edns0 = true;
+
+        // FORMERR is only retried without EDNS when enabled
+        let (response, requests) = legacy_lookup(ResponseCode::FormErr, options.clone());
+        assert_eq!(response.response_code(), ResponseCode::FormErr);
+        assert_eq!(requests, vec![true]);
+
+        options.edns_formerr_fallback = true;
+        let (response, requests) = legacy_lookup(ResponseCode::FormErr, options);
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(requests, vec![true, false]);
+    }
+
+    fn case_randomization_config() -> (NameServerConfig, ResolverOpts, Name) {
+        let config = config(53);
+        let mut options = ResolverOpts::default();
+        options.case_randomization = true;
+
+        // enough letters that the chance of none changing case is negligible
+        let name = Name::parse("the-quick-brown-fox.jumps-over-the-lazy-dog.", None).unwrap();
+        (config, options, name)
+    }
+
+    #[test]
+    fn test_case_randomization() {
+        let (config, options, name) = case_randomization_config();
+        let provider = RecordingProvider(RecordingHandle::answering(|request| Ok(echo(request))));
+        let mut name_server = NameServer::new_with_provider(config, options, provider).unwrap();
+
+        let io_loop = Runtime::new().unwrap();
+        let response = io_loop
+            .block_on(name_server.lookup(
+                Query::query(name.clone(), RecordType::A),
+                DnsRequestOptions::default(),
+            ))
+            .expect("query failed");
+
+        // the names are restored to the case of the request
+        assert!(response.queries()[0].name().eq_case(&name));
+        assert!(response.answers()[0].name().eq_case(&name));
+    }
+
+    #[test]
+    fn test_case_randomization_mismatch() {
+        let (config, options, name) = case_randomization_config();
+
+        // the response echoes the name, but not the case of the name sent
+        let mut response = Message::new();
+        response.add_query(Query::query(name.clone(), RecordType::A));
+        let rdata = RData::A(Ipv4Addr::new(127, 0, 0, 1));
+        response.add_answer(Record::from_rdata(name.clone(), 300, rdata));
+
+        let handle = RecordingHandle::new(response);
+        let requests = Arc::clone(&handle.requests);
+        let mut name_server =
+            NameServer::new_with_provider(config, options, RecordingProvider(handle)).unwrap();
+
+        let io_loop = Runtime::new().unwrap();
+        let response = io_loop.block_on(name_server.lookup(
+            Query::query(name.clone(), RecordType::A),
+            DnsRequestOptions::default(),
+        ));
+        assert!(response.is_err());
+
+        // the name was sent with its case randomized
+        let requests = requests.lock().unwrap();
+        let sent = requests[0].queries()[0].name();
+        assert_eq!(sent, &name);
+        assert!(!sent.eq_case(&name));
+
+        // a spoofed response is not the fault of the name server
+        assert!(!name_server.is_failed());
+    }
+
+    /// Connects to `udp` or `tcp`, by the protocol of the config
+    #[derive(Clone)]
+    struct DualProvider {
+        udp: RecordingHandle,
+        /// TCP can not be connected to without a handle
+        tcp: Option<RecordingHandle>,
+    }
+
+    impl ConnectionProvider for DualProvider {
+        type Conn = RecordingHandle;
+        type FutureConn = future::Ready<Result<RecordingHandle, ResolveError>>;
+        type Time = TokioTime;
+
+        fn new_connection(&self, config: &NameServerConfig, _: &ResolverOpts) -> Self::FutureConn {
+            match config.protocol {
+                Protocol::Tcp => match self.tcp {
+                    Some(ref tcp) => future::ok(tcp.clone()),
+                    None => future::err(ResolveError::from("connection refused")),
+                },
+                _ => future::ok(self.udp.clone()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_dual_name_server() {
+        let config = NameServerConfig {
+            protocol: Protocol::Dual,
+            ..config(53)
+        };
+
+        let name = Name::parse("www.example.com.", None).unwrap();
+        let mut truncated = Message::new();
+        truncated.set_truncated(true);
+        let mut full = Message::new();
+        full.add_answer(Record::from_rdata(
+            name.clone(),
+            300,
+            RData::A(Ipv4Addr::new(127, 0, 0, 1)),
+        ));
+
+        let udp = RecordingHandle::new(truncated);
+        let tcp = RecordingHandle::new(full);
+        let provider = DualProvider {
+            udp: udp.clone(),
+            tcp: Some(tcp.clone()),
+        };
+        let mut name_server =
+            NameServer::new_with_provider(config, ResolverOpts::default(), provider).unwrap();
+
+        let io_loop = Runtime::new().unwrap();
+        let response = io_loop
+            .block_on(name_server.lookup(
+                Query::query(name.clone(), RecordType::A),
+                DnsRequestOptions::default(),
+            ))
+            .expect("query failed");
+
+        // the truncated response is retried over tcp
+        assert!(!response.truncated());
+        assert_eq!(response.answers().len(), 1);
+        assert_eq!(udp.requests.lock().unwrap().len(), 1);
+        assert_eq!(tcp.requests.lock().unwrap().len(), 1);
+
+        // zone transfers go straight to tcp
+        io_loop
+            .block_on(name_server.lookup(
+                Query::query(name, RecordType::AXFR),
+                DnsRequestOptions::default(),
+            ))
+            .expect("query failed");
+        assert_eq!(udp.requests.lock().unwrap().len(), 1);
+        assert_eq!(tcp.requests.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_dual_name_server_tcp_unreachable() {
+        let config = NameServerConfig {
+            protocol: Protocol::Dual,
+            ..config(53)
+        };
+        let mut truncated = Message::new();
+        truncated.set_truncated(true);
+        let provider = DualProvider {
+            udp: RecordingHandle::new(truncated),
+            tcp: None,
+        };
+        let mut name_server =
+            NameServer::new_with_provider(config, ResolverOpts::default(), provider).unwrap();
+
+        // failing to connect for the retry over tcp is a failure of the name server
+        let io_loop = Runtime::new().unwrap();
+        let name = Name::parse("www.example.com.", None).unwrap();
+        assert!(io_loop
+            .block_on(name_server.lookup(
+                Query::query(name, RecordType::A),
+                DnsRequestOptions::default(),
+            ))
+            .is_err());
+
+        let status = name_server.status();
+        assert_eq!(status.state(), ConnectionState::Failed);
+        assert_eq!(status.failures(), 1);
+        assert!(status.last_failure().is_some());
+    }
+
+    #[test]
+    fn test_cookies() {
+        let config = config(53);
+        let mut options = ResolverOpts::default();
+        options.cookies = true;
+
+        let client_cookie = [1, 2, 3, 4, 5, 6, 7, 8];
+        let server_cookie = vec![9; 16];
+        let mut response = Message::new();
+        let mut remote_edns = Edns::new();
+        remote_edns.set_option(EdnsOption::Cookie(Cookie::new(
+            client_cookie,
+            Some(server_cookie.clone()),
+        )));
+        response.set_edns(remote_edns);
+
+        let handle = RecordingHandle::new(response);
+        let requests = Arc::clone(&handle.requests);
+        let mut name_server =
+            NameServer::new_with_provider(config, options, RecordingProvider(handle)).unwrap();
+        name_server.client_cookie = client_cookie;
+
+        let name = Name::parse("www.example.com.", None).unwrap();
+        let io_loop = Runtime::new().unwrap();
+        for _ in 0..2 {
+            io_loop
+                .block_on(name_server.lookup(
+                    Query::query(name.clone(), RecordType::A),
+                    DnsRequestOptions::default(),
+                ))
+                .expect("query failed");
+        }
+
+        let cookie = |request: &DnsRequest| {
+            match request.edns().and_then(|edns| edns.option(EdnsCode::Cookie)) {
+                Some(EdnsOption::Cookie(cookie)) => cookie.clone(),
+                option => panic!("expected a cookie: {:?}", option),
+            }
+        };
+
+        // only the client cookie is sent until the server has responded with its own
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(cookie(&requests[0]), Cookie::new(client_cookie, None));
+        assert_eq!(
+            cookie(&requests[1]),
+            Cookie::new(client_cookie, Some(server_cookie))
+        );
+    }
+
+    #[test]
+    fn test_cookies_ignored() {
+        let config = config(53);
+        let mut options = ResolverOpts::default();
+        options.cookies = true;
+
+        // the name server neither supports cookies, nor EDNS
+        let handle = RecordingHandle::new(Message::new());
+        let requests = Arc::clone(&handle.requests);
+        let mut name_server =
+            NameServer::new_with_provider(config, options, RecordingProvider(handle)).unwrap();
+
+        let name = Name::parse("www.example.com.", None).unwrap();
+        let io_loop = Runtime::new().unwrap();
+        for _ in 0..2 {
+            io_loop
+                .block_on(name_server.lookup(
+                    Query::query(name.clone(), RecordType::A),
+                    DnsRequestOptions::default(),
+                ))
+                .expect("query failed");
+        }
+
+        assert!(name_server.state.server_cookie().is_none());
+        for request in requests.lock().unwrap().iter() {
+            let edns = request.edns().expect("request should carry EDNS");
+            assert_eq!(
+                edns.option(EdnsCode::Cookie),
+                Some(&EdnsOption::Cookie(Cookie::new(name_server.client_cookie, None)))
+            );
+        }
+    }
+
+    #[test]
+    fn test_edns_disabled() {
+        let config = config(53);
+
+        let handle = RecordingHandle::new(Message::new());
+        let requests = Arc::clone(&handle.requests);
+        let mut name_server = NameServer::new_with_provider(
+            config,
+            ResolverOpts::default(),
+            RecordingProvider(handle),
+        )
+        .unwrap();
+
+        let name = Name::parse("www.example.com.", None).unwrap();
+        let io_loop = Runtime::new().unwrap();
+        io_loop
+            .block_on(name_server.lookup(
+                Query::query(name, RecordType::A),
+                DnsRequestOptions::default(),
+            ))
+            .expect("query failed");
+
+        assert!(requests.lock().unwrap()[0].edns().is_none());
+        assert!(name_server.state.remote_edns().is_none());
+    }
+
+    /// Counts the connections made, requests on each are responded to after a short delay
+    #[derive(Clone)]
+    struct SlowProvider(Arc<AtomicUsize>);
+
+    #[derive(Clone)]
+    struct SlowHandle;
+
+    impl DnsHandle for SlowHandle {
+        type Response = Pin<Box<dyn Future<Output = Result<DnsResponse, ResolveError>> + Send>>;
+        type Error = ResolveError;
+
+        fn send<R: Into<DnsRequest> + Unpin + Send + 'static>(&mut self, _: R) -> Self::Response {
+            Box::pin(async {
+                TokioTime::delay_for(Duration::from_millis(50)).await;
+                Ok(Message::new().into())
+            })
+        }
+    }
+
+    impl ConnectionProvider for SlowProvider {
+        type Conn = SlowHandle;
+        type FutureConn = future::Ready<Result<SlowHandle, ResolveError>>;
+        type Time = TokioTime;
+
+        fn new_connection(&self, _: &NameServerConfig, _: &ResolverOpts) -> Self::FutureConn {
+            self.0.fetch_add(1, atomic::Ordering::SeqCst);
+            future::ok(SlowHandle)
+        }
+    }
+
+    #[test]
+    fn test_stream_connections_per_server() {
+        let config = NameServerConfig {
+            protocol: Protocol::Tcp,
+            ..config(53)
+        };
+        let mut options = ResolverOpts::default();
+        options.connections_per_server = 2;
+
+        let connections = Arc::new(AtomicUsize::new(0));
+        let provider = SlowProvider(Arc::clone(&connections));
+        let name_server = NameServer::new_with_provider(config, options, provider).unwrap();
+
+        let io_loop = Runtime::new().unwrap();
+        let name = Name::parse("www.example.com.", None).unwrap();
+        let lookup = || {
+            name_server.clone().lookup(
+                Query::query(name.clone(), RecordType::A),
+                DnsRequestOptions::default(),
+            )
+        };
+
+        // concurrent requests are spread across a second connection, but no more
+        io_loop
+            .block_on(future::try_join_all((0..3).map(|_| lookup())))
+            .expect("query failed");
+        assert_eq!(connections.load(atomic::Ordering::SeqCst), 2);
+
+        // an idle connection is reused
+        io_loop.block_on(lookup()).expect("query failed");
+        assert_eq!(connections.load(atomic::Ordering::SeqCst), 2);
+        assert_eq!(name_server.client.try_lock().unwrap().len(), 2);
+    }
+
+    struct KeepaliveProvider(RecordingHandle, Arc<AtomicUsize>);
+
+    impl ConnectionProvider for KeepaliveProvider {
+        type Conn = RecordingHandle;
+        type FutureConn = future::Ready<Result<RecordingHandle, ResolveError>>;
+        type Time = TokioTime;
+
+        fn new_connection(&self, _: &NameServerConfig, _: &ResolverOpts) -> Self::FutureConn {
+            self.1.fetch_add(1, atomic::Ordering::SeqCst);
+            future::ok(self.0.clone())
+        }
+    }
+
+    #[test]
+    fn test_edns_keepalive() {
+        let config = NameServerConfig {
+            protocol: Protocol::Tcp,
+            ..config(53)
+        };
+        let mut options = ResolverOpts::default();
+        options.edns_keepalive = true;
+        options.stream_idle_timeout = Duration::from_secs(0);
+
+        // the name server keeps idle connections open for a minute
+        let mut response = Message::new();
+        let mut remote_edns = Edns::new();
+        remote_edns.set_option(EdnsOption::Keepalive(Some(600)));
+        response.set_edns(remote_edns);
+
+        let handle = RecordingHandle::new(response);
+        let requests = Arc::clone(&handle.requests);
+        let connections = Arc::new(AtomicUsize::new(0));
+        let provider = KeepaliveProvider(handle, Arc::clone(&connections));
+        let name_server = NameServer::new_with_provider(config, options, provider).unwrap();
+
+        let io_loop = Runtime::new().unwrap();
+        let name = Name::parse("www.example.com.", None).unwrap();
+        let lookup = || {
+            name_server.clone().lookup(
+                Query::query(name.clone(), RecordType::A),
+                DnsRequestOptions::default(),
+            )
+        };
+
+        // the connection is kept open for as long as the name server advertised
+        for _ in 0..2 {
+            io_loop.block_on(lookup()).expect("query failed");
+        }
+        assert_eq!(name_server.state.keepalive(), Some(Duration::from_secs(60)));
+        assert_eq!(connections.load(atomic::Ordering::SeqCst), 1);
+
+        // the requests ask for the timeout, without one of their own
+        for request in requests.lock().unwrap().iter() {
+            assert_eq!(
+                request.edns().and_then(|edns| edns.option(EdnsCode::Keepalive)),
+                Some(&EdnsOption::Keepalive(None))
+            );
+        }
+
+        // once the advertised timeout passes, the connection is reestablished
+        name_server.state.set_keepalive(Duration::from_secs(0));
+        io_loop.block_on(lookup()).expect("query failed");
+        assert_eq!(connections.load(atomic::Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_reconnect_backoff() {
+        let config = config(53);
+
+        let connections = Arc::new(AtomicUsize::new(0));
+        let handle = RecordingHandle::new(Message::new());
+        let provider = KeepaliveProvider(handle, Arc::clone(&connections));
+        let mut name_server =
+            NameServer::new_with_provider(config, ResolverOpts::default(), provider).unwrap();
+
+        let clock = MockClock::new();
+        name_server.set_clock(Arc::new(clock.clone()));
+        name_server.fail(clock.now());
+
+        let io_loop = Runtime::new().unwrap();
+        let name = Name::parse("www.example.com.", None).unwrap();
+        let lookup = || {
+            name_server.clone().lookup(
+                Query::query(name.clone(), RecordType::A),
+                DnsRequestOptions::default(),
+            )
+        };
+
+        // the name server is not reconnected to until it has backed off, however long it takes
+        assert!(name_server.is_tripped(1));
+        assert!(io_loop.block_on(lookup()).is_err());
+        clock.advance(MIN_RETRY_DELAY - Duration::from_millis(1));
+        assert!(io_loop.block_on(lookup()).is_err());
+        assert_eq!(connections.load(atomic::Ordering::SeqCst), 0);
+
+        clock.advance(Duration::from_millis(1));
+        io_loop.block_on(lookup()).expect("query failed");
+        assert_eq!(connections.load(atomic::Ordering::SeqCst), 1);
+        assert!(!name_server.is_tripped(1));
+    }
+
+    struct TestSigner;
+
+    impl MessageFinalizer for TestSigner {
+        fn finalize_message(&self, _: &Message, _: u32) -> ProtoResult<Vec<Record>> {
+            Ok(vec![Record::from_rdata(
+                Name::root(),
+                0,
+                RData::TXT(TXT::new(vec!["signed".to_string()])),
+            )])
+        }
+    }
+
+    #[test]
+    fn test_signer_finalizes_updates() {
+        let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        server
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let server_addr = server.local_addr().unwrap();
+        let received = std::thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            let (len, _) = server.recv_from(&mut buf).expect("no request received");
+            Message::from_vec(&buf[..len]).expect("bad request")
+        });
+
+        let config = NameServerConfig {
+            socket_addr: server_addr,
+            signer: Some(MessageSigner(Arc::new(TestSigner))),
+            ..config(53)
+        };
+        let mut options = ResolverOpts::default();
+        options.timeout = Duration::from_millis(100); // the server never responds
+        let mut name_server = NameServer::new(config, options, TokioHandle).unwrap();
+
+        let mut update = Message::new();
+        update
+            .set_op_code(OpCode::Update)
+            .add_query(Query::query(Name::root(), RecordType::SOA));
+
+        let io_loop = Runtime::new().unwrap();
+        assert!(io_loop
+            .block_on(name_server.send(DnsRequest::new(update, DnsRequestOptions::default())))
+            .is_err());
+
+        let request = received.join().unwrap();
+        assert_eq!(request.op_code(), OpCode::Update);
+        assert_eq!(request.additionals().len(), 1);
+        assert_eq!(request.additionals()[0].rr_type(), RecordType::TXT);
+    }
+
+    #[test]
+    #[cfg(feature = "dns-over-tls")]
+    fn test_tls_name_server_requires_dns_name() {
+        let config = NameServerConfig {
+            socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)), 853),
+            protocol: Protocol::Tls,
+            ..config(53)
+        };
+        let error = NameServer::new(config.clone(), ResolverOpts::default(), TokioHandle)
+            .expect_err("tls without a dns name should fail");
+        assert!(error.to_string().contains("tls_dns_name"));
+
+        // nor is an empty name sent for the certificate to be verified against
+        let empty = NameServerConfig {
+            tls_dns_name: Some(String::new()),
+            ..config.clone()
+        };
+        assert!(NameServer::new(empty, ResolverOpts::default(), TokioHandle).is_err());
+
+        let io_loop = Runtime::new().unwrap();
+        let provider = TokioConnectionProvider::new(TokioHandle);
+        let connection = provider.new_connection(&config, &ResolverOpts::default());
+        assert!(io_loop.block_on(connection).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "dns-over-tls")]
+    #[cfg(not(windows))] // flakes on AppVeyor...
+    fn test_tls_name_server() {
+        let config = NameServerConfig {
+            socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)), 853),
+            protocol: Protocol::Tls,
+            tls_dns_name: Some("cloudflare-dns.com".to_string()),
+            ..config(53)
+        };
+        let io_loop = Runtime::new().unwrap();
+        let name_server = future::lazy(|_| {
+            NameServer::<_, TokioConnectionProvider>::new(
+                config,
+                ResolverOpts::default(),
+                TokioHandle,
+            )
+            .unwrap()
+        });
+
+        let name = Name::parse("www.example.com.", None).unwrap();
+        let response = io_loop
+            .block_on(name_server.then(|mut name_server| {
+                name_server.lookup(
+                    Query::query(name.clone(), RecordType::A),
+                    DnsRequestOptions::default(),
+                )
+            }))
+            .expect("query failed");
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+    }
 }
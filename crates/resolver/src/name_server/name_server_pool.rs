@@ -6,23 +6,37 @@
 // copied, modified, or distributed except according to those terms.
 
 use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
 use std::pin::Pin;
+use std::sync::atomic::{self, AtomicBool, AtomicUsize};
 use std::sync::Arc;
 use std::task::{Context, Poll};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use futures_channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
 use futures_util::stream::{FuturesUnordered, StreamExt};
-use futures_util::{future::Future, future::FutureExt};
+use futures_util::future::{self, Future, FutureExt, Shared};
+use parking_lot::{Mutex, RwLock};
+use rand::seq::SliceRandom;
 use smallvec::SmallVec;
 
-use proto::xfer::{DnsHandle, DnsRequest, DnsResponse};
+use proto::op::{Edns, Message, OpCode, Query};
+use proto::rr::{Name, RecordType};
+use proto::xfer::{DnsHandle, DnsRequest, DnsRequestOptions, DnsResponse};
 use proto::Time;
 
-use crate::config::{ResolverConfig, ResolverOpts};
+use crate::clock::{Clock, SystemClock};
+use crate::config::{
+    NameServerConfig, NameServerConfigGroup, ResolverConfig, ResolverOpts, ServerOrderingStrategy,
+};
 use crate::error::{ResolveError, ResolveErrorKind};
 #[cfg(feature = "mdns")]
 use crate::name_server;
-use crate::name_server::{ConnectionProvider, NameServer};
+use crate::name_server::{
+    qname_minimization, ConnectionProvider, NameServer, NameServerStatsSnapshot, NameServerStatus,
+    ResolverObserver, ResponseMeta, StateChange,
+};
 #[cfg(test)]
 #[cfg(feature = "tokio-runtime")]
 use crate::name_server::{TokioConnection, TokioConnectionProvider, TokioHandle};
@@ -36,14 +50,39 @@ pub struct NameServerPool<
     P: ConnectionProvider<Conn = C> + Send + 'static,
 > {
     // TODO: switch to FuturesMutex (Mutex will have some undesireable locking)
-    datagram_conns: Arc<[NameServer<C, P>]>, /* All NameServers must be the same type */
-    stream_conns: Arc<[NameServer<C, P>]>,   /* All NameServers must be the same type */
+    // All NameServers must be the same type, the conns are replaced as a whole when NameServers
+    //  are added or removed, requests in flight keep using the conns they started with
+    datagram_conns: Arc<RwLock<Arc<[NameServer<C, P>]>>>,
+    stream_conns: Arc<RwLock<Arc<[NameServer<C, P>]>>>,
     #[cfg(feature = "mdns")]
     mdns_conns: NameServer<C, P>, /* All NameServers must be the same type */
     options: ResolverOpts,
     conn_provider: P,
+    rotation: Arc<AtomicUsize>,
+    /// set once the pool is shut down, no further requests are accepted
+    closed: Arc<AtomicBool>,
+    /// the number of requests which have been sent and not yet completed
+    in_flight: Arc<AtomicUsize>,
+    observer: Option<Arc<dyn ResolverObserver>>,
+    /// receives the transitions of the state of the NameServers, see `state_changes`
+    state_changes: Arc<RwLock<Option<UnboundedSender<StateChange>>>>,
+    // the pools to which names in each zone are forwarded, see `add_route`
+    routes: Arc<RwLock<HashMap<Name, NameServerPool<C, P>>>>,
+    /// the queries in flight, identical queries sent concurrently share the response of the first
+    queries: SharedQueries,
+    /// the source of the current time, for deadlines and the latency of responses
+    clock: Arc<dyn Clock>,
 }
 
+type ResponseFuture =
+    Pin<Box<dyn Future<Output = Result<(DnsResponse, ResponseMeta), ResolveError>> + Send>>;
+
+/// The responses of the queries in flight, each with a token held by every request awaiting it
+type SharedQueries = Arc<Mutex<HashMap<QueryKey, (Shared<ResponseFuture>, Arc<()>)>>>;
+
+/// How often a shut down pool checks for requests still in flight
+const DRAIN_INTERVAL: Duration = Duration::from_millis(10);
+
 #[cfg(test)]
 #[cfg(feature = "tokio-runtime")]
 impl NameServerPool<TokioConnection, TokioConnectionProvider> {
@@ -51,7 +90,7 @@ impl NameServerPool<TokioConnection, TokioConnectionProvider> {
         config: &ResolverConfig,
         options: &ResolverOpts,
         runtime: TokioHandle,
-    ) -> Self {
+    ) -> Result<Self, ResolveError> {
         Self::from_config_with_provider(config, options, TokioConnectionProvider::new(runtime))
     }
 }
@@ -61,11 +100,13 @@ where
     C: DnsHandle<Error = ResolveError> + Sync + 'static,
     P: ConnectionProvider<Conn = C> + 'static,
 {
+    /// Returns an error for the first invalid NameServer config, see
+    ///  [`NameServer::new_with_provider`]
     pub(crate) fn from_config_with_provider(
         config: &ResolverConfig,
         options: &ResolverOpts,
         conn_provider: P,
-    ) -> NameServerPool<C, P> {
+    ) -> Result<NameServerPool<C, P>, ResolveError> {
         let datagram_conns: Vec<NameServer<C, P>> = config
             .name_servers()
             .iter()
@@ -80,9 +121,13 @@ where
                 #[cfg(not(feature = "dns-over-rustls"))]
                 let ns_config = { ns_config.clone() };
 
-                NameServer::<C, P>::new_with_provider(ns_config, *options, conn_provider.clone())
+                NameServer::<C, P>::new_with_provider(
+                    ns_config,
+                    options.clone(),
+                    conn_provider.clone(),
+                )
             })
-            .collect();
+            .collect::<Result<_, _>>()?;
 
         let stream_conns: Vec<NameServer<C, P>> = config
             .name_servers()
@@ -98,18 +143,83 @@ where
                 #[cfg(not(feature = "dns-over-rustls"))]
                 let ns_config = { ns_config.clone() };
 
-                NameServer::<C, P>::new_with_provider(ns_config, *options, conn_provider.clone())
+                NameServer::<C, P>::new_with_provider(
+                    ns_config,
+                    options.clone(),
+                    conn_provider.clone(),
+                )
             })
-            .collect();
+            .collect::<Result<_, _>>()?;
+
+        let routes = config
+            .forward_zones()
+            .iter()
+            .map(|(zone, name_servers)| {
+                let mut zone = zone.clone();
+                zone.set_fqdn(true);
 
+                let config = ResolverConfig::from_parts(None, vec![], name_servers.clone());
+                let pool = Self::from_config_with_provider(&config, options, conn_provider.clone());
+                pool.map(|pool| (zone, pool))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self::new(
+            options,
+            Arc::from(datagram_conns),
+            Arc::from(stream_conns),
+            #[cfg(feature = "mdns")]
+            name_server::mdns_nameserver(options.clone(), conn_provider.clone(), false),
+            conn_provider,
+            routes,
+        ))
+    }
+
+    /// Constructs a pool of the NameServers, forwarding the zones in `routes` to their pools
+    fn new(
+        options: &ResolverOpts,
+        datagram_conns: Arc<[NameServer<C, P>]>,
+        stream_conns: Arc<[NameServer<C, P>]>,
+        #[cfg(feature = "mdns")] mdns_conns: NameServer<C, P>,
+        conn_provider: P,
+        routes: HashMap<Name, NameServerPool<C, P>>,
+    ) -> Self {
         NameServerPool {
-            datagram_conns: Arc::from(datagram_conns),
-            stream_conns: Arc::from(stream_conns),
+            datagram_conns: Arc::new(RwLock::new(datagram_conns)),
+            stream_conns: Arc::new(RwLock::new(stream_conns)),
             #[cfg(feature = "mdns")]
-            mdns_conns: name_server::mdns_nameserver(*options, conn_provider.clone(), false),
-            options: *options,
+            mdns_conns,
+            options: options.clone(),
             conn_provider,
+            rotation: Arc::new(AtomicUsize::new(0)),
+            closed: Arc::new(AtomicBool::new(false)),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            observer: None,
+            state_changes: Arc::new(RwLock::new(None)),
+            routes: Arc::new(RwLock::new(routes)),
+            queries: Arc::new(Mutex::new(HashMap::new())),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Constructs a pool from the config, seeding each NameServer with the stats from a previous
+    ///  pool, see [`Self::export_stats`]
+    ///
+    /// NameServers are matched to the stats by address and protocol, those without stats start
+    ///  fresh. Returns an error for the first invalid NameServer config.
+    pub fn from_config_with_stats(
+        config: &ResolverConfig,
+        options: &ResolverOpts,
+        conn_provider: P,
+        stats: &[NameServerStatsSnapshot],
+    ) -> Result<Self, ResolveError> {
+        let pool = Self::from_config_with_provider(config, options, conn_provider)?;
+
+        for conn in pool.datagram_conns().iter().chain(pool.stream_conns().iter()) {
+            conn.import_stats(stats);
         }
+
+        Ok(pool)
     }
 
     #[doc(hidden)]
@@ -120,12 +230,13 @@ where
         stream_conns: Vec<NameServer<C, P>>,
         conn_provider: P,
     ) -> Self {
-        NameServerPool {
-            datagram_conns: Arc::from(datagram_conns),
-            stream_conns: Arc::from(stream_conns),
-            options: *options,
+        Self::new(
+            options,
+            Arc::from(datagram_conns),
+            Arc::from(stream_conns),
             conn_provider,
-        }
+            HashMap::new(),
+        )
     }
 
     #[doc(hidden)]
@@ -137,13 +248,14 @@ where
         mdns_conns: NameServer<C, P>,
         conn_provider: P,
     ) -> Self {
-        NameServerPool {
-            datagram_conns: Arc::from(datagram_conns),
-            stream_conns: Arc::from(stream_conns),
+        Self::new(
+            options,
+            Arc::from(datagram_conns),
+            Arc::from(stream_conns),
             mdns_conns,
-            options: *options,
             conn_provider,
-        }
+            HashMap::new(),
+        )
     }
 
     #[cfg(test)]
@@ -154,12 +266,7 @@ where
         stream_conns: Arc<[NameServer<C, P>]>,
         conn_provider: P,
     ) -> Self {
-        NameServerPool {
-            datagram_conns,
-            stream_conns,
-            options: *options,
-            conn_provider,
-        }
+        Self::new(options, datagram_conns, stream_conns, conn_provider, HashMap::new())
     }
 
     #[cfg(test)]
@@ -171,51 +278,433 @@ where
         mdns_conns: NameServer<C, P>,
         conn_provider: P,
     ) -> Self {
-        NameServerPool {
+        Self::new(
+            options,
             datagram_conns,
             stream_conns,
             mdns_conns,
-            options: *options,
             conn_provider,
+            HashMap::new(),
+        )
+    }
+
+    /// Returns a snapshot of the health of each NameServer in the pool
+    ///
+    /// Datagram NameServers are listed first, followed by stream NameServers. Taking the snapshot
+    ///  does not block requests in flight.
+    pub fn stats(&self) -> Vec<NameServerStatus> {
+        self.datagram_conns()
+            .iter()
+            .chain(self.stream_conns().iter())
+            .map(NameServer::status)
+            .collect()
+    }
+
+    /// Returns the learned stats of each NameServer in the pool, these can be persisted to seed a
+    ///  new pool, see [`Self::from_config_with_stats`]
+    pub fn export_stats(&self) -> Vec<NameServerStatsSnapshot> {
+        self.datagram_conns()
+            .iter()
+            .chain(self.stream_conns().iter())
+            .map(NameServer::export_stats)
+            .collect()
+    }
+
+    /// Sets the observer of the requests sent through the pool, e.g. to record metrics
+    ///
+    /// This applies to clones of the pool made after it is set. Without an observer, requests
+    ///  are sent without any of the timekeeping needed for it.
+    pub fn set_observer(&mut self, observer: Option<Arc<dyn ResolverObserver>>) {
+        self.observer = observer;
+    }
+
+    /// Returns the transitions of the state of the connections to the NameServers, e.g. to log
+    ///  as a NameServer fails and recovers
+    ///
+    /// This is shared by all clones of the pool, including for NameServers added later, and
+    ///  replaces the receiver of any earlier call. The transitions are queued as they happen,
+    ///  without waiting for them to be received, dropping the receiver stops them. Unlike the
+    ///  observer, this only receives the changes of the state of a NameServer.
+    pub fn state_changes(&self) -> UnboundedReceiver<StateChange> {
+        let (sender, receiver) = mpsc::unbounded();
+
+        *self.state_changes.write() = Some(sender.clone());
+        for conn in self.datagram_conns().iter().chain(self.stream_conns().iter()) {
+            conn.set_state_changes(Some(sender.clone()));
+        }
+
+        receiver
+    }
+
+    /// Connects to each of the NameServers, negotiating EDNS, so that the first requests need not
+    ///  wait on connecting, see `ResolverOpts::eager_connect`
+    ///
+    /// A query for the NS records of the root is sent to each of the NameServers at once. Those
+    ///  which fail are left to reconnect as they otherwise would, the returned future resolves to
+    ///  the number of NameServers which were connected.
+    pub fn warmup(&self) -> impl Future<Output = usize> + Send + 'static {
+        let query = Query::query(Name::root(), RecordType::NS);
+        let warmups = self
+            .datagram_conns()
+            .iter()
+            .chain(self.stream_conns().iter())
+            .map(|conn| {
+                let socket_addr = conn.socket_addr();
+                let response = conn
+                    .clone()
+                    .lookup(query.clone(), DnsRequestOptions::default());
+                response.map(move |response| match response {
+                    Ok(..) => true,
+                    Err(e) => {
+                        debug!("failed to connect to {}: {}", socket_addr, e);
+                        false
+                    }
+                })
+            })
+            .collect::<FuturesUnordered<_>>();
+
+        warmups.filter(|connected| future::ready(*connected)).count()
+    }
+
+    /// Adds a NameServer to the pool, it is used by requests sent after this returns
+    ///
+    /// This is shared by all clones of the pool. Returns false, leaving the pool unchanged, if
+    ///  there is already a NameServer with the same address and protocol, or an error if the
+    ///  config is invalid.
+    pub fn add_server(&self, config: NameServerConfig) -> Result<bool, ResolveError> {
+        let conns = if config.protocol.is_datagram() {
+            &self.datagram_conns
+        } else {
+            &self.stream_conns
+        };
+
+        let mut conns = conns.write();
+        if conns.iter().any(|conn| {
+            conn.socket_addr() == config.socket_addr && conn.protocol() == config.protocol
+        }) {
+            return Ok(false);
+        }
+
+        let name_server = NameServer::new_with_provider(
+            config,
+            self.options.clone(),
+            self.conn_provider.clone(),
+        )?;
+        name_server.set_state_changes(self.state_changes.read().clone());
+        *conns = conns.iter().cloned().chain(Some(name_server)).collect();
+        Ok(true)
+    }
+
+    /// Removes the NameServers at `socket_addr` from the pool, for all protocols
+    ///
+    /// This is shared by all clones of the pool. Requests in flight to the removed NameServers
+    ///  are left to complete, the stats of the remaining NameServers are kept. Returns false if
+    ///  there was no NameServer at the address.
+    pub fn remove_server(&self, socket_addr: SocketAddr) -> bool {
+        let mut removed = false;
+        for conns in &[&self.datagram_conns, &self.stream_conns] {
+            let mut conns = conns.write();
+            if conns.iter().any(|conn| conn.socket_addr() == socket_addr) {
+                *conns = conns
+                    .iter()
+                    .filter(|conn| conn.socket_addr() != socket_addr)
+                    .cloned()
+                    .collect();
+                removed = true;
+            }
+        }
+
+        removed
+    }
+
+    /// Forwards requests for names in the `zone`, and its subdomains, to the `pool`
+    ///
+    /// This is shared by all clones of the pool, and replaces any pool already routed for the
+    ///  zone. The zone with the longest match of the name is used, requests for names outside of
+    ///  all of the zones are sent to the NameServers of this pool.
+    pub fn add_route(&self, mut zone: Name, pool: NameServerPool<C, P>) {
+        zone.set_fqdn(true);
+        self.routes.write().insert(zone, pool);
+    }
+
+    /// The pool of the zone with the longest match of the name of the request, if any
+    fn route(&self, request: &DnsRequest) -> Option<NameServerPool<C, P>> {
+        let routes = self.routes.read();
+        if routes.is_empty() {
+            return None;
+        }
+
+        let mut name = request.queries().first()?.name().clone();
+        name.set_fqdn(true);
+        loop {
+            if let Some(pool) = routes.get(&name) {
+                return Some(pool.clone());
+            }
+
+            if name.is_root() {
+                return None;
+            }
+            name = name.base_name();
+        }
+    }
+
+    fn datagram_conns(&self) -> Arc<[NameServer<C, P>]> {
+        Arc::clone(&self.datagram_conns.read())
+    }
+
+    fn stream_conns(&self) -> Arc<[NameServer<C, P>]> {
+        Arc::clone(&self.stream_conns.read())
+    }
+
+    /// Stops accepting requests, the returned future completes once the requests already in flight
+    ///  have completed, or after `timeout`
+    ///
+    /// Requests sent after this fail, the connections are left for the requests in flight to
+    ///  complete, they are closed once the pool is dropped. The future resolves to false if
+    ///  requests were still in flight at the deadline.
+    pub fn shutdown(&self, timeout: Duration) -> impl Future<Output = bool> + Send + 'static {
+        self.closed.store(true, atomic::Ordering::Release);
+
+        let in_flight = Arc::clone(&self.in_flight);
+        let clock = Arc::clone(&self.clock);
+        let deadline = clock.now() + timeout;
+        async move {
+            while in_flight.load(atomic::Ordering::Acquire) > 0 {
+                if clock.now() >= deadline {
+                    return false;
+                }
+
+                P::Time::delay_for(DRAIN_INTERVAL).await;
+            }
+
+            true
         }
     }
 
+    /// Replaces the clock deadlines and the latency of responses are read from
+    #[cfg(test)]
+    pub(crate) fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// True once the pool has been shut down, see [`Self::shutdown`]
+    pub fn is_shutdown(&self) -> bool {
+        self.closed.load(atomic::Ordering::Acquire)
+    }
+
+    /// Sends the request to the best of the conns, returning the response along with the name
+    ///  server which gave it
+    ///
+    /// The name server at `preferred` is tried first, this is the one which truncated the response
+    ///  when falling back from datagram to stream connections. No name servers are tried past
+    ///  the `deadline`.
     async fn try_send(
         opts: ResolverOpts,
         conns: Arc<[NameServer<C, P>]>,
         request: DnsRequest,
-    ) -> Result<DnsResponse, ResolveError> {
+        rotation: usize,
+        preferred: Option<SocketAddr>,
+        deadline: Option<Instant>,
+        clock: Arc<dyn Clock>,
+    ) -> Result<(DnsResponse, ResponseMeta), ResolveError> {
         let mut conns: Vec<NameServer<C, P>> = conns.to_vec();
 
-        // select the highest priority connection
-        //   reorder the connections based on current view...
-        //   this reorders the inner set
-        conns.sort_unstable();
+        // name servers which continue to fail are skipped, so long as there are others to use,
+        //  until one of them has cooled down and this request claims probing it
+        let mut probe = None;
+        if conns.iter().any(|conn| !conn.is_tripped(opts.failure_threshold)) {
+            let (healthy, tripped) = conns
+                .into_iter()
+                .partition::<Vec<_>, _>(|conn| !conn.is_tripped(opts.failure_threshold));
+            conns = healthy;
+            probe = tripped.into_iter().find_map(|conn| {
+                conn.try_probe(opts.failure_cooldown)
+                    .map(|claim| (conn, claim))
+            });
+        }
+
+        // the deprecated `rotate` option is the `RoundRobin` strategy
+        #[allow(deprecated)]
+        let server_ordering = if opts.rotate {
+            ServerOrderingStrategy::RoundRobin
+        } else {
+            opts.server_ordering
+        };
+
+        // orders the connections, counting those ranked equally with the first, with differing
+        //  weights the first is chosen from them in proportion to their weights, the others remain
+        //  in order as fallbacks
+        let equals = match server_ordering {
+            ServerOrderingStrategy::QueryStats => {
+                // select the highest priority connection
+                //   reorder the connections based on current view...
+                //   this reorders the inner set, the highest ranked connection is tried first
+                conns.sort_unstable_by(|a, b| b.cmp(a));
+                conns
+                    .iter()
+                    .take_while(|conn| conn.cmp_rank(&conns[0]) == Ordering::Equal)
+                    .count()
+            }
+            // the connections are already in the order of the configuration
+            ServerOrderingStrategy::Sequential => 0,
+            ServerOrderingStrategy::RoundRobin => {
+                // all connections which have not failed are equal, take turns starting with each
+                conns.sort_by_key(NameServer::is_failed);
+                let healthy = conns.iter().filter(|conn| !conn.is_failed()).count();
+                if healthy > 1 {
+                    conns[..healthy].rotate_left(rotation % healthy);
+                }
+                healthy
+            }
+            ServerOrderingStrategy::Random => {
+                conns.shuffle(&mut rand::thread_rng());
+                conns.sort_by_key(NameServer::is_failed);
+                0
+            }
+        };
+        let equals = &mut conns[..equals];
+        if equals.windows(2).any(|pair| pair[0].weight() != pair[1].weight()) {
+            select_weighted(equals, rotation);
+        }
+
+        if let Some(preferred) = preferred {
+            if let Some(chosen) = conns.iter().position(|conn| conn.socket_addr() == preferred) {
+                conns[..=chosen].rotate_right(1);
+            }
+        }
+
+        // the probe goes first, a response re-admits it while the others remain as fallbacks, the
+        //  claim is held until the request completes
+        let _probe = probe.map(|(conn, claim)| {
+            conns.insert(0, conn);
+            claim
+        });
+
+        // the ranking of each candidate is only gathered when it is logged
+        if log_enabled!(log::Level::Trace) {
+            for (position, conn) in conns.iter().enumerate() {
+                trace!(
+                    "name server candidate: position={} ordering={:?} {}",
+                    position,
+                    server_ordering,
+                    conn.rank()
+                );
+            }
+        }
         let request_loop = request.clone();
 
-        parallel_conn_loop(conns, request_loop, opts).await
+        parallel_conn_loop(conns, request_loop, opts, deadline, clock).await
     }
-}
 
-impl<C, P> DnsHandle for NameServerPool<C, P>
-where
-    C: DnsHandle<Error = ResolveError> + Sync + 'static,
-    P: ConnectionProvider<Conn = C> + 'static,
-{
-    type Response = Pin<Box<dyn Future<Output = Result<DnsResponse, ResolveError>> + Send>>;
-    type Error = ResolveError;
+    /// A pool of the name servers at the addresses, e.g. those of a referral to another zone
+    ///
+    /// The name servers are reached on the port of the name servers of this pool.
+    pub(crate) fn for_referral(&self, ips: &[IpAddr]) -> Result<Self, ResolveError> {
+        let port = self
+            .datagram_conns()
+            .iter()
+            .chain(self.stream_conns().iter())
+            .next()
+            .map_or(53, |conn| conn.socket_addr().port());
 
-    fn send<R: Into<DnsRequest>>(&mut self, request: R) -> Self::Response {
-        let opts = self.options;
-        let request = request.into();
-        let datagram_conns = Arc::clone(&self.datagram_conns);
-        let stream_conns = Arc::clone(&self.stream_conns);
+        let name_servers = NameServerConfigGroup::from_ips_dual(ips, port, false);
+        let config = ResolverConfig::from_parts(None, vec![], name_servers);
+
+        Self::from_config_with_provider(&config, &self.options, self.conn_provider.clone())
+    }
+
+    /// Sends the request, minimizing the name when enabled, see `ResolverOpts::qname_minimization`
+    ///
+    /// Requests for names in a routed zone are sent to the pool of the zone, see `add_route`
+    fn send_query(&mut self, request: DnsRequest) -> ResponseFuture {
+        if !self.is_shutdown() {
+            if let Some(mut pool) = self.route(&request) {
+                debug!("forwarding request: {:?}", request.queries());
+                // counted here as well, shutdown waits for the forwarded requests
+                let in_flight = InFlight::new(&self.in_flight);
+                let response = pool.send_query(request);
+                return Box::pin(async move {
+                    let _in_flight = in_flight;
+                    response.await
+                });
+            }
+        }
+
+        if self.options.qname_minimization {
+            Box::pin(qname_minimization::send(self.clone(), request))
+        } else {
+            self.send_request(request)
+        }
+    }
+
+    /// Sends the request, unless an identical query is already in flight, in which case its
+    ///  response is shared
+    ///
+    /// Under load, many lookups of a popular name may be in flight at once, this sends only one
+    ///  of them to the name servers. Each request receives the response with its own id, and the
+    ///  name in the case it was asked with.
+    fn send_shared(&mut self, request: DnsRequest) -> ResponseFuture {
+        let key = match QueryKey::from_request(&request) {
+            Some(key) if !self.is_shutdown() => key,
+            _ => return self.send_query(request),
+        };
+        let id = request.id();
+        let name = request.queries()[0].name().clone();
+
+        let queries = Arc::clone(&self.queries);
+        let (response, token) = {
+            let mut queries = queries.lock();
+            let (response, token) = queries
+                .entry(key.clone())
+                .or_insert_with(|| (self.send_query(request).shared(), Arc::new(())));
+            (response.clone(), Arc::clone(token))
+        };
+
+        let waiting = Waiting {
+            queries,
+            key,
+            token,
+        };
+        Box::pin(async move {
+            let response = response.await;
+            waiting.release(true);
+            response.map(|(mut response, meta)| {
+                for message in response.messages_mut() {
+                    answer_shared(message, id, &name);
+                }
+                (response, meta)
+            })
+        })
+    }
+
+    /// Sends the request to the NameServers, this is `send` without the observer
+    pub(crate) fn send_request(&mut self, request: DnsRequest) -> ResponseFuture {
+        self.send_request_before(request, None)
+    }
+
+    /// Sends the request to the NameServers, trying no more of them once the deadline passes
+    fn send_request_before(
+        &mut self,
+        request: DnsRequest,
+        deadline: Option<Instant>,
+    ) -> ResponseFuture {
+        if self.is_shutdown() {
+            return Box::pin(future::err(ResolveError::from("name server pool is shut down")));
+        }
+
+        // counted until the request completes, or is dropped
+        let in_flight = InFlight::new(&self.in_flight);
+        let opts = self.options.clone();
+        let rotation = self.rotation.fetch_add(1, atomic::Ordering::Relaxed);
+        let datagram_conns = self.datagram_conns();
+        let stream_conns = self.stream_conns();
         // TODO: remove this clone, return the Message in the error?
         let tcp_message = request.clone();
+        let clock = Arc::clone(&self.clock);
 
         // if it's a .local. query, then we *only* query mDNS, these should never be sent on to upstream resolvers
         #[cfg(feature = "mdns")]
-        let mdns = mdns::maybe_local(&mut self.mdns_conns, request);
+        let mdns = mdns::maybe_local(&mut self.mdns_conns, request, &self.clock);
 
         // TODO: limited to only when mDNS is enabled, but this should probably always be enforced?
         #[cfg(not(feature = "mdns"))]
@@ -223,7 +712,11 @@ where
 
         // local queries are queried through mDNS
         if mdns.is_local() {
-            return mdns.take_future();
+            let local = mdns.take_future();
+            return Box::pin(async move {
+                let _in_flight = in_flight;
+                local.await
+            });
         }
 
         // TODO: should we allow mDNS to be used for standard lookups as well?
@@ -232,21 +725,31 @@ where
         let request = mdns.take_request();
 
         Box::pin(async move {
+            let _in_flight = in_flight;
             debug!("sending request: {:?}", request.queries());
 
             // First try the UDP connections
-            let udp_res = Self::try_send(opts, datagram_conns, request).await;
-
-            let udp_res = match udp_res {
+            let udp_res = Self::try_send(
+                opts.clone(),
+                datagram_conns,
+                request,
+                rotation,
+                None,
+                deadline,
+                Arc::clone(&clock),
+            )
+            .await;
+
+            let (truncated_by, udp_res) = match udp_res {
                 // handling promotion from datagram to stream base on truncation in message
-                Ok(response) if !response.truncated() => {
-                    return Ok(response);
+                Ok((response, meta)) if !response.truncated() => {
+                    return Ok((response, meta));
                 }
-                Ok(response) => {
+                Ok((response, meta)) => {
                     debug!("truncated response received, continuing to TCP");
-                    Ok(response)
+                    (Some(meta.socket_addr()), Ok((response, meta)))
                 }
-                Err(e) => Err(e),
+                Err(e) => (None, Err(e)),
             };
 
             // no TCP connections available
@@ -255,16 +758,25 @@ where
             }
 
             // UDP failed trying TCP connections
-            let tcp_res = Self::try_send(opts, stream_conns, tcp_message).await;
+            let tcp_res = Self::try_send(
+                opts,
+                stream_conns,
+                tcp_message,
+                rotation,
+                truncated_by,
+                deadline,
+                clock,
+            )
+            .await;
 
             let tcp_err = match tcp_res {
-                res @ Ok(..) => return res,
+                Ok(sent) => return Ok(sent),
                 Err(e) => e,
             };
 
             // Even if the UDP result was truncated, return that
             let udp_err = match udp_res {
-                Ok(response) => return Ok(response),
+                Ok(sent) => return Ok(sent),
                 Err(e) => e,
             };
 
@@ -274,15 +786,222 @@ where
             }
         })
     }
+
+    /// Sends the request as `send` does, the response is returned along with the NameServer which
+    ///  gave it
+    ///
+    /// The NameServer is the one which answered the request, i.e. the last one tried after
+    ///  failures, or a stream NameServer after a truncated response.
+    pub fn send_with_meta<R: Into<DnsRequest>>(
+        &mut self,
+        request: R,
+    ) -> Pin<Box<dyn Future<Output = Result<(DnsResponse, ResponseMeta), ResolveError>> + Send>> {
+        self.observe(request.into(), Self::send_shared)
+    }
+
+    /// Sends the request as `send` does, within an overall deadline, e.g. of the whole resolution
+    ///
+    /// Each attempt is given at most the time left before the deadline, rather than the full
+    ///  `ResolverOpts::timeout`, and no further name servers are tried once it has passed, in
+    ///  which case this fails with `ResolveErrorKind::Timeout`. Unlike `send`, the response is
+    ///  not shared with identical queries in flight, which may be given longer.
+    pub fn send_with_deadline<R: Into<DnsRequest>>(
+        &mut self,
+        request: R,
+        deadline: Instant,
+    ) -> Pin<Box<dyn Future<Output = Result<DnsResponse, ResolveError>> + Send>> {
+        // requests for names in a routed zone are sent to the pool of the zone
+        let response = self.observe(request.into(), move |this, request| {
+            match this.route(&request) {
+                Some(mut pool) if !this.is_shutdown() => {
+                    pool.send_request_before(request, Some(deadline))
+                }
+                _ => this.send_request_before(request, Some(deadline)),
+            }
+        });
+
+        Box::pin(response.map(|sent| sent.map(|(response, _)| response)))
+    }
+
+    /// Sends the request with `send`, reporting it to the observer, if there is one
+    fn observe<F>(&mut self, request: DnsRequest, send: F) -> ResponseFuture
+    where
+        F: FnOnce(&mut Self, DnsRequest) -> ResponseFuture,
+    {
+        let observer = match self.observer {
+            Some(ref observer) => Arc::clone(observer),
+            None => return send(self, request),
+        };
+
+        observer.on_query_start(&request);
+        let clock = Arc::clone(&self.clock);
+        let started = clock.now();
+        let response = send(self, request);
+
+        Box::pin(async move {
+            let response = response.await;
+            let elapsed = clock.now().saturating_duration_since(started);
+            match response {
+                Ok(..) => observer.on_query_success(elapsed),
+                Err(ref error) => observer.on_query_failure(error),
+            }
+            response
+        })
+    }
+}
+
+impl<C, P> DnsHandle for NameServerPool<C, P>
+where
+    C: DnsHandle<Error = ResolveError> + Sync + 'static,
+    P: ConnectionProvider<Conn = C> + 'static,
+{
+    type Response = Pin<Box<dyn Future<Output = Result<DnsResponse, ResolveError>> + Send>>;
+    type Error = ResolveError;
+
+    fn send<R: Into<DnsRequest>>(&mut self, request: R) -> Self::Response {
+        Box::pin(
+            self.send_with_meta(request)
+                .map(|sent| sent.map(|(response, _)| response)),
+        )
+    }
+}
+
+/// The queries which share their responses, see `NameServerPool::send_shared`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct QueryKey {
+    query: Query,
+    checking_disabled: bool,
+    dnssec_ok: bool,
+}
+
+impl QueryKey {
+    /// The key of a request with a single query, None for requests which are never shared
+    fn from_request(request: &DnsRequest) -> Option<Self> {
+        let options = request.options();
+        if request.op_code() != OpCode::Query
+            || request.queries().len() != 1
+            || options.expects_multiple_responses
+            || options.forward_hops.is_some()
+        {
+            return None;
+        }
+
+        Some(QueryKey {
+            query: request.queries()[0].clone(),
+            checking_disabled: request.checking_disabled() || options.checking_disabled,
+            dnssec_ok: request.edns().map_or(false, Edns::dnssec_ok),
+        })
+    }
+}
+
+/// Rewrites a shared response for one of the requests awaiting it, with the id of that request and
+///  the name of its query, which may differ in case from the name which was sent
+fn answer_shared(message: &mut Message, id: u16, name: &Name) {
+    message.set_id(id);
+
+    let sent = match message.queries().first() {
+        Some(query) if query.name() == name => query.name().clone(),
+        _ => return,
+    };
+    for query in message.queries_mut() {
+        query.set_name(name.clone());
+    }
+    for record in message.answers_mut() {
+        if record.name().eq_case(&sent) {
+            record.set_name(name.clone());
+        }
+    }
+}
+
+/// A request awaiting a shared response
+///
+/// The query is no longer shared once it is done, or once all the requests awaiting it are
+///  dropped, which also drops the request to the name servers.
+struct Waiting {
+    queries: SharedQueries,
+    key: QueryKey,
+    token: Arc<()>,
+}
+
+impl Waiting {
+    /// Stops sharing the query if it is `done`, or if this is the last request awaiting it
+    fn release(&self, done: bool) {
+        let mut queries = self.queries.lock();
+        let remove = match queries.get(&self.key) {
+            // the token is held by the entry and each request awaiting it
+            Some((_, token)) if Arc::ptr_eq(token, &self.token) => {
+                done || Arc::strong_count(token) == 2
+            }
+            // a later query, this one was already done
+            _ => false,
+        };
+
+        if remove {
+            queries.remove(&self.key);
+        }
+    }
+}
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        self.release(false);
+    }
+}
+
+/// A request in flight on the pool, this is counted until dropped
+struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    fn new(in_flight: &Arc<AtomicUsize>) -> Self {
+        in_flight.fetch_add(1, atomic::Ordering::AcqRel);
+        InFlight(Arc::clone(in_flight))
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, atomic::Ordering::AcqRel);
+    }
+}
+
+/// Moves a connection to the front, each is chosen for `rotation`s in proportion to its weight
+fn select_weighted<C, P>(conns: &mut [NameServer<C, P>], rotation: usize)
+where
+    C: DnsHandle<Error = ResolveError> + 'static,
+    P: ConnectionProvider<Conn = C> + 'static,
+{
+    let total: usize = conns.iter().map(|conn| usize::from(conn.weight())).sum();
+    if total == 0 {
+        return;
+    }
+
+    let mut slot = rotation % total;
+    let chosen = conns.iter().position(|conn| {
+        let weight = usize::from(conn.weight());
+        if slot < weight {
+            return true;
+        }
+        slot -= weight;
+        false
+    });
+
+    if let Some(chosen) = chosen {
+        conns[..=chosen].rotate_right(1);
+    }
 }
 
 // TODO: we should be able to have a self-referential future here with Pin and not require cloned conns
 /// An async function that will loop over all the conns with a max parallel request count of ops.num_concurrent_req
+///
+/// Each request is limited to the time left before the `deadline`, if there is one, no more
+///  requests are sent once it has passed.
 async fn parallel_conn_loop<C, P>(
     mut conns: Vec<NameServer<C, P>>,
     request: DnsRequest,
     opts: ResolverOpts,
-) -> Result<DnsResponse, ResolveError>
+    deadline: Option<Instant>,
+    clock: Arc<dyn Clock>,
+) -> Result<(DnsResponse, ResponseMeta), ResolveError>
 where
     C: DnsHandle<Error = ResolveError> + 'static,
     P: ConnectionProvider<Conn = C> + 'static,
@@ -304,6 +1023,14 @@ where
     loop {
         let request_cont = request.clone();
 
+        // the deadline cuts off any further attempts
+        let now = clock.now();
+        let remaining = deadline.map(|deadline| deadline.saturating_duration_since(now));
+        if remaining == Some(Duration::from_secs(0)) {
+            debug!("deadline passed, not trying further name servers");
+            return Err(ResolveErrorKind::Timeout.into());
+        }
+
         // construct the parallel requests, 2 is the default
         let mut par_conns = SmallVec::<[NameServer<C, P>; 2]>::new();
         let count = conns.len().min(opts.num_concurrent_reqs.max(1));
@@ -321,11 +1048,27 @@ where
             return Err(err);
         }
 
+        let conns_clock = Arc::clone(&clock);
         let mut requests = par_conns
             .into_iter()
             .map(move |mut conn| {
-                conn.send(request_cont.clone())
-                    .map(|result| result.map_err(|e| (conn, e)))
+                if let Some(remaining) = remaining {
+                    conn.limit_timeout(remaining);
+                }
+                debug!("selected name server: {}", conn.rank());
+                let socket_addr = conn.socket_addr();
+                let protocol = conn.protocol();
+                let clock = Arc::clone(&conns_clock);
+                let sent_at = clock.now();
+                conn.send(request_cont.clone()).map(move |result| {
+                    result
+                        .map(|response| {
+                            let latency = clock.now().saturating_duration_since(sent_at);
+                            let meta = ResponseMeta::new(socket_addr, protocol, latency);
+                            (response, meta)
+                        })
+                        .map_err(|e| (conn, e))
+                })
             })
             .collect::<FuturesUnordered<_>>();
 
@@ -359,7 +1102,11 @@ mod mdns {
     use proto::DnsHandle;
 
     /// Returns true
-    pub fn maybe_local<C, P>(name_server: &mut NameServer<C, P>, request: DnsRequest) -> Local
+    pub fn maybe_local<C, P>(
+        name_server: &mut NameServer<C, P>,
+        request: DnsRequest,
+        clock: &Arc<dyn Clock>,
+    ) -> Local
     where
         C: DnsHandle<Error = ResolveError> + 'static,
         P: ConnectionProvider<Conn = C> + 'static,
@@ -370,7 +1117,17 @@ mod mdns {
             .iter()
             .any(|query| usage::LOCAL.name().zone_of(query.name()))
         {
-            Local::ResolveFuture(name_server.send(request))
+            let socket_addr = name_server.socket_addr();
+            let protocol = name_server.protocol();
+            let clock = Arc::clone(clock);
+            let sent_at = clock.now();
+            Local::ResolveFuture(Box::pin(name_server.send(request).map(move |result| {
+                result.map(|response| {
+                    let latency = clock.now().saturating_duration_since(sent_at);
+                    let meta = ResponseMeta::new(socket_addr, protocol, latency);
+                    (response, meta)
+                })
+            })))
         } else {
             Local::NotMdns(request)
         }
@@ -379,7 +1136,7 @@ mod mdns {
 
 pub enum Local {
     #[allow(dead_code)]
-    ResolveFuture(Pin<Box<dyn Future<Output = Result<DnsResponse, ResolveError>> + Send>>),
+    ResolveFuture(ResponseFuture),
     NotMdns(DnsRequest),
 }
 
@@ -393,9 +1150,7 @@ impl Local {
     /// # Panics
     ///
     /// Panics if this is in fact a Local::NotMdns
-    fn take_future(
-        self,
-    ) -> Pin<Box<dyn Future<Output = Result<DnsResponse, ResolveError>> + Send>> {
+    fn take_future(self) -> ResponseFuture {
         match self {
             Local::ResolveFuture(future) => future,
             _ => panic!("non Local queries have no future, see take_message()"),
@@ -416,7 +1171,7 @@ impl Local {
 }
 
 impl Future for Local {
-    type Output = Result<DnsResponse, ResolveError>;
+    type Output = Result<(DnsResponse, ResponseMeta), ResolveError>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
         match *self {
@@ -432,78 +1187,1124 @@ impl Future for Local {
 mod tests {
     use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
     use std::str::FromStr;
+    use std::time::Instant;
 
     use tokio::runtime::Runtime;
 
-    use proto::op::Query;
-    use proto::rr::{Name, RecordType};
-    use proto::xfer::{DnsHandle, DnsRequestOptions};
+    use proto::op::{Message, Query};
+    use proto::rr::{Name, RData, Record, RecordType};
+    use proto::xfer::{DnsHandle, DnsRequestOptions, RetryDnsHandle};
+    use proto::TokioTime;
 
     use super::*;
-    use crate::config::NameServerConfig;
+    use crate::clock::MockClock;
     use crate::config::Protocol;
+    use crate::name_server::name_server::tests::{config, RecordingHandle, RecordingProvider};
+    use crate::name_server::name_server_stats::MIN_RETRY_DELAY;
+    use crate::name_server::{ConnectionState, StateChange};
 
-    #[ignore]
-    // because of there is a real connection that needs a reasonable timeout
     #[test]
-    fn test_failed_then_success_pool() {
-        let config1 = NameServerConfig {
-            socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 252)), 253),
-            protocol: Protocol::Udp,
-            tls_dns_name: None,
-            trust_nx_responses: false,
-            #[cfg(feature = "dns-over-rustls")]
-            tls_config: None,
-        };
-
-        let config2 = NameServerConfig {
-            socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), 53),
-            protocol: Protocol::Udp,
-            tls_dns_name: None,
-            trust_nx_responses: false,
-            #[cfg(feature = "dns-over-rustls")]
-            tls_config: None,
-        };
-
+    fn test_stats() {
         let mut resolver_config = ResolverConfig::new();
-        resolver_config.add_name_server(config1);
-        resolver_config.add_name_server(config2);
+        resolver_config.add_name_server(NameServerConfig {
+            protocol: Protocol::Tcp,
+            ..config(53)
+        });
+        resolver_config.add_name_server(config(53));
 
-        let io_loop = Runtime::new().unwrap();
-        let mut pool = NameServerPool::<_, TokioConnectionProvider>::from_config(
+        let pool = NameServerPool::<_, TokioConnectionProvider>::from_config(
             &resolver_config,
             &ResolverOpts::default(),
             TokioHandle,
-        );
+        )
+        .unwrap();
+
+        let stats = pool.stats();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].protocol(), Protocol::Udp);
+        assert_eq!(stats[1].protocol(), Protocol::Tcp);
+        assert!(stats.iter().all(|s| s.successes() == 0 && s.failures() == 0));
+    }
 
-        let name = Name::parse("www.example.com.", None).unwrap();
+    /// The index of the name server which each of the lookups is sent to first, the name server
+    ///  at `failed` has failed before the first lookup, and since backed off
+    fn selected_name_servers(
+        server_ordering: ServerOrderingStrategy,
+        failed: Option<usize>,
+        lookups: usize,
+    ) -> Vec<usize> {
+        let mut opts = ResolverOpts::default();
+        opts.server_ordering = server_ordering;
+        opts.num_concurrent_reqs = 1;
+
+        let handles: Vec<_> = (0..3).map(|_| RecordingHandle::new(Message::new())).collect();
+        let name_servers: Vec<_> = handles
+            .iter()
+            .zip(53..)
+            .map(|(handle, port)| {
+                NameServer::new_with_provider(
+                    config(port),
+                    opts.clone(),
+                    RecordingProvider(handle.clone()),
+                )
+                .unwrap()
+            })
+            .collect();
 
-        // TODO: it's not clear why there are two failures before the success
-        for i in 0..2 {
-            assert!(
-                io_loop
-                    .block_on(pool.lookup(
-                        Query::query(name.clone(), RecordType::A),
-                        DnsRequestOptions::default()
-                    ))
-                    .is_err(),
-                "iter: {}",
-                i
+        if let Some(failed) = failed {
+            name_servers[failed].fail(Instant::now() - Duration::from_secs(10));
+        }
+
+        let conn_provider = RecordingProvider(RecordingHandle::new(Message::new()));
+        let mut pool = NameServerPool::from_nameservers_test(
+            &opts,
+            Arc::from(name_servers),
+            Arc::from([]),
+            #[cfg(feature = "mdns")]
+            name_server::mdns_nameserver(opts.clone(), conn_provider.clone(), false),
+            conn_provider,
+        );
+
+        let io_loop = Runtime::new().unwrap();
+        let name = Name::from_str("www.example.com.").unwrap();
+        let request_count = |handle: &RecordingHandle| handle.requests.lock().unwrap().len();
+
+        let mut selected = Vec::new();
+        for _ in 0..lookups {
+            let before: Vec<_> = handles.iter().map(request_count).collect();
+            io_loop
+                .block_on(pool.lookup(
+                    Query::query(name.clone(), RecordType::A),
+                    DnsRequestOptions::default(),
+                ))
+                .expect("lookup failed");
+
+            let index = handles
+                .iter()
+                .zip(before)
+                .position(|(handle, before)| request_count(handle) > before)
+                .expect("no name server was used");
+            selected.push(index);
+        }
+
+        selected
+    }
+
+    #[test]
+    fn test_rotate() {
+        let selected = selected_name_servers(ServerOrderingStrategy::RoundRobin, None, 6);
+        assert_eq!(selected, vec![0, 1, 2, 0, 1, 2]);
+
+        // the failed name server is skipped while the others have not failed
+        let selected = selected_name_servers(ServerOrderingStrategy::RoundRobin, Some(1), 4);
+        assert_eq!(selected, vec![0, 2, 0, 2]);
+    }
+
+    #[test]
+    fn test_server_ordering_query_stats() {
+        // the failed name server ranks below those which have not failed
+        let selected = selected_name_servers(ServerOrderingStrategy::QueryStats, Some(0), 6);
+        assert!(!selected.contains(&0));
+    }
+
+    #[test]
+    fn test_server_ordering_sequential() {
+        let selected = selected_name_servers(ServerOrderingStrategy::Sequential, None, 6);
+        assert_eq!(selected, vec![0; 6]);
+
+        // the order of the configuration is kept, even after a failure
+        let selected = selected_name_servers(ServerOrderingStrategy::Sequential, Some(0), 3);
+        assert_eq!(selected, vec![0; 3]);
+    }
+
+    #[test]
+    fn test_server_ordering_random() {
+        let selected = selected_name_servers(ServerOrderingStrategy::Random, Some(0), 40);
+
+        // the name servers which have not failed are chosen at random
+        assert!(!selected.contains(&0));
+        assert!(selected.contains(&1));
+        assert!(selected.contains(&2));
+    }
+
+    #[test]
+    fn test_failure_threshold() {
+        let mut opts = ResolverOpts::default();
+        opts.failure_threshold = 2;
+        opts.failure_cooldown = Duration::from_secs(5);
+
+        let handles: Vec<_> = (0..2).map(|_| RecordingHandle::new(Message::new())).collect();
+        let name_servers: Vec<_> = handles
+            .iter()
+            .zip(53..)
+            .map(|(handle, port)| {
+                NameServer::new_with_provider(
+                    config(port),
+                    opts.clone(),
+                    RecordingProvider(handle.clone()),
+                )
+                .unwrap()
+            })
+            .collect();
+
+        name_servers[0].fail(Instant::now());
+        assert!(!name_servers[0].is_tripped(opts.failure_threshold));
+        name_servers[0].fail(Instant::now());
+        assert!(name_servers[0].is_tripped(opts.failure_threshold));
+        assert!(name_servers[0].try_probe(opts.failure_cooldown).is_none());
+
+        // once it has cooled down, a single request at a time probes the name server
+        let probe = NameServer::new_with_provider(
+            config(55),
+            opts.clone(),
+            RecordingProvider(RecordingHandle::new(Message::new())),
+        )
+        .unwrap();
+        probe.fail(Instant::now() - Duration::from_secs(10));
+        probe.fail(Instant::now() - Duration::from_secs(10));
+        assert!(probe.is_tripped(opts.failure_threshold));
+        let claim = probe.try_probe(opts.failure_cooldown);
+        assert!(claim.is_some());
+        assert!(probe.try_probe(opts.failure_cooldown).is_none());
+        drop(claim);
+        assert!(probe.try_probe(opts.failure_cooldown).is_some());
+
+        let conn_provider = RecordingProvider(RecordingHandle::new(Message::new()));
+        let mut pool = NameServerPool::from_nameservers_test(
+            &opts,
+            Arc::from(name_servers),
+            Arc::from([]),
+            #[cfg(feature = "mdns")]
+            name_server::mdns_nameserver(opts.clone(), conn_provider.clone(), false),
+            conn_provider,
+        );
+
+        let io_loop = Runtime::new().unwrap();
+        let name = Name::from_str("www.example.com.").unwrap();
+        for _ in 0..4 {
+            io_loop
+                .block_on(pool.lookup(
+                    Query::query(name.clone(), RecordType::A),
+                    DnsRequestOptions::default(),
+                ))
+                .expect("lookup failed");
+        }
+
+        assert!(handles[0].requests.lock().unwrap().is_empty());
+        assert_eq!(handles[1].requests.lock().unwrap().len(), 4);
+
+        let stats = pool.stats();
+        assert_eq!(stats[0].failures(), 2);
+        assert_eq!(stats[0].state(), ConnectionState::Failed);
+
+        // the probe of a cooled down name server is sent to it first, its response re-admits it
+        let probed = RecordingHandle::new(Message::new());
+        let handles = [probed.clone(), RecordingHandle::new(Message::new())];
+        let name_servers: Vec<_> = handles
+            .iter()
+            .zip(53..)
+            .map(|(handle, port)| {
+                NameServer::new_with_provider(
+                    config(port),
+                    opts.clone(),
+                    RecordingProvider(handle.clone()),
+                )
+                .unwrap()
+            })
+            .collect();
+        name_servers[0].fail(Instant::now() - Duration::from_secs(10));
+        name_servers[0].fail(Instant::now() - Duration::from_secs(10));
+
+        let conn_provider = RecordingProvider(RecordingHandle::new(Message::new()));
+        let mut pool = NameServerPool::from_nameservers_test(
+            &opts,
+            Arc::from(name_servers),
+            Arc::from([]),
+            #[cfg(feature = "mdns")]
+            name_server::mdns_nameserver(opts.clone(), conn_provider.clone(), false),
+            conn_provider,
+        );
+        io_loop
+            .block_on(pool.lookup(
+                Query::query(name, RecordType::A),
+                DnsRequestOptions::default(),
+            ))
+            .expect("lookup failed");
+
+        assert_eq!(probed.requests.lock().unwrap().len(), 1);
+        assert_eq!(handles[1].requests.lock().unwrap().len(), 0);
+        assert_eq!(pool.stats()[0].state(), ConnectionState::Established);
+    }
+
+    #[test]
+    fn test_all_failed_returns_last_error() {
+        let opts = ResolverOpts::default();
+        let handles: Vec<_> = (0..2)
+            .map(|_| RecordingHandle::failing(ResolveErrorKind::Timeout.into()))
+            .collect();
+        let name_servers: Vec<_> = handles
+            .iter()
+            .zip(53..)
+            .map(|(handle, port)| {
+                NameServer::new_with_provider(
+                    config(port),
+                    opts.clone(),
+                    RecordingProvider(handle.clone()),
+                )
+                .unwrap()
+            })
+            .collect();
+
+        let conn_provider = RecordingProvider(RecordingHandle::new(Message::new()));
+        let mut pool = NameServerPool::from_nameservers_test(
+            &opts,
+            Arc::from(name_servers),
+            Arc::from([]),
+            #[cfg(feature = "mdns")]
+            name_server::mdns_nameserver(opts.clone(), conn_provider.clone(), false),
+            conn_provider,
+        );
+
+        let io_loop = Runtime::new().unwrap();
+        let name = Name::from_str("www.example.com.").unwrap();
+        let mut lookup = || {
+            io_loop
+                .block_on(pool.lookup(
+                    Query::query(name.clone(), RecordType::A),
+                    DnsRequestOptions::default(),
+                ))
+                .expect_err("lookup should fail")
+        };
+
+        assert!(matches!(lookup().kind(), ResolveErrorKind::Timeout));
+
+        // every name server is now backing off, the error they last failed with is returned
+        let error = lookup();
+        assert!(matches!(error.kind(), ResolveErrorKind::Timeout), "{}", error);
+        for handle in &handles {
+            assert_eq!(handle.requests.lock().unwrap().len(), 1);
+        }
+
+        let stats = pool.stats();
+        assert!(stats.iter().all(|s| s.state() == ConnectionState::Failed));
+    }
+
+    #[test]
+    fn test_attempts_fall_through_failed_servers() {
+        let mut opts = ResolverOpts::default();
+        opts.attempts = 1;
+        opts.num_concurrent_reqs = 1;
+
+        let handles = vec![
+            RecordingHandle::failing(ResolveErrorKind::Timeout.into()),
+            RecordingHandle::failing(ResolveErrorKind::Timeout.into()),
+            RecordingHandle::new(Message::new()),
+        ];
+        let name_servers: Vec<_> = handles
+            .iter()
+            .zip(53..)
+            .map(|(handle, port)| {
+                NameServer::new_with_provider(
+                    config(port),
+                    opts.clone(),
+                    RecordingProvider(handle.clone()),
+                )
+                .unwrap()
+            })
+            .collect();
+
+        let conn_provider = RecordingProvider(RecordingHandle::new(Message::new()));
+        let pool = NameServerPool::from_nameservers_test(
+            &opts,
+            Arc::from(name_servers),
+            Arc::from([]),
+            #[cfg(feature = "mdns")]
+            name_server::mdns_nameserver(opts.clone(), conn_provider.clone(), false),
+            conn_provider,
+        );
+        let mut client = RetryDnsHandle::new(pool.clone(), opts.attempts - 1);
+
+        let io_loop = Runtime::new().unwrap();
+        let name = Name::from_str("www.example.com.").unwrap();
+        io_loop
+            .block_on(client.lookup(
+                Query::query(name, RecordType::A),
+                DnsRequestOptions::default(),
+            ))
+            .expect("lookup failed");
+
+        // a single attempt tries each name server in turn, recording the failures
+        for handle in &handles {
+            assert_eq!(handle.requests.lock().unwrap().len(), 1);
+        }
+
+        let stats = pool.stats();
+        assert_eq!(stats[0].failures(), 1);
+        assert_eq!(stats[1].failures(), 1);
+        assert_eq!(stats[2].successes(), 1);
+        assert_eq!(stats[2].failures(), 0);
+    }
+
+    #[test]
+    fn test_send_with_meta() {
+        let mut opts = ResolverOpts::default();
+        opts.num_concurrent_reqs = 1;
+        opts.server_ordering = ServerOrderingStrategy::Sequential;
+
+        let failing = RecordingHandle::failing(ResolveErrorKind::Timeout.into());
+        let answering = RecordingHandle::new(Message::new());
+        let name_servers = vec![
+            NameServer::new_with_provider(
+                config(53),
+                opts.clone(),
+                RecordingProvider(failing.clone()),
+            )
+            .unwrap(),
+            NameServer::new_with_provider(
+                config(54),
+                opts.clone(),
+                RecordingProvider(answering.clone()),
+            )
+            .unwrap(),
+        ];
+
+        let conn_provider = RecordingProvider(RecordingHandle::new(Message::new()));
+        let mut pool = NameServerPool::from_nameservers_test(
+            &opts,
+            Arc::from(name_servers),
+            Arc::from([]),
+            #[cfg(feature = "mdns")]
+            name_server::mdns_nameserver(opts.clone(), conn_provider.clone(), false),
+            conn_provider,
+        );
+
+        let name = Name::from_str("www.example.com.").unwrap();
+        let mut message = Message::new();
+        message.add_query(Query::query(name, RecordType::A));
+        let request = DnsRequest::new(message, DnsRequestOptions::default());
+
+        let io_loop = Runtime::new().unwrap();
+        let (_, meta) = io_loop
+            .block_on(pool.send_with_meta(request))
+            .expect("send failed");
+
+        // the meta is that of the name server which answered, not the first one tried
+        assert_eq!(failing.requests.lock().unwrap().len(), 1);
+        assert_eq!(answering.requests.lock().unwrap().len(), 1);
+        assert_eq!(
+            meta.socket_addr(),
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 54)
+        );
+        assert_eq!(meta.protocol(), Protocol::Udp);
+    }
+
+    /// Never responds, its requests only complete by timing out
+    #[derive(Clone)]
+    struct HangingHandle(Arc<AtomicUsize>);
+
+    impl DnsHandle for HangingHandle {
+        type Response = future::Pending<Result<DnsResponse, ResolveError>>;
+        type Error = ResolveError;
+
+        fn send<R: Into<DnsRequest> + Unpin + Send + 'static>(&mut self, _: R) -> Self::Response {
+            self.0.fetch_add(1, atomic::Ordering::SeqCst);
+            future::pending()
+        }
+    }
+
+    #[derive(Clone)]
+    struct HangingProvider(HangingHandle);
+
+    impl ConnectionProvider for HangingProvider {
+        type Conn = HangingHandle;
+        type FutureConn = future::Ready<Result<HangingHandle, ResolveError>>;
+        type Time = TokioTime;
+
+        fn new_connection(&self, _: &NameServerConfig, _: &ResolverOpts) -> Self::FutureConn {
+            future::ok(self.0.clone())
+        }
+    }
+
+    #[test]
+    fn test_send_with_deadline() {
+        let mut opts = ResolverOpts::default();
+        opts.timeout = Duration::from_secs(5);
+        opts.num_concurrent_reqs = 1;
+        opts.server_ordering = ServerOrderingStrategy::Sequential;
+
+        let first = HangingHandle(Arc::new(AtomicUsize::new(0)));
+        let second = HangingHandle(Arc::new(AtomicUsize::new(0)));
+        let name_servers = vec![
+            NameServer::new_with_provider(config(53), opts.clone(), HangingProvider(first.clone()))
+                .unwrap(),
+            NameServer::new_with_provider(
+                config(54),
+                opts.clone(),
+                HangingProvider(second.clone()),
+            )
+            .unwrap(),
+        ];
+
+        let conn_provider = HangingProvider(HangingHandle(Arc::new(AtomicUsize::new(0))));
+        let mut pool = NameServerPool::from_nameservers_test(
+            &opts,
+            Arc::from(name_servers),
+            Arc::from([]),
+            #[cfg(feature = "mdns")]
+            name_server::mdns_nameserver(opts.clone(), conn_provider.clone(), false),
+            conn_provider,
+        );
+
+        let name = Name::from_str("www.example.com.").unwrap();
+        let mut message = Message::new();
+        message.add_query(Query::query(name, RecordType::A));
+        let request = DnsRequest::new(message, DnsRequestOptions::default());
+
+        let io_loop = Runtime::new().unwrap();
+        let started = Instant::now();
+        let deadline = started + Duration::from_millis(200);
+        let error = io_loop
+            .block_on(pool.send_with_deadline(request.clone(), deadline))
+            .expect_err("send should time out");
+
+        // the first name server is given only what is left of the deadline, rather than the
+        //  full timeout, and the second is never tried
+        assert!(matches!(error.kind(), ResolveErrorKind::Timeout));
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(first.0.load(atomic::Ordering::SeqCst), 1);
+        assert_eq!(second.0.load(atomic::Ordering::SeqCst), 0);
+
+        // the deadline is read from the clock of the pool, no name server is tried once it passed
+        let clock = MockClock::new();
+        pool.set_clock(Arc::new(clock.clone()));
+        let deadline = clock.now() + Duration::from_secs(60);
+        clock.advance(Duration::from_secs(60));
+        let error = io_loop
+            .block_on(pool.send_with_deadline(request, deadline))
+            .expect_err("send should time out");
+        assert!(matches!(error.kind(), ResolveErrorKind::Timeout));
+        assert_eq!(first.0.load(atomic::Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_shutdown() {
+        let config = config(53);
+
+        let opts = ResolverOpts::default();
+        let handle = RecordingHandle::new(Message::new());
+        let name_server =
+            NameServer::new_with_provider(config, opts.clone(), RecordingProvider(handle.clone()))
+                .unwrap();
+
+        let conn_provider = RecordingProvider(RecordingHandle::new(Message::new()));
+        let mut pool = NameServerPool::from_nameservers_test(
+            &opts,
+            Arc::from(vec![name_server]),
+            Arc::from([]),
+            #[cfg(feature = "mdns")]
+            name_server::mdns_nameserver(opts.clone(), conn_provider.clone(), false),
+            conn_provider,
+        );
+
+        let io_loop = Runtime::new().unwrap();
+        let name = Name::from_str("www.example.com.").unwrap();
+        let query = Query::query(name, RecordType::A);
+
+        let before = pool.lookup(query.clone(), DnsRequestOptions::default());
+        let abandoned = pool.lookup(query.clone(), DnsRequestOptions::default());
+
+        // requests in flight hold up the shutdown, until the deadline
+        assert!(!io_loop.block_on(pool.shutdown(Duration::from_millis(50))));
+        assert!(pool.is_shutdown());
+
+        let after = pool.lookup(query, DnsRequestOptions::default());
+        drop(abandoned);
+        let (before, drained) =
+            io_loop.block_on(future::join(before, pool.shutdown(Duration::from_secs(5))));
+
+        // the request sent before the shutdown completes, the one after is rejected
+        before.expect("request sent before the shutdown should complete");
+        assert!(drained);
+        assert!(io_loop.block_on(after).is_err());
+        assert_eq!(handle.requests.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_shared_queries() {
+        let config = config(53);
+
+        let opts = ResolverOpts::default();
+        let handle = RecordingHandle::new(Message::new());
+        let name_server =
+            NameServer::new_with_provider(config, opts.clone(), RecordingProvider(handle.clone()))
+                .unwrap();
+
+        let conn_provider = RecordingProvider(RecordingHandle::new(Message::new()));
+        let mut pool = NameServerPool::from_nameservers_test(
+            &opts,
+            Arc::from(vec![name_server]),
+            Arc::from([]),
+            #[cfg(feature = "mdns")]
+            name_server::mdns_nameserver(opts.clone(), conn_provider.clone(), false),
+            conn_provider,
+        );
+
+        let io_loop = Runtime::new().unwrap();
+        let name = Name::from_str("www.example.com.").unwrap();
+        let query = Query::query(name.clone(), RecordType::A);
+        let request_count = || handle.requests.lock().unwrap().len();
+
+        // the identical queries in flight at once are sent only once
+        let first = pool.lookup(query.clone(), DnsRequestOptions::default());
+        let second = pool.lookup(query.clone(), DnsRequestOptions::default());
+        let other = pool.lookup(
+            Query::query(name, RecordType::AAAA),
+            DnsRequestOptions::default(),
+        );
+        let (first, second, other) = io_loop.block_on(future::join3(first, second, other));
+        first.expect("first lookup failed");
+        second.expect("second lookup failed");
+        other.expect("other lookup failed");
+        assert_eq!(request_count(), 2);
+
+        // once done, the query is sent again
+        io_loop
+            .block_on(pool.lookup(query.clone(), DnsRequestOptions::default()))
+            .expect("lookup failed");
+        assert_eq!(request_count(), 3);
+
+        // an abandoned query is not shared
+        drop(pool.lookup(query.clone(), DnsRequestOptions::default()));
+        assert!(pool.queries.lock().is_empty());
+        io_loop
+            .block_on(pool.lookup(query, DnsRequestOptions::default()))
+            .expect("lookup failed");
+        assert_eq!(request_count(), 4);
+    }
+
+    #[test]
+    fn test_shared_query_ids() {
+        let config = config(53);
+
+        // the name server answers with the id and the case of the name sent first
+        let name = Name::from_str("www.example.com.").unwrap();
+        let mut message = Message::new();
+        message
+            .set_id(1)
+            .add_query(Query::query(name.clone(), RecordType::A))
+            .add_answer(Record::from_rdata(
+                name.clone(),
+                86400,
+                RData::A(Ipv4Addr::new(127, 0, 0, 1)),
+            ));
+
+        let opts = ResolverOpts::default();
+        let handle = RecordingHandle::new(message);
+        let name_server =
+            NameServer::new_with_provider(config, opts.clone(), RecordingProvider(handle.clone()))
+                .unwrap();
+
+        let conn_provider = RecordingProvider(RecordingHandle::new(Message::new()));
+        let mut pool = NameServerPool::from_nameservers_test(
+            &opts,
+            Arc::from(vec![name_server]),
+            Arc::from([]),
+            #[cfg(feature = "mdns")]
+            name_server::mdns_nameserver(opts.clone(), conn_provider.clone(), false),
+            conn_provider,
+        );
+
+        let request = |id: u16, name: &str| {
+            let mut message = Message::new();
+            message
+                .set_id(id)
+                .add_query(Query::query(Name::from_str(name).unwrap(), RecordType::A));
+            DnsRequest::new(message, DnsRequestOptions::default())
+        };
+
+        let io_loop = Runtime::new().unwrap();
+        let first = pool.send(request(1, "www.example.com."));
+        let second = pool.send(request(2, "WWW.Example.COM."));
+        let (first, second) = io_loop.block_on(future::join(first, second));
+        assert_eq!(handle.requests.lock().unwrap().len(), 1);
+
+        let first = first.expect("first lookup failed");
+        assert_eq!(first.id(), 1);
+        assert!(first.queries()[0].name().eq_case(&name));
+
+        // the second request receives the response as if it had been sent itself
+        let second = second.expect("second lookup failed");
+        let second_name = Name::from_str("WWW.Example.COM.").unwrap();
+        assert_eq!(second.id(), 2);
+        assert!(second.queries()[0].name().eq_case(&second_name));
+        assert!(second.answers()[0].name().eq_case(&second_name));
+    }
+
+    #[test]
+    fn test_add_remove_server() {
+        let mut opts = ResolverOpts::default();
+        opts.server_ordering = ServerOrderingStrategy::RoundRobin;
+        opts.num_concurrent_reqs = 1;
+
+        let first = RecordingHandle::new(Message::new());
+        let name_server = NameServer::new_with_provider(
+            config(53),
+            opts.clone(),
+            RecordingProvider(first.clone()),
+        )
+        .unwrap();
+
+        // added name servers connect through the pool's provider
+        let added = RecordingHandle::new(Message::new());
+        let conn_provider = RecordingProvider(added.clone());
+        let mut pool = NameServerPool::from_nameservers_test(
+            &opts,
+            Arc::from(vec![name_server]),
+            Arc::from([]),
+            #[cfg(feature = "mdns")]
+            name_server::mdns_nameserver(opts.clone(), conn_provider.clone(), false),
+            conn_provider,
+        );
+
+        assert!(pool.add_server(config(54)).unwrap());
+        assert!(!pool.add_server(config(54)).unwrap());
+        assert_eq!(pool.stats().len(), 2);
+
+        let io_loop = Runtime::new().unwrap();
+        let name = Name::from_str("www.example.com.").unwrap();
+        let lookup = |pool: &mut NameServerPool<_, _>| {
+            io_loop
+                .block_on(pool.lookup(
+                    Query::query(name.clone(), RecordType::A),
+                    DnsRequestOptions::default(),
+                ))
+                .expect("lookup failed");
+        };
+        let request_count = |handle: &RecordingHandle| handle.requests.lock().unwrap().len();
+
+        for _ in 0..4 {
+            lookup(&mut pool);
+        }
+        assert_eq!(request_count(&first), 2);
+        assert_eq!(request_count(&added), 2);
+
+        // the remaining name server keeps its stats, the removed one is no longer used
+        let removed = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 53);
+        assert!(pool.remove_server(removed));
+        assert!(!pool.remove_server(removed));
+
+        for _ in 0..2 {
+            lookup(&mut pool);
+        }
+        assert_eq!(request_count(&first), 2);
+        assert_eq!(request_count(&added), 4);
+
+        let stats = pool.stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].socket_addr().port(), 54);
+        assert_eq!(stats[0].successes(), 4);
+    }
+
+    #[test]
+    fn test_routes() {
+        let opts = ResolverOpts::default();
+        let pool = |handle: &RecordingHandle| {
+            let config = ResolverConfig::from_parts(
+                None,
+                vec![],
+                NameServerConfigGroup::from_ips_clear(&[IpAddr::from([127, 0, 0, 1])], 53, false),
             );
+            NameServerPool::from_config_with_provider(
+                &config,
+                &opts,
+                RecordingProvider(handle.clone()),
+            )
+            .unwrap()
+        };
+
+        let public = RecordingHandle::new(Message::new());
+        let internal = RecordingHandle::new(Message::new());
+        let dev = RecordingHandle::new(Message::new());
+
+        let mut default_pool = pool(&public);
+        default_pool.add_route(Name::from_str("company.internal").unwrap(), pool(&internal));
+        default_pool.add_route(Name::from_str("dev.company.internal.").unwrap(), pool(&dev));
+
+        let io_loop = Runtime::new().unwrap();
+        let mut lookup = |name: &str| {
+            io_loop
+                .block_on(default_pool.lookup(
+                    Query::query(Name::from_str(name).unwrap(), RecordType::A),
+                    DnsRequestOptions::default(),
+                ))
+                .expect("lookup failed");
+        };
+        let request_count = |handle: &RecordingHandle| handle.requests.lock().unwrap().len();
+
+        lookup("www.company.internal.");
+        lookup("COMPANY.internal.");
+        assert_eq!(request_count(&internal), 2);
+
+        // the longest match wins
+        lookup("db.dev.company.internal.");
+        assert_eq!(request_count(&dev), 1);
+        assert_eq!(request_count(&internal), 2);
+
+        // everything else falls through to the default name servers
+        lookup("www.example.com.");
+        lookup("notcompany.internal.");
+        assert_eq!(request_count(&public), 2);
+        assert_eq!(request_count(&internal), 2);
+        assert_eq!(request_count(&dev), 1);
+    }
+
+    #[test]
+    fn test_observer() {
+        #[derive(Default)]
+        struct CountingObserver {
+            starts: AtomicUsize,
+            successes: AtomicUsize,
+            failures: AtomicUsize,
+        }
+
+        impl ResolverObserver for CountingObserver {
+            fn on_query_start(&self, _: &DnsRequest) {
+                self.starts.fetch_add(1, atomic::Ordering::SeqCst);
+            }
+
+            fn on_query_success(&self, _: Duration) {
+                self.successes.fetch_add(1, atomic::Ordering::SeqCst);
+            }
+
+            fn on_query_failure(&self, _: &ResolveError) {
+                self.failures.fetch_add(1, atomic::Ordering::SeqCst);
+            }
+        }
+
+        let config = config(53);
+
+        let opts = ResolverOpts::default();
+        let handle = RecordingHandle::new(Message::new());
+        let name_server =
+            NameServer::new_with_provider(config, opts.clone(), RecordingProvider(handle)).unwrap();
+
+        let conn_provider = RecordingProvider(RecordingHandle::new(Message::new()));
+        let mut pool = NameServerPool::from_nameservers_test(
+            &opts,
+            Arc::from(vec![name_server]),
+            Arc::from([]),
+            #[cfg(feature = "mdns")]
+            name_server::mdns_nameserver(opts.clone(), conn_provider.clone(), false),
+            conn_provider,
+        );
+
+        let observer = Arc::new(CountingObserver::default());
+        pool.set_observer(Some(Arc::clone(&observer) as Arc<dyn ResolverObserver>));
+
+        let io_loop = Runtime::new().unwrap();
+        let name = Name::from_str("www.example.com.").unwrap();
+        let query = Query::query(name, RecordType::A);
+        for _ in 0..3 {
+            io_loop
+                .block_on(pool.lookup(query.clone(), DnsRequestOptions::default()))
+                .expect("lookup failed");
         }
 
-        for i in 0..10 {
-            assert!(
+        // requests rejected by the pool fail
+        io_loop.block_on(pool.shutdown(Duration::from_secs(1)));
+        assert!(io_loop
+            .block_on(pool.lookup(query, DnsRequestOptions::default()))
+            .is_err());
+
+        assert_eq!(observer.starts.load(atomic::Ordering::SeqCst), 4);
+        assert_eq!(observer.successes.load(atomic::Ordering::SeqCst), 3);
+        assert_eq!(observer.failures.load(atomic::Ordering::SeqCst), 1);
+    }
+
+    /// Connects with the handle, except to `unreachable` which fails to connect
+    #[derive(Clone)]
+    struct UnreachableProvider {
+        handle: RecordingHandle,
+        unreachable: IpAddr,
+    }
+
+    impl ConnectionProvider for UnreachableProvider {
+        type Conn = RecordingHandle;
+        type FutureConn = future::Ready<Result<RecordingHandle, ResolveError>>;
+        type Time = TokioTime;
+
+        fn new_connection(&self, config: &NameServerConfig, _: &ResolverOpts) -> Self::FutureConn {
+            if config.socket_addr.ip() == self.unreachable {
+                return future::err(ResolveError::from("connection refused"));
+            }
+
+            future::ok(self.handle.clone())
+        }
+    }
+
+    #[test]
+    fn test_state_changes() {
+        let failing = Arc::new(AtomicBool::new(true));
+        let flaky = Arc::clone(&failing);
+        let provider = RecordingProvider(RecordingHandle::answering(move |_| {
+            if flaky.load(atomic::Ordering::SeqCst) {
+                Err(ResolveError::from("connection refused"))
+            } else {
+                Ok(Message::new())
+            }
+        }));
+
+        let config = ResolverConfig::from_parts(
+            None,
+            vec![],
+            NameServerConfigGroup::from_ips_clear(&[IpAddr::from([127, 0, 0, 1])], 53, false),
+        );
+        let pool =
+            NameServerPool::from_config_with_provider(&config, &ResolverOpts::default(), provider)
+                .unwrap();
+
+        let mut state_changes = pool.state_changes();
+
+        let io_loop = Runtime::new().unwrap();
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let lookup = || {
+            let mut pool = pool.clone();
+            io_loop.block_on(pool.lookup(query.clone(), DnsRequestOptions::default()))
+        };
+
+        // the name server fails, further attempts back off without changing the state
+        assert!(lookup().is_err());
+
+        // and recovers, once the back off has passed
+        failing.store(false, atomic::Ordering::SeqCst);
+        std::thread::sleep(MIN_RETRY_DELAY);
+        lookup().expect("lookup failed");
+        lookup().expect("lookup failed");
+
+        let changes: Vec<_> = std::iter::from_fn(|| state_changes.try_next().ok().flatten())
+            .map(|change: StateChange| {
+                assert_eq!(change.addr(), SocketAddr::from(([127, 0, 0, 1], 53)));
+                (change.from(), change.to(), change.error().is_some())
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                (ConnectionState::Init, ConnectionState::Failed, true),
+                (ConnectionState::Failed, ConnectionState::Init, false),
+                (ConnectionState::Init, ConnectionState::Established, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_warmup() {
+        let reachable = IpAddr::from([127, 0, 0, 1]);
+        let unreachable = IpAddr::from([127, 0, 0, 2]);
+        let provider = UnreachableProvider {
+            handle: RecordingHandle::new(Message::new()),
+            unreachable,
+        };
+
+        let config = ResolverConfig::from_parts(
+            None,
+            vec![],
+            NameServerConfigGroup::from_ips_dual(&[reachable, unreachable], 53, false),
+        );
+        let options = ResolverOpts::default();
+        let pool = NameServerPool::from_config_with_provider(&config, &options, provider).unwrap();
+        assert!(pool
+            .stats()
+            .iter()
+            .all(|status| status.state() == ConnectionState::Init));
+
+        // the unreachable name server does not keep the others from connecting
+        let io_loop = Runtime::new().unwrap();
+        assert_eq!(io_loop.block_on(pool.warmup()), 1);
+
+        for status in pool.stats() {
+            let established = status.state() == ConnectionState::Established;
+            assert_eq!(established, status.socket_addr().ip() == reachable);
+        }
+    }
+
+    #[test]
+    fn test_truncated_falls_back_to_stream() {
+        let config_at = |ip, protocol| NameServerConfig {
+            socket_addr: SocketAddr::new(IpAddr::V4(ip), 53),
+            protocol,
+            ..config(53)
+        };
+
+        let mut opts = ResolverOpts::default();
+        opts.num_concurrent_reqs = 1;
+
+        let name = Name::from_str("www.example.com.").unwrap();
+        let mut truncated = Message::new();
+        truncated.set_truncated(true);
+        let mut full = Message::new();
+        full.add_answer(Record::from_rdata(
+            name.clone(),
+            300,
+            RData::A(Ipv4Addr::new(127, 0, 0, 1)),
+        ));
+
+        let server = Ipv4Addr::new(127, 0, 0, 1);
+        let other = Ipv4Addr::new(127, 0, 0, 2);
+        let udp = RecordingHandle::new(truncated);
+        let tcp = RecordingHandle::new(full.clone());
+        let other_tcp = RecordingHandle::new(full);
+
+        let datagram_conns = vec![NameServer::new_with_provider(
+            config_at(server, Protocol::Udp),
+            opts.clone(),
+            RecordingProvider(udp.clone()),
+        )
+        .unwrap()];
+        let stream_conns = vec![
+            NameServer::new_with_provider(
+                config_at(other, Protocol::Tcp),
+                opts.clone(),
+                RecordingProvider(other_tcp.clone()),
+            )
+            .unwrap(),
+            NameServer::new_with_provider(
+                config_at(server, Protocol::Tcp),
+                opts.clone(),
+                RecordingProvider(tcp.clone()),
+            )
+            .unwrap(),
+        ];
+
+        let conn_provider = RecordingProvider(RecordingHandle::new(Message::new()));
+        let mut pool = NameServerPool::from_nameservers_test(
+            &opts,
+            Arc::from(datagram_conns),
+            Arc::from(stream_conns),
+            #[cfg(feature = "mdns")]
+            name_server::mdns_nameserver(opts.clone(), conn_provider.clone(), false),
+            conn_provider,
+        );
+
+        let io_loop = Runtime::new().unwrap();
+        let response = io_loop
+            .block_on(pool.lookup(
+                Query::query(name, RecordType::A),
+                DnsRequestOptions::default(),
+            ))
+            .expect("lookup failed");
+
+        // the full response is retried from the name server which truncated it
+        assert!(!response.truncated());
+        assert_eq!(response.answers().len(), 1);
+        assert_eq!(udp.requests.lock().unwrap().len(), 1);
+        assert_eq!(tcp.requests.lock().unwrap().len(), 1);
+        assert_eq!(other_tcp.requests.lock().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_weighted_selection() {
+        let weighted = |port, weight| NameServerConfig {
+            weight: Some(weight),
+            ..config(port)
+        };
+
+        // all healthy name servers are ranked equally, the first is chosen by weight
+        let mut opts = ResolverOpts::default();
+        opts.server_ordering = ServerOrderingStrategy::RoundRobin;
+        opts.num_concurrent_reqs = 1;
+
+        let handles: Vec<_> = (0..2).map(|_| RecordingHandle::new(Message::new())).collect();
+        let name_servers: Vec<_> = handles
+            .iter()
+            .zip(&[(53, 3), (54, 1)])
+            .map(|(handle, &(port, weight))| {
+                let provider = RecordingProvider(handle.clone());
+                NameServer::new_with_provider(weighted(port, weight), opts.clone(), provider)
+                    .unwrap()
+            })
+            .collect();
+        let heavy = name_servers[0].clone();
+
+        let conn_provider = RecordingProvider(RecordingHandle::new(Message::new()));
+        let mut pool = NameServerPool::from_nameservers_test(
+            &opts,
+            Arc::from(name_servers),
+            Arc::from([]),
+            #[cfg(feature = "mdns")]
+            name_server::mdns_nameserver(opts.clone(), conn_provider.clone(), false),
+            conn_provider,
+        );
+
+        let io_loop = Runtime::new().unwrap();
+        let name = Name::from_str("www.example.com.").unwrap();
+        let mut lookup = |count| {
+            for _ in 0..count {
                 io_loop
                     .block_on(pool.lookup(
                         Query::query(name.clone(), RecordType::A),
-                        DnsRequestOptions::default()
+                        DnsRequestOptions::default(),
                     ))
-                    .is_ok(),
-                "iter: {}",
-                i
-            );
-        }
+                    .expect("lookup failed");
+            }
+        };
+
+        // requests are spread in proportion to the weights
+        lookup(400);
+        assert_eq!(handles[0].requests.lock().unwrap().len(), 300);
+        assert_eq!(handles[1].requests.lock().unwrap().len(), 100);
+
+        // the lower weighted name server takes over while the other is failing
+        heavy.fail(Instant::now());
+        lookup(4);
+        assert_eq!(handles[0].requests.lock().unwrap().len(), 300);
+        assert_eq!(handles[1].requests.lock().unwrap().len(), 104);
+    }
+
+    #[test]
+    fn test_stats_round_trip() {
+        let resolver_config =
+            ResolverConfig::from_parts(None, vec![], vec![config(53), config(54)]);
+        let opts = ResolverOpts::default();
+        let conn_provider = || RecordingProvider(RecordingHandle::new(Message::new()));
+
+        // the first name server has been failing for a while
+        let failed_at = Instant::now() - Duration::from_secs(2);
+        let pool =
+            NameServerPool::from_config_with_provider(&resolver_config, &opts, conn_provider())
+                .unwrap();
+        pool.datagram_conns()[0].fail(failed_at);
+        pool.datagram_conns()[0].fail(failed_at);
+
+        let snapshot = pool.export_stats();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[0].failures(), 2);
+        assert_eq!(snapshot[1].failures(), 0);
+
+        // a fresh pool ranks them equally
+        let pool =
+            NameServerPool::from_config_with_provider(&resolver_config, &opts, conn_provider())
+                .unwrap();
+        let conns = pool.datagram_conns();
+        assert_eq!(conns[0].cmp(&conns[1]), Ordering::Equal);
+
+        // a reseeded pool prefers the name server which has not been failing
+        let pool = NameServerPool::from_config_with_stats(
+            &resolver_config,
+            &opts,
+            conn_provider(),
+            &snapshot,
+        )
+        .unwrap();
+        let conns = pool.datagram_conns();
+        assert_eq!(conns[0].cmp(&conns[1]), Ordering::Less);
+
+        let stats = pool.stats();
+        assert_eq!(stats[0].failures(), 2);
+        assert_eq!(stats[0].state(), ConnectionState::Failed);
+        assert!(stats[0].last_failure().unwrap() <= Instant::now() - Duration::from_secs(2));
+        assert_eq!(stats[1].failures(), 0);
+        assert_eq!(stats[1].state(), ConnectionState::Init);
     }
 
     #[test]
@@ -516,15 +2317,13 @@ mod tests {
         let tcp = NameServerConfig {
             socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), 53),
             protocol: Protocol::Tcp,
-            tls_dns_name: None,
-            trust_nx_responses: false,
-            #[cfg(feature = "dns-over-rustls")]
-            tls_config: None,
+            ..config(53)
         };
 
         let opts = ResolverOpts::default();
         let ns_config = { tcp };
-        let name_server = NameServer::new_with_provider(ns_config, opts, conn_provider.clone());
+        let name_server =
+            NameServer::new_with_provider(ns_config, opts.clone(), conn_provider.clone()).unwrap();
         let name_servers: Arc<[_]> = Arc::from([name_server]);
 
         let mut pool = NameServerPool::from_nameservers_test(
@@ -532,7 +2331,7 @@ mod tests {
             Arc::from([]),
             Arc::clone(&name_servers),
             #[cfg(feature = "mdns")]
-            name_server::mdns_nameserver(opts, conn_provider.clone(), false),
+            name_server::mdns_nameserver(opts.clone(), conn_provider.clone(), false),
             conn_provider,
         );
 
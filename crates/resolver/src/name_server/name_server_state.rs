@@ -6,16 +6,89 @@
 // copied, modified, or distributed except according to those terms.
 
 use std::cmp::Ordering;
-use std::sync::atomic::{self, AtomicU8};
+use std::net::SocketAddr;
+use std::sync::atomic::{self, AtomicBool, AtomicU8};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
+use futures_channel::mpsc::UnboundedSender;
 use futures_util::lock::Mutex;
+use parking_lot::Mutex as SyncMutex;
 use proto::op::Edns;
 
+use crate::error::ResolveError;
+
 pub struct NameServerState {
+    /// the address of the remote, reported along with the changes of `conn_state`
+    addr: SocketAddr,
     conn_state: AtomicU8,
     remote_edns: Mutex<Arc<Option<Edns>>>,
+    send_edns: SyncMutex<Option<Edns>>,
+    /// the highest EDNS version the remote implements, known once it has responded with BADVERS
+    edns_version: SyncMutex<Option<u8>>,
+    /// the DNS Cookie last received from the remote, echoed back in requests
+    server_cookie: SyncMutex<Option<Vec<u8>>>,
+    /// how long the remote keeps idle stream connections open, as it advertised with RFC 7828
+    keepalive: SyncMutex<Option<Duration>>,
+    failed_at: SyncMutex<Option<Instant>>,
+    /// the error of the most recent failure, shared rather than copied to each reader
+    last_error: SyncMutex<Option<Arc<ResolveError>>>,
+    /// set while a request probes whether the remote has recovered, see `Probe`
+    probing: Arc<AtomicBool>,
+    /// receives each change of `conn_state`
+    state_changes: SyncMutex<Option<UnboundedSender<StateChange>>>,
+}
+
+/// The state of the connection to a remote NameServer
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum ConnectionState {
+    /// No communication has been attempted, or the connection is being reestablished
+    Init,
+    /// There has been successful communication with the remote
+    Established,
+    /// The connection failed, it will be reestablished on a subsequent request
+    Failed,
+}
+
+/// A transition of the state of the connection to a remote NameServer
+#[derive(Clone, Debug)]
+pub struct StateChange {
+    addr: SocketAddr,
+    from: ConnectionState,
+    to: ConnectionState,
+    error: Option<Arc<ResolveError>>,
+}
+
+impl StateChange {
+    /// The address of the remote NameServer
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The state before the transition
+    pub fn from(&self) -> ConnectionState {
+        self.from
+    }
+
+    /// The state after the transition
+    pub fn to(&self) -> ConnectionState {
+        self.to
+    }
+
+    /// The error which caused a transition to `ConnectionState::Failed`
+    pub fn error(&self) -> Option<&ResolveError> {
+        self.error.as_deref()
+    }
+}
+
+/// The claim of a request on probing a NameServer which was taken out of rotation, released when
+///  dropped
+pub(crate) struct Probe(Arc<AtomicBool>);
+
+impl Drop for Probe {
+    fn drop(&mut self) {
+        self.0.store(false, atomic::Ordering::Release);
+    }
 }
 
 /// State of a connection with a remote NameServer.
@@ -42,6 +115,16 @@ impl From<NameServerStateInner> for u8 {
     }
 }
 
+impl From<NameServerStateInner> for ConnectionState {
+    fn from(val: NameServerStateInner) -> Self {
+        match val {
+            NameServerStateInner::Failed => ConnectionState::Failed,
+            NameServerStateInner::Init => ConnectionState::Init,
+            NameServerStateInner::Established => ConnectionState::Established,
+        }
+    }
+}
+
 impl From<u8> for NameServerStateInner {
     fn from(val: u8) -> Self {
         match val {
@@ -53,9 +136,33 @@ impl From<u8> for NameServerStateInner {
 }
 
 impl NameServerState {
-    fn store(&self, conn_state: NameServerStateInner) {
-        self.conn_state
-            .store(conn_state.into(), atomic::Ordering::Release);
+    fn store(&self, conn_state: NameServerStateInner, error: Option<Arc<ResolveError>>) {
+        let from = self.conn_state.swap(conn_state.into(), atomic::Ordering::AcqRel);
+        let from = NameServerStateInner::from(from);
+        if from == conn_state {
+            return;
+        }
+
+        debug!(
+            "name server state changed: addr={} from={:?} to={:?}",
+            self.addr, from, conn_state
+        );
+
+        // the change is queued without waiting for it to be received, off of the request path
+        let mut state_changes = self.state_changes.lock();
+        if let Some(sender) = state_changes.as_ref() {
+            let change = StateChange {
+                addr: self.addr,
+                from: from.into(),
+                to: conn_state.into(),
+                error,
+            };
+
+            // the receiver was dropped, there is no one left to send to
+            if sender.unbounded_send(change).is_err() {
+                *state_changes = None;
+            }
+        }
     }
 
     fn load(&self) -> NameServerStateInner {
@@ -64,23 +171,35 @@ impl NameServerState {
 
     /// Set at the new Init state
     ///
-    /// If send_dns is some, this will be sent on the first request when it is established
-    pub fn init(_send_edns: Option<Edns>) -> Self {
-        // TODO: need to track send_edns
+    /// * addr - the address of the remote, this is reported along with each transition
+    /// * send_edns - if some, this will be sent on the first request when it is established
+    pub fn init(addr: SocketAddr, send_edns: Option<Edns>) -> Self {
         NameServerState {
+            addr,
             conn_state: AtomicU8::new(NameServerStateInner::Init.into()),
             remote_edns: Mutex::new(Arc::new(None)),
+            send_edns: SyncMutex::new(send_edns),
+            edns_version: SyncMutex::new(None),
+            server_cookie: SyncMutex::new(None),
+            keepalive: SyncMutex::new(None),
+            failed_at: SyncMutex::new(None),
+            last_error: SyncMutex::new(None),
+            probing: Arc::new(AtomicBool::new(false)),
+            state_changes: SyncMutex::new(None),
         }
     }
 
+    /// Sets the sender of the transitions of the state, None to stop sending them
+    pub(crate) fn set_state_changes(&self, sender: Option<UnboundedSender<StateChange>>) {
+        *self.state_changes.lock() = sender;
+    }
+
     /// Set at the new Init state
     ///
     /// If send_dns is some, this will be sent on the first request when it is established
-    pub fn reinit(&self, _send_edns: Option<Edns>) {
-        // eventually do this
-        // self.send_edns.lock() = send_edns;
-
-        self.store(NameServerStateInner::Init);
+    pub fn reinit(&self, send_edns: Option<Edns>) {
+        *self.send_edns.lock() = send_edns;
+        self.store(NameServerStateInner::Init, None);
     }
 
     /// Transition to the Established state
@@ -88,29 +207,122 @@ impl NameServerState {
     /// If remote_edns is Some, then it will be used to effect things like buffer sizes based on
     ///   the remote's support.
     pub fn establish(&self, remote_edns: Option<Edns>) {
-        if remote_edns.is_some() {
+        // the first response determines if the remote supports EDNS at all
+        if remote_edns.is_some() || self.load() == NameServerStateInner::Init {
             // best effort locking, we'll assume a different user of this connection is storing the same thing...
             if let Some(mut current_edns) = self.remote_edns.try_lock() {
                 *current_edns = Arc::new(remote_edns)
             }
         }
 
-        self.store(NameServerStateInner::Established);
+        self.store(NameServerStateInner::Established, None);
     }
 
     /// transition to the Failed state
     ///
-    /// when is the time of the failure
+    /// * when - the time of the failure, reconnection is delayed relative to this
+    pub fn fail(&self, when: Instant) {
+        *self.failed_at.lock() = Some(when);
+        self.store(NameServerStateInner::Failed, None);
+    }
+
+    /// transition to the Failed state, recording the error which caused the failure
     ///
-    /// * when - deprecated
-    pub fn fail(&self, _when: /* FIXME: remove in 0.20 */ Instant) {
-        self.store(NameServerStateInner::Failed);
+    /// * when - the time of the failure, reconnection is delayed relative to this
+    /// * error - the error of the failed request
+    pub fn fail_with(&self, when: Instant, error: Arc<ResolveError>) {
+        *self.last_error.lock() = Some(Arc::clone(&error));
+        *self.failed_at.lock() = Some(when);
+        self.store(NameServerStateInner::Failed, Some(error));
+    }
+
+    /// Claims the probe of the remote, None if another request is already probing it
+    pub(crate) fn try_probe(&self) -> Option<Probe> {
+        if self.probing.swap(true, atomic::Ordering::AcqRel) {
+            return None;
+        }
+
+        Some(Probe(Arc::clone(&self.probing)))
+    }
+
+    /// The error of the most recent failure, regardless of the current state
+    pub(crate) fn last_error(&self) -> Option<Arc<ResolveError>> {
+        self.last_error.lock().clone()
+    }
+
+    /// The EDNS to send with requests, if EDNS is enabled
+    pub(crate) fn send_edns(&self) -> Option<Edns> {
+        self.send_edns.lock().clone()
+    }
+
+    /// The highest EDNS version to send to the remote, None unless it responded with BADVERS
+    pub(crate) fn edns_version(&self) -> Option<u8> {
+        *self.edns_version.lock()
+    }
+
+    /// Limits the EDNS version sent to the remote, which responded with BADVERS to a higher one
+    ///
+    /// The limit is kept as the connection is reestablished, it is a property of the remote.
+    pub(crate) fn downgrade_edns(&self, version: u8) {
+        *self.edns_version.lock() = Some(version);
+    }
+
+    /// The server cookie to send with requests, None until the remote has responded with one
+    pub(crate) fn server_cookie(&self) -> Option<Vec<u8>> {
+        self.server_cookie.lock().clone()
+    }
+
+    /// Stores the server cookie the remote responded with, it is sent with subsequent requests
+    pub(crate) fn set_server_cookie(&self, server_cookie: Vec<u8>) {
+        *self.server_cookie.lock() = Some(server_cookie);
+    }
+
+    /// How long the remote keeps idle stream connections open, None until it has advertised it
+    pub(crate) fn keepalive(&self) -> Option<Duration> {
+        *self.keepalive.lock()
+    }
+
+    /// Stores the idle timeout the remote advertised, connections are closed once it passes
+    ///
+    /// The timeout is kept as the connection is reestablished, it is a property of the remote.
+    pub(crate) fn set_keepalive(&self, keepalive: Duration) {
+        *self.keepalive.lock() = Some(keepalive);
+    }
+
+    /// The EDNS received from the remote, None if the remote did not respond with EDNS
+    ///
+    /// This will not block, None is returned if the remote EDNS is concurrently being stored.
+    pub(crate) fn remote_edns(&self) -> Option<Edns> {
+        self.remote_edns
+            .try_lock()
+            .and_then(|remote_edns| (**remote_edns).clone())
     }
 
     /// True if this is in the Failed state
     pub(crate) fn is_failed(&self) -> bool {
         NameServerStateInner::Failed == self.load()
     }
+
+    /// The current state of the connection
+    pub(crate) fn connection_state(&self) -> ConnectionState {
+        self.load().into()
+    }
+
+    /// The time of the most recent failure, regardless of the current state
+    ///
+    /// The lock is only held to record a failure, this waits for one being recorded concurrently.
+    pub(crate) fn last_failure(&self) -> Option<Instant> {
+        *self.failed_at.lock()
+    }
+
+    /// The time of the most recent failure, if this is in the Failed state
+    pub(crate) fn failed_at(&self) -> Option<Instant> {
+        if self.is_failed() {
+            *self.failed_at.lock()
+        } else {
+            None
+        }
+    }
 }
 
 impl Ord for NameServerStateInner {
@@ -152,14 +364,18 @@ mod tests {
     use super::*;
     use crate::name_server::NameServerState;
 
+    fn addr() -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], 53))
+    }
+
     #[test]
     fn test_state_cmp() {
-        let init = NameServerState::init(None);
+        let init = NameServerState::init(addr(), None);
 
-        let established = NameServerState::init(None);
+        let established = NameServerState::init(addr(), None);
         established.establish(None);
 
-        let failed = NameServerState::init(None);
+        let failed = NameServerState::init(addr(), None);
         failed.fail(Instant::now());
 
         assert_eq!(init.cmp(&init), Ordering::Equal);
@@ -169,4 +385,68 @@ mod tests {
         assert_eq!(established.cmp(&failed), Ordering::Greater);
         assert_eq!(failed.cmp(&failed), Ordering::Equal);
     }
+
+    #[test]
+    fn test_remote_edns() {
+        let mut edns = Edns::new();
+        edns.set_max_payload(1400);
+
+        let state = NameServerState::init(addr(), Some(Edns::new()));
+        assert!(state.send_edns().is_some());
+        assert!(state.remote_edns().is_none());
+
+        state.establish(Some(edns.clone()));
+        assert_eq!(state.remote_edns(), Some(edns.clone()));
+
+        // an established connection keeps the remote EDNS
+        state.establish(None);
+        assert_eq!(state.remote_edns(), Some(edns));
+
+        // a new connection is renegotiated
+        state.reinit(None);
+        assert!(state.send_edns().is_none());
+        state.establish(None);
+        assert!(state.remote_edns().is_none());
+    }
+
+    #[test]
+    fn test_last_error_shared() {
+        let state = Arc::new(NameServerState::init(addr(), None));
+        assert!(state.last_error().is_none());
+
+        let error = Arc::new(ResolveError::from("connection refused"));
+        state.fail_with(Instant::now(), Arc::clone(&error));
+        assert!(state.is_failed());
+
+        // every reader, including those through clones of the state, sees the same error
+        let cloned = Arc::clone(&state);
+        assert!(Arc::ptr_eq(&state.last_error().unwrap(), &error));
+        assert!(Arc::ptr_eq(&cloned.last_error().unwrap(), &error));
+
+        // the error outlives the failed state
+        state.establish(None);
+        assert!(Arc::ptr_eq(&state.last_error().unwrap(), &error));
+    }
+
+    #[test]
+    fn test_last_failure_contended() {
+        let state = Arc::new(NameServerState::init(addr(), None));
+        let failed_at = Instant::now();
+        state.fail(failed_at);
+
+        // hold the lock as a concurrent failure being recorded would
+        let (locked, wait_locked) = std::sync::mpsc::channel();
+        let holder = {
+            let state = Arc::clone(&state);
+            std::thread::spawn(move || {
+                let _failed_at = state.failed_at.lock();
+                locked.send(()).expect("receiver dropped");
+                std::thread::sleep(Duration::from_millis(50));
+            })
+        };
+
+        wait_locked.recv().expect("lock not taken");
+        assert_eq!(state.last_failure(), Some(failed_at));
+        holder.join().expect("holder panicked");
+    }
 }
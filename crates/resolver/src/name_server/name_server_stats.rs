@@ -6,13 +6,24 @@
 // copied, modified, or distributed except according to those terms.
 
 use std::cmp::Ordering;
+use std::sync::atomic::{self, AtomicU64, AtomicUsize};
+use std::time::Duration;
 
-use std::sync::atomic::{self, AtomicUsize};
+/// The delay before reconnecting to a NameServer after its first failure
+pub(crate) const MIN_RETRY_DELAY: Duration = Duration::from_millis(500);
+/// The upper bound on the delay before reconnecting to a failing NameServer
+pub(crate) const MAX_RETRY_DELAY: Duration = Duration::from_secs(360);
+
+/// Weight given to each new latency sample, expressed as a divisor, i.e. 1/8th (as in TCP's SRTT)
+const LATENCY_EWMA_DIVISOR: u64 = 8;
 
 pub(crate) struct NameServerStats {
     successes: AtomicUsize,
     failures: AtomicUsize,
-    // TODO: incorporate latency
+    /// failures since the last success, this drives the reconnection backoff
+    consecutive_failures: AtomicUsize,
+    /// exponentially weighted moving average of the round-trip time, in microseconds
+    latency_micros: AtomicU64,
 }
 
 impl Default for NameServerStats {
@@ -26,15 +37,89 @@ impl NameServerStats {
         NameServerStats {
             successes: AtomicUsize::new(successes),
             failures: AtomicUsize::new(failures),
+            consecutive_failures: AtomicUsize::new(0),
+            latency_micros: AtomicU64::new(0),
         }
     }
 
-    pub fn next_success(&self) {
+    /// Records a successful response, which took `rtt` to be received
+    pub fn next_success(&self, rtt: Duration) {
         self.successes.fetch_add(1, atomic::Ordering::Release);
+        self.consecutive_failures.store(0, atomic::Ordering::Release);
+
+        let sample = rtt.as_micros().min(u128::from(u64::MAX)) as u64;
+        self.latency_micros
+            .fetch_update(atomic::Ordering::AcqRel, atomic::Ordering::Acquire, |current| {
+                // the first sample seeds the average
+                if current == 0 {
+                    return Some(sample.max(1));
+                }
+
+                let current = current as i128;
+                let delta = (sample as i128 - current) / LATENCY_EWMA_DIVISOR as i128;
+                Some((current + delta).max(1) as u64)
+            })
+            .ok();
     }
 
     pub fn next_failure(&self) {
         self.failures.fetch_add(1, atomic::Ordering::Release);
+        self.consecutive_failures.fetch_add(1, atomic::Ordering::Release);
+    }
+
+    /// The total number of successful responses
+    pub fn successes(&self) -> usize {
+        self.successes.load(atomic::Ordering::Acquire)
+    }
+
+    /// The total number of failed requests
+    pub fn failures(&self) -> usize {
+        self.failures.load(atomic::Ordering::Acquire)
+    }
+
+    /// The number of failures since the last successful response
+    pub fn consecutive_failures(&self) -> usize {
+        self.consecutive_failures.load(atomic::Ordering::Acquire)
+    }
+
+    /// The amount of time to wait after a failure before attempting to reconnect
+    ///
+    /// This starts at `MIN_RETRY_DELAY` and doubles with each consecutive failure, up to
+    ///  `MAX_RETRY_DELAY`. It is zero if the last response was a success.
+    pub fn retry_delay(&self) -> Duration {
+        let consecutive_failures = self.consecutive_failures();
+        if consecutive_failures == 0 {
+            return Duration::from_secs(0);
+        }
+
+        // the shift is bounded, beyond this the delay is capped regardless
+        let factor = 1_u32 << (consecutive_failures - 1).min(31) as u32;
+        MIN_RETRY_DELAY
+            .checked_mul(factor)
+            .map_or(MAX_RETRY_DELAY, |delay| delay.min(MAX_RETRY_DELAY))
+    }
+
+    /// Seeds the counters and latency, e.g. with those persisted before a restart
+    pub fn restore(
+        &self,
+        successes: usize,
+        failures: usize,
+        consecutive_failures: usize,
+        latency: Duration,
+    ) {
+        let latency_micros = latency.as_micros().min(u128::from(u64::MAX)) as u64;
+
+        self.successes.store(successes, atomic::Ordering::Release);
+        self.failures.store(failures, atomic::Ordering::Release);
+        self.consecutive_failures.store(consecutive_failures, atomic::Ordering::Release);
+        self.latency_micros.store(latency_micros, atomic::Ordering::Release);
+    }
+
+    /// The moving average of the round-trip time of successful responses
+    ///
+    /// This will be zero until the first successful response is recorded.
+    pub fn latency(&self) -> Duration {
+        Duration::from_micros(self.latency_micros.load(atomic::Ordering::Acquire))
     }
 
     fn noload_eq(
@@ -42,8 +127,12 @@ impl NameServerStats {
         other_successes: usize,
         self_failures: usize,
         other_failures: usize,
+        self_latency: Duration,
+        other_latency: Duration,
     ) -> bool {
-        self_successes == other_successes && self_failures == other_failures
+        self_successes == other_successes
+            && self_failures == other_failures
+            && self_latency == other_latency
     }
 }
 
@@ -61,6 +150,8 @@ impl PartialEq for NameServerStats {
             other_successes,
             self_failures,
             other_failures,
+            self.latency(),
+            other.latency(),
         )
     }
 }
@@ -76,25 +167,35 @@ impl Ord for NameServerStats {
         let self_failures = self.failures.load(atomic::Ordering::Acquire);
         let other_failures = other.failures.load(atomic::Ordering::Acquire);
 
+        let self_latency = self.latency();
+        let other_latency = other.latency();
+
         // if they are literally equal, just return
         if Self::noload_eq(
             self_successes,
             other_successes,
             self_failures,
             other_failures,
+            self_latency,
+            other_latency,
         ) {
             return Ordering::Equal;
         }
 
-        // TODO: track latency and use lowest latency connection...
-
         // invert failure comparison, i.e. the one with the least failures, wins
-        if self_failures <= other_failures {
-            return Ordering::Greater;
+        match other_failures.cmp(&self_failures) {
+            Ordering::Equal => (),
+            o => return o,
+        }
+
+        // invert latency comparison, i.e. the lowest latency connection wins
+        match other_latency.cmp(&self_latency) {
+            Ordering::Equal => (),
+            o => return o,
         }
 
         // at this point we'll go with the lesser of successes to make sure there is balance
-        self_successes.cmp(&other_successes)
+        other_successes.cmp(&self_successes)
     }
 }
 
@@ -127,4 +228,62 @@ mod tests {
         assert_eq!(nil.cmp(&successes), Ordering::Greater);
         assert_eq!(successes.cmp(&failures), Ordering::Greater);
     }
+
+    #[test]
+    fn test_retry_delay() {
+        let stats = NameServerStats::default();
+        assert_eq!(stats.retry_delay(), Duration::from_secs(0));
+
+        stats.next_failure();
+        assert_eq!(stats.retry_delay(), MIN_RETRY_DELAY);
+
+        stats.next_failure();
+        assert_eq!(stats.retry_delay(), MIN_RETRY_DELAY * 2);
+
+        stats.next_failure();
+        assert_eq!(stats.retry_delay(), MIN_RETRY_DELAY * 4);
+
+        for _ in 0..64 {
+            stats.next_failure();
+        }
+        assert_eq!(stats.retry_delay(), MAX_RETRY_DELAY);
+
+        // a success resets the backoff
+        stats.next_success(Duration::from_millis(10));
+        assert_eq!(stats.retry_delay(), Duration::from_secs(0));
+        assert_eq!(stats.consecutive_failures(), 0);
+    }
+
+    #[test]
+    fn test_latency_ewma() {
+        let stats = NameServerStats::default();
+        assert_eq!(stats.latency(), Duration::from_micros(0));
+
+        stats.next_success(Duration::from_millis(80));
+        assert_eq!(stats.latency(), Duration::from_millis(80));
+
+        stats.next_success(Duration::from_millis(160));
+        assert_eq!(stats.latency(), Duration::from_millis(90));
+    }
+
+    #[test]
+    fn test_latency_cmp() {
+        let fast = NameServerStats::default();
+        let slow = NameServerStats::default();
+
+        fast.next_success(Duration::from_millis(10));
+        slow.next_success(Duration::from_millis(100));
+
+        assert_eq!(fast.cmp(&slow), Ordering::Greater);
+        assert_eq!(slow.cmp(&fast), Ordering::Less);
+
+        // the pool tries the highest ranked first
+        let mut ranked = vec![&slow, &fast];
+        ranked.sort_unstable_by(|a, b| b.cmp(a));
+        assert!(std::ptr::eq(ranked[0], &fast));
+
+        // failures still take precedence over latency
+        fast.next_failure();
+        assert_eq!(fast.cmp(&slow), Ordering::Less);
+    }
 }
@@ -0,0 +1,312 @@
+// Copyright 2015-2020 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! QNAME minimization, [RFC 7816](https://tools.ietf.org/html/rfc7816)
+
+use std::net::IpAddr;
+
+use proto::op::{Query, ResponseCode};
+use proto::rr::domain::usage;
+use proto::rr::{Name, RData, RecordType};
+use proto::xfer::{DnsHandle, DnsRequest, DnsResponse};
+
+use crate::error::ResolveError;
+use crate::name_server::{ConnectionProvider, NameServerPool, ResponseMeta};
+
+/// The most NS queries sent for the ancestors of a name, those closest to the root are skipped
+///  for names with more labels
+const MAX_MINIMIZED_QUERIES: usize = 10;
+
+/// Sends the request, revealing only as many labels of the name to each name server as needed
+///
+/// The NS records of each ancestor of the name are queried in turn, starting with the top level
+///  domain. A referral, NS records for the ancestor with glue, moves on to the name servers of
+///  that zone, otherwise the same name servers are queried for the next label. Name servers which
+///  do not answer the NS query with NOERROR are sent the full request instead.
+pub(crate) async fn send<C, P>(
+    pool: NameServerPool<C, P>,
+    request: DnsRequest,
+) -> Result<(DnsResponse, ResponseMeta), ResolveError>
+where
+    C: DnsHandle<Error = ResolveError> + Sync + 'static,
+    P: ConnectionProvider<Conn = C> + 'static,
+{
+    let mut name_servers = pool;
+
+    let name = match request.queries() {
+        [query] if !usage::LOCAL.name().zone_of(query.name()) => query.name().clone(),
+        _ => return name_servers.send_request(request).await,
+    };
+
+    let num_labels = usize::from(name.num_labels());
+    let first = num_labels.saturating_sub(MAX_MINIMIZED_QUERIES).max(1);
+
+    for labels in first..num_labels {
+        let ancestor = name.trim_to(labels);
+
+        let mut ns_request = request.clone();
+        ns_request.take_queries();
+        ns_request.add_query(Query::query(ancestor.clone(), RecordType::NS));
+
+        debug!("minimized query: {} NS", ancestor);
+        let response = name_servers.send_request(ns_request);
+        let response = match response.await {
+            Ok((response, _)) if response.response_code() == ResponseCode::NoError => response,
+            Ok((response, _)) => {
+                debug!(
+                    "minimized query refused with {}, sending the full name",
+                    response.response_code()
+                );
+                break;
+            }
+            Err(e) => {
+                debug!("minimized query failed, sending the full name: {}", e);
+                break;
+            }
+        };
+
+        let glue = referral_glue(&ancestor, &response);
+        if !glue.is_empty() {
+            debug!("referral to {} at: {:?}", ancestor, glue);
+            name_servers = match name_servers.for_referral(&glue) {
+                Ok(referral) => referral,
+                Err(e) => {
+                    debug!("referral can not be followed, sending the full name: {}", e);
+                    break;
+                }
+            };
+        }
+    }
+
+    name_servers.send_request(request).await
+}
+
+/// The addresses of the name servers of the `zone`, from the glue of the NS records for it
+///
+/// Only the glue of name servers within the zone is used, the addresses of others could be those
+///  of any name, e.g. spoofed by the name server which is being left.
+fn referral_glue(zone: &Name, response: &DnsResponse) -> Vec<IpAddr> {
+    let ns_names: Vec<&Name> = response
+        .answers()
+        .iter()
+        .chain(response.name_servers())
+        .filter(|record| record.name() == zone)
+        .filter_map(|record| match record.rdata() {
+            RData::NS(ns_name) => Some(ns_name),
+            _ => None,
+        })
+        .collect();
+
+    response
+        .additionals()
+        .iter()
+        .filter(|record| zone.zone_of(record.name()) && ns_names.contains(&record.name()))
+        .filter_map(|record| match record.rdata() {
+            RData::A(ip) => Some(IpAddr::V4(*ip)),
+            RData::AAAA(ip) => Some(IpAddr::V6(*ip)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+#[cfg(feature = "tokio-runtime")]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+
+    use futures_util::future;
+    use tokio::runtime::Runtime;
+
+    use proto::op::Message;
+    use proto::rr::Record;
+    use proto::xfer::DnsRequestOptions;
+    use proto::TokioTime;
+
+    use super::*;
+    use crate::config::{NameServerConfig, NameServerConfigGroup, ResolverConfig, ResolverOpts};
+
+    type Script = fn(IpAddr, &Query) -> Message;
+
+    /// Responds to each request by the `script`, recording the address it was sent to
+    #[derive(Clone)]
+    struct ScriptedHandle {
+        ip: IpAddr,
+        script: Script,
+        requests: Arc<Mutex<Vec<(IpAddr, Name, RecordType)>>>,
+    }
+
+    impl DnsHandle for ScriptedHandle {
+        type Response = future::Ready<Result<DnsResponse, ResolveError>>;
+        type Error = ResolveError;
+
+        fn send<R: Into<DnsRequest> + Unpin + Send + 'static>(
+            &mut self,
+            request: R,
+        ) -> Self::Response {
+            let request: DnsRequest = request.into();
+            let query = &request.queries()[0];
+            self.requests.lock().unwrap().push((
+                self.ip,
+                query.name().clone(),
+                query.query_type(),
+            ));
+
+            future::ok(DnsResponse::from((self.script)(self.ip, query)))
+        }
+    }
+
+    #[derive(Clone)]
+    struct ScriptedProvider(ScriptedHandle);
+
+    impl ConnectionProvider for ScriptedProvider {
+        type Conn = ScriptedHandle;
+        type FutureConn = future::Ready<Result<ScriptedHandle, ResolveError>>;
+        type Time = TokioTime;
+
+        fn new_connection(&self, config: &NameServerConfig, _: &ResolverOpts) -> Self::FutureConn {
+            // referrals are followed on the port of the configured name servers
+            assert_eq!(config.socket_addr.port(), PORT);
+
+            let mut handle = self.0.clone();
+            handle.ip = config.socket_addr.ip();
+            future::ok(handle)
+        }
+    }
+
+    const PORT: u16 = 5300;
+    const ROOT: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+    const COM: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    const EXAMPLE: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+
+    fn name(name: &str) -> Name {
+        Name::from_str(name).unwrap()
+    }
+
+    fn referral(zone: &str, ns_name: &str, glue: IpAddr) -> Message {
+        let mut message = Message::new();
+        let ns = RData::NS(name(ns_name));
+        message.add_name_server(Record::from_rdata(name(zone), 86400, ns));
+        if let IpAddr::V4(glue) = glue {
+            message.add_additional(Record::from_rdata(name(ns_name), 86400, RData::A(glue)));
+        }
+        message
+    }
+
+    /// `com.` is delegated from the root, and `example.com.` from `com.`
+    fn delegations(ip: IpAddr, query: &Query) -> Message {
+        let qname = query.name().to_string();
+        let query_type = query.query_type();
+
+        if ip == ROOT && query_type == RecordType::NS && qname == "com." {
+            referral("com.", "a.gtld.com.", COM)
+        } else if ip == COM && query_type == RecordType::NS && qname == "example.com." {
+            referral("example.com.", "ns.example.com.", EXAMPLE)
+        } else if ip == EXAMPLE && query_type == RecordType::NS {
+            // no zone cut, i.e. NODATA
+            Message::new()
+        } else if ip == EXAMPLE && query_type == RecordType::A {
+            let mut message = Message::new();
+            let rdata = RData::A(Ipv4Addr::new(127, 0, 0, 10));
+            message.add_answer(Record::from_rdata(query.name().clone(), 300, rdata));
+            message
+        } else {
+            let mut message = Message::new();
+            message.set_response_code(ResponseCode::Refused);
+            message
+        }
+    }
+
+    /// As `delegations`, but `example.com.` does not know of empty non-terminals
+    fn nxdomain_for_ent(ip: IpAddr, query: &Query) -> Message {
+        if ip == EXAMPLE && query.query_type() == RecordType::NS {
+            let mut message = Message::new();
+            message.set_response_code(ResponseCode::NXDomain);
+            return message;
+        }
+
+        delegations(ip, query)
+    }
+
+    fn lookup(script: Script, qname: &str) -> Vec<(IpAddr, Name, RecordType)> {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let provider = ScriptedProvider(ScriptedHandle {
+            ip: ROOT,
+            script,
+            requests: Arc::clone(&requests),
+        });
+
+        let mut options = ResolverOpts::default();
+        options.qname_minimization = true;
+        let config = ResolverConfig::from_parts(
+            None,
+            vec![],
+            NameServerConfigGroup::from_ips_dual(&[ROOT], PORT, false),
+        );
+        let mut pool =
+            NameServerPool::from_config_with_provider(&config, &options, provider).unwrap();
+
+        let io_loop = Runtime::new().unwrap();
+        let response = io_loop
+            .block_on(pool.lookup(
+                Query::query(name(qname), RecordType::A),
+                DnsRequestOptions::default(),
+            ))
+            .expect("lookup failed");
+        assert_eq!(response.answers().len(), 1);
+
+        let requests = requests.lock().unwrap().clone();
+        requests
+    }
+
+    #[test]
+    fn test_qname_minimization() {
+        let requests = lookup(delegations, "www.a.b.example.com.");
+
+        assert_eq!(
+            requests,
+            vec![
+                (ROOT, name("com."), RecordType::NS),
+                (COM, name("example.com."), RecordType::NS),
+                (EXAMPLE, name("b.example.com."), RecordType::NS),
+                (EXAMPLE, name("a.b.example.com."), RecordType::NS),
+                (EXAMPLE, name("www.a.b.example.com."), RecordType::A),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_qname_minimization_fallback() {
+        let requests = lookup(nxdomain_for_ent, "www.a.b.example.com.");
+
+        // the full name is sent once the name server fails to answer the minimized query
+        assert_eq!(
+            requests,
+            vec![
+                (ROOT, name("com."), RecordType::NS),
+                (COM, name("example.com."), RecordType::NS),
+                (EXAMPLE, name("b.example.com."), RecordType::NS),
+                (EXAMPLE, name("www.a.b.example.com."), RecordType::A),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_referral_glue_in_bailiwick() {
+        let zone = name("example.com.");
+        let glue = |ns_name| {
+            let response = DnsResponse::from(referral("example.com.", ns_name, EXAMPLE));
+            referral_glue(&zone, &response)
+        };
+
+        assert_eq!(glue("ns.example.com."), vec![EXAMPLE]);
+
+        // the address of a name server outside of the zone is not trusted
+        assert!(glue("ns.example.net.").is_empty());
+    }
+}
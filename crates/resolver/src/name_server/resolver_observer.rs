@@ -0,0 +1,30 @@
+// Copyright 2015-2020 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::time::Duration;
+
+use proto::xfer::DnsRequest;
+
+use crate::error::ResolveError;
+
+/// Observes the requests sent through a [`NameServerPool`], e.g. to feed a metrics library
+///
+/// Each request sent to the pool is started, and then either succeeds or fails, regardless of
+///  how many name servers were tried along the way. These are called on the request path, so
+///  should not block.
+///
+/// [`NameServerPool`]: struct.NameServerPool.html
+pub trait ResolverObserver: Send + Sync {
+    /// A request has been sent to the pool
+    fn on_query_start(&self, request: &DnsRequest);
+
+    /// A response was received, `latency` is the time since the request was sent to the pool
+    fn on_query_success(&self, latency: Duration);
+
+    /// The request failed, with the error returned from the pool
+    fn on_query_failure(&self, error: &ResolveError);
+}
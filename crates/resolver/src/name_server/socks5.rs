@@ -0,0 +1,222 @@
+// Copyright 2015-2020 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Tunneling of TCP connections through a SOCKS5 proxy,
+//!  [RFC 1928](https://tools.ietf.org/html/rfc1928)
+
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+
+use futures_util::io::{AsyncReadExt, AsyncWriteExt};
+use log::debug;
+
+use proto::tcp::Connect;
+
+const VERSION: u8 = 5;
+const NO_AUTHENTICATION: u8 = 0;
+const CONNECT: u8 = 1;
+const SUCCEEDED: u8 = 0;
+
+const ATYP_IPV4: u8 = 1;
+const ATYP_DOMAIN_NAME: u8 = 3;
+const ATYP_IPV6: u8 = 4;
+
+/// Connects to `target` through the SOCKS5 proxy at `proxy`, from the local `bind_addr` if one is
+///  given
+///
+/// The returned stream is tunneled to `target`, any further data is exchanged with it.
+pub(crate) async fn connect<S: Connect>(
+    proxy: SocketAddr,
+    target: SocketAddr,
+    bind_addr: Option<SocketAddr>,
+) -> io::Result<S> {
+    let mut stream = S::connect_with_bind(proxy, bind_addr).await?;
+
+    // the greeting, offering only no authentication
+    stream.write_all(&[VERSION, 1, NO_AUTHENTICATION]).await?;
+
+    let mut choice = [0u8; 2];
+    stream.read_exact(&mut choice).await?;
+    if choice[0] != VERSION {
+        return Err(invalid_data(format!("unsupported SOCKS version: {}", choice[0])));
+    }
+    if choice[1] != NO_AUTHENTICATION {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("SOCKS5 proxy {} requires authentication", proxy),
+        ));
+    }
+
+    // the request to connect to the target
+    let mut request = vec![VERSION, CONNECT, 0];
+    match target.ip() {
+        IpAddr::V4(ip) => {
+            request.push(ATYP_IPV4);
+            request.extend_from_slice(&ip.octets());
+        }
+        IpAddr::V6(ip) => {
+            request.push(ATYP_IPV6);
+            request.extend_from_slice(&ip.octets());
+        }
+    }
+    request.extend_from_slice(&target.port().to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != VERSION {
+        return Err(invalid_data(format!("unsupported SOCKS version: {}", reply[0])));
+    }
+    if reply[1] != SUCCEEDED {
+        return Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!(
+                "SOCKS5 proxy {} failed to connect to {}: {}",
+                proxy,
+                target,
+                reply_message(reply[1])
+            ),
+        ));
+    }
+
+    // the address the proxy connected from is of no use, but must be read past
+    let addr_len = match reply[3] {
+        ATYP_IPV4 => 4,
+        ATYP_IPV6 => 16,
+        ATYP_DOMAIN_NAME => {
+            let mut len = [0u8; 1];
+            stream.read_exact(&mut len).await?;
+            usize::from(len[0])
+        }
+        atyp => return Err(invalid_data(format!("unsupported SOCKS5 address type: {}", atyp))),
+    };
+    let mut bound_addr = vec![0u8; addr_len + 2];
+    stream.read_exact(&mut bound_addr).await?;
+
+    debug!("SOCKS5 proxy {} connected to: {}", proxy, target);
+    Ok(stream)
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// The meaning of the reply field of a failed request
+fn reply_message(reply: u8) -> &'static str {
+    match reply {
+        1 => "general SOCKS server failure",
+        2 => "connection not allowed by ruleset",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown failure",
+    }
+}
+
+#[cfg(all(test, feature = "tokio-runtime"))]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{Ipv4Addr, TcpListener};
+    use std::str::FromStr;
+    use std::thread;
+
+    use tokio::runtime::Runtime;
+
+    use proto::op::{Message, MessageType};
+    use proto::rr::{Name, RData, Record};
+
+    use super::*;
+    use crate::config::{
+        LookupIpStrategy, NameServerConfig, Protocol, ProxyConfig, ResolverConfig, ResolverOpts,
+    };
+    use crate::{TokioAsyncResolver, TokioHandle};
+
+    /// Accepts a single connection as a SOCKS5 proxy, answering a single query itself, as though
+    ///  it was the target, returns the target requested of it
+    fn socks5_stub(listener: TcpListener) -> SocketAddr {
+        let (mut stream, _) = listener.accept().unwrap();
+
+        let mut greeting = [0u8; 3];
+        stream.read_exact(&mut greeting).unwrap();
+        assert_eq!(greeting, [VERSION, 1, NO_AUTHENTICATION]);
+        stream.write_all(&[VERSION, NO_AUTHENTICATION]).unwrap();
+
+        let mut request = [0u8; 10];
+        stream.read_exact(&mut request).unwrap();
+        assert_eq!(request[..4], [VERSION, CONNECT, 0, ATYP_IPV4]);
+        let ip = Ipv4Addr::new(request[4], request[5], request[6], request[7]);
+        let port = u16::from_be_bytes([request[8], request[9]]);
+        stream
+            .write_all(&[VERSION, SUCCEEDED, 0, ATYP_IPV4, 127, 0, 0, 1, 0, 53])
+            .unwrap();
+
+        let mut len = [0u8; 2];
+        stream.read_exact(&mut len).unwrap();
+        let mut query = vec![0u8; usize::from(u16::from_be_bytes(len))];
+        stream.read_exact(&mut query).unwrap();
+        let query = Message::from_vec(&query).unwrap();
+
+        let mut response = Message::new();
+        response.set_id(query.id());
+        response.set_message_type(MessageType::Response);
+        response.add_queries(query.queries().to_vec());
+        response.add_answer(Record::from_rdata(
+            query.queries()[0].name().clone(),
+            86400,
+            RData::A(Ipv4Addr::new(93, 184, 216, 34)),
+        ));
+        let response = response.to_vec().unwrap();
+        stream
+            .write_all(&(response.len() as u16).to_be_bytes())
+            .unwrap();
+        stream.write_all(&response).unwrap();
+
+        SocketAddr::new(IpAddr::V4(ip), port)
+    }
+
+    #[test]
+    fn test_lookup_through_socks5_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy = listener.local_addr().unwrap();
+        let stub = thread::spawn(move || socks5_stub(listener));
+
+        // a UDP name server, which is only reachable through the proxy, over TCP
+        let target = SocketAddr::from_str("192.0.2.53:53").unwrap();
+        let name_server = NameServerConfig {
+            socket_addr: target,
+            protocol: Protocol::Udp,
+            tls_dns_name: None,
+            trust_nx_responses: false,
+            signer: None,
+            weight: None,
+            bind_addr: None,
+            #[cfg(feature = "dns-over-rustls")]
+            tls_config: None,
+        };
+        let config = ResolverConfig::from_parts(None, vec![], vec![name_server]);
+
+        let mut options = ResolverOpts::default();
+        options.ip_strategy = LookupIpStrategy::Ipv4Only;
+        options.use_hosts_file = false;
+        options.proxy = Some(ProxyConfig { socket_addr: proxy });
+
+        let io_loop = Runtime::new().unwrap();
+        let resolver = TokioAsyncResolver::new(config, options, TokioHandle).unwrap();
+        let lookup = io_loop
+            .block_on(resolver.lookup_ip(Name::from_str("www.example.com.").unwrap()))
+            .expect("lookup through the proxy failed");
+
+        assert_eq!(
+            lookup.iter().collect::<Vec<_>>(),
+            vec![IpAddr::V4(Ipv4Addr::new(93, 184, 216, 34))]
+        );
+        assert_eq!(stub.join().unwrap(), target);
+    }
+}
@@ -80,8 +80,17 @@ impl Resolver {
         builder.enable_all();
 
         let runtime = builder.build()?;
-        let async_resolver =
-            AsyncResolver::new(config, options, TokioHandle).expect("failed to create resolver");
+        let eager_connect = options.eager_connect;
+        let async_resolver = AsyncResolver::new_with_conn(
+            config,
+            options,
+            TokioConnectionProvider::new(TokioHandle),
+        )?;
+
+        // there is no runtime to spawn on outside of the lookups, so the connections are opened now
+        if eager_connect {
+            runtime.block_on(async_resolver.warmup());
+        }
 
         Ok(Resolver {
             runtime: Mutex::new(runtime),
@@ -140,21 +149,29 @@ impl Resolver {
     lookup_fn!(reverse_lookup, lookup::ReverseLookup, IpAddr);
     lookup_fn!(ipv4_lookup, lookup::Ipv4Lookup);
     lookup_fn!(ipv6_lookup, lookup::Ipv6Lookup);
+    lookup_fn!(https_lookup, lookup::HttpsLookup);
     lookup_fn!(mx_lookup, lookup::MxLookup);
     lookup_fn!(ns_lookup, lookup::NsLookup);
     lookup_fn!(soa_lookup, lookup::SoaLookup);
     lookup_fn!(srv_lookup, lookup::SrvLookup);
     lookup_fn!(tlsa_lookup, lookup::TlsaLookup);
     lookup_fn!(txt_lookup, lookup::TxtLookup);
+    lookup_fn!(caa_lookup, lookup::CaaLookup);
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     #![allow(clippy::dbg_macro, clippy::print_stdout)]
 
     use std::net::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    use proto::op::{Message, MessageType};
+    use proto::rr::{Name, RData, Record};
 
     use super::*;
+    use crate::config::NameServerConfigGroup;
 
     fn require_send_sync<S: Send + Sync>() {}
 
@@ -163,6 +180,72 @@ mod tests {
         require_send_sync::<Resolver>();
     }
 
+    /// A name server on localhost which answers all A queries with 127.0.0.2, and all PTR queries
+    ///  with stub.example.com.
+    pub(crate) fn stub_name_server() -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+
+        thread::spawn(move || {
+            let mut buf = [0_u8; 4096];
+            while let Ok((len, src)) = socket.recv_from(&mut buf) {
+                let request = Message::from_vec(&buf[..len]).unwrap();
+
+                let mut response = Message::new();
+                response
+                    .set_id(request.id())
+                    .set_message_type(MessageType::Response)
+                    .set_recursion_desired(request.recursion_desired())
+                    .set_recursion_available(true)
+                    .add_queries(request.queries().to_vec());
+
+                for query in request.queries() {
+                    let rdata = match query.query_type() {
+                        RecordType::A => RData::A(Ipv4Addr::new(127, 0, 0, 2)),
+                        RecordType::PTR => {
+                            RData::PTR(Name::from_ascii("stub.example.com.").unwrap())
+                        }
+                        _ => continue,
+                    };
+                    response.add_answer(Record::from_rdata(query.name().clone(), 300, rdata));
+                }
+
+                socket.send_to(&response.to_vec().unwrap(), src).unwrap();
+            }
+        });
+
+        addr
+    }
+
+    #[test]
+    fn test_lookup_stub_name_server() {
+        let addr = stub_name_server();
+        let name_servers = NameServerConfigGroup::from_ips_clear(&[addr.ip()], addr.port(), true);
+        let config = ResolverConfig::from_parts(None, vec![], name_servers);
+        let resolver = Arc::new(Resolver::new(config, ResolverOpts::default()).unwrap());
+
+        let response = resolver.lookup_ip("www.example.com.").unwrap();
+        assert_eq!(
+            response.iter().collect::<Vec<_>>(),
+            vec![IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2))]
+        );
+
+        // the same Resolver may be used from many threads at once
+        let lookups: Vec<_> = (0..4)
+            .map(|i| {
+                let resolver = Arc::clone(&resolver);
+                thread::spawn(move || {
+                    let name = format!("host{}.example.com.", i);
+                    resolver.ipv4_lookup(name.as_str()).unwrap().iter().count()
+                })
+            })
+            .collect();
+
+        for lookup in lookups {
+            assert_eq!(lookup.join().unwrap(), 1);
+        }
+    }
+
     #[test]
     fn test_lookup() {
         let resolver = Resolver::new(ResolverConfig::default(), ResolverOpts::default()).unwrap();
@@ -185,6 +268,36 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_reverse_lookup_stub_name_server() {
+        let addr = stub_name_server();
+        let name_servers = NameServerConfigGroup::from_ips_clear(&[addr.ip()], addr.port(), true);
+        let config = ResolverConfig::from_parts(None, vec![], name_servers);
+        let mut options = ResolverOpts::default();
+        options.use_hosts_file = false;
+        let resolver = Resolver::new(config, options).unwrap();
+
+        let lookups = vec![
+            (IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)), "1.2.0.192.in-addr.arpa."),
+            (
+                IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0x567, 0x89ab)),
+                "b.a.9.8.7.6.5.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa.",
+            ),
+        ];
+
+        for (ip, reverse_name) in lookups {
+            let response = resolver.reverse_lookup(ip).unwrap();
+            assert_eq!(
+                response.iter().collect::<Vec<_>>(),
+                vec![&Name::from_ascii("stub.example.com.").unwrap()]
+            );
+
+            // the PTR query was for the reversed address
+            let records = response.as_lookup().records();
+            assert_eq!(records[0].name(), &Name::from_ascii(reverse_name).unwrap());
+        }
+    }
+
     #[test]
     #[ignore]
     #[cfg(any(unix, target_os = "windows"))]
@@ -0,0 +1,146 @@
+// Copyright 2015-2020 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A Resolver running on a thread of its own, for embedding without a futures runtime
+
+use std::io;
+use std::sync::mpsc::{self, Receiver};
+
+use proto::op::Query;
+use tokio::runtime::{self, Runtime};
+
+use crate::config::{ResolverConfig, ResolverOpts};
+use crate::error::*;
+use crate::lookup::Lookup;
+use crate::TokioAsyncResolver;
+
+/// The ResolverService performs DNS queries on a background thread, returning each result on a
+///  channel.
+///
+/// Unlike the [`Resolver`](crate::Resolver), which blocks the calling thread until the lookup is
+///  done, queries are submitted without waiting. The service owns the Tokio Runtime, with a single
+///  worker thread driving the I/O of all the lookups, so the caller needs no knowledge of futures.
+///
+/// *Note*: the ResolverService must not be dropped from within an asynchronous context.
+pub struct ResolverService {
+    runtime: Runtime,
+    async_resolver: TokioAsyncResolver,
+}
+
+impl ResolverService {
+    /// Starts the background thread for a Resolver with the specified configuration.
+    ///
+    /// # Arguments
+    /// * `config` - configuration for the resolver
+    /// * `options` - resolver options for performing lookups
+    ///
+    /// # Returns
+    ///
+    /// A new `ResolverService` or an error if there was an error with the configuration.
+    pub fn new(config: ResolverConfig, options: ResolverOpts) -> io::Result<Self> {
+        let runtime = runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("trust-dns-resolver")
+            .enable_all()
+            .build()?;
+
+        // the resolver spawns its background work on the runtime, e.g. for eager connections
+        let async_resolver = {
+            let _guard = runtime.enter();
+            TokioAsyncResolver::tokio(config, options)?
+        };
+
+        Ok(ResolverService {
+            runtime,
+            async_resolver,
+        })
+    }
+
+    /// Constructs a new ResolverService with default config and default options.
+    ///
+    /// See [`ResolverConfig::default`] and [`ResolverOpts::default`] for more information.
+    pub fn default() -> io::Result<Self> {
+        Self::new(ResolverConfig::default(), ResolverOpts::default())
+    }
+
+    /// Submits the query, the result is sent on the returned channel once the lookup is done
+    ///
+    /// Exactly one result is sent for each query, the channel is then closed. The receiver may be
+    ///  dropped if the result is no longer wanted.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - the name and type of the records to lookup, the name is subject to the search
+    ///             domains of the configuration unless it is fully qualified
+    pub fn query(&self, query: Query) -> Receiver<ResolveResult<Lookup>> {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let lookup = self.async_resolver.lookup(
+            query.name().clone(),
+            query.query_type(),
+            Default::default(),
+        );
+
+        self.runtime.spawn(async move {
+            let result = lookup.await;
+            if sender.send(result).is_err() {
+                debug!("receiver dropped before the lookup completed");
+            }
+        });
+
+        receiver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::Duration;
+
+    use proto::rr::{Name, RData, RecordType};
+
+    use super::*;
+    use crate::config::NameServerConfigGroup;
+    use crate::resolver::tests::stub_name_server;
+
+    fn require_send_sync<S: Send + Sync>() {}
+
+    #[test]
+    fn test_resolver_service_sendable() {
+        require_send_sync::<ResolverService>();
+    }
+
+    #[test]
+    fn test_resolver_service_query() {
+        let addr = stub_name_server();
+        let name_servers = NameServerConfigGroup::from_ips_clear(&[addr.ip()], addr.port(), true);
+        let config = ResolverConfig::from_parts(None, vec![], name_servers);
+        let service = ResolverService::new(config, ResolverOpts::default()).unwrap();
+
+        let receivers: Vec<_> = (0..4)
+            .map(|i| {
+                let name = Name::from_ascii(&format!("host{}.example.com.", i)).unwrap();
+                service.query(Query::query(name, RecordType::A))
+            })
+            .collect();
+
+        // the queries are all in flight at once, and answered on their own channel
+        for receiver in receivers {
+            let lookup = receiver
+                .recv_timeout(Duration::from_secs(5))
+                .expect("no result on the channel")
+                .expect("lookup failed");
+            let addresses: Vec<IpAddr> = lookup
+                .iter()
+                .filter_map(|rdata| match rdata {
+                    RData::A(ip) => Some(IpAddr::V4(*ip)),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(addresses, vec![IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2))]);
+        }
+    }
+}
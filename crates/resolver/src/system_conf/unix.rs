@@ -65,6 +65,9 @@ fn into_resolver_config(
             protocol: Protocol::Udp,
             tls_dns_name: None,
             trust_nx_responses: false,
+            signer: None,
+            weight: None,
+            bind_addr: None,
             #[cfg(feature = "dns-over-rustls")]
             tls_config: None,
         });
@@ -73,6 +76,9 @@ fn into_resolver_config(
             protocol: Protocol::Tcp,
             tls_dns_name: None,
             trust_nx_responses: false,
+            signer: None,
+            weight: None,
+            bind_addr: None,
             #[cfg(feature = "dns-over-rustls")]
             tls_config: None,
         });
@@ -98,6 +104,9 @@ fn into_resolver_config(
     options.ndots = parsed_config.ndots as usize;
     options.timeout = Duration::from_secs(u64::from(parsed_config.timeout));
     options.attempts = parsed_config.attempts as usize;
+    if parsed_config.rotate {
+        options.server_ordering = ServerOrderingStrategy::RoundRobin;
+    }
 
     Ok((config, options))
 }
@@ -122,6 +131,9 @@ mod tests {
                 protocol: Protocol::Udp,
                 tls_dns_name: None,
                 trust_nx_responses: false,
+                signer: None,
+                weight: None,
+                bind_addr: None,
                 #[cfg(feature = "dns-over-rustls")]
                 tls_config: None,
             },
@@ -130,6 +142,9 @@ mod tests {
                 protocol: Protocol::Tcp,
                 tls_dns_name: None,
                 trust_nx_responses: false,
+                signer: None,
+                weight: None,
+                bind_addr: None,
                 #[cfg(feature = "dns-over-rustls")]
                 tls_config: None,
             },
@@ -180,6 +195,39 @@ mod tests {
         assert_eq!(ResolverOpts::default(), parsed.1);
     }
 
+    #[test]
+    fn test_options() {
+        let parsed = parse_resolv_conf(
+            "\
+# sample resolv.conf
+nameserver 127.0.0.1
+nameserver ::1
+search example.com example.net
+options ndots:3 timeout:7 attempts:4 rotate
+",
+        )
+        .expect("failed");
+
+        let mut cfg = empty_config();
+        for nameserver in nameserver_config("127.0.0.1")
+            .iter()
+            .chain(nameserver_config("::1").iter())
+        {
+            cfg.add_name_server(nameserver.clone());
+        }
+        cfg.add_search(Name::from_str("example.com.").unwrap());
+        cfg.add_search(Name::from_str("example.net.").unwrap());
+        assert_eq!(cfg.name_servers(), parsed.0.name_servers());
+        assert_eq!(cfg.search(), parsed.0.search());
+
+        let mut opts = ResolverOpts::default();
+        opts.ndots = 3;
+        opts.timeout = Duration::from_secs(7);
+        opts.attempts = 4;
+        opts.server_ordering = ServerOrderingStrategy::RoundRobin;
+        assert_eq!(opts, parsed.1);
+    }
+
     #[test]
     fn test_read_resolv_conf() {
         read_resolv_conf(format!("{}/resolv.conf-simple", tests_dir())).expect("simple failed");
@@ -15,7 +15,9 @@ use ipconfig::get_adapters;
 
 use proto::rr::Name;
 
-use crate::config::{NameServerConfig, Protocol, ResolverConfig, ResolverOpts};
+use crate::config::{
+    NameServerConfig, Protocol, ResolverConfig, ResolverOpts, ServerOrderingStrategy,
+};
 use crate::error::*;
 
 /// Returns the name servers of the computer (of all adapters)
@@ -33,6 +35,9 @@ fn get_name_servers() -> ResolveResult<Vec<NameServerConfig>> {
             protocol: Protocol::Udp,
             tls_dns_name: None,
             trust_nx_responses: false,
+            signer: None,
+            weight: None,
+            bind_addr: None,
             #[cfg(feature = "dns-over-rustls")]
             tls_config: None,
         });
@@ -41,6 +46,9 @@ fn get_name_servers() -> ResolveResult<Vec<NameServerConfig>> {
             protocol: Protocol::Tcp,
             tls_dns_name: None,
             trust_nx_responses: false,
+            signer: None,
+            weight: None,
+            bind_addr: None,
             #[cfg(feature = "dns-over-rustls")]
             tls_config: None,
         });
@@ -63,11 +71,9 @@ pub fn read_system_conf() -> ResolveResult<(ResolverConfig, ResolverOpts)> {
 
     let config = ResolverConfig::from_parts(Some(domain), search_list, name_servers);
 
-    let rotate = is_round_robin_enabled()?;
-
-    let opts = ResolverOpts {
-        rotate,
-        ..Default::default()
-    };
+    let mut opts = ResolverOpts::default();
+    if is_round_robin_enabled()? {
+        opts.server_ordering = ServerOrderingStrategy::RoundRobin;
+    }
     Ok((config, opts))
 }
@@ -28,11 +28,13 @@ cfg_if! {
 #[cfg(any(feature = "dns-over-native-tls", feature = "dns-over-rustls"))]
 #[cfg(test)]
 mod tests {
-    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use std::io::Read;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, TcpListener};
+    use std::thread;
 
     use tokio::runtime::Runtime;
 
-    use crate::config::{ResolverConfig, ResolverOpts};
+    use crate::config::{NameServerConfig, Protocol, ResolverConfig, ResolverOpts};
     use crate::{TokioAsyncResolver, TokioHandle};
 
     fn tls_test(config: ResolverConfig) {
@@ -72,4 +74,45 @@ mod tests {
     fn test_quad9_tls() {
         tls_test(ResolverConfig::quad9_tls())
     }
+
+    #[test]
+    fn test_pinned_tls() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let socket_addr = listener.local_addr().unwrap();
+
+        // reads the type of the first record, the handshake, before the connection is dropped
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut content_type = [0u8; 1];
+            stream.read_exact(&mut content_type).unwrap();
+            content_type[0]
+        });
+
+        // the name can not be resolved, only the pinned address is connected to
+        let name_server = NameServerConfig {
+            socket_addr,
+            protocol: Protocol::Tls,
+            tls_dns_name: Some("dns.invalid".to_string()),
+            trust_nx_responses: false,
+            signer: None,
+            weight: None,
+            bind_addr: None,
+            #[cfg(feature = "dns-over-rustls")]
+            tls_config: None,
+        };
+        let config = ResolverConfig::from_parts(None, vec![], vec![name_server]);
+
+        let mut options = ResolverOpts::default();
+        options.attempts = 1;
+        options.use_hosts_file = false;
+
+        let io_loop = Runtime::new().unwrap();
+        let resolver = TokioAsyncResolver::new(config, options, TokioHandle).unwrap();
+        assert!(io_loop
+            .block_on(resolver.lookup_ip("www.example.com."))
+            .is_err());
+
+        // 22 is the TLS handshake content type, the ClientHello
+        assert_eq!(server.join().unwrap(), 22);
+    }
 }
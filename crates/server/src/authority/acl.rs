@@ -0,0 +1,226 @@
+// Copyright 2015-2020 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Access control lists, restricting the clients which may query, transfer or update a zone
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+/// An operation on a zone which is subject to its `Acl`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AclOperation {
+    /// Queries of the records of the zone
+    Query,
+    /// Transfers of the zone, AXFR and IXFR
+    Transfer,
+    /// Dynamic updates of the zone, RFC 2136
+    Update,
+}
+
+/// An IPv4 or IPv6 network prefix, e.g. `192.0.2.0/24` or `2001:db8::/32`
+///
+/// An address without a prefix length is a prefix of only that address.
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(try_from = "String")]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl Cidr {
+    /// Constructs the prefix of `prefix_len` bits of `addr`, the remaining bits are ignored
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Result<Self, String> {
+        let max_len = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix_len > max_len {
+            return Err(format!("prefix length too long for {}: {}", addr, prefix_len));
+        }
+
+        Ok(Cidr { addr, prefix_len })
+    }
+
+    /// The address of the prefix
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// The number of leading bits of the address which make up the prefix
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// True if the address is within the prefix
+    ///
+    /// IPv4 addresses mapped into IPv6, e.g. of clients of a server listening on `::`, are
+    ///  matched against IPv4 prefixes.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let prefix_len = u32::from(self.prefix_len);
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::max_value().checked_shl(32 - prefix_len).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::max_value().checked_shl(128 - prefix_len).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            (IpAddr::V4(_), IpAddr::V6(ip)) => {
+                mapped_ipv4(ip).map_or(false, |ip| self.contains(IpAddr::V4(ip)))
+            }
+            (IpAddr::V6(_), IpAddr::V4(_)) => false,
+        }
+    }
+}
+
+/// The IPv4 address of an IPv4-mapped IPv6 address, `::ffff:a.b.c.d`
+fn mapped_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    match ip.segments() {
+        [0, 0, 0, 0, 0, 0xffff, high, low] => {
+            Some(Ipv4Addr::from((u32::from(high) << 16) | u32::from(low)))
+        }
+        _ => None,
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_len) = match s.find('/') {
+            Some(slash) => (&s[..slash], Some(&s[slash + 1..])),
+            None => (s, None),
+        };
+
+        let addr = IpAddr::from_str(addr).map_err(|e| format!("bad address in {}: {}", s, e))?;
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => u8::from_str(prefix_len)
+                .map_err(|e| format!("bad prefix length in {}: {}", s, e))?,
+            None if addr.is_ipv4() => 32,
+            None => 128,
+        };
+
+        Cidr::new(addr, prefix_len)
+    }
+}
+
+impl TryFrom<String> for Cidr {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Cidr::from_str(&s)
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// The prefixes of the clients allowed to perform each operation on a zone, see `Catalog::set_acl`
+///
+/// Clients outside of the prefixes of an operation are refused. Without any prefixes configured
+///  for it, all clients may query and update the zone, though updates must still be authorized by
+///  the zone itself, while no client may transfer it.
+#[derive(Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct Acl {
+    /// The prefixes of the clients allowed to query the zone, all if unset
+    pub query: Option<Vec<Cidr>>,
+    /// The prefixes of the clients allowed to transfer the zone, none if unset
+    pub transfer: Option<Vec<Cidr>>,
+    /// The prefixes of the clients allowed to update the zone, all if unset
+    pub update: Option<Vec<Cidr>>,
+}
+
+impl Acl {
+    /// Allows the clients within the prefix to perform the operation
+    pub fn allow(&mut self, operation: AclOperation, cidr: Cidr) {
+        let prefixes = match operation {
+            AclOperation::Query => &mut self.query,
+            AclOperation::Transfer => &mut self.transfer,
+            AclOperation::Update => &mut self.update,
+        };
+
+        prefixes.get_or_insert_with(Vec::new).push(cidr);
+    }
+
+    /// True if the client may perform the operation
+    pub fn is_allowed(&self, operation: AclOperation, client: IpAddr) -> bool {
+        let prefixes = match operation {
+            AclOperation::Query => &self.query,
+            AclOperation::Transfer => &self.transfer,
+            AclOperation::Update => &self.update,
+        };
+
+        match prefixes {
+            Some(prefixes) => prefixes.iter().any(|cidr| cidr.contains(client)),
+            None => operation != AclOperation::Transfer,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        IpAddr::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_cidr_contains() {
+        let v4 = Cidr::from_str("192.0.2.0/24").unwrap();
+        assert!(v4.contains(ip("192.0.2.1")));
+        assert!(v4.contains(ip("::ffff:192.0.2.1")));
+        assert!(!v4.contains(ip("192.0.3.1")));
+        assert!(!v4.contains(ip("2001:db8::1")));
+
+        let v6 = Cidr::from_str("2001:db8::/32").unwrap();
+        assert!(v6.contains(ip("2001:db8:1::1")));
+        assert!(!v6.contains(ip("2001:db9::1")));
+        assert!(!v6.contains(ip("192.0.2.1")));
+
+        // the default route and single addresses
+        assert!(Cidr::from_str("0.0.0.0/0").unwrap().contains(ip("203.0.113.1")));
+        assert!(Cidr::from_str("::/0").unwrap().contains(ip("2001:db8::1")));
+        assert!(Cidr::from_str("192.0.2.1").unwrap().contains(ip("192.0.2.1")));
+        assert!(!Cidr::from_str("192.0.2.1").unwrap().contains(ip("192.0.2.2")));
+    }
+
+    #[test]
+    fn test_cidr_from_str() {
+        let cidr = Cidr::from_str("2001:db8::/32").unwrap();
+        assert_eq!(cidr.addr(), ip("2001:db8::"));
+        assert_eq!(cidr.prefix_len(), 32);
+        assert_eq!(cidr.to_string(), "2001:db8::/32");
+
+        assert!(Cidr::from_str("192.0.2.0/33").is_err());
+        assert!(Cidr::from_str("192.0.2.0/").is_err());
+        assert!(Cidr::from_str("example.com/24").is_err());
+    }
+
+    #[test]
+    fn test_acl_defaults() {
+        let mut acl = Acl::default();
+        assert!(acl.is_allowed(AclOperation::Query, ip("192.0.2.1")));
+        assert!(acl.is_allowed(AclOperation::Update, ip("192.0.2.1")));
+        assert!(!acl.is_allowed(AclOperation::Transfer, ip("192.0.2.1")));
+
+        acl.allow(AclOperation::Transfer, Cidr::from_str("192.0.2.0/24").unwrap());
+        acl.allow(AclOperation::Query, Cidr::from_str("2001:db8::/32").unwrap());
+        assert!(acl.is_allowed(AclOperation::Transfer, ip("192.0.2.1")));
+        assert!(!acl.is_allowed(AclOperation::Transfer, ip("198.51.100.1")));
+        assert!(acl.is_allowed(AclOperation::Query, ip("2001:db8::1")));
+        assert!(!acl.is_allowed(AclOperation::Query, ip("192.0.2.1")));
+    }
+}
@@ -36,6 +36,13 @@ pub enum AuthLookup {
     },
     /// Soa only differs from Records in that the lifetime on the name is from the authority, and not the query
     SOA(LookupRecords),
+    /// A referral to a delegated child zone, the name is at or below a zone cut
+    Referral {
+        /// The NS records of the child zone, for the authority section
+        ns: LookupRecords,
+        /// The address records of the name servers within the zone, i.e. the glue
+        glue: Option<LookupRecords>,
+    },
     /// An axfr starts with soa, chained to all the records, then another soa...
     AXFR {
         /// The first SOA record in an AXFR response
@@ -56,6 +63,16 @@ impl AuthLookup {
         }
     }
 
+    /// Construct a referral to the child zone with the glue for its name servers
+    pub fn referral(ns: LookupRecords, glue: Option<LookupRecords>) -> Self {
+        AuthLookup::Referral { ns, glue }
+    }
+
+    /// Returns true if this is a referral to a child zone, the records are not authoritative
+    pub fn is_referral(&self) -> bool {
+        matches!(*self, AuthLookup::Referral { .. })
+    }
+
     /// Returns true if either the associated Records are empty, or this is a NameExists or NxDomain
     pub fn is_empty(&self) -> bool {
         // TODO: this needs to be cheap
@@ -90,6 +107,7 @@ impl AuthLookup {
                 ref mut additionals,
                 ..
             } => additionals.take(),
+            AuthLookup::Referral { ref mut glue, .. } => glue.take(),
             _ => None,
         }
     }
@@ -109,6 +127,10 @@ impl LookupObject for AuthLookup {
         let additionals = AuthLookup::take_additionals(self);
         additionals.map(|a| Box::new(a) as Box<dyn LookupObject>)
     }
+
+    fn is_referral(&self) -> bool {
+        AuthLookup::is_referral(self)
+    }
 }
 
 impl Default for AuthLookup {
@@ -125,7 +147,9 @@ impl<'a> IntoIterator for &'a AuthLookup {
         match self {
             AuthLookup::Empty => AuthLookupIter::Empty,
             // TODO: what about the additionals? is IntoIterator a bad idea?
-            AuthLookup::Records { answers: r, .. } | AuthLookup::SOA(r) => {
+            AuthLookup::Records { answers: r, .. }
+            | AuthLookup::SOA(r)
+            | AuthLookup::Referral { ns: r, .. } => {
                 AuthLookupIter::Records(r.into_iter())
             }
             AuthLookup::AXFR {
@@ -7,14 +7,22 @@
 
 //! All authority related types
 use std::future::Future;
+use std::net::SocketAddr;
 use std::pin::Pin;
+use std::time::Instant;
 
-use crate::client::op::LowerQuery;
+use crate::client::op::{LowerQuery, ResponseCode};
 use crate::client::proto::rr::dnssec::rdata::key::KEY;
-use crate::client::rr::dnssec::{DnsSecError, DnsSecResult, Signer, SupportedAlgorithms};
-use crate::client::rr::{LowerName, Name, RecordType};
+use crate::client::rr::dnssec::{
+    DnsSecError, DnsSecResult, Signer, SupportedAlgorithms, TsigKey, TsigResponder,
+};
+use crate::client::rr::rdata::SOA;
+use crate::client::rr::{DNSClass, LowerName, Name, Record, RecordType};
 
-use crate::authority::{LookupError, MessageRequest, UpdateResult, ZoneType};
+use crate::authority::notify;
+use crate::authority::{
+    Axfr, Ixfr, LookupError, MessageRequest, Refresh, UpdateResult, ZoneType,
+};
 
 /// Authority implementations can be used with a `Catalog`
 pub trait Authority: Send {
@@ -35,6 +43,67 @@ pub trait Authority: Send {
     /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
     fn origin(&self) -> &LowerName;
 
+    /// The DNSClass of the records in this zone, queries of other classes are refused
+    fn class(&self) -> DNSClass {
+        DNSClass::IN
+    }
+
+    /// Returns the changes to the zone since the specified SOA serial, for an incremental zone
+    ///  transfer, IXFR.
+    ///
+    /// `Ixfr::Full` is returned if the changes are not known, in which case the entire zone should
+    ///  be transferred with an AXFR. This is the default for Authorities which do not track changes.
+    fn ixfr(&self, _serial: u32) -> Ixfr {
+        Ixfr::Full
+    }
+
+    /// Returns all records of the zone for a full zone transfer, AXFR, starting and ending with
+    ///  the SOA of the zone.
+    ///
+    /// The default refuses the transfer, for Authorities which do not support AXFR.
+    fn axfr(&self) -> Result<Axfr, LookupError> {
+        Err(LookupError::from(ResponseCode::Refused))
+    }
+
+    /// The addresses of Secondaries which are sent a NOTIFY when the serial of the zone changes
+    fn notify_targets(&self) -> &[SocketAddr] {
+        &[]
+    }
+
+    /// Sends a NOTIFY, RFC 1996, for this zone to each of the `notify_targets`
+    ///
+    /// The returned future completes once all Secondaries have responded, or the requests have
+    ///  timed out, failures are only logged. It must be run on a Tokio runtime.
+    fn notify_secondaries(&self) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(notify::notify_secondaries(
+            self.origin().into(),
+            self.notify_targets().to_vec(),
+        ))
+    }
+
+    /// The addresses of the Primaries from which this zone, a Secondary, is refreshed
+    fn primaries(&self) -> &[SocketAddr] {
+        &[]
+    }
+
+    /// Decides whether this zone, a Secondary, is to be transferred from its Primary, given the
+    ///  SOA the Primary responded with, see `Refresh::check`
+    ///
+    /// The default always transfers, for Authorities which do not know the serial of the zone.
+    fn refresh(&self, _primary_soa: &SOA) -> Refresh {
+        Refresh::Transfer
+    }
+
+    /// Replaces the records of this zone, a Secondary, with those of an AXFR from its Primary
+    ///
+    /// The default refuses the transfer, for Authorities which can not be Secondaries.
+    fn transfer(&mut self, _records: Vec<Record>) -> UpdateResult<()> {
+        Err(ResponseCode::NotImp)
+    }
+
+    /// Records when this zone, a Secondary, was last refreshed from its Primary
+    fn set_refreshed(&mut self, _refreshed: Instant) {}
+
     /// Looks up all Resource Records matching the giving `Name` and `RecordType`.
     ///
     /// # Arguments
@@ -75,6 +144,21 @@ pub trait Authority: Send {
         supported_algorithms: SupportedAlgorithms,
     ) -> Pin<Box<dyn Future<Output = Result<Self::Lookup, LookupError>> + Send>>;
 
+    /// Using the specified query, which has already been forwarded `hops` times between servers,
+    ///  perform a lookup against this zone.
+    ///
+    /// Only authorities which forward the query need the hops, to break forwarding loops, by
+    ///  default this is a `search`.
+    fn forward(
+        &self,
+        query: &LowerQuery,
+        _hops: u8,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Lookup, LookupError>> + Send>> {
+        self.search(query, is_secure, supported_algorithms)
+    }
+
     /// Get the NS, NameServer, record for the zone
     fn ns(
         &self,
@@ -131,6 +215,19 @@ pub trait Authority: Send {
         )
     }
 
+    /// Returns the SOA record for the authority section of a negative response
+    ///
+    /// The TTL of the SOA should be the lesser of its own and the minimum of the SOA, see
+    ///  [RFC 2308](https://tools.ietf.org/html/rfc2308#section-3), by default this is the same
+    ///  as `soa_secure()`.
+    fn negative_soa(
+        &self,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Lookup, LookupError>> + Send>> {
+        self.soa_secure(is_secure, supported_algorithms)
+    }
+
     // TODO: this should probably be a general purpose higher level component?
     /// Add a (Sig0) key that is authorized to perform updates against this authority
     fn add_update_auth_key(&mut self, _name: Name, _key: KEY) -> DnsSecResult<()> {
@@ -139,6 +236,24 @@ pub trait Authority: Send {
         ))
     }
 
+    /// Add a TSIG key that is authorized to perform updates and zone transfers against this
+    ///  authority, once any key is added, unsigned updates and transfers are refused
+    fn add_tsig_key(&mut self, _key: TsigKey) -> DnsSecResult<()> {
+        Err(DnsSecError::from("TSIG not supported by this Authority type"))
+    }
+
+    /// Checks that the request for a zone transfer is permitted, i.e. carries a valid TSIG if
+    ///  TSIG keys were added, returning `NotAuth` otherwise
+    fn authorize_axfr(&self, _request: &MessageRequest) -> UpdateResult<()> {
+        Ok(())
+    }
+
+    /// Verifies the TSIG of the request, returning the responder to sign the response with, None
+    ///  if the request is not signed with TSIG
+    fn tsig_responder(&self, _request: &MessageRequest) -> Option<TsigResponder> {
+        None
+    }
+
     /// Add Signer
     fn add_zone_signing_key(&mut self, _signer: Signer) -> DnsSecResult<()> {
         Err(DnsSecError::from(
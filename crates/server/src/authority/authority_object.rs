@@ -8,6 +8,7 @@
 //! All authority related types
 
 use std::future::Future;
+use std::net::SocketAddr;
 use std::pin::Pin;
 use std::sync::{Arc, RwLock};
 use std::task::{Context, Poll};
@@ -17,10 +18,13 @@ use log::debug;
 
 use crate::client::op::LowerQuery;
 use crate::client::proto::rr::dnssec::rdata::key::KEY;
-use crate::client::rr::dnssec::{DnsSecError, DnsSecResult, Signer, SupportedAlgorithms};
-use crate::client::rr::{LowerName, Name, Record, RecordType};
+use crate::client::rr::dnssec::{
+    DnsSecError, DnsSecResult, Signer, SupportedAlgorithms, TsigKey, TsigResponder,
+};
+use crate::client::rr::{DNSClass, LowerName, Name, Record, RecordType};
 
-use crate::authority::{Authority, LookupError, MessageRequest, UpdateResult, ZoneType};
+use crate::authority::notify;
+use crate::authority::{Authority, Axfr, Ixfr, LookupError, MessageRequest, UpdateResult, ZoneType};
 
 /// An Object safe Authority
 pub trait AuthorityObject: Send + Sync {
@@ -39,6 +43,27 @@ pub trait AuthorityObject: Send + Sync {
     /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
     fn origin(&self) -> LowerName;
 
+    /// The DNSClass of the records in this zone, queries of other classes are refused
+    fn class(&self) -> DNSClass;
+
+    /// Returns the changes to the zone since the specified SOA serial, for an incremental zone
+    ///  transfer, IXFR.
+    fn ixfr(&self, serial: u32) -> Ixfr;
+
+    /// Returns all records of the zone for a full zone transfer, AXFR, starting and ending with
+    ///  the SOA of the zone.
+    fn axfr(&self) -> Result<Axfr, LookupError>;
+
+    /// The addresses of Secondaries which are sent a NOTIFY when the serial of the zone changes
+    fn notify_targets(&self) -> Vec<SocketAddr>;
+
+    /// Sends a NOTIFY, RFC 1996, for this zone to each of the `notify_targets`
+    fn notify_secondaries(&self) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+
+    /// Checks the SOA of this zone, a Secondary, against that of its Primaries, transferring
+    ///  the zone if the Primary has a newer serial
+    fn refresh_secondary(&self) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+
     /// Looks up all Resource Records matching the giving `Name` and `RecordType`.
     ///
     /// # Arguments
@@ -79,6 +104,20 @@ pub trait AuthorityObject: Send + Sync {
         supported_algorithms: SupportedAlgorithms,
     ) -> BoxedLookupFuture;
 
+    /// Using the specified query, which has already been forwarded `hops` times between servers,
+    ///  perform a lookup against this zone.
+    ///
+    /// By default this is a `search`, see `Authority::forward`.
+    fn forward(
+        &self,
+        query: &LowerQuery,
+        _hops: u8,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> BoxedLookupFuture {
+        self.search(query, is_secure, supported_algorithms)
+    }
+
     /// Get the NS, NameServer, record for the zone
     fn ns(&self, is_secure: bool, supported_algorithms: SupportedAlgorithms) -> BoxedLookupFuture {
         self.lookup(
@@ -131,6 +170,16 @@ pub trait AuthorityObject: Send + Sync {
         )
     }
 
+    /// Returns the SOA record for the authority section of a negative response, see
+    ///  `Authority::negative_soa()`
+    fn negative_soa(
+        &self,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> BoxedLookupFuture {
+        self.soa_secure(is_secure, supported_algorithms)
+    }
+
     // TODO: this should probably be a general purpose higher level component?
     /// Add a (Sig0) key that is authorized to perform updates against this authority
     fn add_update_auth_key(&self, _name: Name, _key: KEY) -> DnsSecResult<()> {
@@ -139,6 +188,24 @@ pub trait AuthorityObject: Send + Sync {
         ))
     }
 
+    /// Add a TSIG key that is authorized to perform updates and zone transfers against this
+    ///  authority, once any key is added, unsigned updates and transfers are refused
+    fn add_tsig_key(&self, _key: TsigKey) -> DnsSecResult<()> {
+        Err(DnsSecError::from("TSIG not supported by this Authority type"))
+    }
+
+    /// Checks that the request for a zone transfer is permitted, i.e. carries a valid TSIG if
+    ///  TSIG keys were added, returning `NotAuth` otherwise
+    fn authorize_axfr(&self, _request: &MessageRequest) -> UpdateResult<()> {
+        Ok(())
+    }
+
+    /// Verifies the TSIG of the request, returning the responder to sign the response with, None
+    ///  if the request is not signed with TSIG
+    fn tsig_responder(&self, _request: &MessageRequest) -> Option<TsigResponder> {
+        None
+    }
+
     /// Add Signer
     fn add_zone_signing_key(&self, _signer: Signer) -> DnsSecResult<()> {
         Err(DnsSecError::from(
@@ -184,6 +251,36 @@ where
         Authority::origin(&*self.read().expect("poisoned")).clone()
     }
 
+    /// The DNSClass of the records in this zone, queries of other classes are refused
+    fn class(&self) -> DNSClass {
+        Authority::class(&*self.read().expect("poisoned"))
+    }
+
+    /// Returns the changes to the zone since the specified SOA serial
+    fn ixfr(&self, serial: u32) -> Ixfr {
+        Authority::ixfr(&*self.read().expect("poisoned"), serial)
+    }
+
+    /// Returns all records of the zone for a full zone transfer
+    fn axfr(&self) -> Result<Axfr, LookupError> {
+        Authority::axfr(&*self.read().expect("poisoned"))
+    }
+
+    /// The addresses of Secondaries which are sent a NOTIFY when the serial of the zone changes
+    fn notify_targets(&self) -> Vec<SocketAddr> {
+        Authority::notify_targets(&*self.read().expect("poisoned")).to_vec()
+    }
+
+    /// Sends a NOTIFY for this zone to each of the `notify_targets`
+    fn notify_secondaries(&self) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Authority::notify_secondaries(&*self.read().expect("poisoned"))
+    }
+
+    /// Checks the SOA of this zone against that of its Primaries, transferring it if outdated
+    fn refresh_secondary(&self) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(notify::refresh_secondary(Arc::clone(self)))
+    }
+
     /// Looks up all Resource Records matching the giving `Name` and `RecordType`.
     ///
     /// # Arguments
@@ -233,6 +330,19 @@ where
         BoxedLookupFuture::from(lookup.map_ok(|l| Box::new(l) as Box<dyn LookupObject>))
     }
 
+    fn forward(
+        &self,
+        query: &LowerQuery,
+        hops: u8,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> BoxedLookupFuture {
+        let this = self.read().expect("poisoned");
+        debug!("forwarding {} on {}, hops: {}", query, this.origin(), hops);
+        let lookup = Authority::forward(&*this, query, hops, is_secure, supported_algorithms);
+        BoxedLookupFuture::from(lookup.map_ok(|l| Box::new(l) as Box<dyn LookupObject>))
+    }
+
     /// Return the NSEC records based on the given name
     ///
     /// # Arguments
@@ -255,10 +365,35 @@ where
         BoxedLookupFuture::from(lookup.map_ok(|l| Box::new(l) as Box<dyn LookupObject>))
     }
 
+    fn negative_soa(
+        &self,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> BoxedLookupFuture {
+        let lookup = Authority::negative_soa(
+            &*self.read().expect("poisoned"),
+            is_secure,
+            supported_algorithms,
+        );
+        BoxedLookupFuture::from(lookup.map_ok(|l| Box::new(l) as Box<dyn LookupObject>))
+    }
+
     fn add_update_auth_key(&self, name: Name, key: KEY) -> DnsSecResult<()> {
         Authority::add_update_auth_key(&mut *self.write().expect("poisoned"), name, key)
     }
 
+    fn add_tsig_key(&self, key: TsigKey) -> DnsSecResult<()> {
+        Authority::add_tsig_key(&mut *self.write().expect("poisoned"), key)
+    }
+
+    fn authorize_axfr(&self, request: &MessageRequest) -> UpdateResult<()> {
+        Authority::authorize_axfr(&*self.read().expect("poisoned"), request)
+    }
+
+    fn tsig_responder(&self, request: &MessageRequest) -> Option<TsigResponder> {
+        Authority::tsig_responder(&*self.read().expect("poisoned"), request)
+    }
+
     fn add_zone_signing_key(&self, signer: Signer) -> DnsSecResult<()> {
         Authority::add_zone_signing_key(&mut *self.write().expect("poisoned"), signer)
     }
@@ -280,6 +415,12 @@ pub trait LookupObject: Send {
     ///
     /// it is acceptable for this to return None after the first call.
     fn take_additionals(&mut self) -> Option<Box<dyn LookupObject>>;
+
+    /// Returns true if the records are a referral to a child zone, i.e. NS records for the
+    ///  authority section with the glue as the additionals
+    fn is_referral(&self) -> bool {
+        false
+    }
 }
 
 /// A lookup that returns no records
@@ -0,0 +1,76 @@
+// Copyright 2015-2020 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Full zone transfer, AXFR, related types
+
+use crate::authority::LookupObject;
+use crate::client::rr::Record;
+
+/// All records of a zone, framed for a full zone transfer, see [`Authority::axfr`]
+///
+/// [`Authority::axfr`]: trait.Authority.html#method.axfr
+#[derive(Clone, Debug, PartialEq)]
+pub struct Axfr {
+    records: Vec<Record>,
+}
+
+impl Axfr {
+    /// Frames the records of a zone for an AXFR response
+    ///
+    /// [RFC 5936](https://tools.ietf.org/html/rfc5936), DNS Zone Transfer Protocol (AXFR), June 2010
+    ///
+    /// ```text
+    /// 2.2.  AXFR Response
+    ///
+    ///    The AXFR server MUST begin an AXFR session by sending the zone's SOA
+    ///    RR as the first RR of the response and MUST end the session with
+    ///    the same SOA RR as the last RR of the response.
+    /// ```
+    ///
+    /// # Arguments
+    ///
+    /// * `soa` - the SOA of the zone, this will be the first and last record
+    /// * `records` - all other records of the zone, including RRSIGs, excluding the SOA itself
+    pub fn new(soa: Record, records: Vec<Record>) -> Self {
+        let mut framed = Vec::with_capacity(records.len() + 2);
+        framed.push(soa.clone());
+        framed.extend(records);
+        framed.push(soa);
+
+        Axfr { records: framed }
+    }
+
+    /// The SOA of the zone which frames the transfer
+    pub fn soa(&self) -> &Record {
+        &self.records[0]
+    }
+
+    /// All records of the transfer, starting and ending with the SOA
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    /// Consumes this, returning all records of the transfer, starting and ending with the SOA
+    pub fn into_records(self) -> Vec<Record> {
+        self.records
+    }
+}
+
+impl LookupObject for Axfr {
+    fn is_empty(&self) -> bool {
+        // always framed by the SOA
+        false
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a Record> + Send + 'a> {
+        Box::new(self.records.iter())
+    }
+
+    fn take_additionals(&mut self) -> Option<Box<dyn LookupObject>> {
+        None
+    }
+}
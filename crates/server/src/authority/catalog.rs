@@ -20,26 +20,34 @@ use std::borrow::Borrow;
 use std::collections::HashMap;
 use std::future::Future;
 use std::io;
+use std::net::IpAddr;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Instant;
 
 use log::{debug, error, info, trace, warn};
 
 use crate::authority::{
-    AuthLookup, MessageRequest, MessageResponse, MessageResponseBuilder, ZoneType,
+    AuthLookup, Ixfr, MessageRequest, MessageResponse, MessageResponseBuilder, ZoneType,
 };
 use crate::authority::{
     AuthorityObject, BoxedLookupFuture, EmptyLookup, LookupError, LookupObject,
 };
+use crate::authority::rate_limit::{RateLimitAction, RateLimitConfig, RateLimiter};
+use crate::authority::{Acl, AclOperation};
 use crate::client::op::{Edns, Header, LowerQuery, MessageType, OpCode, ResponseCode};
 use crate::client::rr::dnssec::{Algorithm, SupportedAlgorithms};
-use crate::client::rr::rdata::opt::{EdnsCode, EdnsOption};
-use crate::client::rr::{LowerName, RecordType};
+use crate::client::rr::rdata::opt::{EdnsCode, EdnsOption, ExtendedError};
+use crate::client::rr::{DNSClass, LowerName, Name, Record, RecordType};
+use crate::proto::xfer::FORWARD_HOPS_OPTION;
 use crate::server::{Request, RequestHandler, ResponseHandler};
 
 /// Set of authorities, zones, available to this server.
 #[derive(Default)]
 pub struct Catalog {
     authorities: HashMap<LowerName, Box<dyn AuthorityObject>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    acls: HashMap<LowerName, Acl>,
 }
 
 fn send_response<R: ResponseHandler>(
@@ -83,6 +91,12 @@ impl RequestHandler for Catalog {
         mut response_handle: R,
     ) -> Self::ResponseFuture {
         let request_message = request.message;
+        let rate_limit = match self.rate_limiter {
+            Some(ref limiter) if request.protocol.is_datagram() => {
+                Some((Arc::clone(limiter), request.src.ip()))
+            }
+            _ => None,
+        };
         trace!("request: {:?}", request_message);
 
         let response_edns: Option<Edns>;
@@ -98,7 +112,8 @@ impl RequestHandler for Catalog {
             // check our version against the request
             // TODO: what version are we?
             let our_version = 0;
-            resp_edns.set_dnssec_ok(true);
+            // RFC 3225, the DO bit of the query MUST be copied in the response
+            resp_edns.set_dnssec_ok(req_edns.dnssec_ok());
             resp_edns.set_max_payload(req_edns.max_payload().max(512));
             resp_edns.set_version(our_version);
 
@@ -108,7 +123,9 @@ impl RequestHandler for Catalog {
                     our_version,
                     req_edns.version()
                 );
+                // the header only holds the low bits of the extended response code
                 response_header.set_response_code(ResponseCode::BADVERS);
+                resp_edns.set_rcode_high(ResponseCode::BADVERS.high());
                 response.edns(resp_edns);
 
                 // TODO: should ResponseHandle consume self?
@@ -125,19 +142,54 @@ impl RequestHandler for Catalog {
             response_edns = None;
         }
 
+        if request_message.message_type() == MessageType::Query
+            && !self.is_allowed(&request_message, request.src.ip())
+        {
+            warn!("request {} from {} refused by ACL", request_message.id(), request.src);
+            let mut response_edns = response_edns;
+            if let Some(edns) = response_edns.as_mut() {
+                let error = ExtendedError::new(ExtendedError::PROHIBITED, "denied by ACL");
+                edns.set_option(EdnsOption::ExtendedError(error));
+            }
+
+            let response = MessageResponseBuilder::new(Some(request_message.raw_queries()));
+            let result = send_response(
+                response_edns,
+                response.error_msg(
+                    request_message.id(),
+                    request_message.op_code(),
+                    ResponseCode::Refused,
+                ),
+                response_handle,
+            );
+            if let Err(e) = result {
+                error!("request error: {}", e);
+            }
+            return Box::pin(async {});
+        }
+
         let result = match request_message.message_type() {
             // TODO think about threading query lookups for multiple lookups, this could be a huge improvement
             //  especially for recursive lookups
             MessageType::Query => match request_message.op_code() {
                 OpCode::Query => {
                     debug!("query received: {}", request_message.id());
-                    return Box::pin(self.lookup(request_message, response_edns, response_handle));
+                    return Box::pin(self.lookup_rate_limited(
+                        request_message,
+                        response_edns,
+                        response_handle,
+                        rate_limit,
+                    ));
                 }
                 OpCode::Update => {
                     debug!("update received: {}", request_message.id());
                     // TODO: this should be a future
                     self.update(&request_message, response_edns, response_handle)
                 }
+                OpCode::Notify => {
+                    debug!("notify received: {}", request_message.id());
+                    self.notify(&request_message, response_edns, response_handle)
+                }
                 c => {
                     warn!("unimplemented op_code: {:?}", c);
                     let response = MessageResponseBuilder::new(Some(request_message.raw_queries()));
@@ -174,9 +226,51 @@ impl Catalog {
     pub fn new() -> Self {
         Catalog {
             authorities: HashMap::new(),
+            rate_limiter: None,
+            acls: HashMap::new(),
         }
     }
 
+    /// Limits the rate of identical responses sent to each client over UDP
+    ///
+    /// Responses over TCP, TLS and HTTPS are never limited, see `RateLimitConfig`.
+    pub fn set_rate_limit(&mut self, config: RateLimitConfig) {
+        self.rate_limiter = Some(Arc::new(RateLimiter::new(config)));
+    }
+
+    /// Restricts the clients which may query, transfer or update the zone, see `Acl`
+    ///
+    /// Requests from other clients are refused. Zones without an ACL are treated as having the
+    ///  default `Acl`, which allows any client to query or update them but none to transfer them.
+    ///  The ACL is kept apart from the authority, it applies to any which later replaces it with
+    ///  `upsert`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - zone name, e.g. example.com.
+    /// * `acl` - the prefixes of the clients allowed to perform each operation
+    pub fn set_acl(&mut self, name: LowerName, acl: Acl) {
+        self.acls.insert(name, acl);
+    }
+
+    /// True if the client may perform the request on each of the zones of its queries, according
+    ///  to their ACLs, or the default `Acl` for zones without one
+    fn is_allowed(&self, request: &MessageRequest, client: IpAddr) -> bool {
+        let default_acl = Acl::default();
+        request.queries().iter().all(|query| {
+            let operation = match (request.op_code(), query.query_type()) {
+                (OpCode::Update, _) => AclOperation::Update,
+                (_, RecordType::AXFR) | (_, RecordType::IXFR) => AclOperation::Transfer,
+                _ => AclOperation::Query,
+            };
+
+            self.find(query.name())
+                .and_then(|authority| self.acls.get(&authority.origin()))
+                .unwrap_or(&default_acl)
+                .is_allowed(operation, client)
+        })
+    }
+
     /// Insert or update a zone authority
     ///
     /// # Arguments
@@ -188,10 +282,20 @@ impl Catalog {
     }
 
     /// Remove a zone from the catalog
+    ///
+    /// Queries already being answered by the zone hold their own reference to the authority, and
+    ///  complete as though it had not been removed. Later queries for the zone are refused.
     pub fn remove(&mut self, name: &LowerName) -> Option<Box<dyn AuthorityObject>> {
         self.authorities.remove(name)
     }
 
+    /// The origins of all the zones in the catalog, in sorted order
+    pub fn zones(&self) -> Vec<Name> {
+        let mut zones: Vec<Name> = self.authorities.keys().map(Name::from).collect();
+        zones.sort();
+        zones
+    }
+
     /// Update the zone given the Update request.
     ///
     /// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
@@ -275,8 +379,11 @@ impl Catalog {
                 .ok_or(ResponseCode::Refused)
         };
 
-        let response_code = match result {
+        // the response is signed with the TSIG of the update, or carries the TSIG error
+        let (response_code, tsig) = match result {
             Ok(authority) => {
+                let tsig = authority.tsig_responder(update);
+
                 // Ask for Master/Slave terms to be replaced
                 #[allow(deprecated)]
                 match authority.zone_type() {
@@ -287,7 +394,7 @@ impl Catalog {
                 }
 
                 #[allow(deprecated)]
-                match authority.zone_type() {
+                let response_code = match authority.zone_type() {
                     ZoneType::Secondary | ZoneType::Slave => {
                         error!("secondary forwarding for update not yet implemented");
                         ResponseCode::NotImp
@@ -296,17 +403,29 @@ impl Catalog {
                         let update_result = authority.update(update);
                         match update_result {
                             // successful update
-                            Ok(..) => ResponseCode::NoError,
+                            Ok(updated) => {
+                                // the serial was incremented, let the secondaries know
+                                if updated && !authority.notify_targets().is_empty() {
+                                    tokio::spawn(authority.notify_secondaries());
+                                }
+                                ResponseCode::NoError
+                            }
                             Err(response_code) => response_code,
                         }
                     }
                     _ => ResponseCode::NotAuth,
-                }
+                };
+
+                (response_code, tsig)
             }
-            Err(response_code) => response_code,
+            Err(response_code) => (response_code, None),
         };
 
-        let response = MessageResponseBuilder::new(None);
+        let mut response = MessageResponseBuilder::new(None);
+        if let Some(tsig) = tsig {
+            response.tsig(tsig);
+        }
+
         let mut response_header = Header::default();
         response_header.set_id(update.id());
         response_header.set_op_code(OpCode::Update);
@@ -320,6 +439,68 @@ impl Catalog {
         )
     }
 
+    /// Handles a NOTIFY of a change to a zone for which this server is a Secondary
+    ///
+    /// [RFC 1996](https://tools.ietf.org/html/rfc1996), DNS NOTIFY, August 1996
+    ///
+    /// ```text
+    /// 3.7. A NOTIFY request has QDCOUNT>0, ANCOUNT>=0, AUCOUNT>=0,
+    ///   ADCOUNT>=0.  If ANCOUNT>0, then the answer section represents an
+    ///   unsecure hint at the new RRset for this <QNAME,QCLASS,QTYPE>.  A
+    ///   slave receiving such a hint is free to treat equivilence of this
+    ///   answer section with its local data as a "no further work needs to
+    ///   be done" indication.  If ANCOUNT=0, or ANCOUNT>0 and the answer
+    ///   section differs from the slave's local data, then the slave should
+    ///   query its known masters to retrieve the new data.
+    /// ```
+    ///
+    /// The NOTIFY is acknowledged for Secondary zones, and an immediate refresh of the zone from
+    ///  its Primaries is spawned, see `AuthorityObject::refresh_secondary`. This must be called
+    ///  on a Tokio runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - a notify message
+    /// * `response_handle` - sink for the response message to be sent
+    pub fn notify<R: ResponseHandler + 'static>(
+        &self,
+        notify: &MessageRequest,
+        response_edns: Option<Edns>,
+        response_handle: R,
+    ) -> io::Result<()> {
+        let response_code = match notify.queries().first() {
+            None => ResponseCode::FormErr,
+            Some(query) => match self.find(query.name()) {
+                None => ResponseCode::Refused,
+                Some(authority) => {
+                    #[allow(deprecated)]
+                    match authority.zone_type() {
+                        ZoneType::Secondary | ZoneType::Slave => {
+                            info!("NOTIFY received for {}, refreshing", authority.origin());
+                            tokio::spawn(authority.refresh_secondary());
+                            ResponseCode::NoError
+                        }
+                        _ => ResponseCode::NotAuth,
+                    }
+                }
+            },
+        };
+
+        let response = MessageResponseBuilder::new(Some(notify.raw_queries()));
+        let mut response_header = Header::default();
+        response_header.set_id(notify.id());
+        response_header.set_op_code(OpCode::Notify);
+        response_header.set_message_type(MessageType::Response);
+        response_header.set_authoritative(response_code == ResponseCode::NoError);
+        response_header.set_response_code(response_code);
+
+        send_response(
+            response_edns,
+            response.build_no_records(response_header),
+            response_handle,
+        )
+    }
+
     /// Checks whether the `Catalog` contains DNS records for `name`
     ///
     /// Use this when you know the exact `LowerName` that was used when
@@ -344,6 +525,17 @@ impl Catalog {
         request: MessageRequest,
         response_edns: Option<Edns>,
         response_handle: R,
+    ) -> impl Future<Output = ()> + 'static {
+        self.lookup_rate_limited(request, response_edns, response_handle, None)
+    }
+
+    /// As `lookup`, counting the responses to the client against the `RateLimiter`
+    fn lookup_rate_limited<R: ResponseHandler>(
+        &self,
+        request: MessageRequest,
+        response_edns: Option<Edns>,
+        response_handle: R,
+        rate_limit: Option<(Arc<RateLimiter>, IpAddr)>,
     ) -> impl Future<Output = ()> + 'static {
         let queries_and_authorities = request
             .queries()
@@ -355,13 +547,14 @@ impl Catalog {
             })
             .collect::<Vec<_>>();
 
+        // the server is not authoritative for any of the queries, e.g. the zone was removed
         if queries_and_authorities.is_empty() {
             let response = MessageResponseBuilder::new(Some(request.raw_queries()));
             send_response(
                 response_edns
                     .as_ref()
                     .map(|arc| Borrow::<Edns>::borrow(arc).clone()),
-                response.error_msg(request.id(), request.op_code(), ResponseCode::NXDomain),
+                response.error_msg(request.id(), request.op_code(), ResponseCode::Refused),
                 response_handle.clone(),
             )
             .map_err(|e| error!("failed to send response: {}", e))
@@ -373,10 +566,16 @@ impl Catalog {
             request,
             response_edns,
             response_handle,
+            rate_limit,
         )
     }
 
     /// Recursively searches the catalog for a matching authority
+    ///
+    /// The authority with the longest origin which is a zone of the name is returned, starting
+    ///  with the name itself and removing a label at a time. With nested zones, e.g.
+    ///  `example.com.` and `sub.example.com.`, names at and below `sub.example.com.` are found in
+    ///  the child zone, all other names of `example.com.` in the parent.
     pub fn find(&self, name: &LowerName) -> Option<&(dyn AuthorityObject + 'static)> {
         debug!("searching authorities for: {}", name);
         self.authorities
@@ -398,6 +597,7 @@ async fn lookup<R: ResponseHandler + Unpin>(
     request: MessageRequest,
     response_edns: Option<Edns>,
     response_handle: R,
+    rate_limit: Option<(Arc<RateLimiter>, IpAddr)>,
 ) {
     // TODO: the spec is very unclear on what to do with multiple queries
     //  we will search for each, in the future, maybe make this threaded to respond even faster.
@@ -410,10 +610,63 @@ async fn lookup<R: ResponseHandler + Unpin>(
             authority.origin()
         );
 
-        let (response_header, sections) =
-            build_response(&*authority, request.id(), query, request.edns()).await;
+        let (mut response_header, sections) = build_response(&*authority, &request, query).await;
+
+        if let Some((ref limiter, client)) = rate_limit {
+            let response_code = ResponseCode::from(0, response_header.response_code());
+            // random names in a zone are all limited as one
+            let name = if response_code == ResponseCode::NXDomain {
+                authority.origin()
+            } else {
+                query.name().clone()
+            };
+
+            let action = limiter.check(
+                client,
+                &name,
+                query.query_type(),
+                response_code,
+                Instant::now(),
+            );
+            match action {
+                RateLimitAction::Send => (),
+                RateLimitAction::Truncate => {
+                    debug!("rate limited, truncating response to: {}", client);
+                    response_header.set_truncated(true);
+                    let response = MessageResponseBuilder::new(Some(request.raw_queries()))
+                        .build_no_records(response_header);
+
+                    let result =
+                        send_response(response_edns.clone(), response, response_handle.clone());
+                    if let Err(e) = result {
+                        error!("error sending response: {}", e);
+                    }
+                    continue;
+                }
+                RateLimitAction::Drop => {
+                    debug!("rate limited, dropping response to: {}", client);
+                    continue;
+                }
+            }
+        }
+
+        let mut edns = response_edns.clone();
+        if let (Some(edns), Some(error)) = (edns.as_mut(), sections.extended_error.as_ref()) {
+            edns.set_option(EdnsOption::ExtendedError(error.clone()));
+        }
+
+        // zone transfers are signed with the TSIG of the request, or carry the TSIG error
+        let mut response = MessageResponseBuilder::new(Some(request.raw_queries()));
+        match query.query_type() {
+            RecordType::AXFR | RecordType::IXFR => {
+                if let Some(tsig) = authority.tsig_responder(&request) {
+                    response.tsig(tsig);
+                }
+            }
+            _ => (),
+        }
 
-        let response = MessageResponseBuilder::new(Some(request.raw_queries())).build(
+        let response = response.build(
             response_header,
             sections.answers.iter(),
             sections.ns.iter(),
@@ -421,7 +674,7 @@ async fn lookup<R: ResponseHandler + Unpin>(
             sections.additionals.iter(),
         );
 
-        let result = send_response(response_edns.clone(), response, response_handle.clone());
+        let result = send_response(edns, response, response_handle.clone());
         if let Err(e) = result {
             error!("error sending response: {}", e);
         }
@@ -430,10 +683,11 @@ async fn lookup<R: ResponseHandler + Unpin>(
 
 async fn build_response(
     authority: &dyn AuthorityObject,
-    request_id: u16,
+    request: &MessageRequest,
     query: &LowerQuery,
-    edns: Option<&Edns>,
 ) -> (Header, LookupSections) {
+    let request_id = request.id();
+    let edns = request.edns();
     let (is_dnssec, supported_algorithms) =
         edns.map_or((false, SupportedAlgorithms::new()), |edns| {
             let supported_algorithms =
@@ -461,8 +715,52 @@ async fn build_response(
     response_header.set_message_type(MessageType::Response);
     response_header.set_authoritative(authority.zone_type().is_authoritative());
 
+    // the zone only holds records of its own class, e.g. IN or CH
+    if query.query_class() != authority.class() && query.query_class() != DNSClass::ANY {
+        debug!(
+            "refusing {} query on {} zone {}",
+            query.query_class(),
+            authority.class(),
+            authority.origin()
+        );
+        response_header.set_response_code(ResponseCode::Refused);
+        let extended_error = ExtendedError::new(
+            ExtendedError::NOT_AUTHORITATIVE,
+            format!("{} is a {} zone", authority.origin(), authority.class()),
+        );
+        let sections = LookupSections {
+            answers: Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
+            ns: Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
+            soa: Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
+            additionals: Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
+            extended_error: Some(extended_error),
+        };
+        return (response_header, sections);
+    }
+
+    // zone transfers are answered with the entire zone, or its changes, not a search
+    match query.query_type() {
+        RecordType::AXFR => {
+            debug!("performing AXFR on {}", authority.origin());
+            let sections = send_axfr_response(authority, &mut response_header, request, query);
+            return (response_header, sections);
+        }
+        RecordType::IXFR => {
+            debug!("performing IXFR on {}", authority.origin());
+            let sections = send_ixfr_response(authority, &mut response_header, request, query);
+            return (response_header, sections);
+        }
+        _ => (),
+    }
+
     debug!("performing {} on {}", query, authority.origin());
-    let future = authority.search(query, is_dnssec, supported_algorithms);
+    let future = match authority.zone_type() {
+        ZoneType::Forward => {
+            let hops = edns.map_or(0, forward_hops);
+            authority.forward(query, hops, is_dnssec, supported_algorithms)
+        }
+        _ => authority.search(query, is_dnssec, supported_algorithms),
+    };
 
     #[allow(deprecated)]
     let sections = match authority.zone_type() {
@@ -486,6 +784,14 @@ async fn build_response(
     (response_header, sections)
 }
 
+/// The number of times the request has already been forwarded, 0 if it came from a client
+fn forward_hops(edns: &Edns) -> u8 {
+    match edns.option(EdnsCode::Unknown(FORWARD_HOPS_OPTION)) {
+        Some(EdnsOption::Unknown(_, hops)) => hops.first().copied().unwrap_or(0),
+        _ => 0,
+    }
+}
+
 async fn send_authoritative_response(
     future: BoxedLookupFuture,
     authority: &dyn AuthorityObject,
@@ -500,20 +806,38 @@ async fn send_authoritative_response(
     //
     // On Errors, the transition depends on the type of error.
     let answers = match future.await {
+        // the name is delegated, the NS records of the child zone go in the authority section
+        Ok(mut referral) if referral.is_referral() => {
+            response_header.set_response_code(ResponseCode::NoError);
+            let additionals = referral
+                .take_additionals()
+                .unwrap_or_else(|| Box::new(AuthLookup::default()) as Box<dyn LookupObject>);
+            return LookupSections {
+                answers: Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
+                ns: referral,
+                soa: Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
+                additionals,
+                extended_error: None,
+            };
+        }
         Ok(records) => {
             response_header.set_response_code(ResponseCode::NoError);
             response_header.set_authoritative(true);
             Some(records)
         }
-        // This request was refused
-        // TODO: there are probably other error cases that should just drop through (FormErr, ServFail)
-        Err(LookupError::ResponseCode(ResponseCode::Refused)) => {
-            response_header.set_response_code(ResponseCode::Refused);
+        // This request was refused, or the zone can not be answered from, e.g. it has expired
+        // TODO: there are probably other error cases that should just drop through (FormErr)
+        Err(LookupError::ResponseCode(response_code))
+            if response_code == ResponseCode::Refused || response_code == ResponseCode::ServFail =>
+        {
+            response_header.set_response_code(response_code);
+            response_header.set_authoritative(false);
             return LookupSections {
                 answers: Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
                 ns: Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
                 soa: Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
                 additionals: Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
+                extended_error: None,
             };
         }
         Err(e) => {
@@ -554,7 +878,7 @@ async fn send_authoritative_response(
             None
         };
 
-        match authority.soa_secure(is_dnssec, supported_algorithms).await {
+        match authority.negative_soa(is_dnssec, supported_algorithms).await {
             Ok(soa) => (nsecs, Some(soa)),
             Err(e) => {
                 warn!("failed to lookup soa: {}", e);
@@ -583,6 +907,144 @@ async fn send_authoritative_response(
         ns: ns.unwrap_or_else(|| Box::new(AuthLookup::default()) as Box<dyn LookupObject>),
         soa: soa.unwrap_or_else(|| Box::new(AuthLookup::default()) as Box<dyn LookupObject>),
         additionals,
+        extended_error: None,
+    }
+}
+
+fn send_axfr_response(
+    authority: &dyn AuthorityObject,
+    response_header: &mut Header,
+    request: &MessageRequest,
+    query: &LowerQuery,
+) -> LookupSections {
+    let axfr = authorize_transfer(authority, request, query).and_then(|()| {
+        authority
+            .axfr()
+            .map(|axfr| Box::new(axfr) as Box<dyn LookupObject>)
+            .map_err(|e| (e, None))
+    });
+
+    transfer_sections(authority, response_header, RecordType::AXFR, axfr)
+}
+
+/// Answers an IXFR with the changes to the zone since the serial of the SOA in the authority
+///  section of the request, or with the entire zone if the changes are not known
+///
+/// [RFC 1995](https://tools.ietf.org/html/rfc1995), Incremental Zone Transfer in DNS, August 1996
+///
+/// ```text
+/// 4. Response Format
+///
+///    If incremental zone transfer is not available, the entire zone is
+///    returned.  The first and the last RR of the response is the SOA record
+///    of the zone.  I.e. the behavior is the same as an AXFR response except
+///    the query type is IXFR.
+/// ```
+fn send_ixfr_response(
+    authority: &dyn AuthorityObject,
+    response_header: &mut Header,
+    request: &MessageRequest,
+    query: &LowerQuery,
+) -> LookupSections {
+    let serial = request
+        .name_servers()
+        .iter()
+        .filter_map(|record| record.rdata().as_soa())
+        .map(|soa| soa.serial())
+        .next();
+
+    let ixfr = authorize_transfer(authority, request, query)
+        .and_then(|()| serial.ok_or((LookupError::from(ResponseCode::FormErr), None)))
+        .and_then(|serial| match authority.ixfr(serial) {
+            Ixfr::Incremental(delta) => {
+                debug!("IXFR of {} from serial {}", authority.origin(), serial);
+                Ok(Box::new(TransferRecords(delta.to_records())) as Box<dyn LookupObject>)
+            }
+            Ixfr::Full => {
+                debug!("IXFR of {} from serial {} is a full transfer", authority.origin(), serial);
+                authority
+                    .axfr()
+                    .map(|axfr| Box::new(axfr) as Box<dyn LookupObject>)
+                    .map_err(|e| (e, None))
+            }
+        });
+
+    transfer_sections(authority, response_header, RecordType::IXFR, ixfr)
+}
+
+/// The sections of the response to a zone transfer, or of its refusal
+fn transfer_sections(
+    authority: &dyn AuthorityObject,
+    response_header: &mut Header,
+    transfer_type: RecordType,
+    transfer: Result<Box<dyn LookupObject>, (LookupError, Option<u16>)>,
+) -> LookupSections {
+    let (answers, extended_error) = match transfer {
+        Ok(records) => {
+            response_header.set_response_code(ResponseCode::NoError);
+            (records, None)
+        }
+        Err((e, info_code)) => {
+            warn!("{} of {} failed: {}", transfer_type, authority.origin(), e);
+            response_header.set_response_code(e.response_code());
+            let extended_error = info_code.map(|info_code| {
+                let text = format!("{} of {} denied", transfer_type, authority.origin());
+                ExtendedError::new(info_code, text)
+            });
+            (
+                Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
+                extended_error,
+            )
+        }
+    };
+
+    LookupSections {
+        answers,
+        ns: Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
+        soa: Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
+        additionals: Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
+        extended_error,
+    }
+}
+
+/// Checks that the zone may be transferred to the requester, only the apex of a zone for which
+///  this server is authoritative may be
+fn authorize_transfer(
+    authority: &dyn AuthorityObject,
+    request: &MessageRequest,
+    query: &LowerQuery,
+) -> Result<(), (LookupError, Option<u16>)> {
+    if !authority.zone_type().is_authoritative() {
+        Err((
+            LookupError::from(ResponseCode::Refused),
+            Some(ExtendedError::NOT_AUTHORITATIVE),
+        ))
+    } else if *query.name() != authority.origin() {
+        Err((LookupError::from(ResponseCode::NotAuth), None))
+    } else if let Err(response_code) = authority.authorize_axfr(request) {
+        Err((
+            LookupError::from(response_code),
+            Some(ExtendedError::PROHIBITED),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// The records of a zone transfer, in the order they are sent
+struct TransferRecords(Vec<Record>);
+
+impl LookupObject for TransferRecords {
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a Record> + Send + 'a> {
+        Box::new(self.0.iter())
+    }
+
+    fn take_additionals(&mut self) -> Option<Box<dyn LookupObject>> {
+        None
     }
 }
 
@@ -590,14 +1052,13 @@ async fn send_forwarded_response(
     future: BoxedLookupFuture,
     response_header: &mut Header,
 ) -> LookupSections {
-    let answers = match future.await {
-        Ok(rsp) => rsp,
+    let (answers, extended_error) = match future.await {
+        Ok(rsp) => (rsp, None),
         Err(e) => {
-            if e.is_nx_domain() {
-                response_header.set_response_code(ResponseCode::NXDomain);
-            }
+            // NXDOMAIN and NODATA are answers, anything else, e.g. a timeout, is a failure
+            response_header.set_response_code(e.response_code());
             error!("error resolving: {}", e);
-            Box::new(EmptyLookup)
+            (Box::new(EmptyLookup) as Box<dyn LookupObject>, e.extended_error())
         }
     };
 
@@ -607,6 +1068,7 @@ async fn send_forwarded_response(
         ns: Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
         soa: Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
         additionals: Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
+        extended_error,
     }
 }
 
@@ -615,4 +1077,6 @@ struct LookupSections {
     ns: Box<dyn LookupObject>,
     soa: Box<dyn LookupObject>,
     additionals: Box<dyn LookupObject>,
+    /// explains a failed lookup to the client, sent only if the request had EDNS
+    extended_error: Option<ExtendedError>,
 }
@@ -11,8 +11,9 @@ use enum_as_inner::EnumAsInner;
 use thiserror::Error;
 
 use crate::client::op::ResponseCode;
+use crate::client::rr::rdata::opt::ExtendedError;
 #[cfg(feature = "trust-dns-resolver")]
-use crate::resolver::error::ResolveError;
+use crate::resolver::error::{ResolveError, ResolveErrorKind};
 
 // TODO: should this implement Failure?
 /// A query could not be fulfilled
@@ -53,6 +54,46 @@ impl LookupError {
     pub fn is_refused(&self) -> bool {
         matches!(*self, LookupError::ResponseCode(ResponseCode::Refused))
     }
+
+    /// The ResponseCode which should be returned to the client for this error
+    ///
+    /// Errors from upstream resolvers keep their ResponseCode if there was a response, otherwise,
+    ///  e.g. on a timeout, this is `ServFail`.
+    pub fn response_code(&self) -> ResponseCode {
+        match self {
+            LookupError::NameExists => ResponseCode::NoError,
+            LookupError::ResponseCode(code) => *code,
+            #[cfg(feature = "trust-dns-resolver")]
+            LookupError::ResolveError(e) => match e.kind() {
+                ResolveErrorKind::NoRecordsFound { response_code, .. } => *response_code,
+                _ => ResponseCode::ServFail,
+            },
+            LookupError::Io(..) => ResponseCode::ServFail,
+        }
+    }
+
+    /// The Extended DNS Error, RFC 8914, which explains this error to the client, if any
+    ///
+    /// Errors from upstream resolvers pass along the extended error of the upstream response,
+    ///  otherwise failures to reach the upstream servers are explained as network errors.
+    pub fn extended_error(&self) -> Option<ExtendedError> {
+        match self {
+            #[cfg(feature = "trust-dns-resolver")]
+            LookupError::ResolveError(e) => match (e.extended_error(), e.kind()) {
+                (Some(extended_error), _) => Some(extended_error.clone()),
+                (None, ResolveErrorKind::NoRecordsFound { .. }) => None,
+                (None, ResolveErrorKind::Timeout) => Some(ExtendedError::new(
+                    ExtendedError::NO_REACHABLE_AUTHORITY,
+                    "upstream name servers timed out",
+                )),
+                (None, kind) => Some(ExtendedError::new(
+                    ExtendedError::NETWORK_ERROR,
+                    kind.to_string(),
+                )),
+            },
+            _ => None,
+        }
+    }
 }
 
 impl From<ResponseCode> for LookupError {
@@ -0,0 +1,108 @@
+// Copyright 2015-2020 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Incremental zone transfer, IXFR, related types
+
+use crate::client::rr::Record;
+
+/// The result of an incremental zone transfer request, see [`Authority::ixfr`]
+///
+/// [`Authority::ixfr`]: trait.Authority.html#method.ixfr
+#[derive(Clone, Debug, PartialEq)]
+pub enum Ixfr {
+    /// The changes to the zone since the requested serial
+    Incremental(ZoneDelta),
+    /// The changes since the requested serial are not known, e.g. the serial is too old, the
+    ///  entire zone should be transferred with an AXFR
+    Full,
+}
+
+/// The changes made to a zone between two SOA serials
+#[derive(Clone, Debug, PartialEq)]
+pub struct ZoneDelta {
+    old_soa: Record,
+    new_soa: Record,
+    deleted: Vec<Record>,
+    added: Vec<Record>,
+}
+
+impl ZoneDelta {
+    /// Creates a new delta
+    ///
+    /// # Arguments
+    ///
+    /// * `old_soa` - the SOA of the zone at the requested serial
+    /// * `new_soa` - the current SOA of the zone
+    /// * `deleted` - records removed from the zone since `old_soa`, excluding the SOA
+    /// * `added` - records added to the zone since `old_soa`, excluding the SOA
+    pub fn new(
+        old_soa: Record,
+        new_soa: Record,
+        deleted: Vec<Record>,
+        added: Vec<Record>,
+    ) -> Self {
+        ZoneDelta {
+            old_soa,
+            new_soa,
+            deleted,
+            added,
+        }
+    }
+
+    /// The SOA of the zone at the requested serial
+    pub fn old_soa(&self) -> &Record {
+        &self.old_soa
+    }
+
+    /// The current SOA of the zone
+    pub fn new_soa(&self) -> &Record {
+        &self.new_soa
+    }
+
+    /// Records which have been removed from the zone
+    pub fn deleted(&self) -> &[Record] {
+        &self.deleted
+    }
+
+    /// Records which have been added to the zone
+    pub fn added(&self) -> &[Record] {
+        &self.added
+    }
+
+    /// Returns the records framed for an IXFR response
+    ///
+    /// [RFC 1995](https://tools.ietf.org/html/rfc1995), Incremental Zone Transfer in DNS, August 1996
+    ///
+    /// ```text
+    /// 4. Response Format
+    ///
+    ///    If incremental zone transfer is available, one or more difference
+    ///    sequences is returned.  The list of difference sequences is preceded
+    ///    and followed by a copy of the server's current version of the SOA.
+    ///
+    ///    Each difference sequence represents one update to the zone (one SOA
+    ///    serial change) consisting of deleted RRs and added RRs.  The first RR
+    ///    of the deleted RRs is the older SOA RR and the first RR of the added
+    ///    RRs is the newer SOA RR.
+    /// ```
+    ///
+    /// If the zone has not changed, only the current SOA is returned.
+    pub fn to_records(&self) -> Vec<Record> {
+        if self.old_soa == self.new_soa {
+            return vec![self.new_soa.clone()];
+        }
+
+        let mut records = Vec::with_capacity(self.deleted.len() + self.added.len() + 4);
+        records.push(self.new_soa.clone());
+        records.push(self.old_soa.clone());
+        records.extend(self.deleted.iter().cloned());
+        records.push(self.new_soa.clone());
+        records.extend(self.added.iter().cloned());
+        records.push(self.new_soa.clone());
+        records
+    }
+}
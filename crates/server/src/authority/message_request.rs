@@ -73,11 +73,12 @@ impl MessageRequest {
     /// # Return value
     ///
     /// The `ResponseCode`, if this is an EDNS message then this will join the section from the OPT
-    ///  record to create the EDNS `ResponseCode`
+    ///  record to create the EDNS `ResponseCode`, see `ResponseCode::from_extended`
     pub fn response_code(&self) -> ResponseCode {
-        ResponseCode::from(
+        ResponseCode::from_extended(
             self.edns.as_ref().map_or(0, Edns::rcode_high),
             self.header.response_code(),
+            !self.sig0.is_empty(),
         )
     }
 
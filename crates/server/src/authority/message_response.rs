@@ -5,13 +5,17 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use chrono::Utc;
+
 use crate::authority::message_request::QueriesEmitAndCount;
 use crate::authority::Queries;
 use crate::proto::error::*;
 use crate::proto::op::message::EmitAndCount;
 use crate::proto::op::{message, Edns, Header, MessageType, OpCode, ResponseCode};
+use crate::proto::rr::dnssec::TsigResponder;
+use crate::proto::rr::rdata::opt::EdnsOption;
 use crate::proto::rr::Record;
-use crate::proto::serialize::binary::BinEncoder;
+use crate::proto::serialize::binary::{BinEncodable, BinEncoder};
 
 /// A EncodableMessage with borrowed data for Responses in the Server
 #[derive(Debug)]
@@ -36,6 +40,7 @@ pub struct MessageResponse<
     additionals: D,
     sig0: Vec<Record>,
     edns: Option<Edns>,
+    tsig: Option<TsigResponder>,
 }
 
 enum EmptyOrQueries<'q> {
@@ -82,6 +87,7 @@ where
     pub fn destructive_emit(mut self, encoder: &mut BinEncoder) -> ProtoResult<()> {
         // soa records are part of the nameserver section
         let mut name_servers = self.name_servers.chain(self.soa);
+        let start = encoder.offset();
 
         message::emit_message_parts(
             &self.header,
@@ -92,15 +98,148 @@ where
             self.edns.as_ref(),
             &self.sig0,
             encoder,
-        )
+        )?;
+
+        match self.tsig {
+            Some(ref tsig) => emit_tsig(tsig, start, encoder),
+            None => Ok(()),
+        }
+    }
+
+    /// Consumes self, and emits to the buffer without exceeding `max_size` bytes
+    ///
+    /// A response which is too large is emitted again without the additional section, if it is
+    ///  still too large the answers and name servers are cut short and the truncated bit is set.
+    ///  Room is always left for the OPT, SIG(0) and TSIG records.
+    pub fn destructive_emit_max_size(
+        self,
+        buffer: &mut Vec<u8>,
+        max_size: u16,
+    ) -> ProtoResult<()> {
+        self.destructive_emit_padded(buffer, max_size, None)
+    }
+
+    /// As `destructive_emit_max_size`, padding the response to a multiple of `block_size` octets
+    ///
+    /// [RFC 7830](https://tools.ietf.org/html/rfc7830), The EDNS(0) Padding Option, May 2016. Only
+    ///  a response with EDNS is padded, and not beyond `max_size`.
+    pub fn destructive_emit_padded(
+        self,
+        buffer: &mut Vec<u8>,
+        max_size: u16,
+        block_size: Option<u16>,
+    ) -> ProtoResult<()> {
+        let MessageResponse {
+            header,
+            queries,
+            answers,
+            name_servers,
+            soa,
+            additionals,
+            sig0,
+            mut edns,
+            tsig,
+        } = self;
+
+        // the records are collected, as they may need to be emitted twice
+        let answers: Vec<&'a Record> = answers.collect();
+        let name_servers: Vec<&'a Record> = name_servers.chain(soa).collect();
+        let additionals: Vec<&'a Record> = additionals.collect();
+
+        let emit = |buffer: &mut Vec<u8>,
+                    additionals: &[&'a Record],
+                    edns: Option<&Edns>,
+                    max_size: u16| {
+            buffer.clear();
+            let mut encoder = BinEncoder::new(buffer);
+            encoder.set_max_size(max_size);
+
+            message::emit_message_parts(
+                &header,
+                &mut EmptyOrQueries::from(queries),
+                &mut answers.iter().copied(),
+                &mut name_servers.iter().copied(),
+                &mut additionals.iter().copied(),
+                edns,
+                &sig0,
+                &mut encoder,
+            )
+        };
+
+        // the unpadded length includes the header of the padding option
+        let block_size = block_size.filter(|_| edns.is_some()).map(usize::from);
+        if let (Some(_), Some(edns)) = (block_size, edns.as_mut()) {
+            edns.set_option(EdnsOption::Padding(0));
+        }
+
+        emit(buffer, &additionals, edns.as_ref(), u16::max_value())?;
+
+        // the TSIG has the same length for any response, the message is emitted to fit before it
+        let tsig_len = match tsig {
+            Some(ref tsig) => tsig_len(tsig, buffer)?,
+            None => 0,
+        };
+        let max_size = max_size.saturating_sub(tsig_len);
+
+        let additionals: &[&'a Record] = if buffer.len() <= max_size as usize {
+            &additionals[..]
+        } else {
+            // EDNS and SIG0 are still emitted, room is kept for them when cutting the sections
+            emit(buffer, &[], edns.as_ref(), max_size)?;
+            &[]
+        };
+
+        if let (Some(block_size), Some(edns)) = (block_size, edns.as_mut()) {
+            let padding = (block_size - buffer.len() % block_size) % block_size;
+            if padding > 0 && buffer.len() + padding <= max_size as usize {
+                edns.set_option(EdnsOption::Padding(padding as u16));
+                emit(buffer, additionals, Some(edns), max_size)?;
+            }
+        }
+
+        match tsig {
+            Some(ref tsig) => {
+                let mut encoder = BinEncoder::new(buffer);
+                encoder.set_offset(encoder.len());
+                emit_tsig(tsig, 0, &mut encoder)
+            }
+            None => Ok(()),
+        }
     }
 }
 
+/// Signs the response emitted from `start` with the TSIG of the request, appending the TSIG
+///
+/// [RFC 8945](https://tools.ietf.org/html/rfc8945#section-5.3), Secret Key Transaction
+///  Authentication for DNS (TSIG), November 2020. The TSIG is the last additional record.
+fn emit_tsig(tsig: &TsigResponder, start: usize, encoder: &mut BinEncoder) -> ProtoResult<()> {
+    let now = Utc::now().timestamp() as u64;
+    let record = tsig.sign_response(encoder.slice_of(start, encoder.offset()), now)?;
+
+    // the additional count of the header is at octet 10
+    let count = encoder.slice_of(start + 10, start + 12);
+    let additional_count = u16::from_be_bytes([count[0], count[1]]);
+
+    record.emit(encoder)?;
+    let end = encoder.offset();
+    encoder.set_offset(start + 10);
+    encoder.emit_u16(additional_count + 1)?;
+    encoder.set_offset(end);
+    Ok(())
+}
+
+/// The length of the TSIG of the response
+fn tsig_len(tsig: &TsigResponder, response: &[u8]) -> ProtoResult<u16> {
+    let record = tsig.sign_response(response, Utc::now().timestamp() as u64)?;
+    Ok(record.to_bytes()?.len() as u16)
+}
+
 /// A builder for MessageResponses
 pub struct MessageResponseBuilder<'q> {
     queries: Option<&'q Queries>,
     sig0: Option<Vec<Record>>,
     edns: Option<Edns>,
+    tsig: Option<TsigResponder>,
 }
 
 impl<'q> MessageResponseBuilder<'q> {
@@ -114,6 +253,7 @@ impl<'q> MessageResponseBuilder<'q> {
             queries,
             sig0: None,
             edns: None,
+            tsig: None,
         }
     }
 
@@ -123,6 +263,12 @@ impl<'q> MessageResponseBuilder<'q> {
         self
     }
 
+    /// Sign the Response with the TSIG of the request
+    pub fn tsig(&mut self, tsig: TsigResponder) -> &mut Self {
+        self.tsig = Some(tsig);
+        self
+    }
+
     /// Constructs the new MessageResponse with associated Header
     ///
     /// # Arguments
@@ -155,6 +301,7 @@ impl<'q> MessageResponseBuilder<'q> {
             additionals: additionals.into_iter(),
             sig0: self.sig0.unwrap_or_default(),
             edns: self.edns,
+            tsig: self.tsig,
         }
     }
 
@@ -169,6 +316,7 @@ impl<'q> MessageResponseBuilder<'q> {
             additionals: Box::new(None.into_iter()),
             sig0: self.sig0.unwrap_or_default(),
             edns: self.edns,
+            tsig: self.tsig,
         }
     }
 
@@ -200,6 +348,7 @@ impl<'q> MessageResponseBuilder<'q> {
             additionals: Box::new(None.into_iter()),
             sig0: self.sig0.unwrap_or_default(),
             edns: self.edns,
+            tsig: self.tsig,
         }
     }
 }
@@ -238,6 +387,7 @@ mod tests {
                 additionals: iter::once(&answer),
                 sig0: vec![],
                 edns: None,
+                tsig: None,
             };
 
             message
@@ -274,6 +424,7 @@ mod tests {
                 additionals: iter::repeat(&answer),
                 sig0: vec![],
                 edns: None,
+                tsig: None,
             };
 
             message
@@ -286,4 +437,160 @@ mod tests {
         assert_eq!(response.answer_count(), 0);
         assert!(response.name_server_count() > 1);
     }
+
+    fn www_example_com() -> Record {
+        Record::new()
+            .set_name(Name::from_str("www.example.com.").unwrap())
+            .set_rdata(RData::A(Ipv4Addr::new(93, 184, 216, 34)))
+            .set_dns_class(DNSClass::IN)
+            .clone()
+    }
+
+    #[test]
+    fn test_max_size_drops_additionals() {
+        let record = www_example_com();
+
+        // 30 answers fit in 507 bytes, the additional record does not
+        let message = MessageResponse {
+            header: Header::new(),
+            queries: None,
+            answers: iter::repeat(&record).take(30),
+            name_servers: iter::empty(),
+            soa: iter::empty(),
+            additionals: iter::once(&record),
+            sig0: vec![],
+            edns: None,
+            tsig: None,
+        };
+
+        let mut buf = Vec::with_capacity(512);
+        message
+            .destructive_emit_max_size(&mut buf, 512)
+            .expect("failed to encode");
+
+        let response = Message::from_vec(&buf).expect("failed to decode");
+        assert!(!response.header().truncated());
+        assert_eq!(response.answer_count(), 30);
+        assert_eq!(response.additional_count(), 0);
+    }
+
+    #[test]
+    fn test_max_size_truncates_answers() {
+        let record = www_example_com();
+
+        // 31 answers are just over 512 bytes
+        let message = MessageResponse {
+            header: Header::new(),
+            queries: None,
+            answers: iter::repeat(&record).take(31),
+            name_servers: iter::empty(),
+            soa: iter::empty(),
+            additionals: iter::once(&record),
+            sig0: vec![],
+            edns: None,
+            tsig: None,
+        };
+
+        let mut buf = Vec::with_capacity(512);
+        message
+            .destructive_emit_max_size(&mut buf, 512)
+            .expect("failed to encode");
+
+        assert!(buf.len() <= 512);
+        let response = Message::from_vec(&buf).expect("failed to decode");
+        assert!(response.header().truncated());
+        assert_eq!(response.answer_count(), 30);
+        assert_eq!(response.additional_count(), 0);
+    }
+
+    #[test]
+    fn test_max_size_truncated_keeps_edns() {
+        let record = www_example_com();
+
+        // 30 answers would fit in 507 bytes, but not with the 11 bytes of the OPT record
+        let message = MessageResponse {
+            header: Header::new(),
+            queries: None,
+            answers: iter::repeat(&record).take(31),
+            name_servers: iter::empty(),
+            soa: iter::empty(),
+            additionals: iter::once(&record),
+            sig0: vec![],
+            edns: Some(Edns::new()),
+            tsig: None,
+        };
+
+        let mut buf = Vec::with_capacity(512);
+        message
+            .destructive_emit_max_size(&mut buf, 512)
+            .expect("failed to encode");
+
+        assert!(buf.len() <= 512);
+        let response = Message::from_vec(&buf).expect("failed to decode");
+        assert!(response.header().truncated());
+        assert_eq!(response.answer_count(), 29);
+        assert!(response.edns().is_some());
+    }
+
+    #[test]
+    fn test_max_size_fits() {
+        let record = www_example_com();
+
+        let message = MessageResponse {
+            header: Header::new(),
+            queries: None,
+            answers: iter::repeat(&record).take(31),
+            name_servers: iter::empty(),
+            soa: iter::empty(),
+            additionals: iter::once(&record),
+            sig0: vec![],
+            edns: None,
+            tsig: None,
+        };
+
+        let mut buf = Vec::with_capacity(512);
+        message
+            .destructive_emit_max_size(&mut buf, 4096)
+            .expect("failed to encode");
+
+        let response = Message::from_vec(&buf).expect("failed to decode");
+        assert!(!response.header().truncated());
+        assert_eq!(response.answer_count(), 31);
+        assert_eq!(response.additional_count(), 1);
+    }
+
+    #[test]
+    fn test_padding() {
+        let record = www_example_com();
+        let response = |edns: Option<Edns>| MessageResponse {
+            header: Header::new(),
+            queries: None,
+            answers: iter::repeat(&record).take(3),
+            name_servers: iter::empty(),
+            soa: iter::empty(),
+            additionals: iter::empty(),
+            sig0: vec![],
+            edns,
+            tsig: None,
+        };
+
+        let mut buf = Vec::with_capacity(512);
+        response(Some(Edns::new()))
+            .destructive_emit_padded(&mut buf, u16::max_value(), Some(468))
+            .expect("failed to encode");
+        assert_eq!(buf.len(), 468);
+
+        let response_message = Message::from_vec(&buf).expect("failed to decode");
+        assert_eq!(response_message.answer_count(), 3);
+
+        // without EDNS there is nowhere to put the padding
+        let mut unpadded = Vec::with_capacity(512);
+        response(None)
+            .destructive_emit_max_size(&mut unpadded, u16::max_value())
+            .expect("failed to encode");
+        response(None)
+            .destructive_emit_padded(&mut buf, u16::max_value(), Some(468))
+            .expect("failed to encode");
+        assert_eq!(buf, unpadded);
+    }
 }
@@ -12,23 +12,35 @@ use crate::client::op::ResponseCode;
 /// Result of an Update operation
 pub type UpdateResult<T> = Result<T, ResponseCode>;
 
+mod acl;
 mod auth_lookup;
+mod axfr;
 #[allow(clippy::module_inception)]
 mod authority;
 pub(crate) mod authority_object;
 mod catalog;
 mod error;
+mod ixfr;
 pub(crate) mod message_request;
 mod message_response;
+mod notify;
+mod rate_limit;
+mod serial;
 mod zone_type;
 
+pub use self::acl::{Acl, AclOperation, Cidr};
 pub use self::auth_lookup::{
     AnyRecords, AuthLookup, AuthLookupIter, LookupRecords, LookupRecordsIter,
 };
 pub use self::authority::Authority;
+pub use self::axfr::Axfr;
 pub use self::authority_object::{AuthorityObject, BoxedLookupFuture, EmptyLookup, LookupObject};
 pub use self::catalog::Catalog;
 pub use self::error::{LookupError, LookupResult};
+pub use self::ixfr::{Ixfr, ZoneDelta};
 pub use self::message_request::{MessageRequest, Queries, UpdateRequest};
 pub use self::message_response::{MessageResponse, MessageResponseBuilder};
+pub use self::rate_limit::RateLimitConfig;
+pub use self::serial::{Refresh, SerialScheme};
 pub use self::zone_type::ZoneType;
+pub use crate::proto::rr::{serial_gt, Serial};
@@ -0,0 +1,140 @@
+// Copyright 2015-2020 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! NOTIFY of zone changes from a Primary to its Secondaries, and the refresh of a Secondary
+
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+
+use futures_util::future;
+use futures_util::stream::TryStreamExt;
+use log::{info, warn};
+use tokio::net::UdpSocket;
+
+use crate::authority::{Authority, Refresh};
+use crate::client::client::{axfr_stream, AsyncClient, ClientHandle};
+use crate::client::error::{ClientError, ClientResult};
+use crate::client::op::ResponseCode;
+use crate::client::rr::{DNSClass, Name, RecordSet, RecordType};
+use crate::client::udp::UdpClientStream;
+
+/// Sends a NOTIFY for the zone to each of the targets, failures are logged
+///
+/// [RFC 1996](https://tools.ietf.org/html/rfc1996), DNS NOTIFY, August 1996
+///
+/// ```text
+/// 3.5. If TCP is used, both master and slave must continue to offer
+///   name service during the transaction, even when the TCP transaction is
+///   not making progress.  The NOTIFY request is sent once, and a
+///   "timeout" is said to have occurred if no NOTIFY response is received
+///   within a reasonable interval.
+/// ```
+pub(crate) async fn notify_secondaries(origin: Name, targets: Vec<SocketAddr>) {
+    let notifies = targets.into_iter().map(|target| {
+        let origin = origin.clone();
+
+        async move {
+            match notify(origin.clone(), target).await {
+                Ok(response_code) => info!(
+                    "NOTIFY of {} to {} completed: {}",
+                    origin, target, response_code
+                ),
+                Err(e) => warn!("NOTIFY of {} to {} failed: {}", origin, target, e),
+            }
+        }
+    });
+
+    future::join_all(notifies).await;
+}
+
+async fn notify(origin: Name, target: SocketAddr) -> ClientResult<ResponseCode> {
+    let stream = UdpClientStream::<UdpSocket>::new(target);
+    let (mut client, bg) = AsyncClient::connect(stream).await?;
+    tokio::spawn(bg);
+
+    let response = client
+        .notify(origin, DNSClass::IN, RecordType::SOA, None::<RecordSet>)
+        .await?;
+
+    Ok(response.response_code())
+}
+
+/// Refreshes the Secondary zone from the first of its Primaries to respond
+///
+/// [RFC 1996](https://tools.ietf.org/html/rfc1996), DNS NOTIFY, August 1996
+///
+/// ```text
+/// 4.7 Slave Receives a NOTIFY Request from a Master
+///
+///    When a slave server receives a NOTIFY request from one of its locally
+///    designated masters for the zone, it must query for the SOA RR of the
+///    zone (if the SOA query is not already in progress).
+/// ```
+///
+/// The zone is transferred with an AXFR if the serial of the Primary is newer, see
+///  `Refresh::check`, failures are logged.
+pub(crate) async fn refresh_secondary<A: Authority>(authority: Arc<RwLock<A>>) {
+    let (origin, primaries) = {
+        let authority = authority.read().expect("poisoned");
+        (Name::from(authority.origin()), authority.primaries().to_vec())
+    };
+
+    if primaries.is_empty() {
+        warn!("no primaries to refresh {} from", origin);
+        return;
+    }
+
+    for primary in primaries {
+        match refresh(&authority, origin.clone(), primary).await {
+            Ok(refresh) => {
+                info!("refresh of {} from {} completed: {:?}", origin, primary, refresh);
+                return;
+            }
+            Err(e) => warn!("refresh of {} from {} failed: {}", origin, primary, e),
+        }
+    }
+}
+
+async fn refresh<A: Authority>(
+    authority: &RwLock<A>,
+    origin: Name,
+    primary: SocketAddr,
+) -> ClientResult<Refresh> {
+    let stream = UdpClientStream::<UdpSocket>::new(primary);
+    let (mut client, bg) = AsyncClient::connect(stream).await?;
+    tokio::spawn(bg);
+
+    let response = client
+        .query(origin.clone(), DNSClass::IN, RecordType::SOA)
+        .await?;
+    let primary_soa = response
+        .answers()
+        .iter()
+        .find_map(|record| record.rdata().as_soa())
+        .cloned()
+        .ok_or_else(|| ClientError::from(format!("no SOA for {} from {}", origin, primary)))?;
+
+    let refresh = authority.read().expect("poisoned").refresh(&primary_soa);
+    if refresh == Refresh::Transfer {
+        let records = axfr_stream(origin.clone(), primary)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        authority
+            .write()
+            .expect("poisoned")
+            .transfer(records)
+            .map_err(|code| ClientError::from(format!("AXFR of {} rejected: {}", origin, code)))?;
+    }
+
+    authority
+        .write()
+        .expect("poisoned")
+        .set_refreshed(Instant::now());
+    Ok(refresh)
+}
@@ -0,0 +1,251 @@
+// Copyright 2015-2020 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Response rate limiting, dampening reflection and amplification attacks over UDP
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::client::op::ResponseCode;
+use crate::client::rr::{LowerName, RecordType};
+
+/// The number of clients tracked before those which are no longer limited are forgotten, which is
+///  done at most once per window
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Configuration for response rate limiting, see `Catalog::set_rate_limit`
+///
+/// Identical responses, by name, type and response code, to the same client prefix, a /24 for
+///  IPv4 and a /64 for IPv6, are counted. Once more than `responses_per_window` have been sent
+///  within the window the responses are dropped, except that every `slip`th is sent truncated so
+///  that legitimate clients retry over TCP.
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// The identical responses sent to a client prefix within the window, defaults to 5
+    pub responses_per_window: u32,
+    /// The length of the window in seconds, defaults to 1
+    pub window_secs: u64,
+    /// Every `slip`th limited response is sent truncated, the others are dropped, defaults to 2
+    ///
+    /// 0 drops all of the limited responses, and 1 truncates all of them.
+    pub slip: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            responses_per_window: 5,
+            window_secs: 1,
+            slip: 2,
+        }
+    }
+}
+
+/// What to do with a response, see `RateLimiter::check`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RateLimitAction {
+    /// The response is within the limit
+    Send,
+    /// Send an empty, truncated, response in its place
+    Truncate,
+    /// Send nothing
+    Drop,
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct RateLimitKey {
+    prefix: IpAddr,
+    name: LowerName,
+    record_type: RecordType,
+    response_code: ResponseCode,
+}
+
+struct Window {
+    started: Instant,
+    responses: u32,
+}
+
+struct Windows {
+    by_key: HashMap<RateLimitKey, Window>,
+    /// when the expired windows were last forgotten, None if they never have been
+    pruned_at: Option<Instant>,
+}
+
+/// Counts the responses sent to each client prefix
+pub(crate) struct RateLimiter {
+    config: RateLimitConfig,
+    windows: Mutex<Windows>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(config: RateLimitConfig) -> Self {
+        RateLimiter {
+            config,
+            windows: Mutex::new(Windows {
+                by_key: HashMap::new(),
+                pruned_at: None,
+            }),
+        }
+    }
+
+    /// Counts the response to the client, returning whether it is to be sent
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - the address of the client
+    /// * `name` - the name of the response, for NXDOMAIN this should be the zone so that random
+    ///            names do not each have a limit of their own
+    /// * `record_type` - the queried type
+    /// * `response_code` - the response code of the response
+    /// * `now` - the current time
+    pub(crate) fn check(
+        &self,
+        client: IpAddr,
+        name: &LowerName,
+        record_type: RecordType,
+        response_code: ResponseCode,
+        now: Instant,
+    ) -> RateLimitAction {
+        let window_len = Duration::from_secs(self.config.window_secs);
+        let key = RateLimitKey {
+            prefix: prefix(client),
+            name: name.clone(),
+            record_type,
+            response_code,
+        };
+
+        let mut windows = self.windows.lock().expect("poisoned lock");
+        let prune = windows.by_key.len() > PRUNE_THRESHOLD
+            && windows.pruned_at.map_or(true, |pruned_at| {
+                now.saturating_duration_since(pruned_at) >= window_len
+            });
+        if prune {
+            windows
+                .by_key
+                .retain(|_, window| now.duration_since(window.started) < window_len);
+            windows.pruned_at = Some(now);
+        }
+
+        let window = windows.by_key.entry(key).or_insert(Window {
+            started: now,
+            responses: 0,
+        });
+        if now.duration_since(window.started) >= window_len {
+            window.started = now;
+            window.responses = 0;
+        }
+
+        window.responses = window.responses.saturating_add(1);
+        if window.responses <= self.config.responses_per_window {
+            return RateLimitAction::Send;
+        }
+
+        let limited = window.responses - self.config.responses_per_window;
+        if self.config.slip > 0 && limited % self.config.slip == 0 {
+            RateLimitAction::Truncate
+        } else {
+            RateLimitAction::Drop
+        }
+    }
+}
+
+/// The prefix of the client which is limited as one, a /24 for IPv4 and a /64 for IPv6
+fn prefix(client: IpAddr) -> IpAddr {
+    match client {
+        IpAddr::V4(ip) => IpAddr::V4(Ipv4Addr::from(u32::from(ip) & 0xFFFF_FF00)),
+        IpAddr::V6(ip) => IpAddr::V6(Ipv6Addr::from(
+            u128::from(ip) & 0xFFFF_FFFF_FFFF_FFFF_0000_0000_0000_0000,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::client::rr::Name;
+
+    fn check(limiter: &RateLimiter, client: &str, now: Instant) -> RateLimitAction {
+        let name = LowerName::from(Name::from_str("www.example.com.").unwrap());
+        let client = IpAddr::from_str(client).unwrap();
+        limiter.check(client, &name, RecordType::A, ResponseCode::NoError, now)
+    }
+
+    #[test]
+    fn test_flood_is_limited() {
+        let limiter = RateLimiter::new(RateLimitConfig::default());
+        let now = Instant::now();
+
+        for i in 0..5 {
+            assert_eq!(check(&limiter, "192.0.2.1", now), RateLimitAction::Send, "{}", i);
+        }
+
+        // every other limited response slips through truncated
+        assert_eq!(check(&limiter, "192.0.2.1", now), RateLimitAction::Drop);
+        assert_eq!(check(&limiter, "192.0.2.2", now), RateLimitAction::Truncate);
+        assert_eq!(check(&limiter, "192.0.2.3", now), RateLimitAction::Drop);
+
+        // other prefixes are limited apart
+        assert_eq!(check(&limiter, "192.0.3.1", now), RateLimitAction::Send);
+        assert_eq!(check(&limiter, "2001:db8::1", now), RateLimitAction::Send);
+
+        // until the window has passed
+        let later = now + Duration::from_secs(1);
+        assert_eq!(check(&limiter, "192.0.2.1", later), RateLimitAction::Send);
+    }
+
+    #[test]
+    fn test_ipv6_prefix() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            responses_per_window: 1,
+            window_secs: 1,
+            slip: 1,
+        });
+        let now = Instant::now();
+
+        assert_eq!(check(&limiter, "2001:db8::1", now), RateLimitAction::Send);
+        assert_eq!(
+            check(&limiter, "2001:db8::ffff:1", now),
+            RateLimitAction::Truncate
+        );
+        assert_eq!(check(&limiter, "2001:db8:0:1::1", now), RateLimitAction::Send);
+    }
+
+    #[test]
+    fn test_prune_once_per_window() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            window_secs: 2,
+            ..RateLimitConfig::default()
+        });
+        let flood = |first_octet: usize, now: Instant| {
+            for i in 0..=PRUNE_THRESHOLD {
+                let client = format!("{}.{}.{}.1", first_octet, i / 256, i % 256);
+                check(&limiter, &client, now);
+            }
+        };
+        let tracked = || limiter.windows.lock().unwrap().by_key.len();
+        let now = Instant::now();
+
+        // nothing has expired the first time the threshold is passed
+        flood(10, now);
+        flood(11, now + Duration::from_secs(1));
+        assert_eq!(tracked(), 2 * (PRUNE_THRESHOLD + 1));
+
+        // the first flood has expired, but the window since the last pruning has not
+        check(&limiter, "192.0.2.1", now + Duration::from_secs(2));
+        assert_eq!(tracked(), 2 * (PRUNE_THRESHOLD + 1) + 1);
+
+        check(&limiter, "192.0.3.1", now + Duration::from_secs(3));
+        assert_eq!(tracked(), 2);
+    }
+}
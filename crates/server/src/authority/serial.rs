@@ -0,0 +1,148 @@
+// Copyright 2015-2020 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! SOA serial numbers, and how they are advanced when a zone changes
+
+use std::cmp::Ordering;
+
+use chrono::{Datelike, Utc};
+use serde::Deserialize;
+
+use crate::client::rr::rdata::SOA;
+use crate::proto::rr::Serial;
+
+/// How the SOA serial of a zone is advanced each time the zone is changed
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum SerialScheme {
+    /// The serial is incremented by one
+    Counter,
+    /// The serial is the date of the change, as `YYYYMMDDnn` where `nn` counts the changes on that
+    ///  day. Once the changes of a day are exhausted, the serial continues to be incremented by one.
+    Date,
+}
+
+impl Default for SerialScheme {
+    fn default() -> Self {
+        SerialScheme::Counter
+    }
+}
+
+impl SerialScheme {
+    /// Returns the serial following `serial`, this is always greater in serial number arithmetic
+    pub fn next(self, serial: u32) -> u32 {
+        let today = Utc::now().date();
+        self.next_on(serial, today.year() as u32, today.month(), today.day())
+    }
+
+    fn next_on(self, serial: u32, year: u32, month: u32, day: u32) -> u32 {
+        match self {
+            SerialScheme::Counter => serial.wrapping_add(1),
+            SerialScheme::Date => {
+                let first_of_day = ((year * 100 + month) * 100 + day) * 100;
+                if Serial::from(first_of_day) > Serial::from(serial) {
+                    first_of_day
+                } else {
+                    serial.wrapping_add(1)
+                }
+            }
+        }
+    }
+}
+
+/// Whether a Secondary zone is to be transferred from its Primary, see `Refresh::check`
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Refresh {
+    /// The Primary has a newer serial, or the zone has no SOA yet, the zone is to be transferred
+    Transfer,
+    /// The serial of the zone is the same as, or newer than, that of the Primary
+    UpToDate,
+}
+
+impl Refresh {
+    /// Compares the SOA of a Secondary zone with that of its Primary
+    ///
+    /// [RFC 1034](https://tools.ietf.org/html/rfc1034), Domain Concepts and Facilities, November 1987
+    ///
+    /// ```text
+    /// 4.3.5. Zone maintenance and transfers
+    ///
+    /// To detect changes, secondaries just check the SERIAL field of the SOA
+    /// for the zone.
+    /// ```
+    ///
+    /// A serial which is not comparable with that of the Primary is transferred, the zone can not
+    ///  otherwise recover from it.
+    ///
+    /// # Arguments
+    ///
+    /// * `soa` - the SOA of the Secondary zone, None if the zone has not yet been transferred
+    /// * `primary_soa` - the SOA the Primary responded with
+    pub fn check(soa: Option<&SOA>, primary_soa: &SOA) -> Self {
+        let serial = match soa {
+            Some(soa) => Serial::from(soa.serial()),
+            None => return Refresh::Transfer,
+        };
+
+        match Serial::from(primary_soa.serial()).partial_cmp(&serial) {
+            Some(Ordering::Equal) | Some(Ordering::Less) => Refresh::UpToDate,
+            Some(Ordering::Greater) | None => Refresh::Transfer,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::client::rr::Name;
+    use crate::proto::rr::serial_gt;
+
+    #[test]
+    fn test_counter() {
+        assert_eq!(SerialScheme::Counter.next(1), 2);
+        assert_eq!(SerialScheme::Counter.next(u32::MAX), 0);
+        assert!(serial_gt(SerialScheme::Counter.next(u32::MAX), u32::MAX));
+    }
+
+    #[test]
+    fn test_date() {
+        let next = |serial| SerialScheme::Date.next_on(serial, 2020, 10, 15);
+
+        // an older, or counter based, serial moves to the date
+        assert_eq!(next(2020101403), 2020101500);
+        assert_eq!(next(7), 2020101500);
+
+        // further changes on the same day are counted
+        assert_eq!(next(2020101500), 2020101501);
+        assert_eq!(next(2020101501), 2020101502);
+
+        // a serial ahead of the date is incremented
+        assert_eq!(next(2020101600), 2020101601);
+    }
+
+    #[test]
+    fn test_refresh() {
+        let soa = |serial| {
+            let name = Name::from_str("example.com.").unwrap();
+            SOA::new(name.clone(), name, serial, 3600, 600, 86400, 300)
+        };
+        let check = |serial, primary| Refresh::check(Some(&soa(serial)), &soa(primary));
+
+        assert_eq!(check(1, 2), Refresh::Transfer);
+        assert_eq!(check(2, 2), Refresh::UpToDate);
+        assert_eq!(check(3, 2), Refresh::UpToDate);
+        assert_eq!(Refresh::check(None, &soa(1)), Refresh::Transfer);
+
+        // around the wrap, the Primary at 1 is newer than the Secondary at the maximum
+        assert_eq!(check(0xFFFF_FFFF, 0x0000_0001), Refresh::Transfer);
+        assert_eq!(check(0x0000_0001, 0xFFFF_FFFF), Refresh::UpToDate);
+
+        // without an ordering, the zone is transferred rather than left stuck
+        assert_eq!(check(0, 0x8000_0000), Refresh::Transfer);
+    }
+}
@@ -11,7 +11,7 @@ pub mod dnssec;
 
 use std::fs::File;
 use std::io::Read;
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::time::Duration;
@@ -23,8 +23,9 @@ use toml;
 use crate::client::rr::Name;
 use crate::proto::error::ProtoResult;
 
-use crate::authority::ZoneType;
+use crate::authority::{Acl, RateLimitConfig, SerialScheme, ZoneType};
 use crate::error::{ConfigError, ConfigResult};
+use crate::store::chaos::ChaosConfig;
 use crate::store::StoreConfig;
 
 static DEFAULT_PATH: &str = "/var/named"; // TODO what about windows (do I care? ;)
@@ -59,6 +60,10 @@ pub struct Config {
     zones: Vec<ZoneConfig>,
     /// Certificate to associate to TLS connections (currently the same is used for HTTPS and TLS)
     tls_cert: Option<dnssec::TlsCertConfig>,
+    /// The CHAOS class zone, `bind.`, not served unless configured
+    chaos: Option<ChaosConfig>,
+    /// Response rate limiting of UDP responses, not limited unless configured
+    rate_limit: Option<RateLimitConfig>,
 }
 
 impl Config {
@@ -141,6 +146,16 @@ impl Config {
     pub fn get_tls_cert(&self) -> Option<&dnssec::TlsCertConfig> {
         self.tls_cert.as_ref()
     }
+
+    /// the CHAOS class zone, answering `version.bind.` and `hostname.bind.`
+    pub fn get_chaos(&self) -> Option<&ChaosConfig> {
+        self.chaos.as_ref()
+    }
+
+    /// the rate limit of identical UDP responses to each client
+    pub fn get_rate_limit(&self) -> Option<&RateLimitConfig> {
+        self.rate_limit.as_ref()
+    }
 }
 
 impl FromStr for Config {
@@ -172,6 +187,23 @@ pub struct ZoneConfig {
     /// Store configurations, TODO: allow chained Stores
     #[serde(default)]
     pub stores: Option<StoreConfig>,
+    /// Secondaries to NOTIFY when the zone is updated
+    #[serde(default)]
+    pub notify: Vec<SocketAddr>,
+    /// Primaries of a Secondary zone, which is refreshed from them when a NOTIFY is received,
+    ///  only supported for zones in a File store
+    #[serde(default)]
+    pub primaries: Vec<SocketAddr>,
+    /// How the SOA serial is advanced on dynamic updates, Counter or Date, defaults to Counter
+    #[serde(default)]
+    pub serial_scheme: SerialScheme,
+    /// Rotate the records of the RRset in each answer, e.g. to spread clients across addresses
+    #[serde(default)]
+    pub rotate_rrsets: bool,
+    /// The prefixes of the clients allowed to query, transfer or update the zone, if unset any
+    ///  client may query or update the zone but none may transfer it
+    #[serde(default)]
+    pub acl: Option<Acl>,
 }
 
 impl ZoneConfig {
@@ -204,6 +236,11 @@ impl ZoneConfig {
             enable_dnssec,
             keys,
             stores: None,
+            notify: Vec::new(),
+            primaries: Vec::new(),
+            serial_scheme: SerialScheme::default(),
+            rotate_rrsets: false,
+            acl: None,
         }
     }
 
@@ -246,4 +283,29 @@ impl ZoneConfig {
     pub fn get_keys(&self) -> &[dnssec::KeyConfig] {
         &self.keys
     }
+
+    /// the Secondaries which are sent a NOTIFY when the serial of the zone is incremented
+    pub fn get_notify(&self) -> &[SocketAddr] {
+        &self.notify
+    }
+
+    /// the Primaries from which a Secondary zone is refreshed on NOTIFY
+    pub fn get_primaries(&self) -> &[SocketAddr] {
+        &self.primaries
+    }
+
+    /// how the SOA serial is advanced when the zone is updated
+    pub fn get_serial_scheme(&self) -> SerialScheme {
+        self.serial_scheme
+    }
+
+    /// rotate the order of the records of the RRset in each answer, round-robin
+    pub fn is_rrset_rotation_enabled(&self) -> bool {
+        self.rotate_rrsets
+    }
+
+    /// the access control list of the zone, see `Catalog::set_acl`
+    pub fn get_acl(&self) -> Option<&Acl> {
+        self.acl.as_ref()
+    }
 }
@@ -17,7 +17,7 @@ use trust_dns_https::https_server;
 
 use crate::authority::{MessageRequest, MessageResponse};
 use crate::proto::serialize::binary::BinDecodable;
-use crate::server::request_handler::RequestHandler;
+use crate::server::request_handler::{Protocol, RequestHandler};
 use crate::server::response_handler::ResponseHandler;
 use crate::server::server_future;
 
@@ -55,7 +55,10 @@ pub async fn h2_handler<T, I>(
         debug!("Received request: {:#?}", request);
         let dns_hostname = dns_hostname.clone();
         let handler = handler.clone();
-        let responder = HttpsResponseHandle(Arc::new(Mutex::new(respond)));
+        let responder = HttpsResponseHandle {
+            respond: Arc::new(Mutex::new(respond)),
+            block_size: None,
+        };
 
         match https_server::message_from(dns_hostname, request).await {
             Ok(bytes) => handle_request(bytes, src_addr, handler, responder).await,
@@ -70,7 +73,7 @@ async fn handle_request<T>(
     bytes: BytesMut,
     src_addr: SocketAddr,
     handler: Arc<Mutex<T>>,
-    responder: HttpsResponseHandle,
+    mut responder: HttpsResponseHandle,
 ) where
     T: RequestHandler,
 {
@@ -83,31 +86,30 @@ async fn handle_request<T>(
     };
 
     debug!("received message: {:?}", message);
+    responder.block_size = server_future::response_block_size(&message, Protocol::Https);
 
-    server_future::handle_request(message, src_addr, handler, responder).await
+    server_future::handle_request(message, src_addr, Protocol::Https, handler, responder).await
 }
 
 #[derive(Clone)]
-struct HttpsResponseHandle(Arc<Mutex<::h2::server::SendResponse<Bytes>>>);
+struct HttpsResponseHandle {
+    respond: Arc<Mutex<::h2::server::SendResponse<Bytes>>>,
+    block_size: Option<u16>,
+}
 
 impl ResponseHandler for HttpsResponseHandle {
     fn send_response(&mut self, response: MessageResponse) -> io::Result<()> {
-        use crate::proto::serialize::binary::BinEncoder;
         use trust_dns_https::response;
         use trust_dns_https::HttpsError;
 
         let mut bytes = Vec::with_capacity(512);
-        // mut block
-        {
-            let mut encoder = BinEncoder::new(&mut bytes);
-            response.destructive_emit(&mut encoder)?;
-        };
+        response.destructive_emit_padded(&mut bytes, u16::max_value(), self.block_size)?;
         let bytes = Bytes::from(bytes);
         let response = response::new(bytes.len())?;
 
         debug!("sending response: {:#?}", response);
         let mut stream = self
-            .0
+            .respond
             .lock()
             .expect("https poisoned")
             .send_response(response, false)
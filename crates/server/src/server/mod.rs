@@ -14,7 +14,7 @@ mod response_handler;
 mod server_future;
 mod timeout_stream;
 
-pub use self::request_handler::{Request, RequestHandler};
+pub use self::request_handler::{Protocol, Request, RequestHandler};
 pub use self::response_handler::{ResponseHandle, ResponseHandler};
 pub use self::server_future::ServerFuture;
 pub use self::timeout_stream::TimeoutStream;
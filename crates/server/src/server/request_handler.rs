@@ -13,12 +13,39 @@ use std::net::SocketAddr;
 use crate::authority::MessageRequest;
 use crate::server::ResponseHandler;
 
+/// The protocol a request was received over
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    /// UDP, responses are limited to the payload size of the requestor
+    Udp,
+    /// TCP
+    Tcp,
+    /// DNS over TLS
+    Tls,
+    /// DNS over HTTPS
+    Https,
+}
+
+impl Protocol {
+    /// Returns true if this is a datagram oriented protocol, i.e. UDP
+    pub fn is_datagram(self) -> bool {
+        self == Protocol::Udp
+    }
+
+    /// Returns true if this is an encrypted protocol, i.e. TLS or HTTPS
+    pub fn is_encrypted(self) -> bool {
+        self == Protocol::Tls || self == Protocol::Https
+    }
+}
+
 /// An incoming request to the DNS catalog
 pub struct Request {
     /// Message with the associated query or update data
     pub message: MessageRequest,
     /// Source address of the Client
     pub src: SocketAddr,
+    /// Protocol the request was received over
+    pub protocol: Protocol,
 }
 
 /// Trait for handling incoming requests, and providing a message response.
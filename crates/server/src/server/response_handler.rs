@@ -11,7 +11,6 @@ use std::net::SocketAddr;
 use log::info;
 
 use crate::authority::MessageResponse;
-use crate::client::serialize::binary::BinEncoder;
 use crate::proto::xfer::SerialMessage;
 use crate::proto::BufStreamHandle;
 
@@ -32,12 +31,34 @@ pub trait ResponseHandler: Clone + Send + Unpin + 'static {
 pub struct ResponseHandle {
     dst: SocketAddr,
     stream_handle: BufStreamHandle,
+    max_size: u16,
+    block_size: Option<u16>,
 }
 
 impl ResponseHandle {
     /// Returns a new `ResponseHandle` for sending a response message
     pub fn new(dst: SocketAddr, stream_handle: BufStreamHandle) -> Self {
-        ResponseHandle { dst, stream_handle }
+        ResponseHandle {
+            dst,
+            stream_handle,
+            max_size: u16::max_value(),
+            block_size: None,
+        }
+    }
+
+    /// Limits the size of the response, i.e. to the UDP payload size of the requestor
+    ///
+    /// Responses which are too large drop the additional section, and are then truncated.
+    pub fn set_max_size(&mut self, max_size: u16) {
+        self.max_size = max_size;
+    }
+
+    /// Pads the response to a multiple of `block_size` octets, with the EDNS Padding option
+    ///
+    /// This should only be set for encrypted connections, where the size of the response would
+    ///  otherwise reveal its content.
+    pub fn set_padding(&mut self, block_size: u16) {
+        self.block_size = Some(block_size);
     }
 }
 
@@ -52,10 +73,8 @@ impl ResponseHandler for ResponseHandle {
             response.header().response_code(),
         );
         let mut buffer = Vec::with_capacity(512);
-        let encode_result = {
-            let mut encoder: BinEncoder = BinEncoder::new(&mut buffer);
-            response.destructive_emit(&mut encoder)
-        };
+        let encode_result =
+            response.destructive_emit_padded(&mut buffer, self.max_size, self.block_size);
 
         encode_result.map_err(|e| {
             io::Error::new(
@@ -23,12 +23,15 @@ use crate::authority::MessageRequest;
 use crate::proto::error::ProtoError;
 use crate::proto::iocompat::AsyncIoTokioAsStd;
 use crate::proto::op::Edns;
+use crate::proto::rr::rdata::opt::EdnsCode;
 use crate::proto::serialize::binary::{BinDecodable, BinDecoder};
 use crate::proto::tcp::TcpStream;
 use crate::proto::udp::UdpStream;
 use crate::proto::xfer::SerialMessage;
 use crate::proto::BufStreamHandle;
-use crate::server::{Request, RequestHandler, ResponseHandle, ResponseHandler, TimeoutStream};
+use crate::server::{
+    Protocol, Request, RequestHandler, ResponseHandle, ResponseHandler, TimeoutStream,
+};
 #[cfg(all(feature = "dns-over-openssl", not(feature = "dns-over-rustls")))]
 use trust_dns_openssl::tls_server::*;
 
@@ -76,7 +79,8 @@ impl<T: RequestHandler> ServerFuture<T> {
                     let stream_handle = stream_handle.clone();
 
                     tokio::spawn(async move {
-                        self::handle_raw_request(message, handler, stream_handle).await;
+                        self::handle_raw_request(message, handler, stream_handle, Protocol::Udp)
+                            .await;
                     });
                 }
 
@@ -155,6 +159,7 @@ impl<T: RequestHandler> ServerFuture<T> {
                                 message,
                                 handler.clone(),
                                 stream_handle.clone(),
+                                Protocol::Tcp,
                             )
                             .await;
                         }
@@ -272,6 +277,7 @@ impl<T: RequestHandler> ServerFuture<T> {
                                 message,
                                 handler.clone(),
                                 stream_handle.clone(),
+                                Protocol::Tls,
                             )
                             .await;
                         }
@@ -403,6 +409,7 @@ impl<T: RequestHandler> ServerFuture<T> {
                                 message,
                                 handler.clone(),
                                 stream_handle.clone(),
+                                Protocol::Tls,
                             )
                             .await;
                         }
@@ -546,9 +553,10 @@ pub(crate) fn handle_raw_request<T: RequestHandler>(
     message: SerialMessage,
     request_handler: Arc<Mutex<T>>,
     response_handler: BufStreamHandle,
+    protocol: Protocol,
 ) -> HandleRawRequest<T::ResponseFuture> {
     let src_addr = message.addr();
-    let response_handler = ResponseHandle::new(message.addr(), response_handler);
+    let mut response_handler = ResponseHandle::new(message.addr(), response_handler);
 
     // TODO: rather than decoding the message here, this RequestStream should instead
     //       forward the request to another sender such that we could pull serialization off
@@ -557,23 +565,62 @@ pub(crate) fn handle_raw_request<T: RequestHandler>(
     let mut decoder = BinDecoder::new(message.bytes());
     match MessageRequest::read(&mut decoder) {
         Ok(message) => {
-            let handle_request =
-                self::handle_request(message, src_addr, request_handler, response_handler);
+            // responses over UDP must fit in the payload size of the requestor, at least 512
+            if protocol.is_datagram() {
+                response_handler.set_max_size(message.max_payload());
+            }
+            if let Some(block_size) = response_block_size(&message, protocol) {
+                response_handler.set_padding(block_size);
+            }
+
+            let handle_request = self::handle_request(
+                message,
+                src_addr,
+                protocol,
+                request_handler,
+                response_handler,
+            );
             HandleRawRequest::HandleRequest(handle_request)
         }
         Err(e) => HandleRawRequest::Result(e.into()),
     }
 }
 
+/// The block size to pad the response to, if it is sent over an encrypted protocol
+///
+/// [RFC 7830](https://tools.ietf.org/html/rfc7830#section-4), The EDNS(0) Padding Option, May 2016
+///
+/// ```text
+/// Responders MUST pad DNS responses when the respective DNS query
+/// included the 'Padding' option, unless doing so would violate the
+/// maximum UDP payload size.
+/// ```
+///
+/// The block size of 468 is as recommended by RFC 8467.
+pub(crate) fn response_block_size(message: &MessageRequest, protocol: Protocol) -> Option<u16> {
+    let padded = message
+        .edns()
+        .and_then(|edns| edns.option(EdnsCode::Padding))
+        .is_some();
+
+    if padded && protocol.is_encrypted() {
+        Some(468)
+    } else {
+        None
+    }
+}
+
 pub(crate) fn handle_request<R: ResponseHandler, T: RequestHandler>(
     message: MessageRequest,
     src_addr: SocketAddr,
+    protocol: Protocol,
     request_handler: Arc<Mutex<T>>,
     response_handler: R,
 ) -> T::ResponseFuture {
     let request = Request {
         message,
         src: src_addr,
+        protocol,
     };
 
     info!(
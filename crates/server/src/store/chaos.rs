@@ -0,0 +1,61 @@
+// Copyright 2015-2020 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The built-in CHAOS class zone, `bind.`, answering `version.bind.` and `hostname.bind.`
+
+use serde::Deserialize;
+
+use crate::authority::ZoneType;
+use crate::client::rr::rdata::{SOA, TXT};
+use crate::client::rr::{DNSClass, Name, RData, Record};
+use crate::store::in_memory::InMemoryAuthority;
+
+/// Configuration for the CHAOS class zone, which is only served when configured
+#[derive(Deserialize, PartialEq, Debug, Default)]
+pub struct ChaosConfig {
+    /// The TXT answered to `version.bind. CH TXT`, the name does not exist if unset
+    pub version: Option<String>,
+    /// The TXT answered to `hostname.bind. CH TXT`, the name does not exist if unset
+    pub hostname: Option<String>,
+}
+
+impl ChaosConfig {
+    /// The origin of the zone, `bind.`
+    pub fn origin() -> Name {
+        Name::from_ascii("bind.").expect("bad chaos origin")
+    }
+
+    /// Creates the CHAOS class zone with the configured records
+    pub fn authority(&self) -> InMemoryAuthority {
+        let origin = Self::origin();
+        let mut authority = InMemoryAuthority::empty(origin.clone(), ZoneType::Primary, false);
+        authority.set_class(DNSClass::CH);
+
+        let chaos = |name: Name, rdata: RData| {
+            let mut record = Record::from_rdata(name, 0, rdata);
+            record.set_dns_class(DNSClass::CH);
+            record
+        };
+
+        // the same SOA and NS as the CHAOS zone of BIND
+        let hostmaster = Name::from_ascii("hostmaster.bind.").expect("bad chaos hostmaster");
+        let soa = SOA::new(origin.clone(), hostmaster, 0, 28_800, 7_200, 604_800, 86_400);
+        authority.upsert(chaos(origin.clone(), RData::SOA(soa)), 0);
+        authority.upsert(chaos(origin.clone(), RData::NS(origin)), 0);
+
+        let txts = [("version.bind.", &self.version), ("hostname.bind.", &self.hostname)];
+        for (name, txt) in txts.iter() {
+            if let Some(txt) = txt {
+                let name = Name::from_ascii(name).expect("bad chaos name");
+                let rdata = RData::TXT(TXT::new(vec![txt.to_string()]));
+                authority.upsert(chaos(name, rdata), 0);
+            }
+        }
+
+        authority
+    }
+}
@@ -10,18 +10,25 @@
 use std::collections::BTreeMap;
 use std::fs::File;
 use std::future::Future;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Read};
+use std::net::SocketAddr;
 use std::ops::{Deref, DerefMut};
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
+use std::time::Instant;
 
 use log::{debug, info};
 
-use crate::authority::{Authority, LookupError, MessageRequest, UpdateResult, ZoneType};
+use crate::authority::{
+    Authority, Axfr, Ixfr, LookupError, MessageRequest, Refresh, UpdateResult, ZoneType,
+};
 use crate::client::op::LowerQuery;
 use crate::client::proto::rr::dnssec::rdata::key::KEY;
-use crate::client::rr::dnssec::{DnsSecResult, Signer, SupportedAlgorithms};
-use crate::client::rr::{LowerName, Name, RecordSet, RecordType, RrKey};
+use crate::client::rr::dnssec::{
+    DnsSecResult, Signer, SupportedAlgorithms, TsigKey, TsigResponder,
+};
+use crate::client::rr::rdata::SOA;
+use crate::client::rr::{DNSClass, LowerName, Name, Record, RecordSet, RecordType, RrKey};
 use crate::client::serialize::txt::{Lexer, Parser, Token};
 use crate::store::file::FileConfig;
 use crate::store::in_memory::InMemoryAuthority;
@@ -190,10 +197,45 @@ impl FileAuthority {
         FileAuthority::read_file(zone_path, &mut buf, FileReaderState::new())
             .map_err(|e| format!("failed to read {}: {:?}", &config.zone_file_path, e))?;
 
-        let lexer = Lexer::new(&buf);
-        let (origin, records) = Parser::new()
+        FileAuthority::parse(origin, &buf, zone_type, allow_axfr, &config.zone_file_path)
+    }
+
+    /// Read the Authority for the origin from a zone in the master file format
+    ///
+    /// `$ORIGIN`, `$TTL`, names relative to the origin and records continued across lines with
+    ///  parentheses are all supported. `$INCLUDE` is not, as there is no file to resolve it
+    ///  against, see `try_from_config`.
+    ///
+    /// # Errors
+    ///
+    /// Parse errors include the line of the zone on which the failing record started.
+    pub fn read_from<R: Read>(
+        origin: Name,
+        mut reader: R,
+        zone_type: ZoneType,
+        allow_axfr: bool,
+    ) -> Result<Self, String> {
+        let mut buf = String::new();
+        reader
+            .read_to_string(&mut buf)
+            .map_err(|e| format!("failed to read zone for {}: {}", origin, e))?;
+
+        let zone = origin.to_string();
+        FileAuthority::parse(origin, &buf, zone_type, allow_axfr, &zone)
+    }
+
+    fn parse(
+        origin: Name,
+        buf: &str,
+        zone_type: ZoneType,
+        allow_axfr: bool,
+        source: &str,
+    ) -> Result<Self, String> {
+        let lexer = Lexer::new(buf);
+        let mut parser = Parser::new();
+        let (origin, records) = parser
             .parse(lexer, Some(origin), None)
-            .map_err(|e| format!("failed to parse {}: {:?}", config.zone_file_path, e))?;
+            .map_err(|e| format!("failed to parse {}: {}", source, e))?;
 
         info!(
             "zone file loaded: {} with {} records",
@@ -202,7 +244,9 @@ impl FileAuthority {
         );
         debug!("zone: {:#?}", records);
 
-        FileAuthority::new(origin, records, zone_type, allow_axfr)
+        let mut authority = FileAuthority::new(origin, records, zone_type, allow_axfr)?;
+        authority.set_default_ttl(parser.default_ttl());
+        Ok(authority)
     }
 
     /// Unwrap the InMemoryAuthority
@@ -250,6 +294,46 @@ impl Authority for FileAuthority {
         self.0.origin()
     }
 
+    /// The DNSClass of the records in this zone
+    fn class(&self) -> DNSClass {
+        self.0.class()
+    }
+
+    /// Returns the changes to the zone since the specified SOA serial
+    fn ixfr(&self, serial: u32) -> Ixfr {
+        self.0.ixfr(serial)
+    }
+
+    /// Returns all records of the zone for a full zone transfer
+    fn axfr(&self) -> Result<Axfr, LookupError> {
+        self.0.axfr()
+    }
+
+    /// The addresses of Secondaries which are sent a NOTIFY when the serial of the zone changes
+    fn notify_targets(&self) -> &[SocketAddr] {
+        self.0.notify_targets()
+    }
+
+    /// The addresses of the Primaries from which this zone, a Secondary, is refreshed
+    fn primaries(&self) -> &[SocketAddr] {
+        self.0.primaries()
+    }
+
+    /// Decides whether this zone is to be transferred, given the SOA of its Primary
+    fn refresh(&self, primary_soa: &SOA) -> Refresh {
+        self.0.refresh(primary_soa)
+    }
+
+    /// Replaces the records of this zone with those of an AXFR from its Primary
+    fn transfer(&mut self, records: Vec<Record>) -> UpdateResult<()> {
+        self.0.transfer(records)
+    }
+
+    /// Records when this zone was last refreshed from its Primary
+    fn set_refreshed(&mut self, refreshed: Instant) {
+        self.0.set_refreshed(refreshed)
+    }
+
     /// Looks up all Resource Records matching the giving `Name` and `RecordType`.
     ///
     /// # Arguments
@@ -337,11 +421,35 @@ impl Authority for FileAuthority {
         self.0.soa_secure(is_secure, supported_algorithms)
     }
 
+    /// Returns the SOA record for the authority section of a negative response
+    fn negative_soa(
+        &self,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Lookup, LookupError>> + Send>> {
+        self.0.negative_soa(is_secure, supported_algorithms)
+    }
+
     /// Add a (Sig0) key that is authorized to perform updates against this authority
     fn add_update_auth_key(&mut self, name: Name, key: KEY) -> DnsSecResult<()> {
         self.0.add_update_auth_key(name, key)
     }
 
+    /// Add a TSIG key that is authorized to perform updates and zone transfers
+    fn add_tsig_key(&mut self, key: TsigKey) -> DnsSecResult<()> {
+        self.0.add_tsig_key(key)
+    }
+
+    /// Checks the TSIG of a zone transfer request, if TSIG keys were added
+    fn authorize_axfr(&self, request: &MessageRequest) -> UpdateResult<()> {
+        self.0.authorize_axfr(request)
+    }
+
+    /// Verifies the TSIG of the request, if it is signed with TSIG
+    fn tsig_responder(&self, request: &MessageRequest) -> Option<TsigResponder> {
+        self.0.tsig_responder(request)
+    }
+
     /// Add Signer
     fn add_zone_signing_key(&mut self, signer: Signer) -> DnsSecResult<()> {
         self.0.add_zone_signing_key(signer)
@@ -358,7 +466,7 @@ mod tests {
     use std::net::Ipv4Addr;
     use std::str::FromStr;
 
-    use crate::client::rr::RData;
+    use crate::client::rr::{RData, Record};
     use futures_executor::block_on;
 
     use super::*;
@@ -416,4 +524,214 @@ mod tests {
             _ => panic!("wrong rdata type returned"),
         }
     }
+
+    #[test]
+    fn test_read_from() {
+        let zone = r#"
+$ORIGIN example.com.
+$TTL 3600
+@       IN  SOA     ns1 hostmaster (
+                        2020101501 ; SERIAL
+                        7200       ; REFRESH
+                        600        ; RETRY
+                        3600000    ; EXPIRE
+                        60 )       ; MINIMUM
+        IN  NS      ns1
+        IN  NS      ns2.example.net.
+        IN  MX  10  mail
+ns1     IN  A       192.0.2.1
+www     IN  A       192.0.2.2
+        IN  A       192.0.2.3
+        IN  AAAA    2001:db8::2
+mail    300 IN A    192.0.2.4
+alias   IN  CNAME   www
+_sip._udp IN SRV    10 5 5060 www
+info    IN  TXT     "v=spf1 -all"
+"#;
+
+        let authority = FileAuthority::read_from(
+            Name::from_str("example.com.").unwrap(),
+            zone.as_bytes(),
+            ZoneType::Primary,
+            false,
+        )
+        .expect("failed to read zone");
+
+        let count = |name: &str, rtype| {
+            block_on(Authority::lookup(
+                &authority,
+                &LowerName::from_str(name).unwrap(),
+                rtype,
+                false,
+                SupportedAlgorithms::new(),
+            ))
+            .map(|lookup| lookup.iter().count())
+            .unwrap_or(0)
+        };
+
+        assert_eq!(count("example.com.", RecordType::SOA), 1);
+        assert_eq!(count("example.com.", RecordType::NS), 2);
+        assert_eq!(count("example.com.", RecordType::MX), 1);
+        assert_eq!(count("www.example.com.", RecordType::A), 2);
+        assert_eq!(count("www.example.com.", RecordType::AAAA), 1);
+        assert_eq!(count("alias.example.com.", RecordType::CNAME), 1);
+        assert_eq!(count("_sip._udp.example.com.", RecordType::SRV), 1);
+        assert_eq!(count("info.example.com.", RecordType::TXT), 1);
+
+        let mail = block_on(Authority::lookup(
+            &authority,
+            &LowerName::from_str("mail.example.com.").unwrap(),
+            RecordType::A,
+            false,
+            SupportedAlgorithms::new(),
+        ))
+        .expect("mail lookup failed");
+        let mail = mail.iter().next().expect("A record not found for mail");
+        assert_eq!(mail.ttl(), 300);
+
+        let ns1 = block_on(Authority::lookup(
+            &authority,
+            &LowerName::from_str("ns1.example.com.").unwrap(),
+            RecordType::A,
+            false,
+            SupportedAlgorithms::new(),
+        ))
+        .expect("ns1 lookup failed");
+        assert_eq!(ns1.iter().next().expect("A record not found").ttl(), 3600);
+
+        // the explicit TTL of mail does not carry over to the following records
+        let info = block_on(Authority::lookup(
+            &authority,
+            &LowerName::from_str("info.example.com.").unwrap(),
+            RecordType::TXT,
+            false,
+            SupportedAlgorithms::new(),
+        ))
+        .expect("info lookup failed");
+        assert_eq!(info.iter().next().expect("TXT record not found").ttl(), 3600);
+    }
+
+    #[test]
+    fn test_default_ttl() {
+        let zone = r#"
+$ORIGIN example.com.
+$TTL 3600
+@       IN  SOA     ns1 hostmaster ( 1 7200 600 3600000 60 )
+        IN  NS      ns1
+ns1     IN  A       192.0.2.1
+"#;
+
+        let mut authority = FileAuthority::read_from(
+            Name::from_str("example.com.").unwrap(),
+            zone.as_bytes(),
+            ZoneType::Primary,
+            false,
+        )
+        .expect("failed to read zone");
+        assert_eq!(authority.default_ttl(), 3600);
+
+        let soa = block_on(authority.soa()).expect("soa lookup failed");
+        assert_eq!(soa.iter().next().expect("SOA not found").ttl(), 3600);
+
+        // a record added without a TTL gets the $TTL of the zone
+        let www = Name::from_str("www.example.com.").unwrap();
+        assert!(authority.upsert_rdata(www.clone(), RData::A(Ipv4Addr::new(192, 0, 2, 2)), 1));
+        let lookup = block_on(Authority::lookup(
+            &authority,
+            &LowerName::from(&www),
+            RecordType::A,
+            false,
+            SupportedAlgorithms::new(),
+        ))
+        .expect("www lookup failed");
+        assert_eq!(lookup.iter().next().expect("A record not found").ttl(), 3600);
+
+        // negative answers may only be cached for the SOA minimum
+        let negative_soa = block_on(authority.negative_soa(false, SupportedAlgorithms::new()))
+            .expect("negative soa lookup failed");
+        let negative_soa = negative_soa.iter().next().expect("SOA not found");
+        assert_eq!(negative_soa.rr_type(), RecordType::SOA);
+        assert_eq!(negative_soa.ttl(), 60);
+
+        // without $TTL the default is the SOA minimum
+        authority.set_default_ttl(None);
+        assert_eq!(authority.default_ttl(), 60);
+        let mail = Name::from_str("mail.example.com.").unwrap();
+        assert!(authority.upsert_rdata(mail.clone(), RData::A(Ipv4Addr::new(192, 0, 2, 3)), 1));
+        let lookup = block_on(Authority::lookup(
+            &authority,
+            &LowerName::from(&mail),
+            RecordType::A,
+            false,
+            SupportedAlgorithms::new(),
+        ))
+        .expect("mail lookup failed");
+        assert_eq!(lookup.iter().next().expect("A record not found").ttl(), 60);
+    }
+
+    fn zone_records(authority: &FileAuthority) -> Vec<(Record, u32)> {
+        authority
+            .records()
+            .values()
+            .flat_map(|rrset| rrset.records_without_rrsigs())
+            .map(|record| (record.clone(), record.ttl()))
+            .collect()
+    }
+
+    #[test]
+    fn test_zone_file_round_trip() {
+        let config = FileConfig {
+            zone_file_path: "../../tests/test-data/named_test_configs/example.com.zone".to_string(),
+        };
+        let origin = Name::from_str("example.com.").unwrap();
+        let loaded =
+            FileAuthority::try_from_config(origin.clone(), ZoneType::Primary, false, None, &config)
+                .expect("failed to load file");
+
+        let zone = loaded.to_zone_file();
+        assert!(zone.starts_with("$ORIGIN example.com.\nexample.com.\t"));
+        assert!(zone.lines().nth(1).unwrap().contains("\tSOA\t"));
+
+        let reloaded =
+            FileAuthority::read_from(origin.clone(), zone.as_bytes(), ZoneType::Primary, false)
+                .expect("failed to reload dumped zone");
+        assert_eq!(zone_records(&loaded), zone_records(&reloaded));
+        assert_eq!(zone, reloaded.to_zone_file());
+
+        // TXT character-strings with whitespace, quotes, escapes and comment characters
+        let zone = r#"
+@       IN  SOA     ns1 hostmaster ( 1 7200 600 3600000 60 )
+        IN  NS      ns1
+info    IN  TXT     "v=spf1 -all" "say \"hi\"; \\ done" ""
+"#;
+        let loaded =
+            FileAuthority::read_from(origin.clone(), zone.as_bytes(), ZoneType::Primary, false)
+                .expect("failed to read zone");
+        let zone = loaded.to_zone_file();
+        assert!(zone.contains("\"v=spf1 -all\" \"say \\\"hi\\\"; \\\\ done\" \"\""));
+
+        let reloaded = FileAuthority::read_from(origin, zone.as_bytes(), ZoneType::Primary, false)
+            .expect("failed to reload dumped zone");
+        assert_eq!(zone_records(&loaded), zone_records(&reloaded));
+    }
+
+    #[test]
+    fn test_read_from_error_line() {
+        let zone = r#"$ORIGIN example.com.
+$TTL 3600
+@       IN  SOA     ns1 hostmaster ( 1 7200 600 3600000 60 )
+www     IN  A       192.0.2.2
+bad     IN  A       not.an.address
+"#;
+
+        let error = FileAuthority::read_from(
+            Name::from_str("example.com.").unwrap(),
+            zone.as_bytes(),
+            ZoneType::Primary,
+            false,
+        )
+        .err()
+        .expect("zone should not parse");
+        assert!(error.contains("line 5:"), "{}", error);
+    }
 }
@@ -11,7 +11,7 @@ use std::pin::Pin;
 use std::task::{Context, Poll};
 
 use futures_util::{future, FutureExt};
-use log::info;
+use log::{info, warn};
 
 use crate::client::op::LowerQuery;
 use crate::client::op::ResponseCode;
@@ -19,6 +19,7 @@ use crate::client::rr::dnssec::SupportedAlgorithms;
 use crate::client::rr::{LowerName, Name, Record, RecordType};
 use crate::resolver::config::ResolverConfig;
 use crate::resolver::error::ResolveError;
+use crate::proto::xfer::DnsRequestOptions;
 use crate::resolver::lookup::Lookup as ResolverLookup;
 use crate::resolver::{TokioAsyncResolver, TokioHandle};
 
@@ -27,12 +28,18 @@ use crate::authority::{
 };
 use crate::store::forwarder::ForwardConfig;
 
+/// The default number of times a query may have been forwarded, see `ForwardConfig::hop_limit`
+const DEFAULT_HOP_LIMIT: u8 = 8;
+
 /// An authority that will forward resolutions to upstream resolvers.
 ///
-/// This uses the trust-dns-resolver for resolving requests.
+/// This uses the trust-dns-resolver for resolving requests. Forwarded queries carry the number of
+///  times they have been forwarded, queries which reach the hop limit are refused with SERVFAIL
+///  rather than looping between servers which forward to each other.
 pub struct ForwardAuthority {
     origin: LowerName,
     resolver: TokioAsyncResolver,
+    hop_limit: u8,
 }
 
 impl ForwardAuthority {
@@ -46,6 +53,7 @@ impl ForwardAuthority {
         Ok(ForwardAuthority {
             origin: Name::root().into(),
             resolver,
+            hop_limit: DEFAULT_HOP_LIMIT,
         })
     }
 
@@ -58,7 +66,7 @@ impl ForwardAuthority {
         info!("loading forwarder config: {}", origin);
 
         let name_servers = config.name_servers.clone();
-        let options = config.options.unwrap_or_default();
+        let options = config.options.clone().unwrap_or_default();
         let config = ResolverConfig::from_parts(None, vec![], name_servers);
 
         let resolver = TokioAsyncResolver::new(config, options, TokioHandle)
@@ -70,8 +78,38 @@ impl ForwardAuthority {
         Ok(ForwardAuthority {
             origin: origin.into(),
             resolver,
+            hop_limit: config.hop_limit.unwrap_or(DEFAULT_HOP_LIMIT),
         })
     }
+
+    /// Forwards the lookup, which has already been forwarded `hops` times
+    fn forward_lookup(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        hops: u8,
+    ) -> Pin<Box<dyn Future<Output = Result<ForwardLookup, LookupError>> + Send>> {
+        // only names in the zone are forwarded
+        if !self.origin.zone_of(name) {
+            return Box::pin(future::err(LookupError::from(ResponseCode::Refused)));
+        }
+
+        if hops >= self.hop_limit {
+            warn!(
+                "forwarding loop, refusing lookup: {} {} after {} hops",
+                name, rtype, hops
+            );
+            return Box::pin(future::err(LookupError::from(ResponseCode::ServFail)));
+        }
+
+        info!("forwarding lookup: {} {}", name, rtype);
+        let name: LowerName = name.clone();
+        let options = DnsRequestOptions {
+            forward_hops: Some(hops + 1),
+            ..Default::default()
+        };
+        Box::pin(ForwardLookupFuture(self.resolver.lookup(name, rtype, options)))
+    }
 }
 
 impl Authority for ForwardAuthority {
@@ -109,16 +147,7 @@ impl Authority for ForwardAuthority {
         _is_secure: bool,
         _supported_algorithms: SupportedAlgorithms,
     ) -> Pin<Box<dyn Future<Output = Result<Self::Lookup, LookupError>> + Send>> {
-        // TODO: make this an error?
-        assert!(self.origin.zone_of(name));
-
-        info!("forwarding lookup: {} {}", name, rtype);
-        let name: LowerName = name.clone();
-        Box::pin(ForwardLookupFuture(self.resolver.lookup(
-            name,
-            rtype,
-            Default::default(),
-        )))
+        self.forward_lookup(name, rtype, 0)
     }
 
     fn search(
@@ -135,6 +164,17 @@ impl Authority for ForwardAuthority {
         ))
     }
 
+    /// Forwards the lookup, unless it has reached the hop limit of this authority
+    fn forward(
+        &self,
+        query: &LowerQuery,
+        hops: u8,
+        _is_secure: bool,
+        _supported_algorithms: SupportedAlgorithms,
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Lookup, LookupError>> + Send>> {
+        self.forward_lookup(query.name(), query.query_type(), hops)
+    }
+
     fn get_nsec_records(
         &self,
         _name: &LowerName,
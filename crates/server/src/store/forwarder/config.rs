@@ -16,4 +16,7 @@ pub struct ForwardConfig {
     pub name_servers: NameServerConfigGroup,
     /// Resolver options
     pub options: Option<ResolverOpts>,
+    /// The number of times a query may have been forwarded between servers before it is refused
+    ///  with SERVFAIL as a forwarding loop, defaults to 8
+    pub hop_limit: Option<u8>,
 }
@@ -8,16 +8,22 @@
 //! All authority related types
 
 use std::borrow::Borrow;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::{self, Write};
 use std::future::Future;
+use std::net::SocketAddr;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use futures_util::future::{self, TryFutureExt};
-use log::{debug, error};
+use log::{debug, error, warn};
 
 use crate::client::op::{LowerQuery, ResponseCode};
-use crate::client::rr::dnssec::{DnsSecResult, Signer, SupportedAlgorithms};
+use crate::client::rr::dnssec::{
+    DnsSecResult, Signer, SupportedAlgorithms, TsigKey, TsigResponder,
+};
 use crate::client::rr::rdata::key::KEY;
 #[cfg(feature = "dnssec")]
 use crate::client::rr::rdata::DNSSECRData;
@@ -26,10 +32,13 @@ use crate::client::rr::rdata::SOA;
 use crate::client::rr::{DNSClass, LowerName, Name, RData, Record, RecordSet, RecordType, RrKey};
 
 use crate::authority::{
-    AnyRecords, AuthLookup, Authority, LookupError, LookupRecords, LookupResult, MessageRequest,
-    UpdateResult, ZoneType,
+    AnyRecords, AuthLookup, Authority, Axfr, Ixfr, LookupError, LookupRecords, LookupResult,
+    MessageRequest, Refresh, Serial, SerialScheme, UpdateResult, ZoneDelta, ZoneType,
 };
 
+/// The number of previous versions of the zone retained for incremental zone transfers
+const MAX_HISTORY: usize = 16;
+
 /// InMemoryAuthority is responsible for storing the resource records for a particular zone.
 ///
 /// Authorities default to DNSClass IN. The ZoneType specifies if this should be treated as the
@@ -46,6 +55,21 @@ pub struct InMemoryAuthority {
     //   may not support dynamic updates to register the new key... Trust-DNS will provide support
     //   for this, in some form, perhaps alternate root zones...
     secure_keys: Vec<Signer>,
+    // shared secrets of which one must sign updates and transfers, if any
+    tsig_keys: Vec<TsigKey>,
+    // previous versions of the zone by serial, oldest first, for computing IXFR deltas
+    history: VecDeque<(u32, BTreeMap<RrKey, Arc<RecordSet>>)>,
+    notify_targets: Vec<SocketAddr>,
+    primaries: Vec<SocketAddr>,
+    serial_scheme: SerialScheme,
+    minimal_any: bool,
+    rotate_rrsets: bool,
+    // the number of answers rotated so far, by which the next is rotated
+    rotations: AtomicUsize,
+    // when a Secondary zone was last refreshed from its Primary, or loaded
+    refreshed: Instant,
+    // the $TTL of the zone, for records without a TTL of their own
+    default_ttl: Option<u32>,
 }
 
 impl InMemoryAuthority {
@@ -98,6 +122,7 @@ impl InMemoryAuthority {
             }
         }
 
+        this.record_history();
         Ok(this)
     }
 
@@ -114,6 +139,16 @@ impl InMemoryAuthority {
             zone_type,
             allow_axfr,
             secure_keys: Vec::new(),
+            tsig_keys: Vec::new(),
+            history: VecDeque::new(),
+            notify_targets: Vec::new(),
+            primaries: Vec::new(),
+            serial_scheme: SerialScheme::default(),
+            minimal_any: false,
+            rotate_rrsets: false,
+            rotations: AtomicUsize::new(0),
+            refreshed: Instant::now(),
+            default_ttl: None,
         }
     }
 
@@ -127,16 +162,120 @@ impl InMemoryAuthority {
         self.class
     }
 
+    /// Sets the DNSClass of the zone, this should be set before any records are added
+    pub fn set_class(&mut self, class: DNSClass) {
+        self.class = class;
+    }
+
     /// Enables AXFRs of all the zones records
     pub fn set_allow_axfr(&mut self, allow_axfr: bool) {
         self.allow_axfr = allow_axfr;
     }
 
+    /// Sets the addresses of the Secondaries which are sent a NOTIFY when the serial of the zone
+    ///  is incremented
+    pub fn set_notify_targets(&mut self, notify_targets: Vec<SocketAddr>) {
+        self.notify_targets = notify_targets;
+    }
+
+    /// Sets the addresses of the Primaries from which the zone, a Secondary, is refreshed when
+    ///  a NOTIFY is received
+    pub fn set_primaries(&mut self, primaries: Vec<SocketAddr>) {
+        self.primaries = primaries;
+    }
+
+    /// How the SOA serial is advanced when the zone is changed
+    pub fn serial_scheme(&self) -> SerialScheme {
+        self.serial_scheme
+    }
+
+    /// Sets how the SOA serial is advanced when the zone is changed, see [`SerialScheme`]
+    pub fn set_serial_scheme(&mut self, serial_scheme: SerialScheme) {
+        self.serial_scheme = serial_scheme;
+    }
+
+    /// If true, ANY queries are answered with only one of the RRsets at the name
+    ///
+    /// [RFC 8482](https://tools.ietf.org/html/rfc8482), Providing Minimal-Sized Responses to DNS Queries That Have QTYPE=ANY, January 2019
+    ///
+    /// ```text
+    /// 4.1.  Select a Subset of RRsets
+    ///
+    ///    A DNS responder can choose to select one or a larger subset of the
+    ///    available RRsets at the QNAME.
+    /// ```
+    pub fn set_minimal_any(&mut self, minimal_any: bool) {
+        self.minimal_any = minimal_any;
+    }
+
+    /// If true, the records of the RRset in an answer are rotated by one more for each response
+    ///
+    /// Clients which only use the first of the addresses are spread across all of them this way,
+    ///  the order of the records within an RRset is not significant,
+    ///  [RFC 2181](https://tools.ietf.org/html/rfc2181#section-5).
+    pub fn set_rotate_rrsets(&mut self, rotate_rrsets: bool) {
+        self.rotate_rrsets = rotate_rrsets;
+    }
+
+    /// The RRset rotated for the next response, see `set_rotate_rrsets`
+    fn rotate(&self, rr_set: Arc<RecordSet>) -> Arc<RecordSet> {
+        if !self.rotate_rrsets || rr_set.records_without_rrsigs().nth(1).is_none() {
+            return rr_set;
+        }
+
+        let mut rotated = RecordSet::clone(&rr_set);
+        rotated.rotate_records(self.rotations.fetch_add(1, Ordering::Relaxed));
+        Arc::new(rotated)
+    }
+
     /// Retrieve the Signer, which contains the private keys, for this zone
     pub fn secure_keys(&self) -> &[Signer] {
         &self.secure_keys
     }
 
+    /// The TSIG keys of which one must sign updates and transfers, none if not required
+    pub fn tsig_keys(&self) -> &[TsigKey] {
+        &self.tsig_keys
+    }
+
+    /// Verifies the TSIG of the request with the TSIG keys of the zone
+    ///
+    /// Returns false if the request is not signed with TSIG, and `NotAuth` if the TSIG does not
+    ///  verify with any of the keys. The TSIG error, e.g. `BADSIG`, is returned to the requestor
+    ///  in the TSIG of the response, see `tsig_responder`.
+    pub fn verify_tsig(&self, request: &MessageRequest) -> UpdateResult<bool> {
+        let responder = match self.tsig_responder(request) {
+            Some(responder) => responder,
+            None => return Ok(false),
+        };
+
+        match responder.error() {
+            None => {
+                debug!("verified tsig of {} with key: {}", request.id(), responder.key_name());
+                Ok(true)
+            }
+            Some(error) => {
+                warn!("tsig of {} not verified: {}", request.id(), error);
+                Err(ResponseCode::NotAuth)
+            }
+        }
+    }
+
+    /// Verifies the TSIG of the request, returning the responder to sign the response with
+    ///
+    /// Returns None if the request is not signed with TSIG.
+    pub fn tsig_responder(&self, request: &MessageRequest) -> Option<TsigResponder> {
+        use chrono::Utc;
+
+        let tsig = request
+            .sig0()
+            .last()
+            .filter(|tsig| tsig.rr_type() == RecordType::DNSSEC(DNSSECRecordType::TSIG))?;
+
+        let now = Utc::now().timestamp() as u64;
+        TsigResponder::verify(&self.tsig_keys, request, tsig, now)
+    }
+
     /// Get all the records
     pub fn records(&self) -> &BTreeMap<RrKey, Arc<RecordSet>> {
         &self.records
@@ -171,6 +310,17 @@ impl InMemoryAuthority {
         soa.minimum()
     }
 
+    /// The TTL of records added without one, the `$TTL` of the zone if set, otherwise the minimum
+    ///  ttl of the SOA record
+    pub fn default_ttl(&self) -> u32 {
+        self.default_ttl.unwrap_or_else(|| self.minimum_ttl())
+    }
+
+    /// Sets the `$TTL` of the zone, `None` falls back to the minimum ttl of the SOA record
+    pub fn set_default_ttl(&mut self, default_ttl: Option<u32>) {
+        self.default_ttl = default_ttl;
+    }
+
     /// get the current serial number for the zone.
     pub fn serial(&self) -> u32 {
         let soa = self.inner_soa();
@@ -186,6 +336,163 @@ impl InMemoryAuthority {
         soa.serial()
     }
 
+    /// Decides whether this zone, a Secondary, is to be transferred from its Primary, given the
+    ///  SOA the Primary responded with, see `Refresh::check`
+    pub fn refresh(&self, primary_soa: &SOA) -> Refresh {
+        Refresh::check(self.inner_soa(), primary_soa)
+    }
+
+    /// Records when the zone, a Secondary, was last refreshed from its Primary
+    ///
+    /// The zone is considered refreshed when it is created, see `is_expired`.
+    pub fn set_refreshed(&mut self, refreshed: Instant) {
+        self.refreshed = refreshed;
+    }
+
+    /// Replaces the records of the zone, a Secondary, with those of an AXFR from its Primary
+    ///
+    /// The records start with the SOA of the zone, the closing SOA of the AXFR is ignored. The
+    ///  zone is left unchanged if any of the records do not belong to it.
+    pub fn transfer(&mut self, records: Vec<Record>) -> UpdateResult<()> {
+        let serial = records
+            .first()
+            .and_then(|record| record.rdata().as_soa())
+            .map(SOA::serial)
+            .ok_or(ResponseCode::FormErr)?;
+
+        let foreign = records.iter().any(|record| {
+            record.dns_class() != self.class || !self.origin.zone_of(&record.name().into())
+        });
+        if foreign {
+            return Err(ResponseCode::NotZone);
+        }
+
+        self.records.clear();
+
+        let mut records = records.into_iter();
+        if let Some(soa) = records.next() {
+            self.upsert(soa, serial);
+        }
+        for record in records.filter(|record| record.rr_type() != RecordType::SOA) {
+            self.upsert(record, serial);
+        }
+
+        self.record_history();
+        Ok(())
+    }
+
+    /// True if the zone is a Secondary which has not been refreshed from its Primary within the
+    ///  EXPIRE interval of its SOA, queries of an expired zone are answered with SERVFAIL
+    ///
+    /// [RFC 1035](https://tools.ietf.org/html/rfc1035), DOMAIN NAMES - IMPLEMENTATION AND SPECIFICATION, November 1987
+    ///
+    /// ```text
+    /// 3.3.13. SOA RDATA format
+    ///
+    /// EXPIRE          A 32 bit time value that specifies the upper limit on
+    ///                 the time interval that can elapse before the zone is no
+    ///                 longer authoritative.
+    /// ```
+    pub fn is_expired(&self) -> bool {
+        #[allow(deprecated)]
+        match self.zone_type {
+            ZoneType::Secondary | ZoneType::Slave => (),
+            _ => return false,
+        }
+
+        self.inner_soa().map_or(false, |soa| {
+            let expire = Duration::from_secs(soa.expire().max(0) as u64);
+            self.refreshed.elapsed() >= expire
+        })
+    }
+
+    /// Returns the zone as text in the master file format
+    ///
+    /// The SOA is written first, followed by all other records grouped by name. RRSIGs are not
+    ///  written, they are generated again when the zone is signed. Reading the text back, e.g.
+    ///  with `FileAuthority::read_from`, results in the same records.
+    ///
+    /// [RFC 1035](https://tools.ietf.org/html/rfc1035), DOMAIN NAMES - IMPLEMENTATION AND SPECIFICATION, November 1987
+    ///
+    /// ```text
+    /// 5.1. Format
+    ///
+    /// <domain-name><rr> [<comment>]
+    /// <blank><rr> [<comment>]
+    ///
+    /// The last two forms represent RRs.  If an entry for an RR begins with a
+    /// blank, then the RR is assumed to be owned by the last stated owner.
+    /// ```
+    pub fn to_zone_file(&self) -> String {
+        let mut zone = String::new();
+        self.write_zone_file(&mut zone).expect("records of a zone are always displayable");
+        zone
+    }
+
+    fn write_zone_file(&self, zone: &mut String) -> fmt::Result {
+        writeln!(zone, "$ORIGIN {}", self.origin)?;
+        if let Some(default_ttl) = self.default_ttl {
+            writeln!(zone, "$TTL {}", default_ttl)?;
+        }
+
+        let soa_key = RrKey::new(self.origin.clone(), RecordType::SOA);
+        let soa = self.records.get(&soa_key);
+        let others = self
+            .records
+            .iter()
+            .filter(|(key, _)| **key != soa_key)
+            .map(|(_, rrset)| rrset);
+
+        let mut last_name: Option<&Name> = None;
+        for rrset in soa.into_iter().chain(others) {
+            for record in rrset.records_without_rrsigs() {
+                if last_name == Some(record.name()) {
+                    zone.push('\t');
+                } else {
+                    write!(zone, "{}\t", record.name())?;
+                    last_name = Some(record.name());
+                }
+
+                write!(
+                    zone,
+                    "{ttl}\t{class}\t{ty}\t",
+                    ttl = record.ttl(),
+                    class = record.dns_class(),
+                    ty = record.rr_type(),
+                )?;
+                Self::write_rdata(zone, record.rdata())?;
+                writeln!(zone)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_rdata(zone: &mut String, rdata: &RData) -> fmt::Result {
+        match rdata {
+            // each character-string is quoted, so that whitespace and ; are preserved
+            RData::TXT(txt) => {
+                for (i, data) in txt.txt_data().iter().enumerate() {
+                    if i > 0 {
+                        zone.push(' ');
+                    }
+
+                    zone.push('"');
+                    for ch in String::from_utf8_lossy(data).chars() {
+                        if ch == '"' || ch == '\\' {
+                            zone.push('\\');
+                        }
+                        zone.push(ch);
+                    }
+                    zone.push('"');
+                }
+
+                Ok(())
+            }
+            rdata => write!(zone, "{}", rdata),
+        }
+    }
+
     fn inner_lookup(
         &self,
         name: &LowerName,
@@ -220,6 +527,55 @@ impl InMemoryAuthority {
         }
     }
 
+    /// Returns true if there are records at the name, or below it, i.e. it is an empty non-terminal
+    fn name_exists(&self, name: &LowerName) -> bool {
+        // names below the name directly follow it in the canonical order of the records
+        let start_range_key = RrKey::new(name.clone(), RecordType::Unknown(u16::min_value()));
+
+        self.records
+            .range(&start_range_key..)
+            .next()
+            .map_or(false, |(key, _)| key.name() == name || name.zone_of(key.name()))
+    }
+
+    /// Returns the wildcard from which records are synthesized for the name, if it does not exist
+    ///
+    /// [RFC 4592](https://tools.ietf.org/html/rfc4592), The Role of Wildcards in the DNS, July 2006
+    ///
+    /// ```text
+    /// 3.3.1.  Locate the Closest Encloser and Source of Synthesis
+    ///
+    ///   The closest encloser is the node in the zone's tree of existing
+    ///   domain names that has the most labels matching the query name
+    ///   (consecutively, counting from the root label downward).  Each match
+    ///   is a "label match" and the order of the labels is the same.
+    ///
+    ///   The closest encloser is, by definition, an existing name in the zone.
+    ///   The closest encloser might be an empty non-terminal or even be a
+    ///   wildcard domain name itself.  In no circumstances is the closest
+    ///   encloser to be used to synthesize responses to the query.
+    ///
+    ///   The source of synthesis is defined in the context of a query process
+    ///   as that wildcard domain name immediately descending from the closest
+    ///   encloser, provided that this wildcard domain name exists.
+    /// ```
+    fn source_of_synthesis(&self, name: &LowerName) -> Option<LowerName> {
+        // if this is a wildcard or a root, both should break continued lookups
+        if name.is_wildcard() || name.is_root() || self.name_exists(name) {
+            return None;
+        }
+
+        let mut closest_encloser = name.base_name();
+        while !closest_encloser.is_root() && !self.name_exists(&closest_encloser) {
+            closest_encloser = closest_encloser.base_name();
+        }
+
+        let wildcard = Name::from(name)
+            .trim_to(closest_encloser.num_labels() as usize + 1)
+            .into_wildcard();
+        Some(LowerName::from(wildcard))
+    }
+
     fn inner_lookup_wildcard(
         &self,
         name: &LowerName,
@@ -227,12 +583,7 @@ impl InMemoryAuthority {
         and_rrsigs: bool,
         supported_algorithms: SupportedAlgorithms,
     ) -> Option<Arc<RecordSet>> {
-        // if this is a wildcard or a root, both should break continued lookups
-        let wildcard = if name.is_wildcard() || name.is_root() {
-            return None;
-        } else {
-            name.clone().into_wildcard()
-        };
+        let wildcard = self.source_of_synthesis(name)?;
 
         self.inner_lookup(&wildcard, record_type, and_rrsigs, supported_algorithms)
             // we need to change the name to the query name in the result set since this was a wildcard
@@ -257,6 +608,182 @@ impl InMemoryAuthority {
             })
     }
 
+    /// Returns the referral to the child zone if the name is at or below a zone cut
+    ///
+    /// [RFC 1034](https://tools.ietf.org/html/rfc1034), Domain Concepts and Facilities, November 1987
+    ///
+    /// ```text
+    /// 4.3.2. Algorithm
+    ///
+    /// b. If a match would take us out of the authoritative data,
+    ///    we have a referral.  This happens when we encounter a
+    ///    node with NS RRs marking cuts along the bottom of a
+    ///    zone.
+    ///
+    ///    Copy the NS RRs for the subzone into the authority
+    ///    section of the reply.  Put whatever addresses are
+    ///    available into the additional section, using glue RRs
+    ///    if the addresses are not available from authoritative
+    ///    data or the cache.  Go to step 4.
+    /// ```
+    ///
+    /// Glue is only included for the name servers within this zone, the addresses of those out of
+    ///  bailiwick are not ours to give.
+    fn referral(
+        &self,
+        name: &LowerName,
+        record_type: RecordType,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Option<AuthLookup> {
+        // the DS records of the child zone are in the parent, at the cut
+        let name = Name::from(name);
+        let num_labels = match record_type {
+            RecordType::DNSSEC(DNSSECRecordType::DS) => name.num_labels().saturating_sub(1),
+            _ => name.num_labels(),
+        };
+
+        // the topmost cut is the delegation, anything below it is the child zone's
+        let ns = (self.origin.num_labels() + 1..=num_labels)
+            .map(|labels| LowerName::from(name.trim_to(labels as usize)))
+            .find_map(|cut| self.records.get(&RrKey::new(cut, RecordType::NS)))?;
+
+        let mut glue: Vec<Arc<RecordSet>> = vec![];
+        for record in ns.records_without_rrsigs() {
+            let ns_name = match record.rdata() {
+                RData::NS(ns_name) => LowerName::from(ns_name),
+                _ => continue,
+            };
+
+            if !self.origin.zone_of(&ns_name) {
+                continue;
+            }
+
+            for glue_type in &[RecordType::A, RecordType::AAAA] {
+                let key = RrKey::new(ns_name.clone(), *glue_type);
+                if let Some(addresses) = self.records.get(&key) {
+                    glue.push(addresses.clone());
+                }
+            }
+        }
+
+        let ns = LookupRecords::new(is_secure, supported_algorithms, ns.clone());
+        let glue = if !glue.is_empty() {
+            Some(LookupRecords::many(is_secure, supported_algorithms, glue))
+        } else {
+            None
+        };
+
+        Some(AuthLookup::referral(ns, glue))
+    }
+
+    /// Returns the DNAME and the CNAME synthesized from it, if the name is below a DNAME owner
+    ///
+    /// [RFC 6672](https://tools.ietf.org/html/rfc6672), DNAME Redirection in the DNS, June 2012
+    ///
+    /// ```text
+    /// 3.2.  Server Algorithm
+    ///
+    ///            If at some label, a match is impossible (i.e., the
+    ///            corresponding label does not exist), look to see whether
+    ///            the last label matched has a DNAME record.
+    ///
+    ///            If a DNAME record exists at that point, copy that record
+    ///            into the answer section.  If substitution of its <target>
+    ///            for its <owner> in QNAME would overflow the legal size for
+    ///            a <domain-name>, set RCODE to YXDOMAIN [RFC2136] and exit;
+    ///            otherwise, perform the substitution and continue.  The
+    ///            server MUST synthesize a CNAME record as described in
+    ///            Section 2.3 and copy it into the answer section.
+    /// ```
+    fn dname_redirect(
+        &self,
+        name: &LowerName,
+        query_type: RecordType,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Option<Result<AuthLookup, LookupError>> {
+        // the DNAME does not apply to its owner, only to the names below it
+        let name = Name::from(name);
+        let dname = (self.origin.num_labels()..name.num_labels())
+            .map(|labels| LowerName::from(name.trim_to(labels as usize)))
+            .find_map(|owner| self.records.get(&RrKey::new(owner, RecordType::DNAME)))?;
+        let target = dname
+            .records_without_rrsigs()
+            .next()
+            .and_then(|record| record.rdata().as_dname())?;
+
+        // the labels of the name below the owner are kept, the owner is replaced by the target
+        let prefix = name.num_labels() - dname.name().num_labels();
+        let cname_target = Name::from_labels(name.iter().take(prefix as usize))
+            .ok()?
+            .append_domain(target);
+
+        // 255 octets on the wire, including the root label
+        if cname_target.len() > 254 {
+            return Some(Err(LookupError::from(ResponseCode::YXDomain)));
+        }
+
+        // the synthesized CNAME is not signed, it is validated against the signed DNAME
+        let mut cname = RecordSet::with_ttl(name, RecordType::CNAME, dname.ttl());
+        cname.add_rdata(RData::CNAME(cname_target));
+
+        let additionals = maybe_next_name(&cname, query_type).and_then(|(next, search_type)| {
+            self.additional_search(
+                query_type,
+                next,
+                search_type,
+                is_secure,
+                supported_algorithms,
+            )
+        });
+
+        let answers = vec![dname.clone(), Arc::new(cname)];
+        let answers = LookupRecords::many(is_secure, supported_algorithms, answers);
+        let additionals =
+            additionals.map(|a| LookupRecords::many(is_secure, supported_algorithms, a));
+
+        Some(Ok(AuthLookup::answers(answers, additionals)))
+    }
+
+    /// Returns true if the record may not be added to the zone alongside a DNAME
+    ///
+    /// There is no data below the owner of a DNAME, all of those names are redirected by it, and
+    ///  other than at the apex there can be no delegation at the owner.
+    fn dname_conflicts(&self, record: &Record) -> bool {
+        let name = LowerName::from(record.name());
+        let below_dname = (self.origin.num_labels()..name.num_labels()).any(|labels| {
+            let owner = LowerName::from(record.name().trim_to(labels as usize));
+            self.records.contains_key(&RrKey::new(owner, RecordType::DNAME))
+        });
+
+        let is_apex = name == self.origin;
+        match record.rr_type() {
+            RecordType::DNAME => {
+                // names below the owner directly follow it in the canonical order of the records
+                let after_owner = RrKey::new(name.clone(), RecordType::Unknown(u16::max_value()));
+                let data_below = self
+                    .records
+                    .range(&after_owner..)
+                    .next()
+                    .map_or(false, |(key, _)| key.name() != &name && name.zone_of(key.name()));
+                let is_cut = self
+                    .records
+                    .contains_key(&RrKey::new(name.clone(), RecordType::NS));
+
+                below_dname || data_below || (is_cut && !is_apex)
+            }
+            RecordType::NS => {
+                let has_dname = self
+                    .records
+                    .contains_key(&RrKey::new(name.clone(), RecordType::DNAME));
+
+                below_dname || (has_dname && !is_apex)
+            }
+            _ => below_dname,
+        }
+    }
+
     /// Search for additional records to include in the response
     ///
     /// # Arguments
@@ -314,6 +841,8 @@ impl InMemoryAuthority {
     }
 
     #[cfg(any(feature = "dnssec", feature = "sqlite"))]
+    /// Increments the serial of the SOA, the new version of the zone is not retained for IXFR
+    ///  until `record_history` is called, once all changes, e.g. signatures, are complete
     pub(crate) fn increment_soa_serial(&mut self) -> u32 {
         // we'll remove the SOA and then replace it
         let rr_key = RrKey::new(self.origin.clone(), RecordType::SOA);
@@ -331,7 +860,7 @@ impl InMemoryAuthority {
         };
 
         let serial = if let RData::SOA(ref mut soa_rdata) = *record.rdata_mut() {
-            soa_rdata.increment_serial();
+            soa_rdata.set_serial(self.serial_scheme.next(soa_rdata.serial()));
             soa_rdata.serial()
         } else {
             panic!("This was not an SOA record"); // valid panic, never should happen
@@ -341,9 +870,30 @@ impl InMemoryAuthority {
         serial
     }
 
+    /// Retains the current version of the zone, incremental zone transfers from its serial will
+    ///  be computed against it
+    pub(crate) fn record_history(&mut self) {
+        let serial = self.serial();
+        if self.history.len() >= MAX_HISTORY {
+            self.history.pop_front();
+        }
+
+        self.history.push_back((serial, self.records.clone()));
+    }
+
+    fn soa_record(
+        records: &BTreeMap<RrKey, Arc<RecordSet>>,
+        origin: &LowerName,
+    ) -> Option<Record> {
+        records
+            .get(&RrKey::new(origin.clone(), RecordType::SOA))
+            .and_then(|rrset| rrset.records_without_rrsigs().next().cloned())
+    }
+
     /// Inserts or updates a `Record` depending on it's existence in the authority.
     ///
     /// Guarantees that SOA, CNAME only has one record, will implicitly update if they already exist.
+    ///  Records below the owner of a DNAME are rejected, as is a DNAME over existing records.
     ///
     /// # Arguments
     ///
@@ -402,7 +952,7 @@ impl InMemoryAuthority {
                     )
             });
 
-        if multiple_records_at_label_disallowed {
+        if multiple_records_at_label_disallowed || self.dname_conflicts(&record) {
             // consider making this an error?
             return false;
         }
@@ -423,6 +973,15 @@ impl InMemoryAuthority {
         }
     }
 
+    /// Inserts or updates the `RData` at `name` with the default TTL of the zone, see `upsert`
+    ///  and `default_ttl`
+    pub fn upsert_rdata(&mut self, name: Name, rdata: RData, serial: u32) -> bool {
+        let mut record = Record::from_rdata(name, self.default_ttl(), rdata);
+        record.set_dns_class(self.class);
+
+        self.upsert(record, serial)
+    }
+
     /// (Re)generates the nsec records, increments the serial number and signs the zone
     #[cfg(feature = "dnssec")]
     pub fn secure_zone(&mut self) -> DnsSecResult<()> {
@@ -435,7 +994,11 @@ impl InMemoryAuthority {
         self.increment_soa_serial();
 
         // TODO: should we auto sign here? or maybe up a level...
-        self.sign_zone()
+        self.sign_zone()?;
+
+        // retained only once re-signed, so that IXFR deltas carry the new RRSIGs and NSECs
+        self.record_history();
+        Ok(())
     }
 
     /// (Re)generates the nsec records, increments the serial number and signs the zone
@@ -700,6 +1263,42 @@ fn maybe_next_name(
     }
 }
 
+/// Returns all records, other than the SOA, in `records` which are not in `other`
+fn records_not_in(
+    records: &BTreeMap<RrKey, Arc<RecordSet>>,
+    other: &BTreeMap<RrKey, Arc<RecordSet>>,
+) -> Vec<Record> {
+    let mut not_in = Vec::new();
+
+    for (key, rrset) in records {
+        if key.record_type == RecordType::SOA {
+            continue;
+        }
+
+        let other = other.get(key);
+
+        // unchanged RecordSets are shared between versions of the zone
+        if other.map_or(false, |other| Arc::ptr_eq(rrset, other)) {
+            continue;
+        }
+
+        for record in rrset.records_without_rrsigs().chain(rrset.rrsigs()) {
+            let found = other.map_or(false, |other| {
+                other
+                    .records_without_rrsigs()
+                    .chain(other.rrsigs())
+                    .any(|r| r == record)
+            });
+
+            if !found {
+                not_in.push(record.clone());
+            }
+        }
+    }
+
+    not_in
+}
+
 impl Authority for InMemoryAuthority {
     type Lookup = AuthLookup;
     type LookupFuture = future::Ready<Result<Self::Lookup, LookupError>>;
@@ -780,13 +1379,97 @@ impl Authority for InMemoryAuthority {
         &self.origin
     }
 
+    /// The DNSClass of the records in this zone
+    fn class(&self) -> DNSClass {
+        self.class
+    }
+
+    /// Returns the changes to the zone since the specified SOA serial
+    ///
+    /// The changes are condensed into a single difference sequence, as allowed by RFC 1995. Only
+    ///  the most recent versions of the zone are retained, `Ixfr::Full` is returned for older serials.
+    fn ixfr(&self, serial: u32) -> Ixfr {
+        let new_soa = match Self::soa_record(&self.records, &self.origin) {
+            Some(soa) => soa,
+            None => return Ixfr::Full,
+        };
+
+        // the requester is up to date
+        if Serial::from(serial) >= Serial::from(self.serial()) {
+            return Ixfr::Incremental(ZoneDelta::new(new_soa.clone(), new_soa, vec![], vec![]));
+        }
+
+        let old_records = match self.history.iter().find(|(s, _)| *s == serial) {
+            Some((_, records)) => records,
+            None => return Ixfr::Full,
+        };
+        let old_soa = match Self::soa_record(old_records, &self.origin) {
+            Some(soa) => soa,
+            None => return Ixfr::Full,
+        };
+
+        let deleted = records_not_in(old_records, &self.records);
+        let added = records_not_in(&self.records, old_records);
+
+        Ixfr::Incremental(ZoneDelta::new(old_soa, new_soa, deleted, added))
+    }
+
+    /// The addresses of Secondaries which are sent a NOTIFY when the serial of the zone changes
+    fn notify_targets(&self) -> &[SocketAddr] {
+        &self.notify_targets
+    }
+
+    /// The addresses of the Primaries from which this zone, a Secondary, is refreshed
+    fn primaries(&self) -> &[SocketAddr] {
+        &self.primaries
+    }
+
+    /// Decides whether this zone is to be transferred, given the SOA of its Primary
+    fn refresh(&self, primary_soa: &SOA) -> Refresh {
+        InMemoryAuthority::refresh(self, primary_soa)
+    }
+
+    /// Replaces the records of this zone with those of an AXFR from its Primary
+    fn transfer(&mut self, records: Vec<Record>) -> UpdateResult<()> {
+        InMemoryAuthority::transfer(self, records)
+    }
+
+    /// Records when this zone was last refreshed from its Primary
+    fn set_refreshed(&mut self, refreshed: Instant) {
+        InMemoryAuthority::set_refreshed(self, refreshed)
+    }
+
+    /// Returns all records of the zone for a full zone transfer
+    ///
+    /// The transfer is refused unless AXFR is allowed and this is a Primary or Secondary zone.
+    fn axfr(&self) -> Result<Axfr, LookupError> {
+        if !self.is_axfr_allowed() || !self.zone_type().is_authoritative() {
+            return Err(LookupError::from(ResponseCode::Refused));
+        }
+
+        // without an SOA the zone has not been loaded, there is nothing to transfer
+        let soa = Self::soa_record(&self.records, &self.origin)
+            .ok_or_else(|| LookupError::from(ResponseCode::ServFail))?;
+
+        let mut records = Vec::new();
+        for (key, rrset) in &self.records {
+            // the SOA frames the transfer, its RRSIGs are transferred with the other records
+            if key.record_type != RecordType::SOA {
+                records.extend(rrset.records_without_rrsigs().cloned());
+            }
+            records.extend(rrset.rrsigs().iter().cloned());
+        }
+
+        Ok(Axfr::new(soa, records))
+    }
+
     /// Looks up all Resource Records matching the giving `Name` and `RecordType`.
     ///
     /// # Arguments
     ///
     /// * `name` - The `Name`, label, to lookup.
     /// * `rtype` - The `RecordType`, to lookup. `RecordType::ANY` will return all records matching
-    ///             `name`. `RecordType::AXFR` will return all record types except `RecordType::SOA`
+    ///             `name`, or only one RRset of them, see `set_minimal_any`. `RecordType::AXFR` will return all record types except `RecordType::SOA`
     ///             due to the requirements that on zone transfers the `RecordType::SOA` must both
     ///             precede and follow all other records.
     /// * `is_secure` - If the DO bit is set on the EDNS OPT record, then return RRSIGs as well.
@@ -804,7 +1487,33 @@ impl Authority for InMemoryAuthority {
         // Collect the records from each rr_set
         let (result, additionals): (LookupResult<LookupRecords>, Option<LookupRecords>) =
             match query_type {
-                RecordType::AXFR | RecordType::ANY => {
+                RecordType::ANY => {
+                    // all the RecordTypes at the name
+                    let start_range_key =
+                        RrKey::new(name.clone(), RecordType::Unknown(u16::min_value()));
+                    let end_range_key =
+                        RrKey::new(name.clone(), RecordType::Unknown(u16::max_value()));
+
+                    let mut rrsets: Vec<Arc<RecordSet>> = self
+                        .records
+                        .range(&start_range_key..&end_range_key)
+                        .map(|(_key, rrset)| rrset.clone())
+                        .collect();
+
+                    if self.minimal_any {
+                        rrsets.truncate(1);
+                    }
+
+                    let result = AnyRecords::new(
+                        is_secure,
+                        supported_algorithms,
+                        rrsets,
+                        query_type,
+                        name.clone(),
+                    );
+                    (Ok(LookupRecords::AnyRecords(result)), None)
+                }
+                RecordType::AXFR => {
                     let result = AnyRecords::new(
                         is_secure,
                         supported_algorithms,
@@ -918,6 +1627,7 @@ impl Authority for InMemoryAuthority {
                     // map the answer to a result
                     let answer = answer
                         .map_or(Err(LookupError::from(ResponseCode::NXDomain)), |rr_set| {
+                            let rr_set = self.rotate(rr_set);
                             Ok(LookupRecords::new(is_secure, supported_algorithms, rr_set))
                         });
 
@@ -935,11 +1645,11 @@ impl Authority for InMemoryAuthority {
         // TODO: can we get rid of this?
         let result = match result {
             Err(LookupError::ResponseCode(ResponseCode::NXDomain)) => {
-                if self
-                    .records
-                    .keys()
-                    .any(|key| key.name() == name || name.zone_of(key.name()))
-                {
+                let wildcard_exists = self
+                    .source_of_synthesis(name)
+                    .map_or(false, |wildcard| self.name_exists(&wildcard));
+
+                if self.name_exists(name) || wildcard_exists {
                     return Box::pin(future::err(LookupError::NameExists));
                 } else {
                     let code = if self.origin().zone_of(name) {
@@ -967,6 +1677,11 @@ impl Authority for InMemoryAuthority {
     ) -> Pin<Box<dyn Future<Output = Result<Self::Lookup, LookupError>> + Send>> {
         debug!("searching InMemoryAuthority for: {}", query);
 
+        if self.is_expired() {
+            warn!("secondary zone {} has expired", self.origin);
+            return Box::pin(future::err(LookupError::from(ResponseCode::ServFail)));
+        }
+
         let lookup_name = query.name();
         let record_type: RecordType = query.query_type();
 
@@ -1011,10 +1726,58 @@ impl Authority for InMemoryAuthority {
                 Box::pin(lookup)
             }
             // A standard Lookup path
-            _ => Box::pin(self.lookup(lookup_name, record_type, is_secure, supported_algorithms)),
+            _ => {
+                if let Some(referral) =
+                    self.referral(lookup_name, record_type, is_secure, supported_algorithms)
+                {
+                    debug!("referral for {} to a child zone", query);
+                    return Box::pin(future::ok(referral));
+                }
+
+                if let Some(redirect) =
+                    self.dname_redirect(lookup_name, record_type, is_secure, supported_algorithms)
+                {
+                    debug!("DNAME redirect for {}", query);
+                    return Box::pin(future::ready(redirect));
+                }
+
+                Box::pin(self.lookup(lookup_name, record_type, is_secure, supported_algorithms))
+            }
         }
     }
 
+    /// Returns the SOA record for the authority section of a negative response
+    ///
+    /// [RFC 2308](https://tools.ietf.org/html/rfc2308#section-3), Negative Caching of DNS Queries, March 1998
+    ///
+    /// ```text
+    /// The TTL of this record is set from the minimum of the MINIMUM field of the SOA record and
+    /// the TTL of the SOA itself, and indicates how long a resolver may cache the negative answer.
+    /// ```
+    fn negative_soa(
+        &self,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Lookup, LookupError>> + Send>> {
+        let minimum_ttl = self.minimum_ttl();
+        let soa = match self.records.get(&RrKey::new(self.origin.clone(), RecordType::SOA)) {
+            Some(soa) if soa.ttl() > minimum_ttl => soa,
+            _ => return self.soa_secure(is_secure, supported_algorithms),
+        };
+
+        let mut negative_soa = RecordSet::clone(soa);
+        negative_soa.set_ttl(minimum_ttl);
+        let rrsigs = negative_soa.rrsigs().to_vec();
+        negative_soa.clear_rrsigs();
+        for mut rrsig in rrsigs {
+            rrsig.set_ttl(minimum_ttl);
+            negative_soa.insert_rrsig(rrsig);
+        }
+
+        let records = LookupRecords::new(is_secure, supported_algorithms, Arc::new(negative_soa));
+        Box::pin(future::ok(AuthLookup::answers(records, None)))
+    }
+
     /// Return the NSEC records based on the given name
     ///
     /// # Arguments
@@ -1136,6 +1899,24 @@ impl Authority for InMemoryAuthority {
         Err("DNSSEC was not enabled during compilation.".into())
     }
 
+    fn add_tsig_key(&mut self, key: TsigKey) -> DnsSecResult<()> {
+        self.tsig_keys.push(key);
+        Ok(())
+    }
+
+    fn tsig_responder(&self, request: &MessageRequest) -> Option<TsigResponder> {
+        InMemoryAuthority::tsig_responder(self, request)
+    }
+
+    fn authorize_axfr(&self, request: &MessageRequest) -> UpdateResult<()> {
+        if self.verify_tsig(request)? || self.tsig_keys.is_empty() {
+            Ok(())
+        } else {
+            warn!("transfer of {} without tsig refused", self.origin);
+            Err(ResponseCode::NotAuth)
+        }
+    }
+
     /// By adding a secure key, this will implicitly enable dnssec for the zone.
     ///
     /// # Arguments
@@ -1177,7 +1958,11 @@ impl Authority for InMemoryAuthority {
         self.increment_soa_serial();
 
         // TODO: should we auto sign here? or maybe up a level...
-        self.sign_zone()
+        self.sign_zone()?;
+
+        // retained only once re-signed, so that IXFR deltas carry the new RRSIGs and NSECs
+        self.record_history();
+        Ok(())
     }
 
     /// (Re)generates the nsec records, increments the serial number and signs the zone
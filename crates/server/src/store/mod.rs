@@ -7,6 +7,7 @@
 
 //! All persistent store implementations
 
+pub mod chaos;
 mod config;
 pub mod file;
 pub mod forwarder;
@@ -7,7 +7,9 @@
 
 //! All authority related types
 
+use std::collections::HashMap;
 use std::future::Future;
+use std::net::SocketAddr;
 use std::ops::{Deref, DerefMut};
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
@@ -16,7 +18,9 @@ use std::sync::Arc;
 use log::{error, info, warn};
 
 use crate::client::op::LowerQuery;
-use crate::client::rr::dnssec::{DnsSecResult, Signer, SupportedAlgorithms};
+use crate::client::rr::dnssec::{
+    DnsSecResult, Signer, SupportedAlgorithms, TsigKey, TsigResponder,
+};
 use crate::client::rr::{LowerName, RrKey};
 use crate::proto::op::ResponseCode;
 use crate::proto::rr::dnssec::rdata::key::KEY;
@@ -24,7 +28,7 @@ use crate::proto::rr::{DNSClass, Name, RData, Record, RecordSet, RecordType};
 
 #[cfg(feature = "dnssec")]
 use crate::authority::UpdateRequest;
-use crate::authority::{Authority, LookupError, MessageRequest, UpdateResult, ZoneType};
+use crate::authority::{Authority, Axfr, Ixfr, LookupError, MessageRequest, UpdateResult, ZoneType};
 use crate::error::{PersistenceErrorKind, PersistenceResult};
 use crate::store::in_memory::InMemoryAuthority;
 use crate::store::sqlite::{Journal, SqliteConfig};
@@ -161,6 +165,8 @@ impl SqliteAuthority {
             }
         }
 
+        // incremental zone transfers are computed from the recovered zone
+        self.in_memory.record_history();
         Ok(())
     }
 
@@ -255,8 +261,6 @@ impl SqliteAuthority {
     ///   zone     rrset    rr       RRset exists (value dependent)
     /// ```
     pub fn verify_prerequisites(&self, pre_requisites: &[Record]) -> UpdateResult<()> {
-        use futures_executor::block_on;
-
         //   3.2.5 - Pseudocode for Prerequisite Section Processing
         //
         //      for rr in prerequisites
@@ -290,6 +294,7 @@ impl SqliteAuthority {
         //      for rrset in temp
         //           if (zone_rrset<rrset.name, rrset.type> != rrset)
         //                return (NXRRSET)
+        let mut required_rrsets: HashMap<RrKey, Vec<&Record>> = HashMap::new();
         for require in pre_requisites {
             let required_name = LowerName::from(require.name());
 
@@ -309,16 +314,7 @@ impl SqliteAuthority {
                         match require.rr_type() {
                             // ANY      ANY      empty    Name is in use
                             RecordType::ANY => {
-                                /*TODO: this works because the future here is always complete*/
-                                if block_on(self.lookup(
-                                    &required_name,
-                                    RecordType::ANY,
-                                    false,
-                                    SupportedAlgorithms::new(),
-                                ))
-                                .unwrap_or_default()
-                                .was_empty()
-                                {
+                                if !self.name_in_use(&required_name) {
                                     return Err(ResponseCode::NXDomain);
                                 } else {
                                     continue;
@@ -326,16 +322,7 @@ impl SqliteAuthority {
                             }
                             // ANY      rrset    empty    RRset exists (value independent)
                             rrset => {
-                                /*TODO: this works because the future here is always complete*/
-                                if block_on(self.lookup(
-                                    &required_name,
-                                    rrset,
-                                    false,
-                                    SupportedAlgorithms::new(),
-                                ))
-                                .unwrap_or_default()
-                                .was_empty()
-                                {
+                                if self.zone_rrset(&required_name, rrset).is_none() {
                                     return Err(ResponseCode::NXRRSet);
                                 } else {
                                     continue;
@@ -351,16 +338,7 @@ impl SqliteAuthority {
                         match require.rr_type() {
                             // NONE     ANY      empty    Name is not in use
                             RecordType::ANY => {
-                                /*TODO: this works because the future here is always complete*/
-                                if !block_on(self.lookup(
-                                    &required_name,
-                                    RecordType::ANY,
-                                    false,
-                                    SupportedAlgorithms::new(),
-                                ))
-                                .unwrap_or_default()
-                                .was_empty()
-                                {
+                                if self.name_in_use(&required_name) {
                                     return Err(ResponseCode::YXDomain);
                                 } else {
                                     continue;
@@ -368,16 +346,7 @@ impl SqliteAuthority {
                             }
                             // NONE     rrset    empty    RRset does not exist
                             rrset => {
-                                /*TODO: this works because the future here is always complete*/
-                                if !block_on(self.lookup(
-                                    &required_name,
-                                    rrset,
-                                    false,
-                                    SupportedAlgorithms::new(),
-                                ))
-                                .unwrap_or_default()
-                                .was_empty()
-                                {
+                                if self.zone_rrset(&required_name, rrset).is_some() {
                                     return Err(ResponseCode::YXRRSet);
                                 } else {
                                     continue;
@@ -391,31 +360,56 @@ impl SqliteAuthority {
                 class if class == self.class() =>
                 // zone     rrset    rr       RRset exists (value dependent)
                 {
-                    /*TODO: this works because the future here is always complete*/
-                    if block_on(self.lookup(
-                        &required_name,
-                        require.rr_type(),
-                        false,
-                        SupportedAlgorithms::new(),
-                    ))
-                    .unwrap_or_default()
-                    .iter()
-                    .find(|rr| *rr == require)
-                    .is_none()
-                    {
-                        return Err(ResponseCode::NXRRSet);
-                    } else {
-                        continue;
+                    // the whole RRset is compared once all of its records are collected
+                    let required = required_rrsets
+                        .entry(RrKey::new(required_name, require.rr_type()))
+                        .or_insert_with(Vec::new);
+                    if !required.contains(&require) {
+                        required.push(require);
                     }
                 }
                 _ => return Err(ResponseCode::FormErr),
             }
         }
 
+        // a value dependent RRset must be exactly the RRset in the zone, not just a subset of it
+        for (key, required) in required_rrsets {
+            let existing: Vec<&Record> = self
+                .zone_rrset(&key.name, key.record_type)
+                .map(|rrset| rrset.records_without_rrsigs().collect())
+                .unwrap_or_default();
+
+            if existing.len() != required.len()
+                || !existing.iter().all(|rr| required.contains(rr))
+            {
+                return Err(ResponseCode::NXRRSet);
+            }
+        }
+
         // if we didn't bail everything checked out...
         Ok(())
     }
 
+    /// True if there are records at the name, the prerequisites are evaluated against the records
+    ///  of the zone as they are, without following CNAMEs or synthesizing from wildcards
+    fn name_in_use(&self, name: &LowerName) -> bool {
+        // this range covers all the records for any of the RecordTypes at a given label.
+        let start_range_key = RrKey::new(name.clone(), RecordType::Unknown(u16::min_value()));
+        let end_range_key = RrKey::new(name.clone(), RecordType::Unknown(u16::max_value()));
+
+        self.records()
+            .range(&start_range_key..&end_range_key)
+            .any(|(_, rrset)| !rrset.is_empty())
+    }
+
+    /// The RRset of the type at the name in the zone, None if there are no such records
+    fn zone_rrset(&self, name: &LowerName, record_type: RecordType) -> Option<&RecordSet> {
+        self.records()
+            .get(&RrKey::new(name.clone(), record_type))
+            .map(|rrset| &**rrset)
+            .filter(|rrset| !rrset.is_empty())
+    }
+
     /// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
     ///
     /// ```text
@@ -466,7 +460,15 @@ impl SqliteAuthority {
             return Err(ResponseCode::Refused);
         }
 
-        // verify sig0, currently the only authorization that is accepted.
+        // a valid TSIG authorizes the update, and once TSIG keys are added is required.
+        if self.in_memory.verify_tsig(update_message)? {
+            return Ok(());
+        } else if !self.in_memory.tsig_keys().is_empty() {
+            warn!("update without tsig refused: id {}", update_message.id());
+            return Err(ResponseCode::NotAuth);
+        }
+
+        // verify sig0
         let sig0s: &[Record] = update_message.sig0();
         debug!("authorizing with: {:?}", sig0s);
         if !sig0s.is_empty()
@@ -809,6 +811,7 @@ impl SqliteAuthority {
                 // the secure_zone() function increments the SOA during it's operation, if we're not
                 //  dnssec, then we need to do it here...
                 self.increment_soa_serial();
+                self.record_history();
             }
         }
 
@@ -922,6 +925,26 @@ impl Authority for SqliteAuthority {
         self.in_memory.origin()
     }
 
+    /// The DNSClass of the records in this zone
+    fn class(&self) -> DNSClass {
+        self.in_memory.class()
+    }
+
+    /// Returns the changes to the zone since the specified SOA serial
+    fn ixfr(&self, serial: u32) -> Ixfr {
+        self.in_memory.ixfr(serial)
+    }
+
+    /// Returns all records of the zone for a full zone transfer
+    fn axfr(&self) -> Result<Axfr, LookupError> {
+        self.in_memory.axfr()
+    }
+
+    /// The addresses of Secondaries which are sent a NOTIFY when the serial of the zone changes
+    fn notify_targets(&self) -> &[SocketAddr] {
+        self.in_memory.notify_targets()
+    }
+
     /// Looks up all Resource Records matching the giving `Name` and `RecordType`.
     ///
     /// # Arguments
@@ -974,10 +997,30 @@ impl Authority for SqliteAuthority {
             .get_nsec_records(name, is_secure, supported_algorithms)
     }
 
+    fn negative_soa(
+        &self,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Lookup, LookupError>> + Send>> {
+        self.in_memory.negative_soa(is_secure, supported_algorithms)
+    }
+
     fn add_update_auth_key(&mut self, name: Name, key: KEY) -> DnsSecResult<()> {
         self.in_memory.add_update_auth_key(name, key)
     }
 
+    fn add_tsig_key(&mut self, key: TsigKey) -> DnsSecResult<()> {
+        self.in_memory.add_tsig_key(key)
+    }
+
+    fn authorize_axfr(&self, request: &MessageRequest) -> UpdateResult<()> {
+        self.in_memory.authorize_axfr(request)
+    }
+
+    fn tsig_responder(&self, request: &MessageRequest) -> Option<TsigResponder> {
+        self.in_memory.tsig_responder(request)
+    }
+
     /// By adding a secure key, this will implicitly enable dnssec for the zone.
     ///
     /// # Arguments
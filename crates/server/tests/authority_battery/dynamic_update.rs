@@ -10,6 +10,8 @@ use trust_dns_client::op::update_message;
 use trust_dns_client::op::{Message, Query, ResponseCode};
 use trust_dns_client::proto::rr::{DNSClass, Name, RData, Record, RecordSet, RecordType};
 use trust_dns_client::rr::dnssec::{Algorithm, Signer, SupportedAlgorithms, Verifier};
+#[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+use trust_dns_client::rr::dnssec::{TsigAlgorithm, TsigKey};
 use trust_dns_client::serialize::binary::{BinDecodable, BinEncodable, BinSerializable};
 use trust_dns_server::authority::{
     AuthLookup, Authority, LookupError, MessageRequest, UpdateResult,
@@ -601,6 +603,85 @@ pub fn test_delete_all<A: Authority<Lookup = AuthLookup>>(mut authority: A, keys
     }
 }
 
+#[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+fn tsig_update_request(mut message: Message, key: &TsigKey) -> Vec<u8> {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("time before epoch")
+        .as_secs();
+    message
+        .finalize(key, now as u32)
+        .expect("failed to sign message");
+    message.to_bytes().unwrap()
+}
+
+#[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+fn add_tsig_key<A: Authority<Lookup = AuthLookup>>(authority: &mut A) -> TsigKey {
+    let key = TsigKey::new(
+        Name::from_str("update-key.example.com.").unwrap(),
+        TsigAlgorithm::HmacSha256,
+        b"the shared secret".to_vec(),
+    );
+    authority
+        .add_tsig_key(key.clone())
+        .expect("failed to add tsig key to zone");
+    key
+}
+
+#[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+pub fn test_tsig_update<A: Authority<Lookup = AuthLookup>>(mut authority: A, _keys: &[Signer]) {
+    let key = add_tsig_key(&mut authority);
+
+    let name = Name::from_str("tsig.example.com.").unwrap();
+    let record = Record::from_rdata(name.clone(), 8, RData::A(Ipv4Addr::new(127, 0, 0, 10)));
+    let message = update_message::create(record.into(), Name::from_str("example.com.").unwrap());
+
+    let request = tsig_update_request(message, &key);
+    let request = MessageRequest::from_bytes(&request).unwrap();
+    assert!(authority.update(&request).expect("signed update failed"));
+
+    let query = Query::query(name, RecordType::A);
+    let lookup =
+        block_on(authority.search(&query.into(), false, SupportedAlgorithms::new())).unwrap();
+    assert_eq!(
+        *lookup.into_iter().next().expect("A record not found").rdata(),
+        RData::A(Ipv4Addr::new(127, 0, 0, 10))
+    );
+}
+
+#[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+pub fn test_tsig_tampered<A: Authority<Lookup = AuthLookup>>(mut authority: A, _keys: &[Signer]) {
+    let key = add_tsig_key(&mut authority);
+
+    let name = Name::from_str("tsig.example.com.").unwrap();
+    let record = Record::from_rdata(name.clone(), 8, RData::A(Ipv4Addr::new(127, 0, 0, 10)));
+    let message = update_message::create(record.into(), Name::from_str("example.com.").unwrap());
+
+    // unsigned updates are refused once a tsig key is required
+    let unsigned = MessageRequest::from_bytes(&message.to_bytes().unwrap()).unwrap();
+    assert_eq!(authority.update(&unsigned), Err(ResponseCode::NotAuth));
+
+    // change the address being created after signing
+    let mut request = tsig_update_request(message, &key);
+    let address = request
+        .windows(4)
+        .position(|window| window == [127, 0, 0, 10])
+        .expect("address not found in request");
+    request[address + 3] = 11;
+
+    let request = MessageRequest::from_bytes(&request).unwrap();
+    assert_eq!(authority.update(&request), Err(ResponseCode::NotAuth));
+
+    let query = Query::query(name, RecordType::A);
+    let lookup = block_on(authority.search(&query.into(), false, SupportedAlgorithms::new()));
+    assert_eq!(
+        *lookup.unwrap_err().as_response_code().unwrap(),
+        ResponseCode::NXDomain
+    );
+}
+
 pub fn add_auth<A: Authority<Lookup = AuthLookup>>(authority: &mut A) -> Vec<Signer> {
     use trust_dns_client::rr::rdata::key::KeyUsage;
     use trust_dns_server::config::dnssec::*;
@@ -732,6 +813,12 @@ macro_rules! dynamic_update {
                     test_delete_rrset,
                     test_delete_all,
                 );
+
+                #[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+                define_update_test!($new;
+                    test_tsig_update,
+                    test_tsig_tampered,
+                );
             }
         }
     };
@@ -18,8 +18,9 @@ use std::net::{Ipv4Addr, Ipv6Addr};
 use std::path::{Path, PathBuf};
 use std::time::Duration;
 
-use trust_dns_server::authority::ZoneType;
+use trust_dns_server::authority::{AclOperation, RateLimitConfig, ZoneType};
 use trust_dns_server::config::*;
+use trust_dns_server::store::chaos::ChaosConfig;
 
 #[test]
 fn test_read_config() {
@@ -249,6 +250,86 @@ tls_listen_port = 8853
     );
 }
 
+#[test]
+fn test_parse_chaos() {
+    let config: Config = "".parse().unwrap();
+    assert_eq!(config.get_chaos(), None);
+
+    let config: Config = "
+[chaos]
+version = \"trust-dns\"
+  "
+    .parse()
+    .unwrap();
+
+    assert_eq!(
+        config.get_chaos(),
+        Some(&ChaosConfig {
+            version: Some("trust-dns".to_string()),
+            hostname: None,
+        })
+    );
+}
+
+#[test]
+fn test_parse_rate_limit() {
+    let config: Config = "".parse().unwrap();
+    assert_eq!(config.get_rate_limit(), None);
+
+    let config: Config = "
+[rate_limit]
+responses_per_window = 10
+  "
+    .parse()
+    .unwrap();
+
+    assert_eq!(
+        config.get_rate_limit(),
+        Some(&RateLimitConfig {
+            responses_per_window: 10,
+            window_secs: 1,
+            slip: 2,
+        })
+    );
+}
+
+#[test]
+fn test_parse_acl() {
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Primary\"
+file = \"example.com.zone\"
+
+[zones.acl]
+transfer = [\"192.0.2.0/24\", \"2001:db8::/32\"]
+query = []
+  "
+    .parse()
+    .unwrap();
+
+    let acl = config.get_zones()[0].get_acl().expect("acl is configured");
+    let allowed = |operation, client: &str| acl.is_allowed(operation, client.parse().unwrap());
+    assert!(allowed(AclOperation::Transfer, "192.0.2.53"));
+    assert!(allowed(AclOperation::Transfer, "2001:db8::53"));
+    assert!(!allowed(AclOperation::Transfer, "198.51.100.53"));
+    assert!(!allowed(AclOperation::Query, "192.0.2.53"));
+    assert!(allowed(AclOperation::Update, "198.51.100.53"));
+
+    // a bad prefix fails the configuration
+    let config: Result<Config, _> = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Primary\"
+file = \"example.com.zone\"
+
+[zones.acl]
+transfer = [\"192.0.2.0/33\"]
+  "
+    .parse();
+    assert!(config.is_err());
+}
+
 fn test_config(path: &str) {
     let workspace = env::var("TDNS_WORKSPACE_ROOT").unwrap_or_else(|_| "../..".to_owned());
     let path = PathBuf::from(workspace)
@@ -1,15 +1,27 @@
 #![recursion_limit = "128"]
 #![cfg(feature = "trust-dns-resolver")]
 
-use std::net::Ipv4Addr;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
 use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
 
+use tokio::net;
 use tokio::runtime::Runtime;
 
-use trust_dns_client::rr::{Name, RecordType};
+use trust_dns_client::op::{Message, MessageType, OpCode, Query, ResponseCode};
+use trust_dns_client::rr::{Name, RData, Record, RecordType};
+use trust_dns_client::serialize::binary::{BinDecodable, BinEncoder};
+use trust_dns_resolver::config::{NameServerConfigGroup, ResolverOpts};
 use trust_dns_resolver::TokioHandle;
-use trust_dns_server::authority::{Authority, LookupObject};
-use trust_dns_server::store::forwarder::ForwardAuthority;
+use trust_dns_server::authority::{
+    Authority, Catalog, LookupObject, MessageRequest, MessageResponse, ZoneType,
+};
+use trust_dns_server::server::ResponseHandler;
+use trust_dns_server::store::forwarder::{ForwardAuthority, ForwardConfig};
+use trust_dns_server::ServerFuture;
 
 #[ignore]
 #[test]
@@ -33,3 +45,168 @@ fn test_lookup() {
     let address = address.rdata().as_a().expect("not an A record");
     assert_eq!(*address, Ipv4Addr::new(93, 184, 216, 34));
 }
+
+/// Captures the response sent by the Catalog
+#[derive(Clone, Default)]
+struct CapturingResponseHandler(Arc<Mutex<Vec<u8>>>);
+
+impl CapturingResponseHandler {
+    fn message(&self) -> Message {
+        Message::from_vec(&self.0.lock().unwrap()).expect("no response")
+    }
+}
+
+impl ResponseHandler for CapturingResponseHandler {
+    fn send_response(&mut self, response: MessageResponse) -> io::Result<()> {
+        let mut buf = self.0.lock().unwrap();
+        let mut encoder = BinEncoder::new(&mut buf);
+        response.destructive_emit(&mut encoder).map_err(Into::into)
+    }
+}
+
+/// An upstream server answering every A query with 127.0.0.7, except for nx.example.com.
+fn stub_upstream() -> SocketAddr {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = socket.local_addr().unwrap();
+
+    thread::spawn(move || {
+        let mut buf = [0_u8; 512];
+        while let Ok((len, src)) = socket.recv_from(&mut buf) {
+            let request = Message::from_vec(&buf[..len]).expect("bad request");
+            let query = request.queries()[0].clone();
+
+            let mut response = Message::new();
+            response
+                .set_id(request.id())
+                .set_message_type(MessageType::Response)
+                .set_op_code(OpCode::Query)
+                .set_recursion_available(true)
+                .add_query(query.clone());
+
+            if *query.name() == Name::from_str("nx.example.com.").unwrap() {
+                response.set_response_code(ResponseCode::NXDomain);
+            } else {
+                response.add_answer(Record::from_rdata(
+                    query.name().clone(),
+                    300,
+                    RData::A(Ipv4Addr::new(127, 0, 0, 7)),
+                ));
+            }
+
+            socket
+                .send_to(&response.to_vec().unwrap(), src)
+                .expect("send failed");
+        }
+    });
+
+    addr
+}
+
+fn forward(catalog: &Catalog, runtime: &Runtime, name: &str) -> Message {
+    let mut question = Message::new();
+    question.add_query(Query::query(Name::from_str(name).unwrap(), RecordType::A));
+    let request = MessageRequest::from_bytes(&question.to_vec().unwrap()).unwrap();
+
+    let response_handler = CapturingResponseHandler::default();
+    runtime.block_on(catalog.lookup(request, None, response_handler.clone()));
+    response_handler.message()
+}
+
+#[test]
+fn test_catalog_forwards() {
+    let runtime = Runtime::new().expect("failed to create Tokio Runtime");
+    let upstream = stub_upstream();
+
+    let origin = Name::from_str("example.com.").unwrap();
+    let config = ForwardConfig {
+        name_servers: NameServerConfigGroup::from_ips_clear(
+            &[upstream.ip()],
+            upstream.port(),
+            true,
+        ),
+        options: None,
+        hop_limit: None,
+    };
+    let forwarder = runtime
+        .block_on(ForwardAuthority::try_from_config(
+            origin.clone(),
+            ZoneType::Forward,
+            &config,
+        ))
+        .expect("failed to create forwarder");
+
+    let mut catalog = Catalog::new();
+    catalog.upsert(origin.into(), Box::new(Arc::new(RwLock::new(forwarder))));
+
+    let response = forward(&catalog, &runtime, "www.example.com.");
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert!(!response.authoritative());
+    assert_eq!(
+        response.answers()[0].rdata(),
+        &RData::A(Ipv4Addr::new(127, 0, 0, 7))
+    );
+
+    let response = forward(&catalog, &runtime, "nx.example.com.");
+    assert_eq!(response.response_code(), ResponseCode::NXDomain);
+    assert!(response.answers().is_empty());
+}
+
+/// A catalog forwarding the zone to `upstream`, refusing queries forwarded 3 times
+fn looping_catalog(runtime: &Runtime, origin: &Name, upstream: SocketAddr) -> Catalog {
+    let mut options = ResolverOpts::default();
+    options.timeout = Duration::from_secs(5);
+
+    let config = ForwardConfig {
+        name_servers: NameServerConfigGroup::from_ips_clear(
+            &[upstream.ip()],
+            upstream.port(),
+            true,
+        ),
+        options: Some(options),
+        hop_limit: Some(3),
+    };
+    let forwarder = runtime
+        .block_on(ForwardAuthority::try_from_config(
+            origin.clone(),
+            ZoneType::Forward,
+            &config,
+        ))
+        .expect("failed to create forwarder");
+
+    let mut catalog = Catalog::new();
+    catalog.upsert(
+        origin.clone().into(),
+        Box::new(Arc::new(RwLock::new(forwarder))),
+    );
+    catalog
+}
+
+#[test]
+fn test_forwarding_loop() {
+    let runtime = Runtime::new().expect("failed to create Tokio Runtime");
+    let origin = Name::from_str("example.com.").unwrap();
+
+    // two servers which forward the zone to each other
+    let (socket_a, socket_b) = runtime.block_on(async {
+        let socket_a = net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let socket_b = net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        (socket_a, socket_b)
+    });
+    let addr_a = socket_a.local_addr().unwrap();
+    let addr_b = socket_b.local_addr().unwrap();
+
+    let mut server_a = ServerFuture::new(looping_catalog(&runtime, &origin, addr_b));
+    let mut server_b = ServerFuture::new(looping_catalog(&runtime, &origin, addr_a));
+    runtime.block_on(async {
+        server_a.register_socket(socket_a);
+        server_b.register_socket(socket_b);
+    });
+
+    // without the hop limit, the query would loop until the forwarded queries time out
+    let started = Instant::now();
+    let catalog = looping_catalog(&runtime, &origin, addr_a);
+    let response = forward(&catalog, &runtime, "www.example.com.");
+
+    assert_eq!(response.response_code(), ResponseCode::ServFail);
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
@@ -1,11 +1,12 @@
 #![cfg(feature = "sqlite")]
 
 use std::fs;
+use std::net::Ipv4Addr;
 use std::path::PathBuf;
 use std::str::FromStr;
 
-use trust_dns_client::rr::Name;
-use trust_dns_server::authority::ZoneType;
+use trust_dns_client::rr::{DNSClass, Name, RData, Record};
+use trust_dns_server::authority::{Authority, Ixfr, ZoneType};
 use trust_dns_server::store::sqlite::{SqliteAuthority, SqliteConfig};
 
 #[macro_use]
@@ -71,3 +72,78 @@ basic_battery!(sqlite);
 dnssec_battery!(sqlite);
 #[cfg(feature = "dnssec")]
 dynamic_update!(sqlite_update);
+
+#[test]
+fn test_ixfr() {
+    let journal_path = PathBuf::from("target/tests/store_sqlite_tests/test_ixfr/ixfr.jrnl");
+    fs::create_dir_all(journal_path.parent().unwrap()).ok();
+
+    // cleanup anything from previous test
+    fs::remove_file(&journal_path).ok();
+
+    let config = SqliteConfig {
+        zone_file_path: "../../tests/test-data/named_test_configs/example.com.zone".to_string(),
+        journal_file_path: journal_path.to_str().unwrap().to_string(),
+        allow_update: true,
+    };
+
+    let mut authority = SqliteAuthority::try_from_config(
+        Name::from_str("example.com.").unwrap(),
+        ZoneType::Primary,
+        false,
+        false,
+        None,
+        &config,
+    )
+    .expect("failed to load file");
+
+    let serial = authority.serial();
+    let bbb = Name::from_str("bbb.example.com.").unwrap();
+
+    let added = Record::from_rdata(
+        Name::from_str("new.example.com.").unwrap(),
+        86400,
+        RData::A(Ipv4Addr::new(127, 0, 0, 10)),
+    );
+    let mut delete = Record::from_rdata(bbb.clone(), 0, RData::A(Ipv4Addr::new(127, 0, 0, 2)));
+    delete.set_dns_class(DNSClass::NONE);
+
+    assert!(authority
+        .update_records(&[added.clone(), delete], true)
+        .expect("update failed"));
+    assert_eq!(authority.serial(), serial + 1);
+
+    let delta = match authority.ixfr(serial) {
+        Ixfr::Incremental(delta) => delta,
+        Ixfr::Full => panic!("expected an incremental transfer"),
+    };
+
+    assert_eq!(delta.old_soa().rdata().as_soa().unwrap().serial(), serial);
+    assert_eq!(delta.new_soa().rdata().as_soa().unwrap().serial(), serial + 1);
+    assert_eq!(delta.added(), &[added]);
+    assert_eq!(delta.deleted().len(), 1);
+    assert_eq!(delta.deleted()[0].name(), &bbb);
+    assert_eq!(
+        *delta.deleted()[0].rdata(),
+        RData::A(Ipv4Addr::new(127, 0, 0, 2))
+    );
+
+    // the difference sequence is framed by the new SOA
+    let records = delta.to_records();
+    assert_eq!(records.len(), 6);
+    assert_eq!(&records[0], delta.new_soa());
+    assert_eq!(&records[1], delta.old_soa());
+    assert_eq!(&records[2], &delta.deleted()[0]);
+    assert_eq!(&records[3], delta.new_soa());
+    assert_eq!(&records[4], &delta.added()[0]);
+    assert_eq!(&records[5], delta.new_soa());
+
+    // an up to date serial only receives the SOA
+    match authority.ixfr(serial + 1) {
+        Ixfr::Incremental(delta) => assert_eq!(delta.to_records(), vec![delta.new_soa().clone()]),
+        Ixfr::Full => panic!("expected an incremental transfer"),
+    }
+
+    // unknown serials require a full zone transfer
+    assert_eq!(authority.ixfr(serial - 1), Ixfr::Full);
+}
@@ -55,6 +55,8 @@ _443._tcp.www.example.com. IN TLSA (
       0 0 1 d2abde240d7cd3ee6b4b28c54df034b9
             7983a1d16e8a410e4561cb106618e971)
 
+www          HTTPS 1 . alpn=h2,h3 port=8443 ipv4hint=192.0.2.1 ipv6hint=2001:db8::1
+
 tech.   3600    in      soa     ns0.centralnic.net.     hostmaster.centralnic.net.      271851  900     1800    6048000 3600
 "###,
     );
@@ -79,7 +81,8 @@ tech.   3600    in      soa     ns0.centralnic.net.     hostmaster.centralnic.ne
         .unwrap();
     assert_eq!(RecordType::SOA, soa_record.rr_type());
     assert_eq!(&Name::from_str("isi.edu").unwrap(), soa_record.name()); // i.e. the origin or domain
-    assert_eq!(3_600_000, soa_record.ttl());
+    // without a TTL or $TTL, the SOA takes its minimum
+    assert_eq!(60, soa_record.ttl());
     assert_eq!(DNSClass::IN, soa_record.dns_class());
     if let RData::SOA(ref soa) = *soa_record.rdata() {
         // this should all be lowercased
@@ -418,6 +421,32 @@ tech.   3600    in      soa     ns0.centralnic.net.     hostmaster.centralnic.ne
     } else {
         panic!();
     }
+
+    // HTTPS
+    let https_record: Record = block_on(authority.lookup(
+        &Name::from_str("www.isi.edu.").unwrap().into(),
+        RecordType::HTTPS,
+        false,
+        SupportedAlgorithms::new(),
+    ))
+    .unwrap()
+    .iter()
+    .next()
+    .cloned()
+    .expect("https record not found");
+    if let RData::HTTPS(ref rdata) = *https_record.rdata() {
+        assert_eq!(rdata.svc_priority(), 1);
+        assert!(rdata.target_name().is_root());
+        assert_eq!(rdata.alpn(), Some(&["h2".to_string(), "h3".to_string()][..]));
+        assert_eq!(rdata.port(), Some(8443));
+        assert_eq!(rdata.ipv4hint(), Some(&[Ipv4Addr::new(192, 0, 2, 1)][..]));
+        assert_eq!(
+            rdata.ipv6hint(),
+            Some(&[Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)][..])
+        );
+    } else {
+        panic!();
+    }
 }
 
 #[test]
@@ -523,3 +552,65 @@ fn test_named_root() {
     assert!(records.contains_key(&key));
     assert_eq!(records[&key].dns_class(), DNSClass::IN)
 }
+
+#[test]
+fn test_wildcard() {
+    let lexer = Lexer::new(
+        r###"
+@       IN  SOA     ns1 hostmaster ( 1 7200 600 3600000 60 )
+            NS      ns1
+ns1         A       192.0.2.1
+*           A       192.0.2.100
+            TXT     "wildcard"
+host        A       192.0.2.2
+x.empty     A       192.0.2.3
+"###,
+    );
+
+    let records = Parser::new().parse(lexer, Some(Name::from_str("example.com").unwrap()), None);
+    let (origin, records) = records.expect("failed to parse");
+    let authority = InMemoryAuthority::new(origin, records, ZoneType::Primary, false).unwrap();
+
+    let lookup = |name: &str, rtype| {
+        block_on(authority.lookup(
+            &Name::from_str(name).unwrap().into(),
+            rtype,
+            false,
+            SupportedAlgorithms::new(),
+        ))
+    };
+
+    // the wildcard below the apex matches names at any depth
+    for &name in &["foo.example.com.", "a.b.example.com."] {
+        let answer = lookup(name, RecordType::A).expect("wildcard did not match");
+        let record = answer.iter().next().expect("no records");
+        assert_eq!(record.name(), &Name::from_str(name).unwrap());
+        assert_eq!(record.rdata(), &RData::A(Ipv4Addr::new(192, 0, 2, 100)));
+    }
+
+    // the wildcard exists, but not with this type
+    assert!(lookup("foo.example.com.", RecordType::MX)
+        .err()
+        .expect("MX should not exist")
+        .is_name_exists());
+
+    // a closer name exists, so the wildcard does not apply
+    assert!(lookup("host.example.com.", RecordType::TXT)
+        .err()
+        .expect("TXT should not exist at host")
+        .is_name_exists());
+    assert!(lookup("example.com.", RecordType::A)
+        .err()
+        .expect("A should not exist at the apex")
+        .is_name_exists());
+
+    // empty.example.com. exists, without records of its own, and is the closest encloser
+    assert!(lookup("empty.example.com.", RecordType::A)
+        .err()
+        .expect("A should not exist at empty")
+        .is_name_exists());
+    assert!(lookup("y.empty.example.com.", RecordType::A)
+        .err()
+        .expect("there is no wildcard at empty")
+        .is_nx_domain());
+}
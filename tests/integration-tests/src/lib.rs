@@ -27,7 +27,7 @@ use trust_dns_proto::xfer::{
 use trust_dns_proto::{StreamHandle, TokioTime};
 
 use trust_dns_server::authority::{Catalog, MessageRequest, MessageResponse};
-use trust_dns_server::server::{Request, RequestHandler, ResponseHandler};
+use trust_dns_server::server::{Protocol, Request, RequestHandler, ResponseHandler};
 
 pub mod authority;
 pub mod mock_client;
@@ -73,6 +73,11 @@ impl TestResponseHandler {
         TestResponseHandler { message_ready, buf }
     }
 
+    /// Returns true if a response has been sent to this handler
+    pub fn has_response(&self) -> bool {
+        self.message_ready.load(Ordering::Acquire)
+    }
+
     fn into_inner(self) -> impl Future<Output = Vec<u8>> {
         future::poll_fn(move |_| {
             if self
@@ -140,6 +145,7 @@ impl Stream for TestClientStream {
                 let request = Request {
                     message,
                     src: src_addr,
+                    protocol: Protocol::Udp,
                 };
 
                 let response_handler = TestResponseHandler::new();
@@ -17,9 +17,13 @@ use trust_dns_client::rr::{Name, RData, Record};
 use trust_dns_proto::error::ProtoError;
 use trust_dns_proto::xfer::{DnsHandle, DnsRequest, DnsResponse};
 
+/// A scripted response, for any Query if there is none
+type Scripted<E> = (Option<Query>, Result<DnsResponse, E>);
+
 #[derive(Clone)]
 pub struct MockClientHandle<O: OnSend, E> {
-    messages: Arc<Mutex<Vec<Result<DnsResponse, E>>>>,
+    messages: Arc<Mutex<Vec<Scripted<E>>>>,
+    requests: Arc<Mutex<Vec<DnsRequest>>>,
     on_send: O,
 }
 
@@ -28,8 +32,26 @@ impl<E> MockClientHandle<DefaultOnSend, E> {
     pub fn mock(messages: Vec<Result<DnsResponse, E>>) -> Self {
         println!("MockClientHandle::mock message count: {}", messages.len());
 
+        Self::mock_on_send(messages, DefaultOnSend)
+    }
+
+    /// constructs a new MockClient which returns each Message to a request for its Query
+    ///
+    /// As with `mock`, the Messages for the same Query are returned from the last to the first.
+    pub fn mock_queries(messages: Vec<(Query, Result<DnsResponse, E>)>) -> Self {
+        println!(
+            "MockClientHandle::mock_queries message count: {}",
+            messages.len()
+        );
+
         MockClientHandle {
-            messages: Arc::new(Mutex::new(messages)),
+            messages: Arc::new(Mutex::new(
+                messages
+                    .into_iter()
+                    .map(|(query, message)| (Some(query), message))
+                    .collect(),
+            )),
+            requests: Arc::new(Mutex::new(vec![])),
             on_send: DefaultOnSend,
         }
     }
@@ -44,10 +66,28 @@ impl<O: OnSend, E> MockClientHandle<O, E> {
         );
 
         MockClientHandle {
-            messages: Arc::new(Mutex::new(messages)),
+            messages: Arc::new(Mutex::new(
+                messages.into_iter().map(|message| (None, message)).collect(),
+            )),
+            requests: Arc::new(Mutex::new(vec![])),
             on_send,
         }
     }
+
+    /// the number of requests sent to this client, and all of its clones
+    pub fn send_count(&self) -> usize {
+        self.requests.lock().expect("failed to lock at requests").len()
+    }
+
+    /// the queries of all the requests sent to this client, and all of its clones, in order
+    pub fn sent_queries(&self) -> Vec<Query> {
+        self.requests
+            .lock()
+            .expect("failed to lock at requests")
+            .iter()
+            .flat_map(|request| request.queries().iter().cloned())
+            .collect()
+    }
 }
 
 impl<O: OnSend + Unpin, E> DnsHandle for MockClientHandle<O, E>
@@ -57,15 +97,27 @@ where
     type Response = Pin<Box<dyn Future<Output = Result<DnsResponse, E>> + Send>>;
     type Error = E;
 
-    fn send<R: Into<DnsRequest>>(&mut self, _: R) -> Self::Response {
+    fn send<R: Into<DnsRequest>>(&mut self, request: R) -> Self::Response {
+        let request = request.into();
+        let query = request.queries().first().cloned();
+        self.requests
+            .lock()
+            .expect("failed to lock at requests")
+            .push(request);
+
         let mut messages = self.messages.lock().expect("failed to lock at messages");
         println!("MockClientHandle::send message count: {}", messages.len());
 
-        Box::pin(self.on_send.on_send(messages.pop().unwrap_or_else(|| {
-            error(E::from(ProtoError::from(
+        let scripted = messages.iter().rposition(|(scripted, _)| {
+            scripted.is_none() || scripted.as_ref() == query.as_ref()
+        });
+
+        Box::pin(self.on_send.on_send(match scripted {
+            Some(position) => messages.remove(position).1,
+            None => error(E::from(ProtoError::from(
                 "Messages exhausted in MockClientHandle",
-            )))
-        })))
+            ))),
+        }))
     }
 }
 
@@ -1,6 +1,7 @@
 use std::net::*;
 use std::str::FromStr;
 use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
 use futures::executor::block_on;
 
@@ -9,7 +10,11 @@ use trust_dns_client::rr::rdata::*;
 use trust_dns_client::rr::*;
 use trust_dns_client::serialize::binary::{BinDecodable, BinEncodable};
 
-use trust_dns_server::authority::{Authority, Catalog, MessageRequest, ZoneType};
+use trust_dns_server::authority::{
+    Acl, AclOperation, Authority, Catalog, Cidr, MessageRequest, RateLimitConfig, ZoneType,
+};
+use trust_dns_server::store::chaos::ChaosConfig;
+use trust_dns_server::server::{Protocol, Request, RequestHandler};
 use trust_dns_server::store::in_memory::InMemoryAuthority;
 
 use trust_dns_integration::authority::create_example;
@@ -401,6 +406,348 @@ fn test_axfr_refused() {
     assert!(result.additionals().is_empty());
 }
 
+#[test]
+fn test_axfr_refused_extended_error() {
+    let mut test = create_test();
+    test.set_allow_axfr(false);
+
+    let origin = test.origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), Box::new(Arc::new(RwLock::new(test))));
+
+    let mut question: Message = Message::new();
+    question.add_query(Query::query(origin.into(), RecordType::AXFR));
+    question.set_edns(Edns::new());
+
+    let question_bytes = question.to_bytes().unwrap();
+    let question_req = MessageRequest::from_bytes(&question_bytes).unwrap();
+
+    // the extended error is only sent to clients which sent EDNS
+    let response_handler = TestResponseHandler::new();
+    block_on(catalog.lookup(question_req, Some(Edns::new()), response_handler.clone()));
+    let result = block_on(response_handler.into_message());
+
+    assert_eq!(result.response_code(), ResponseCode::Refused);
+    let extended_error = result
+        .edns()
+        .and_then(|edns| edns.option(opt::EdnsCode::ExtendedError));
+    match extended_error {
+        Some(opt::EdnsOption::ExtendedError(error)) => {
+            assert_eq!(error.info_code(), opt::ExtendedError::PROHIBITED)
+        }
+        other => panic!("expected an extended error: {:?}", other),
+    }
+}
+
+#[test]
+#[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+fn test_axfr_tsig() {
+    use trust_dns_client::rr::dnssec::{TsigAlgorithm, TsigKey};
+    use trust_dns_client::rr::rdata::{DNSSECRData, DNSSECRecordType, TSIG};
+
+    fn tsig_rdata(message: &Message) -> &TSIG {
+        let tsig = message.sig0().last().expect("no tsig");
+        assert_eq!(tsig.rr_type(), RecordType::DNSSEC(DNSSECRecordType::TSIG));
+        match tsig.rdata() {
+            RData::DNSSEC(DNSSECRData::TSIG(rdata)) => rdata,
+            rdata => panic!("expected a tsig: {:?}", rdata),
+        }
+    }
+
+    let mut test = create_test();
+    test.set_allow_axfr(true);
+
+    let key = TsigKey::new(
+        Name::from_str("transfer-key.test.com.").unwrap(),
+        TsigAlgorithm::HmacSha512,
+        b"the shared secret".to_vec(),
+    );
+    test.add_tsig_key(key.clone()).unwrap();
+
+    let origin = test.origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), Box::new(Arc::new(RwLock::new(test))));
+
+    let mut question: Message = Message::new();
+    question.add_query(Query::query(origin.into(), RecordType::AXFR));
+
+    // unsigned transfers are refused
+    let question_req = MessageRequest::from_bytes(&question.to_bytes().unwrap()).unwrap();
+    let response_handler = TestResponseHandler::new();
+    block_on(catalog.lookup(question_req, None, response_handler.clone()));
+    let result = block_on(response_handler.into_message());
+
+    assert_eq!(result.response_code(), ResponseCode::NotAuth);
+    assert!(result.answers().is_empty());
+
+    // signed transfers are answered with the zone
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let mut signed = question.clone();
+    signed.finalize(&key, now as u32).unwrap();
+    let request_mac = tsig_rdata(&signed).mac().to_vec();
+
+    let question_req = MessageRequest::from_bytes(&signed.to_bytes().unwrap()).unwrap();
+    let response_handler = TestResponseHandler::new();
+    block_on(catalog.lookup(question_req, None, response_handler.clone()));
+    let result = block_on(response_handler.into_message());
+
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert_eq!(
+        result.answers().first().expect("no records found?").rr_type(),
+        RecordType::SOA
+    );
+
+    // the response is signed with the key, over the MAC of the request
+    let tsig = result.sig0().last().expect("no tsig").clone();
+    assert_eq!(tsig_rdata(&result).error(), 0);
+    key.verify_response(&result, &tsig, &request_mac, now)
+        .expect("response tsig did not verify");
+
+    // a transfer signed with the wrong secret is refused with BADSIG in the TSIG
+    let wrong_key = TsigKey::new(
+        key.name().clone(),
+        TsigAlgorithm::HmacSha512,
+        b"the wrong secret".to_vec(),
+    );
+    let mut signed = question.clone();
+    signed.finalize(&wrong_key, now as u32).unwrap();
+
+    let question_req = MessageRequest::from_bytes(&signed.to_bytes().unwrap()).unwrap();
+    let response_handler = TestResponseHandler::new();
+    block_on(catalog.lookup(question_req, None, response_handler.clone()));
+    let result = block_on(response_handler.into_message());
+
+    assert_eq!(result.response_code(), ResponseCode::NotAuth);
+    assert!(result.answers().is_empty());
+    let rdata = tsig_rdata(&result);
+    assert_eq!(rdata.error(), u16::from(ResponseCode::BADSIG));
+    assert!(rdata.mac().is_empty());
+
+    // and with BADKEY for an unknown key
+    let unknown_key = TsigKey::new(
+        Name::from_str("unknown-key.test.com.").unwrap(),
+        TsigAlgorithm::HmacSha512,
+        b"the shared secret".to_vec(),
+    );
+    let mut signed = question;
+    signed.finalize(&unknown_key, now as u32).unwrap();
+
+    let question_req = MessageRequest::from_bytes(&signed.to_bytes().unwrap()).unwrap();
+    let response_handler = TestResponseHandler::new();
+    block_on(catalog.lookup(question_req, None, response_handler.clone()));
+    let result = block_on(response_handler.into_message());
+
+    assert_eq!(result.response_code(), ResponseCode::NotAuth);
+    assert_eq!(tsig_rdata(&result).error(), u16::from(ResponseCode::BADKEY));
+}
+
+#[test]
+fn test_axfr_framing() {
+    let mut test = create_test();
+    test.set_allow_axfr(true);
+
+    let axfr = test.axfr().expect("axfr failed");
+    let records = axfr.records();
+
+    assert_eq!(records.first(), Some(axfr.soa()));
+    assert_eq!(records.last(), Some(axfr.soa()));
+    assert_eq!(
+        records
+            .iter()
+            .filter(|r| r.rr_type() == RecordType::SOA)
+            .count(),
+        2
+    );
+}
+
+fn ixfr_query(origin: &Name, serial: Option<u32>) -> MessageRequest {
+    let mut question: Message = Message::new();
+    question.add_query(Query::query(origin.clone(), RecordType::IXFR));
+    if let Some(serial) = serial {
+        let soa = SOA::new(origin.clone(), origin.clone(), serial, 0, 0, 0, 0);
+        question.add_name_server(Record::from_rdata(origin.clone(), 0, RData::SOA(soa)));
+    }
+
+    let question_bytes = question.to_bytes().unwrap();
+    MessageRequest::from_bytes(&question_bytes).unwrap()
+}
+
+#[test]
+fn test_ixfr() {
+    let mut test = create_test();
+    test.set_allow_axfr(true);
+    let origin = Name::from(test.origin());
+    let serial = test.serial();
+    let record_count = test.axfr().expect("axfr failed").records().len();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone().into(), Box::new(Arc::new(RwLock::new(test))));
+
+    // an up to date secondary only receives the current SOA
+    let response_handler = TestResponseHandler::new();
+    block_on(catalog.lookup(ixfr_query(&origin, Some(serial)), None, response_handler.clone()));
+    let result = block_on(response_handler.into_message());
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert_eq!(result.answers().len(), 1);
+    assert_eq!(result.answers()[0].rr_type(), RecordType::SOA);
+
+    // the changes since an unknown serial are not known, the entire zone is transferred
+    let response_handler = TestResponseHandler::new();
+    block_on(catalog.lookup(
+        ixfr_query(&origin, Some(serial - 1)),
+        None,
+        response_handler.clone(),
+    ));
+    let result = block_on(response_handler.into_message());
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert_eq!(result.answers().len(), record_count);
+    assert_eq!(result.answers().first(), result.answers().last());
+
+    // the SOA of the secondary is required
+    let response_handler = TestResponseHandler::new();
+    block_on(catalog.lookup(ixfr_query(&origin, None), None, response_handler.clone()));
+    let result = block_on(response_handler.into_message());
+    assert_eq!(result.response_code(), ResponseCode::FormErr);
+    assert!(result.answers().is_empty());
+}
+
+#[test]
+#[allow(clippy::unreadable_literal)]
+fn test_axfr_apex_only() {
+    let origin: Name = Name::parse("test.com.", None).unwrap();
+    let soa = Record::new()
+        .set_name(origin.clone())
+        .set_ttl(3600)
+        .set_rr_type(RecordType::SOA)
+        .set_dns_class(DNSClass::IN)
+        .set_rdata(RData::SOA(SOA::new(
+            Name::parse("sns.dns.icann.org.", None).unwrap(),
+            Name::parse("noc.dns.icann.org.", None).unwrap(),
+            2015082403,
+            7200,
+            3600,
+            1209600,
+            3600,
+        )))
+        .clone();
+
+    // an empty zone has nothing to transfer
+    let mut test = InMemoryAuthority::empty(origin.clone(), ZoneType::Primary, true);
+    assert_eq!(
+        test.axfr().unwrap_err().as_response_code(),
+        Some(&ResponseCode::ServFail)
+    );
+
+    // a zone with only an SOA is the SOA twice
+    test.upsert(soa.clone(), 0);
+    let axfr = test.axfr().expect("axfr failed");
+    assert_eq!(axfr.records(), &[soa.clone(), soa][..]);
+}
+
+fn axfr_from(catalog: &Catalog, origin: &LowerName, client: &str) -> Message {
+    let mut question: Message = Message::new();
+    question.add_query(Query::query(origin.into(), RecordType::AXFR));
+
+    let question_bytes = question.to_bytes().unwrap();
+    let request = Request {
+        message: MessageRequest::from_bytes(&question_bytes).unwrap(),
+        src: SocketAddr::new(IpAddr::from_str(client).unwrap(), 53),
+        protocol: Protocol::Tcp,
+    };
+
+    let response_handler = TestResponseHandler::new();
+    block_on(catalog.handle_request(request, response_handler.clone()));
+    block_on(response_handler.into_message())
+}
+
+#[test]
+fn test_axfr_acl() {
+    let mut test = create_test();
+    test.set_allow_axfr(true);
+    let origin = test.origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), Box::new(Arc::new(RwLock::new(test))));
+
+    // a zone without an ACL may not be transferred by any client, even when allowed by the zone
+    let result = axfr_from(&catalog, &origin, "192.0.2.1");
+    assert_eq!(result.response_code(), ResponseCode::Refused);
+    assert!(result.answers().is_empty());
+
+    // an ACL denies transfers to all clients, unless allowed
+    catalog.set_acl(origin.clone(), Acl::default());
+    let result = axfr_from(&catalog, &origin, "192.0.2.1");
+    assert_eq!(result.response_code(), ResponseCode::Refused);
+
+    let mut acl = Acl::default();
+    acl.allow(AclOperation::Transfer, Cidr::from_str("192.0.2.0/24").unwrap());
+    acl.allow(AclOperation::Transfer, Cidr::from_str("2001:db8::/32").unwrap());
+    catalog.set_acl(origin.clone(), acl);
+
+    for client in &["192.0.2.1", "2001:db8::1"] {
+        let result = axfr_from(&catalog, &origin, client);
+        assert_eq!(result.response_code(), ResponseCode::NoError, "{}", client);
+        assert!(!result.answers().is_empty(), "{}", client);
+    }
+
+    for client in &["198.51.100.1", "2001:db9::1"] {
+        let result = axfr_from(&catalog, &origin, client);
+        assert_eq!(result.response_code(), ResponseCode::Refused, "{}", client);
+        assert!(result.answers().is_empty(), "{}", client);
+    }
+}
+
+#[test]
+#[allow(clippy::unreadable_literal)]
+fn test_axfr_not_authoritative() {
+    let origin: Name = Name::parse("test.com.", None).unwrap();
+    let mut test = InMemoryAuthority::empty(origin.clone(), ZoneType::Hint, true);
+    test.upsert(
+        Record::new()
+            .set_name(origin.clone())
+            .set_ttl(3600)
+            .set_rr_type(RecordType::SOA)
+            .set_dns_class(DNSClass::IN)
+            .set_rdata(RData::SOA(SOA::new(
+                Name::parse("sns.dns.icann.org.", None).unwrap(),
+                Name::parse("noc.dns.icann.org.", None).unwrap(),
+                2015082403,
+                7200,
+                3600,
+                1209600,
+                3600,
+            )))
+            .clone(),
+        0,
+    );
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone().into(), Box::new(Arc::new(RwLock::new(test))));
+
+    let mut query: Query = Query::new();
+    query.set_name(origin);
+    query.set_query_type(RecordType::AXFR);
+
+    let mut question: Message = Message::new();
+    question.add_query(query);
+
+    // temp request
+    let question_bytes = question.to_bytes().unwrap();
+    let question_req = MessageRequest::from_bytes(&question_bytes).unwrap();
+
+    let response_handler = TestResponseHandler::new();
+    block_on(catalog.lookup(question_req, None, response_handler.clone()));
+    let result = block_on(response_handler.into_message());
+
+    assert_eq!(result.response_code(), ResponseCode::Refused);
+    assert!(result.answers().is_empty());
+}
+
 // TODO: add this test
 // #[test]
 // fn test_truncated_returns_records() {
@@ -505,3 +852,619 @@ fn test_multiple_cname_additionals() {
         &RData::A(Ipv4Addr::new(93, 184, 216, 34))
     );
 }
+
+#[cfg(feature = "dnssec")]
+fn secure_query(catalog: &Catalog, dnssec_ok: bool) -> Message {
+    let mut question: Message = Message::new();
+    question.add_query(Query::query(
+        Name::from_str("www.example.com.").unwrap(),
+        RecordType::A,
+    ));
+    question.edns_mut().set_dnssec_ok(dnssec_ok);
+
+    let question_bytes = question.to_bytes().unwrap();
+    let request = Request {
+        message: MessageRequest::from_bytes(&question_bytes).unwrap(),
+        src: SocketAddr::from(([127, 0, 0, 1], 53)),
+        protocol: Protocol::Udp,
+    };
+
+    let response_handler = TestResponseHandler::new();
+    block_on(catalog.handle_request(request, response_handler.clone()));
+    block_on(response_handler.into_message())
+}
+
+#[cfg(feature = "dnssec")]
+#[test]
+fn test_dnssec_ok_signs_answers() {
+    use trust_dns_client::rr::dnssec::Verifier;
+    use trust_dns_integration::authority::create_secure_example;
+
+    let example = create_secure_example();
+    let key = example.secure_keys()[0].to_dnskey().unwrap();
+    let origin = example.origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin, Box::new(Arc::new(RwLock::new(example))));
+
+    // without DO, no signatures are returned
+    let response = secure_query(&catalog, false);
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert!(!response.edns().expect("no edns").dnssec_ok());
+    assert!(response.answers().iter().all(|r| r.rr_type() == RecordType::A));
+
+    // with DO, the answer is signed with the zone signing key
+    let response = secure_query(&catalog, true);
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert!(response.edns().expect("no edns").dnssec_ok());
+
+    let (rrsigs, a_records): (Vec<Record>, Vec<Record>) = response
+        .answers()
+        .iter()
+        .cloned()
+        .partition(|r| r.rr_type() == RecordType::DNSSEC(DNSSECRecordType::RRSIG));
+    assert!(!a_records.is_empty());
+
+    let rrsig = rrsigs
+        .iter()
+        .filter_map(|r| r.rdata().as_dnssec()?.as_sig())
+        .find(|sig| sig.type_covered() == RecordType::A)
+        .expect("no RRSIG for the A records");
+    key.verify_rrsig(a_records[0].name(), DNSClass::IN, rrsig, &a_records)
+        .expect("RRSIG did not verify");
+}
+
+fn any_query(catalog: &Catalog, name: &str) -> Message {
+    let mut question: Message = Message::new();
+
+    let mut query: Query = Query::new();
+    query.set_name(Name::from_str(name).unwrap());
+    query.set_query_type(RecordType::ANY);
+
+    question.add_query(query);
+
+    // temp request
+    let question_bytes = question.to_bytes().unwrap();
+    let question_req = MessageRequest::from_bytes(&question_bytes).unwrap();
+
+    let response_handler = TestResponseHandler::new();
+    block_on(catalog.lookup(question_req, None, response_handler.clone()));
+    block_on(response_handler.into_message())
+}
+
+#[test]
+fn test_any_lookup() {
+    let example = create_example();
+    let origin = example.origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin, Box::new(Arc::new(RwLock::new(example))));
+
+    let result = any_query(&catalog, "www.example.com.");
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+
+    let mut types: Vec<RecordType> = result.answers().iter().map(Record::rr_type).collect();
+    types.sort();
+    assert_eq!(
+        types,
+        vec![RecordType::A, RecordType::TXT, RecordType::AAAA]
+    );
+    assert!(result
+        .answers()
+        .iter()
+        .all(|r| *r.name() == Name::from_str("www.example.com.").unwrap()));
+
+    // the apex includes the SOA
+    let result = any_query(&catalog, "example.com.");
+    let mut types: Vec<RecordType> = result.answers().iter().map(Record::rr_type).collect();
+    types.sort();
+    types.dedup();
+    assert_eq!(
+        types,
+        vec![
+            RecordType::A,
+            RecordType::NS,
+            RecordType::SOA,
+            RecordType::TXT,
+            RecordType::AAAA
+        ]
+    );
+}
+
+#[test]
+fn test_minimal_any_lookup() {
+    let mut example = create_example();
+    example.set_minimal_any(true);
+    let origin = example.origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin, Box::new(Arc::new(RwLock::new(example))));
+
+    let result = any_query(&catalog, "www.example.com.");
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+
+    // only a single RRset is returned
+    let answers = result.answers();
+    assert!(!answers.is_empty());
+    assert!(answers.iter().all(|r| r.rr_type() == answers[0].rr_type()));
+}
+
+#[test]
+fn test_rotate_rrsets() {
+    let mut example = create_example();
+    example.set_rotate_rrsets(true);
+    let origin = example.origin().clone();
+
+    let www_name = Name::from_str("www.example.com.").unwrap();
+    let serial = example.serial();
+    for octet in 1..=2 {
+        let a = RData::A(Ipv4Addr::new(127, 0, 0, octet));
+        assert!(example.upsert(Record::from_rdata(www_name.clone(), 86400, a), serial));
+    }
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin, Box::new(Arc::new(RwLock::new(example))));
+
+    let lookup_a = || -> Vec<Ipv4Addr> {
+        let mut question: Message = Message::new();
+        question.add_query(Query::query(www_name.clone(), RecordType::A));
+
+        let question_bytes = question.to_bytes().unwrap();
+        let question_req = MessageRequest::from_bytes(&question_bytes).unwrap();
+
+        let response_handler = TestResponseHandler::new();
+        block_on(catalog.lookup(question_req, None, response_handler.clone()));
+        let result = block_on(response_handler.into_message());
+        assert_eq!(result.response_code(), ResponseCode::NoError);
+
+        result
+            .answers()
+            .iter()
+            .map(|r| *r.rdata().as_a().expect("not an A record"))
+            .collect()
+    };
+
+    // each response starts with the address after the one the last started with
+    let mut expected = lookup_a();
+    assert_eq!(expected.len(), 3);
+    for _ in 0..3 {
+        expected.rotate_left(1);
+        assert_eq!(lookup_a(), expected);
+    }
+}
+
+fn class_query(catalog: &Catalog, name: &str, class: DNSClass) -> Message {
+    let mut question: Message = Message::new();
+
+    let mut query = Query::query(Name::from_str(name).unwrap(), RecordType::TXT);
+    query.set_query_class(class);
+    question.add_query(query);
+
+    // temp request
+    let question_bytes = question.to_bytes().unwrap();
+    let question_req = MessageRequest::from_bytes(&question_bytes).unwrap();
+
+    let response_handler = TestResponseHandler::new();
+    block_on(catalog.lookup(question_req, None, response_handler.clone()));
+    block_on(response_handler.into_message())
+}
+
+#[test]
+fn test_chaos_version() {
+    let chaos = ChaosConfig {
+        version: Some("trust-dns".to_string()),
+        hostname: None,
+    };
+    let example = create_example();
+    let origin = example.origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin, Box::new(Arc::new(RwLock::new(example))));
+    catalog.upsert(
+        ChaosConfig::origin().into(),
+        Box::new(Arc::new(RwLock::new(chaos.authority()))),
+    );
+
+    let result = class_query(&catalog, "version.bind.", DNSClass::CH);
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert_eq!(result.answers().len(), 1);
+    assert_eq!(result.answers()[0].dns_class(), DNSClass::CH);
+    assert_eq!(
+        result.answers()[0].rdata(),
+        &RData::TXT(TXT::new(vec!["trust-dns".to_string()]))
+    );
+
+    // the hostname is not configured
+    let result = class_query(&catalog, "hostname.bind.", DNSClass::CH);
+    assert_eq!(result.response_code(), ResponseCode::NXDomain);
+
+    // each zone only answers queries of its own class
+    let result = class_query(&catalog, "version.bind.", DNSClass::IN);
+    assert_eq!(result.response_code(), ResponseCode::Refused);
+    assert!(result.answers().is_empty());
+
+    let result = class_query(&catalog, "www.example.com.", DNSClass::CH);
+    assert_eq!(result.response_code(), ResponseCode::Refused);
+}
+
+fn flood_query(catalog: &Catalog, src: [u8; 4], protocol: Protocol) -> Option<Message> {
+    let mut question: Message = Message::new();
+    question.add_query(Query::query(
+        Name::from_str("www.example.com.").unwrap(),
+        RecordType::A,
+    ));
+
+    let question_bytes = question.to_bytes().unwrap();
+    let request = Request {
+        message: MessageRequest::from_bytes(&question_bytes).unwrap(),
+        src: SocketAddr::from((src, 53)),
+        protocol,
+    };
+
+    let response_handler = TestResponseHandler::new();
+    block_on(catalog.handle_request(request, response_handler.clone()));
+    if response_handler.has_response() {
+        Some(block_on(response_handler.into_message()))
+    } else {
+        None
+    }
+}
+
+#[test]
+fn test_rate_limit() {
+    let example = create_example();
+    let origin = example.origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin, Box::new(Arc::new(RwLock::new(example))));
+    catalog.set_rate_limit(RateLimitConfig {
+        responses_per_window: 3,
+        window_secs: 60,
+        slip: 2,
+    });
+
+    for _ in 0..3 {
+        let response = flood_query(&catalog, [192, 0, 2, 1], Protocol::Udp).expect("no response");
+        assert!(!response.truncated());
+        assert_eq!(response.answers().len(), 1);
+    }
+
+    // the flood is dropped, with every other response truncated for a retry over TCP
+    assert!(flood_query(&catalog, [192, 0, 2, 1], Protocol::Udp).is_none());
+    let response = flood_query(&catalog, [192, 0, 2, 2], Protocol::Udp).expect("no response");
+    assert!(response.truncated());
+    assert!(response.answers().is_empty());
+    assert!(flood_query(&catalog, [192, 0, 2, 3], Protocol::Udp).is_none());
+
+    // TCP and other clients are not limited
+    let response = flood_query(&catalog, [192, 0, 2, 1], Protocol::Tcp).expect("no response");
+    assert_eq!(response.answers().len(), 1);
+    let response = flood_query(&catalog, [198, 51, 100, 1], Protocol::Udp).expect("no response");
+    assert_eq!(response.answers().len(), 1);
+}
+
+#[test]
+fn test_delegation_glue() {
+    let mut example = create_example();
+    let origin = example.origin().clone();
+
+    // sub.example.com. is delegated to a name server within the zone, and one out of bailiwick
+    let sub = Name::from_str("sub.example.com.").unwrap();
+    let ns1 = Name::from_str("ns1.sub.example.com.").unwrap();
+    let ns2 = Name::from_str("ns2.example.net.").unwrap();
+    example.upsert(Record::from_rdata(sub.clone(), 86400, RData::NS(ns1.clone())), 0);
+    example.upsert(Record::from_rdata(sub.clone(), 86400, RData::NS(ns2.clone())), 0);
+    let glue_a = RData::A(Ipv4Addr::new(192, 0, 2, 53));
+    let glue_aaaa = RData::AAAA(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x53));
+    example.upsert(Record::from_rdata(ns1.clone(), 86400, glue_a.clone()), 0);
+    example.upsert(Record::from_rdata(ns1, 86400, glue_aaaa.clone()), 0);
+    example.upsert(
+        Record::from_rdata(ns2, 86400, RData::A(Ipv4Addr::new(192, 0, 2, 54))),
+        0,
+    );
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin, Box::new(Arc::new(RwLock::new(example))));
+
+    let mut question: Message = Message::new();
+    question.add_query(Query::query(
+        Name::from_str("www.sub.example.com.").unwrap(),
+        RecordType::A,
+    ));
+
+    let question_bytes = question.to_bytes().unwrap();
+    let question_req = MessageRequest::from_bytes(&question_bytes).unwrap();
+
+    let response_handler = TestResponseHandler::new();
+    block_on(catalog.lookup(question_req, None, response_handler.clone()));
+    let result = block_on(response_handler.into_message());
+
+    // a referral is not authoritative, the NS records of the child zone are in the authority
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert!(!result.authoritative());
+    assert!(result.answers().is_empty());
+    assert_eq!(result.name_servers().len(), 2);
+    assert!(result.name_servers().iter().all(|r| *r.name() == sub));
+
+    // only the in bailiwick name server has glue
+    let glue: Vec<&RData> = result.additionals().iter().map(Record::rdata).collect();
+    assert_eq!(glue, vec![&glue_a, &glue_aaaa]);
+}
+
+#[test]
+fn test_dname_redirect() {
+    let mut example = create_example();
+    let origin = example.origin().clone();
+
+    // everything below old.example.com. is redirected to example.com.
+    let old = Name::from_str("old.example.com.").unwrap();
+    let dname = RData::DNAME(Name::from_str("example.com.").unwrap());
+    assert!(example.upsert(Record::from_rdata(old.clone(), 3600, dname.clone()), 0));
+
+    // there can be no data below the DNAME, nor a delegation at it
+    let below = Name::from_str("ftp.old.example.com.").unwrap();
+    let a = RData::A(Ipv4Addr::new(192, 0, 2, 1));
+    assert!(!example.upsert(Record::from_rdata(below, 3600, a), 0));
+    let ns = RData::NS(Name::from_str("ns.example.net.").unwrap());
+    assert!(!example.upsert(Record::from_rdata(old.clone(), 3600, ns), 0));
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin, Box::new(Arc::new(RwLock::new(example))));
+
+    let mut question: Message = Message::new();
+    let www_old = Name::from_str("www.old.example.com.").unwrap();
+    question.add_query(Query::query(www_old.clone(), RecordType::A));
+
+    let question_bytes = question.to_bytes().unwrap();
+    let question_req = MessageRequest::from_bytes(&question_bytes).unwrap();
+
+    let response_handler = TestResponseHandler::new();
+    block_on(catalog.lookup(question_req, None, response_handler.clone()));
+    let result = block_on(response_handler.into_message());
+
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert!(result.authoritative());
+
+    // the DNAME, followed by the CNAME synthesized from it for the query name
+    let answers: &[Record] = result.answers();
+    assert_eq!(answers.len(), 2);
+    assert_eq!(answers[0].name(), &old);
+    assert_eq!(answers[0].rdata(), &dname);
+    assert_eq!(answers[1].name(), &www_old);
+    assert_eq!(answers[1].ttl(), 3600);
+    assert_eq!(
+        answers[1].rdata(),
+        &RData::CNAME(Name::from_str("www.example.com.").unwrap())
+    );
+
+    // the target of the CNAME is followed
+    let additionals: &[Record] = result.additionals();
+    assert_eq!(additionals.len(), 1);
+    assert_eq!(
+        additionals[0].rdata(),
+        &RData::A(Ipv4Addr::new(93, 184, 216, 34))
+    );
+}
+
+fn a_query(name: &str) -> MessageRequest {
+    let mut question: Message = Message::new();
+    question.add_query(Query::query(Name::from_str(name).unwrap(), RecordType::A));
+
+    let question_bytes = question.to_bytes().unwrap();
+    MessageRequest::from_bytes(&question_bytes).unwrap()
+}
+
+#[test]
+fn test_catalog_zones() {
+    let example = create_example();
+    let test = create_test();
+    let origin = example.origin().clone();
+    let test_origin = test.origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), Box::new(Arc::new(RwLock::new(example))));
+    assert_eq!(catalog.zones(), vec![Name::from(&origin)]);
+
+    // the added zone answers queries
+    catalog.upsert(test_origin.clone(), Box::new(Arc::new(RwLock::new(test))));
+    assert_eq!(
+        catalog.zones(),
+        vec![Name::from(&origin), Name::from(&test_origin)]
+    );
+
+    let response_handler = TestResponseHandler::new();
+    block_on(catalog.lookup(a_query("www.test.com."), None, response_handler.clone()));
+    let result = block_on(response_handler.into_message());
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert_eq!(result.answers().len(), 1);
+
+    // a lookup in progress completes with the zone it started with
+    let response_handler = TestResponseHandler::new();
+    let lookup = catalog.lookup(a_query("www.test.com."), None, response_handler.clone());
+    assert!(catalog.remove(&test_origin).is_some());
+    block_on(lookup);
+    let result = block_on(response_handler.into_message());
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert_eq!(result.answers().len(), 1);
+
+    // the removed zone is refused
+    assert_eq!(catalog.zones(), vec![Name::from(&origin)]);
+    let response_handler = TestResponseHandler::new();
+    block_on(catalog.lookup(a_query("www.test.com."), None, response_handler.clone()));
+    let result = block_on(response_handler.into_message());
+    assert_eq!(result.response_code(), ResponseCode::Refused);
+    assert!(result.answers().is_empty());
+}
+
+#[test]
+fn test_edns_badvers() {
+    let example = create_example();
+    let origin = example.origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin, Box::new(Arc::new(RwLock::new(example))));
+
+    let mut edns = Edns::new();
+    edns.set_version(1);
+
+    let mut question: Message = Message::new();
+    question.add_query(Query::query(
+        Name::from_str("www.example.com.").unwrap(),
+        RecordType::A,
+    ));
+    question.set_edns(edns);
+
+    let question_bytes = question.to_bytes().unwrap();
+    let request = Request {
+        message: MessageRequest::from_bytes(&question_bytes).unwrap(),
+        src: SocketAddr::from(([127, 0, 0, 1], 53)),
+        protocol: Protocol::Udp,
+    };
+
+    let response_handler = TestResponseHandler::new();
+    block_on(catalog.handle_request(request, response_handler.clone()));
+    let result = block_on(response_handler.into_message());
+
+    // the extended response code is split between the header and EDNS
+    assert_eq!(
+        u16::from(result.response_code()),
+        u16::from(ResponseCode::BADVERS)
+    );
+    assert_eq!(result.edns().map(Edns::version), Some(0));
+}
+
+#[test]
+#[allow(clippy::unreadable_literal)]
+fn test_secondary_expired() {
+    let origin = Name::parse("test.com.", None).unwrap();
+    let mut secondary = InMemoryAuthority::empty(origin.clone(), ZoneType::Secondary, false);
+    secondary.upsert(
+        Record::from_rdata(
+            origin.clone(),
+            3600,
+            RData::SOA(SOA::new(
+                Name::parse("sns.dns.icann.org.", None).unwrap(),
+                Name::parse("noc.dns.icann.org.", None).unwrap(),
+                2015082403,
+                7200,
+                3600,
+                60,
+                3600,
+            )),
+        ),
+        0,
+    );
+    secondary.upsert(
+        Record::from_rdata(
+            Name::parse("www.test.com.", None).unwrap(),
+            3600,
+            RData::A(Ipv4Addr::new(127, 0, 0, 1)),
+        ),
+        0,
+    );
+
+    let secondary = Arc::new(RwLock::new(secondary));
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(LowerName::new(&origin), Box::new(Arc::clone(&secondary)));
+
+    let query = |catalog: &Catalog| {
+        let response_handler = TestResponseHandler::new();
+        block_on(catalog.lookup(a_query("www.test.com."), None, response_handler.clone()));
+        block_on(response_handler.into_message())
+    };
+
+    // within the EXPIRE interval of the SOA the zone is answered from
+    let refreshed = Instant::now() - Duration::from_secs(30);
+    secondary.write().unwrap().set_refreshed(refreshed);
+    let result = query(&catalog);
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert!(result.authoritative());
+    assert_eq!(result.answers().len(), 1);
+
+    // once expired, the stale records are no longer answered
+    let refreshed = Instant::now() - Duration::from_secs(61);
+    secondary.write().unwrap().set_refreshed(refreshed);
+    assert!(secondary.read().unwrap().is_expired());
+    let result = query(&catalog);
+    assert_eq!(result.response_code(), ResponseCode::ServFail);
+    assert!(!result.authoritative());
+    assert!(result.answers().is_empty());
+}
+
+#[test]
+#[allow(clippy::unreadable_literal)]
+fn test_nested_zones() {
+    let example = create_example();
+    let example_origin = example.origin().clone();
+
+    let sub_origin = Name::parse("sub.example.com.", None).unwrap();
+    let mut sub = InMemoryAuthority::empty(sub_origin.clone(), ZoneType::Primary, false);
+    sub.upsert(
+        Record::from_rdata(
+            sub_origin.clone(),
+            3600,
+            RData::SOA(SOA::new(
+                Name::parse("ns.sub.example.com.", None).unwrap(),
+                Name::parse("noc.sub.example.com.", None).unwrap(),
+                2020101501,
+                7200,
+                3600,
+                1209600,
+                3600,
+            )),
+        ),
+        0,
+    );
+    sub.upsert(
+        Record::from_rdata(
+            Name::parse("host.sub.example.com.", None).unwrap(),
+            3600,
+            RData::A(Ipv4Addr::new(127, 0, 0, 2)),
+        ),
+        0,
+    );
+
+    // the zones are added parent last, the order does not change which is found
+    let sub_zone = LowerName::new(&sub_origin);
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(sub_zone.clone(), Box::new(Arc::new(RwLock::new(sub))));
+    catalog.upsert(
+        example_origin.clone(),
+        Box::new(Arc::new(RwLock::new(example))),
+    );
+
+    let find = |name: &str| {
+        let name = LowerName::from_str(name).unwrap();
+        catalog.find(&name).map(|authority| authority.origin())
+    };
+    assert_eq!(find("host.sub.example.com."), Some(sub_zone.clone()));
+    assert_eq!(find("sub.example.com."), Some(sub_zone));
+    assert_eq!(find("www.example.com."), Some(example_origin.clone()));
+    assert_eq!(find("www.example.org."), None);
+
+    let query = |name: &str| {
+        let response_handler = TestResponseHandler::new();
+        block_on(catalog.lookup(a_query(name), None, response_handler.clone()));
+        block_on(response_handler.into_message())
+    };
+
+    // names in the child zone are answered by the child
+    let result = query("host.sub.example.com.");
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert_eq!(
+        result.answers()[0].rdata(),
+        &RData::A(Ipv4Addr::new(127, 0, 0, 2))
+    );
+
+    let result = query("missing.sub.example.com.");
+    assert_eq!(result.response_code(), ResponseCode::NXDomain);
+    assert_eq!(result.name_servers()[0].name(), &sub_origin);
+
+    // the rest of the names are answered by the parent
+    let result = query("www.example.com.");
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert_eq!(
+        result.answers()[0].rdata(),
+        &RData::A(Ipv4Addr::new(93, 184, 216, 34))
+    );
+}
@@ -25,7 +25,8 @@ use trust_dns_proto::xfer::{DnsExchangeBackground, DnsMultiplexer, DnsStreamHand
 #[cfg(all(feature = "dnssec", feature = "sqlite"))]
 use trust_dns_proto::TokioTime;
 
-use trust_dns_server::authority::{Authority, Catalog};
+use trust_dns_server::authority::{Authority, Catalog, ZoneType};
+use trust_dns_server::store::in_memory::InMemoryAuthority;
 
 use trust_dns_integration::authority::create_example;
 use trust_dns_integration::{NeverReturnsClientStream, TestClientStream};
@@ -182,7 +183,20 @@ fn test_query(client: &mut AsyncClient) -> impl Future<Output = ()> {
 
 #[test]
 fn test_notify() {
-    let authority = create_example();
+    let example = create_example();
+    let records = example
+        .records()
+        .iter()
+        .map(|(key, rrset)| (key.clone(), RecordSet::clone(rrset)))
+        .collect();
+    let authority = InMemoryAuthority::new(
+        example.origin().clone().into(),
+        records,
+        ZoneType::Secondary,
+        false,
+    )
+    .unwrap();
+
     let mut catalog = Catalog::new();
     catalog.upsert(
         authority.origin().clone(),
@@ -201,11 +215,94 @@ fn test_notify() {
         io_loop.block_on(client.notify(name, DNSClass::IN, RecordType::A, None::<RecordSet>));
     assert!(message.is_ok());
     let message = message.unwrap();
-    assert_eq!(
-        message.response_code(),
-        ResponseCode::NotImp,
-        "the catalog must support Notify now, update this"
+    assert_eq!(message.response_code(), ResponseCode::NoError);
+}
+
+#[test]
+fn test_notify_refreshes_secondary() {
+    use std::time::Duration as StdDuration;
+
+    use tokio::net::TcpListener;
+    use trust_dns_client::rr::rdata::SOA;
+    use trust_dns_client::rr::{Record, RrKey};
+    use trust_dns_server::ServerFuture;
+
+    let io_loop = Runtime::new().unwrap();
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, 0));
+    let udp_socket = io_loop.block_on(TokioUdpSocket::bind(&addr)).unwrap();
+    let primary_addr = udp_socket.local_addr().unwrap();
+    let tcp_listener = io_loop.block_on(TcpListener::bind(&primary_addr)).unwrap();
+
+    // the Primary has a newer version of the zone, with an added record
+    let mut primary = create_example();
+    let origin: Name = primary.origin().clone().into();
+    let serial = primary.serial() + 1;
+    let soa = SOA::new(
+        Name::from_str("sns.dns.icann.org.").unwrap(),
+        Name::from_str("noc.dns.icann.org.").unwrap(),
+        serial,
+        7200,
+        3600,
+        1_209_600,
+        3600,
     );
+    let added = Name::from_str("new.example.com.").unwrap();
+    primary.upsert(Record::from_rdata(origin.clone(), 3600, RData::SOA(soa)), serial);
+    primary.upsert(
+        Record::from_rdata(added.clone(), 3600, RData::A(Ipv4Addr::new(100, 10, 100, 10))),
+        serial,
+    );
+    primary.set_allow_axfr(true);
+
+    let mut catalog = Catalog::new();
+    catalog.upsert(
+        primary.origin().clone(),
+        Box::new(Arc::new(RwLock::new(primary))),
+    );
+
+    let _guard = io_loop.enter();
+    let mut server = ServerFuture::new(catalog);
+    server.register_socket(udp_socket);
+    server.register_listener(tcp_listener, StdDuration::from_secs(30));
+
+    // the Secondary is at the serial of the example
+    let example = create_example();
+    let records = example
+        .records()
+        .iter()
+        .map(|(key, rrset)| (key.clone(), RecordSet::clone(rrset)))
+        .collect();
+    let mut secondary =
+        InMemoryAuthority::new(origin.clone(), records, ZoneType::Secondary, false).unwrap();
+    secondary.set_primaries(vec![primary_addr]);
+    let secondary = Arc::new(RwLock::new(secondary));
+
+    let mut catalog = Catalog::new();
+    catalog.upsert(origin.clone().into(), Box::new(secondary.clone()));
+
+    let (stream, sender) = TestClientStream::new(Arc::new(Mutex::new(catalog)));
+    let client = AsyncClient::new(stream, Box::new(sender), None);
+    let (mut client, bg) = io_loop.block_on(client).expect("client failed to connect");
+    trust_dns_proto::spawn_bg(&io_loop, bg);
+
+    let message = io_loop
+        .block_on(client.notify(origin, DNSClass::IN, RecordType::SOA, None::<RecordSet>))
+        .expect("notify failed");
+    assert_eq!(message.response_code(), ResponseCode::NoError);
+
+    // the refresh is spawned, the zone is transferred once the SOA check finds the newer serial
+    for _ in 0..500 {
+        if secondary.read().unwrap().serial() == serial {
+            break;
+        }
+        io_loop.block_on(tokio::time::sleep(StdDuration::from_millis(10)));
+    }
+
+    let secondary = secondary.read().unwrap();
+    assert_eq!(secondary.serial(), serial);
+    assert!(secondary
+        .records()
+        .contains_key(&RrKey::new(added.into(), RecordType::A)));
 }
 
 // update tests
@@ -223,6 +320,24 @@ async fn create_sig0_ready_client() -> (
         >,
     ),
     Name,
+) {
+    create_sig0_ready_client_with_notify(vec![]).await
+}
+
+/// create a client with a sig0 section, updates to the zone are notified to `notify_targets`
+#[cfg(all(feature = "dnssec", feature = "sqlite"))]
+#[allow(clippy::type_complexity)]
+async fn create_sig0_ready_client_with_notify(
+    notify_targets: Vec<SocketAddr>,
+) -> (
+    (
+        AsyncClient,
+        DnsExchangeBackground<
+            DnsMultiplexer<TestClientStream, Signer, Box<dyn DnsStreamHandle>>,
+            TokioTime,
+        >,
+    ),
+    Name,
 ) {
     use openssl::rsa::Rsa;
     use trust_dns_client::rr::dnssec::{Algorithm, KeyPair};
@@ -231,6 +346,7 @@ async fn create_sig0_ready_client() -> (
 
     let authority = create_example();
     let mut authority = SqliteAuthority::new(authority, true, false);
+    authority.set_notify_targets(notify_targets);
     let origin = authority.origin().clone();
 
     let trusted_name = Name::from_str("trusted.example.com").unwrap();
@@ -266,6 +382,46 @@ async fn create_sig0_ready_client() -> (
     (client, origin.into())
 }
 
+#[cfg(all(feature = "dnssec", feature = "sqlite"))]
+#[test]
+fn test_update_notifies_secondaries() {
+    use std::time::Duration as StdDuration;
+
+    use trust_dns_client::op::{Message, OpCode};
+
+    let secondary = UdpSocket::bind("127.0.0.1:0").unwrap();
+    secondary
+        .set_read_timeout(Some(StdDuration::from_secs(5)))
+        .unwrap();
+    let secondary_addr = secondary.local_addr().unwrap();
+
+    let io_loop = Runtime::new().unwrap();
+    let ((mut client, bg), origin) =
+        io_loop.block_on(create_sig0_ready_client_with_notify(vec![secondary_addr]));
+    trust_dns_proto::spawn_bg(&io_loop, bg);
+
+    let mut record = Record::with(
+        Name::from_str("new.example.com").unwrap(),
+        RecordType::A,
+        Duration::minutes(5).num_seconds() as u32,
+    );
+    record.set_rdata(RData::A(Ipv4Addr::new(100, 10, 100, 10)));
+
+    let result = io_loop
+        .block_on(client.create(record, origin.clone()))
+        .expect("create failed");
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+
+    // the update incremented the serial, the secondary is notified
+    let mut buf = [0_u8; 512];
+    let (len, _) = secondary.recv_from(&mut buf).expect("no NOTIFY received");
+    let notify = Message::from_vec(&buf[..len]).expect("bad NOTIFY");
+
+    assert_eq!(notify.op_code(), OpCode::Notify);
+    assert_eq!(notify.queries()[0].name(), &origin);
+    assert_eq!(notify.queries()[0].query_type(), RecordType::SOA);
+}
+
 #[cfg(all(feature = "dnssec", feature = "sqlite"))]
 #[test]
 fn test_create() {
@@ -10,15 +10,17 @@ use tokio::runtime::Runtime;
 
 use trust_dns_client::client::{AsyncClient, ClientHandle, MemoizeClientHandle};
 use trust_dns_client::op::ResponseCode;
-use trust_dns_client::rr::dnssec::TrustAnchor;
+use trust_dns_client::rr::dnssec::{DigestType, TrustAnchor, TrustAnchorStore};
 use trust_dns_client::rr::Name;
-use trust_dns_client::rr::{DNSClass, RData, RecordType};
+use trust_dns_client::rr::{DNSClass, RData, Record, RecordSet, RecordType, RrKey};
 use trust_dns_client::tcp::TcpClientStream;
 
 use trust_dns_proto::iocompat::AsyncIoTokioAsStd;
+use trust_dns_proto::rr::dnssec::rdata::DS;
 use trust_dns_proto::udp::{UdpClientConnect, UdpClientStream};
 use trust_dns_proto::DnssecDnsHandle;
 use trust_dns_server::authority::{Authority, Catalog};
+use trust_dns_server::store::in_memory::InMemoryAuthority;
 
 use trust_dns_integration::authority::create_secure_example;
 use trust_dns_integration::TestClientStream;
@@ -186,7 +188,114 @@ where
 //     assert!(response.answers().is_empty());
 // }
 
+#[test]
+fn test_bogus_signature_nonet() {
+    with_authority_nonet(create_bogus_example(), test_bogus_signature);
+}
+
+fn test_bogus_signature<H>(mut client: DnssecDnsHandle<H>, io_loop: Runtime)
+where
+    H: ClientHandle + Sync + 'static,
+{
+    let name = Name::from_str("www.example.com").unwrap();
+    let response = io_loop.block_on(client.query(name, DNSClass::IN, RecordType::A));
+
+    assert!(response.is_err(), "bogus record should not validate: {:?}", response);
+}
+
+/// The secure example with the www A record replaced after signing, leaving the original RRSIG
+fn create_bogus_example() -> InMemoryAuthority {
+    let mut authority = create_secure_example();
+
+    let name = Name::from_str("www.example.com.").unwrap();
+    let key = RrKey::new(name.clone().into(), RecordType::A);
+    let rrset = authority.records_mut().get_mut(&key).expect("www A missing");
+
+    let mut bogus = RecordSet::new(&name, RecordType::A, 0);
+    bogus.insert(
+        Record::from_rdata(name, rrset.ttl(), RData::A(Ipv4Addr::new(10, 0, 0, 1))),
+        0,
+    );
+    for rrsig in rrset.rrsigs() {
+        bogus.insert_rrsig(rrsig.clone());
+    }
+
+    *rrset = Arc::new(bogus);
+    authority
+}
+
+#[test]
+fn test_ds_trust_anchor_nonet() {
+    let authority = create_secure_example();
+    let signer = authority.secure_keys().first().expect("expected a key in the authority");
+    let name = signer.signer_name().clone();
+    let dnskey = signer.to_dnskey().expect("failed to convert to DNSKEY");
+    let ds = DS::new(
+        dnskey.calculate_key_tag().expect("failed to calculate key tag"),
+        dnskey.algorithm(),
+        DigestType::SHA256,
+        dnskey
+            .to_digest(&name, DigestType::SHA256)
+            .expect("failed to digest DNSKEY")
+            .as_ref()
+            .to_vec(),
+    );
+
+    let mut trust_anchor = TrustAnchorStore::new();
+    trust_anchor.insert_ds(name, ds);
+
+    with_trust_anchor_nonet(authority, trust_anchor, test_secure_query_example);
+}
+
+#[test]
+fn test_no_trust_anchor_nonet() {
+    with_trust_anchor_nonet(
+        create_secure_example(),
+        TrustAnchorStore::new(),
+        test_insecure_query_example,
+    );
+}
+
+fn test_insecure_query_example<H>(mut client: DnssecDnsHandle<H>, io_loop: Runtime)
+where
+    H: ClientHandle + Sync + 'static,
+{
+    let name = Name::from_str("www.example.com").unwrap();
+    let response = io_loop.block_on(client.query(name, DNSClass::IN, RecordType::A));
+
+    assert!(response.is_err(), "record without a trust anchor validated: {:?}", response);
+}
+
 fn with_nonet<F>(test: F)
+where
+    F: Fn(DnssecDnsHandle<MemoizeClientHandle<AsyncClient>>, Runtime),
+{
+    with_authority_nonet(create_secure_example(), test)
+}
+
+fn with_authority_nonet<F>(authority: InMemoryAuthority, test: F)
+where
+    F: Fn(DnssecDnsHandle<MemoizeClientHandle<AsyncClient>>, Runtime),
+{
+    let trust_anchor = {
+        let signers = authority.secure_keys();
+        let public_key = signers
+            .first()
+            .expect("expected a key in the authority")
+            .key()
+            .to_public_key()
+            .expect("could not convert keypair to public_key");
+
+        let mut trust_anchor = TrustAnchor::new();
+        trust_anchor.insert_trust_anchor(&public_key);
+
+        trust_anchor
+    };
+
+    with_trust_anchor_nonet(authority, trust_anchor.into(), test)
+}
+
+fn with_trust_anchor_nonet<F>(authority: InMemoryAuthority, trust_anchor: TrustAnchorStore, test: F)
 where
     F: Fn(DnssecDnsHandle<MemoizeClientHandle<AsyncClient>>, Runtime),
 {
@@ -208,23 +317,6 @@ where
         })
         .unwrap();
 
-    let authority = create_secure_example();
-
-    let trust_anchor = {
-        let signers = authority.secure_keys();
-        let public_key = signers
-            .first()
-            .expect("expected a key in the authority")
-            .key()
-            .to_public_key()
-            .expect("could not convert keypair to public_key");
-
-        let mut trust_anchor = TrustAnchor::new();
-        trust_anchor.insert_trust_anchor(&public_key);
-
-        trust_anchor
-    };
-
     let mut catalog = Catalog::new();
     catalog.upsert(
         authority.origin().clone(),
@@ -241,7 +333,7 @@ where
 
     trust_dns_proto::spawn_bg(&io_loop, bg);
     let client = MemoizeClientHandle::new(client);
-    let secure_client = DnssecDnsHandle::with_trust_anchor(client, trust_anchor);
+    let secure_client = DnssecDnsHandle::with_trust_anchor_store(client, trust_anchor);
 
     test(secure_client, io_loop);
     succeeded.store(true, std::sync::atomic::Ordering::Relaxed);
@@ -3,12 +3,13 @@ use std::pin::Pin;
 use std::str::FromStr;
 use std::sync::{
     atomic::{AtomicIsize, Ordering},
-    Arc,
+    Arc, Mutex,
 };
 use std::task::Poll;
+use std::time::Duration;
 
-use futures::executor::block_on;
 use futures::{future, Future};
+use tokio::runtime::Runtime;
 
 use trust_dns_client::op::Query;
 use trust_dns_client::rr::{Name, RecordType};
@@ -17,7 +18,7 @@ use trust_dns_proto::error::ProtoError;
 use trust_dns_proto::xfer::{DnsHandle, DnsResponse};
 use trust_dns_proto::TokioTime;
 use trust_dns_resolver::config::*;
-use trust_dns_resolver::error::ResolveError;
+use trust_dns_resolver::error::{ResolveError, ResolveErrorKind};
 use trust_dns_resolver::name_server::{ConnectionProvider, NameServer, NameServerPool};
 
 #[derive(Clone)]
@@ -45,6 +46,11 @@ impl<O: OnSend + Unpin> ConnectionProvider for MockConnProvider<O> {
     }
 }
 
+/// NameServers bound each request with a timeout, which requires the tokio timer
+fn block_on<F: Future>(future: F) -> F::Output {
+    Runtime::new().expect("failed to create runtime").block_on(future)
+}
+
 type MockedNameServer<O> = NameServer<MockClientHandle<O, ResolveError>, MockConnProvider<O>>;
 type MockedNameServerPool<O> =
     NameServerPool<MockClientHandle<O, ResolveError>, MockConnProvider<O>>;
@@ -82,17 +88,33 @@ fn mock_nameserver_on_send_nx<O: OnSend + Unpin>(
     on_send: O,
     trust_nx_responses: bool,
 ) -> MockedNameServer<O> {
-    let conn_provider = MockConnProvider {
-        on_send: on_send.clone(),
-    };
-    let client = MockClientHandle::mock_on_send(messages, on_send);
+    let client = MockClientHandle::mock_on_send(messages, on_send.clone());
+    let socket_addr = SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 0);
+
+    mock_nameserver_from_client(client, socket_addr, options, on_send, trust_nx_responses)
+}
+
+/// The client may be cloned beforehand to inspect the requests sent to the NameServer, NameServers
+///  are equal when their `socket_addr` are equal
+#[cfg(test)]
+fn mock_nameserver_from_client<O: OnSend + Unpin>(
+    client: MockClientHandle<O, ResolveError>,
+    socket_addr: SocketAddr,
+    options: ResolverOpts,
+    on_send: O,
+    trust_nx_responses: bool,
+) -> MockedNameServer<O> {
+    let conn_provider = MockConnProvider { on_send };
 
     NameServer::from_conn(
         NameServerConfig {
-            socket_addr: SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 0),
+            socket_addr,
             protocol: Protocol::Udp,
             tls_dns_name: None,
             trust_nx_responses,
+            signer: None,
+            weight: None,
+            bind_addr: None,
             #[cfg(any(feature = "dns-over-rustls", feature = "dns-over-https-rustls"))]
             tls_config: None,
         },
@@ -133,7 +155,7 @@ fn mock_nameserver_pool_on_send<O: OnSend + Unpin>(
         &options,
         udp,
         tcp,
-        _mdns.unwrap_or_else(move || mock_nameserver_on_send(vec![], options, on_send)),
+        _mdns.unwrap_or_else(|| mock_nameserver_on_send(vec![], options.clone(), on_send)),
         conn_provider,
     );
 }
@@ -165,6 +187,52 @@ fn test_datagram() {
     assert_eq!(response.answers()[0], udp_record);
 }
 
+#[test]
+fn test_failed_then_success_pool() {
+    let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+    let record = v4_record(query.name().clone(), Ipv4Addr::new(127, 0, 0, 1));
+    let success = message(query.clone(), vec![record.clone()], vec![], vec![]);
+
+    let mut options = ResolverOpts::default();
+    options.num_concurrent_reqs = 1;
+
+    let failing = MockClientHandle::mock_queries(vec![(
+        query.clone(),
+        Err(ResolveError::from("Forced Testing Error")),
+    )]);
+    let working = MockClientHandle::mock_queries(
+        (0..10)
+            .map(|_| (query.clone(), Ok(success.clone().into())))
+            .collect(),
+    );
+
+    let nameserver = |client: &MockClientHandle<DefaultOnSend, ResolveError>, port| {
+        mock_nameserver_from_client(
+            client.clone(),
+            SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), port),
+            options.clone(),
+            DefaultOnSend,
+            false,
+        )
+    };
+    let mut pool = mock_nameserver_pool(
+        vec![nameserver(&failing, 253), nameserver(&working, 53)],
+        vec![],
+        None,
+        options,
+    );
+
+    // a request may fall through the failing name server, after that it is skipped
+    for i in 0..10 {
+        let request = message(query.clone(), vec![], vec![], vec![]);
+        let response = block_on(pool.send(request)).unwrap();
+        assert_eq!(response.answers()[0], record, "iter: {}", i);
+    }
+
+    assert!(failing.send_count() <= 1);
+    assert_eq!(working.sent_queries(), vec![query; 10]);
+}
+
 #[test]
 fn test_datagram_stream_upgrade() {
     // lookup to UDP should return truncated message
@@ -285,16 +353,21 @@ fn test_trust_nx_responses_fails_servfail() {
             Ok(udp_message.into()),
             servfail_message.clone().map(Into::into),
         ],
-        options,
+        options.clone(),
         false,
     );
     let tcp_nameserver = mock_nameserver_trust_nx(
         vec![Err(ResolveError::from("Forced Testing Error"))],
-        options,
+        options.clone(),
         false,
     );
 
-    let mut pool = mock_nameserver_pool(vec![udp_nameserver], vec![tcp_nameserver], None, options);
+    let mut pool = mock_nameserver_pool(
+        vec![udp_nameserver],
+        vec![tcp_nameserver],
+        None,
+        options.clone(),
+    );
 
     // lookup on UDP succeeds, any other would fail
     let request = message(query.clone(), vec![], vec![], vec![]);
@@ -304,8 +377,8 @@ fn test_trust_nx_responses_fails_servfail() {
     assert!(response.response_code() == ResponseCode::ServFail);
 
     // fail all udp succeed tcp
-    let udp_nameserver = mock_nameserver(vec![servfail_message.map(Into::into)], options);
-    let tcp_nameserver = mock_nameserver(vec![Ok(tcp_message.into())], options);
+    let udp_nameserver = mock_nameserver(vec![servfail_message.map(Into::into)], options.clone());
+    let tcp_nameserver = mock_nameserver(vec![Ok(tcp_message.into())], options.clone());
 
     let mut pool = mock_nameserver_pool(vec![udp_nameserver], vec![tcp_nameserver], None, options);
 
@@ -336,8 +409,9 @@ fn test_distrust_nx_responses() {
 
     // fail the first udp request
     let udp_nameserver =
-        mock_nameserver_trust_nx(vec![servfail_message.map(Into::into)], options, true);
-    let tcp_nameserver = mock_nameserver_trust_nx(vec![Ok(tcp_message.into())], options, true);
+        mock_nameserver_trust_nx(vec![servfail_message.map(Into::into)], options.clone(), true);
+    let tcp_nameserver =
+        mock_nameserver_trust_nx(vec![Ok(tcp_message.into())], options.clone(), true);
 
     let mut pool = mock_nameserver_pool(vec![udp_nameserver], vec![tcp_nameserver], None, options);
 
@@ -418,8 +492,8 @@ fn test_concurrent_requests_2_conns() {
     let udp_message = message(query.clone(), vec![udp_record.clone()], vec![], vec![]);
 
     let udp1_nameserver =
-        mock_nameserver_on_send(vec![Ok(udp_message.into())], options, on_send.clone());
-    let udp2_nameserver = mock_nameserver_on_send(vec![], options, on_send.clone());
+        mock_nameserver_on_send(vec![Ok(udp_message.into())], options.clone(), on_send.clone());
+    let udp2_nameserver = mock_nameserver_on_send(vec![], options.clone(), on_send.clone());
 
     let mut pool = mock_nameserver_pool_on_send(
         vec![udp2_nameserver, udp1_nameserver],
@@ -458,8 +532,8 @@ fn test_concurrent_requests_more_than_conns() {
     let udp_message = message(query.clone(), vec![udp_record.clone()], vec![], vec![]);
 
     let udp1_nameserver =
-        mock_nameserver_on_send(vec![Ok(udp_message.into())], options, on_send.clone());
-    let udp2_nameserver = mock_nameserver_on_send(vec![], options, on_send.clone());
+        mock_nameserver_on_send(vec![Ok(udp_message.into())], options.clone(), on_send.clone());
+    let udp2_nameserver = mock_nameserver_on_send(vec![], options.clone(), on_send.clone());
 
     let mut pool = mock_nameserver_pool_on_send(
         vec![udp2_nameserver, udp1_nameserver],
@@ -498,7 +572,7 @@ fn test_concurrent_requests_1_conn() {
     let udp_message = message(query.clone(), vec![udp_record.clone()], vec![], vec![]);
 
     let udp1_nameserver =
-        mock_nameserver_on_send(vec![Ok(udp_message.into())], options, on_send.clone());
+        mock_nameserver_on_send(vec![Ok(udp_message.into())], options.clone(), on_send.clone());
     let udp2_nameserver = udp1_nameserver.clone();
 
     let mut pool = mock_nameserver_pool_on_send(
@@ -538,7 +612,7 @@ fn test_concurrent_requests_0_conn() {
     let udp_message = message(query.clone(), vec![udp_record.clone()], vec![], vec![]);
 
     let udp1_nameserver =
-        mock_nameserver_on_send(vec![Ok(udp_message.into())], options, on_send.clone());
+        mock_nameserver_on_send(vec![Ok(udp_message.into())], options.clone(), on_send.clone());
     let udp2_nameserver = udp1_nameserver.clone();
 
     let mut pool = mock_nameserver_pool_on_send(
@@ -560,3 +634,121 @@ fn test_concurrent_requests_0_conn() {
     let response = block_on(future).unwrap();
     assert_eq!(response.answers()[0], udp_record);
 }
+
+// === Timeouts ===
+
+#[derive(Clone)]
+struct OnSendNever;
+
+impl OnSend for OnSendNever {
+    fn on_send<E>(
+        &mut self,
+        _response: Result<DnsResponse, E>,
+    ) -> Pin<Box<dyn Future<Output = Result<DnsResponse, E>> + Send>>
+    where
+        E: From<ProtoError> + Send + 'static,
+    {
+        Box::pin(future::pending())
+    }
+}
+
+#[test]
+fn test_hung_name_server_times_out() {
+    let mut options = ResolverOpts::default();
+    options.timeout = Duration::from_millis(10);
+
+    let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+
+    let udp_nameserver = mock_nameserver_on_send(vec![], options.clone(), OnSendNever);
+    let mut pool =
+        mock_nameserver_pool_on_send(vec![udp_nameserver], vec![], None, options, OnSendNever);
+
+    let request = message(query, vec![], vec![], vec![]);
+    let error = block_on(pool.send(request)).expect_err("hung name server should time out");
+
+    assert!(
+        matches!(error.kind(), ResolveErrorKind::Timeout),
+        "unexpected error: {}",
+        error
+    );
+}
+
+// === Logging ===
+
+/// Records the log lines of the resolver, see `test_selection_logging`
+#[derive(Default)]
+struct CapturingLogger {
+    lines: Mutex<Vec<(log::Level, String)>>,
+}
+
+impl log::Log for CapturingLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.target().starts_with("trust_dns_resolver")
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            let line = record.args().to_string();
+            self.lines.lock().unwrap().push((record.level(), line));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[test]
+fn test_selection_logging() {
+    // the logger is global, this is the only test to install one
+    let logger: &'static CapturingLogger = Box::leak(Box::new(CapturingLogger::default()));
+    log::set_logger(logger).expect("logger already installed");
+    log::set_max_level(log::LevelFilter::Trace);
+
+    let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+    let udp_record = v4_record(query.name().clone(), Ipv4Addr::new(127, 0, 0, 1));
+    let udp_message = message(query.clone(), vec![udp_record], vec![], vec![]);
+
+    // the address distinguishes the lines of this test from those of others running alongside
+    let socket_addr = SocketAddr::new(Ipv4Addr::new(127, 0, 0, 2).into(), 5353);
+    let client = MockClientHandle::mock_on_send(vec![Ok(udp_message.into())], DefaultOnSend);
+    let udp_nameserver = mock_nameserver_from_client(
+        client,
+        socket_addr,
+        Default::default(),
+        DefaultOnSend,
+        false,
+    );
+    let mut pool = mock_nameserver_pool(vec![udp_nameserver], vec![], None, Default::default());
+
+    let request = message(query, vec![], vec![], vec![]);
+    block_on(pool.send(request)).expect("lookup failed");
+
+    let addr = format!("addr={}", socket_addr);
+    let lines = logger.lines.lock().unwrap();
+    let logged = |level: log::Level, prefix: &str| {
+        lines
+            .iter()
+            .find(|(l, line)| *l == level && line.starts_with(prefix) && line.contains(&addr))
+            .cloned()
+    };
+
+    // the ranking of the candidate is logged at trace, with each of its components
+    let (_, candidate) =
+        logged(log::Level::Trace, "name server candidate:").expect("candidate not logged");
+    for field in &[
+        "position=0",
+        "ordering=QueryStats",
+        "protocol=udp",
+        "state=Init",
+        "failures=0",
+        "consecutive_failures=0",
+        "latency=0ns",
+        "successes=0",
+        "weight=1",
+    ] {
+        assert!(candidate.contains(field), "{} missing from: {}", field, candidate);
+    }
+
+    assert!(logged(log::Level::Debug, "selected name server:").is_some());
+    assert!(logged(log::Level::Trace, "name server success:").is_some());
+    assert!(logged(log::Level::Debug, "name server state changed:").is_some());
+}
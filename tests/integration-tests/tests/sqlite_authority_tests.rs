@@ -12,7 +12,7 @@ use trust_dns_client::rr::dnssec::*;
 use trust_dns_client::rr::rdata::*;
 use trust_dns_client::rr::*;
 
-use trust_dns_server::authority::{Authority, ZoneType};
+use trust_dns_server::authority::{serial_gt, Authority, SerialScheme, ZoneType};
 use trust_dns_server::store::in_memory::InMemoryAuthority;
 use trust_dns_server::store::sqlite::{Journal, SqliteAuthority};
 
@@ -357,6 +357,56 @@ fn test_prerequisites() {
             .clone()],),
         Err(ResponseCode::NXRRSet)
     );
+
+    // value dependent RRsets must match the whole RRset in the zone
+    let ns = |target: &str| {
+        Record::new()
+            .set_name(authority.origin().clone().into())
+            .set_ttl(0)
+            .set_dns_class(DNSClass::IN)
+            .set_rr_type(RecordType::NS)
+            .set_rdata(RData::NS(Name::parse(target, None).unwrap()))
+            .clone()
+    };
+    assert!(authority
+        .verify_prerequisites(&[ns("a.iana-servers.net."), ns("b.iana-servers.net.")])
+        .is_ok());
+    // only a subset of the RRset
+    assert_eq!(
+        authority.verify_prerequisites(&[ns("a.iana-servers.net.")]),
+        Err(ResponseCode::NXRRSet)
+    );
+    // a superset of the RRset
+    assert_eq!(
+        authority.verify_prerequisites(&[
+            ns("a.iana-servers.net."),
+            ns("b.iana-servers.net."),
+            ns("c.iana-servers.net."),
+        ]),
+        Err(ResponseCode::NXRRSet)
+    );
+
+    // the records of the zone are compared as they are, a CNAME is not the RRset it points to
+    let alias = Name::from_str("alias.example.com.").unwrap();
+    assert_eq!(
+        authority.verify_prerequisites(&[Record::new()
+            .set_name(alias.clone())
+            .set_ttl(0)
+            .set_dns_class(DNSClass::ANY)
+            .set_rr_type(RecordType::A)
+            .set_rdata(RData::NULL(NULL::new()))
+            .clone()],),
+        Err(ResponseCode::NXRRSet)
+    );
+    assert!(authority
+        .verify_prerequisites(&[Record::new()
+            .set_name(alias)
+            .set_ttl(0)
+            .set_dns_class(DNSClass::NONE)
+            .set_rr_type(RecordType::A)
+            .set_rdata(RData::NULL(NULL::new()))
+            .clone()],)
+        .is_ok());
 }
 
 #[test]
@@ -807,6 +857,36 @@ fn test_update() {
     assert_eq!(serial + 6, authority.serial());
 }
 
+#[test]
+fn test_update_date_serial() {
+    let new_name = Name::from_str("new.example.com").unwrap();
+    let mut authority = create_example();
+    authority.set_allow_update(true);
+    authority.set_serial_scheme(SerialScheme::Date);
+
+    let mut serials = vec![authority.serial()];
+    for octet in 1..=2 {
+        let add_record = &[Record::new()
+            .set_name(new_name.clone())
+            .set_ttl(86400)
+            .set_rr_type(RecordType::A)
+            .set_dns_class(DNSClass::IN)
+            .set_rdata(RData::A(Ipv4Addr::new(192, 0, 2, octet)))
+            .clone()];
+        assert!(authority
+            .update_records(add_record, true)
+            .expect("update failed"));
+
+        serials.push(authority.serial());
+    }
+
+    // each update advances the serial
+    assert!(serial_gt(serials[1], serials[0]));
+    assert!(serial_gt(serials[2], serials[1]));
+    assert_eq!(serials[1] % 100, 0);
+    assert_eq!(serials[2], serials[1] + 1);
+}
+
 #[cfg(feature = "dnssec")]
 #[test]
 fn test_zone_signing() {
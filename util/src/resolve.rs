@@ -135,6 +135,9 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
             protocol: Protocol::Tcp,
             tls_dns_name: None,
             trust_nx_responses: false,
+            signer: None,
+            weight: None,
+            bind_addr: None,
             #[cfg(feature = "dns-over-rustls")]
             tls_config: None,
         });
@@ -144,6 +147,9 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
             protocol: Protocol::Udp,
             tls_dns_name: None,
             trust_nx_responses: false,
+            signer: None,
+            weight: None,
+            bind_addr: None,
             #[cfg(feature = "dns-over-rustls")]
             tls_config: None,
         });